@@ -0,0 +1,87 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use email_spoof_detector::{
+    dns::DnsSnapshot,
+    email_verdict::{AnalysisOptions, analyze_email_with_options},
+    parse::parse_email,
+    text_heuristics::PhraseList,
+};
+use std::hint::black_box;
+
+/// Plain, DKIM-signed message from a well-configured domain
+const PLAIN: &[u8] = b"Return-Path: <bounce@example.com>\r\n\
+Authentication-Results: mx.corp.test; spf=pass smtp.mailfrom=example.com; dkim=pass header.d=example.com; dmarc=pass\r\n\
+DKIM-Signature: v=1; a=rsa-sha256; d=example.com; s=selector1; h=from:to:subject:date; bh=abc; b=def\r\n\
+From: Alice <alice@example.com>\r\n\
+To: bob@corp.test\r\n\
+Subject: Quarterly numbers\r\n\
+Date: Mon, 02 Feb 2026 15:15:37 +0000\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Hi Bob, the numbers are attached. See https://example.com/reports/q1 for details.\r\n";
+
+/// Multipart phishing message with an obfuscated HTML part
+const PHISH: &[u8] = b"From: PayPal Security <service@paypa1.com>\r\n\
+To: victim@corp.test\r\n\
+Subject: Your account will be suspended\r\n\
+Date: Mon, 02 Feb 2026 15:15:37 +0000\r\n\
+Content-Type: multipart/alternative; boundary=b\r\n\
+\r\n\
+--b\r\n\
+Content-Type: text/plain\r\n\
+\r\n\
+URGENT: verify your account within 24 hours: https://bit.ly/x1\r\n\
+--b\r\n\
+Content-Type: text/html\r\n\
+\r\n\
+<p>Please <a href=\"ht&#116;ps&#x3a;//paypa1&period;com/log\xe2\x80\x8bin\">log in</a> immediately.</p>\r\n\
+<script>window.location.href = 'https://pay' + 'pa1.com/x';</script>\r\n\
+--b--\r\n";
+
+fn snapshot() -> DnsSnapshot {
+    serde_json::from_str(
+        r#"{"domains": {
+            "example.com": {"spf": "v=spf1 -all", "dmarc": "v=DMARC1; p=reject", "exists": true, "mx": true},
+            "paypa1.com": {"exists": true}
+        }}"#,
+    )
+    .unwrap()
+}
+
+fn options() -> AnalysisOptions {
+    AnalysisOptions {
+        protected_domains: vec!["paypal.com".to_string(), "example.org".to_string()],
+        text_phrases: Some(PhraseList::default()),
+        ..AnalysisOptions::default()
+    }
+}
+
+fn bench_parse(c: &mut Criterion) {
+    c.bench_function("parse/plain", |b| {
+        b.iter(|| parse_email(black_box(PLAIN)).unwrap())
+    });
+    c.bench_function("parse/phish", |b| {
+        b.iter(|| parse_email(black_box(PHISH)).unwrap())
+    });
+}
+
+fn bench_analyze(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let snapshot = snapshot();
+    let options = options();
+
+    for (name, raw) in [("plain", PLAIN), ("phish", PHISH)] {
+        c.bench_function(&format!("parse_and_score/{}", name), |b| {
+            b.iter(|| {
+                let parsed = parse_email(black_box(raw)).unwrap();
+                runtime
+                    .block_on(analyze_email_with_options(&parsed, &snapshot, &options))
+                    .unwrap()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_parse, bench_analyze);
+criterion_main!(benches);
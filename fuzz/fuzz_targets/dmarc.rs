@@ -0,0 +1,8 @@
+#![no_main]
+
+use email_spoof_detector::dmarc_lint::lint_dmarc_record;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|record: &str| {
+    lint_dmarc_record("example.com", record);
+});
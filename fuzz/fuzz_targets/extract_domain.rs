@@ -0,0 +1,8 @@
+#![no_main]
+
+use email_spoof_detector::parse::extract_domain;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|from: &str| {
+    extract_domain(Some(from));
+});
@@ -0,0 +1,26 @@
+#![no_main]
+
+use std::net::{IpAddr, Ipv6Addr};
+
+use email_spoof_detector::{checks::spf::evaluate, dns::DnsSnapshot};
+use libfuzzer_sys::fuzz_target;
+use serde_json::json;
+
+// The first 16 bytes are the client address, the rest the record of example.com, which
+// may include itself or include.test
+fuzz_target!(|data: &[u8]| {
+    let Some((ip, record)) = data.split_first_chunk::<16>() else {
+        return;
+    };
+    let Ok(record) = std::str::from_utf8(record) else {
+        return;
+    };
+    let ip = Ipv6Addr::from(*ip);
+    let ip = ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4);
+    let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+        "example.com": {"spf": record},
+        "include.test": {"spf": "v=spf1 ip4:192.0.2.0/24 include:example.com ~all"}
+    }}))
+    .unwrap();
+    futures::executor::block_on(evaluate(&dns, "example.com", ip));
+});
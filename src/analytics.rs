@@ -0,0 +1,210 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+/// Where and how often analytics rows are inserted
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyticsConfig {
+    /// Insert endpoint receiving NDJSON, e.g. ClickHouse's
+    /// `http://clickhouse:8123/?query=INSERT+INTO+esd.analyses+FORMAT+JSONEachRow`
+    pub url: String,
+    /// Rows after which a batch is inserted without waiting for the interval
+    pub batch_size: usize,
+    /// Longest a row waits for its batch to be inserted
+    pub flush_interval: Duration,
+    /// Rows held while inserts lag behind; further rows are dropped
+    pub queue: usize,
+}
+
+/// One analysis as a flat row for columnar analytics databases
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AnalyticsRow {
+    pub id: String,
+    /// Unix time the result was answered
+    pub at: i64,
+    pub tenant: String,
+    pub verdict: String,
+    pub risk_score: u32,
+    pub depth: String,
+    pub partial: bool,
+    pub from_domain: Option<String>,
+    /// IDs of the rules that fired, e.g. `ESD-0005`
+    pub rule_ids: Vec<String>,
+}
+
+impl AnalyticsRow {
+    /// The row of `result`, an analysis result as JSON, answered now for `tenant`
+    pub fn new(tenant: &str, result: &serde_json::Value) -> Self {
+        let text = |value: &serde_json::Value| value.as_str().unwrap_or_default().to_string();
+        Self {
+            id: text(&result["id"]),
+            at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            tenant: tenant.to_string(),
+            verdict: text(&result["verdict"]),
+            risk_score: result["risk_score"].as_u64().unwrap_or_default() as u32,
+            depth: text(&result["depth"]),
+            partial: result["partial"].as_bool().unwrap_or_default(),
+            from_domain: result["evidence"]["from_domain"]
+                .as_str()
+                .map(str::to_string),
+            rule_ids: result["reasons"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|reason| reason["rule_id"].as_str())
+                .filter(|rule_id| !rule_id.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+}
+
+/// Rows handed to an [`AnalyticsSink`] so far, by outcome
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AnalyticsStats {
+    /// Rows inserted
+    pub sent: u64,
+    /// Rows dropped because the queue was full
+    pub dropped: u64,
+    /// Rows lost to failed inserts
+    pub failed: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicU64,
+    dropped: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// Batches analysis rows into an analytics database over HTTP, apart from the
+/// transactional [`AnalysisStore`](crate::store::AnalysisStore)
+///
+/// Rows are sent from a background task, so a slow or unreachable database never
+/// delays a verdict: a full queue drops rows, and a failed insert drops its batch.
+/// Both are counted in [`stats`](Self::stats).
+pub struct AnalyticsSink {
+    rows: mpsc::Sender<AnalyticsRow>,
+    counters: Arc<Counters>,
+}
+
+impl AnalyticsSink {
+    /// Starts the background task inserting batches with `client`
+    pub fn spawn(client: reqwest::Client, config: AnalyticsConfig) -> Self {
+        let (rows, receiver) = mpsc::channel(config.queue.max(1));
+        let counters = Arc::new(Counters::default());
+        tokio::spawn(insert_batches(client, config, receiver, counters.clone()));
+        Self { rows, counters }
+    }
+
+    /// Queues `row` for the next batch
+    pub fn send(&self, row: AnalyticsRow) {
+        if self.rows.try_send(row).is_err() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn stats(&self) -> AnalyticsStats {
+        AnalyticsStats {
+            sent: self.counters.sent.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            failed: self.counters.failed.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `rows` as an NDJSON insert body (ClickHouse's `JSONEachRow`)
+pub fn ndjson_batch(rows: &[AnalyticsRow]) -> String {
+    rows.iter()
+        .filter_map(|row| serde_json::to_string(row).ok())
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Inserts a batch when it is full or the flush interval passes, until the sink is
+/// dropped; the last rows are inserted then
+async fn insert_batches(
+    client: reqwest::Client,
+    config: AnalyticsConfig,
+    mut receiver: mpsc::Receiver<AnalyticsRow>,
+    counters: Arc<Counters>,
+) {
+    let batch_size = config.batch_size.max(1);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut ticker = tokio::time::interval(config.flush_interval);
+    loop {
+        let open = tokio::select! {
+            row = receiver.recv() => match row {
+                Some(row) => {
+                    batch.push(row);
+                    if batch.len() < batch_size {
+                        continue;
+                    }
+                    true
+                }
+                None => false,
+            },
+            _ = ticker.tick() => true,
+        };
+        if !batch.is_empty() {
+            let rows = batch.len() as u64;
+            match insert(&client, &config.url, ndjson_batch(&batch)).await {
+                Ok(()) => counters.sent.fetch_add(rows, Ordering::Relaxed),
+                Err(e) => {
+                    log::warn!("Inserting {} analytics rows failed: {}", rows, e);
+                    counters.failed.fetch_add(rows, Ordering::Relaxed)
+                }
+            };
+            batch.clear();
+        }
+        if !open {
+            return;
+        }
+    }
+}
+
+async fn insert(client: &reqwest::Client, url: &str, body: String) -> anyhow::Result<()> {
+    client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+        .body(body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalyticsRow, ndjson_batch};
+    use crate::dns::DnsSnapshot;
+    use crate::email_verdict::{AnalysisOptions, analyze_email_with_options};
+    use crate::parse::parse_email;
+
+    #[tokio::test]
+    async fn test_analytics_row() {
+        let snapshot: DnsSnapshot =
+            serde_json::from_str(include_str!("../fixtures/dns.json")).unwrap();
+        let parsed = parse_email(include_bytes!("../fixtures/spoofed.eml")).unwrap();
+        let result = analyze_email_with_options(&parsed, &snapshot, &AnalysisOptions::default())
+            .await
+            .unwrap();
+        let row = AnalyticsRow::new("acme", &serde_json::to_value(&result).unwrap());
+
+        assert_eq!(row.id, result.id);
+        assert_eq!(row.verdict, "PolicyViolation");
+        assert_eq!(row.risk_score, result.risk_score);
+        assert_eq!(row.from_domain, result.evidence.from_domain);
+        assert!(row.rule_ids.iter().any(|id| id == "ESD-0005"));
+
+        let body = ndjson_batch(&[row.clone(), row]);
+        assert_eq!(body.lines().count(), 2);
+        let line: serde_json::Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+        assert_eq!(line["tenant"], "acme");
+    }
+}
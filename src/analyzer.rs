@@ -0,0 +1,310 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use futures::future::{AbortHandle, Abortable};
+
+use crate::{
+    dns::{DeadlineResolver, DnsSnapshot, ResolverTrait, SnapshotRecorder},
+    email_verdict::{
+        AnalysisDepth, AnalysisOptions, AnalysisResult, Verdict, analyze_email_at_depth,
+    },
+    parse::EmailParsed,
+};
+use tokio::time::Instant;
+
+/// Long-lived analyzer sharing one resolver, and therefore its DNS cache, across messages
+///
+/// `DnsResolver` caches answers for their TTL, so reusing an `Analyzer` avoids repeating
+/// lookups for frequently seen sender domains. The options can be replaced while the
+/// analyzer is in use.
+pub struct Analyzer<R> {
+    resolver: R,
+    options: RwLock<Arc<AnalysisOptions>>,
+}
+
+impl<R: ResolverTrait + Sync + Send> Analyzer<R> {
+    pub fn new(resolver: R, options: AnalysisOptions) -> Self {
+        Self {
+            resolver,
+            options: RwLock::new(Arc::new(options)),
+        }
+    }
+
+    /// The current options
+    pub fn options(&self) -> Arc<AnalysisOptions> {
+        self.options.read().unwrap().clone()
+    }
+
+    /// Replaces the options; analyses already running finish with the previous ones
+    pub fn set_options(&self, options: AnalysisOptions) {
+        *self.options.write().unwrap() = Arc::new(options);
+    }
+
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    /// Analyze a parsed email with this analyzer's resolver and options
+    pub async fn analyze(&self, parsed: &EmailParsed) -> anyhow::Result<AnalysisResult> {
+        self.analyze_at(parsed, AnalysisDepth::Standard, None).await
+    }
+
+    /// Like `analyze`, as thoroughly as `depth` asks
+    ///
+    /// Lookups still pending at `deadline` are given up: the result is marked `partial`
+    /// and its verdict becomes `Indeterminate`, keeping the evidence gathered so far.
+    pub async fn analyze_at(
+        &self,
+        parsed: &EmailParsed,
+        depth: AnalysisDepth,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<AnalysisResult> {
+        let options = self.options();
+        let bounded = DeadlineResolver::new(&self.resolver, deadline);
+        let mut result = analyze_email_at_depth(parsed, &bounded, &options, depth).await?;
+        mark_partial(&mut result, &bounded);
+        Ok(result)
+    }
+
+    /// Like `analyze_at`, also returning the DNS answers the analysis was based on
+    ///
+    /// Lookups cut off by the deadline are left out of the snapshot.
+    pub async fn analyze_recording(
+        &self,
+        parsed: &EmailParsed,
+        depth: AnalysisDepth,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<(AnalysisResult, DnsSnapshot)> {
+        let options = self.options();
+        let recorder = SnapshotRecorder::new(&self.resolver);
+        let bounded = DeadlineResolver::new(&recorder, deadline);
+        let mut result = analyze_email_at_depth(parsed, &bounded, &options, depth).await?;
+        mark_partial(&mut result, &bounded);
+        Ok((result, recorder.into_snapshot()))
+    }
+
+    /// Resolves SPF, DMARC, existence, and MX for `domains` so the first messages
+    /// from them after startup are answered from cache
+    pub async fn prefetch_domains(&self, domains: &[&str]) {
+        futures::future::join_all(domains.iter().map(|domain| async move {
+            futures::join!(
+                self.resolver.resolve_spf(domain),
+                self.resolver.resolve_dmarc(domain),
+                self.resolver.domain_exists(domain),
+                self.resolver.resolve_mx(domain),
+            );
+        }))
+        .await;
+    }
+}
+
+/// Returned by an analysis cancelled through its [`AnalysisHandle`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "analysis cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Cancels a running analysis from elsewhere, e.g. a job queue shutting down
+///
+/// The analysis is dropped at its next await point, aborting its pending DNS lookups,
+/// and fails with [`Cancelled`]. Dropping the analysis future cancels it as well.
+#[derive(Debug, Clone)]
+pub struct AnalysisHandle {
+    abort: AbortHandle,
+    finished: Arc<AtomicBool>,
+}
+
+impl AnalysisHandle {
+    pub fn cancel(&self) {
+        self.abort.abort();
+    }
+
+    /// Whether the analysis completed or was cancelled; false while it is pending or
+    /// after it was dropped unfinished
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+}
+
+/// Makes `analysis` cancellable, returning its handle and the future to await
+pub fn cancellable<F: Future>(
+    analysis: F,
+) -> (
+    AnalysisHandle,
+    impl Future<Output = Result<F::Output, Cancelled>>,
+) {
+    let (abort, registration) = AbortHandle::new_pair();
+    let finished = Arc::new(AtomicBool::new(false));
+    let handle = AnalysisHandle {
+        abort,
+        finished: finished.clone(),
+    };
+    let analysis = async move {
+        let output = Abortable::new(analysis, registration).await;
+        finished.store(true, Ordering::SeqCst);
+        output.map_err(|_| Cancelled)
+    };
+    (handle, analysis)
+}
+
+/// Marks `result` partial if `resolver` cut off any lookup, since its verdict then rests
+/// on missing DNS answers
+fn mark_partial<R>(result: &mut AnalysisResult, resolver: &DeadlineResolver<'_, R>) {
+    if resolver.expired() {
+        result.partial = true;
+        result.verdict = Verdict::Indeterminate;
+        result.rescore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Analyzer, Cancelled, cancellable};
+    use crate::dns::ResolverTrait;
+    use crate::email_verdict::{AnalysisDepth, AnalysisOptions, Verdict};
+    use crate::parse::parse_email;
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[derive(Default)]
+    struct RecordingResolver {
+        queries: Mutex<Vec<String>>,
+    }
+
+    impl RecordingResolver {
+        fn record(&self, query: String) {
+            self.queries.lock().unwrap().push(query);
+        }
+    }
+
+    #[async_trait]
+    impl ResolverTrait for RecordingResolver {
+        async fn resolve_spf(&self, domain: &str) -> Option<String> {
+            self.record(format!("spf:{}", domain));
+            None
+        }
+
+        async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
+            self.record(format!("dmarc:{}", domain));
+            None
+        }
+
+        async fn domain_exists(&self, domain: &str) -> bool {
+            self.record(format!("exists:{}", domain));
+            true
+        }
+
+        async fn resolve_mx(&self, domain: &str) -> bool {
+            self.record(format!("mx:{}", domain));
+            true
+        }
+    }
+
+    #[test]
+    fn test_set_options_replaces_options() {
+        let analyzer = Analyzer::new(RecordingResolver::default(), AnalysisOptions::default());
+        let before = analyzer.options();
+        analyzer.set_options(AnalysisOptions {
+            protected_domains: vec!["example.com".to_string()],
+            ..AnalysisOptions::default()
+        });
+
+        assert!(before.protected_domains.is_empty());
+        assert_eq!(analyzer.options().protected_domains, vec!["example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_domains_queries_every_record() {
+        let analyzer = Analyzer::new(RecordingResolver::default(), AnalysisOptions::default());
+        analyzer
+            .prefetch_domains(&["example.com", "example.org"])
+            .await;
+
+        let mut queries = analyzer.resolver().queries.lock().unwrap().clone();
+        queries.sort();
+        assert_eq!(
+            queries,
+            vec![
+                "dmarc:example.com",
+                "dmarc:example.org",
+                "exists:example.com",
+                "exists:example.org",
+                "mx:example.com",
+                "mx:example.org",
+                "spf:example.com",
+                "spf:example.org",
+            ]
+        );
+    }
+
+    /// Answers SPF at once, everything else only after a minute
+    struct SlowResolver;
+
+    #[async_trait]
+    impl ResolverTrait for SlowResolver {
+        async fn resolve_spf(&self, _domain: &str) -> Option<String> {
+            Some("v=spf1 -all".to_string())
+        }
+
+        async fn resolve_dmarc(&self, _domain: &str) -> Option<String> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Some("v=DMARC1; p=reject".to_string())
+        }
+
+        async fn domain_exists(&self, _domain: &str) -> bool {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            true
+        }
+
+        async fn resolve_mx(&self, _domain: &str) -> bool {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            true
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deadline_returns_partial_result() {
+        let analyzer = Analyzer::new(SlowResolver, AnalysisOptions::default());
+        let parsed = parse_email(b"From: ceo@example.com\r\nSubject: hi\r\n\r\nbody").unwrap();
+        let deadline = Instant::now() + Duration::from_millis(50);
+
+        let (result, snapshot) = analyzer
+            .analyze_recording(&parsed, AnalysisDepth::Standard, Some(deadline))
+            .await
+            .unwrap();
+
+        assert!(result.partial);
+        assert_eq!(result.verdict, Verdict::Indeterminate);
+        assert!(result.evidence.domain_valid);
+        assert_eq!(result.evidence.spf_policy.as_deref(), Some("v=spf1 -all"));
+        assert!(result.reasons.iter().any(|r| r.key == "deadline_exceeded"));
+        assert_eq!(snapshot.domains["example.com"].dmarc, None);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_aborts_pending_lookups() {
+        let analyzer = Analyzer::new(SlowResolver, AnalysisOptions::default());
+        let parsed = parse_email(b"From: ceo@example.com\r\n\r\nbody").unwrap();
+        let (handle, analysis) =
+            cancellable(analyzer.analyze_at(&parsed, AnalysisDepth::Standard, None));
+        assert!(!handle.is_finished());
+
+        let canceller = handle.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+        let started = Instant::now();
+        assert_eq!(analysis.await.unwrap_err(), Cancelled);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(handle.is_finished());
+    }
+}
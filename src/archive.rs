@@ -0,0 +1,944 @@
+use lzma_rs::decompress::{Options, UnpackedSize};
+
+/// Extensions of files that run when opened, lowercase
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "scr", "com", "pif", "bat", "cmd", "msi", "dll", "cpl", "js", "jse", "vbs", "vbe",
+    "wsf", "wsh", "hta", "ps1", "lnk", "jar", "reg", "iso", "img", "vhd",
+];
+
+/// Extensions of archives, lowercase
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "rar", "7z", "gz", "tgz", "tar", "cab", "arj", "ace"];
+
+/// Members listed per archive; the rest are left out
+const MAX_ENTRIES: usize = 10_000;
+
+/// 7-Zip coder IDs
+const SEVENZ_LZMA: &[u8] = &[0x03, 0x01, 0x01];
+const SEVENZ_AES: &[u8] = &[0x06, 0xf1, 0x07, 0x01];
+
+/// The container format of an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveFormat {
+    Zip,
+    Rar,
+    SevenZip,
+}
+
+/// A file in an archive, as its directory describes it
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    /// Uncompressed size, when the directory records it
+    pub size: Option<u64>,
+    /// Whether the member needs a password to extract
+    pub encrypted: bool,
+}
+
+/// The contents of an archive attachment, read from its directory without extracting
+/// anything
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ArchiveListing {
+    pub format: ArchiveFormat,
+    /// Members, in archive order; empty when the directory itself is encrypted
+    pub entries: Vec<ArchiveEntry>,
+    /// Whether any member, or the directory itself, needs a password
+    pub encrypted: bool,
+    /// Whether the directory could be read; `false` for encrypted 7z and RAR
+    /// directories and for 7z directories compressed other than with LZMA
+    pub listed: bool,
+    /// Names of members that run when opened (`.exe`, `.js`, `.lnk`, ...)
+    pub executables: Vec<String>,
+    /// Names of members that are archives themselves
+    pub nested_archives: Vec<String>,
+}
+
+/// Lists the members of a zip, RAR, or 7z archive; `None` for other data
+///
+/// Only the archive's directory is read. A 7z directory compressed with LZMA is
+/// decompressed when its declared size is at most `max_directory` bytes.
+pub fn list_archive(data: &[u8], max_directory: u64) -> Option<ArchiveListing> {
+    let (format, directory) = if data.starts_with(b"PK\x03\x04") {
+        let entries = zip_members(data)
+            .into_iter()
+            .map(|member| ArchiveEntry {
+                name: member.name,
+                size: Some(member.uncompressed_size),
+                encrypted: member.encrypted,
+            })
+            .collect();
+        (ArchiveFormat::Zip, Directory::listed(entries))
+    } else if data.starts_with(b"Rar!\x1a\x07\x01\x00") {
+        (ArchiveFormat::Rar, rar5_directory(data))
+    } else if data.starts_with(b"Rar!\x1a\x07\x00") {
+        (ArchiveFormat::Rar, rar4_directory(data))
+    } else if data.starts_with(b"7z\xbc\xaf\x27\x1c") {
+        (
+            ArchiveFormat::SevenZip,
+            sevenz_directory(data, max_directory),
+        )
+    } else {
+        return None;
+    };
+    let entries = directory.entries;
+
+    let with_extension = |extensions: &[&str]| -> Vec<String> {
+        entries
+            .iter()
+            .filter(|entry| {
+                let name = entry.name.to_ascii_lowercase();
+                name.rsplit_once('.')
+                    .is_some_and(|(_, extension)| extensions.contains(&extension))
+            })
+            .map(|entry| entry.name.clone())
+            .collect()
+    };
+    Some(ArchiveListing {
+        format,
+        executables: with_extension(EXECUTABLE_EXTENSIONS),
+        nested_archives: with_extension(ARCHIVE_EXTENSIONS),
+        encrypted: directory.encrypted || entries.iter().any(|entry| entry.encrypted),
+        listed: directory.listed,
+        entries,
+    })
+}
+
+/// A file in a zip archive, as its central directory describes it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ZipMember {
+    pub name: String,
+    /// Compression method: 0 stored, 8 deflate
+    pub method: u16,
+    pub encrypted: bool,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// Start of the member's data, after its local header
+    pub data_offset: usize,
+}
+
+/// The members of a zip archive; empty when the central directory cannot be found
+pub(crate) fn zip_members(data: &[u8]) -> Vec<ZipMember> {
+    let u16_at = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+    // The end of central directory record sits within the last 64 KiB (its comment)
+    let tail_start = data.len().saturating_sub(22 + 0xffff);
+    let Some(eocd) = data[tail_start..]
+        .windows(4)
+        .rposition(|w| w == b"PK\x05\x06")
+        .map(|at| tail_start + at)
+    else {
+        return Vec::new();
+    };
+    let (Some(count), Some(mut at)) = (u16_at(eocd + 10), u32_at(eocd + 16)) else {
+        return Vec::new();
+    };
+
+    let mut members = Vec::new();
+    for _ in 0..count.min(MAX_ENTRIES) {
+        if data.get(at..at + 4) != Some(b"PK\x01\x02") {
+            break;
+        }
+        let fields = (
+            u16_at(at + 8),
+            u16_at(at + 10),
+            u32_at(at + 20),
+            u32_at(at + 24),
+            u16_at(at + 28),
+            u16_at(at + 30),
+            u16_at(at + 32),
+            u32_at(at + 42),
+        );
+        let (
+            Some(flags),
+            Some(method),
+            Some(compressed_size),
+            Some(uncompressed_size),
+            Some(name_len),
+            Some(extra_len),
+            Some(comment_len),
+            Some(local),
+        ) = fields
+        else {
+            break;
+        };
+        let name = data
+            .get(at + 46..at + 46 + name_len)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .unwrap_or_default();
+        let data_offset = match (u16_at(local + 26), u16_at(local + 28)) {
+            (Some(local_name), Some(local_extra)) => local + 30 + local_name + local_extra,
+            _ => usize::MAX,
+        };
+        members.push(ZipMember {
+            name,
+            method: method as u16,
+            encrypted: flags & 1 != 0,
+            compressed_size: compressed_size as u64,
+            uncompressed_size: uncompressed_size as u64,
+            data_offset,
+        });
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    members
+}
+
+/// What could be read of an archive's directory
+struct Directory {
+    entries: Vec<ArchiveEntry>,
+    listed: bool,
+    /// Whether the directory itself is encrypted
+    encrypted: bool,
+}
+
+impl Directory {
+    fn listed(entries: Vec<ArchiveEntry>) -> Self {
+        Self {
+            entries,
+            listed: true,
+            encrypted: false,
+        }
+    }
+
+    fn unlisted(encrypted: bool) -> Self {
+        Self {
+            entries: Vec::new(),
+            listed: false,
+            encrypted,
+        }
+    }
+}
+
+/// Reads little-endian integers and variable-length numbers from a byte slice; every
+/// read past the end yields `None`
+struct Cursor<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8], at: usize) -> Self {
+        Self { data, at }
+    }
+
+    fn bytes(&mut self, len: u64) -> Option<&'a [u8]> {
+        let end = self.at.checked_add(usize::try_from(len).ok()?)?;
+        let bytes = self.data.get(self.at..end)?;
+        self.at = end;
+        Some(bytes)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u64> {
+        let b = self.bytes(2)?;
+        Some(u16::from_le_bytes([b[0], b[1]]) as u64)
+    }
+
+    fn u32(&mut self) -> Option<u64> {
+        let b = self.bytes(4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as u64)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    /// RAR5 `vint`: 7 bits per byte, least significant first, high bit set on all
+    /// but the last byte
+    fn vint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        for shift in (0..70).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// 7z `NUMBER`: the leading one bits of the first byte count the bytes that follow
+    fn number(&mut self) -> Option<u64> {
+        let first = self.byte()?;
+        let mut mask = 0x80;
+        let mut value = 0u64;
+        for i in 0..8 {
+            if first & mask == 0 {
+                let high = (first & mask.wrapping_sub(1)) as u64;
+                return Some(value | (high << (8 * i)));
+            }
+            value |= (self.byte()? as u64) << (8 * i);
+            mask >>= 1;
+        }
+        Some(value)
+    }
+
+    /// A count that is used to size a loop, capped so a forged one cannot stall it
+    fn count(&mut self) -> Option<usize> {
+        let count = self.number()?;
+        (count <= MAX_ENTRIES as u64).then_some(count as usize)
+    }
+}
+
+/// The directory of a RAR 5 archive: its file headers
+fn rar5_directory(data: &[u8]) -> Directory {
+    let mut entries = Vec::new();
+    let mut cursor = Cursor::new(data, 8);
+    while entries.len() < MAX_ENTRIES {
+        let Some(block) = rar5_block(&mut cursor) else {
+            break;
+        };
+        match block {
+            Rar5Block::Encryption => return Directory::unlisted(true),
+            Rar5Block::File(entry) => entries.push(entry),
+            Rar5Block::End => break,
+            Rar5Block::Other => {}
+        }
+    }
+    Directory::listed(entries)
+}
+
+enum Rar5Block {
+    /// Archive encryption header: every header after it is encrypted
+    Encryption,
+    File(ArchiveEntry),
+    End,
+    Other,
+}
+
+/// Reads the header block at the cursor and moves past its data
+fn rar5_block(cursor: &mut Cursor) -> Option<Rar5Block> {
+    cursor.u32()?;
+    let size = cursor.vint()?;
+    let header = cursor.bytes(size)?;
+    let data_start = cursor.at;
+
+    let mut fields = Cursor::new(header, 0);
+    let kind = fields.vint()?;
+    let flags = fields.vint()?;
+    let extra_size = if flags & 0x01 != 0 { fields.vint()? } else { 0 };
+    let data_size = if flags & 0x02 != 0 { fields.vint()? } else { 0 };
+    cursor.at = data_start.checked_add(usize::try_from(data_size).ok()?)?;
+
+    Some(match kind {
+        2 => {
+            let file_flags = fields.vint()?;
+            let size = fields.vint()?;
+            fields.vint()?;
+            if file_flags & 0x02 != 0 {
+                fields.u32()?;
+            }
+            if file_flags & 0x04 != 0 {
+                fields.u32()?;
+            }
+            fields.vint()?;
+            fields.vint()?;
+            let name_len = fields.vint()?;
+            let name = String::from_utf8_lossy(fields.bytes(name_len)?).into_owned();
+            // Extra area records: size, type, data; type 1 is file encryption
+            let extra_start = header
+                .len()
+                .checked_sub(usize::try_from(extra_size).ok()?)?;
+            let mut extra = Cursor::new(header, extra_start);
+            let mut encrypted = false;
+            while extra.at < header.len() {
+                let Some(record_size) = extra.vint() else {
+                    break;
+                };
+                let record_start = extra.at;
+                encrypted |= extra.vint() == Some(1);
+                let Some(next) = usize::try_from(record_size)
+                    .ok()
+                    .and_then(|size| record_start.checked_add(size))
+                else {
+                    break;
+                };
+                extra.at = next;
+            }
+            Rar5Block::File(ArchiveEntry {
+                name,
+                size: (file_flags & 0x08 == 0).then_some(size),
+                encrypted,
+            })
+        }
+        4 => Rar5Block::Encryption,
+        5 => Rar5Block::End,
+        _ => Rar5Block::Other,
+    })
+}
+
+/// The directory of a RAR 4 archive: its file headers
+fn rar4_directory(data: &[u8]) -> Directory {
+    let mut entries = Vec::new();
+    let mut at = 7;
+    while entries.len() < MAX_ENTRIES {
+        let mut block = Cursor::new(data, at);
+        let (Some(_crc), Some(kind), Some(flags), Some(size)) =
+            (block.u16(), block.byte(), block.u16(), block.u16())
+        else {
+            break;
+        };
+        let added = if flags & 0x8000 != 0 {
+            block.u32().unwrap_or_default()
+        } else {
+            0
+        };
+        match kind {
+            // Main header with encrypted block headers
+            0x73 if flags & 0x0080 != 0 => return Directory::unlisted(true),
+            0x74 => {
+                let Some(entry) = rar4_file(&mut block, flags) else {
+                    break;
+                };
+                entries.push(entry);
+            }
+            0x7b => break,
+            _ => {}
+        }
+        if size < 7 {
+            break;
+        }
+        at += (size + added) as usize;
+    }
+    Directory::listed(entries)
+}
+
+/// A RAR 4 file header, read from after its packed size
+fn rar4_file(block: &mut Cursor, flags: u64) -> Option<ArchiveEntry> {
+    let size = block.u32()?;
+    block.bytes(11)?;
+    let name_len = block.u16()?;
+    block.u32()?;
+    if flags & 0x100 != 0 {
+        block.bytes(8)?;
+    }
+    // Unicode names follow the ASCII one after a NUL
+    let name = block.bytes(name_len)?;
+    let name = name.split(|&b| b == 0).next().unwrap_or_default();
+    Some(ArchiveEntry {
+        name: String::from_utf8_lossy(name).into_owned(),
+        size: Some(size),
+        encrypted: flags & 0x04 != 0,
+    })
+}
+
+/// A 7z coder's ID and properties
+type Coder = (Vec<u8>, Vec<u8>);
+
+/// What the streams info of a 7z header says about how data is packed
+#[derive(Debug, Default)]
+struct SevenzStreams {
+    pack_pos: u64,
+    pack_sizes: Vec<u64>,
+    /// The coders of each folder
+    folders: Vec<Vec<Coder>>,
+    /// Unpacked size of each folder's final output
+    unpack_sizes: Vec<u64>,
+}
+
+impl SevenzStreams {
+    fn encrypted(&self) -> bool {
+        self.folders
+            .iter()
+            .flatten()
+            .any(|(id, _)| id.as_slice() == SEVENZ_AES)
+    }
+}
+
+/// The directory of a 7z archive: the file list in its header
+fn sevenz_directory(data: &[u8], max_directory: u64) -> Directory {
+    let mut start = Cursor::new(data, 12);
+    let (Some(offset), Some(size)) = (start.u64(), start.u64()) else {
+        return Directory::unlisted(false);
+    };
+    let Some(header) = offset
+        .checked_add(32)
+        .and_then(|at| Cursor::new(data, usize::try_from(at).ok()?).bytes(size))
+    else {
+        return Directory::unlisted(false);
+    };
+    let mut cursor = Cursor::new(header, 0);
+    let entries = match cursor.byte() {
+        Some(0x01) => sevenz_header(&mut cursor),
+        // Encoded header: the real one is packed like file data
+        Some(0x17) => match sevenz_streams(&mut cursor) {
+            Some(streams) if streams.encrypted() => return Directory::unlisted(true),
+            Some(streams) => {
+                sevenz_decode_header(data, &streams, max_directory).and_then(|decoded| {
+                    let mut cursor = Cursor::new(&decoded, 0);
+                    match cursor.byte()? {
+                        0x01 => sevenz_header(&mut cursor),
+                        _ => None,
+                    }
+                })
+            }
+            None => None,
+        },
+        _ => None,
+    };
+    match entries {
+        Some(entries) => Directory::listed(entries),
+        None => Directory::unlisted(false),
+    }
+}
+
+/// Unpacks an LZMA-compressed 7z header; `None` when it is packed otherwise or larger
+/// than `max_directory`
+fn sevenz_decode_header(
+    data: &[u8],
+    streams: &SevenzStreams,
+    max_directory: u64,
+) -> Option<Vec<u8>> {
+    let [coder] = streams.folders.first()?.as_slice() else {
+        return None;
+    };
+    let (id, props) = coder;
+    let unpacked = *streams.unpack_sizes.first()?;
+    if id.as_slice() != SEVENZ_LZMA || props.len() != 5 || unpacked > max_directory {
+        return None;
+    }
+    let start = usize::try_from(streams.pack_pos.checked_add(32)?).ok()?;
+    let packed = Cursor::new(data, start).bytes(*streams.pack_sizes.first()?)?;
+    // lzma-rs reads the properties from a header in front of the stream
+    let mut input = props.clone();
+    input.extend_from_slice(packed);
+    let mut output = Vec::new();
+    let options = Options {
+        unpacked_size: UnpackedSize::UseProvided(Some(unpacked)),
+        memlimit: Some(max_directory as usize),
+        allow_incomplete: false,
+    };
+    lzma_rs::lzma_decompress_with_options(&mut input.as_slice(), &mut output, &options).ok()?;
+    Some(output)
+}
+
+/// Parses the 7z header after its ID; `None` when it is malformed
+fn sevenz_header(cursor: &mut Cursor) -> Option<Vec<ArchiveEntry>> {
+    let mut id = cursor.byte()?;
+    if id == 0x02 {
+        // Archive properties: type and size until a zero type
+        while cursor.byte()? != 0 {
+            let size = cursor.number()?;
+            cursor.bytes(size)?;
+        }
+        id = cursor.byte()?;
+    }
+    if id == 0x03 {
+        sevenz_streams(cursor)?;
+        id = cursor.byte()?;
+    }
+    let mut encrypted = false;
+    if id == 0x04 {
+        encrypted = sevenz_streams(cursor)?.encrypted();
+        id = cursor.byte()?;
+    }
+    if id != 0x05 {
+        return Some(Vec::new());
+    }
+
+    let count = cursor.count()?;
+    let mut names = Vec::new();
+    loop {
+        let property = cursor.byte()?;
+        if property == 0 {
+            break;
+        }
+        let size = cursor.number()?;
+        let value = cursor.bytes(size)?;
+        // Names: an external flag, then NUL-terminated UTF-16LE strings
+        if property == 0x11 && value.first() == Some(&0) {
+            let units: Vec<u16> = value[1..]
+                .chunks_exact(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+                .collect();
+            names = units
+                .split(|&unit| unit == 0)
+                .take(count)
+                .map(String::from_utf16_lossy)
+                .collect();
+        }
+    }
+    Some(
+        names
+            .into_iter()
+            .map(|name| ArchiveEntry {
+                name,
+                size: None,
+                encrypted,
+            })
+            .collect(),
+    )
+}
+
+/// Parses a 7z streams info block after its ID, up to and including its end marker
+fn sevenz_streams(cursor: &mut Cursor) -> Option<SevenzStreams> {
+    let mut streams = SevenzStreams::default();
+    let mut id = cursor.byte()?;
+    if id == 0x06 {
+        streams.pack_pos = cursor.number()?;
+        let count = cursor.count()?;
+        loop {
+            match cursor.byte()? {
+                0x00 => break,
+                0x09 => {
+                    for _ in 0..count {
+                        streams.pack_sizes.push(cursor.number()?);
+                    }
+                }
+                0x0a => sevenz_digests(cursor, count)?,
+                _ => return None,
+            }
+        }
+        id = cursor.byte()?;
+    }
+    let mut outputs = Vec::new();
+    if id == 0x07 {
+        if cursor.byte()? != 0x0b {
+            return None;
+        }
+        let count = cursor.count()?;
+        if cursor.byte()? != 0 {
+            return None;
+        }
+        for _ in 0..count {
+            let (coders, total_out) = sevenz_folder(cursor)?;
+            streams.folders.push(coders);
+            outputs.push(total_out);
+        }
+        if cursor.byte()? != 0x0c {
+            return None;
+        }
+        for total_out in &outputs {
+            let mut last = 0;
+            for _ in 0..*total_out {
+                last = cursor.number()?;
+            }
+            streams.unpack_sizes.push(last);
+        }
+        loop {
+            match cursor.byte()? {
+                0x00 => break,
+                0x0a => sevenz_digests(cursor, count)?,
+                _ => return None,
+            }
+        }
+        id = cursor.byte()?;
+    }
+    if id == 0x08 {
+        sevenz_substreams(cursor, outputs.len())?;
+        id = cursor.byte()?;
+    }
+    (id == 0x00).then_some(streams)
+}
+
+/// Reads a folder: its coders' IDs and properties, and its number of outputs
+fn sevenz_folder(cursor: &mut Cursor) -> Option<(Vec<Coder>, u64)> {
+    let count = cursor.count()?;
+    let mut coders = Vec::new();
+    let (mut total_in, mut total_out) = (0u64, 0u64);
+    for _ in 0..count {
+        let flags = cursor.byte()?;
+        // Alternative methods were never implemented by 7-Zip
+        if flags & 0x80 != 0 {
+            return None;
+        }
+        let id = cursor.bytes((flags & 0x0f) as u64)?.to_vec();
+        let (inputs, outputs) = if flags & 0x10 != 0 {
+            (cursor.number()?, cursor.number()?)
+        } else {
+            (1, 1)
+        };
+        total_in = total_in.checked_add(inputs)?;
+        total_out = total_out.checked_add(outputs)?;
+        let props = if flags & 0x20 != 0 {
+            let size = cursor.number()?;
+            cursor.bytes(size)?.to_vec()
+        } else {
+            Vec::new()
+        };
+        coders.push((id, props));
+    }
+    if total_out == 0 || total_out > MAX_ENTRIES as u64 {
+        return None;
+    }
+    let bind_pairs = total_out - 1;
+    for _ in 0..bind_pairs {
+        cursor.number()?;
+        cursor.number()?;
+    }
+    let packed = total_in.checked_sub(bind_pairs)?;
+    if packed > 1 {
+        for _ in 0..packed.min(MAX_ENTRIES as u64) {
+            cursor.number()?;
+        }
+    }
+    Some((coders, total_out))
+}
+
+/// Skips a 7z substreams info block after its ID, up to and including its end marker
+fn sevenz_substreams(cursor: &mut Cursor, folders: usize) -> Option<()> {
+    let mut per_folder = vec![1usize; folders];
+    let mut id = cursor.byte()?;
+    if id == 0x0d {
+        for count in &mut per_folder {
+            *count = cursor.count()?;
+        }
+        id = cursor.byte()?;
+    }
+    if id == 0x09 {
+        for count in &per_folder {
+            for _ in 1..*count {
+                cursor.number()?;
+            }
+        }
+        id = cursor.byte()?;
+    }
+    while id != 0x00 {
+        match id {
+            // Digests of streams whose folder has none of its own; without tracking
+            // folder digests, take them all
+            0x0a => sevenz_digests(cursor, per_folder.iter().sum())?,
+            _ => return None,
+        }
+        id = cursor.byte()?;
+    }
+    Some(())
+}
+
+/// Skips a digest list of `count` entries
+fn sevenz_digests(cursor: &mut Cursor, count: usize) -> Option<()> {
+    let defined = if cursor.byte()? != 0 {
+        count
+    } else {
+        let bits = cursor.bytes(count.div_ceil(8) as u64)?;
+        (0..count)
+            .filter(|i| bits[i / 8] & (0x80 >> (i % 8)) != 0)
+            .count()
+    };
+    cursor.bytes(4 * defined as u64)?;
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArchiveFormat, MAX_ENTRIES, list_archive};
+
+    /// A zip of stored members, each `(name, encrypted)`
+    fn zip(members: &[(&str, bool)]) -> Vec<u8> {
+        let (mut data, mut directory) = (Vec::new(), Vec::new());
+        for (name, encrypted) in members {
+            let offset = data.len() as u32;
+            let flags: u16 = if *encrypted { 1 } else { 0 };
+            data.extend_from_slice(b"PK\x03\x04\x14\x00");
+            data.extend_from_slice(&flags.to_le_bytes());
+            data.extend_from_slice(&[0; 18]);
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0; 2]);
+            data.extend_from_slice(name.as_bytes());
+            directory.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00");
+            directory.extend_from_slice(&flags.to_le_bytes());
+            directory.extend_from_slice(&[0; 18]);
+            directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = data.len() as u32;
+        data.extend_from_slice(&directory);
+        data.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+        data.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(members.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&directory_offset.to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+        data
+    }
+
+    /// A RAR 5 archive with an empty file header for `name`
+    fn rar5(name: &str, encrypted: bool) -> Vec<u8> {
+        let mut header = vec![2, 0x01 | 0x02];
+        let extra: &[u8] = if encrypted { &[1, 1] } else { &[] };
+        header.extend_from_slice(&[extra.len() as u8, 0, 0, 0, 0, 0, 0, name.len() as u8]);
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(extra);
+        let mut data = b"Rar!\x1a\x07\x01\x00".to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.push(header.len() as u8);
+        data.extend_from_slice(&header);
+        data.extend_from_slice(&[0, 0, 0, 0, 3, 5, 0, 0]);
+        data
+    }
+
+    /// A 7z header listing `names`, uncompressed
+    fn sevenz_header(names: &[&str]) -> Vec<u8> {
+        let mut utf16 = vec![0];
+        for name in names {
+            for unit in name.encode_utf16().chain([0]) {
+                utf16.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        let mut header = vec![0x01, 0x05, names.len() as u8, 0x11, utf16.len() as u8];
+        header.extend_from_slice(&utf16);
+        header.extend_from_slice(&[0, 0]);
+        header
+    }
+
+    /// A 7z archive: `packed` as its only pack stream, then `header`
+    fn sevenz(packed: &[u8], header: &[u8]) -> Vec<u8> {
+        let mut data = b"7z\xbc\xaf\x27\x1c\x00\x04".to_vec();
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(packed.len() as u64).to_le_bytes());
+        data.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(packed);
+        data.extend_from_slice(header);
+        data
+    }
+
+    /// A 7z archive whose header listing `names` is LZMA-compressed into a pack stream,
+    /// as 7-Zip writes it by default
+    fn sevenz_lzma(names: &[&str]) -> Vec<u8> {
+        let plain = sevenz_header(names);
+        let mut compressed = Vec::new();
+        lzma_rs::lzma_compress(&mut plain.as_slice(), &mut compressed).unwrap();
+        let (props, stream) = (&compressed[..5], &compressed[13..]);
+        let mut encoded = vec![0x17, 0x06, 0x00, 0x01, 0x09, stream.len() as u8, 0x00];
+        encoded.extend_from_slice(&[0x07, 0x0b, 0x01, 0x00, 0x01, 0x23, 0x03, 0x01, 0x01, 0x05]);
+        encoded.extend_from_slice(props);
+        encoded.extend_from_slice(&[0x0c, plain.len() as u8, 0x00, 0x00]);
+        sevenz(stream, &encoded)
+    }
+
+    #[test]
+    fn test_list_archive() {
+        let listing =
+            list_archive(&zip(&[("invoice.pdf.exe", false), ("docs.zip", true)]), 0).unwrap();
+        assert_eq!(listing.format, ArchiveFormat::Zip);
+        assert_eq!(listing.entries.len(), 2);
+        assert_eq!(listing.executables, ["invoice.pdf.exe"]);
+        assert_eq!(listing.nested_archives, ["docs.zip"]);
+        assert!(listing.encrypted);
+        assert!(
+            !list_archive(&zip(&[("a.txt", false)]), 0)
+                .unwrap()
+                .encrypted
+        );
+
+        let listing = list_archive(&rar5("Scan.JS", true), 0).unwrap();
+        assert_eq!(listing.format, ArchiveFormat::Rar);
+        assert_eq!(listing.entries[0].name, "Scan.JS");
+        assert_eq!(listing.executables, ["Scan.JS"]);
+        assert!(listing.encrypted && listing.listed);
+
+        let listing = list_archive(&sevenz(&[], &sevenz_header(&["a.txt", "b.lnk"])), 0).unwrap();
+        assert_eq!(listing.format, ArchiveFormat::SevenZip);
+        assert_eq!(listing.executables, ["b.lnk"]);
+
+        let listing = list_archive(&sevenz_lzma(&["payload.exe"]), 1024).unwrap();
+        assert_eq!(listing.executables, ["payload.exe"]);
+        assert!(!listing.encrypted);
+        // Too large to decompress, so not listed
+        let listing = list_archive(&sevenz_lzma(&["payload.exe"]), 8).unwrap();
+        assert!(!listing.listed);
+
+        assert!(list_archive(b"%PDF-1.4", 0).is_none());
+    }
+
+    #[test]
+    fn test_truncated_archives() {
+        // Each sample with the length of its signature
+        let samples = [
+            (zip(&[("invoice.pdf.exe", false), ("docs.zip", true)]), 4),
+            (rar5("Scan.JS", true), 8),
+            (
+                b"Rar!\x1a\x07\x00\xff\xff\xff\xff\xff\xff\xff\xff".to_vec(),
+                7,
+            ),
+            (sevenz(&[], &sevenz_header(&["a.txt", "b.lnk"])), 6),
+            (sevenz_lzma(&["payload.exe"]), 6),
+        ];
+        for (sample, signature) in &samples {
+            for len in 0..sample.len() {
+                let listing = list_archive(&sample[..len], 1024);
+                assert_eq!(listing.is_some(), len >= *signature, "{:?}", &sample[..len]);
+            }
+        }
+
+        // Cut before the end of central directory record
+        let whole = zip(&[("a.exe", false)]);
+        let listing = list_archive(&whole[..whole.len() - 22], 0).unwrap();
+        assert!(listing.entries.is_empty() && listing.executables.is_empty());
+        // Directory offset past the end of the data
+        let mut corrupt = whole.clone();
+        let at = corrupt.len() - 6;
+        corrupt[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(list_archive(&corrupt, 0).unwrap().entries.is_empty());
+        // More members declared than present
+        let mut short = whole.clone();
+        let at = short.len() - 12;
+        short[at..at + 2].copy_from_slice(&5u16.to_le_bytes());
+        assert_eq!(list_archive(&short, 0).unwrap().entries.len(), 1);
+
+        // A 7z header cut short, or declared longer than the data
+        let header = sevenz_header(&["a.exe"]);
+        let listing = list_archive(&sevenz(&[], &header[..header.len() - 3]), 0).unwrap();
+        assert!(!listing.listed && listing.entries.is_empty());
+        let mut long = sevenz(&[], &header);
+        long[20..28].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(!list_archive(&long, 0).unwrap().listed);
+    }
+
+    #[test]
+    fn test_archive_limits() {
+        // Nested archives are matched on their last extension, in any case
+        let listing = list_archive(
+            &zip(&[
+                ("inner.7z", false),
+                ("X.RAR", false),
+                ("backup.tar.gz", false),
+                ("zip.txt", false),
+                ("archive", false),
+            ]),
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            listing.nested_archives,
+            ["inner.7z", "X.RAR", "backup.tar.gz"]
+        );
+        assert!(listing.executables.is_empty());
+
+        // Members past MAX_ENTRIES are left out
+        let names: Vec<String> = (0..MAX_ENTRIES + 5).map(|n| format!("{}.exe", n)).collect();
+        let members: Vec<(&str, bool)> = names.iter().map(|name| (name.as_str(), false)).collect();
+        let listing = list_archive(&zip(&members), 0).unwrap();
+        assert_eq!(listing.entries.len(), MAX_ENTRIES);
+        assert_eq!(listing.executables.len(), MAX_ENTRIES);
+
+        // A compressed 7z header is decompressed up to `max_directory` bytes
+        let size = sevenz_header(&["payload.exe"]).len() as u64;
+        assert!(
+            list_archive(&sevenz_lzma(&["payload.exe"]), size)
+                .unwrap()
+                .listed
+        );
+        assert!(
+            !list_archive(&sevenz_lzma(&["payload.exe"]), size - 1)
+                .unwrap()
+                .listed
+        );
+        assert!(
+            !list_archive(&sevenz_lzma(&["payload.exe"]), 0)
+                .unwrap()
+                .listed
+        );
+    }
+}
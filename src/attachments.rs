@@ -0,0 +1,357 @@
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use flate2::read::{DeflateDecoder, MultiGzDecoder};
+use mailparse::ParsedMail;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    archive::{ArchiveListing, list_archive, zip_members},
+    html::{HtmlIndicators, inspect_html, is_html_attachment},
+    office::{OfficeIndicators, inspect_office},
+    pdf::{PdfIndicators, inspect_pdf},
+};
+
+/// Bytes processed between checks of the deadline and the decompression budget
+const CHUNK: usize = 64 * 1024;
+
+/// Decompressed bytes below which no compression ratio counts as a bomb; a megabyte of
+/// blank spreadsheet cells legitimately compresses a thousandfold
+const MIN_BOMB_BYTES: u64 = 1024 * 1024;
+
+/// Resource limits on inspecting attachments, which attackers control byte for byte
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttachmentLimits {
+    /// Decoded bytes of an attachment inspected; larger ones are listed but not hashed
+    pub max_bytes: usize,
+    /// Bytes decompressed from one attachment, over all archive members
+    pub max_decompressed: u64,
+    /// Decompressed-to-compressed ratio from which an archive member is taken as a
+    /// decompression bomb
+    pub max_ratio: u64,
+    /// Time inspecting one attachment may take
+    pub part_timeout: Duration,
+    /// Whether Office documents are searched for macros, external references, and DDE
+    pub office: bool,
+}
+
+impl Default for AttachmentLimits {
+    fn default() -> Self {
+        Self {
+            max_bytes: 25 * 1024 * 1024,
+            max_decompressed: 100 * 1024 * 1024,
+            max_ratio: 100,
+            part_timeout: Duration::from_secs(2),
+            office: true,
+        }
+    }
+}
+
+/// Which limit inspecting an attachment ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    /// The attachment is larger than `max_bytes`
+    Size,
+    /// Its archive members decompress to more than `max_decompressed`
+    Decompressed,
+    /// An archive member expands beyond `max_ratio`: a decompression bomb
+    CompressionRatio,
+    /// Inspection took longer than `part_timeout`
+    Timeout,
+}
+
+/// A limit an attachment's inspection ran into; inspection stopped there
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LimitViolation {
+    pub kind: LimitKind,
+    pub detail: String,
+}
+
+impl LimitViolation {
+    fn new(kind: LimitKind, detail: impl ToString) -> Self {
+        Self {
+            kind,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+/// A file attached to a message
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Attachment {
+    pub filename: Option<String>,
+    /// MIME type, lowercase
+    pub content_type: String,
+    /// Bytes after transfer decoding
+    pub size: usize,
+    /// SHA-256 of the decoded content, hex; `None` when a limit stopped inspection first
+    pub sha256: Option<String>,
+    /// The members of a zip, RAR, or 7z archive
+    pub archive: Option<ArchiveListing>,
+    /// Macros, external references, and DDE of an Office document
+    pub office: Option<OfficeIndicators>,
+    /// Links, JavaScript, automatic actions, and embedded files of a PDF
+    pub pdf: Option<PdfIndicators>,
+    /// Base64 payloads and file-building script calls of a web page
+    pub html: Option<HtmlIndicators>,
+    /// Limits the inspection ran into
+    pub violations: Vec<LimitViolation>,
+}
+
+/// Lists and inspects the attachments of a parsed message within `limits`
+///
+/// Attachments are the leaf parts that have a file name, are marked as attachments,
+/// or are not text. Each is hashed, archives are listed (see [`list_archive`]), Office
+/// documents, PDFs, and web pages are searched for active content (see
+/// [`inspect_office`], [`inspect_pdf`], and [`inspect_html`]), and zip
+/// and gzip files are decompressed to count their size without keeping the output. Whatever limit stops the inspection of a
+/// part is recorded in its `violations`; other parts are inspected regardless.
+pub fn inspect_attachments(mail: &ParsedMail, limits: &AttachmentLimits) -> Vec<Attachment> {
+    let mut attachments = Vec::new();
+    collect_attachments(mail, limits, &mut attachments);
+    attachments
+}
+
+fn collect_attachments(part: &ParsedMail, limits: &AttachmentLimits, out: &mut Vec<Attachment>) {
+    for sub in &part.subparts {
+        collect_attachments(sub, limits, out);
+    }
+    if !part.subparts.is_empty() {
+        return;
+    }
+    let disposition = part.get_content_disposition();
+    let filename = disposition
+        .params
+        .get("filename")
+        .or_else(|| part.ctype.params.get("name"))
+        .cloned();
+    let content_type = part.ctype.mimetype.to_ascii_lowercase();
+    let attached = disposition.disposition == mailparse::DispositionType::Attachment;
+    if filename.is_none() && !attached && content_type.starts_with("text/") {
+        return;
+    }
+    // Undecodable transfer encoding is already a parse anomaly; take the raw body
+    let data = part
+        .get_body_raw()
+        .unwrap_or_else(|_| part.raw_bytes.to_vec());
+    let (sha256, violations) = inspect(&data, filename.as_deref(), &content_type, limits);
+    let html = (is_html_attachment(filename.as_deref(), &content_type)
+        && data.len() <= limits.max_bytes)
+        .then(|| inspect_html(&data));
+    let (archive, office, pdf) = if data.len() <= limits.max_bytes {
+        let office = limits
+            .office
+            .then(|| inspect_office(&data, limits.max_decompressed))
+            .flatten();
+        (
+            list_archive(&data, limits.max_decompressed),
+            office,
+            inspect_pdf(&data, limits.max_decompressed),
+        )
+    } else {
+        (None, None, None)
+    };
+    out.push(Attachment {
+        filename,
+        content_type,
+        size: data.len(),
+        sha256,
+        archive,
+        office,
+        pdf,
+        html,
+        violations,
+    });
+}
+
+/// Hashes `data` and decompresses it when it is an archive, within `limits`
+fn inspect(
+    data: &[u8],
+    filename: Option<&str>,
+    content_type: &str,
+    limits: &AttachmentLimits,
+) -> (Option<String>, Vec<LimitViolation>) {
+    if data.len() > limits.max_bytes {
+        let violation = LimitViolation::new(
+            LimitKind::Size,
+            format!("{} bytes, limit {}", data.len(), limits.max_bytes),
+        );
+        return (None, vec![violation]);
+    }
+    let mut budget = Budget::new(limits);
+    let mut hasher = Sha256::new();
+    for chunk in data.chunks(CHUNK) {
+        if let Err(violation) = budget.check_time() {
+            return (None, vec![violation]);
+        }
+        hasher.update(chunk);
+    }
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let name = filename.unwrap_or_default().to_ascii_lowercase();
+    let result = if data.starts_with(b"PK\x03\x04") {
+        inspect_zip(data, &mut budget)
+    } else if data.starts_with(&[0x1f, 0x8b])
+        || name.ends_with(".gz")
+        || content_type.ends_with("gzip")
+    {
+        budget
+            .decompress(MultiGzDecoder::new(data), data.len() as u64, &name)
+            .map(|_| ())
+    } else {
+        Ok(())
+    };
+    (Some(sha256), result.err().into_iter().collect())
+}
+
+/// Decompresses the members of a zip archive, found through its central directory;
+/// encrypted members and unknown compression methods are skipped
+fn inspect_zip(data: &[u8], budget: &mut Budget) -> Result<(), LimitViolation> {
+    for member in zip_members(data) {
+        if member.encrypted {
+            continue;
+        }
+        let Some(compressed) = data.get(member.data_offset..) else {
+            continue;
+        };
+        let compressed = &compressed[..compressed.len().min(member.compressed_size as usize)];
+        match member.method {
+            0 => budget.decompress(compressed, compressed.len() as u64, &member.name)?,
+            8 => budget.decompress(
+                DeflateDecoder::new(compressed),
+                compressed.len() as u64,
+                &member.name,
+            )?,
+            _ => 0,
+        };
+    }
+    Ok(())
+}
+
+/// What inspecting one attachment may still spend
+struct Budget<'a> {
+    limits: &'a AttachmentLimits,
+    deadline: Instant,
+    decompressed: u64,
+}
+
+impl<'a> Budget<'a> {
+    fn new(limits: &'a AttachmentLimits) -> Self {
+        Self {
+            limits,
+            deadline: Instant::now() + limits.part_timeout,
+            decompressed: 0,
+        }
+    }
+
+    fn check_time(&self) -> Result<(), LimitViolation> {
+        if Instant::now() > self.deadline {
+            return Err(LimitViolation::new(
+                LimitKind::Timeout,
+                format!("over {} ms", self.limits.part_timeout.as_millis()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads `reader` to the end, discarding the output, and returns the bytes read
+    ///
+    /// `compressed` is the input size `name` decompresses from.
+    fn decompress(
+        &mut self,
+        mut reader: impl Read,
+        compressed: u64,
+        name: &str,
+    ) -> Result<u64, LimitViolation> {
+        let mut buf = vec![0; CHUNK];
+        let mut total = 0u64;
+        loop {
+            self.check_time()?;
+            // Corrupt data ends the member; what was read so far still counts
+            let read = match reader.read(&mut buf) {
+                Ok(0) | Err(_) => return Ok(total),
+                Ok(read) => read as u64,
+            };
+            total += read;
+            self.decompressed += read;
+            if self.decompressed > self.limits.max_decompressed {
+                return Err(LimitViolation::new(
+                    LimitKind::Decompressed,
+                    format!(
+                        "more than {} bytes decompressed",
+                        self.limits.max_decompressed
+                    ),
+                ));
+            }
+            if total > MIN_BOMB_BYTES
+                && total > compressed.max(1).saturating_mul(self.limits.max_ratio)
+            {
+                return Err(LimitViolation::new(
+                    LimitKind::CompressionRatio,
+                    format!(
+                        "{} expands more than {}-fold from {} bytes",
+                        if name.is_empty() { "content" } else { name },
+                        self.limits.max_ratio,
+                        compressed
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{AttachmentLimits, LimitKind, inspect_attachments};
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use flate2::{Compression, write::GzEncoder};
+
+    fn message(filename: &str, content: &[u8]) -> Vec<u8> {
+        format!(
+            "From: a@example.com\r\nMIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\nContent-Type: text/plain\r\n\r\nSee attached\r\n--b\r\nContent-Type: application/octet-stream\r\nContent-Disposition: attachment; filename=\"{}\"\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n--b--\r\n",
+            filename,
+            STANDARD.encode(content)
+        )
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_inspect_attachments() {
+        let raw = message("invoice.pdf", b"%PDF-1.4");
+        let mail = mailparse::parse_mail(&raw).unwrap();
+        let attachments = inspect_attachments(&mail, &AttachmentLimits::default());
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].filename.as_deref(), Some("invoice.pdf"));
+        assert_eq!(attachments[0].size, 8);
+        assert_eq!(attachments[0].sha256.as_ref().unwrap().len(), 64);
+        assert!(attachments[0].violations.is_empty());
+
+        // 16 MiB of zeros gzip to about 16 KiB
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&vec![0; 16 * 1024 * 1024]).unwrap();
+        let raw = message("data.gz", &gz.finish().unwrap());
+        let mail = mailparse::parse_mail(&raw).unwrap();
+        let attachments = inspect_attachments(&mail, &AttachmentLimits::default());
+        assert_eq!(
+            attachments[0].violations[0].kind,
+            LimitKind::CompressionRatio
+        );
+
+        let limits = AttachmentLimits {
+            max_bytes: 4,
+            ..AttachmentLimits::default()
+        };
+        let raw = message("invoice.pdf", b"%PDF-1.4");
+        let mail = mailparse::parse_mail(&raw).unwrap();
+        let attachments = inspect_attachments(&mail, &limits);
+        assert_eq!(attachments[0].violations[0].kind, LimitKind::Size);
+        assert_eq!(attachments[0].sha256, None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+
+/// One change to the running configuration
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    /// Unix time of the change
+    pub at: i64,
+    /// Name of the admin token used
+    pub actor: String,
+    /// What was done, e.g. `reload` or `list_add`
+    pub action: String,
+    /// Action-specific details, e.g. the list entries added
+    pub detail: serde_json::Value,
+}
+
+/// Append-only log of configuration changes
+///
+/// Entries are appended as JSON lines to the log file, or kept in memory when there is
+/// none. Existing entries are never rewritten.
+pub struct AuditLog {
+    path: Option<String>,
+    /// Serializes appends; holds the entries when there is no file
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Appends an entry, returning once it is written to disk
+    pub fn record(
+        &self,
+        actor: &str,
+        action: &str,
+        detail: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let entry = AuditEntry {
+            at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            actor: actor.to_string(),
+            action: action.to_string(),
+            detail,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        match &self.path {
+            Some(path) => append_json_line(path, &entry)?,
+            None => entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> anyhow::Result<Vec<AuditEntry>> {
+        let entries = self.entries.lock().unwrap();
+        match &self.path {
+            Some(path) => read_json_lines(path),
+            None => Ok(entries.clone()),
+        }
+    }
+}
+
+/// Appends `value` as one JSON line, returning once it is written to disk
+pub(crate) fn append_json_line(path: &str, value: &impl serde::Serialize) -> anyhow::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(value)?)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Reads a file of JSON lines; a missing file has none
+pub(crate) fn read_json_lines<T: serde::de::DeserializeOwned>(
+    path: &str,
+) -> anyhow::Result<Vec<T>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    std::io::BufReader::new(file)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuditLog;
+    use serde_json::json;
+
+    #[test]
+    fn test_audit_log_appends_to_file() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let _ = std::fs::remove_file(&path);
+
+        let log = AuditLog::new(Some(path.clone()));
+        assert!(log.entries().unwrap().is_empty());
+        log.record(
+            "alice",
+            "list_add",
+            json!({"list": "blocklist", "entries": ["evil.test"]}),
+        )
+        .unwrap();
+        log.record("bob", "reload", json!({})).unwrap();
+
+        // A restarted service sees the earlier entries
+        let entries = AuditLog::new(Some(path.clone())).entries().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].actor, "alice");
+        assert_eq!(entries[0].detail["entries"][0], "evil.test");
+        assert_eq!(entries[1].action, "reload");
+    }
+}
@@ -0,0 +1,107 @@
+use crate::email_verdict::{AnalysisResult, Verdict};
+
+/// Renders `result` as an RFC 8601 `Authentication-Results` header field, naming us
+/// `authserv_id`
+///
+/// The field has no trailing CRLF.
+pub fn authentication_results(result: &AnalysisResult, authserv_id: &str) -> String {
+    format!(
+        "Authentication-Results: {}; {}",
+        authserv_id,
+        render_results(result)
+    )
+}
+
+/// The results of `result` as RFC 8601 method results, e.g.
+/// `spf=pass; dkim=none; dmarc=fail header.from=example.com`
+///
+/// Results of a trusted upstream MTA are passed on as recorded. Our own DKIM check only
+/// sees that a signature is present, so signed messages get `dkim=neutral`. Our verdict
+/// and risk score follow as `x-esd-verdict`.
+pub fn render_results(result: &AnalysisResult) -> String {
+    let evidence = &result.evidence;
+
+    let (spf, dkim, dmarc) = match &evidence.upstream_auth {
+        Some(upstream) => {
+            let recorded = |value: &Option<String>| value.clone().unwrap_or("none".to_string());
+            (
+                recorded(&upstream.spf),
+                recorded(&upstream.dkim),
+                recorded(&upstream.dmarc),
+            )
+        }
+        None => {
+            let spf = match (&evidence.spf_policy, evidence.spf_authorized) {
+                (None, _) => "none",
+                (Some(_), true) => "pass",
+                (Some(_), false) => "neutral",
+            };
+            let dkim = match evidence.dkim_present {
+                false => "none",
+                true => "neutral (signature not verified)",
+            };
+            let dmarc = match (&evidence.dmarc_policy, evidence.alignment_ok) {
+                (None, _) => "none",
+                (Some(_), true) => "pass",
+                (Some(_), false) => "fail",
+            };
+            (spf.to_string(), dkim.to_string(), dmarc.to_string())
+        }
+    };
+
+    let dmarc = match &evidence.from_domain {
+        Some(from) => format!("dmarc={} header.from={}", dmarc, from),
+        None => format!("dmarc={}", dmarc),
+    };
+    let verdict = format!(
+        "x-esd-verdict={} (risk score {})",
+        verdict_keyword(&result.verdict),
+        result.risk_score
+    );
+    [
+        format!("spf={}", spf),
+        format!("dkim={}", dkim),
+        dmarc,
+        verdict,
+    ]
+    .join("; ")
+}
+
+/// The verdict as an RFC 8601 result keyword
+fn verdict_keyword(verdict: &Verdict) -> &'static str {
+    match verdict {
+        Verdict::Authenticated => "authenticated",
+        Verdict::PolicyViolation => "policy-violation",
+        Verdict::Unauthenticated => "unauthenticated",
+        Verdict::Suspicious => "suspicious",
+        Verdict::Indeterminate => "indeterminate",
+        Verdict::Automated => "automated",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::authentication_results;
+    use crate::{dns::DnsSnapshot, email_verdict::analyze_email, parse::parse_email};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_authentication_results() {
+        let snapshot: DnsSnapshot = serde_json::from_value(json!({
+            "domains": {"example.com": {"spf": "v=spf1 -all", "dmarc": "v=DMARC1; p=reject", "exists": true}}
+        }))
+        .unwrap();
+        let parsed = parse_email(b"From: ceo@example.com\r\n").unwrap();
+        let result = analyze_email(&parsed, &snapshot).await.unwrap();
+
+        assert_eq!(
+            authentication_results(&result, "mx.example.org"),
+            format!(
+                "Authentication-Results: mx.example.org; spf=pass; dkim=none; \
+                 dmarc=pass header.from=example.com; x-esd-verdict=suspicious \
+                 (risk score {})",
+                result.risk_score
+            )
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use crate::parse::EmailParsed;
+
+/// What kind of machine-generated mail a message is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomatedKind {
+    /// A delivery status notification: null envelope sender or a `delivery-status` report
+    Bounce,
+    /// An out-of-office or other automatic reply (`Auto-Submitted: auto-replied`)
+    AutoReply,
+    /// Other mail a system generated on its own (`Auto-Submitted: auto-generated`,
+    /// `X-Auto-Response-Suppress`)
+    AutoGenerated,
+}
+
+/// A message sent by a machine rather than a person, with the headers that tell
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct AutomatedMessage {
+    pub kind: AutomatedKind,
+    /// The signals found, e.g. `Auto-Submitted: auto-replied` or `Return-Path: <>`
+    pub signals: Vec<String>,
+}
+
+/// Detects bounces and automatic replies (RFC 3834, RFC 3464), which legitimately fail
+/// many sender checks: DSNs have no envelope sender, and auto-replies often come from
+/// systems outside the domain's SPF
+pub fn detect_automated(parsed: &EmailParsed) -> Option<AutomatedMessage> {
+    let mut signals = Vec::new();
+    let mut kind = None;
+
+    let null_sender = parsed.return_path.as_deref().is_some_and(|path| {
+        path.trim()
+            .trim_start_matches('<')
+            .trim_end_matches('>')
+            .trim()
+            .is_empty()
+    });
+    if null_sender {
+        signals.push("Return-Path: <>".to_string());
+        kind = Some(AutomatedKind::Bounce);
+    }
+    if let Some(content_type) = &parsed.content_type {
+        let lower = content_type.to_ascii_lowercase();
+        if lower.contains("multipart/report") && lower.contains("delivery-status") {
+            signals.push(format!("Content-Type: {}", content_type));
+            kind = Some(AutomatedKind::Bounce);
+        }
+    }
+
+    if let Some(value) = &parsed.auto_submitted {
+        // `auto-replied; owner-email="a@example.com"` or `auto-replied (vacation)`
+        let keyword = value
+            .split([';', '('])
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if keyword != "no" {
+            signals.push(format!("Auto-Submitted: {}", value));
+            kind = kind.or(Some(match keyword.as_str() {
+                "auto-replied" => AutomatedKind::AutoReply,
+                _ => AutomatedKind::AutoGenerated,
+            }));
+        }
+    }
+    if parsed
+        .header_names
+        .iter()
+        .any(|name| name == "x-auto-response-suppress")
+    {
+        signals.push("X-Auto-Response-Suppress".to_string());
+        kind = kind.or(Some(AutomatedKind::AutoGenerated));
+    }
+
+    kind.map(|kind| AutomatedMessage { kind, signals })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutomatedKind, detect_automated};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_detect_automated() {
+        let detect = |headers: &str| {
+            let raw = format!("From: a@example.com\r\n{}\r\n\r\nbody", headers);
+            detect_automated(&parse_email(raw.as_bytes()).unwrap()).map(|m| m.kind)
+        };
+
+        assert_eq!(
+            detect(
+                "Return-Path: <>\r\nContent-Type: multipart/report; report-type=delivery-status; boundary=x"
+            ),
+            Some(AutomatedKind::Bounce)
+        );
+        assert_eq!(
+            detect("Auto-Submitted: auto-replied (vacation)"),
+            Some(AutomatedKind::AutoReply)
+        );
+        assert_eq!(
+            detect("X-Auto-Response-Suppress: All"),
+            Some(AutomatedKind::AutoGenerated)
+        );
+        assert_eq!(detect("Auto-Submitted: no"), None);
+        assert_eq!(detect("Return-Path: <bounce@example.com>"), None);
+    }
+}
@@ -0,0 +1,138 @@
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use tokio::sync::mpsc;
+
+use crate::mbox::{MboxReader, is_mbox};
+use crate::parse::{EmailParsed, parse_email};
+
+/// Messages analyzed at once by default
+pub const DEFAULT_BATCH_CONCURRENCY: usize = 16;
+
+/// Messages read and parsed ahead of their analysis by default
+pub const DEFAULT_READ_AHEAD: usize = 64;
+
+/// One message of a batch, parsed ahead of its analysis
+pub struct BatchMessage {
+    /// File the message came from, with `#n` appended for the nth message of an mbox file
+    pub source: String,
+    /// The parsed message, or why it could not be read or parsed
+    pub parsed: anyhow::Result<EmailParsed>,
+}
+
+/// Files of a batch: files as-is and the files of directories, one level deep, in name
+/// order
+pub fn batch_files(paths: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if std::fs::metadata(path)?.is_dir() {
+            let mut entries: Vec<_> = std::fs::read_dir(path)?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<Result<_, _>>()?;
+            entries.sort();
+            files.extend(entries.into_iter().filter(|p| p.is_file()));
+        } else {
+            files.push(path.into());
+        }
+    }
+    Ok(files)
+}
+
+/// Reads and parses the messages of `files`, in order, at most `read_ahead` messages
+/// ahead of the receiver
+///
+/// Reading and parsing run on a blocking thread, apart from the analyses and their DNS
+/// lookups. Files are opened one at a time and mbox files are split as they are read,
+/// so a sweep of any size holds one file open and `read_ahead` messages in memory.
+/// Dropping the receiver stops the reading.
+pub fn read_batch(files: Vec<PathBuf>, read_ahead: usize) -> mpsc::Receiver<BatchMessage> {
+    let (sender, receiver) = mpsc::channel(read_ahead.max(1));
+    tokio::task::spawn_blocking(move || {
+        for file in files {
+            if !read_file(&file, &sender) {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// Sends the messages of `file`; `false` once the receiver is gone
+fn read_file(file: &Path, sender: &mpsc::Sender<BatchMessage>) -> bool {
+    let name = file.display().to_string();
+    let send = |source, raw: io::Result<Vec<u8>>| {
+        let parsed = raw.map_err(Into::into).and_then(|raw| parse_email(&raw));
+        sender
+            .blocking_send(BatchMessage { source, parsed })
+            .is_ok()
+    };
+    let mut reader = match std::fs::File::open(file) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => return send(name, Err(e)),
+    };
+    let mbox = match reader.fill_buf() {
+        Ok(start) => is_mbox(start),
+        Err(e) => return send(name, Err(e)),
+    };
+    if !mbox {
+        let mut raw = Vec::new();
+        let read = reader.read_to_end(&mut raw).map(|_| raw);
+        return send(name, read);
+    }
+    for (i, message) in MboxReader::new(reader).enumerate() {
+        // The rest of a file that failed to read is lost
+        let failed = message.is_err();
+        if !send(format!("{}#{}", name, i + 1), message) {
+            return false;
+        }
+        if failed {
+            break;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{batch_files, read_batch};
+
+    #[tokio::test]
+    async fn test_read_batch() {
+        let dir = std::env::temp_dir().join(format!("batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.eml"),
+            "From: a@example.com\r\nSubject: one\r\n\r\nHi",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("b.mbox"),
+            "From a@example.com Thu Jan  1 00:00:00 2026\nFrom: a@example.com\nSubject: two\n\n\
+             >From the start\n\nFrom b@example.org Thu Jan  1 00:00:01 2026\n\
+             From: b@example.org\nSubject: three\n\nbody\n",
+        )
+        .unwrap();
+
+        let mut files = batch_files(&[dir.display().to_string()]).unwrap();
+        files.push(dir.join("missing.eml"));
+        let mut messages = read_batch(files, 1);
+        let mut read = Vec::new();
+        while let Some(message) = messages.recv().await {
+            let subject = message.parsed.ok().and_then(|parsed| parsed.subject);
+            let source = message.source.rsplit('/').next().unwrap().to_string();
+            read.push((source, subject));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let subject = |s: &str| Some(s.to_string());
+        assert_eq!(
+            read,
+            [
+                ("a.eml".to_string(), subject("one")),
+                ("b.mbox#1".to_string(), subject("two")),
+                ("b.mbox#2".to_string(), subject("three")),
+                ("missing.eml".to_string(), None),
+            ]
+        );
+    }
+}
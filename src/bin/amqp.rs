@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use email_spoof_detector::{
+    analytics::{AnalyticsRow, AnalyticsSink},
+    analyzer::{AnalysisHandle, Analyzer, Cancelled, cancellable},
+    attachments::AttachmentLimits,
+    config::{
+        analytics_sink_from_env, attachment_limits_from_env, env_list, env_number,
+        resolver_from_env, result_signer_from_env, service_analysis_options,
+        service_config_from_env,
+    },
+    dedup::{DedupCache, message_hash},
+    dns::DnsResolver,
+    integrity::{ResultSigner, seal},
+    parse::parse_email_with_limits,
+};
+use env_logger::Env;
+use futures::StreamExt;
+use lapin::{
+    BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind,
+    message::Delivery,
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicNackOptions, BasicPublishOptions,
+        BasicQosOptions, ExchangeDeclareOptions, QueueDeclareOptions,
+    },
+    types::FieldTable,
+};
+
+/// AMQP settings, read from the environment
+struct Settings {
+    url: String,
+    queue: String,
+    exchange: String,
+    routing_key: String,
+    /// Unacknowledged deliveries in flight; the broker stops delivering beyond this
+    prefetch: u16,
+    /// Signs the digest of published results
+    signer: Option<ResultSigner>,
+    /// Receives a row per result (`ANALYTICS_URL`)
+    analytics: Option<AnalyticsSink>,
+    /// Resources inspecting each attachment may take
+    attachments: AttachmentLimits,
+}
+
+impl Settings {
+    fn from_env() -> anyhow::Result<Self> {
+        let var =
+            |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.into());
+        Ok(Self {
+            url: var("AMQP_URL", "amqp://127.0.0.1:5672/%2f"),
+            queue: var("AMQP_QUEUE", "raw-emails"),
+            exchange: var("AMQP_RESULT_EXCHANGE", "email-verdicts"),
+            routing_key: var("AMQP_RESULT_ROUTING_KEY", "verdict"),
+            prefetch: env_number("AMQP_PREFETCH", 16).max(1),
+            signer: result_signer_from_env()?,
+            analytics: analytics_sink_from_env()?,
+            attachments: attachment_limits_from_env(),
+        })
+    }
+}
+
+/// What became of a delivery
+enum Outcome {
+    Published,
+    Unparseable,
+}
+
+/// Running analyses by delivery tag, cancelled on shutdown
+type InFlight = Mutex<HashMap<u64, AnalysisHandle>>;
+
+/// Analyzes one delivery and publishes its result to the exchange
+async fn process(
+    delivery: &Delivery,
+    channel: &Channel,
+    settings: &Settings,
+    analyzer: &Analyzer<DnsResolver>,
+    dedup: Option<&DedupCache>,
+    in_flight: &InFlight,
+) -> anyhow::Result<Outcome> {
+    let parsed = match parse_email_with_limits(&delivery.data, &settings.attachments) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            log::warn!("Rejecting unparseable message: {}", e);
+            return Ok(Outcome::Unparseable);
+        }
+    };
+
+    let cached = dedup.map(|cache| (cache, message_hash(&parsed)));
+    let mut result = match cached.as_ref().and_then(|(cache, key)| cache.get(key)) {
+        Some(result) => result,
+        None => {
+            let (handle, analysis) = cancellable(analyzer.analyze(&parsed));
+            let tag = delivery.delivery_tag;
+            in_flight.lock().unwrap().insert(tag, handle);
+            let analysis = analysis.await;
+            in_flight.lock().unwrap().remove(&tag);
+            let mut result = analysis??;
+            result.rescore();
+            let result = serde_json::to_value(&result)?;
+            if let Some((cache, key)) = cached {
+                cache.insert(key, result.clone());
+            }
+            result
+        }
+    };
+    if let Some(analytics) = &settings.analytics {
+        analytics.send(AnalyticsRow::new("default", &result));
+    }
+    seal(&mut result, settings.signer.as_ref());
+
+    let mut properties = BasicProperties::default().with_content_type("application/json".into());
+    if let Some(id) = delivery.properties.message_id() {
+        properties = properties.with_correlation_id(id.clone());
+    }
+    channel
+        .basic_publish(
+            settings.exchange.as_str().into(),
+            settings.routing_key.as_str().into(),
+            BasicPublishOptions::default(),
+            &serde_json::to_vec(&result)?,
+            properties,
+        )
+        .await?
+        .await?;
+    Ok(Outcome::Published)
+}
+
+/// Acks published deliveries; unparseable ones are rejected without requeueing, so a
+/// dead-letter exchange configured on the queue receives them, and failures and
+/// analyses cancelled at shutdown are requeued
+async fn settle(delivery: &Delivery, outcome: anyhow::Result<Outcome>) -> lapin::Result<bool> {
+    let requeue = match outcome {
+        Ok(Outcome::Published) => return delivery.ack(BasicAckOptions::default()).await,
+        Ok(Outcome::Unparseable) => false,
+        Err(e) if e.is::<Cancelled>() => true,
+        Err(e) => {
+            log::error!("Failed to process delivery: {}", e);
+            true
+        }
+    };
+    delivery
+        .nack(BasicNackOptions {
+            requeue,
+            ..BasicNackOptions::default()
+        })
+        .await
+}
+
+/// Resolves on Ctrl-C or, on Unix, SIGTERM
+async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            signal = tokio::signal::ctrl_c() => signal,
+            _ = terminate.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+
+    let settings = Arc::new(Settings::from_env()?);
+    let analyzer = Analyzer::new(
+        resolver_from_env()?,
+        service_analysis_options(&service_config_from_env()?),
+    );
+    let prefetch = env_list("PREFETCH_DOMAINS");
+    if !prefetch.is_empty() {
+        let domains: Vec<&str> = prefetch.iter().map(String::as_str).collect();
+        analyzer.prefetch_domains(&domains).await;
+    }
+    let analyzer = Arc::new(analyzer);
+    let dedup_size = env_number("DEDUP_CACHE_SIZE", 0);
+    let dedup = Arc::new((dedup_size > 0).then(|| DedupCache::new(dedup_size)));
+
+    let connection = Connection::connect(&settings.url, ConnectionProperties::default()).await?;
+    let channel = connection.create_channel().await?;
+    channel
+        .basic_qos(settings.prefetch, BasicQosOptions::default())
+        .await?;
+    channel
+        .queue_declare(
+            settings.queue.as_str().into(),
+            QueueDeclareOptions {
+                durable: true,
+                ..QueueDeclareOptions::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    channel
+        .exchange_declare(
+            settings.exchange.as_str().into(),
+            ExchangeKind::Topic,
+            ExchangeDeclareOptions {
+                durable: true,
+                ..ExchangeDeclareOptions::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    let mut consumer = channel
+        .basic_consume(
+            settings.queue.as_str().into(),
+            "email-spoof-detector".into(),
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+    log::info!(
+        "Consuming from {} (prefetch {}), publishing to {}",
+        settings.queue,
+        settings.prefetch,
+        settings.exchange
+    );
+
+    // Deliveries are handled concurrently; prefetch bounds how many are in flight
+    let in_flight = Arc::new(InFlight::default());
+    let mut tasks = tokio::task::JoinSet::new();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        let delivery = tokio::select! {
+            delivery = consumer.next() => match delivery {
+                Some(delivery) => delivery?,
+                None => break,
+            },
+            signal = &mut shutdown => {
+                signal?;
+                let in_flight = in_flight.lock().unwrap();
+                log::info!("Shutting down, requeueing {} analyses", in_flight.len());
+                in_flight.values().for_each(AnalysisHandle::cancel);
+                break;
+            }
+        };
+        let (channel, settings) = (channel.clone(), settings.clone());
+        let (analyzer, dedup, in_flight) = (analyzer.clone(), dedup.clone(), in_flight.clone());
+        tasks.spawn(async move {
+            let outcome = process(
+                &delivery,
+                &channel,
+                &settings,
+                &analyzer,
+                dedup.as_ref().as_ref(),
+                &in_flight,
+            )
+            .await;
+            if let Err(e) = settle(&delivery, outcome).await {
+                log::error!("Failed to settle delivery: {}", e);
+            }
+        });
+        while tasks.try_join_next().is_some() {}
+    }
+
+    // Cancelled deliveries are settled before the connection closes
+    while tasks.join_next().await.is_some() {}
+    Ok(())
+}
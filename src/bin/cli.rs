@@ -1,18 +1,69 @@
-use clap::Parser;
-use email_spoof_detector::domain_verdict::{calculate_domain_verdict, resolve_dkim, resolve_spf_structured};
+use clap::{ArgGroup, Parser, Subcommand};
+use email_spoof_detector::domain_verdict::{
+    COMMON_DKIM_SELECTORS, calculate_domain_verdict, resolve_dkim, resolve_spf_structured,
+};
 use email_spoof_detector::{
-    dns::{DnsResolver, ResolverTrait},
-    email_verdict::analyze_email,
-    parse::parse_email,
+    arc::ArcSealer,
+    auth_results::{authentication_results, render_results},
+    batch::{DEFAULT_BATCH_CONCURRENCY, DEFAULT_READ_AHEAD, batch_files, read_batch},
+    brand_watch::{DEFAULT_CONCURRENCY, discover},
+    bundle::{BUNDLE_KEY_VAR, ConfigBundle, SignedBundle},
+    calibration::{Sample, calibrate, parse_manifest},
+    campaign::{CampaignMessage, campaigns_of},
+    checks::spf::explain_ip,
+    config::open_analysis_store,
+    ct::{self, CRT_SH_URL, CrtSh},
+    dangling::find_dangling,
+    diff::ResultDiff,
+    dkim_lint::lint_dkim,
+    dmarc_lint::lint_dmarc,
+    dns::{DnsResolver, DnsSnapshot, ResolverStats, ResolverTrait},
+    email_verdict::{
+        AnalysisDepth, AnalysisOptions, AnalysisResult, Verdict, analyze_email_at_depth,
+        analyze_email_with_options,
+    },
+    export::{ExportFormat, export},
+    feedback::{FeedbackLabel, FeedbackLog},
+    http::{EgressPolicy, HttpFetcher, ReqwestFetcher, client_builder},
+    integrity::{ResultSigner, seal, verify},
+    lists::SenderLists,
+    messages::Lang,
+    monitor::{MonitorState, check_domains, describe, send_alert},
+    normalize::{header_block, normalize},
+    offline::forbid_network,
+    parse::{EmailParsed, parse_email, parse_time},
+    passive_dns::{HttpPassiveDns, enrich},
+    redact::redact,
+    registration::{RDAP_URL, Rdap, RegistrationProvider, ReputationRules, evaluate_registration},
+    report::incident_markdown,
+    rules::RuleSettings,
+    spf_flatten::flatten_spf,
+    spf_lint::lint_spf,
+    store::{AnalysisStore, Retention, StoreQuery},
+    text_heuristics::PhraseList,
+    trust_store::TrustStore,
+    url_expand::{DEFAULT_MAX_HOPS, UrlExpander, expand_body_urls},
 };
+use futures::StreamExt;
 use serde_json::json;
+use std::io::Write;
+use std::sync::Arc;
 
 #[derive(Parser)]
+#[command(group(ArgGroup::new("message").args(["input", "headers_file"])))]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to .eml file (optional)
     #[arg(short, long)]
     input: Option<String>,
 
+    /// Path to a header block copied from a mail client ("Show original"), analyzed
+    /// headers-only
+    #[arg(long)]
+    headers_file: Option<String>,
+
     /// Domain to analyze (optional)
     #[arg(short, long)]
     domain: Option<String>,
@@ -20,29 +71,1086 @@ struct Cli {
     /// Output JSON
     #[arg(long)]
     json: bool,
+
+    /// Mask address local parts, link paths, and message content in JSON output, for sharing
+    #[arg(long)]
+    redact: bool,
+
+    /// Additional DKIM selector to audit in domain mode (repeatable)
+    #[arg(long = "dkim-selector")]
+    dkim_selectors: Vec<String>,
+
+    /// Print the networks the domain's SPF record authorizes, one CIDR per line
+    #[arg(long, requires = "domain", conflicts_with = "json")]
+    spf_ips: bool,
+
+    /// Explain which chain of SPF terms authorizes (or rejects) this IP for the domain
+    #[arg(long, requires = "domain", value_name = "IP")]
+    explain: Option<std::net::IpAddr>,
+
+    /// Compare the analysis against an earlier `--json` result saved in this file
+    #[arg(long)]
+    compare: Option<String>,
+
+    /// Language of the reason messages (en, de, fr)
+    #[arg(long, default_value = "en", value_parser = parse_lang)]
+    lang: Lang,
+
+    /// How thoroughly to analyze: headers-only, standard (adds the body), or deep (adds the enrichments enabled by flags)
+    #[arg(long, default_value = "deep", value_parser = parse_depth)]
+    depth: AnalysisDepth,
+
+    /// Evaluate time-based checks as of this time instead of now (Unix seconds,
+    /// `YYYY-MM-DDTHH:MM:SS`, or an RFC 2822 date), e.g. when replaying old incident mail
+    #[arg(long, value_parser = parse_as_of)]
+    as_of: Option<i64>,
+
+    /// Record the time spent on each check and class of DNS lookup
+    #[arg(long)]
+    timings: bool,
+
+    /// Forbid all network access (DNS, HTTP); requires --dns-snapshot
+    #[arg(long)]
+    offline: bool,
+
+    /// Analyze against the DNS answers recorded in this JSON file instead of live DNS
+    #[arg(long)]
+    dns_snapshot: Option<String>,
+
+    /// Produce byte-identical output for the same message and --dns-snapshot, e.g. for
+    /// snapshot tests or hashing as evidence; time-based checks run as of --as-of or,
+    /// without it, the message's Date
+    #[arg(long, requires = "dns_snapshot")]
+    deterministic: bool,
+
+    /// Sign the digest of the JSON result with this Ed25519 private key (PKCS#8 PEM),
+    /// for attaching results to cases; check with the `verify` subcommand
+    #[arg(long, requires = "json")]
+    signing_key: Option<String>,
+
+    /// Write an incident summary of the analyzed message, for tickets, to this Markdown file
+    #[arg(long, requires = "message")]
+    report_md: Option<String>,
+
+    /// Write the incident summary as PDF to this file
+    #[cfg(feature = "pdf")]
+    #[arg(long, requires = "message")]
+    report_pdf: Option<String>,
+
+    /// ARC-seal the analyzed message with this RSA private key (PEM), recording our
+    /// results for the relays behind us; the sealed copy goes to --arc-output
+    #[arg(long, requires_all = ["arc_domain", "arc_output"])]
+    arc_key: Option<String>,
+
+    /// Signing domain of the ARC set; publish the key at <selector>._domainkey.<domain>
+    #[arg(long)]
+    arc_domain: Option<String>,
+
+    /// Selector of the ARC key
+    #[arg(long, default_value = "arc")]
+    arc_selector: String,
+
+    /// authserv-id naming us in the recorded results; defaults to --arc-domain
+    #[arg(long)]
+    authserv_id: Option<String>,
+
+    /// Print only an Authentication-Results header field with our results, for
+    /// prepending to the message
+    #[arg(long, requires = "authserv_id")]
+    authentication_results: bool,
+
+    /// File to write the ARC-sealed message to
+    #[arg(long)]
+    arc_output: Option<String>,
+
+    /// Print resolver statistics (queries, failures, latency by record type) to stderr
+    #[arg(long)]
+    debug_dns: bool,
+
+    /// Trust Authentication-Results from this authserv-id instead of re-checking SPF/DKIM/DMARC (repeatable)
+    #[arg(long = "trust-authserv-id")]
+    trusted_authserv_ids: Vec<String>,
+
+    /// Protected brand domain; sender domains imitating it are reported (repeatable)
+    #[arg(long = "protected-domain")]
+    protected_domains: Vec<String>,
+
+    /// JSON registry of owned domains' sending infrastructure; mail claiming them from elsewhere is flagged
+    #[arg(long)]
+    trust_store: Option<String>,
+
+    /// JSON sender allowlist, blocklist, and VIP names
+    #[arg(long)]
+    sender_lists: Option<String>,
+
+    /// JSON rule settings: disabled rule IDs and per-domain suppressions
+    #[arg(long)]
+    rule_settings: Option<String>,
+
+    /// Look up Certificate Transparency logs (crt.sh) for detected lookalike domains
+    #[arg(long)]
+    ct_lookup: bool,
+
+    /// Follow redirects of body URLs (HEAD requests, no cookies) to find their landing domains
+    #[arg(long)]
+    expand_urls: bool,
+
+    /// Maximum redirects followed per body URL
+    #[arg(long, default_value_t = DEFAULT_MAX_HOPS)]
+    max_redirects: usize,
+
+    /// Check the body for urgency, payment, and credential-prompt phrases
+    #[arg(long)]
+    text_heuristics: bool,
+
+    /// JSON phrase list used by the text heuristics instead of the built-in one (implies --text-heuristics)
+    #[arg(long)]
+    phrases: Option<String>,
+
+    /// Passive DNS API base URL (COF output) used to check the sender domain's DNS history
+    #[arg(long)]
+    passive_dns_url: Option<String>,
+
+    /// API key for the passive DNS provider
+    #[arg(long)]
+    passive_dns_key: Option<String>,
+
+    /// Look up the sender domain's name servers and (via RDAP) registrar
+    #[arg(long)]
+    registration_lookup: bool,
+
+    /// RDAP base URL used for registrar lookups
+    #[arg(long, default_value = RDAP_URL)]
+    rdap_url: String,
+
+    /// JSON rules naming abused registrars and name server providers (implies --registration-lookup)
+    #[arg(long)]
+    reputation_rules: Option<String>,
+
+    /// Proxy for outbound HTTP (enrichments, webhooks): http, https, socks5, or socks5h URL, with optional credentials
+    #[arg(long, alias = "proxy")]
+    egress_proxy: Option<String>,
+
+    /// Host the enrichments may contact, including its subdomains; any host when unset (repeatable)
+    #[arg(long = "egress-allowed-host")]
+    egress_allowed_hosts: Vec<String>,
+
+    /// Timeout in seconds of every enrichment HTTP request
+    #[arg(long)]
+    egress_timeout: Option<u64>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Re-check owned domains on an interval and alert when their mail DNS records change
+    Monitor {
+        /// Domains to watch
+        #[arg(required = true)]
+        domains: Vec<String>,
+
+        /// Seconds between checks
+        #[arg(long, default_value_t = 3600)]
+        interval: u64,
+
+        /// File holding the last seen records of each domain
+        #[arg(long, default_value = "monitor-state.json")]
+        state: String,
+
+        /// Webhook (e.g. Slack incoming webhook) notified of changes
+        #[arg(long)]
+        webhook: Option<String>,
+
+        /// Check once and exit with status 2 if anything changed
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// List registered typo, homoglyph, and cousin domains of owned brand domains
+    BrandWatch {
+        /// Owned brand domains, e.g. example.com
+        #[arg(required = true)]
+        brands: Vec<String>,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Candidate domains resolved at once
+        #[arg(long, default_value_t = DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+    },
+
+    /// Analyze .eml files, directories of them, or mbox files and group the messages into campaigns
+    Batch {
+        /// Message files, directories, or mbox files
+        #[arg(required = true)]
+        paths: Vec<String>,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Smallest group of messages reported as a campaign
+        #[arg(long, default_value_t = 2)]
+        min_campaign_size: usize,
+
+        /// Messages analyzed at once
+        #[arg(long, default_value_t = DEFAULT_BATCH_CONCURRENCY)]
+        concurrency: usize,
+
+        /// Messages read and parsed ahead of their analysis
+        #[arg(long, default_value_t = DEFAULT_READ_AHEAD)]
+        read_ahead: usize,
+    },
+
+    /// Measure precision and recall of verdicts and rules on a directory of labeled
+    /// messages, and suggest adjustments
+    Calibrate {
+        /// Directory holding the messages and the manifest
+        dir: String,
+
+        /// CSV of `path,expected_verdict` lines, paths relative to the directory;
+        /// defaults to manifest.csv in it
+        #[arg(long)]
+        manifest: Option<String>,
+
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Export or import a signed bundle of the analysis configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Mark an analysis as a false positive or false negative
+    Feedback {
+        /// `id` of the analysis result
+        id: String,
+
+        /// false-positive or false-negative
+        #[arg(value_parser = parse_label)]
+        label: FeedbackLabel,
+
+        /// Why the verdict was wrong
+        #[arg(long)]
+        note: Option<String>,
+
+        /// Name recorded with the feedback
+        #[arg(long, default_value = "cli")]
+        analyst: String,
+
+        /// JSON-lines feedback file, shared with the web service's FEEDBACK_LOG
+        #[arg(long, default_value = "feedback.jsonl")]
+        log: String,
+    },
+
+    /// Rewrite a message in canonical form: unfolded, decoded headers and LF line endings
+    Normalize {
+        /// Message file
+        path: String,
+
+        /// Drop the headers added from the Received header `by` this host upwards
+        #[arg(long)]
+        cut_at: Option<String>,
+
+        /// File to write instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Apply a retention policy to a web service analysis store (ANALYSIS_STORE)
+    Prune {
+        /// Analysis store: a directory, sqlite:<path>, or a postgres:// URL
+        store: String,
+
+        /// Drop raw messages and DNS answers of analyses older than this many days
+        #[arg(long)]
+        raw_days: Option<u64>,
+
+        /// Delete analyses older than this many days
+        #[arg(long)]
+        days: Option<u64>,
+
+        /// Only prune the analyses of this tenant
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+
+    /// Export stored analyses (ANALYSIS_STORE) for offline analytics, oldest first
+    Export {
+        /// Analysis store: a directory, sqlite:<path>, or a postgres:// URL
+        store: String,
+
+        /// Only analyses from this time on (Unix seconds, `YYYY-MM-DDTHH:MM:SS`, or an
+        /// RFC 2822 date)
+        #[arg(long, value_parser = parse_as_of)]
+        since: Option<i64>,
+
+        /// Only analyses before this time
+        #[arg(long, value_parser = parse_as_of)]
+        until: Option<i64>,
+
+        /// Only analyses of this tenant
+        #[arg(long)]
+        tenant: Option<String>,
+
+        /// ndjson, or parquet in builds with the parquet feature
+        #[arg(long, default_value = "ndjson", value_parser = parse_export_format)]
+        format: ExportFormat,
+
+        /// Include the raw messages still kept, base64-encoded; decrypted with
+        /// $ANALYSIS_STORE_KEY
+        #[arg(long)]
+        raw: bool,
+
+        /// File to write instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Check that a `--json` result matches its digest and, given the key, its signature
+    Verify {
+        /// Result file
+        path: String,
+
+        /// Ed25519 public key (PEM) of the signer
+        #[arg(long)]
+        public_key: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Bundle the lists given by the top-level flags, signed with $CONFIG_BUNDLE_KEY
+    Export {
+        /// Bundle file to write instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+
+    /// Verify a bundle against $CONFIG_BUNDLE_KEY and write its files and config.env
+    Import {
+        /// Bundle file
+        bundle: String,
+
+        /// Directory receiving the files
+        #[arg(long, default_value = ".")]
+        dir: String,
+    },
+}
+
+fn parse_label(name: &str) -> Result<FeedbackLabel, String> {
+    FeedbackLabel::from_name(name).ok_or_else(|| {
+        format!(
+            "unknown label '{}' (expected false-positive or false-negative)",
+            name
+        )
+    })
+}
+
+fn parse_as_of(value: &str) -> Result<i64, String> {
+    parse_time(value).ok_or_else(|| format!("unrecognized time '{}'", value))
+}
+
+fn parse_export_format(name: &str) -> Result<ExportFormat, String> {
+    ExportFormat::from_name(name)
+        .ok_or_else(|| format!("unknown format '{}' (expected ndjson or parquet)", name))
+}
+
+fn parse_depth(name: &str) -> Result<AnalysisDepth, String> {
+    AnalysisDepth::from_name(name).ok_or_else(|| {
+        format!(
+            "unknown depth '{}' (expected headers-only, standard, or deep)",
+            name
+        )
+    })
+}
+
+fn parse_lang(tag: &str) -> Result<Lang, String> {
+    Lang::from_tag(tag)
+        .ok_or_else(|| format!("unsupported language '{}' (expected en, de, or fr)", tag))
+}
+
+fn print_diff(diff: &ResultDiff, json: bool) -> anyhow::Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(diff)?);
+    } else if diff.is_empty() {
+        println!("No changes");
+    } else {
+        for change in &diff.changes {
+            println!("  {}: {} -> {}", change.field, change.before, change.after);
+        }
+        for reason in &diff.reasons_added {
+            println!("  + {}", reason["key"].as_str().unwrap_or_default());
+        }
+        for reason in &diff.reasons_removed {
+            println!("  - {}", reason["key"].as_str().unwrap_or_default());
+        }
+    }
+    Ok(())
+}
+
+/// Runs the domain monitor until interrupted, or for a single check with `once`
+async fn monitor(
+    domains: &[String],
+    interval: u64,
+    state_path: &str,
+    webhook: Option<&str>,
+    once: bool,
+    proxy: Option<&str>,
+) -> anyhow::Result<()> {
+    let resolver = DnsResolver::new()?;
+    let client = client_builder(proxy)?.build()?;
+    let mut state = MonitorState::load(state_path)?;
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval.max(1)));
+
+    loop {
+        ticker.tick().await;
+        let changes = check_domains(&resolver, domains, &mut state).await;
+        state.save(state_path)?;
+
+        if !changes.is_empty() {
+            println!("{}", describe(&changes));
+            if let Some(url) = webhook
+                && let Err(e) = send_alert(&client, url, &changes).await
+            {
+                eprintln!("Warning: webhook alert failed: {}", e);
+            }
+        }
+
+        if once {
+            if !changes.is_empty() {
+                std::process::exit(2);
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// Prints the registered lookalikes of each brand
+async fn brand_watch(brands: &[String], json: bool, concurrency: usize) -> anyhow::Result<()> {
+    let resolver = DnsResolver::new()?;
+    let mut report = serde_json::Map::new();
+
+    for brand in brands {
+        let live = discover(&resolver, brand, concurrency).await;
+        if json {
+            report.insert(brand.clone(), serde_json::to_value(&live)?);
+            continue;
+        }
+
+        println!("{}: {} registered lookalike(s)", brand, live.len());
+        for candidate in &live {
+            println!(
+                "  {:<40} {:<10} {}",
+                candidate.lookalike.domain,
+                format!("{:?}", candidate.lookalike.kind).to_lowercase(),
+                if candidate.has_mx { "MX" } else { "" }
+            );
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    }
+    Ok(())
+}
+
+/// Analyzes every message of a batch and reports the campaigns among them
+///
+/// Messages are read and parsed ahead on a thread of their own and analyzed
+/// `concurrency` at a time. Each is reported, in order, as soon as it is analyzed, and
+/// only what campaigns need of it is kept, so memory stays flat over a whole mailbox;
+/// JSON output keeps the results for the report instead.
+#[allow(clippy::too_many_arguments)]
+async fn batch(
+    paths: &[String],
+    json: bool,
+    min_campaign_size: usize,
+    concurrency: usize,
+    read_ahead: usize,
+    options: &AnalysisOptions,
+    lang: Lang,
+    redacted: bool,
+) -> anyhow::Result<()> {
+    let resolver = DnsResolver::new()?;
+    let mut queue = read_batch(batch_files(paths)?, read_ahead);
+    let analyses = futures::stream::poll_fn(|cx| queue.poll_recv(cx))
+        .filter_map(|message| async move {
+            match message.parsed {
+                Ok(email) => Some((message.source, email)),
+                Err(e) => {
+                    eprintln!("Warning: skipping {}: {}", message.source, e);
+                    None
+                }
+            }
+        })
+        .map(|(source, email)| {
+            let resolver = &resolver;
+            async move {
+                let result = analyze_email_with_options(&email, resolver, options).await;
+                (source, email, result)
+            }
+        })
+        .buffered(concurrency.max(1));
+    let mut analyses = std::pin::pin!(analyses);
+
+    let mut out = std::io::BufWriter::new(std::io::stdout());
+    let mut analyzed: Vec<CampaignMessage> = Vec::new();
+    let mut messages = Vec::new();
+    while let Some((source, email, result)) = analyses.next().await {
+        let mut result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {}", source, e);
+                continue;
+            }
+        };
+        result.localize(lang);
+        analyzed.push(CampaignMessage::new(&email, &result));
+        if json {
+            messages.push(json!({ "source": source, "result": result }));
+        } else {
+            writeln!(
+                out,
+                "{:<50} {:<8} {:>3} {:?}",
+                source,
+                format!("{:?}", result.verdict),
+                result.risk_score,
+                result.evidence.from_domain
+            )?;
+        }
+    }
+
+    let mut found = campaigns_of(&analyzed);
+    found.retain(|c| c.size >= min_campaign_size);
+
+    if json {
+        let mut output = json!({ "messages": messages, "campaigns": found });
+        if redacted {
+            redact(&mut output);
+        }
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+    out.flush()?;
+
+    println!("{} message(s), {} campaign(s)", analyzed.len(), found.len());
+    for campaign in &found {
+        println!(
+            "  Campaign of {} messages: subject {:?}, max risk {} ({:?})",
+            campaign.size, campaign.subject, campaign.max_risk_score, campaign.severity
+        );
+        println!(
+            "    From domains: {}",
+            Vec::from_iter(campaign.from_domains.iter().cloned()).join(", ")
+        );
+        println!(
+            "    Sending IPs: {}",
+            Vec::from_iter(campaign.client_ips.iter().cloned()).join(", ")
+        );
+        println!(
+            "    URL domains: {}",
+            Vec::from_iter(campaign.url_domains.iter().cloned()).join(", ")
+        );
+        let verdicts: Vec<String> = campaign
+            .verdicts
+            .iter()
+            .map(|(verdict, count)| format!("{} {}", count, verdict))
+            .collect();
+        println!("    Verdicts: {}", verdicts.join(", "));
+    }
+    Ok(())
+}
+
+/// Analyzes the labeled messages of `dir` and reports how the results compare with the
+/// labels, against --dns-snapshot when given
+async fn calibrate_corpus(
+    cli: &Cli,
+    dir: &str,
+    manifest: Option<&str>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let dir = std::path::Path::new(dir);
+    let manifest_path = manifest.map_or_else(|| dir.join("manifest.csv"), Into::into);
+    let labeled = parse_manifest(&std::fs::read_to_string(&manifest_path)?)?;
+    let options = analysis_options(cli)?;
+    let results = match &cli.dns_snapshot {
+        Some(path) => {
+            let snapshot: DnsSnapshot = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            analyze_labeled(dir, &labeled, &snapshot, &options).await?
+        }
+        None => analyze_labeled(dir, &labeled, &DnsResolver::new()?, &options).await?,
+    };
+    let samples: Vec<Sample> = labeled
+        .iter()
+        .zip(&results)
+        .map(|((name, expected), result)| Sample {
+            name,
+            expected: *expected,
+            result,
+        })
+        .collect();
+    let calibration = calibrate(&samples);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&calibration)?);
+        return Ok(());
+    }
+    let score = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.2}", v));
+    println!(
+        "{} message(s), {} given their labeled verdict",
+        calibration.messages, calibration.correct
+    );
+    println!(
+        "{:<16} {:>8} {:>9} {:>9} {:>6}",
+        "Verdict", "labeled", "given", "precision", "recall"
+    );
+    for stats in &calibration.verdicts {
+        println!(
+            "{:<16} {:>8} {:>9} {:>9} {:>6}",
+            format!("{:?}", stats.verdict),
+            stats.expected,
+            stats.predicted,
+            score(stats.precision),
+            score(stats.recall)
+        );
+    }
+    println!(
+        "{:<32} {:>6} {:>9} {:>6}",
+        "Rule", "fired", "precision", "recall"
+    );
+    for rule in &calibration.rules {
+        println!(
+            "{:<32} {:>6} {:>9} {:>6}",
+            format!("{} {}", rule.rule_id, rule.key),
+            rule.fired,
+            score(Some(rule.precision)),
+            score(rule.recall)
+        );
+    }
+    for miss in &calibration.misclassified {
+        println!(
+            "Misclassified: {} labeled {:?}, given {:?} (risk {})",
+            miss.name, miss.expected, miss.verdict, miss.risk_score
+        );
+    }
+    for suggestion in &calibration.suggestions {
+        println!("Suggestion: {}", suggestion);
+    }
+    Ok(())
+}
+
+/// Analyzes each labeled message, in manifest order
+async fn analyze_labeled<R: ResolverTrait + Sync + Send>(
+    dir: &std::path::Path,
+    labeled: &[(String, Verdict)],
+    dns: &R,
+    options: &AnalysisOptions,
+) -> anyhow::Result<Vec<AnalysisResult>> {
+    let mut results = Vec::new();
+    for (path, _) in labeled {
+        let raw = std::fs::read(dir.join(path))
+            .map_err(|e| anyhow::anyhow!("Reading {}: {}", path, e))?;
+        results.push(analyze_email_with_options(&parse_email(&raw)?, dns, options).await?);
+    }
+    Ok(results)
+}
+
+/// Exports or imports a configuration bundle
+fn config(cli: &Cli, action: &ConfigAction) -> anyhow::Result<()> {
+    let key = std::env::var(BUNDLE_KEY_VAR)
+        .map_err(|_| anyhow::anyhow!("{} must hold the bundle signing key", BUNDLE_KEY_VAR))?;
+
+    match action {
+        ConfigAction::Export { output } => {
+            let rules = cli
+                .reputation_rules
+                .as_deref()
+                .map(ReputationRules::from_file)
+                .transpose()?;
+            let bundle = ConfigBundle {
+                proxy: cli.egress_proxy.clone(),
+                ..ConfigBundle::new(&analysis_options(cli)?, rules, cli.dkim_selectors.clone())
+            };
+            let json = serde_json::to_string_pretty(&bundle.sign(key.as_bytes())?)?;
+            match output {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+        }
+        ConfigAction::Import { bundle, dir } => {
+            let bundle = SignedBundle::from_file(bundle)?.verify(key.as_bytes())?;
+            for path in bundle.install(std::path::Path::new(dir))? {
+                println!("Wrote {}", path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Analysis options from the top-level flags
+fn analysis_options(cli: &Cli) -> anyhow::Result<AnalysisOptions> {
+    Ok(AnalysisOptions {
+        trusted_authserv_ids: cli.trusted_authserv_ids.clone(),
+        protected_domains: cli.protected_domains.clone(),
+        text_phrases: match &cli.phrases {
+            Some(path) => Some(PhraseList::from_file(path)?),
+            None => cli.text_heuristics.then(PhraseList::default),
+        },
+        trust_store: cli
+            .trust_store
+            .as_deref()
+            .map(TrustStore::from_file)
+            .transpose()?,
+        sender_lists: cli
+            .sender_lists
+            .as_deref()
+            .map(SenderLists::from_file)
+            .transpose()?,
+        rules: cli
+            .rule_settings
+            .as_deref()
+            .map(RuleSettings::from_file)
+            .transpose()?
+            .unwrap_or_default(),
+        as_of: cli.as_of,
+        timings: cli.timings,
+    })
+}
+
+/// Applies the enrichments enabled by flags to a live analysis
+async fn enrich_result(
+    cli: &Cli,
+    result: &mut AnalysisResult,
+    parsed: &EmailParsed,
+    resolver: &DnsResolver,
+    options: &AnalysisOptions,
+) -> anyhow::Result<()> {
+    let fetcher: Arc<dyn HttpFetcher> = Arc::new(ReqwestFetcher::new(EgressPolicy {
+        proxy: cli.egress_proxy.clone(),
+        allowed_hosts: cli.egress_allowed_hosts.clone(),
+        timeout: cli.egress_timeout.map(std::time::Duration::from_secs),
+    })?);
+
+    // Optional enrichment: sender domain DNS history
+    if let (Some(url), Some(domain)) = (&cli.passive_dns_url, &result.evidence.from_domain) {
+        let provider = HttpPassiveDns::new(url, cli.passive_dns_key.clone(), fetcher.clone());
+        match enrich(&provider, domain, parsed.date_timestamp(), cli.as_of).await {
+            Ok(findings) => result.evidence.passive_dns = Some(findings),
+            Err(e) => eprintln!("Warning: passive DNS lookup failed: {}", e),
+        }
+    }
+
+    // Optional enrichment: name servers and registrar of the sender domain
+    if (cli.registration_lookup || cli.reputation_rules.is_some())
+        && let Some(domain) = result.evidence.from_domain.clone()
+    {
+        let rules = match &cli.reputation_rules {
+            Some(path) => ReputationRules::from_file(path)?,
+            None => ReputationRules::default(),
+        };
+        let rdap = Rdap::new(&cli.rdap_url, fetcher.clone());
+        let registrar = match rdap.registrar(&domain).await {
+            Ok(info) => Some(info),
+            Err(e) => {
+                eprintln!("Warning: RDAP lookup failed: {}", e);
+                None
+            }
+        };
+        let nameservers = resolver.ns_hosts(&domain).await;
+        result.evidence.registration = Some(evaluate_registration(nameservers, registrar, &rules));
+    }
+
+    // Optional enrichment: certificates issued for a lookalike domain
+    if cli.ct_lookup
+        && let Some(lookalike) = result.evidence.lookalike.as_mut()
+    {
+        let provider = CrtSh::new(CRT_SH_URL, fetcher.clone());
+        let date = parsed.date_timestamp();
+        match ct::enrich(&provider, &lookalike.domain, date, cli.as_of).await {
+            Ok(findings) => lookalike.certificates = Some(findings),
+            Err(e) => eprintln!("Warning: CT log lookup failed: {}", e),
+        }
+    }
+
+    // Optional enrichment: landing domains of shortened/redirecting body URLs
+    if cli.expand_urls {
+        let expander = UrlExpander::new(cli.max_redirects, fetcher.clone());
+        expand_body_urls(
+            &expander,
+            &mut result.evidence.body,
+            &options.protected_domains,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Writes `raw` to --arc-output with an ARC set recording `result`
+fn seal_message(cli: &Cli, key: &str, raw: &[u8], result: &AnalysisResult) -> anyhow::Result<()> {
+    let domain = cli.arc_domain.as_deref().unwrap_or_default();
+    let authserv_id = cli.authserv_id.as_deref().unwrap_or(domain);
+    let pem = std::fs::read_to_string(key)?;
+    let sealer = ArcSealer::new(domain, &cli.arc_selector, authserv_id, &pem)?;
+    let results = render_results(result);
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs() as i64;
+    let output = cli.arc_output.as_deref().unwrap_or_default();
+    std::fs::write(output, sealer.seal(raw, &results, now)?)?;
+    Ok(())
+}
+
+/// Prints resolver statistics to stderr, keeping stdout parseable
+fn print_dns_stats(stats: &ResolverStats) {
+    eprintln!("DNS nameservers: {}", stats.nameservers.join(", "));
+    for (record_type, query) in &stats.queries {
+        eprintln!(
+            "DNS {}: {} queries, {} failed, {:.1} ms avg, {:.1} ms max",
+            record_type,
+            query.count,
+            query.errors,
+            query.seconds_sum * 1000.0 / query.count.max(1) as f64,
+            query.seconds_max * 1000.0
+        );
+    }
+    for (cause, count) in &stats.errors {
+        eprintln!("DNS failures ({}): {}", cause, count);
+    }
+    for backend in stats
+        .backends
+        .iter()
+        .filter(|b| !b.healthy || b.failures > 0)
+    {
+        eprintln!(
+            "DNS backend {}: {}, {} failures",
+            backend.name,
+            if backend.healthy { "up" } else { "down" },
+            backend.failures
+        );
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    if cli.offline {
+        forbid_network();
+    }
 
-    // Require at least --input or --domain
-    if cli.input.is_none() && cli.domain.is_none() {
+    if let Some(Command::Monitor {
+        domains,
+        interval,
+        state,
+        webhook,
+        once,
+    }) = &cli.command
+    {
+        let proxy = cli.egress_proxy.as_deref();
+        return monitor(domains, *interval, state, webhook.as_deref(), *once, proxy).await;
+    }
+    if let Some(Command::BrandWatch {
+        brands,
+        json,
+        concurrency,
+    }) = &cli.command
+    {
+        return brand_watch(brands, *json, *concurrency).await;
+    }
+    if let Some(Command::Batch {
+        paths,
+        json,
+        min_campaign_size,
+        concurrency,
+        read_ahead,
+    }) = &cli.command
+    {
+        let options = analysis_options(&cli)?;
+        return batch(
+            paths,
+            *json,
+            *min_campaign_size,
+            *concurrency,
+            *read_ahead,
+            &options,
+            cli.lang,
+            cli.redact,
+        )
+        .await;
+    }
+    if let Some(Command::Calibrate {
+        dir,
+        manifest,
+        json,
+    }) = &cli.command
+    {
+        return calibrate_corpus(&cli, dir, manifest.as_deref(), *json).await;
+    }
+    if let Some(Command::Config { action }) = &cli.command {
+        return config(&cli, action);
+    }
+    if let Some(Command::Feedback {
+        id,
+        label,
+        note,
+        analyst,
+        log,
+    }) = &cli.command
+    {
+        let feedback =
+            FeedbackLog::new(Some(log.clone())).record(id, *label, analyst, note.clone())?;
+        println!(
+            "Recorded {} for {}",
+            feedback.label.as_str(),
+            feedback.analysis_id
+        );
+        return Ok(());
+    }
+    if let Some(Command::Normalize {
+        path,
+        cut_at,
+        output,
+    }) = &cli.command
+    {
+        let canonical = normalize(&std::fs::read(path)?, cut_at.as_deref())?;
+        match output {
+            Some(output) => std::fs::write(output, canonical)?,
+            None => std::io::Write::write_all(&mut std::io::stdout(), &canonical)?,
+        }
+        return Ok(());
+    }
+
+    if let Some(Command::Prune {
+        store,
+        raw_days,
+        days,
+        tenant,
+    }) = &cli.command
+    {
+        let retention = Retention {
+            raw_days: *raw_days,
+            result_days: *days,
+        };
+        if retention.is_unlimited() {
+            anyhow::bail!("Give --raw-days, --days, or both");
+        }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64;
+        let stats = AnalysisStore::open(store, false)
+            .await?
+            .prune(tenant.as_deref(), &retention, now)
+            .await?;
+        println!(
+            "Dropped {} raw messages, removed {} analyses",
+            stats.stripped, stats.removed
+        );
+        return Ok(());
+    }
+
+    if let Some(Command::Export {
+        store,
+        since,
+        until,
+        tenant,
+        format,
+        raw,
+        output,
+    }) = &cli.command
+    {
+        let store = open_analysis_store(store, false).await?;
+        let query = StoreQuery {
+            tenant: tenant.clone(),
+            since: *since,
+            until: *until,
+        };
+        let analyses = match raw {
+            true => store.query(&query).await?,
+            false => store.query_results(&query).await?,
+        };
+        let out: Box<dyn std::io::Write + Send> = match output {
+            Some(output) => Box::new(std::fs::File::create(output)?),
+            None => Box::new(std::io::stdout()),
+        };
+        export(&analyses, *format, *raw, std::io::BufWriter::new(out))?;
+        eprintln!("Exported {} analyses", analyses.len());
+        return Ok(());
+    }
+
+    if let Some(Command::Verify { path, public_key }) = &cli.command {
+        let result: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let public_key = public_key
+            .as_deref()
+            .map(std::fs::read_to_string)
+            .transpose()?;
+        verify(&result, public_key.as_deref())?;
+        match public_key {
+            Some(_) => println!("Digest and signature verified"),
+            None => println!("Digest verified; pass --public-key to check the signature"),
+        }
+        return Ok(());
+    }
+
+    // Require at least --input, --headers-file, or --domain
+    let message = cli.input.is_some() || cli.headers_file.is_some();
+    if !message && cli.domain.is_none() {
         eprintln!("Error: You must provide either --input <file> or --domain <domain>.");
         std::process::exit(1);
     }
 
-    // Initialize DNS resolver
-    let resolver = DnsResolver::new()?;
+    let snapshot: Option<DnsSnapshot> = match &cli.dns_snapshot {
+        Some(path) => Some(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+        None => None,
+    };
+    if cli.offline && snapshot.is_none() {
+        anyhow::bail!("--offline requires --dns-snapshot with the DNS answers to analyze against");
+    }
 
     // Case 1: Only domain provided
-    if cli.input.is_none() && cli.domain.is_some() {
+    if !message && cli.domain.is_some() {
+        // Initialize DNS resolver
+        let resolver = DnsResolver::new()?;
         let domain = cli.domain.clone().unwrap();
+        if let Some(ip) = cli.explain {
+            let explanation = explain_ip(&resolver, &domain, ip).await;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&explanation)?);
+            } else {
+                println!(
+                    "SPF {:?} for {} sending as {}",
+                    explanation.result, ip, domain
+                );
+                for step in &explanation.path {
+                    println!("  {}: {}", step.domain, step.term);
+                }
+            }
+            return Ok(());
+        }
         let exists = resolver.domain_exists(&domain).await;
         let spf_eval = resolve_spf_structured(&resolver, &domain, 0).await;
         let dkim = resolve_dkim(&resolver, &domain).await;
         let dmarc = resolver.resolve_dmarc(&domain).await;
         let verdict = calculate_domain_verdict(exists, &spf_eval, dmarc.as_deref());
+        let spf_lint = lint_spf(&resolver, &domain).await;
+        let dmarc_lint = lint_dmarc(&resolver, &domain).await;
+        let mut selectors: Vec<String> = COMMON_DKIM_SELECTORS.map(String::from).to_vec();
+        selectors.extend(cli.dkim_selectors.iter().cloned());
+        let dkim_lint = lint_dkim(&resolver, &domain, &selectors).await;
+        let dangling = find_dangling(&resolver, &domain, &selectors).await;
+        let spf_ips = flatten_spf(&resolver, &domain).await;
+        if cli.debug_dns {
+            print_dns_stats(&resolver.stats());
+        }
+        if cli.spf_ips {
+            for cidr in &spf_ips.effective {
+                println!("{}", cidr);
+            }
+            return Ok(());
+        }
 
         if cli.json {
             let output = json!({
@@ -52,54 +1160,328 @@ async fn main() -> anyhow::Result<()> {
                 "dmarc": dmarc,
                 "dkim": dkim,
                 "verdict": verdict,
+                "spf_lint": spf_lint,
+                "dmarc_lint": dmarc_lint,
+                "dkim_lint": dkim_lint,
+                "dangling_dns": dangling,
+                "spf_ips": spf_ips,
             });
             println!("{}", serde_json::to_string_pretty(&output)?);
         } else {
             println!("Domain analysis for: {}", domain);
             println!("  Exists: {}", exists);
             println!(
-                "  SPF: strict_all={}, soft_all={}",
-                spf_eval.has_strict_all, spf_eval.has_soft_all
+                "  SPF: strict_all={}, soft_all={}, pass_all={}, missing_all={}, lookups={}, \
+                 permerror={}",
+                spf_eval.has_strict_all,
+                spf_eval.has_soft_all,
+                spf_eval.has_pass_all,
+                spf_eval.missing_all,
+                spf_eval.lookup_count,
+                spf_eval.permerror
             );
             println!("  DMARC record: {}", dmarc.as_deref().unwrap_or("None"));
             println!("  DKIM record: {}", dkim);
             println!("  Verdict: {:?}", verdict);
+            println!("  SPF DNS lookups: {}", spf_lint.dns_lookups);
+            println!("  SPF void lookups: {}", spf_lint.void_lookups);
+            println!(
+                "  SPF authorizes: {} IPv4 and {} IPv6 addresses in {} networks",
+                spf_ips.ipv4_addresses,
+                spf_ips.ipv6_addresses,
+                spf_ips.effective.len()
+            );
+            let findings = spf_lint.findings.iter().chain(&dmarc_lint.findings);
+            for finding in findings.chain(&dkim_lint.findings).chain(&dangling) {
+                println!(
+                    "  - [{:?}] {}: {}\n      Fix: {}",
+                    finding.severity, finding.domain, finding.detail, finding.remediation
+                );
+            }
+            if let Some(step) = &dmarc_lint.next_step {
+                println!("  DMARC next step: {}", step);
+            }
         }
         return Ok(());
     }
 
-    // Case 2: Email input provided
-    let mut parsed_email = if let Some(input_path) = cli.input.clone() {
-        let raw = std::fs::read(&input_path)?;
-        Some(parse_email(&raw)?)
-    } else {
-        None
+    // Case 2: Email input, or a pasted header block, provided
+    let raw_email = cli.input.as_ref().map(std::fs::read).transpose()?;
+    let headers = cli
+        .headers_file
+        .as_deref()
+        .map(std::fs::read_to_string)
+        .transpose()?
+        .map(|pasted| header_block(&pasted));
+    let mut parsed_email = match (&raw_email, &headers) {
+        (Some(raw), _) => Some(parse_email(raw)?),
+        (None, Some(headers)) => Some(parse_email(headers.as_bytes())?),
+        (None, None) => None,
+    };
+    let depth = match headers {
+        Some(_) => AnalysisDepth::HeadersOnly,
+        None => cli.depth,
     };
 
     // Case 3: Override from domain if --domain provided
-    if let Some(domain_override) = cli.domain.clone() {
-        if let Some(ref mut parsed) = parsed_email {
-            parsed.from = Some(domain_override);
-        }
+    if let Some(domain_override) = cli.domain.clone()
+        && let Some(ref mut parsed) = parsed_email
+    {
+        parsed.from = Some(domain_override);
     }
 
     let parsed = parsed_email.expect("Parsed email must exist");
 
-    // Analyze email using your existing engine
-    let result = analyze_email(&parsed, &resolver).await?;
+    let mut options = analysis_options(&cli)?;
+    if cli.deterministic {
+        options.as_of = options.as_of.or(parsed.date_timestamp());
+        if options.as_of.is_none() {
+            anyhow::bail!("--deterministic needs --as-of for a message without a Date");
+        }
+    }
+
+    // Analyze email using your existing engine, against recorded DNS when given
+    let mut result = match &snapshot {
+        Some(snapshot) => {
+            // Enrichments need the network, so go no deeper than standard
+            let depth = match depth {
+                AnalysisDepth::Deep => AnalysisDepth::Standard,
+                depth => depth,
+            };
+            analyze_email_at_depth(&parsed, snapshot, &options, depth).await?
+        }
+        None => {
+            let resolver = DnsResolver::new()?;
+            let mut result = analyze_email_at_depth(&parsed, &resolver, &options, depth).await?;
+            if depth == AnalysisDepth::Deep {
+                enrich_result(&cli, &mut result, &parsed, &resolver, &options).await?;
+            }
+            if cli.debug_dns {
+                print_dns_stats(&resolver.stats());
+            }
+            result
+        }
+    };
+
+    result.rescore();
+    result.localize(cli.lang);
+
+    if let (Some(key), Some(raw)) = (&cli.arc_key, &raw_email) {
+        seal_message(&cli, key, raw, &result)?;
+    }
+
+    let report = incident_markdown(&parsed, &result);
+    if let Some(path) = &cli.report_md {
+        std::fs::write(path, &report)?;
+    }
+    #[cfg(feature = "pdf")]
+    if let Some(path) = &cli.report_pdf {
+        std::fs::write(path, email_spoof_detector::report::incident_pdf(&report))?;
+    }
+
+    if cli.authentication_results {
+        let authserv_id = cli.authserv_id.as_deref().unwrap_or_default();
+        println!("{}", authentication_results(&result, authserv_id));
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.compare {
+        let before: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let diff = ResultDiff::between(&before, &serde_json::to_value(&result)?);
+        print_diff(&diff, cli.json)?;
+        return Ok(());
+    }
 
     if cli.json {
-        println!("{}", serde_json::to_string_pretty(&result)?);
+        let mut output = serde_json::to_value(&result)?;
+        if cli.redact {
+            redact(&mut output);
+        }
+        let signer = cli
+            .signing_key
+            .as_deref()
+            .map(ResultSigner::from_file)
+            .transpose()?;
+        seal(&mut output, signer.as_ref());
+        println!("{}", serde_json::to_string_pretty(&output)?);
     } else {
+        println!("ID: {}", result.id);
         println!("Verdict: {:?}", result.verdict);
+        println!(
+            "Risk score: {} (severity {:?})",
+            result.risk_score, result.severity
+        );
+        for reason in &result.reasons {
+            println!(
+                "  - [{:?}] {} {}",
+                reason.severity, reason.rule_id, reason.message
+            );
+        }
+        for reason in &result.suppressed {
+            println!("  - suppressed: {} {}", reason.rule_id, reason.message);
+        }
         println!("Evidence:");
         println!("  From domain: {:?}", result.evidence.from_domain);
+        if let Some(labels) = &result.evidence.from_domain_labels
+            && labels.contains_non_ascii
+        {
+            println!("  From domain displays as: {}", labels.u_label);
+        }
         println!("  Domain valid: {}", result.evidence.domain_valid);
         println!("  SPF policy: {:?}", result.evidence.spf_policy);
+        if let Some(spf) = &result.evidence.spf_result {
+            println!(
+                "  SPF result: {:?} ({})",
+                spf.result,
+                spf.path.last().map_or("no term matched", |step| &step.term)
+            );
+        }
+        if let Some(spf) = &result.evidence.envelope_spf_result {
+            println!(
+                "  Envelope SPF result ({}): {:?} ({})",
+                spf.domain,
+                spf.explanation.result,
+                spf.explanation
+                    .path
+                    .last()
+                    .map_or("no term matched", |step| &step.term)
+            );
+        }
         println!("  DMARC policy: {:?}", result.evidence.dmarc_policy);
         println!("  DKIM present: {}", result.evidence.dkim_present);
         println!("  Alignment OK: {}", result.evidence.alignment_ok);
+        if let Some(history) = &result.evidence.passive_dns {
+            println!(
+                "  Passive DNS: first_seen={:?}, recently_created={}, changed_before_message={}",
+                history.first_seen, history.recently_created, history.changed_before_message
+            );
+        }
+        if let Some(check) = &result.evidence.infrastructure {
+            println!(
+                "  Registered infrastructure: verified={}, matched={:?}, client_ip={:?}",
+                check.verified, check.matched, check.client_ip
+            );
+        }
+        if let Some(registration) = &result.evidence.registration {
+            println!("  Name servers: {}", registration.nameservers.join(", "));
+            if let Some(registrar) = &registration.registrar {
+                println!(
+                    "  Registrar: {:?} (IANA {:?}), registered={:?}",
+                    registrar.name, registrar.iana_id, registrar.registered
+                );
+            }
+        }
+        if let Some(timeline) = &result.evidence.received {
+            println!("  Received hops (oldest first):");
+            for hop in &timeline.hops {
+                println!(
+                    "    {} from {}: timestamp={:?}, delta_secs={:?}",
+                    hop.by.as_deref().unwrap_or("?"),
+                    hop.from.as_deref().unwrap_or("?"),
+                    hop.timestamp,
+                    hop.delta_secs
+                );
+            }
+        }
+        for coverage in &result.evidence.dkim_coverage {
+            println!(
+                "  DKIM {} (s={}) signs: {}",
+                coverage.domain,
+                coverage.selector,
+                coverage.signed_headers.join(":")
+            );
+            for header in &coverage.critical {
+                println!(
+                    "    {}: present={}, signed={}, oversigned={}",
+                    header.header, header.present, header.signed, header.oversigned
+                );
+            }
+        }
+        if let Some(lookalike) = &result.evidence.lookalike {
+            println!(
+                "  Lookalike: {} ({}) imitates {} ({:?})",
+                lookalike.domain,
+                lookalike.domain_labels.u_label,
+                lookalike.protected_domain,
+                lookalike.kind
+            );
+            if let Some(certs) = &lookalike.certificates {
+                println!(
+                    "  CT certificates: {}, last_issued={:?}, recently_issued={}",
+                    certs.certificates.len(),
+                    certs.last_issued,
+                    certs.recently_issued
+                );
+            }
+        }
+        for url in &result.evidence.body.urls {
+            println!(
+                "  URL: {} -> {:?}{}{}",
+                url.url,
+                url.landing_domain,
+                match &url.landing_domain_labels {
+                    Some(labels) if labels.contains_non_ascii => format!(" ({})", labels.u_label),
+                    _ => String::new(),
+                },
+                match &url.lookalike {
+                    Some(l) => format!(" (imitates {})", l.protected_domain),
+                    None => String::new(),
+                }
+            );
+        }
+        if let Some(text) = &result.evidence.body.text {
+            for m in &text.matches {
+                println!("  Phrase ({:?}): {}", m.category, m.phrase);
+            }
+        }
+        if let Some(upstream) = &result.evidence.upstream_auth {
+            println!(
+                "  Upstream ({}): spf={}, dkim={}, dmarc={}",
+                upstream.authserv_id,
+                upstream.spf.as_deref().unwrap_or("none"),
+                upstream.dkim.as_deref().unwrap_or("none"),
+                upstream.dmarc.as_deref().unwrap_or("none"),
+            );
+        }
+        print_embedded(&result.embedded, 0);
+        if let Some(timings) = &result.timings {
+            println!("Timings:");
+            for (check, timing) in &timings.checks {
+                println!("  {}: {:.3} ms", check, timing.seconds * 1000.0);
+            }
+            for (class, timing) in &timings.dns {
+                println!(
+                    "  DNS {}: {} lookups, {:.3} ms",
+                    class,
+                    timing.count,
+                    timing.seconds * 1000.0
+                );
+            }
+        }
     }
 
     Ok(())
 }
+
+/// Prints the verdicts of attached messages, separately from the carrier's
+fn print_embedded(results: &[AnalysisResult], level: usize) {
+    let indent = "  ".repeat(level);
+    for (i, result) in results.iter().enumerate() {
+        println!(
+            "{}Attached message {} ({:?}): verdict {:?}, risk score {} (severity {:?})",
+            indent,
+            i + 1,
+            result.evidence.from_domain,
+            result.verdict,
+            result.risk_score,
+            result.severity
+        );
+        for reason in &result.reasons {
+            println!(
+                "{}  - [{:?}] {} {}",
+                indent, reason.severity, reason.rule_id, reason.message
+            );
+        }
+        print_embedded(&result.embedded, level + 1);
+    }
+}
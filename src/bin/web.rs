@@ -1,57 +1,2565 @@
-use actix_web::{App, HttpResponse, HttpServer, Responder, web};
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, Responder, web};
+use clap::Parser;
+use email_spoof_detector::{
+    analytics::{AnalyticsRow, AnalyticsSink},
+    analyzer::{AnalysisHandle, Analyzer, cancellable},
+    attachments::AttachmentLimits,
+    audit::AuditLog,
+    bundle::ConfigBundle,
+    config::{
+        analysis_store_from_env, analytics_sink_from_env, attachment_limits_from_env,
+        early_exit_from_env, egress_policy_from_env, env_flag, env_list, env_number,
+        resolver_from_env, result_signer_from_env, retention_from_env, service_analysis_options,
+        service_config_from_env, shadow_config_from_env,
+    },
+    ct::{self, CRT_SH_URL, CrtSh},
+    dedup::{DedupCache, message_hash},
+    diff::ResultDiff,
+    dns::{DnsResolver, DnsSnapshot, ResolverTrait},
+    early_exit::EarlyExitPolicy,
+    email_verdict::{AnalysisDepth, AnalysisResult, analyze_email_at_depth},
+    export::{ExportFormat, export, ndjson_line},
+    feedback::{FeedbackLabel, FeedbackLog},
+    fingerprint::FingerprintHistory,
+    http::{HttpFetcher, ReqwestFetcher, client_builder},
+    inbound::{InboundFormat, authenticate, extract_raw_mime, forward},
+    integrity::{ResultSigner, seal},
+    lists::{ListKind, SenderLists},
+    messages::Lang,
+    normalize::header_block,
+    parse::{EmailParsed, normalize_domain, parse_email, parse_email_with_limits, parse_time},
+    passive_dns::{HttpPassiveDns, enrich},
+    phish_report::{PhishReport, locate_original},
+    pool::WorkerPool,
+    redact::redact,
+    registration::{RDAP_URL, Rdap, RegistrationProvider, ReputationRules, evaluate_registration},
+    rules::Suppression,
+    session::SmtpSession,
+    settings,
+    shadow::Shadow,
+    spf_lint::lint_spf,
+    store::{AnalysisStore, PruneStats, Retention, StoreQuery, StoredAnalysis},
+    systemd,
+    tenants::{TenantError, TenantRegistry, keys_equal},
+    timing::Timings,
+    url_expand::{DEFAULT_MAX_HOPS, UrlExpander, expand_body_urls},
+};
 use env_logger::Env;
-use email_spoof_detector::{dns::DnsResolver, email_verdict::analyze_email, parse::parse_email};
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
 
 #[derive(Deserialize)]
 struct AnalyzeRequest {
     raw_email: String, // base64 or plain text email
+
+    /// Recorded DNS answers to analyze against instead of live DNS
+    #[serde(default)]
+    dns_snapshot: Option<DnsSnapshot>,
+
+    /// Skip DNS entirely (equivalent to an empty snapshot)
+    #[serde(default)]
+    no_dns: bool,
+
+    /// Language of the reason messages; defaults to the `Accept-Language` header
+    #[serde(default)]
+    lang: Option<Lang>,
+
+    /// How thoroughly to analyze; defaults to `deep`
+    #[serde(default)]
+    depth: Option<AnalysisDepth>,
+
+    /// Milliseconds after which to answer with the evidence gathered so far; defaults
+    /// to `ANALYSIS_DEADLINE_MS`
+    #[serde(default)]
+    deadline_ms: Option<u64>,
+
+    /// Time to evaluate time-based checks at instead of now, e.g. `2026-02-02T15:15:37Z`;
+    /// see `parse_time`
+    #[serde(default)]
+    as_of: Option<String>,
+
+    /// SMTP session the message arrived in, as seen by the receiving MTA
+    #[serde(default)]
+    session: Option<SmtpSession>,
+
+    /// Apply the enrichments of a deep analysis even when the message is already
+    /// decided (`ENRICHMENT_SKIP_SCORE`, `ENRICHMENT_SKIP_CLEAN`)
+    #[serde(default)]
+    full_analysis: bool,
 }
 
-async fn analyze(req: web::Json<AnalyzeRequest>) -> impl Responder {
+async fn analyze(
+    http: HttpRequest,
+    req: web::Json<AnalyzeRequest>,
+    tenants: web::Data<Tenants>,
+    enrichment: web::Data<Enrichment>,
+    limits: web::Data<Limits>,
+    signer: web::Data<Option<ResultSigner>>,
+) -> impl Responder {
+    let deadline = limits.deadline(req.deadline_ms);
+    let as_of = match req.as_of.as_deref() {
+        Some(value) => match parse_time(value) {
+            Some(as_of) => Some(as_of),
+            None => {
+                return HttpResponse::BadRequest().body(format!("Unrecognized as_of: {}", value));
+            }
+        },
+        None => None,
+    };
+    let (tenant, analyzer) = match tenants.select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+
+    // Held until the response is built
+    let _permit = match limits.acquire_permit().await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
     let raw_bytes = req.raw_email.as_bytes();
 
-    let parsed = match parse_email(raw_bytes) {
-        Ok(p) => p,
+    let parsed = match parse_email_with_limits(raw_bytes, &limits.attachments) {
+        Ok(p) => match req.session.clone() {
+            Some(session) => p.with_session(session),
+            None => p,
+        },
         Err(e) => return HttpResponse::BadRequest().body(format!("Failed to parse email: {}", e)),
     };
 
-    let resolver = match DnsResolver::new() {
-        Ok(r) => r,
+    let lang = req.lang.unwrap_or_else(|| preferred_language(&http));
+
+    // Offline analyses never query third parties, so go no deeper than standard
+    let offline = req.no_dns || req.dns_snapshot.is_some();
+    let depth = match req.depth.unwrap_or(AnalysisDepth::Deep) {
+        AnalysisDepth::Deep if offline => AnalysisDepth::Standard,
+        depth => depth,
+    };
+
+    // Repeats of a live analysis are answered from the dedup cache
+    let dedup_key = match limits.dedup.as_ref() {
+        Some(_) if !offline => Some(format!(
+            "{}/{}/{:?}/{:?}/{:?}/{:?}/{}",
+            tenant,
+            message_hash(&parsed),
+            lang,
+            depth,
+            as_of,
+            req.session,
+            req.full_analysis
+        )),
+        _ => None,
+    };
+    if let (Some(cache), Some(key)) = (limits.dedup.as_ref(), &dedup_key)
+        && let Some(result) = cache.get(key)
+    {
+        tenants.record(tenant, result["verdict"].as_str().unwrap_or_default());
+        tenants.publish(tenant, &result);
+        return sealed_response(result, &signer);
+    }
+
+    let analysis = if offline {
+        let snapshot = req.dns_snapshot.clone().unwrap_or_default();
+        analyze_email_at_depth(&parsed, &snapshot, &analyzer.options(), depth)
+            .await
+            .map(|result| (result, snapshot))
+    } else {
+        tenants.analyze(analyzer, &parsed, depth, deadline).await
+    };
+
+    let mut result = match analysis {
+        Ok((mut result, snapshot)) => {
+            tenants
+                .evaluate_shadow(tenant, &parsed, &snapshot, &result)
+                .await;
+            tenants.keep(tenant, raw_bytes, snapshot, &mut result).await;
+            result
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Analysis error: {}", e));
+        }
+    };
+
+    if depth == AnalysisDepth::Deep {
+        let full = req.full_analysis;
+        enrich_until(
+            &mut result,
+            &parsed,
+            analyzer,
+            &enrichment,
+            as_of,
+            deadline,
+            full,
+        )
+        .await;
+    }
+
+    result.as_of = as_of.or(result.as_of);
+    result.rescore();
+    result.localize(lang);
+    tenants.record(tenant, &format!("{:?}", result.verdict));
+    tenants.record_timings(&result);
+    tenants.publish(tenant, &result);
+    // Partial results are not reused; a repeat may have more time
+    if let (Some(cache), Some(key)) = (limits.dedup.as_ref(), dedup_key)
+        && !result.partial
+        && let Ok(value) = serde_json::to_value(&result)
+    {
+        cache.insert(key, value);
+    }
+    sealed_response(result, &signer)
+}
+
+/// Analyzes a header block pasted from a mail client, at `headers_only` depth
+///
+/// The request body is the pasted text; `normalize::header_block` lists the copy
+/// artifacts tolerated.
+async fn analyze_headers(
+    http: HttpRequest,
+    pasted: String,
+    tenants: web::Data<Tenants>,
+    limits: web::Data<Limits>,
+    signer: web::Data<Option<ResultSigner>>,
+) -> impl Responder {
+    let deadline = limits.deadline(None);
+    let (tenant, analyzer) = match tenants.select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+
+    let _permit = match limits.acquire_permit().await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let raw = header_block(&pasted);
+    let parsed = match parse_email(raw.as_bytes()) {
+        Ok(parsed) if parsed.from.is_some() || !parsed.received.is_empty() => parsed,
+        Ok(_) => return HttpResponse::BadRequest().body("No From or Received header found"),
+        Err(e) => {
+            return HttpResponse::BadRequest().body(format!("Failed to parse headers: {}", e));
+        }
+    };
+    let lang = preferred_language(&http);
+
+    let mut result = match tenants
+        .analyze(analyzer, &parsed, AnalysisDepth::HeadersOnly, deadline)
+        .await
+    {
+        Ok((mut result, snapshot)) => {
+            tenants
+                .evaluate_shadow(tenant, &parsed, &snapshot, &result)
+                .await;
+            tenants
+                .keep(tenant, raw.as_bytes(), snapshot, &mut result)
+                .await;
+            result
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Analysis error: {}", e));
+        }
+    };
+    result.rescore();
+    result.localize(lang);
+    tenants.record(tenant, &format!("{:?}", result.verdict));
+    tenants.record_timings(&result);
+    tenants.publish(tenant, &result);
+    sealed_response(result, &signer)
+}
+
+#[derive(Deserialize)]
+struct ReportRequest {
+    /// The user's report, with the reported message attached or forwarded inline
+    raw_email: String,
+
+    /// Language of the reason messages; defaults to the `Accept-Language` header
+    #[serde(default)]
+    lang: Option<Lang>,
+
+    /// Milliseconds after which to answer with the evidence gathered so far; defaults
+    /// to `ANALYSIS_DEADLINE_MS`
+    #[serde(default)]
+    deadline_ms: Option<u64>,
+}
+
+/// Triages a message a user reported as phishing, e.g. from a report button
+///
+/// The reported message is the report's first attached message or, failing that, one
+/// forwarded inline. It is analyzed in depth, the report itself at standard depth, and
+/// both analyses are kept.
+async fn report(
+    http: HttpRequest,
+    req: web::Json<ReportRequest>,
+    tenants: web::Data<Tenants>,
+    enrichment: web::Data<Enrichment>,
+    limits: web::Data<Limits>,
+    signer: web::Data<Option<ResultSigner>>,
+) -> impl Responder {
+    let deadline = limits.deadline(req.deadline_ms);
+    let (tenant, analyzer) = match tenants.select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+
+    let _permit = match limits.acquire_permit().await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let raw = req.raw_email.as_bytes();
+    let parsed = match parse_email_with_limits(raw, &limits.attachments) {
+        Ok(parsed) => parsed,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Failed to parse email: {}", e)),
+    };
+    let lang = req.lang.unwrap_or_else(|| preferred_language(&http));
+
+    let mut carrier = match tenants
+        .analyze(analyzer, &parsed, AnalysisDepth::Standard, deadline)
+        .await
+    {
+        Ok((mut result, snapshot)) => {
+            tenants.keep(tenant, raw, snapshot, &mut result).await;
+            result
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Analysis error: {}", e));
+        }
+    };
+    // Attached messages are returned as `reported` instead, analyzed in depth
+    carrier.embedded.clear();
+    carrier.rescore();
+    carrier.localize(lang);
+    tenants.record(tenant, &format!("{:?}", carrier.verdict));
+    tenants.record_timings(&carrier);
+    tenants.publish(tenant, &carrier);
+
+    let Some(original) = locate_original(raw, &parsed) else {
+        return sealed_response(PhishReport::new(&parsed, carrier, None), &signer);
+    };
+    let reported = match parse_email_with_limits(&original.raw, &limits.attachments) {
+        Ok(reported) => reported,
+        Err(e) => {
+            return HttpResponse::BadRequest()
+                .body(format!("Failed to parse reported email: {}", e));
+        }
+    };
+    let mut result = match tenants
+        .analyze(analyzer, &reported, AnalysisDepth::Deep, deadline)
+        .await
+    {
+        Ok((mut result, snapshot)) => {
+            tenants
+                .keep(tenant, &original.raw, snapshot, &mut result)
+                .await;
+            result
+        }
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Analysis error: {}", e));
+        }
+    };
+    enrich_until(
+        &mut result,
+        &reported,
+        analyzer,
+        &enrichment,
+        None,
+        deadline,
+        false,
+    )
+    .await;
+    result.rescore();
+    result.localize(lang);
+    tenants.record(tenant, &format!("{:?}", result.verdict));
+    tenants.record_timings(&result);
+    tenants.publish(tenant, &result);
+
+    let report = PhishReport::new(&parsed, carrier, Some((original.source, &reported, result)));
+    sealed_response(report, &signer)
+}
+
+/// Responds with `result` sealed with its digest and, when `RESULT_SIGNING_KEY` is set,
+/// a signature
+fn sealed_response(result: impl serde::Serialize, signer: &Option<ResultSigner>) -> HttpResponse {
+    match serde_json::to_value(result) {
+        Ok(mut result) => {
+            seal(&mut result, signer.as_ref());
+            HttpResponse::Ok().json(result)
+        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("Serialization error: {}", e)),
+    }
+}
+
+/// Page pasting or uploading a message and rendering its analysis, for helpdesk staff
+#[cfg(feature = "ui")]
+async fn ui() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header((
+            "Content-Security-Policy",
+            "default-src 'none'; script-src 'unsafe-inline'; style-src 'unsafe-inline'; \
+             connect-src 'self'; frame-ancestors 'none'",
+        ))
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .body(include_str!("../../ui/index.html"))
+}
+
+/// Registers the UI at `/ui` when built with the `ui` feature
+fn ui_routes(cfg: &mut web::ServiceConfig) {
+    #[cfg(feature = "ui")]
+    cfg.route("/ui", web::get().to(ui));
+    #[cfg(not(feature = "ui"))]
+    let _ = cfg;
+}
+
+/// The public key verifying result signatures, as PEM
+async fn result_signing_key(signer: web::Data<Option<ResultSigner>>) -> impl Responder {
+    match signer.as_ref().as_ref().map(ResultSigner::public_key_pem) {
+        Some(Ok(pem)) => HttpResponse::Ok()
+            .content_type("application/x-pem-file")
+            .body(pem),
+        Some(Err(e)) => HttpResponse::InternalServerError().body(e.to_string()),
+        None => HttpResponse::NotFound().body("Results are not signed"),
+    }
+}
+
+/// Audits the SPF record of a domain and its includes, so its owner can fix what
+/// makes receivers distrust it
+async fn spf_lint(
+    http: HttpRequest,
+    domain: web::Path<String>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    let analyzer = match tenants.select(&http) {
+        Ok((_, analyzer)) => analyzer,
+        Err(response) => return response,
+    };
+    let Some(domain) = normalize_domain(&domain) else {
+        return HttpResponse::BadRequest().body("Not a domain name");
+    };
+    HttpResponse::Ok().json(lint_spf(analyzer.resolver(), &domain).await)
+}
+
+/// Receives inbound-mail webhooks of SendGrid, Mailgun, and Postmark
+///
+/// Requests must carry `INBOUND_SECRET` (see [`authenticate`]) or get a 401. The
+/// verdict is returned and, when `INBOUND_FORWARD_URL` is set, posted there in the
+/// background so the provider gets its 2xx without waiting on the destination.
+async fn inbound(
+    http: HttpRequest,
+    provider: web::Path<String>,
+    body: web::Bytes,
+    tenants: web::Data<Tenants>,
+    enrichment: web::Data<Enrichment>,
+    limits: web::Data<Limits>,
+    forwarding: web::Data<Forwarding>,
+) -> impl Responder {
+    let deadline = limits.deadline(None);
+    let Some(format) = InboundFormat::from_name(&provider) else {
+        return HttpResponse::NotFound().body(format!("Unknown provider: {}", provider));
+    };
+    let Some(secret) = &forwarding.secret else {
+        return HttpResponse::NotFound().finish();
+    };
+    let header = |name| http.headers().get(name).and_then(|h| h.to_str().ok());
+    let content_type = header(actix_web::http::header::CONTENT_TYPE).unwrap_or_default();
+    let authorization = header(actix_web::http::header::AUTHORIZATION);
+    if !authenticate(format, secret, authorization, content_type, &body) {
+        return HttpResponse::Unauthorized().finish();
+    }
+    let (tenant, analyzer) = match tenants.select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+
+    let _permit = match limits.acquire_permit().await {
+        Ok(permit) => permit,
+        Err(response) => return response,
+    };
+
+    let (raw, parsed) = match extract_raw_mime(format, content_type, &body).and_then(|raw| {
+        parse_email_with_limits(&raw, &limits.attachments).map(|parsed| (raw, parsed))
+    }) {
+        Ok(read) => read,
+        Err(e) => return HttpResponse::BadRequest().body(format!("Failed to read webhook: {}", e)),
+    };
+
+    let cached = limits
+        .dedup
+        .as_ref()
+        .map(|cache| (cache, format!("{}/{}", tenant, message_hash(&parsed))));
+    let mut result = match cached.as_ref().and_then(|(cache, key)| cache.get(key)) {
+        Some(result) => result,
+        None => {
+            let mut result = match tenants
+                .analyze(analyzer, &parsed, AnalysisDepth::Deep, deadline)
+                .await
+            {
+                Ok((mut result, snapshot)) => {
+                    tenants
+                        .evaluate_shadow(tenant, &parsed, &snapshot, &result)
+                        .await;
+                    tenants.keep(tenant, &raw, snapshot, &mut result).await;
+                    result
+                }
+                Err(e) => {
+                    return HttpResponse::InternalServerError()
+                        .body(format!("Analysis error: {}", e));
+                }
+            };
+            enrich_until(
+                &mut result,
+                &parsed,
+                analyzer,
+                &enrichment,
+                None,
+                deadline,
+                false,
+            )
+            .await;
+            result.rescore();
+            tenants.record_timings(&result);
+
+            let partial = result.partial;
+            let result = serde_json::json!(result);
+            if let Some((cache, key)) = cached.filter(|_| !partial) {
+                cache.insert(key, result.clone());
+            }
+            result
+        }
+    };
+
+    tenants.record(tenant, result["verdict"].as_str().unwrap_or_default());
+    tenants.publish(tenant, &result);
+    let signer = http
+        .app_data::<web::Data<Option<ResultSigner>>>()
+        .and_then(|signer| signer.as_ref().as_ref());
+    seal(&mut result, signer);
+
+    let verdict = serde_json::json!({
+        "provider": format,
+        "result": result,
+    });
+    if let Some(url) = forwarding.url.clone() {
+        let client = forwarding.client.clone();
+        let mut verdict = verdict.clone();
+        if forwarding.redact {
+            redact(&mut verdict);
+            seal(&mut verdict["result"], signer);
+        }
+        tokio::spawn(async move {
+            if let Err(e) = forward(&client, &url, &verdict).await {
+                log::warn!("Forwarding verdict to {} failed: {}", url, e);
+            }
+        });
+    }
+    HttpResponse::Ok().json(verdict)
+}
+
+/// Applies the enrichments until `deadline`, keeping those finished by then and marking
+/// the result partial if any were cut off
+///
+/// Unless `full`, they stop once the early exit policy finds the message decided.
+async fn enrich_until<R: ResolverTrait + Sync + Send>(
+    result: &mut AnalysisResult,
+    parsed: &EmailParsed,
+    analyzer: &Analyzer<R>,
+    enrichment: &Enrichment,
+    as_of: Option<i64>,
+    deadline: Option<Instant>,
+    full: bool,
+) {
+    if stop_early(result, enrichment, full) {
+        return;
+    }
+    let enriched = enrich_result(result, parsed, analyzer, enrichment, as_of, full);
+    match deadline {
+        Some(deadline) => {
+            if tokio::time::timeout_at(deadline, enriched).await.is_err() {
+                result.partial = true;
+            }
+        }
+        None => enriched.await,
+    }
+}
+
+/// Third-party enrichments of a deep analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnrichmentStep {
+    PassiveDns,
+    CtLog,
+    Registration,
+    UrlExpansion,
+}
+
+/// The enrichments, cheapest first: one passive DNS query; a CT log query, made only
+/// for lookalike domains; an RDAP query plus a name server lookup; and a request per
+/// redirect hop of every link
+const ENRICHMENTS: [EnrichmentStep; 4] = [
+    EnrichmentStep::PassiveDns,
+    EnrichmentStep::CtLog,
+    EnrichmentStep::Registration,
+    EnrichmentStep::UrlExpansion,
+];
+
+/// Applies the configured third-party enrichments to a live analysis, evaluating
+/// time windows as of `as_of` (default now)
+///
+/// The cheaper ones go first; unless `full`, the rest are skipped once the evidence
+/// decides the message.
+async fn enrich_result<R: ResolverTrait + Sync + Send>(
+    result: &mut AnalysisResult,
+    parsed: &EmailParsed,
+    analyzer: &Analyzer<R>,
+    enrichment: &Enrichment,
+    as_of: Option<i64>,
+    full: bool,
+) {
+    let mut enriched = false;
+    for step in ENRICHMENTS {
+        if enriched && stop_early(result, enrichment, full) {
+            return;
+        }
+        enriched |= apply_enrichment(step, result, parsed, analyzer, enrichment, as_of).await;
+    }
+}
+
+/// Applies one enrichment when it is configured and the message gives it something to
+/// look up; whether it ran
+async fn apply_enrichment<R: ResolverTrait + Sync + Send>(
+    step: EnrichmentStep,
+    result: &mut AnalysisResult,
+    parsed: &EmailParsed,
+    analyzer: &Analyzer<R>,
+    enrichment: &Enrichment,
+    as_of: Option<i64>,
+) -> bool {
+    let start = Instant::now();
+    match step {
+        // Sender domain DNS history
+        EnrichmentStep::PassiveDns => {
+            let (Some(provider), Some(domain)) = (
+                enrichment.passive_dns.as_ref(),
+                result.evidence.from_domain.as_deref(),
+            ) else {
+                return false;
+            };
+            match enrich(provider, domain, parsed.date_timestamp(), as_of).await {
+                Ok(findings) => result.evidence.passive_dns = Some(findings),
+                Err(e) => log::warn!("Passive DNS lookup for {} failed: {}", domain, e),
+            }
+            time_enrichment(result, "passive_dns", start);
+        }
+        // Certificates issued for a lookalike domain
+        EnrichmentStep::CtLog => {
+            let (Some(provider), Some(lookalike)) = (
+                enrichment.ct_log.as_ref(),
+                result.evidence.lookalike.as_mut(),
+            ) else {
+                return false;
+            };
+            let date = parsed.date_timestamp();
+            match ct::enrich(provider, &lookalike.domain, date, as_of).await {
+                Ok(findings) => lookalike.certificates = Some(findings),
+                Err(e) => log::warn!("CT log lookup for {} failed: {}", lookalike.domain, e),
+            }
+            time_enrichment(result, "ct_log", start);
+        }
+        // Name servers and registrar of the sender domain
+        EnrichmentStep::Registration => {
+            let (Some(rdap), Some(domain)) = (
+                enrichment.rdap.as_ref(),
+                result.evidence.from_domain.as_deref(),
+            ) else {
+                return false;
+            };
+            let registrar = match rdap.registrar(domain).await {
+                Ok(info) => Some(info),
+                Err(e) => {
+                    log::warn!("RDAP lookup for {} failed: {}", domain, e);
+                    None
+                }
+            };
+            let nameservers = analyzer.resolver().ns_hosts(domain).await;
+            let rules = enrichment.reputation_rules.read().unwrap().clone();
+            let findings = evaluate_registration(nameservers, registrar, &rules);
+            result.evidence.registration = Some(findings);
+            time_enrichment(result, "registration", start);
+        }
+        // Landing domains of shortened/redirecting body URLs
+        EnrichmentStep::UrlExpansion => {
+            let Some(expander) = enrichment.url_expander.as_ref() else {
+                return false;
+            };
+            let options = analyzer.options();
+            expand_body_urls(
+                expander,
+                &mut result.evidence.body,
+                &options.protected_domains,
+            )
+            .await;
+            time_enrichment(result, "url_expansion", start);
+        }
+    }
+    true
+}
+
+/// Whether the enrichments still to come can be skipped, with the reason recorded in
+/// the rescored result
+fn stop_early(result: &mut AnalysisResult, enrichment: &Enrichment, full: bool) -> bool {
+    if full || enrichment.early_exit == EarlyExitPolicy::default() {
+        return false;
+    }
+    result.rescore();
+    result.early_exit = enrichment.early_exit.decide(result);
+    result.early_exit.is_some()
+}
+
+/// Charges the time since `start` to `enrichment`, when the result records timings
+fn time_enrichment(result: &mut AnalysisResult, enrichment: &str, start: Instant) {
+    if let Some(timings) = &mut result.timings {
+        timings.record(enrichment, start.elapsed());
+    }
+}
+
+/// Optional enrichment providers, configured at startup
+struct Enrichment {
+    passive_dns: Option<HttpPassiveDns>,
+    ct_log: Option<CrtSh>,
+    url_expander: Option<UrlExpander>,
+    /// Set when name server and registrar lookups are enabled
+    rdap: Option<Rdap>,
+    /// Replaced on configuration reload
+    reputation_rules: std::sync::RwLock<ReputationRules>,
+    /// When decided messages skip the enrichments
+    early_exit: EarlyExitPolicy,
+}
+
+/// Secret of inbound-mail webhooks and destination for their verdicts
+struct Forwarding {
+    /// Secret webhooks must carry (`INBOUND_SECRET`); without one they are disabled
+    secret: Option<String>,
+    url: Option<String>,
+    client: reqwest::Client,
+    /// Mask personal data in forwarded verdicts
+    redact: bool,
+}
+
+/// Backpressure settings
+struct Limits {
+    pool: WorkerPool,
+    /// Seconds clients are told to wait when the pool is saturated
+    retry_after_secs: u64,
+    /// Results of recently analyzed messages, so repeats skip the analysis
+    dedup: Option<DedupCache>,
+    /// Milliseconds an analysis may take unless the request asks otherwise; 0 for none
+    deadline_ms: u64,
+    /// Resources inspecting each attachment may take
+    attachments: AttachmentLimits,
+}
+
+impl Limits {
+    /// Deadline of an analysis starting now
+    fn deadline(&self, requested_ms: Option<u64>) -> Option<Instant> {
+        match requested_ms.unwrap_or(self.deadline_ms) {
+            0 => None,
+            ms => Some(Instant::now() + std::time::Duration::from_millis(ms)),
+        }
+    }
+
+    /// A worker permit, to hold until the response is built, or a 503 with
+    /// `Retry-After` once the pool and its queue are full
+    async fn acquire_permit(&self) -> Result<tokio::sync::SemaphorePermit<'_>, HttpResponse> {
+        self.pool.acquire().await.map_err(|e| {
+            HttpResponse::ServiceUnavailable()
+                .insert_header((
+                    actix_web::http::header::RETRY_AFTER,
+                    self.retry_after_secs.to_string(),
+                ))
+                .body(e.to_string())
+        })
+    }
+}
+
+/// The language of the reason messages the `Accept-Language` header asks for, or the
+/// default one
+fn preferred_language(http: &HttpRequest) -> Lang {
+    http.headers()
+        .get(actix_web::http::header::ACCEPT_LANGUAGE)
+        .and_then(|h| h.to_str().ok())
+        .and_then(Lang::from_accept_language)
+        .unwrap_or_default()
+}
+
+/// Cancels an analysis whose request was dropped unfinished, which actix-web does when
+/// the client disconnects, and counts it
+struct CancelOnDrop<'a> {
+    handle: AnalysisHandle,
+    cancelled: &'a AtomicU64,
+}
+
+impl Drop for CancelOnDrop<'_> {
+    fn drop(&mut self) {
+        if !self.handle.is_finished() {
+            self.handle.cancel();
+            self.cancelled.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Analyzers of the default configuration and of each tenant, sharing one resolver
+struct Tenants {
+    default: web::Data<Analyzer<DnsResolver>>,
+    registry: TenantRegistry,
+    analyzers: BTreeMap<String, Analyzer<DnsResolver>>,
+    /// Answered analyses by tenant and verdict
+    analyses: Mutex<BTreeMap<(String, String), u64>>,
+    /// Analyses abandoned because their client disconnected
+    cancelled: AtomicU64,
+    /// Time spent on checks and DNS lookups by the analyses that recorded it
+    /// (`ANALYSIS_TIMINGS`)
+    timings: Mutex<Timings>,
+    /// Analyzed messages kept for replay (`ANALYSIS_STORE`)
+    store: Option<AnalysisStore>,
+    /// Mailer fingerprints of the stored analyses, by sender domain
+    fingerprints: Mutex<FingerprintHistory>,
+    /// Candidate configuration scored alongside the default one (`SHADOW_CONFIG_BUNDLE`)
+    shadow: Option<Shadow>,
+    /// Receives a row per answered analysis (`ANALYTICS_URL`)
+    analytics: Option<AnalyticsSink>,
+}
+
+impl Tenants {
+    /// Label of requests without a tenant in metrics and cache keys
+    const DEFAULT: &str = "default";
+
+    /// The tenant named by the `X-Api-Key` or `X-Tenant` header, and its analyzer
+    fn select(&self, http: &HttpRequest) -> Result<(&str, &Analyzer<DnsResolver>), HttpResponse> {
+        let header = |name: &str| http.headers().get(name).and_then(|h| h.to_str().ok());
+        match self
+            .registry
+            .select(header("X-Api-Key"), header("X-Tenant"))
+        {
+            Ok(Some(name)) => Ok((name, &self.analyzers[name])),
+            Ok(None) => Ok((Self::DEFAULT, &self.default)),
+            Err(e @ TenantError::Unauthorized) => {
+                Err(HttpResponse::Unauthorized().body(e.to_string()))
+            }
+            Err(e @ TenantError::Unknown(_)) => Err(HttpResponse::BadRequest().body(e.to_string())),
+        }
+    }
+
+    /// The tenant an admin request acts on, and its analyzer: the one its `X-Tenant`
+    /// header names, or the default one without the header
+    fn admin_select<'a>(
+        &'a self,
+        http: &'a HttpRequest,
+    ) -> Result<(&'a str, &'a Analyzer<DnsResolver>), HttpResponse> {
+        let name = match http.headers().get("X-Tenant").map(|h| h.to_str()) {
+            None => Self::DEFAULT,
+            Some(Ok(name)) => name,
+            Some(Err(_)) => return Err(HttpResponse::BadRequest().body("Unknown tenant")),
+        };
+        match self.analyzer(name) {
+            Some(analyzer) => Ok((name, analyzer)),
+            None => Err(HttpResponse::BadRequest().body(format!("unknown tenant '{}'", name))),
+        }
+    }
+
+    /// Applies the configurations of `registry` to the running tenants; tenants it adds
+    /// or drops, and changed API keys, take effect on restart
+    fn set_tenant_options(&self, registry: &TenantRegistry) {
+        for (name, analyzer) in &self.analyzers {
+            match registry.tenants.get(name) {
+                Some(tenant) => analyzer.set_options(service_analysis_options(&tenant.config)),
+                None => log::warn!("Tenant {} was removed; it is served until restart", name),
+            }
+        }
+        for name in registry.tenants.keys() {
+            if !self.analyzers.contains_key(name) {
+                log::warn!("Tenant {} was added; it is served after restart", name);
+            }
+        }
+    }
+
+    /// The analyzer of `tenant`, unless it is no longer configured
+    fn analyzer(&self, tenant: &str) -> Option<&Analyzer<DnsResolver>> {
+        match tenant {
+            Self::DEFAULT => Some(&self.default),
+            _ => self.analyzers.get(tenant),
+        }
+    }
+
+    /// Analyzes a message with live DNS, recording the answers when analyses are kept or
+    /// a shadow configuration is evaluated
+    async fn analyze(
+        &self,
+        analyzer: &Analyzer<DnsResolver>,
+        parsed: &EmailParsed,
+        depth: AnalysisDepth,
+        deadline: Option<Instant>,
+    ) -> anyhow::Result<(AnalysisResult, DnsSnapshot)> {
+        let (handle, analysis) = cancellable(async {
+            if self.store.is_some() || self.shadow.is_some() {
+                analyzer.analyze_recording(parsed, depth, deadline).await
+            } else {
+                let result = analyzer.analyze_at(parsed, depth, deadline).await?;
+                Ok((result, DnsSnapshot::default()))
+            }
+        });
+        let _disconnect = CancelOnDrop {
+            handle,
+            cancelled: &self.cancelled,
+        };
+        analysis.await?
+    }
+
+    /// Scores a message of the default configuration with the shadow one, logging
+    /// where the results differ; the live result is unaffected
+    async fn evaluate_shadow(
+        &self,
+        tenant: &str,
+        parsed: &EmailParsed,
+        snapshot: &DnsSnapshot,
+        result: &AnalysisResult,
+    ) {
+        let Some(shadow) = self.shadow.as_ref().filter(|_| tenant == Self::DEFAULT) else {
+            return;
+        };
+        match shadow.evaluate(parsed, snapshot, result).await {
+            Ok(diff) if !diff.is_empty() => {
+                let fields: Vec<&str> = diff.changes.iter().map(|c| c.field.as_str()).collect();
+                log::info!(
+                    "Shadow configuration differs on {}: {}",
+                    result.id,
+                    fields.join(", ")
+                );
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Shadow analysis of {} failed: {}", result.id, e),
+        }
+    }
+
+    /// Keeps an analysis for replay, when enabled, after comparing its mailer
+    /// fingerprint with those the sender domain used before
+    async fn keep(
+        &self,
+        tenant: &str,
+        raw: &[u8],
+        snapshot: DnsSnapshot,
+        result: &mut AnalysisResult,
+    ) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        if let Some(domain) = result.evidence.from_domain.as_deref() {
+            let mut fingerprints = self.fingerprints.lock().unwrap();
+            let fingerprint = &result.evidence.fingerprint;
+            result.evidence.fingerprint_anomaly = fingerprints.check(domain, fingerprint);
+            fingerprints.record(domain, fingerprint.clone());
+        }
+        let saved = match serde_json::to_value(&*result) {
+            Ok(value) => {
+                store
+                    .save(StoredAnalysis::new(tenant, raw, snapshot, value))
+                    .await
+            }
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = saved {
+            log::warn!("Storing analysis {} failed: {}", result.id, e);
+        }
+    }
+
+    fn record(&self, tenant: &str, verdict: &str) {
+        let mut analyses = self.analyses.lock().unwrap();
+        *analyses
+            .entry((tenant.to_string(), verdict.to_string()))
+            .or_default() += 1;
+    }
+
+    /// Adds the timings of a fresh analysis to the totals in `/metrics`
+    fn record_timings(&self, result: &AnalysisResult) {
+        if let Some(timings) = &result.timings {
+            self.timings.lock().unwrap().add(timings);
+        }
+    }
+
+    /// Sends an answered result to the analytics sink, if any
+    fn publish(&self, tenant: &str, result: &impl serde::Serialize) {
+        let Some(analytics) = &self.analytics else {
+            return;
+        };
+        match serde_json::to_value(result) {
+            Ok(result) => analytics.send(AnalyticsRow::new(tenant, &result)),
+            Err(e) => log::warn!("Serializing an analytics row failed: {}", e),
+        }
+    }
+}
+
+/// Shared secret guarding the admin endpoints, which are disabled without one
+struct Admin {
+    /// Names and tokens of the administrators; the name is recorded in the audit log
+    tokens: Vec<(String, String)>,
+    audit: AuditLog,
+    /// Analysts' judgements of verdicts (`FEEDBACK_LOG`)
+    feedback: FeedbackLog,
+    /// File the sender lists are persisted to (`SENDER_LISTS`)
+    lists_path: Option<String>,
+    /// File the rule settings are persisted to (`RULE_SETTINGS`)
+    rules_path: Option<String>,
+    /// File the tenants' lists and rule settings are persisted to (`TENANTS`)
+    tenants_path: Option<String>,
+    /// Serializes configuration changes, so concurrent updates are not lost
+    update: std::sync::Mutex<()>,
+}
+
+impl Admin {
+    /// Checks the `Authorization: Bearer <token>` header, returning the administrator's name
+    fn authorize(&self, http: &HttpRequest) -> Result<String, HttpResponse> {
+        if self.tokens.is_empty() {
+            return Err(HttpResponse::NotFound().finish());
+        }
+        let given = http
+            .headers()
+            .get(actix_web::http::header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        self.tokens
+            .iter()
+            .find(|(_, token)| keys_equal(given, token))
+            .map(|(name, _)| name.clone())
+            .ok_or_else(|| HttpResponse::Unauthorized().finish())
+    }
+
+    /// Applies `update` to the running sender lists of `tenant`. When it returns a
+    /// description of the change, that is audited, then the lists are persisted and
+    /// swapped in.
+    fn update_lists(
+        &self,
+        actor: &str,
+        action: &str,
+        tenant: &str,
+        analyzer: &Analyzer<DnsResolver>,
+        limits: &Limits,
+        update: impl FnOnce(&mut SenderLists) -> Option<serde_json::Value>,
+    ) -> anyhow::Result<SenderLists> {
+        let _guard = self.update.lock().unwrap();
+        let mut options = (*analyzer.options()).clone();
+        let mut lists = options.sender_lists.clone().unwrap_or_default();
+        let Some(detail) = update(&mut lists) else {
+            return Ok(lists);
+        };
+
+        // Nothing changes unless the change is on record
+        self.audit.record(actor, action, detail)?;
+        match &self.lists_path {
+            Some(path) if tenant == Tenants::DEFAULT => lists.save(path)?,
+            _ => self.save_tenant(tenant, |config| config.sender_lists = Some(lists.clone()))?,
+        }
+        options.sender_lists = Some(lists.clone());
+        analyzer.set_options(options);
+        if let Some(cache) = &limits.dedup {
+            cache.clear();
+        }
+        Ok(lists)
+    }
+
+    /// Applies `update` to the persisted configuration of `tenant` in `TENANTS`; the
+    /// default configuration is not kept there
+    fn save_tenant(
+        &self,
+        tenant: &str,
+        update: impl FnOnce(&mut ConfigBundle),
+    ) -> anyhow::Result<()> {
+        let Some(path) = &self.tenants_path else {
+            return Ok(());
+        };
+        if tenant == Tenants::DEFAULT {
+            return Ok(());
+        }
+        let mut registry = TenantRegistry::from_file(path)?;
+        let Some(entry) = registry.tenants.get_mut(tenant) else {
+            anyhow::bail!("Tenant {} is no longer in {}", tenant, path);
+        };
+        update(&mut entry.config);
+        registry.save(path)
+    }
+}
+
+/// Re-reads the configuration (`CONFIG_BUNDLE`, or `TRUST_STORE`, `PHRASES_FILE`,
+/// `REPUTATION_RULES`, `SENDER_LISTS`) and that of the tenants (`TENANTS`) and swaps
+/// them in without a restart
+///
+/// Requires `Authorization: Bearer <ADMIN_TOKEN>`. A configuration that fails to load
+/// leaves the running one in place.
+async fn reload(
+    http: HttpRequest,
+    admin: web::Data<Admin>,
+    analyzer: web::Data<Analyzer<DnsResolver>>,
+    enrichment: web::Data<Enrichment>,
+    limits: web::Data<Limits>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    let actor = match admin.authorize(&http) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+
+    let _guard = admin.update.lock().unwrap();
+    let configs = service_config_from_env().and_then(|config| {
+        let shadow = match &tenants.shadow {
+            Some(_) => shadow_config_from_env()?,
+            None => None,
+        };
+        let registry = match settings::var("TENANTS") {
+            Ok(path) => Some(TenantRegistry::from_file(&path)?),
+            Err(_) => None,
+        };
+        Ok((config, shadow, registry))
+    });
+    let (config, shadow_config, registry) = match configs {
+        Ok(configs) => configs,
+        Err(e) => {
+            log::error!("Configuration reload failed: {}", e);
+            return HttpResponse::InternalServerError().body(format!("Reload failed: {}", e));
+        }
+    };
+    let detail = serde_json::json!({
+        "config_bundle": settings::var("CONFIG_BUNDLE").ok(),
+        "tenants": settings::var("TENANTS").ok(),
+    });
+    if let Err(e) = admin.audit.record(&actor, "reload", detail) {
+        return HttpResponse::InternalServerError().body(format!("Audit log failed: {}", e));
+    }
+    analyzer.set_options(service_analysis_options(&config));
+    if let Some(registry) = &registry {
+        tenants.set_tenant_options(registry);
+    }
+    if let (Some(shadow), Some(shadow_config)) = (&tenants.shadow, shadow_config) {
+        shadow.set_options(shadow_config.analysis_options());
+    }
+    *enrichment.reputation_rules.write().unwrap() = config.reputation_rules.unwrap_or_default();
+    // Cached results were computed under the previous configuration
+    if let Some(cache) = &limits.dedup {
+        cache.clear();
+    }
+    log::info!("Configuration reloaded");
+    HttpResponse::Ok().body("Configuration reloaded")
+}
+
+#[derive(Deserialize)]
+struct ListEntries {
+    entries: Vec<String>,
+}
+
+/// Entries of `/admin/{allowlist,blocklist,vips}` of the tenant named by `X-Tenant`, or
+/// the default one
+async fn list_entries(
+    http: HttpRequest,
+    list: web::Path<String>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    if let Err(response) = admin.authorize(&http) {
+        return response;
+    }
+    let (_, analyzer) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    let Some(kind) = ListKind::from_name(&list) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let lists = analyzer.options().sender_lists.clone().unwrap_or_default();
+    HttpResponse::Ok().json(lists.list(kind))
+}
+
+/// Adds `{"entries": [...]}` to a list of the tenant named by `X-Tenant`, or the default
+/// one, and returns the updated list
+async fn add_list_entries(
+    http: HttpRequest,
+    list: web::Path<String>,
+    body: web::Json<ListEntries>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+    limits: web::Data<Limits>,
+) -> impl Responder {
+    let actor = match admin.authorize(&http) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    let (tenant, analyzer) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    let Some(kind) = ListKind::from_name(&list) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let updated = admin.update_lists(&actor, "list_add", tenant, analyzer, &limits, |lists| {
+        let added: Vec<&String> = body
+            .entries
+            .iter()
+            .filter(|entry| lists.add(kind, entry))
+            .collect();
+        (!added.is_empty())
+            .then(|| serde_json::json!({ "tenant": tenant, "list": *list, "entries": added }))
+    });
+    match updated {
+        Ok(lists) => {
+            log::info!("Added {} entries to the {}", body.entries.len(), list);
+            HttpResponse::Ok().json(lists.list(kind))
+        }
+        Err(e) => HttpResponse::InternalServerError().body(format!("Saving lists failed: {}", e)),
+    }
+}
+
+/// Removes one entry from a list of the tenant named by `X-Tenant`, or the default one
+async fn remove_list_entry(
+    http: HttpRequest,
+    path: web::Path<(String, String)>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+    limits: web::Data<Limits>,
+) -> impl Responder {
+    let actor = match admin.authorize(&http) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    let (tenant, analyzer) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    let (list, entry) = path.into_inner();
+    let Some(kind) = ListKind::from_name(&list) else {
+        return HttpResponse::NotFound().finish();
+    };
+    let mut removed = false;
+    let updated = admin.update_lists(&actor, "list_remove", tenant, analyzer, &limits, |lists| {
+        removed = lists.remove(kind, &entry);
+        removed.then(|| serde_json::json!({ "tenant": tenant, "list": list, "entry": entry }))
+    });
+    match updated {
+        Ok(_) if removed => {
+            log::info!("Removed {} from the {}", entry, list);
+            HttpResponse::NoContent().finish()
+        }
+        Ok(_) => HttpResponse::NotFound().finish(),
+        Err(e) => HttpResponse::InternalServerError().body(format!("Saving lists failed: {}", e)),
+    }
+}
+
+/// Rule suppressions of the tenant named by `X-Tenant`, or the default configuration
+async fn suppressions(
+    http: HttpRequest,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    if let Err(response) = admin.authorize(&http) {
+        return response;
+    }
+    let (_, analyzer) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    HttpResponse::Ok().json(&analyzer.options().rules.suppressions)
+}
+
+/// Suppresses a rule for a sender domain of the tenant named by `X-Tenant`, or the
+/// default configuration, until the suppression expires
+///
+/// The suppression, with its justification and expiry, is audited as
+/// `suppression_add` before it is persisted and swapped in.
+async fn add_suppression(
+    http: HttpRequest,
+    body: web::Json<Suppression>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+    limits: web::Data<Limits>,
+) -> impl Responder {
+    let actor = match admin.authorize(&http) {
+        Ok(actor) => actor,
+        Err(response) => return response,
+    };
+    let (tenant, analyzer) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    let suppression = body.into_inner();
+    if let Err(e) = suppression.validate() {
+        return HttpResponse::BadRequest().body(e.to_string());
+    }
+
+    let _guard = admin.update.lock().unwrap();
+    let mut options = (*analyzer.options()).clone();
+    options.rules.suppressions.push(suppression.clone());
+    let saved = serde_json::to_value(&suppression)
+        .map(|mut detail| {
+            detail["tenant"] = tenant.into();
+            detail
+        })
+        .map_err(anyhow::Error::from)
+        .and_then(|detail| admin.audit.record(&actor, "suppression_add", detail))
+        .and_then(|_| match &admin.rules_path {
+            Some(path) if tenant == Tenants::DEFAULT => options.rules.save(path),
+            _ => admin.save_tenant(tenant, |config| config.rules = options.rules.clone()),
+        });
+    if let Err(e) = saved {
+        return HttpResponse::InternalServerError()
+            .body(format!("Saving suppression failed: {}", e));
+    }
+    analyzer.set_options(options);
+    if let Some(cache) = &limits.dedup {
+        cache.clear();
+    }
+    log::info!(
+        "Suppressed {} for {} until {}",
+        suppression.rule_id,
+        suppression.domain,
+        suppression.expires
+    );
+    HttpResponse::Ok().json(&analyzer.options().rules.suppressions)
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    /// Return only the most recent entries
+    limit: Option<usize>,
+}
+
+/// Audit log of configuration changes, oldest first
+async fn audit(
+    http: HttpRequest,
+    query: web::Query<AuditQuery>,
+    admin: web::Data<Admin>,
+) -> impl Responder {
+    if let Err(response) = admin.authorize(&http) {
+        return response;
+    }
+    match admin.audit.entries() {
+        Ok(entries) => {
+            let skip = query
+                .limit
+                .map_or(0, |limit| entries.len().saturating_sub(limit));
+            HttpResponse::Ok().json(&entries[skip..])
+        }
+        Err(e) => {
+            HttpResponse::InternalServerError().body(format!("Reading audit log failed: {}", e))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct FeedbackRequest {
+    label: FeedbackLabel,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+/// Marks the stored analysis `id` of the tenant named by `X-Tenant`, or the default one,
+/// as a false positive or false negative
+///
+/// The analysis's verdict and the label are audited as `feedback` before the feedback
+/// is recorded.
+async fn add_feedback(
+    http: HttpRequest,
+    id: web::Path<String>,
+    body: web::Json<FeedbackRequest>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    let analyst = match admin.authorize(&http) {
+        Ok(analyst) => analyst,
+        Err(response) => return response,
+    };
+    let (tenant, _) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    let Some(store) = &tenants.store else {
+        return HttpResponse::NotFound().finish();
+    };
+    let stored = match store.load(tenant, &id).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return HttpResponse::NotFound().finish(),
         Err(e) => {
-            return HttpResponse::InternalServerError().body(format!("DNS resolver error: {}", e));
+            return HttpResponse::InternalServerError()
+                .body(format!("Reading analysis failed: {}", e));
         }
     };
+    let FeedbackRequest { label, note } = body.into_inner();
+    let detail = serde_json::json!({
+        "analysis_id": stored.id,
+        "tenant": tenant,
+        "old_verdict": stored.result["verdict"],
+        "new_verdict": label,
+    });
+    let recorded = admin
+        .audit
+        .record(&analyst, "feedback", detail)
+        .and_then(|_| admin.feedback.record(&stored.id, label, &analyst, note));
+    match recorded {
+        Ok(feedback) => {
+            log::info!(
+                "{} marked {} as {}",
+                analyst,
+                feedback.analysis_id,
+                label.as_str()
+            );
+            HttpResponse::Created().json(feedback)
+        }
+        Err(e) => {
+            HttpResponse::InternalServerError().body(format!("Saving feedback failed: {}", e))
+        }
+    }
+}
+
+/// All feedback, oldest first, as labeled data for tuning the scoring
+async fn feedback_entries(http: HttpRequest, admin: web::Data<Admin>) -> impl Responder {
+    if let Err(response) = admin.authorize(&http) {
+        return response;
+    }
+    match admin.feedback.entries() {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => {
+            HttpResponse::InternalServerError().body(format!("Reading feedback failed: {}", e))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    /// Resolve DNS again instead of using the recorded answers
+    #[serde(default)]
+    live: bool,
+}
+
+/// Re-analyzes a stored message of the tenant named by `X-Tenant`, or the default one,
+/// under its current configuration and returns what changed from the original result
+async fn replay(
+    http: HttpRequest,
+    id: web::Path<String>,
+    query: web::Query<ReplayQuery>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    if let Err(response) = admin.authorize(&http) {
+        return response;
+    }
+    let (tenant, _) = match tenants.admin_select(&http) {
+        Ok(selected) => selected,
+        Err(response) => return response,
+    };
+    let Some(store) = &tenants.store else {
+        return HttpResponse::NotFound().finish();
+    };
+    let stored = match store.load(tenant, &id).await {
+        Ok(Some(stored)) => stored,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Reading analysis failed: {}", e));
+        }
+    };
+    if !stored.has_raw() {
+        return HttpResponse::Conflict().body("The stored message was redacted or has expired");
+    }
+    let Some(analyzer) = tenants.analyzer(&stored.tenant) else {
+        return HttpResponse::Conflict().body(format!("Unknown tenant: {}", stored.tenant));
+    };
+    let parsed = match stored.raw().and_then(|raw| parse_email(&raw)) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Stored message unreadable: {}", e));
+        }
+    };
+
+    // At the depth of the original; enrichments are not replayed
+    let depth = serde_json::from_value(stored.result["depth"].clone()).unwrap_or_default();
+    let result = if query.live {
+        analyzer.analyze_at(&parsed, depth, None).await
+    } else {
+        let snapshot = &stored.dns_snapshot;
+        analyze_email_at_depth(&parsed, snapshot, &analyzer.options(), depth).await
+    };
+    let replayed = match result.and_then(|result| Ok(serde_json::to_value(result)?)) {
+        Ok(replayed) => replayed,
+        Err(e) => {
+            return HttpResponse::InternalServerError().body(format!("Analysis error: {}", e));
+        }
+    };
+    let diff = ResultDiff::between(&stored.result, &replayed);
+    HttpResponse::Ok().json(serde_json::json!({
+        "id": stored.id,
+        "tenant": stored.tenant,
+        "analyzed_at": stored.at,
+        "original": stored.result,
+        "replayed": replayed,
+        "diff": diff,
+    }))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// Only analyses from this time on; see `parse_time`
+    since: Option<String>,
+    /// Only analyses before this time
+    until: Option<String>,
+    tenant: Option<String>,
+    /// `ndjson` (default) or `parquet`
+    format: Option<String>,
+    /// Include the raw messages still kept
+    #[serde(default)]
+    raw: bool,
+}
+
+/// Stored analyses matching the query, oldest first, for offline analytics; NDJSON is
+/// streamed line by line
+async fn export_analyses(
+    http: HttpRequest,
+    query: web::Query<ExportQuery>,
+    admin: web::Data<Admin>,
+    tenants: web::Data<Tenants>,
+) -> impl Responder {
+    let analyst = match admin.authorize(&http) {
+        Ok(analyst) => analyst,
+        Err(response) => return response,
+    };
+    let Some(store) = &tenants.store else {
+        return HttpResponse::NotFound().body("No analysis store is configured");
+    };
+    let format = match query.format.as_deref().map(ExportFormat::from_name) {
+        None => ExportFormat::Ndjson,
+        Some(Some(format)) => format,
+        Some(None) => return HttpResponse::BadRequest().body("Expected format ndjson or parquet"),
+    };
+    let time = |value: &Option<String>| match value {
+        Some(value) => parse_time(value).map(Some).ok_or(value.clone()),
+        None => Ok(None),
+    };
+    let (since, until) = match (time(&query.since), time(&query.until)) {
+        (Ok(since), Ok(until)) => (since, until),
+        (Err(value), _) | (_, Err(value)) => {
+            return HttpResponse::BadRequest().body(format!("Unrecognized time '{}'", value));
+        }
+    };
+    let store_query = StoreQuery {
+        tenant: query.tenant.clone(),
+        since,
+        until,
+    };
+    let analyses = match query.raw {
+        true => store.query(&store_query).await,
+        false => store.query_results(&store_query).await,
+    };
+    let analyses = match analyses {
+        Ok(analyses) => analyses,
+        Err(e) => {
+            return HttpResponse::InternalServerError()
+                .body(format!("Reading analyses failed: {}", e));
+        }
+    };
+    log::info!(
+        "{} exported {} analyses{}",
+        analyst,
+        analyses.len(),
+        if query.raw { " with raw messages" } else { "" }
+    );
+
+    let raw = query.raw;
+    match format {
+        ExportFormat::Ndjson => {
+            let lines = analyses.into_iter().map(move |analysis| {
+                ndjson_line(&analysis, raw)
+                    .map(web::Bytes::from)
+                    .map_err(std::io::Error::other)
+            });
+            HttpResponse::Ok()
+                .content_type(format.content_type())
+                .streaming(futures::stream::iter(lines))
+        }
+        ExportFormat::Parquet => {
+            let mut body = Vec::new();
+            match export(&analyses, format, raw, &mut body) {
+                Ok(()) => HttpResponse::Ok()
+                    .content_type(format.content_type())
+                    .body(body),
+                Err(e) => HttpResponse::InternalServerError().body(format!("Export failed: {}", e)),
+            }
+        }
+    }
+}
+
+/// Prometheus text exposition, built a line at a time
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    /// Declares `name` a `counter`, `gauge`, or `summary`
+    fn kind(&mut self, name: &str, kind: &str) {
+        self.0.push_str(&format!("# TYPE {} {}\n", name, kind));
+    }
+
+    /// One sample of `name`, with its label values escaped
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            self.0.push_str(&format!("{{{}}}", labels.join(",")));
+        }
+        self.0.push_str(&format!(" {}\n", value));
+    }
+}
+
+/// Escapes a label value as the exposition format requires: backslash, double quote,
+/// and line feed
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Prometheus text exposition of the worker pool counters
+async fn metrics(
+    limits: web::Data<Limits>,
+    tenants: web::Data<Tenants>,
+    admin: web::Data<Admin>,
+) -> impl Responder {
+    let mut out = Exposition::default();
+    let stats = limits.pool.stats();
+    for (name, kind, value) in [
+        ("esd_analysis_workers", "gauge", stats.workers),
+        ("esd_analysis_in_flight", "gauge", stats.in_flight),
+        ("esd_analysis_queue_limit", "gauge", stats.max_queue),
+        ("esd_analysis_queue_depth", "gauge", stats.queued),
+    ] {
+        out.kind(name, kind);
+        out.sample(name, &[], value);
+    }
+    out.kind("esd_analysis_rejected_total", "counter");
+    out.sample("esd_analysis_rejected_total", &[], stats.rejected_total);
+    out.kind("esd_analyses_total", "counter");
+    for ((tenant, verdict), count) in tenants.analyses.lock().unwrap().iter() {
+        out.sample(
+            "esd_analyses_total",
+            &[("tenant", tenant), ("verdict", verdict)],
+            count,
+        );
+    }
+    out.kind("esd_analyses_cancelled_total", "counter");
+    out.sample(
+        "esd_analyses_cancelled_total",
+        &[],
+        tenants.cancelled.load(Ordering::Relaxed),
+    );
+
+    let timings = tenants.timings.lock().unwrap().clone();
+    out.kind("esd_check_seconds", "summary");
+    for (check, timing) in &timings.checks {
+        out.sample("esd_check_seconds_sum", &[("check", check)], timing.seconds);
+        out.sample("esd_check_seconds_count", &[("check", check)], timing.count);
+    }
+    out.kind("esd_analysis_dns_seconds", "summary");
+    for (class, timing) in &timings.dns {
+        out.sample(
+            "esd_analysis_dns_seconds_sum",
+            &[("lookup", class)],
+            timing.seconds,
+        );
+        out.sample(
+            "esd_analysis_dns_seconds_count",
+            &[("lookup", class)],
+            timing.count,
+        );
+    }
+
+    // Tenants share the default analyzer's resolver
+    let dns = tenants.default.resolver().stats();
+    out.kind("esd_dns_in_flight", "gauge");
+    out.sample("esd_dns_in_flight", &[], dns.in_flight);
+    out.kind("esd_dns_query_seconds", "summary");
+    for (record_type, query) in &dns.queries {
+        out.sample(
+            "esd_dns_query_seconds_sum",
+            &[("type", record_type)],
+            query.seconds_sum,
+        );
+        out.sample(
+            "esd_dns_query_seconds_count",
+            &[("type", record_type)],
+            query.count,
+        );
+    }
+    out.kind("esd_dns_query_seconds_max", "gauge");
+    for (record_type, query) in &dns.queries {
+        out.sample(
+            "esd_dns_query_seconds_max",
+            &[("type", record_type)],
+            query.seconds_max,
+        );
+    }
+    out.kind("esd_dns_throttled_total", "counter");
+    out.sample("esd_dns_throttled_total", &[], dns.throttled);
+    out.kind("esd_dns_queued_total", "counter");
+    out.sample("esd_dns_queued_total", &[], dns.queued);
+    out.kind("esd_dns_backend_up", "gauge");
+    for backend in &dns.backends {
+        out.sample(
+            "esd_dns_backend_up",
+            &[("backend", &backend.name)],
+            u8::from(backend.healthy),
+        );
+    }
+    out.kind("esd_dns_backend_failures_total", "counter");
+    for backend in &dns.backends {
+        out.sample(
+            "esd_dns_backend_failures_total",
+            &[("backend", &backend.name)],
+            backend.failures,
+        );
+    }
+    out.kind("esd_dns_errors_total", "counter");
+    for (cause, count) in &dns.errors {
+        out.sample("esd_dns_errors_total", &[("cause", cause)], count);
+    }
+
+    if let Some(shadow) = &tenants.shadow {
+        let stats = shadow.stats();
+        out.kind("esd_shadow_analyses_total", "counter");
+        out.sample("esd_shadow_analyses_total", &[], stats.analyses);
+        out.kind("esd_shadow_divergences_total", "counter");
+        for ((live, shadow), count) in stats.divergences {
+            let labels = [
+                ("verdict", live.as_str()),
+                ("shadow_verdict", shadow.as_str()),
+            ];
+            out.sample("esd_shadow_divergences_total", &labels, count);
+        }
+    }
+    if let Some(analytics) = &tenants.analytics {
+        let stats = analytics.stats();
+        out.kind("esd_analytics_rows_total", "counter");
+        for (outcome, count) in [
+            ("sent", stats.sent),
+            ("dropped", stats.dropped),
+            ("failed", stats.failed),
+        ] {
+            out.sample("esd_analytics_rows_total", &[("outcome", outcome)], count);
+        }
+    }
+    match admin.feedback.stats() {
+        Ok(stats) => {
+            out.kind("esd_feedback_total", "counter");
+            for (label, count) in stats {
+                out.sample("esd_feedback_total", &[("label", label.as_str())], count);
+            }
+        }
+        Err(e) => log::warn!("Reading feedback failed: {}", e),
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(out.0)
+}
+
+/// Applies the retention to the analysis store every `interval` seconds
+async fn prune_periodically(tenants: web::Data<Tenants>, retention: Retention, interval: u64) {
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval));
+    loop {
+        ticker.tick().await;
+        let Some(store) = &tenants.store else {
+            return;
+        };
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        match store.prune(None, &retention, now).await {
+            Ok(stats) if stats != PruneStats::default() => log::info!(
+                "Pruned stored analyses: {} raw messages dropped, {} removed",
+                stats.stripped,
+                stats.removed
+            ),
+            Err(e) => log::warn!("Pruning stored analyses failed: {}", e),
+            _ => {}
+        }
+    }
+}
 
-    match analyze_email(&parsed, &resolver).await {
-        Ok(result) => HttpResponse::Ok().json(result),
-        Err(e) => HttpResponse::InternalServerError().body(format!("Analysis error: {}", e)),
+/// Administrator tokens: `ADMIN_TOKEN` (recorded as `admin`) and the `name=token`
+/// pairs of `ADMIN_TOKENS`
+fn admin_tokens() -> Vec<(String, String)> {
+    let mut tokens: Vec<(String, String)> = env_list("ADMIN_TOKENS")
+        .iter()
+        .filter_map(|pair| pair.split_once('='))
+        .filter(|(_, token)| !token.is_empty())
+        .map(|(name, token)| (name.to_string(), token.to_string()))
+        .collect();
+    if let Ok(token) = settings::var("ADMIN_TOKEN")
+        && !token.is_empty()
+    {
+        tokens.push(("admin".to_string(), token));
     }
+    tokens
+}
+
+/// Settings come from the environment unless a config file gives them
+#[derive(Parser)]
+struct Args {
+    /// YAML config file, e.g. mounted from a Kubernetes ConfigMap; its settings take
+    /// precedence over environment variables
+    #[arg(long)]
+    config: Option<String>,
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    if let Some(path) = &args.config {
+        settings::load(path).map_err(std::io::Error::other)?;
+        log::info!("Loaded settings from {}", path);
+    }
 
     log::info!("Starting Email Spoof Analysis Service");
 
-    let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".into());
-    let port = std::env::var("PORT")
+    let host = settings::var("HOST").unwrap_or_else(|_| "0.0.0.0".into());
+    let port = settings::var("PORT")
         .ok()
         .and_then(|p| p.parse().ok())
         .unwrap_or(8080);
 
-    log::info!("Binding to {}:{}", host, port);
+    // Trusted authserv-ids, protected domains, text heuristics, trust store, and
+    // reputation rules, from a signed bundle or individual variables
+    let config = service_config_from_env().map_err(std::io::Error::other)?;
+    let options = service_analysis_options(&config);
+
+    // One resolver shared by all workers, so its cache, backend health, and zone rate
+    // limit are too; warm it for comma-separated high-volume sender domains
+    let resolver = resolver_from_env().map_err(std::io::Error::other)?;
+    let analyzer = Analyzer::new(resolver.clone(), options);
+    let prefetch = env_list("PREFETCH_DOMAINS");
+    if !prefetch.is_empty() {
+        let domains: Vec<&str> = prefetch.iter().map(String::as_str).collect();
+        analyzer.prefetch_domains(&domains).await;
+        log::info!("Prefetched DNS for {} domains", domains.len());
+    }
+    let analyzer = web::Data::new(analyzer);
+
+    // Mask personal data in stored analyses and forwarded verdicts
+    let redact_artifacts = env_flag("REDACT");
+
+    // Optional tenants with their own configuration, selected per request
+    let registry = match settings::var("TENANTS") {
+        Ok(path) => TenantRegistry::from_file(&path).map_err(std::io::Error::other)?,
+        Err(_) => TenantRegistry::default(),
+    };
+    let analyzers = registry
+        .tenants
+        .iter()
+        .map(|(name, tenant)| {
+            let options = service_analysis_options(&tenant.config);
+            (name.clone(), Analyzer::new(resolver.clone(), options))
+        })
+        .collect();
+    let store = analysis_store_from_env(redact_artifacts)
+        .await
+        .map_err(std::io::Error::other)?;
+    let fingerprints = match &store {
+        Some(store) => {
+            FingerprintHistory::from_results(&store.results().await.map_err(std::io::Error::other)?)
+        }
+        None => FingerprintHistory::default(),
+    };
+    let tenants = web::Data::new(Tenants {
+        default: analyzer.clone(),
+        registry,
+        analyzers,
+        analyses: Mutex::new(BTreeMap::new()),
+        cancelled: AtomicU64::new(0),
+        timings: Mutex::new(Timings::default()),
+        // Optional store of analyzed messages, replayed against later configurations
+        store,
+        fingerprints: Mutex::new(fingerprints),
+        // Optional candidate configuration evaluated on live traffic
+        shadow: shadow_config_from_env()
+            .map_err(std::io::Error::other)?
+            .map(|config| Shadow::new(config.analysis_options())),
+        // Optional analytics database fed apart from the store
+        analytics: analytics_sink_from_env().map_err(std::io::Error::other)?,
+    });
+
+    // Stored analyses are pruned periodically once a retention is configured
+    let retention = retention_from_env();
+    if tenants.store.is_some() && !retention.is_unlimited() {
+        let interval = env_number("PRUNE_INTERVAL_SECS", 3600).max(1);
+        tokio::spawn(prune_periodically(tenants.clone(), retention, interval));
+    }
+
+    // Every enrichment goes through one HTTP client following the egress policy
+    let mut egress = egress_policy_from_env();
+    egress.proxy = egress.proxy.or(config.proxy.clone());
+    let forward_client = client_builder(egress.proxy.as_deref())
+        .and_then(|builder| {
+            Ok(builder
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?)
+        })
+        .map_err(std::io::Error::other)?;
+    let fetcher: Arc<dyn HttpFetcher> =
+        Arc::new(ReqwestFetcher::new(egress).map_err(std::io::Error::other)?);
+
+    // Optional passive DNS enrichment
+    let passive_dns = settings::var("PASSIVE_DNS_URL").ok().map(|url| {
+        HttpPassiveDns::new(&url, settings::var("PASSIVE_DNS_KEY").ok(), fetcher.clone())
+    });
+
+    // Optional CT log lookups for lookalike domains
+    let ct_log = match settings::var("CT_LOG_URL") {
+        Ok(url) => Some(CrtSh::new(&url, fetcher.clone())),
+        Err(_) if env_flag("CT_LOOKUP") => Some(CrtSh::new(CRT_SH_URL, fetcher.clone())),
+        Err(_) => None,
+    };
+
+    // Optional redirect expansion of body URLs
+    let url_expander = if env_flag("EXPAND_URLS") {
+        let max_hops = env_number("MAX_REDIRECTS", DEFAULT_MAX_HOPS);
+        Some(UrlExpander::new(max_hops, fetcher.clone()))
+    } else {
+        None
+    };
+
+    // Optional name server and registrar lookups, scored against abused-provider rules
+    let reputation_rules = config.reputation_rules;
+    let rdap = if reputation_rules.is_some() || env_flag("REGISTRATION_LOOKUP") {
+        let url = settings::var("RDAP_URL").unwrap_or_else(|_| RDAP_URL.into());
+        Some(Rdap::new(&url, fetcher.clone()))
+    } else {
+        None
+    };
+
+    let enrichment = web::Data::new(Enrichment {
+        passive_dns,
+        ct_log,
+        url_expander,
+        rdap,
+        reputation_rules: std::sync::RwLock::new(reputation_rules.unwrap_or_default()),
+        early_exit: early_exit_from_env(),
+    });
+
+    // Secret of inbound-mail webhooks and optional destination for their verdicts
+    let forwarding = web::Data::new(Forwarding {
+        secret: settings::var("INBOUND_SECRET").ok(),
+        url: settings::var("INBOUND_FORWARD_URL").ok(),
+        client: forward_client,
+        redact: redact_artifacts,
+    });
+
+    // Bounded analysis concurrency; excess requests queue, then get 503 + Retry-After
+    let limits = web::Data::new(Limits {
+        pool: WorkerPool::new(
+            env_number("ANALYSIS_WORKERS", num_cpus::get() * 4).max(1),
+            env_number("ANALYSIS_QUEUE", 100),
+        ),
+        retry_after_secs: env_number("RETRY_AFTER_SECS", 1),
+        // Optional cache answering repeats of recently analyzed messages
+        dedup: match env_number("DEDUP_CACHE_SIZE", 0) {
+            0 => None,
+            size => Some(DedupCache::new(size)),
+        },
+        deadline_ms: env_number("ANALYSIS_DEADLINE_MS", 0),
+        attachments: attachment_limits_from_env(),
+    });
+
+    let admin = web::Data::new(Admin {
+        tokens: admin_tokens(),
+        audit: AuditLog::new(settings::var("AUDIT_LOG").ok()),
+        feedback: FeedbackLog::new(settings::var("FEEDBACK_LOG").ok()),
+        lists_path: settings::var("SENDER_LISTS").ok(),
+        rules_path: settings::var("RULE_SETTINGS").ok(),
+        tenants_path: settings::var("TENANTS").ok(),
+        update: std::sync::Mutex::new(()),
+    });
+
+    // Optional key signing the digests of results
+    let signer = web::Data::new(result_signer_from_env().map_err(std::io::Error::other)?);
+
+    // On shutdown, open connections get this long to finish before being closed
+    let shutdown_timeout = env_number("SHUTDOWN_TIMEOUT_SECS", 30);
 
-    HttpServer::new(|| {
+    let server = HttpServer::new(move || {
         App::new()
+            .app_data(analyzer.clone())
+            .app_data(tenants.clone())
+            .app_data(enrichment.clone())
+            .app_data(limits.clone())
+            .app_data(forwarding.clone())
+            .app_data(admin.clone())
+            .app_data(signer.clone())
+            .app_data(web::PayloadConfig::new(env_number(
+                "INBOUND_MAX_BYTES",
+                25 << 20,
+            )))
             .route("/analyze", web::post().to(analyze))
+            .route("/analyze/headers", web::post().to(analyze_headers))
+            .route("/report", web::post().to(report))
+            .route("/inbound/{provider}", web::post().to(inbound))
+            .route("/metrics", web::get().to(metrics))
+            .route("/result-signing-key", web::get().to(result_signing_key))
+            .route("/domains/{domain}/spf-lint", web::get().to(spf_lint))
+            .configure(ui_routes)
+            .route("/analyses/export", web::get().to(export_analyses))
+            .route("/analyses/feedback", web::get().to(feedback_entries))
+            .route("/analyses/{id}/feedback", web::post().to(add_feedback))
+            .route("/analyses/{id}/replay", web::post().to(replay))
+            .route("/admin/reload", web::post().to(reload))
+            .route("/admin/audit", web::get().to(audit))
+            .route("/admin/suppressions", web::get().to(suppressions))
+            .route("/admin/suppressions", web::post().to(add_suppression))
+            .route("/admin/{list}", web::get().to(list_entries))
+            .route("/admin/{list}", web::post().to(add_list_entries))
+            .route("/admin/{list}/{entry}", web::delete().to(remove_list_entry))
             .wrap(actix_web::middleware::Logger::default())
     })
-        .workers(num_cpus::get())         // spawn one worker per CPU core
-        .keep_alive(std::time::Duration::from_secs(75)) // typical production keep-alive
-        .max_connections(1_000)          // limit simultaneous connections
-        .bind((host.as_str(), port))?     // bind to dynamic host/port
-        .run()
-        .await
+    .workers(num_cpus::get()) // spawn one worker per CPU core
+    .keep_alive(std::time::Duration::from_secs(75)) // typical production keep-alive
+    .shutdown_timeout(shutdown_timeout)
+    .max_connections(1_000); // limit simultaneous connections
+
+    // A socket passed by systemd socket activation takes the place of HOST and PORT
+    let server = match systemd::inherited_listener().map_err(std::io::Error::other)? {
+        Some(listener) => {
+            log::info!("Listening on {} from systemd", listener.local_addr()?);
+            server.listen(listener)?
+        }
+        None => {
+            log::info!("Binding to {}:{}", host, port);
+            server.bind((host.as_str(), port))? // bind to dynamic host/port
+        }
+    };
+    let server = server.run();
+    systemd::notify_ready();
+    server.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Admin, Enrichment, Forwarding, Limits, Tenants, add_feedback, add_list_entries,
+        enrich_until, inbound, metrics,
+    };
+    use actix_web::test::{TestRequest, call_service, init_service, read_body};
+    use actix_web::{App, web};
+    use async_trait::async_trait;
+    use email_spoof_detector::analyzer::Analyzer;
+    use email_spoof_detector::attachments::AttachmentLimits;
+    use email_spoof_detector::audit::AuditLog;
+    use email_spoof_detector::ct::CrtSh;
+    use email_spoof_detector::dns::{DnsResolver, DnsSnapshot, ResolverTrait};
+    use email_spoof_detector::early_exit::{EarlyExit, EarlyExitPolicy};
+    use email_spoof_detector::email_verdict::{
+        AnalysisDepth, AnalysisOptions, analyze_email_at_depth,
+    };
+    use email_spoof_detector::feedback::FeedbackLog;
+    use email_spoof_detector::fingerprint::FingerprintHistory;
+    use email_spoof_detector::http::{HttpFetcher, HttpRequest, HttpResponse, MockFetcher};
+    use email_spoof_detector::parse::parse_email;
+    use email_spoof_detector::passive_dns::HttpPassiveDns;
+    use email_spoof_detector::pool::WorkerPool;
+    use email_spoof_detector::registration::{Rdap, ReputationRules};
+    use email_spoof_detector::store::{AnalysisStore, StoredAnalysis};
+    use email_spoof_detector::tenants::TenantRegistry;
+    use email_spoof_detector::timing::Timings;
+    use email_spoof_detector::url_expand::{DEFAULT_MAX_HOPS, UrlExpander};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::sync::atomic::AtomicU64;
+    use std::sync::{Arc, Mutex};
+
+    const AS_OF: i64 = 1_000 * 24 * 3600;
+
+    /// The tenants `acme` and `globex` next to the default one, keeping analyses in
+    /// `store`
+    fn tenants(store: Option<AnalysisStore>) -> Tenants {
+        let registry = serde_json::from_value(json!({"acme": {}, "globex": {}})).unwrap();
+        let analyzer = || Analyzer::new(DnsResolver::new().unwrap(), AnalysisOptions::default());
+        Tenants {
+            default: web::Data::new(analyzer()),
+            registry,
+            analyzers: BTreeMap::from([("acme".into(), analyzer()), ("globex".into(), analyzer())]),
+            analyses: Mutex::new(BTreeMap::new()),
+            cancelled: AtomicU64::new(0),
+            timings: Mutex::new(Timings::default()),
+            store,
+            fingerprints: Mutex::new(FingerprintHistory::default()),
+            shadow: None,
+            analytics: None,
+        }
+    }
+
+    /// Administration by `alice`, with token `secret`, auditing in memory
+    fn admin() -> Admin {
+        Admin {
+            tokens: vec![("alice".into(), "secret".into())],
+            audit: AuditLog::new(None),
+            feedback: FeedbackLog::new(None),
+            lists_path: None,
+            rules_path: None,
+            tenants_path: None,
+            update: std::sync::Mutex::new(()),
+        }
+    }
+
+    fn limits() -> Limits {
+        Limits {
+            pool: WorkerPool::new(1, 0),
+            retry_after_secs: 1,
+            dedup: None,
+            deadline_ms: 0,
+            attachments: AttachmentLimits::default(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_list_changes_apply_to_selected_tenant() {
+        let path = std::env::temp_dir().join(format!("web-tenants-{}.json", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        std::fs::write(&path, r#"{"acme": {}, "globex": {}}"#).unwrap();
+        let admin = web::Data::new(Admin {
+            tenants_path: Some(path.clone()),
+            ..admin()
+        });
+        let tenants = web::Data::new(tenants(None));
+        let app = init_service(
+            App::new()
+                .app_data(admin.clone())
+                .app_data(tenants.clone())
+                .app_data(web::Data::new(limits()))
+                .route("/admin/{list}", web::post().to(add_list_entries)),
+        )
+        .await;
+        let add = |tenant: &str| {
+            TestRequest::post()
+                .uri("/admin/blocklist")
+                .insert_header(("Authorization", "Bearer secret"))
+                .insert_header(("X-Tenant", tenant))
+                .set_json(json!({"entries": ["bad.example"]}))
+                .to_request()
+        };
+
+        assert_eq!(call_service(&app, add("acme")).await.status(), 200);
+        assert_eq!(call_service(&app, add("initech")).await.status(), 400);
+        let saved = TenantRegistry::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let blocked = |tenant: &str| {
+            let options = tenants.analyzer(tenant).unwrap().options();
+            let lists = options.sender_lists.clone().unwrap_or_default();
+            lists.blocklist.contains("bad.example")
+        };
+        assert!(blocked("acme"));
+        assert!(!blocked("globex") && !blocked(Tenants::DEFAULT));
+        let saved = saved.unwrap();
+        assert!(saved.tenants["acme"].config.sender_lists.is_some());
+        assert!(saved.tenants["globex"].config.sender_lists.is_none());
+        assert_eq!(admin.audit.entries().unwrap()[0].detail["tenant"], "acme");
+    }
+
+    #[actix_web::test]
+    async fn test_inbound_requires_secret() {
+        let forwarding = |secret: Option<&str>| Forwarding {
+            secret: secret.map(String::from),
+            url: None,
+            client: reqwest::Client::new(),
+            redact: false,
+        };
+        let enrichment = web::Data::new(Enrichment {
+            passive_dns: None,
+            ct_log: None,
+            url_expander: None,
+            rdap: None,
+            reputation_rules: std::sync::RwLock::new(ReputationRules::default()),
+            early_exit: EarlyExitPolicy::default(),
+        });
+        let service = |secret| {
+            App::new()
+                .app_data(web::Data::new(tenants(None)))
+                .app_data(enrichment.clone())
+                .app_data(web::Data::new(limits()))
+                .app_data(web::Data::new(forwarding(secret)))
+                .route("/inbound/{provider}", web::post().to(inbound))
+        };
+        // The Postmark body lacks the raw message, so an accepted request gets a 400
+        let post = |authorization: Option<&str>| {
+            let request = TestRequest::post()
+                .uri("/inbound/postmark")
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload("{}");
+            match authorization {
+                Some(authorization) => request.insert_header(("Authorization", authorization)),
+                None => request,
+            }
+            .to_request()
+        };
+        // inbound:s3cret and inbound:wrong
+        let (basic, wrong) = ("Basic aW5ib3VuZDpzM2NyZXQ=", "Basic aW5ib3VuZDp3cm9uZw==");
+
+        let app = init_service(service(Some("s3cret"))).await;
+        assert_eq!(call_service(&app, post(None)).await.status(), 401);
+        assert_eq!(call_service(&app, post(Some(wrong))).await.status(), 401);
+        assert_eq!(call_service(&app, post(Some(basic))).await.status(), 400);
+        let disabled = init_service(service(None)).await;
+        assert_eq!(
+            call_service(&disabled, post(Some(basic))).await.status(),
+            404
+        );
+    }
+
+    /// Name, labels, and value of an exposition sample
+    type Sample = (String, Vec<(String, String)>, f64);
+
+    /// Parses an exposition sample line
+    fn parse_sample(line: &str) -> Option<Sample> {
+        let end = line.find(['{', ' '])?;
+        let (name, mut rest) = line.split_at(end);
+        let mut labels = Vec::new();
+        if let Some(mut inner) = rest.strip_prefix('{') {
+            while let Some((label, tail)) = inner.split_once("=\"") {
+                let mut value = String::new();
+                let mut chars = tail.char_indices();
+                let close = loop {
+                    match chars.next()? {
+                        (_, '\\') => match chars.next()?.1 {
+                            'n' => value.push('\n'),
+                            c @ ('\\' | '"') => value.push(c),
+                            _ => return None,
+                        },
+                        (i, '"') => break i,
+                        (_, c) => value.push(c),
+                    }
+                };
+                labels.push((label.to_string(), value));
+                inner = &tail[close + 1..];
+                match inner.strip_prefix(',') {
+                    Some(next) => inner = next,
+                    None => break,
+                }
+            }
+            rest = inner.strip_prefix('}')?;
+        }
+        let value = rest.strip_prefix(' ')?.parse().ok()?;
+        Some((name.to_string(), labels, value))
+    }
+
+    #[actix_web::test]
+    async fn test_metrics_parse_with_escaped_labels() {
+        let tenants = tenants(None);
+        let tenant = "ac\"me\\\nco";
+        tenants
+            .analyses
+            .lock()
+            .unwrap()
+            .insert((tenant.into(), "Suspicious".into()), 3);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(limits()))
+                .app_data(web::Data::new(tenants))
+                .app_data(web::Data::new(admin()))
+                .route("/metrics", web::get().to(metrics)),
+        )
+        .await;
+        let response = call_service(&app, TestRequest::get().uri("/metrics").to_request()).await;
+        let body = String::from_utf8(read_body(response).await.to_vec()).unwrap();
+
+        let mut samples = Vec::new();
+        for line in body.lines() {
+            match line.strip_prefix("# TYPE ") {
+                Some(declaration) => {
+                    let (_, kind) = declaration.split_once(' ').unwrap();
+                    assert!(["counter", "gauge", "summary"].contains(&kind), "{}", line);
+                }
+                None => samples.push(parse_sample(line).unwrap_or_else(|| panic!("{}", line))),
+            }
+        }
+        let labels = vec![
+            ("tenant".to_string(), tenant.to_string()),
+            ("verdict".to_string(), "Suspicious".to_string()),
+        ];
+        assert!(samples.contains(&("esd_analyses_total".into(), labels, 3.0)));
+        assert!(samples.contains(&("esd_analysis_workers".into(), Vec::new(), 1.0)));
+    }
+
+    #[test]
+    fn test_reload_applies_to_every_tenant() {
+        let tenants = tenants(None);
+        let registry = serde_json::from_value(json!({
+            "acme": {"protected_domains": ["acme.example"]},
+            "globex": {"protected_domains": ["globex.example"]},
+            "initech": {"protected_domains": ["initech.example"]},
+        }))
+        .unwrap();
+        tenants.set_tenant_options(&registry);
+
+        let protected = |tenant: &str| {
+            let options = tenants.analyzer(tenant).unwrap().options();
+            options.protected_domains.clone()
+        };
+        assert_eq!(protected("acme"), ["acme.example"]);
+        assert_eq!(protected("globex"), ["globex.example"]);
+        assert!(protected(Tenants::DEFAULT).is_empty());
+        // Added tenants are served after restart
+        assert!(tenants.analyzer("initech").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_feedback_on_stored_analysis_of_tenant() {
+        let dir = std::env::temp_dir().join(format!("web-feedback-{}", std::process::id()));
+        let store = AnalysisStore::new(&dir, false).unwrap();
+        let id = "7e".repeat(32);
+        let analysis = StoredAnalysis::new(
+            "acme",
+            b"From: a@example.com\r\n\r\nbody",
+            DnsSnapshot::default(),
+            json!({"id": id, "verdict": "Suspicious"}),
+        );
+        store.save(analysis).await.unwrap();
+        let admin = web::Data::new(admin());
+        let app = init_service(
+            App::new()
+                .app_data(admin.clone())
+                .app_data(web::Data::new(tenants(Some(store))))
+                .route("/analyses/{id}/feedback", web::post().to(add_feedback)),
+        )
+        .await;
+        let feedback = |tenant: &str, id: &str| {
+            TestRequest::post()
+                .uri(&format!("/analyses/{}/feedback", id))
+                .insert_header(("Authorization", "Bearer secret"))
+                .insert_header(("X-Tenant", tenant))
+                .set_json(json!({"label": "false_positive"}))
+                .to_request()
+        };
+
+        let unknown = call_service(&app, feedback("acme", &"00".repeat(32))).await;
+        let other_tenant = call_service(&app, feedback("globex", &id)).await;
+        let own = call_service(&app, feedback("acme", &id)).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unknown.status(), 404);
+        assert_eq!(other_tenant.status(), 404);
+        assert_eq!(own.status(), 201);
+        let audit = admin.audit.entries().unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(
+            (audit[0].actor.as_str(), audit[0].action.as_str()),
+            ("alice", "feedback")
+        );
+        assert_eq!(
+            audit[0].detail,
+            json!({
+                "analysis_id": id,
+                "tenant": "acme",
+                "old_verdict": "Suspicious",
+                "new_verdict": "false_positive",
+            })
+        );
+        assert_eq!(admin.feedback.entries().unwrap().len(), 1);
+    }
+
+    /// The lookups of the enrichments, HTTP requests and DNS queries alike, in order
+    #[derive(Default)]
+    struct Recorder {
+        fetcher: MockFetcher,
+        calls: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl HttpFetcher for Recorder {
+        async fn fetch(&self, request: HttpRequest) -> anyhow::Result<HttpResponse> {
+            self.calls.lock().unwrap().push(request.url.to_string());
+            self.fetcher.fetch(request).await
+        }
+    }
+
+    /// Resolves nothing, recording name server lookups
+    struct RecordingResolver(Arc<Recorder>);
+
+    #[async_trait]
+    impl ResolverTrait for RecordingResolver {
+        async fn resolve_spf(&self, _domain: &str) -> Option<String> {
+            None
+        }
+
+        async fn resolve_dmarc(&self, _domain: &str) -> Option<String> {
+            None
+        }
+
+        async fn domain_exists(&self, _domain: &str) -> bool {
+            true
+        }
+
+        async fn resolve_mx(&self, _domain: &str) -> bool {
+            true
+        }
+
+        async fn ns_hosts(&self, domain: &str) -> Vec<String> {
+            self.0.calls.lock().unwrap().push(format!("NS {}", domain));
+            Vec::new()
+        }
+    }
+
+    /// Lookups made enriching mail from a lookalike of a protected domain, which its
+    /// passive DNS history settles
+    async fn enrichment_calls(full: bool) -> Vec<String> {
+        let snapshot: DnsSnapshot = serde_json::from_value(json!({"domains": {
+            "examp1e.com": {"spf": "v=spf1 -all", "exists": true, "mx": true},
+        }}))
+        .unwrap();
+        let parsed = parse_email(
+            b"From: a@examp1e.com\r\nSubject: Invoice\r\n\r\nPay at https://bit.ly/abc\r\n",
+        )
+        .unwrap();
+        let options = AnalysisOptions {
+            protected_domains: vec!["example.com".into()],
+            ..AnalysisOptions::default()
+        };
+        let mut result = analyze_email_at_depth(&parsed, &snapshot, &options, AnalysisDepth::Deep)
+            .await
+            .unwrap();
+        result.rescore();
+
+        let history = format!(
+            r#"{{"rrname":"examp1e.com","rrtype":"A","time_first":{},"time_last":{}}}"#,
+            AS_OF - 24 * 3600,
+            AS_OF
+        );
+        let recorder = Arc::new(Recorder {
+            fetcher: MockFetcher::default().respond("https://pdns.test/examp1e.com", 200, &history),
+            ..Recorder::default()
+        });
+        let enrichment = Enrichment {
+            passive_dns: Some(HttpPassiveDns::new(
+                "https://pdns.test",
+                None,
+                recorder.clone(),
+            )),
+            ct_log: Some(CrtSh::new("https://ct.test", recorder.clone())),
+            url_expander: Some(UrlExpander::new(DEFAULT_MAX_HOPS, recorder.clone())),
+            rdap: Some(Rdap::new("https://rdap.test", recorder.clone())),
+            reputation_rules: std::sync::RwLock::new(ReputationRules::default()),
+            early_exit: EarlyExitPolicy {
+                decisive_score: Some(result.risk_score + 1),
+                skip_clean: false,
+            },
+        };
+        let analyzer = Analyzer::new(RecordingResolver(recorder.clone()), options);
+        enrich_until(
+            &mut result,
+            &parsed,
+            &analyzer,
+            &enrichment,
+            Some(AS_OF),
+            None,
+            full,
+        )
+        .await;
+        recorder.calls.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_enrichments_run_cheapest_first() {
+        assert_eq!(
+            enrichment_calls(true).await,
+            [
+                "https://pdns.test/examp1e.com",
+                "https://ct.test/?q=examp1e.com&output=json",
+                "https://rdap.test/domain/examp1e.com",
+                "NS examp1e.com",
+                "https://bit.ly/abc",
+            ]
+        );
+        // Nothing is looked up once the passive DNS history decides the message
+        assert_eq!(
+            enrichment_calls(false).await,
+            ["https://pdns.test/examp1e.com"]
+        );
+    }
+
+    /// URLs fetched while enriching a message whose passive DNS history settles it
+    async fn enriched_urls(full: bool) -> (Option<EarlyExit>, Vec<String>) {
+        let snapshot: DnsSnapshot = serde_json::from_value(json!({"domains": {
+            "new.example": {"spf": "v=spf1 -all", "exists": true, "mx": true},
+        }}))
+        .unwrap();
+        let parsed = parse_email(
+            b"From: a@new.example\r\nSubject: Invoice\r\n\r\nPay at https://bit.ly/abc\r\n",
+        )
+        .unwrap();
+        let options = AnalysisOptions::default();
+        let mut result = analyze_email_at_depth(&parsed, &snapshot, &options, AnalysisDepth::Deep)
+            .await
+            .unwrap();
+        result.rescore();
+
+        let history = format!(
+            r#"{{"rrname":"new.example","rrtype":"A","time_first":{},"time_last":{}}}"#,
+            AS_OF - 24 * 3600,
+            AS_OF
+        );
+        let fetcher = Arc::new(MockFetcher::default().respond(
+            "https://pdns.test/new.example",
+            200,
+            &history,
+        ));
+        let enrichment = Enrichment {
+            passive_dns: Some(HttpPassiveDns::new(
+                "https://pdns.test",
+                None,
+                fetcher.clone(),
+            )),
+            ct_log: None,
+            url_expander: Some(UrlExpander::new(DEFAULT_MAX_HOPS, fetcher.clone())),
+            rdap: None,
+            reputation_rules: std::sync::RwLock::new(ReputationRules::default()),
+            // Reached only once the recent DNS history counts
+            early_exit: EarlyExitPolicy {
+                decisive_score: Some(result.risk_score + 1),
+                skip_clean: false,
+            },
+        };
+        let analyzer = Analyzer::new(DnsResolver::new().unwrap(), options);
+        enrich_until(
+            &mut result,
+            &parsed,
+            &analyzer,
+            &enrichment,
+            Some(AS_OF),
+            None,
+            full,
+        )
+        .await;
+        let urls = fetcher.requests().into_iter().map(|r| r.url).collect();
+        (result.early_exit, urls)
+    }
+
+    #[tokio::test]
+    async fn test_settled_verdict_skips_later_enrichments() {
+        let (early_exit, urls) = enriched_urls(false).await;
+        assert_eq!(early_exit, Some(EarlyExit::Decisive));
+        assert_eq!(urls, ["https://pdns.test/new.example"]);
+
+        let (early_exit, urls) = enriched_urls(true).await;
+        assert_eq!(early_exit, None);
+        assert_eq!(
+            urls,
+            ["https://pdns.test/new.example", "https://bit.ly/abc"]
+        );
+    }
 }
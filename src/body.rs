@@ -0,0 +1,170 @@
+use crate::attachments::Attachment;
+use crate::deobfuscate::deobfuscate;
+use crate::idn::{DomainLabels, domain_labels};
+use crate::lookalike::{LookalikeMatch, find_lookalike};
+use crate::text_heuristics::{PhraseList, TextFindings, analyze_text};
+
+/// A link found in the message body
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct UrlEvidence {
+    /// The URL as written in the body
+    pub url: String,
+
+    /// Host of `url`
+    pub domain: Option<String>,
+
+    /// `domain` as A-label and U-label
+    pub domain_labels: Option<DomainLabels>,
+
+    /// Redirect targets followed from `url`, in order, when URL expansion is enabled.
+    pub redirect_chain: Vec<String>,
+
+    /// Host of the last URL in the redirect chain, or of `url` when not expanded
+    pub landing_domain: Option<String>,
+
+    /// `landing_domain` as A-label and U-label
+    pub landing_domain_labels: Option<DomainLabels>,
+
+    /// The protected domain the landing domain imitates, if any.
+    pub lookalike: Option<LookalikeMatch>,
+
+    /// Name of the attachment the link was found in; `None` for links in the body
+    pub attachment: Option<String>,
+}
+
+/// Evidence collected from the message body
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct BodyEvidence {
+    pub urls: Vec<UrlEvidence>,
+
+    /// Social-engineering phrases found, when text heuristics are enabled.
+    pub text: Option<TextFindings>,
+}
+
+/// Extracts the links in `body` and checks their domains against the protected domains
+///
+/// The body is deobfuscated first so encoded or split links are not missed. With a
+/// phrase list, the text is also checked for social-engineering patterns.
+pub fn analyze_body(
+    body: &str,
+    protected_domains: &[String],
+    phrases: Option<&PhraseList>,
+) -> BodyEvidence {
+    let urls = extract_urls(&deobfuscate(body))
+        .into_iter()
+        .map(|url| url_evidence(url, protected_domains, None))
+        .collect();
+    let text = phrases.map(|p| analyze_text(body, p));
+
+    BodyEvidence { urls, text }
+}
+
+/// Adds the http(s) link targets of PDF attachments to the body's links, so they are
+/// checked and expanded like links in the text
+pub fn add_attachment_urls(
+    body: &mut BodyEvidence,
+    attachments: &[Attachment],
+    protected_domains: &[String],
+) {
+    for attachment in attachments {
+        let Some(pdf) = &attachment.pdf else {
+            continue;
+        };
+        let name = attachment
+            .filename
+            .clone()
+            .unwrap_or_else(|| attachment.content_type.clone());
+        for url in extract_urls(&pdf.uris.join(" ")) {
+            if !body.urls.iter().any(|u| u.url == url) {
+                body.urls
+                    .push(url_evidence(url, protected_domains, Some(name.clone())));
+            }
+        }
+    }
+}
+
+fn url_evidence(
+    url: String,
+    protected_domains: &[String],
+    attachment: Option<String>,
+) -> UrlEvidence {
+    let domain = url_domain(&url);
+    let lookalike = domain
+        .as_deref()
+        .and_then(|d| find_lookalike(d, protected_domains));
+    let labels = domain.as_deref().map(domain_labels);
+    UrlEvidence {
+        url,
+        landing_domain: domain.clone(),
+        landing_domain_labels: labels.clone(),
+        domain_labels: labels,
+        domain,
+        redirect_chain: Vec::new(),
+        lookalike,
+        attachment,
+    }
+}
+
+/// Finds the distinct http(s) URLs in `text`, in order of appearance
+pub fn extract_urls(text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    let lower = text.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find("http") {
+        let start = pos + offset;
+        let rest = &lower[start..];
+        if !(rest.starts_with("http://") || rest.starts_with("https://")) {
+            pos = start + 4;
+            continue;
+        }
+
+        let len = text[start..]
+            .find(|c: char| c.is_whitespace() || "\"'<>()[]{}`".contains(c))
+            .unwrap_or(text.len() - start);
+        let url = text[start..start + len].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        if url_domain(url).is_some() && !urls.iter().any(|u| u == url) {
+            urls.push(url.to_string());
+        }
+        pos = start + len.max(1);
+    }
+
+    urls
+}
+
+/// Host of a URL, normalized to a lowercase A-label
+pub fn url_domain(url: &str) -> Option<String> {
+    let parsed = url::Url::parse(url).ok()?;
+    match parsed.host()? {
+        url::Host::Domain(domain) => Some(domain.trim_end_matches('.').to_string()),
+        host => Some(host.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze_body, extract_urls};
+
+    #[test]
+    fn test_extract_urls() {
+        let body = "Click https://bit.ly/abc. Or <a href=\"HTTP://Example.com/login?x=1\">here</a>,\n\
+                    again https://bit.ly/abc and http:// nothing; httpbin";
+        assert_eq!(
+            extract_urls(body),
+            vec!["https://bit.ly/abc", "HTTP://Example.com/login?x=1"]
+        );
+    }
+
+    #[test]
+    fn test_body_url_lookalike() {
+        let body = "Verify at https://paypa1.com/verify";
+        let evidence = analyze_body(body, &["paypal.com".to_string()], None);
+        let url = &evidence.urls[0];
+        assert_eq!(url.domain.as_deref(), Some("paypa1.com"));
+        assert_eq!(url.landing_domain, url.domain);
+        assert_eq!(
+            url.lookalike.as_ref().unwrap().protected_domain,
+            "paypal.com"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use futures::StreamExt;
+
+use crate::{
+    dns::ResolverTrait,
+    lookalike::{LookalikeMatch, permutations},
+};
+
+/// Default number of candidate domains resolved at once
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// A registered permutation of an owned brand domain
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BrandCandidate {
+    #[serde(flatten)]
+    pub lookalike: LookalikeMatch,
+
+    /// The candidate publishes MX records and can therefore receive (and usually send) mail
+    pub has_mx: bool,
+}
+
+/// Generates lookalike permutations of `brand` and returns those that are registered
+///
+/// Candidates able to receive mail come first, then they are sorted by domain.
+pub async fn discover<R: ResolverTrait + Sync>(
+    resolver: &R,
+    brand: &str,
+    concurrency: usize,
+) -> Vec<BrandCandidate> {
+    let mut live: Vec<BrandCandidate> = futures::stream::iter(permutations(brand))
+        .map(|lookalike| async move {
+            if !resolver.domain_exists(&lookalike.domain).await {
+                return None;
+            }
+            let has_mx = resolver.resolve_mx(&lookalike.domain).await;
+            Some(BrandCandidate { lookalike, has_mx })
+        })
+        .buffer_unordered(concurrency.max(1))
+        .filter_map(|candidate| async move { candidate })
+        .collect()
+        .await;
+
+    live.sort_by(|a, b| {
+        b.has_mx
+            .cmp(&a.has_mx)
+            .then_with(|| a.lookalike.domain.cmp(&b.lookalike.domain))
+    });
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::discover;
+    use crate::dns::DnsSnapshot;
+    use crate::lookalike::LookalikeKind;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_discover_reports_registered_candidates() {
+        let snapshot: DnsSnapshot = serde_json::from_value(json!({
+            "domains": {
+                "example.com": {"exists": true, "mx": true},
+                "examp1e.com": {"exists": true, "mx": true},
+                "example.net": {"exists": true, "mx": false},
+                "exampel.com": {"exists": false}
+            }
+        }))
+        .unwrap();
+
+        let live = discover(&snapshot, "example.com", 4).await;
+        let domains: Vec<_> = live.iter().map(|c| c.lookalike.domain.as_str()).collect();
+        assert_eq!(domains, vec!["examp1e.com", "example.net"]);
+        assert_eq!(live[0].lookalike.kind, LookalikeKind::Homoglyph);
+        assert!(live[0].has_mx);
+        assert!(!live[1].has_mx);
+    }
+}
@@ -0,0 +1,212 @@
+use std::path::{Path, PathBuf};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{
+    email_verdict::AnalysisOptions, lists::SenderLists, registration::ReputationRules,
+    rules::RuleSettings, text_heuristics::PhraseList, trust_store::TrustStore,
+};
+
+/// Format version written to new bundles
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Environment variable holding the shared key bundles are signed with
+pub const BUNDLE_KEY_VAR: &str = "CONFIG_BUNDLE_KEY";
+
+/// Analyzer configuration promoted as one unit between deployments
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ConfigBundle {
+    /// Authserv-ids of border MTAs whose Authentication-Results are trusted
+    pub trusted_authserv_ids: Vec<String>,
+    pub protected_domains: Vec<String>,
+    /// Text heuristics phrase list; `None` disables the heuristics
+    pub phrases: Option<PhraseList>,
+    pub trust_store: Option<TrustStore>,
+    pub reputation_rules: Option<ReputationRules>,
+    /// Sender allowlist, blocklist, and VIP names
+    pub sender_lists: Option<SenderLists>,
+    /// DKIM selectors audited in domain mode besides the common ones
+    pub dkim_selectors: Vec<String>,
+    /// Disabled rules and per-domain suppressions
+    pub rules: RuleSettings,
+    /// Proxy for outbound HTTP; `EGRESS_PROXY` takes precedence
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+}
+
+impl ConfigBundle {
+    /// Bundles the given analysis options and lists
+    pub fn new(
+        options: &AnalysisOptions,
+        reputation_rules: Option<ReputationRules>,
+        dkim_selectors: Vec<String>,
+    ) -> Self {
+        Self {
+            trusted_authserv_ids: options.trusted_authserv_ids.clone(),
+            protected_domains: options.protected_domains.clone(),
+            phrases: options.text_phrases.clone(),
+            trust_store: options.trust_store.clone(),
+            reputation_rules,
+            sender_lists: options.sender_lists.clone(),
+            dkim_selectors,
+            rules: options.rules.clone(),
+            proxy: None,
+        }
+    }
+
+    pub fn analysis_options(&self) -> AnalysisOptions {
+        AnalysisOptions {
+            trusted_authserv_ids: self.trusted_authserv_ids.clone(),
+            protected_domains: self.protected_domains.clone(),
+            text_phrases: self.phrases.clone(),
+            trust_store: self.trust_store.clone(),
+            sender_lists: self.sender_lists.clone(),
+            rules: self.rules.clone(),
+            as_of: None,
+            timings: false,
+        }
+    }
+
+    /// Signs the bundle with HMAC-SHA256 under `key`
+    pub fn sign(&self, key: &[u8]) -> anyhow::Result<SignedBundle> {
+        let bundle = serde_json::to_value(self)?;
+        let signature = STANDARD.encode(mac(key, &bundle)?.finalize().into_bytes());
+        Ok(SignedBundle {
+            version: BUNDLE_VERSION,
+            bundle,
+            signature,
+        })
+    }
+
+    /// Writes the lists as the files and `config.env` read by the CLI flags and the
+    /// `TRUST_STORE`/`PHRASES_FILE`/`SENDER_LISTS`/... variables of the services
+    pub fn install(&self, dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        std::fs::create_dir_all(dir)?;
+        let mut written = Vec::new();
+        let mut env = vec![
+            format!(
+                "TRUSTED_AUTHSERV_IDS={}",
+                self.trusted_authserv_ids.join(",")
+            ),
+            format!("PROTECTED_DOMAINS={}", self.protected_domains.join(",")),
+        ];
+        if let Some(proxy) = &self.proxy {
+            env.push(format!("EGRESS_PROXY={}", proxy));
+        }
+
+        let mut write = |name: &str, var: &str, value: serde_json::Value| -> anyhow::Result<()> {
+            let path = dir.join(name);
+            std::fs::write(&path, serde_json::to_string_pretty(&value)?)?;
+            env.push(format!("{}={}", var, path.display()));
+            written.push(path);
+            Ok(())
+        };
+        if let Some(phrases) = &self.phrases {
+            write(
+                "phrases.json",
+                "PHRASES_FILE",
+                serde_json::to_value(phrases)?,
+            )?;
+        }
+        if let Some(store) = &self.trust_store {
+            write(
+                "trust-store.json",
+                "TRUST_STORE",
+                serde_json::to_value(store)?,
+            )?;
+        }
+        if let Some(rules) = &self.reputation_rules {
+            write(
+                "reputation-rules.json",
+                "REPUTATION_RULES",
+                serde_json::to_value(rules)?,
+            )?;
+        }
+        if let Some(lists) = &self.sender_lists {
+            write(
+                "sender-lists.json",
+                "SENDER_LISTS",
+                serde_json::to_value(lists)?,
+            )?;
+        }
+
+        let path = dir.join("config.env");
+        std::fs::write(&path, env.join("\n") + "\n")?;
+        written.push(path);
+        Ok(written)
+    }
+}
+
+/// A bundle together with its signature, as exported to a file
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct SignedBundle {
+    pub version: u32,
+    /// The bundle exactly as signed
+    pub bundle: serde_json::Value,
+    /// Base64 HMAC-SHA256 of the serialized bundle
+    pub signature: String,
+}
+
+impl SignedBundle {
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// The bundle, if it was signed with `key` and has not been modified since
+    pub fn verify(&self, key: &[u8]) -> anyhow::Result<ConfigBundle> {
+        if self.version != BUNDLE_VERSION {
+            anyhow::bail!("unsupported bundle version {}", self.version);
+        }
+        let signature = STANDARD.decode(&self.signature)?;
+        mac(key, &self.bundle)?
+            .verify_slice(&signature)
+            .map_err(|_| anyhow::anyhow!("bundle signature does not match"))?;
+        Ok(serde_json::from_value(self.bundle.clone())?)
+    }
+}
+
+fn mac(key: &[u8], bundle: &serde_json::Value) -> anyhow::Result<Hmac<Sha256>> {
+    if key.is_empty() {
+        anyhow::bail!("empty bundle signing key");
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    mac.update(serde_json::to_string(bundle)?.as_bytes());
+    Ok(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigBundle;
+    use crate::registration::ReputationRules;
+
+    #[test]
+    fn test_bundle_signature_round_trip() {
+        let bundle = ConfigBundle {
+            protected_domains: vec!["example.com".to_string()],
+            trusted_authserv_ids: vec!["mx.example.com".to_string()],
+            reputation_rules: Some(ReputationRules {
+                registrars: vec!["9999".to_string()],
+                nameservers: Vec::new(),
+            }),
+            ..ConfigBundle::default()
+        };
+        let signed = bundle.sign(b"staging-to-prod").unwrap();
+
+        let json = serde_json::to_string(&signed).unwrap();
+        let imported: super::SignedBundle = serde_json::from_str(&json).unwrap();
+        let verified = imported.verify(b"staging-to-prod").unwrap();
+        assert_eq!(verified.protected_domains, bundle.protected_domains);
+        assert_eq!(
+            verified.analysis_options().trusted_authserv_ids,
+            vec!["mx.example.com"]
+        );
+
+        assert!(imported.verify(b"another key").is_err());
+        let mut tampered = imported.clone();
+        tampered.bundle["protected_domains"] = serde_json::json!([]);
+        assert!(tampered.verify(b"staging-to-prod").is_err());
+    }
+}
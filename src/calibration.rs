@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    email_verdict::{AnalysisResult, Verdict},
+    rules::rule_key,
+};
+
+/// Verdicts, in the order reported
+const VERDICTS: &[Verdict] = &[
+    Verdict::Authenticated,
+    Verdict::PolicyViolation,
+    Verdict::Unauthenticated,
+    Verdict::Suspicious,
+    Verdict::Indeterminate,
+    Verdict::Automated,
+];
+
+/// Messages a rule must fire on before its precision is judged
+const MIN_FIRINGS: usize = 3;
+
+/// Precision below which a rule is suggested for suppression
+const MIN_RULE_PRECISION: f64 = 0.5;
+
+/// Whether mail with this expected verdict should be flagged: policy violations,
+/// suspicious and unauthenticated mail
+pub fn is_threat(verdict: Verdict) -> bool {
+    matches!(
+        verdict,
+        Verdict::PolicyViolation | Verdict::Suspicious | Verdict::Unauthenticated
+    )
+}
+
+/// Reads a manifest of labeled messages: `path,expected_verdict` per line
+///
+/// Blank lines, `#` comments, and a `path,...` header line are skipped. Verdicts are
+/// named as in results (`PolicyViolation`) or in snake or kebab case.
+pub fn parse_manifest(text: &str) -> anyhow::Result<Vec<(String, Verdict)>> {
+    let mut labeled = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((path, verdict)) = line.rsplit_once(',') else {
+            anyhow::bail!("line {}: expected path,expected_verdict", i + 1);
+        };
+        let (path, verdict) = (path.trim().trim_matches('"'), verdict.trim());
+        match Verdict::from_name(verdict) {
+            Some(verdict) => labeled.push((path.to_string(), verdict)),
+            None if i == 0 && path.eq_ignore_ascii_case("path") => {}
+            None => anyhow::bail!("line {}: unknown verdict '{}'", i + 1, verdict),
+        }
+    }
+    Ok(labeled)
+}
+
+/// A labeled message and its analysis
+#[derive(Debug, Clone, Copy)]
+pub struct Sample<'a> {
+    pub name: &'a str,
+    pub expected: Verdict,
+    pub result: &'a AnalysisResult,
+}
+
+/// How often the analyzer gave a verdict, and how often rightly
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct VerdictStats {
+    pub verdict: Verdict,
+    /// Messages labeled with the verdict
+    pub expected: usize,
+    /// Messages given the verdict
+    pub predicted: usize,
+    /// Messages labeled with and given the verdict
+    pub correct: usize,
+    /// `correct / predicted`; `None` when never given
+    pub precision: Option<f64>,
+    /// `correct / expected`; `None` when never labeled
+    pub recall: Option<f64>,
+}
+
+/// How well a rule firing predicts a threat (see [`is_threat`])
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RuleStats {
+    pub rule_id: String,
+    pub key: String,
+    /// Messages the rule fired on
+    pub fired: usize,
+    /// Of those, messages labeled as threats
+    pub fired_on_threats: usize,
+    /// `fired_on_threats / fired`
+    pub precision: f64,
+    /// Share of the threats the rule fired on; `None` without threats in the corpus
+    pub recall: Option<f64>,
+}
+
+/// The risk score cutoff that best separates threats from the rest
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ThresholdSuggestion {
+    /// Flag messages scoring at least this
+    pub risk_score: u32,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+/// A message given a verdict other than its label
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Misclassified {
+    pub name: String,
+    pub expected: Verdict,
+    pub verdict: Verdict,
+    pub risk_score: u32,
+}
+
+/// Precision and recall of a labeled corpus, with suggested adjustments
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Calibration {
+    pub messages: usize,
+    /// Messages given their labeled verdict
+    pub correct: usize,
+    pub verdicts: Vec<VerdictStats>,
+    /// Rules that fired at all, by rule ID
+    pub rules: Vec<RuleStats>,
+    /// `None` when the corpus lacks threats or other mail
+    pub threshold: Option<ThresholdSuggestion>,
+    pub misclassified: Vec<Misclassified>,
+    /// Adjustments worth trying, in words
+    pub suggestions: Vec<String>,
+}
+
+fn ratio(part: usize, whole: usize) -> Option<f64> {
+    (whole > 0).then(|| part as f64 / whole as f64)
+}
+
+/// Compares the analyses of a labeled corpus with its labels
+pub fn calibrate(samples: &[Sample]) -> Calibration {
+    let verdicts = VERDICTS
+        .iter()
+        .map(|&verdict| {
+            let expected = samples.iter().filter(|s| s.expected == verdict).count();
+            let predicted = samples
+                .iter()
+                .filter(|s| s.result.verdict == verdict)
+                .count();
+            let correct = samples
+                .iter()
+                .filter(|s| s.expected == verdict && s.result.verdict == verdict)
+                .count();
+            VerdictStats {
+                verdict,
+                expected,
+                predicted,
+                correct,
+                precision: ratio(correct, predicted),
+                recall: ratio(correct, expected),
+            }
+        })
+        .filter(|stats| stats.expected > 0 || stats.predicted > 0)
+        .collect();
+
+    let threats = samples.iter().filter(|s| is_threat(s.expected)).count();
+    let mut firings: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for sample in samples {
+        let mut fired: Vec<&str> = sample
+            .result
+            .reasons
+            .iter()
+            .map(|r| r.rule_id.as_str())
+            .collect();
+        fired.sort_unstable();
+        fired.dedup();
+        for rule_id in fired {
+            let (count, on_threats) = firings.entry(rule_id).or_default();
+            *count += 1;
+            *on_threats += usize::from(is_threat(sample.expected));
+        }
+    }
+    let rules: Vec<RuleStats> = firings
+        .into_iter()
+        .filter(|(rule_id, _)| !rule_id.is_empty())
+        .map(|(rule_id, (fired, fired_on_threats))| RuleStats {
+            rule_id: rule_id.to_string(),
+            key: rule_key(rule_id).unwrap_or_default().to_string(),
+            fired,
+            fired_on_threats,
+            precision: fired_on_threats as f64 / fired as f64,
+            recall: ratio(fired_on_threats, threats),
+        })
+        .collect();
+
+    let threshold = suggest_threshold(samples, threats);
+    let misclassified: Vec<Misclassified> = samples
+        .iter()
+        .filter(|s| s.result.verdict != s.expected)
+        .map(|s| Misclassified {
+            name: s.name.to_string(),
+            expected: s.expected,
+            verdict: s.result.verdict,
+            risk_score: s.result.risk_score,
+        })
+        .collect();
+
+    let mut suggestions = Vec::new();
+    if let Some(threshold) = &threshold {
+        suggestions.push(format!(
+            "Flag messages with a risk score of {} or more: precision {:.2}, recall {:.2}",
+            threshold.risk_score, threshold.precision, threshold.recall
+        ));
+    }
+    for rule in &rules {
+        if rule.fired >= MIN_FIRINGS && rule.precision < MIN_RULE_PRECISION {
+            suggestions.push(format!(
+                "{} ({}) fired on {} messages, {} of them not threats: consider disabling it or suppressing it for the senders involved",
+                rule.rule_id,
+                rule.key,
+                rule.fired,
+                rule.fired - rule.fired_on_threats
+            ));
+        }
+    }
+
+    Calibration {
+        messages: samples.len(),
+        correct: samples.len() - misclassified.len(),
+        verdicts,
+        rules,
+        threshold,
+        misclassified,
+        suggestions,
+    }
+}
+
+/// The cutoff with the best F1 score for telling threats from the rest; the highest
+/// one on ties, as it flags the least mail
+fn suggest_threshold(samples: &[Sample], threats: usize) -> Option<ThresholdSuggestion> {
+    if threats == 0 || threats == samples.len() {
+        return None;
+    }
+    let mut best: Option<ThresholdSuggestion> = None;
+    for risk_score in 0..=100 {
+        let flagged: Vec<&Sample> = samples
+            .iter()
+            .filter(|s| s.result.risk_score >= risk_score)
+            .collect();
+        let caught = flagged.iter().filter(|s| is_threat(s.expected)).count();
+        let (Some(precision), Some(recall)) =
+            (ratio(caught, flagged.len()), ratio(caught, threats))
+        else {
+            continue;
+        };
+        if caught == 0 {
+            continue;
+        }
+        let f1 = 2.0 * precision * recall / (precision + recall);
+        if best.as_ref().is_none_or(|best| f1 >= best.f1) {
+            best = Some(ThresholdSuggestion {
+                risk_score,
+                precision,
+                recall,
+                f1,
+            });
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sample, calibrate, parse_manifest};
+    use crate::dns::DnsSnapshot;
+    use crate::email_verdict::{AnalysisOptions, Verdict, analyze_email_with_options};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = "path,expected_verdict\nlegit.eml,Authenticated\n# spoofs\nspoofed.eml, policy-violation\n";
+        let labeled = parse_manifest(manifest).unwrap();
+        assert_eq!(
+            labeled,
+            [
+                ("legit.eml".to_string(), Verdict::Authenticated),
+                ("spoofed.eml".to_string(), Verdict::PolicyViolation)
+            ]
+        );
+        assert!(parse_manifest("a.eml,Spoofy").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_calibrate_fixture_corpus() {
+        let snapshot: DnsSnapshot =
+            serde_json::from_str(include_str!("../fixtures/dns.json")).unwrap();
+        let corpus: [(&str, &[u8], Verdict); 3] = [
+            (
+                "legit",
+                include_bytes!("../fixtures/legit.eml"),
+                Verdict::Authenticated,
+            ),
+            (
+                "spoofed",
+                include_bytes!("../fixtures/spoofed.eml"),
+                Verdict::PolicyViolation,
+            ),
+            (
+                "list_mail",
+                include_bytes!("../fixtures/list_mail.eml"),
+                Verdict::Authenticated,
+            ),
+        ];
+        let mut results = Vec::new();
+        for (_, raw, _) in &corpus {
+            let parsed = parse_email(raw).unwrap();
+            let options = AnalysisOptions::default();
+            results.push(
+                analyze_email_with_options(&parsed, &snapshot, &options)
+                    .await
+                    .unwrap(),
+            );
+        }
+        let samples: Vec<Sample> = corpus
+            .iter()
+            .zip(&results)
+            .map(|((name, _, expected), result)| Sample {
+                name,
+                expected: *expected,
+                result,
+            })
+            .collect();
+
+        let calibration = calibrate(&samples);
+        assert_eq!(calibration.messages, 3);
+        assert_eq!(calibration.correct, 3);
+        let violations = calibration
+            .verdicts
+            .iter()
+            .find(|v| v.verdict == Verdict::PolicyViolation)
+            .unwrap();
+        assert_eq!(violations.recall, Some(1.0));
+        let threshold = calibration.threshold.unwrap();
+        assert_eq!((threshold.precision, threshold.recall), (1.0, 1.0));
+
+        // ESD-0005 fires only on the spoof
+        let rule = calibration
+            .rules
+            .iter()
+            .find(|r| r.rule_id == "ESD-0005")
+            .unwrap();
+        assert_eq!((rule.fired, rule.precision), (1, 1.0));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    email_verdict::{AnalysisResult, Verdict},
+    parse::EmailParsed,
+    reasons::Severity,
+};
+
+/// Subject token overlap (Jaccard) at which two subjects count as similar
+const SUBJECT_SIMILARITY: f64 = 0.6;
+
+/// Shared features two messages need, out of four, to belong to the same campaign
+const MIN_SHARED_FEATURES: usize = 2;
+
+/// What messages of one campaign tend to share
+#[derive(Debug, Clone, Default)]
+pub struct MessageFeatures {
+    pub from_domain: Option<String>,
+    /// Lowercased subject words, without reply prefixes and digits
+    pub subject_tokens: BTreeSet<String>,
+    pub client_ip: Option<String>,
+    pub url_domains: BTreeSet<String>,
+}
+
+impl MessageFeatures {
+    pub fn new(parsed: &EmailParsed, result: &AnalysisResult) -> Self {
+        Self {
+            from_domain: result.evidence.from_domain.clone(),
+            subject_tokens: subject_tokens(parsed.subject.as_deref().unwrap_or_default()),
+            client_ip: parsed.client_ip.clone(),
+            url_domains: result
+                .evidence
+                .body
+                .urls
+                .iter()
+                .filter_map(|u| u.landing_domain.clone().or_else(|| u.domain.clone()))
+                .collect(),
+        }
+    }
+
+    /// How many of the four clustering features `self` and `other` share
+    fn shared_features(&self, other: &MessageFeatures) -> usize {
+        let same = |a: &Option<String>, b: &Option<String>| a.is_some() && a == b;
+        [
+            same(&self.from_domain, &other.from_domain),
+            jaccard(&self.subject_tokens, &other.subject_tokens) >= SUBJECT_SIMILARITY,
+            same(&self.client_ip, &other.client_ip),
+            !self.url_domains.is_disjoint(&other.url_domains),
+        ]
+        .into_iter()
+        .filter(|shared| *shared)
+        .count()
+    }
+}
+
+/// Normalizes a subject into words, so `RE: Invoice #4411` and `Invoice #9082` match
+fn subject_tokens(subject: &str) -> BTreeSet<String> {
+    subject
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !matches!(*w, "re" | "fw" | "fwd" | "aw" | "wg" | "tr"))
+        .map(|w| {
+            w.chars()
+                .filter(|c| !c.is_ascii_digit())
+                .collect::<String>()
+        })
+        .filter(|w| !w.is_empty())
+        .collect()
+}
+
+fn jaccard(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / a.union(b).count() as f64
+}
+
+/// Groups messages into campaigns, returning the message indices of each
+///
+/// Each message joins the first campaign whose first message shares at least two of
+/// from-domain, similar subject, sending IP, and a URL domain; otherwise it starts one.
+pub fn cluster(messages: &[MessageFeatures]) -> Vec<Vec<usize>> {
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+        match clusters
+            .iter_mut()
+            .find(|c| messages[c[0]].shared_features(message) >= MIN_SHARED_FEATURES)
+        {
+            Some(cluster) => cluster.push(i),
+            None => clusters.push(vec![i]),
+        }
+    }
+    clusters
+}
+
+/// Verdict summary of one campaign
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Campaign {
+    /// Number of messages
+    pub size: usize,
+    /// Indices of the messages in the batch
+    pub messages: Vec<usize>,
+    pub from_domains: BTreeSet<String>,
+    /// Subject of the first message
+    pub subject: Option<String>,
+    pub client_ips: BTreeSet<String>,
+    pub url_domains: BTreeSet<String>,
+    /// Message count per verdict
+    pub verdicts: BTreeMap<String, usize>,
+    pub max_risk_score: u32,
+    pub severity: Severity,
+}
+
+/// What a campaign summary needs of one analyzed message, so a large batch can drop
+/// its messages and results once analyzed
+#[derive(Debug, Clone)]
+pub struct CampaignMessage {
+    pub features: MessageFeatures,
+    pub subject: Option<String>,
+    pub verdict: Verdict,
+    pub risk_score: u32,
+    pub severity: Severity,
+}
+
+impl CampaignMessage {
+    pub fn new(parsed: &EmailParsed, result: &AnalysisResult) -> Self {
+        Self {
+            features: MessageFeatures::new(parsed, result),
+            subject: parsed.subject.clone(),
+            verdict: result.verdict,
+            risk_score: result.risk_score,
+            severity: result.severity,
+        }
+    }
+}
+
+/// Clusters a batch and summarizes each campaign, largest first
+pub fn campaigns(parsed: &[EmailParsed], results: &[AnalysisResult]) -> Vec<Campaign> {
+    let messages: Vec<CampaignMessage> = parsed
+        .iter()
+        .zip(results)
+        .map(|(p, r)| CampaignMessage::new(p, r))
+        .collect();
+    campaigns_of(&messages)
+}
+
+/// [`campaigns`] of messages summarized as they were analyzed
+pub fn campaigns_of(messages: &[CampaignMessage]) -> Vec<Campaign> {
+    let features: Vec<MessageFeatures> = messages.iter().map(|m| m.features.clone()).collect();
+
+    let mut campaigns: Vec<Campaign> = cluster(&features)
+        .into_iter()
+        .map(|members| {
+            let mut campaign = Campaign {
+                size: members.len(),
+                subject: messages[members[0]].subject.clone(),
+                messages: members.clone(),
+                from_domains: BTreeSet::new(),
+                client_ips: BTreeSet::new(),
+                url_domains: BTreeSet::new(),
+                verdicts: BTreeMap::new(),
+                max_risk_score: 0,
+                severity: Severity::Info,
+            };
+            for &i in &members {
+                campaign
+                    .from_domains
+                    .extend(features[i].from_domain.clone());
+                campaign.client_ips.extend(features[i].client_ip.clone());
+                campaign
+                    .url_domains
+                    .extend(features[i].url_domains.iter().cloned());
+                *campaign
+                    .verdicts
+                    .entry(format!("{:?}", messages[i].verdict))
+                    .or_default() += 1;
+                campaign.max_risk_score = campaign.max_risk_score.max(messages[i].risk_score);
+                campaign.severity = campaign.severity.max(messages[i].severity);
+            }
+            campaign
+        })
+        .collect();
+
+    campaigns.sort_by(|a, b| b.size.cmp(&a.size).then(a.messages[0].cmp(&b.messages[0])));
+    campaigns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageFeatures, cluster, subject_tokens};
+    use std::collections::BTreeSet;
+
+    fn message(domain: &str, subject: &str, ip: &str, url: &str) -> MessageFeatures {
+        MessageFeatures {
+            from_domain: Some(domain.to_string()),
+            subject_tokens: subject_tokens(subject),
+            client_ip: Some(ip.to_string()),
+            url_domains: BTreeSet::from([url.to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_cluster_campaigns() {
+        let messages = vec![
+            message(
+                "paypa1.com",
+                "Invoice #4411 overdue",
+                "203.0.113.5",
+                "paypa1-login.com",
+            ),
+            message("other.example", "newsletter", "198.51.100.1", "example.org"),
+            // Rotated sender domain and IP, same lure and landing page
+            message(
+                "paypa1.net",
+                "RE: Invoice #9082 overdue",
+                "203.0.113.77",
+                "paypa1-login.com",
+            ),
+            message("paypa1.com", "Your account", "203.0.113.5", "other.test"),
+        ];
+
+        assert_eq!(cluster(&messages), vec![vec![0, 2, 3], vec![1]]);
+        assert_eq!(
+            subject_tokens("Fwd: Invoice #4411 overdue"),
+            subject_tokens("invoice 77 OVERDUE")
+        );
+    }
+}
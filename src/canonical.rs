@@ -0,0 +1,89 @@
+/// Canonical form of a body under the body half of a DKIM `c=` tag, e.g.
+/// `relaxed/simple`
+pub fn canonical_body(body: &[u8], canonicalization: &str) -> Vec<u8> {
+    match canonicalization.split_once('/').map(|(_, body)| body) {
+        Some("relaxed") => relaxed_body(body),
+        _ => simple_body(body),
+    }
+}
+
+/// A header in relaxed canonical form (RFC 6376 3.4.2), ending in CRLF
+pub fn relaxed_header(name: &str, value: &[u8]) -> Vec<u8> {
+    let value = collapse_whitespace(value.iter().filter(|&&b| b != b'\r' && b != b'\n'));
+    let mut out = name.trim().to_ascii_lowercase().into_bytes();
+    out.push(b':');
+    out.extend_from_slice(value.trim_ascii());
+    out.extend_from_slice(b"\r\n");
+    out
+}
+
+/// A body in relaxed canonical form (RFC 6376 3.4.4)
+pub fn relaxed_body(body: &[u8]) -> Vec<u8> {
+    let mut lines: Vec<Vec<u8>> = body
+        .split(|&b| b == b'\n')
+        .map(|line| {
+            let line = collapse_whitespace(line.strip_suffix(b"\r").unwrap_or(line).iter());
+            line.trim_ascii_end().to_vec()
+        })
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+        .into_iter()
+        .flat_map(|mut line| {
+            line.extend_from_slice(b"\r\n");
+            line
+        })
+        .collect()
+}
+
+/// A body in simple canonical form (RFC 6376 3.4.3)
+pub fn simple_body(body: &[u8]) -> Vec<u8> {
+    let mut lines: Vec<&[u8]> = body
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return b"\r\n".to_vec();
+    }
+    lines
+        .into_iter()
+        .flat_map(|line| [line, b"\r\n"].concat())
+        .collect()
+}
+
+/// Reduces every run of spaces and tabs to one space
+fn collapse_whitespace<'a>(bytes: impl Iterator<Item = &'a u8>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &b in bytes {
+        let space = b == b' ' || b == b'\t';
+        if !(space && out.last() == Some(&b' ')) {
+            out.push(if space { b' ' } else { b });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{canonical_body, relaxed_body, relaxed_header};
+
+    #[test]
+    fn test_canonicalization() {
+        assert_eq!(
+            relaxed_header("Subject", b" Hello \r\n\t  world  "),
+            b"subject:Hello world\r\n"
+        );
+        assert_eq!(relaxed_body(b" a \t b  \r\nc\n\r\n\r\n"), b" a b\r\nc\r\n");
+        assert_eq!(relaxed_body(b"\r\n\r\n"), b"");
+        assert_eq!(
+            canonical_body(b" a  b \n\n", "relaxed/simple"),
+            b" a  b \r\n"
+        );
+        assert_eq!(canonical_body(b"", "simple/simple"), b"\r\n");
+    }
+}
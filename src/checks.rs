@@ -0,0 +1,63 @@
+pub mod spf;
+
+pub use crate::{
+    dkim_coverage::dkim_coverage, dkim_replay::check_replay, received::received_timeline,
+    session::check_session,
+};
+
+use crate::{
+    lookalike::{LookalikeMatch, find_lookalike},
+    parse::{EmailParsed, extract_domain, organizational_domain},
+};
+
+/// A `Reply-To` that sends replies to a different domain than the `From` address
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ReplyToMismatch {
+    pub from_domain: String,
+    pub reply_to_domain: String,
+}
+
+/// Checks whether replies to `parsed` go to a domain unrelated to its `From` domain
+///
+/// Domains with the same organizational domain count as related, e.g. `From:
+/// news@mail.example.com` with `Reply-To: support@help.example.com`.
+pub fn reply_to_mismatch(parsed: &EmailParsed) -> Option<ReplyToMismatch> {
+    let from_domain = extract_domain(parsed.from.as_deref())?.to_ascii_lowercase();
+    let reply_to_domain = extract_domain(parsed.reply_to.as_deref())?.to_ascii_lowercase();
+    if organizational_domain(&from_domain) == organizational_domain(&reply_to_domain) {
+        return None;
+    }
+    Some(ReplyToMismatch {
+        from_domain,
+        reply_to_domain,
+    })
+}
+
+/// Checks whether `domain` imitates one of the `protected` domains
+pub fn lookalike(domain: &str, protected: &[String]) -> Option<LookalikeMatch> {
+    find_lookalike(domain, protected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reply_to_mismatch;
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_reply_to_mismatch() {
+        let check = |reply_to: &str| {
+            let raw = format!(
+                "From: CEO <ceo@example.com>\r\nReply-To: {}\r\n\r\n",
+                reply_to
+            );
+            reply_to_mismatch(&parse_email(raw.as_bytes()).unwrap())
+        };
+
+        assert!(check("assistant@example.com").is_none());
+        assert!(check("<support@help.example.com>").is_none());
+        assert!(check("billing@example.com.evil.test").is_some());
+        let mismatch = check("CEO <ceo.private@freemail.test>").unwrap();
+        assert_eq!(mismatch.from_domain, "example.com");
+        assert_eq!(mismatch.reply_to_domain, "freemail.test");
+    }
+}
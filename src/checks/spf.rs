@@ -0,0 +1,418 @@
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+use crate::{
+    dns::ResolverTrait,
+    spf_flatten::dual_cidr,
+    spf_lint::MAX_DNS_LOOKUPS,
+    trust_store::{cidr_contains, parse_cidr},
+};
+
+/// MX hosts an `mx` mechanism may name; more are a `permerror` (RFC 7208, section 4.6.4)
+const MAX_MX_HOSTS: usize = 10;
+
+/// Result of an SPF evaluation (RFC 7208, section 2.6)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpfResult {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+    None,
+    PermError,
+}
+
+/// One term on the way from a domain's SPF record to the term that decided a result
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SpfStep {
+    /// Domain whose record holds the term
+    pub domain: String,
+    /// The term, e.g. `include:_spf.google.com` or `ip4:209.85.128.0/17`
+    pub term: String,
+}
+
+/// Why an SPF evaluation came out the way it did
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SpfExplanation {
+    pub result: SpfResult,
+    /// The `include` and `redirect` terms followed, ending with the term that matched;
+    /// empty when no term matched
+    pub path: Vec<SpfStep>,
+}
+
+/// SPF result for the envelope sender (`MAIL FROM`, `Return-Path`), the identity SPF
+/// authorizes
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct EnvelopeSpf {
+    /// Domain of the `Return-Path` as received, before any SRS decoding
+    pub domain: String,
+    #[serde(flatten)]
+    pub explanation: SpfExplanation,
+}
+
+/// Evaluates the SPF record of `domain` for a message sent from `ip`
+///
+/// `ip4`, `ip6`, `a`, `mx`, `exists`, `include`, `all`, and `redirect` are evaluated,
+/// and every lookup counts towards the limit of ten. Terms with macros (`%{i}`) and
+/// the deprecated `ptr` never match, though `ptr` still counts as a lookup. A range or
+/// prefix that does not parse is a `permerror`. An IPv4-mapped IPv6 address is
+/// evaluated as IPv4.
+pub async fn evaluate<R: ResolverTrait + Sync>(dns: &R, domain: &str, ip: IpAddr) -> SpfResult {
+    explain_ip(dns, domain, ip).await.result
+}
+
+/// Evaluates the SPF record of `domain` for `ip` like [`evaluate`], and names the
+/// chain of terms that authorizes (or rejects) it, e.g. which vendor's include lets
+/// the IP send as the domain
+pub async fn explain_ip<R: ResolverTrait + Sync>(
+    dns: &R,
+    domain: &str,
+    ip: IpAddr,
+) -> SpfExplanation {
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    };
+    let mut lookups = 0;
+    let mut path = Vec::new();
+    let result = check_host(dns, domain, ip, &mut lookups, &mut path).await;
+    SpfExplanation { result, path }
+}
+
+fn check_host<'a, R: ResolverTrait + Sync>(
+    dns: &'a R,
+    domain: &'a str,
+    ip: IpAddr,
+    lookups: &'a mut usize,
+    path: &'a mut Vec<SpfStep>,
+) -> Pin<Box<dyn Future<Output = SpfResult> + Send + 'a>> {
+    Box::pin(async move {
+        let Some(record) = dns.resolve_spf(domain).await else {
+            return SpfResult::None;
+        };
+
+        let mut redirect = None;
+        for term in record.split_whitespace().skip(1) {
+            let term = term.to_ascii_lowercase();
+            let (qualifier, mechanism) = match term.chars().next() {
+                Some('-') => (SpfResult::Fail, &term[1..]),
+                Some('~') => (SpfResult::SoftFail, &term[1..]),
+                Some('?') => (SpfResult::Neutral, &term[1..]),
+                Some('+') => (SpfResult::Pass, &term[1..]),
+                _ => (SpfResult::Pass, term.as_str()),
+            };
+            let name = mechanism.split([':', '/', '=']).next().unwrap_or_default();
+            let step = SpfStep {
+                domain: domain.to_string(),
+                term: term.clone(),
+            };
+            let depth = path.len();
+
+            let matched = match name {
+                "all" => true,
+                "ip4" | "ip6" => {
+                    let range = mechanism.split_once(':').map(|(_, range)| range);
+                    let Some(range) = range.filter(|range| {
+                        parse_cidr(range).is_some_and(|(net, _)| net.is_ipv4() == (name == "ip4"))
+                    }) else {
+                        return SpfResult::PermError;
+                    };
+                    cidr_contains(range, ip)
+                }
+                "include" => {
+                    *lookups += 1;
+                    let Some((_, target)) = mechanism.split_once(':') else {
+                        return SpfResult::PermError;
+                    };
+                    if *lookups > MAX_DNS_LOOKUPS {
+                        return SpfResult::PermError;
+                    }
+                    path.push(step.clone());
+                    match check_host(dns, target, ip, lookups, path).await {
+                        SpfResult::Pass => true,
+                        SpfResult::Fail | SpfResult::SoftFail | SpfResult::Neutral => {
+                            path.truncate(depth);
+                            false
+                        }
+                        SpfResult::None | SpfResult::PermError => return SpfResult::PermError,
+                    }
+                }
+                "a" | "mx" => {
+                    *lookups += 1;
+                    if *lookups > MAX_DNS_LOOKUPS {
+                        return SpfResult::PermError;
+                    }
+                    let Some((target, v4_prefix, v6_prefix)) = dual_cidr(mechanism, domain) else {
+                        return SpfResult::PermError;
+                    };
+                    let hosts = match name {
+                        _ if target.contains('%') => Vec::new(),
+                        "a" => vec![target],
+                        _ => dns.mx_hosts(&target).await,
+                    };
+                    if hosts.len() > MAX_MX_HOSTS {
+                        return SpfResult::PermError;
+                    }
+                    let mut matched = false;
+                    for host in &hosts {
+                        matched |= dns.addresses(host).await.into_iter().any(|address| {
+                            let prefix = if address.is_ipv4() {
+                                v4_prefix
+                            } else {
+                                v6_prefix
+                            };
+                            cidr_contains(&format!("{}/{}", address, prefix), ip)
+                        });
+                        if matched {
+                            break;
+                        }
+                    }
+                    matched
+                }
+                "exists" => {
+                    *lookups += 1;
+                    if *lookups > MAX_DNS_LOOKUPS {
+                        return SpfResult::PermError;
+                    }
+                    match mechanism.split_once(':') {
+                        Some((_, target)) if !target.contains('%') => {
+                            dns.addresses(target).await.iter().any(IpAddr::is_ipv4)
+                        }
+                        _ => false,
+                    }
+                }
+                // Never matches, but costs a lookup all the same (RFC 7208, section 4.6.4)
+                "ptr" => {
+                    *lookups += 1;
+                    if *lookups > MAX_DNS_LOOKUPS {
+                        return SpfResult::PermError;
+                    }
+                    false
+                }
+                "redirect" => {
+                    redirect = mechanism
+                        .split_once('=')
+                        .map(|(_, target)| (target.to_string(), step));
+                    continue;
+                }
+                // Other modifiers, e.g. exp=
+                _ if mechanism.contains('=') => continue,
+                _ => return SpfResult::PermError,
+            };
+            if matched {
+                if name != "include" {
+                    path.push(step);
+                }
+                return qualifier;
+            }
+        }
+
+        match redirect {
+            Some((target, step)) => {
+                *lookups += 1;
+                if *lookups > MAX_DNS_LOOKUPS {
+                    return SpfResult::PermError;
+                }
+                path.push(step);
+                match check_host(dns, &target, ip, lookups, path).await {
+                    SpfResult::None => SpfResult::PermError,
+                    result => result,
+                }
+            }
+            None => SpfResult::Neutral,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpfResult, evaluate, explain_ip};
+    use crate::dns::DnsSnapshot;
+    use proptest::prelude::*;
+    use serde_json::json;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    #[tokio::test]
+    async fn test_evaluate() {
+        let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+            "example.com": {"spf": "v=spf1 ip4:192.0.2.0/24 include:_spf.esp.test ~all"},
+            "_spf.esp.test": {"spf": "v=spf1 ip6:2001:db8::/32 -all"},
+            "alias.test": {"spf": "v=spf1 redirect=example.com"},
+            "loop.test": {"spf": "v=spf1 include:loop.test -all"},
+            "dynamic.test": {
+                "spf": "v=spf1 mx/24 a:relay.dynamic.test ptr -all",
+                "mx_hosts": ["mx.dynamic.test"]
+            },
+            "mx.dynamic.test": {"addresses": ["198.51.100.9"]},
+            "relay.dynamic.test": {"addresses": ["2001:db8:1::25"]},
+            "exists.test": {"spf": "v=spf1 exists:%{i}.bl.test exists:relay.dynamic.test ?all"}
+        }}))
+        .unwrap();
+        let eval = |domain: &'static str, ip: &str| {
+            let ip = ip.parse().unwrap();
+            let dns = dns.clone();
+            async move { evaluate(&dns, domain, ip).await }
+        };
+
+        assert_eq!(eval("example.com", "192.0.2.10").await, SpfResult::Pass);
+        assert_eq!(eval("example.com", "2001:db8::1").await, SpfResult::Pass);
+        assert_eq!(
+            eval("example.com", "203.0.113.1").await,
+            SpfResult::SoftFail
+        );
+        assert_eq!(eval("alias.test", "192.0.2.10").await, SpfResult::Pass);
+        assert_eq!(eval("loop.test", "192.0.2.10").await, SpfResult::PermError);
+        assert_eq!(
+            eval("dynamic.test", "198.51.100.200").await,
+            SpfResult::Pass
+        );
+        assert_eq!(
+            eval("dynamic.test", "2001:db8:1::25").await,
+            SpfResult::Pass
+        );
+        assert_eq!(
+            eval("dynamic.test", "::ffff:198.51.100.1").await,
+            SpfResult::Pass
+        );
+        assert_eq!(eval("dynamic.test", "192.0.2.10").await, SpfResult::Fail);
+        assert_eq!(eval("exists.test", "192.0.2.10").await, SpfResult::Neutral);
+        assert_eq!(eval("unknown.test", "192.0.2.10").await, SpfResult::None);
+    }
+
+    #[tokio::test]
+    async fn test_explain_ip() {
+        let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+            "example.com": {"spf": "v=spf1 include:_spf.crm.test include:_spf.esp.test ~all"},
+            "_spf.crm.test": {"spf": "v=spf1 ip4:198.51.100.0/24 -all"},
+            "_spf.esp.test": {"spf": "v=spf1 redirect=_netblocks.esp.test"},
+            "_netblocks.esp.test": {"spf": "v=spf1 ip4:192.0.2.0/24 -all"}
+        }}))
+        .unwrap();
+
+        let explanation = explain_ip(&dns, "example.com", "192.0.2.10".parse().unwrap()).await;
+        assert_eq!(explanation.result, SpfResult::Pass);
+        let path: Vec<(&str, &str)> = explanation
+            .path
+            .iter()
+            .map(|step| (step.domain.as_str(), step.term.as_str()))
+            .collect();
+        assert_eq!(
+            path,
+            [
+                ("example.com", "include:_spf.esp.test"),
+                ("_spf.esp.test", "redirect=_netblocks.esp.test"),
+                ("_netblocks.esp.test", "ip4:192.0.2.0/24"),
+            ]
+        );
+
+        let explanation = explain_ip(&dns, "example.com", "203.0.113.1".parse().unwrap()).await;
+        assert_eq!(explanation.result, SpfResult::SoftFail);
+        assert_eq!(explanation.path.len(), 1);
+        assert_eq!(explanation.path[0].term, "~all");
+    }
+
+    #[tokio::test]
+    async fn test_too_many_mx_hosts() {
+        let hosts = |count: usize| -> Vec<String> {
+            (1..=count)
+                .map(|i| format!("mx{}.example.com", i))
+                .collect()
+        };
+        let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+            "ten.example.com": {"spf": "v=spf1 mx -all", "mx_hosts": hosts(10)},
+            "eleven.example.com": {"spf": "v=spf1 mx -all", "mx_hosts": hosts(11)},
+            "mx1.example.com": {"addresses": ["192.0.2.1"]}
+        }}))
+        .unwrap();
+        let ip = "192.0.2.1".parse().unwrap();
+
+        assert_eq!(evaluate(&dns, "ten.example.com", ip).await, SpfResult::Pass);
+        // The first host matches, but the record is broken all the same
+        assert_eq!(
+            evaluate(&dns, "eleven.example.com", ip).await,
+            SpfResult::PermError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ptr_counts_toward_lookups() {
+        let record = |ptrs: usize| format!("v=spf1 {}ip4:192.0.2.0/24 -all", "ptr ".repeat(ptrs));
+        let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+            "ten.example.com": {"spf": record(10)},
+            "eleven.example.com": {"spf": record(11)}
+        }}))
+        .unwrap();
+        let ip = "192.0.2.1".parse().unwrap();
+
+        assert_eq!(evaluate(&dns, "ten.example.com", ip).await, SpfResult::Pass);
+        assert_eq!(
+            evaluate(&dns, "eleven.example.com", ip).await,
+            SpfResult::PermError
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_prefix() {
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+        for term in [
+            "ip4:192.0.2.0/40",
+            "ip4:192.0.2.0/x",
+            "ip4:2001:db8::/32",
+            "ip6:2001:db8::/129",
+            "ip6:192.0.2.0/24",
+            "a/33",
+            "mx//129",
+        ] {
+            let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+                "example.com": {"spf": format!("v=spf1 {} +all", term), "addresses": ["192.0.2.1"]}
+            }}))
+            .unwrap();
+            assert_eq!(
+                evaluate(&dns, "example.com", ip).await,
+                SpfResult::PermError,
+                "{}",
+                term
+            );
+        }
+    }
+
+    fn spf_term() -> impl Strategy<Value = String> {
+        let qualifier = prop_oneof![Just(""), Just("+"), Just("-"), Just("~"), Just("?")];
+        let mechanism = prop_oneof![
+            Just("all".to_string()),
+            (any::<Ipv4Addr>(), 0u8..=40).prop_map(|(ip, prefix)| format!("ip4:{}/{}", ip, prefix)),
+            (any::<Ipv6Addr>(), 0u8..=140)
+                .prop_map(|(ip, prefix)| format!("ip6:{}/{}", ip, prefix)),
+            "(include|redirect|exists|a|mx|exp)[:=/]?[a-z.]{0,12}",
+            "\\PC{0,12}",
+        ];
+        (qualifier, mechanism).prop_map(|(q, m)| format!("{}{}", q, m))
+    }
+
+    proptest! {
+        #[test]
+        fn prop_evaluate_never_panics(
+            terms in proptest::collection::vec(spf_term(), 0..12),
+            ip in any::<IpAddr>(),
+        ) {
+            let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+                "example.com": {"spf": format!("v=spf1 {}", terms.join(" "))}
+            }}))
+            .unwrap();
+            futures::executor::block_on(evaluate(&dns, "example.com", ip));
+        }
+
+        #[test]
+        fn prop_evaluate_matches_ip4_range(ip in any::<Ipv4Addr>(), prefix in 0u32..=32) {
+            let network = Ipv4Addr::from(u32::from(ip) & u32::MAX.checked_shl(32 - prefix).unwrap_or(0));
+            let dns: DnsSnapshot = serde_json::from_value(json!({"domains": {
+                "example.com": {"spf": format!("v=spf1 ip4:{}/{} -all", network, prefix)}
+            }}))
+            .unwrap();
+            let result = futures::executor::block_on(evaluate(&dns, "example.com", IpAddr::V4(ip)));
+            prop_assert_eq!(result, SpfResult::Pass);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{
+    analytics::{AnalyticsConfig, AnalyticsSink},
+    attachments::AttachmentLimits,
+    bundle::{BUNDLE_KEY_VAR, ConfigBundle, SignedBundle},
+    dns::DnsResolver,
+    early_exit::EarlyExitPolicy,
+    email_verdict::AnalysisOptions,
+    http::{EgressPolicy, client_builder},
+    integrity::ResultSigner,
+    lists::SenderLists,
+    registration::ReputationRules,
+    rules::RuleSettings,
+    settings,
+    store::{AnalysisStore, Retention},
+    text_heuristics::PhraseList,
+    trust_store::TrustStore,
+    zone_limit::{ConcurrencyLimit, ZoneLimit},
+};
+
+/// Reads a numeric environment variable, falling back to `default`
+///
+/// Like all settings here, it is taken from the config file when one is loaded and
+/// sets it; see [`settings::var`].
+pub fn env_number<T: std::str::FromStr>(name: &str, default: T) -> T {
+    settings::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Reads a comma-separated list from an environment variable
+pub fn env_list(name: &str) -> Vec<String> {
+    settings::var(name)
+        .map(|values| {
+            values
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether an environment variable is set to `true` or `1`
+pub fn env_flag(name: &str) -> bool {
+    settings::var(name).is_ok_and(|v| v == "true" || v == "1")
+}
+
+/// Limits on attachment inspection: `ATTACHMENT_MAX_BYTES` (default 25 MiB) of each
+/// attachment, `ATTACHMENT_MAX_DECOMPRESSED` (default 100 MiB) decompressed from it at
+/// a ratio of at most `ATTACHMENT_MAX_RATIO` (default 100), within
+/// `ATTACHMENT_TIMEOUT_MS` (default 2000); `ATTACHMENT_OFFICE=false` skips the search of
+/// Office documents for macros
+pub fn attachment_limits_from_env() -> AttachmentLimits {
+    let default = AttachmentLimits::default();
+    AttachmentLimits {
+        max_bytes: env_number("ATTACHMENT_MAX_BYTES", default.max_bytes),
+        max_decompressed: env_number("ATTACHMENT_MAX_DECOMPRESSED", default.max_decompressed),
+        max_ratio: env_number("ATTACHMENT_MAX_RATIO", default.max_ratio),
+        part_timeout: std::time::Duration::from_millis(env_number(
+            "ATTACHMENT_TIMEOUT_MS",
+            default.part_timeout.as_millis() as u64,
+        )),
+        office: settings::var("ATTACHMENT_OFFICE")
+            .map_or(default.office, |v| v != "false" && v != "0"),
+    }
+}
+
+/// The analysis store in `ANALYSIS_STORE`, if set: a directory, `sqlite:<path>`, or a
+/// `postgres://` URL (see [`AnalysisStore::open`]). Raw messages are encrypted under the
+/// base64 32-byte key in `ANALYSIS_STORE_KEY` when that is set.
+pub async fn analysis_store_from_env(redact: bool) -> anyhow::Result<Option<AnalysisStore>> {
+    let Ok(location) = settings::var("ANALYSIS_STORE") else {
+        return Ok(None);
+    };
+    Ok(Some(open_analysis_store(&location, redact).await?))
+}
+
+/// The analysis store at `location`, with the `ANALYSIS_STORE_KEY` if set
+pub async fn open_analysis_store(location: &str, redact: bool) -> anyhow::Result<AnalysisStore> {
+    let store = AnalysisStore::open(location, redact).await?;
+    match settings::var("ANALYSIS_STORE_KEY") {
+        Ok(key) => store.with_key(&STANDARD.decode(key.trim())?),
+        Err(_) => Ok(store),
+    }
+}
+
+/// The analytics sink posting to `ANALYTICS_URL`, if set, in batches of
+/// `ANALYTICS_BATCH_SIZE` rows (default 1000) at least every `ANALYTICS_FLUSH_SECS`
+/// (default 5), holding up to `ANALYTICS_QUEUE` rows (default 100000); must be called
+/// within the Tokio runtime
+pub fn analytics_sink_from_env() -> anyhow::Result<Option<AnalyticsSink>> {
+    let Ok(url) = settings::var("ANALYTICS_URL") else {
+        return Ok(None);
+    };
+    let flush_secs = env_number("ANALYTICS_FLUSH_SECS", 5).max(1);
+    let config = AnalyticsConfig {
+        url,
+        batch_size: env_number("ANALYTICS_BATCH_SIZE", 1000),
+        flush_interval: std::time::Duration::from_secs(flush_secs),
+        queue: env_number("ANALYTICS_QUEUE", 100_000),
+    };
+    // The analytics database is internal, so the egress proxy does not apply
+    let client = client_builder(None)?
+        .timeout(std::time::Duration::from_secs(30))
+        .build()?;
+    Ok(Some(AnalyticsSink::spawn(client, config)))
+}
+
+/// Signer of result digests, from the Ed25519 private key (PKCS#8 PEM) in the file
+/// named by `RESULT_SIGNING_KEY`
+pub fn result_signer_from_env() -> anyhow::Result<Option<ResultSigner>> {
+    match settings::var("RESULT_SIGNING_KEY") {
+        Ok(path) => Ok(Some(ResultSigner::from_file(&path)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// How long stored analyses are kept: `RAW_RETENTION_DAYS` for the raw message and
+/// DNS answers, `ANALYSIS_RETENTION_DAYS` for the result; unset keeps them
+pub fn retention_from_env() -> Retention {
+    let days = |name: &str| settings::var(name).ok().and_then(|v| v.parse().ok());
+    Retention {
+        raw_days: days("RAW_RETENTION_DAYS"),
+        result_days: days("ANALYSIS_RETENTION_DAYS"),
+    }
+}
+
+/// Egress policy of the enrichments: `EGRESS_PROXY`, the comma-separated
+/// `EGRESS_ALLOWED_HOSTS`, and `EGRESS_TIMEOUT_SECS`
+pub fn egress_policy_from_env() -> EgressPolicy {
+    EgressPolicy {
+        proxy: settings::var("EGRESS_PROXY").ok(),
+        allowed_hosts: env_list("EGRESS_ALLOWED_HOSTS"),
+        timeout: settings::var("EGRESS_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(std::time::Duration::from_secs),
+    }
+}
+
+/// Per-zone DNS rate limit: `DNS_ZONE_RATE` queries per second, with bursts of
+/// `DNS_ZONE_BURST` (default 20); unset leaves queries unlimited
+pub fn zone_limit_from_env() -> Option<ZoneLimit> {
+    let per_second: f64 = settings::var("DNS_ZONE_RATE").ok()?.parse().ok()?;
+    (per_second > 0.0).then(|| ZoneLimit {
+        per_second,
+        burst: env_number("DNS_ZONE_BURST", 20.0_f64).max(1.0),
+    })
+}
+
+/// Limit on outstanding DNS queries: `DNS_MAX_IN_FLIGHT` in all and
+/// `DNS_ZONE_MAX_IN_FLIGHT` per zone; `None` when neither is set
+pub fn concurrency_limit_from_env() -> Option<ConcurrencyLimit> {
+    let limit = |name| {
+        settings::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&n: &usize| n > 0)
+    };
+    let limit = ConcurrencyLimit {
+        max_in_flight: limit("DNS_MAX_IN_FLIGHT"),
+        per_zone: limit("DNS_ZONE_MAX_IN_FLIGHT"),
+    };
+    (limit != ConcurrencyLimit::default()).then_some(limit)
+}
+
+/// The DNS resolver of the services: the comma-separated `DNS_BACKENDS` to fail over
+/// between (default `default`), cross-checked with `DNS_CROSS_CHECK`, rate limited
+/// per [`zone_limit_from_env`], and bounded per [`concurrency_limit_from_env`]
+pub fn resolver_from_env() -> anyhow::Result<DnsResolver> {
+    let specs = env_list("DNS_BACKENDS");
+    let mut resolver = if specs.is_empty() {
+        DnsResolver::new()?
+    } else {
+        DnsResolver::with_backends(&specs.iter().map(String::as_str).collect::<Vec<_>>())?
+    };
+    if env_flag("DNS_CROSS_CHECK") {
+        resolver = resolver.with_cross_check()?;
+    }
+    if let Some(limit) = zone_limit_from_env() {
+        resolver = resolver.with_zone_limit(limit);
+    }
+    if let Some(limit) = concurrency_limit_from_env() {
+        resolver = resolver.with_concurrency_limit(limit);
+    }
+    Ok(resolver)
+}
+
+/// When deep analyses skip their enrichments: from the risk score
+/// `ENRICHMENT_SKIP_SCORE`, and for authenticated messages without reasons when
+/// `ENRICHMENT_SKIP_CLEAN` is set; by default never
+pub fn early_exit_from_env() -> EarlyExitPolicy {
+    EarlyExitPolicy {
+        decisive_score: settings::var("ENRICHMENT_SKIP_SCORE")
+            .ok()
+            .and_then(|v| v.parse().ok()),
+        skip_clean: env_flag("ENRICHMENT_SKIP_CLEAN"),
+    }
+}
+
+/// Analysis options shared by the services, read from the environment
+///
+/// - `TRUSTED_AUTHSERV_IDS`: comma-separated authserv-ids of border MTAs whose
+///   Authentication-Results are trusted
+/// - `PROTECTED_DOMAINS`: comma-separated brand domains to report lookalikes of
+/// - `PHRASES_FILE` / `TEXT_HEURISTICS`: text heuristics, with a custom JSON phrase list
+/// - `TRUST_STORE`: registry of the sending infrastructure of owned domains
+/// - `SENDER_LISTS`: allowlist, blocklist, and VIP names
+/// - `RULE_SETTINGS`: disabled rules and per-domain suppressions
+pub fn analysis_options_from_env() -> anyhow::Result<AnalysisOptions> {
+    let text_phrases = match settings::var("PHRASES_FILE") {
+        Ok(path) => Some(PhraseList::from_file(&path)?),
+        Err(_) => env_flag("TEXT_HEURISTICS").then(PhraseList::default),
+    };
+    let trust_store = match settings::var("TRUST_STORE") {
+        Ok(path) => Some(TrustStore::from_file(&path)?),
+        Err(_) => None,
+    };
+    let sender_lists = match settings::var("SENDER_LISTS") {
+        Ok(path) => Some(SenderLists::from_file(&path)?),
+        Err(_) => None,
+    };
+    let rules = match settings::var("RULE_SETTINGS") {
+        Ok(path) => RuleSettings::from_file(&path)?,
+        Err(_) => RuleSettings::default(),
+    };
+
+    Ok(AnalysisOptions {
+        trusted_authserv_ids: env_list("TRUSTED_AUTHSERV_IDS"),
+        protected_domains: env_list("PROTECTED_DOMAINS"),
+        text_phrases,
+        trust_store,
+        sender_lists,
+        rules,
+        as_of: None,
+        timings: false,
+    })
+}
+
+/// The analysis options of `config` for the services, recording the time spent on each
+/// check and class of DNS lookup in results when `ANALYSIS_TIMINGS` is set
+pub fn service_analysis_options(config: &ConfigBundle) -> AnalysisOptions {
+    AnalysisOptions {
+        timings: env_flag("ANALYSIS_TIMINGS"),
+        ..config.analysis_options()
+    }
+}
+
+/// Analysis configuration of the services
+///
+/// Read from the signed bundle named by `CONFIG_BUNDLE`, verified with `CONFIG_BUNDLE_KEY`,
+/// or else from the individual variables of [`analysis_options_from_env`] and
+/// `REPUTATION_RULES`. `SENDER_LISTS` and `RULE_SETTINGS` apply in both cases. Files are re-read on every call, so this also reloads them.
+pub fn service_config_from_env() -> anyhow::Result<ConfigBundle> {
+    if let Some(mut bundle) = signed_bundle_from_env("CONFIG_BUNDLE")? {
+        // Lists managed at runtime through the admin API take precedence
+        if let Ok(path) = settings::var("SENDER_LISTS") {
+            bundle.sender_lists = Some(SenderLists::from_file(&path)?);
+        }
+        if let Ok(path) = settings::var("RULE_SETTINGS") {
+            bundle.rules = RuleSettings::from_file(&path)?;
+        }
+        return Ok(bundle);
+    }
+
+    let reputation_rules = match settings::var("REPUTATION_RULES") {
+        Ok(path) => Some(ReputationRules::from_file(&path)?),
+        Err(_) => None,
+    };
+    Ok(ConfigBundle::new(
+        &analysis_options_from_env()?,
+        reputation_rules,
+        Vec::new(),
+    ))
+}
+
+/// Candidate configuration evaluated alongside the live one: the signed bundle named by
+/// `SHADOW_CONFIG_BUNDLE`, verified with `CONFIG_BUNDLE_KEY`
+pub fn shadow_config_from_env() -> anyhow::Result<Option<ConfigBundle>> {
+    signed_bundle_from_env("SHADOW_CONFIG_BUNDLE")
+}
+
+/// The bundle file named by the variable `name`, if set
+fn signed_bundle_from_env(name: &str) -> anyhow::Result<Option<ConfigBundle>> {
+    let Ok(path) = settings::var(name) else {
+        return Ok(None);
+    };
+    let key = settings::var(BUNDLE_KEY_VAR)
+        .map_err(|_| anyhow::anyhow!("{} requires {}", name, BUNDLE_KEY_VAR))?;
+    Ok(Some(
+        SignedBundle::from_file(&path)?.verify(key.as_bytes())?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::{
+        concurrency_limit_from_env, env_list, env_number, signed_bundle_from_env,
+        zone_limit_from_env,
+    };
+    use crate::bundle::{BUNDLE_KEY_VAR, ConfigBundle};
+
+    /// Held while a test changes the environment
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn set(name: &str, value: Option<&str>) {
+        // SAFETY: the variables changed here are read only by the tests holding `ENV`
+        unsafe {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_numeric_settings() {
+        let _env = ENV.lock().unwrap();
+        set("ESD_TEST_NUMBER", Some("many"));
+        assert_eq!(env_number("ESD_TEST_NUMBER", 7), 7);
+        set("ESD_TEST_NUMBER", Some("-1"));
+        assert_eq!(env_number("ESD_TEST_NUMBER", 7_u32), 7);
+        set("ESD_TEST_NUMBER", Some(" a, ,b "));
+        assert_eq!(env_list("ESD_TEST_NUMBER"), ["a", "b"]);
+        set("ESD_TEST_NUMBER", None);
+
+        // Rates that are unparsable or not positive leave queries unlimited
+        for rate in ["fast", "0", "-5"] {
+            set("DNS_ZONE_RATE", Some(rate));
+            assert_eq!(zone_limit_from_env(), None, "{}", rate);
+        }
+        set("DNS_ZONE_RATE", Some("5"));
+        set("DNS_ZONE_BURST", Some("0"));
+        assert_eq!(zone_limit_from_env().unwrap().burst, 1.0);
+        set("DNS_ZONE_RATE", None);
+        set("DNS_ZONE_BURST", None);
+
+        set("DNS_MAX_IN_FLIGHT", Some("0"));
+        set("DNS_ZONE_MAX_IN_FLIGHT", Some("many"));
+        assert_eq!(concurrency_limit_from_env(), None);
+        set("DNS_ZONE_MAX_IN_FLIGHT", Some("4"));
+        let limit = concurrency_limit_from_env().unwrap();
+        assert_eq!((limit.max_in_flight, limit.per_zone), (None, Some(4)));
+        set("DNS_MAX_IN_FLIGHT", None);
+        set("DNS_ZONE_MAX_IN_FLIGHT", None);
+    }
+
+    #[test]
+    fn test_signed_bundle_from_env() {
+        let _env = ENV.lock().unwrap();
+        let dir = std::env::temp_dir().join(format!("bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bundle.json");
+        let signed = ConfigBundle {
+            protected_domains: vec!["example.com".to_string()],
+            ..ConfigBundle::default()
+        }
+        .sign(b"release")
+        .unwrap();
+        std::fs::write(&path, serde_json::to_string(&signed).unwrap()).unwrap();
+
+        set("ESD_TEST_BUNDLE", None);
+        set(BUNDLE_KEY_VAR, None);
+        assert!(signed_bundle_from_env("ESD_TEST_BUNDLE").unwrap().is_none());
+
+        set("ESD_TEST_BUNDLE", Some(path.to_str().unwrap()));
+        let error = signed_bundle_from_env("ESD_TEST_BUNDLE").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ESD_TEST_BUNDLE requires CONFIG_BUNDLE_KEY"
+        );
+
+        set(BUNDLE_KEY_VAR, Some("another key"));
+        let error = signed_bundle_from_env("ESD_TEST_BUNDLE").unwrap_err();
+        assert_eq!(error.to_string(), "bundle signature does not match");
+
+        set(BUNDLE_KEY_VAR, Some("release"));
+        let bundle = signed_bundle_from_env("ESD_TEST_BUNDLE").unwrap().unwrap();
+        assert_eq!(bundle.protected_domains, ["example.com"]);
+
+        std::fs::write(&path, "{\"version\": 1").unwrap();
+        assert!(signed_bundle_from_env("ESD_TEST_BUNDLE").is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(signed_bundle_from_env("ESD_TEST_BUNDLE").is_err());
+
+        set("ESD_TEST_BUNDLE", None);
+        set(BUNDLE_KEY_VAR, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,196 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::http::{HttpFetcher, HttpRequest};
+
+/// Public crt.sh instance
+pub const CRT_SH_URL: &str = "https://crt.sh";
+
+/// Certificates issued this close to the reference time count as freshly issued
+const RECENT_ISSUANCE_SECS: i64 = 30 * 24 * 3600;
+
+/// A certificate logged to Certificate Transparency
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CtCertificate {
+    pub issuer_name: String,
+    pub common_name: String,
+    /// Unix timestamp the certificate became valid
+    pub not_before: i64,
+    /// Unix timestamp the certificate was logged
+    pub logged_at: Option<i64>,
+}
+
+/// Source of CT-logged certificates for a domain
+#[async_trait]
+pub trait CtLogProvider {
+    async fn certificates(&self, domain: &str) -> anyhow::Result<Vec<CtCertificate>>;
+}
+
+/// CT search via the crt.sh JSON API
+///
+/// Queries `{base_url}/?q={domain}&output=json`.
+pub struct CrtSh {
+    fetcher: Arc<dyn HttpFetcher>,
+    base_url: String,
+}
+
+impl CrtSh {
+    pub fn new(base_url: &str, fetcher: Arc<dyn HttpFetcher>) -> Self {
+        Self {
+            fetcher,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+/// One entry of the crt.sh JSON output
+#[derive(serde::Deserialize)]
+struct CrtShEntry {
+    #[serde(default)]
+    issuer_name: String,
+    #[serde(default)]
+    common_name: String,
+    not_before: String,
+    entry_timestamp: Option<String>,
+}
+
+#[async_trait]
+impl CtLogProvider for CrtSh {
+    async fn certificates(&self, domain: &str) -> anyhow::Result<Vec<CtCertificate>> {
+        let url = url::Url::parse_with_params(
+            &format!("{}/", self.base_url),
+            &[("q", domain), ("output", "json")],
+        )?;
+        let request = HttpRequest::get(url, Duration::from_secs(20));
+        let body = self.fetcher.fetch(request).await?.text()?;
+        parse_crt_sh(&body)
+    }
+}
+
+/// Parses crt.sh JSON output, skipping entries with unreadable dates
+pub fn parse_crt_sh(body: &str) -> anyhow::Result<Vec<CtCertificate>> {
+    let entries: Vec<CrtShEntry> = serde_json::from_str(body)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|e| {
+            Some(CtCertificate {
+                not_before: parse_timestamp(&e.not_before)?,
+                logged_at: e.entry_timestamp.as_deref().and_then(parse_timestamp),
+                issuer_name: e.issuer_name,
+                common_name: e.common_name,
+            })
+        })
+        .collect())
+}
+
+/// Parses a UTC `YYYY-MM-DDTHH:MM:SS[.fff]` timestamp into Unix seconds
+pub(crate) fn parse_timestamp(value: &str) -> Option<i64> {
+    let (date, time) = value.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    let time = time.trim_end_matches('Z');
+    let time = time.split_once('.').map_or(time, |(t, _)| t);
+    let mut time = time.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hh, mm, ss) = (time.next()??, time.next()??, time.next()??);
+
+    // Days since the epoch for a proleptic Gregorian date (Howard Hinnant's algorithm)
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * (m + if m > 2 { -3 } else { 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Some(days * 86_400 + hh * 3600 + mm * 60 + ss)
+}
+
+/// What the CT logs say about a domain's certificates
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct CtFindings {
+    /// Earliest certificate validity start
+    pub first_issued: Option<i64>,
+
+    /// Latest certificate validity start
+    pub last_issued: Option<i64>,
+
+    /// A certificate was issued shortly before the message `Date` (or now when absent)
+    pub recently_issued: bool,
+
+    pub certificates: Vec<CtCertificate>,
+}
+
+/// Evaluates certificate issuance relative to the message `Date` (or `now` when absent)
+pub fn evaluate_certificates(
+    certificates: Vec<CtCertificate>,
+    message_date: Option<i64>,
+    now: i64,
+) -> CtFindings {
+    let reference = message_date.unwrap_or(now);
+    let first_issued = certificates.iter().map(|c| c.not_before).min();
+    let last_issued = certificates.iter().map(|c| c.not_before).max();
+
+    let recently_issued = certificates
+        .iter()
+        .any(|c| reference - c.not_before >= 0 && reference - c.not_before < RECENT_ISSUANCE_SECS);
+
+    CtFindings {
+        first_issued,
+        last_issued,
+        recently_issued,
+        certificates,
+    }
+}
+
+/// Fetches and evaluates the certificates logged for `domain`, as of `as_of` (default now)
+pub async fn enrich<P: CtLogProvider + Sync>(
+    provider: &P,
+    domain: &str,
+    message_date: Option<i64>,
+    as_of: Option<i64>,
+) -> anyhow::Result<CtFindings> {
+    let certificates = provider.certificates(domain).await?;
+    let now = match as_of {
+        Some(as_of) => as_of,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64,
+    };
+    Ok(evaluate_certificates(certificates, message_date, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate_certificates, parse_crt_sh, parse_timestamp};
+
+    const DAY: i64 = 24 * 3600;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00"), Some(0));
+        assert_eq!(parse_timestamp("2026-02-02T15:15:37"), Some(1_770_045_337));
+        assert_eq!(
+            parse_timestamp("2026-02-02T15:15:37.123"),
+            Some(1_770_045_337)
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+    }
+
+    #[test]
+    fn test_fresh_certificate_for_lookalike() {
+        let body = r#"[
+            {"id": 1, "issuer_name": "C=US, O=Let's Encrypt, CN=R3", "common_name": "paypa1.com",
+             "name_value": "paypa1.com", "not_before": "2026-02-01T00:00:00",
+             "entry_timestamp": "2026-02-01T00:05:00.412"},
+            {"id": 2, "common_name": "paypa1.com", "not_before": "garbage"}
+        ]"#;
+        let certificates = parse_crt_sh(body).unwrap();
+        assert_eq!(certificates.len(), 1);
+
+        let date = 1_770_045_337;
+        let findings = evaluate_certificates(certificates, Some(date), date + 400 * DAY);
+        assert!(findings.recently_issued);
+        assert_eq!(findings.first_issued, findings.last_issued);
+    }
+}
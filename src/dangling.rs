@@ -0,0 +1,268 @@
+use std::collections::HashSet;
+
+use crate::{
+    dns::ResolverTrait,
+    lint::{LintFinding, RecordSource},
+    reasons::Severity,
+    spf_lint::{include_targets, spf_records},
+};
+
+/// Include nesting followed when looking for dangling SPF includes
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Hosting suffixes where a deleted app, bucket, or site name can be claimed by anyone
+pub const TAKEOVER_PRONE_SUFFIXES: &[&str] = &[
+    "azurewebsites.net",
+    "cloudapp.net",
+    "trafficmanager.net",
+    "blob.core.windows.net",
+    "herokuapp.com",
+    "herokudns.com",
+    "s3.amazonaws.com",
+    "elasticbeanstalk.com",
+    "github.io",
+    "bitbucket.io",
+    "netlify.app",
+    "pantheonsite.io",
+    "ghost.io",
+    "myshopify.com",
+    "surge.sh",
+    "wordpress.com",
+    "zendesk.com",
+];
+
+/// Whether `host` is on a takeover-prone hosting platform
+pub fn is_takeover_prone(host: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    TAKEOVER_PRONE_SUFFIXES.iter().any(|suffix| {
+        host.strip_suffix(suffix)
+            .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+    })
+}
+
+/// Finds SPF includes, DKIM CNAMEs, and MX hosts of `domain` that point at
+/// non-existent names or takeover-prone hosting
+///
+/// Whoever registers such a name can send mail that passes SPF or DKIM for `domain`.
+pub async fn find_dangling<S: RecordSource + ResolverTrait + Sync>(
+    source: &S,
+    domain: &str,
+    selectors: &[String],
+) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+
+    // SPF includes and redirects, followed through the include tree
+    let mut visited = HashSet::new();
+    let mut pending = vec![(domain.to_ascii_lowercase(), 0)];
+    while let Some((name, depth)) = pending.pop() {
+        if depth >= MAX_INCLUDE_DEPTH || !visited.insert(name.clone()) {
+            continue;
+        }
+        let Some(record) = spf_records(source, &name)
+            .await
+            .and_then(|r| r.into_iter().next())
+        else {
+            continue;
+        };
+        for target in include_targets(&record) {
+            let issue = check_target(source, &target).await;
+            if let Some(finding) = issue.finding("spf_include", &name, &target) {
+                findings.push(finding.with_record(record.clone()));
+            } else {
+                pending.push((target, depth + 1));
+            }
+        }
+    }
+
+    // DKIM selectors delegated via CNAME, typically to an email service provider
+    for selector in selectors {
+        let name = format!("{}._domainkey.{}", selector, domain);
+        if let Some(target) = source.cname(&name).await
+            && let Some(finding) =
+                check_target(source, &target)
+                    .await
+                    .finding("dkim_cname", &name, &target)
+        {
+            findings.push(finding.with_record(format!("{} CNAME {}", name, target)));
+        }
+    }
+
+    // MX hosts
+    for host in RecordSource::mx_hosts(source, domain).await {
+        if let Some(finding) = check_target(source, &host)
+            .await
+            .finding("mx", domain, &host)
+        {
+            findings.push(finding.with_record(format!("{} MX {}", domain, host)));
+        }
+    }
+
+    findings
+}
+
+/// Why a record's target is a weakness
+enum TargetIssue {
+    None,
+    Nxdomain,
+    TakeoverProne,
+}
+
+async fn check_target<S: ResolverTrait + Sync>(source: &S, target: &str) -> TargetIssue {
+    if !source.domain_exists(target).await {
+        TargetIssue::Nxdomain
+    } else if is_takeover_prone(target) {
+        TargetIssue::TakeoverProne
+    } else {
+        TargetIssue::None
+    }
+}
+
+impl TargetIssue {
+    /// Describes the issue of a `kind` record at `name` pointing at `target`
+    fn finding(&self, kind: &str, name: &str, target: &str) -> Option<LintFinding> {
+        let label = match kind {
+            "spf_include" => "SPF include",
+            "dkim_cname" => "DKIM selector CNAME",
+            _ => "MX host",
+        };
+        match self {
+            TargetIssue::None => None,
+            TargetIssue::Nxdomain => Some(LintFinding::new(
+                "dangling_target",
+                Severity::Critical,
+                name,
+                format!(
+                    "The {} {} does not exist; whoever registers it can send mail on this domain's behalf.",
+                    label, target
+                ),
+                "Remove the record or point it at infrastructure you control.",
+            )),
+            TargetIssue::TakeoverProne => Some(LintFinding::new(
+                "takeover_prone_target",
+                Severity::Medium,
+                name,
+                format!(
+                    "The {} {} is on a hosting platform where released names can be claimed by others.",
+                    label, target
+                ),
+                "Make sure the account behind it stays provisioned, and remove the record when it is retired.",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_dangling, is_takeover_prone};
+    use crate::dns::ResolverTrait;
+    use crate::lint::RecordSource;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct Zone {
+        txt: HashMap<String, Vec<String>>,
+        cname: HashMap<String, String>,
+        mx: HashMap<String, Vec<String>>,
+        existing: Vec<String>,
+    }
+
+    #[async_trait]
+    impl RecordSource for Zone {
+        async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+            self.txt.get(name).cloned()
+        }
+
+        async fn cname(&self, name: &str) -> Option<String> {
+            self.cname.get(name).cloned()
+        }
+
+        async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+            self.mx.get(domain).cloned().unwrap_or_default()
+        }
+    }
+
+    #[async_trait]
+    impl ResolverTrait for Zone {
+        async fn resolve_spf(&self, _domain: &str) -> Option<String> {
+            None
+        }
+
+        async fn resolve_dmarc(&self, _domain: &str) -> Option<String> {
+            None
+        }
+
+        async fn domain_exists(&self, domain: &str) -> bool {
+            self.existing.iter().any(|d| d == domain)
+        }
+
+        async fn resolve_mx(&self, domain: &str) -> bool {
+            self.mx.contains_key(domain)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dangling_mail_records() {
+        let mut zone = Zone::default();
+        zone.txt.insert(
+            "example.com".to_string(),
+            vec![
+                "v=spf1 include:_spf.example.net include:spf.retired-esp.example -all".to_string(),
+            ],
+        );
+        zone.txt.insert(
+            "_spf.example.net".to_string(),
+            vec!["v=spf1 ip4:192.0.2.0/24 -all".to_string()],
+        );
+        zone.cname.insert(
+            "selector1._domainkey.example.com".to_string(),
+            "dkim.example.azurewebsites.net".to_string(),
+        );
+        zone.mx.insert(
+            "example.com".to_string(),
+            vec![
+                "mx1.example.com".to_string(),
+                "mx.old-host.example".to_string(),
+            ],
+        );
+        zone.existing = [
+            "_spf.example.net",
+            "dkim.example.azurewebsites.net",
+            "mx1.example.com",
+        ]
+        .map(String::from)
+        .to_vec();
+
+        let selectors = vec!["selector1".to_string()];
+        let findings = find_dangling(&zone, "example.com", &selectors).await;
+        let summary: Vec<_> = findings
+            .iter()
+            .map(|f| (f.code, f.record.clone().unwrap()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "dangling_target",
+                    "v=spf1 include:_spf.example.net include:spf.retired-esp.example -all"
+                        .to_string()
+                ),
+                (
+                    "takeover_prone_target",
+                    "selector1._domainkey.example.com CNAME dkim.example.azurewebsites.net"
+                        .to_string()
+                ),
+                (
+                    "dangling_target",
+                    "example.com MX mx.old-host.example".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_takeover_prone_suffixes() {
+        assert!(is_takeover_prone("mail-app.herokuapp.com."));
+        assert!(!is_takeover_prone("notherokuapp.com"));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::parse::EmailParsed;
+
+/// Canonical hash of the parts of a message the analysis looks at
+///
+/// Header values are lowercased with whitespace collapsed, and the body is hashed on
+/// its own. Per-recipient headers (`To`, `Message-ID`, `Date`, ...) are left out, so
+/// copies of one message sent to many recipients share a hash.
+pub fn message_hash(parsed: &EmailParsed) -> String {
+    let normalize = |value: Option<&str>| {
+        value
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    let signatures: Vec<String> = parsed
+        .dkim_signatures
+        .iter()
+        .map(|s| format!("{}/{}", s.domain, s.selector).to_lowercase())
+        .collect();
+
+    let mut hasher = Sha256::new();
+    for (name, value) in [
+        ("from", normalize(parsed.from.as_deref())),
+        ("subject", normalize(parsed.subject.as_deref())),
+        ("return-path", normalize(parsed.return_path.as_deref())),
+        (
+            "authentication-results",
+            normalize(parsed.auth_results.as_deref()),
+        ),
+        ("dkim-signature", signatures.join(",")),
+        ("dkim-present", parsed.dkim_present.to_string()),
+        ("client-ip", normalize(parsed.client_ip.as_deref())),
+        ("body", hex(&Sha256::digest(parsed.body.as_bytes()))),
+    ] {
+        hasher.update(format!("{}:{}\n", name, value));
+    }
+    hex(&hasher.finalize())
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Least recently used results, keyed by message hash
+#[derive(Default)]
+struct Lru {
+    tick: u64,
+    entries: HashMap<String, (u64, serde_json::Value)>,
+    /// Keys by last use, oldest first
+    order: BTreeMap<u64, String>,
+}
+
+/// In-memory cache of results of already analyzed messages
+///
+/// Repeats of a cached message get the stored result, marked `"deduplicated": true`,
+/// instead of a full analysis, so a storm of identical phish costs one analysis.
+pub struct DedupCache {
+    capacity: usize,
+    lru: Mutex<Lru>,
+}
+
+impl DedupCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            lru: Mutex::new(Lru::default()),
+        }
+    }
+
+    /// The cached result for `key`, marked as deduplicated
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        let mut lru = self.lru.lock().unwrap();
+        lru.tick += 1;
+        let tick = lru.tick;
+        let (used, result) = lru.entries.get_mut(key)?;
+        let previous = std::mem::replace(used, tick);
+        let mut result = result.clone();
+        lru.order.remove(&previous);
+        lru.order.insert(tick, key.to_string());
+
+        if let Some(fields) = result.as_object_mut() {
+            fields.insert("deduplicated".into(), true.into());
+        }
+        Some(result)
+    }
+
+    /// Stores the result for `key`, evicting the least recently used one when full
+    pub fn insert(&self, key: String, result: serde_json::Value) {
+        let mut lru = self.lru.lock().unwrap();
+        lru.tick += 1;
+        let tick = lru.tick;
+        if let Some((previous, _)) = lru.entries.insert(key.clone(), (tick, result)) {
+            lru.order.remove(&previous);
+        }
+        lru.order.insert(tick, key);
+
+        while lru.entries.len() > self.capacity {
+            let Some((_, oldest)) = lru.order.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+        }
+    }
+
+    /// Drops every cached result, e.g. after the configuration changed
+    pub fn clear(&self) {
+        let mut lru = self.lru.lock().unwrap();
+        lru.entries.clear();
+        lru.order.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lru.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DedupCache, message_hash};
+    use crate::parse::parse_email;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_hash_ignores_recipient_headers() {
+        let first = parse_email(
+            b"From: PayPal <service@paypa1.com>\r\nTo: a@corp.test\r\nMessage-ID: <1@x>\r\n\
+              Subject: Verify  your account\r\n\r\nhttps://paypa1.com/login\r\n",
+        )
+        .unwrap();
+        let second = parse_email(
+            b"From: PayPal <service@paypa1.com>\r\nTo: b@corp.test\r\nMessage-ID: <2@x>\r\n\
+              Subject: verify your account\r\n\r\nhttps://paypa1.com/login\r\n",
+        )
+        .unwrap();
+        let other = parse_email(
+            b"From: PayPal <service@paypa1.com>\r\nSubject: verify your account\r\n\r\nhello\r\n",
+        )
+        .unwrap();
+
+        assert_eq!(message_hash(&first), message_hash(&second));
+        assert_ne!(message_hash(&first), message_hash(&other));
+    }
+
+    #[test]
+    fn test_dedup_cache_evicts_least_recently_used() {
+        let cache = DedupCache::new(2);
+        cache.insert("a".into(), json!({"verdict": "Spoofed"}));
+        cache.insert("b".into(), json!({"verdict": "Legit"}));
+        assert_eq!(
+            cache.get("a"),
+            Some(json!({"verdict": "Spoofed", "deduplicated": true}))
+        );
+
+        cache.insert("c".into(), json!({}));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+    }
+}
@@ -0,0 +1,292 @@
+use std::borrow::Cow;
+
+use base64::{
+    Engine, alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+
+/// Base64 decoder accepting payloads with or without padding
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Characters that render as nothing and are used to break up URLs
+const INVISIBLE: [char; 6] = [
+    '\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', '\u{feff}', '\u{ad}',
+];
+
+/// Undoes common HTML link obfuscation so URL extraction sees the real targets
+///
+/// Strips zero-width characters, decodes HTML entities, joins string concatenations
+/// and whitespace-split attribute values, inlines base64 `data:` payloads, and appends
+/// the targets of meta-refresh and JavaScript `location` redirects.
+pub fn deobfuscate(html: &str) -> String {
+    // Each pass is skipped when its trigger characters are absent, which is the common case
+    let mut text = Cow::Borrowed(html);
+    if text.contains('&') {
+        text = Cow::Owned(decode_entities(&text));
+    }
+    if text.contains(INVISIBLE) {
+        text = Cow::Owned(text.chars().filter(|c| !INVISIBLE.contains(c)).collect());
+    }
+    if text.contains('+') {
+        text = Cow::Owned(join_concatenations(&text));
+    }
+    if text.contains('=') {
+        text = Cow::Owned(join_split_attributes(&text));
+    }
+
+    let payloads = data_uri_payloads(&text);
+    let targets = redirect_targets(&text);
+    let mut out = text.into_owned();
+    for payload in payloads {
+        out.push('\n');
+        out.push_str(&deobfuscate(&payload));
+    }
+    for target in targets {
+        out.push('\n');
+        out.push_str(&target);
+    }
+    out
+}
+
+/// Decodes numeric and the common named HTML character references
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..1 + end])?, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(num) = name.strip_prefix('#') {
+        let code = match num.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "colon" => ':',
+        "sol" => '/',
+        "period" => '.',
+        "commat" => '@',
+        "quest" => '?',
+        "equals" => '=',
+        _ => return None,
+    })
+}
+
+/// Joins JavaScript string concatenations such as `"https://ev" + "il.com"`
+fn join_concatenations(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '"' || c == '\'' {
+            // Look for <quote> ws* + ws* <quote>
+            let mut j = i + 1;
+            while j < chars.len() && chars[j].is_whitespace() {
+                j += 1;
+            }
+            if j < chars.len() && chars[j] == '+' {
+                j += 1;
+                while j < chars.len() && chars[j].is_whitespace() {
+                    j += 1;
+                }
+                if j < chars.len() && (chars[j] == '"' || chars[j] == '\'') {
+                    i = j + 1;
+                    continue;
+                }
+            }
+        }
+        out.push(c);
+        i += 1;
+    }
+
+    out
+}
+
+/// Removes whitespace inside quoted `href`/`src`/`action` values, e.g. `href="h t t p s://..."`
+fn join_split_attributes(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut pos = 0;
+
+    while let Some((start, quote)) = next_attribute_value(&lower, pos) {
+        let Some(len) = text[start..].find(quote) else {
+            break;
+        };
+        out.push_str(&text[pos..start]);
+        out.extend(
+            text[start..start + len]
+                .chars()
+                .filter(|c| !c.is_whitespace()),
+        );
+        pos = start + len;
+    }
+
+    out.push_str(&text[pos..]);
+    out
+}
+
+/// Finds the next quoted link attribute value at or after `from`, returning its start and quote
+fn next_attribute_value(lower: &str, from: usize) -> Option<(usize, char)> {
+    ["href", "src", "action"]
+        .iter()
+        .filter_map(|attr| {
+            let mut search = from;
+            loop {
+                let at = search + lower[search..].find(attr)?;
+                search = at + attr.len();
+                let Some(value) = lower[search..].trim_start().strip_prefix('=') else {
+                    continue;
+                };
+                let value = value.trim_start();
+                if let Some(quote) = value.chars().next().filter(|q| *q == '"' || *q == '\'') {
+                    return Some((lower.len() - value.len() + 1, quote));
+                }
+            }
+        })
+        .min_by_key(|(start, _)| *start)
+}
+
+/// Decoded contents of base64 `data:` URIs
+fn data_uri_payloads(text: &str) -> Vec<String> {
+    let mut payloads = Vec::new();
+    let lower = text.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while let Some(offset) = lower[pos..].find("data:") {
+        let start = pos + offset;
+        pos = start + 5;
+        let header_end = match lower[start..].find(',') {
+            Some(end) if end < 100 => start + end,
+            _ => continue,
+        };
+        if !lower[start..header_end].ends_with(";base64") {
+            continue;
+        }
+        let data: String = text[header_end + 1..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+            .collect();
+        if let Ok(bytes) = BASE64.decode(&data)
+            && let Ok(decoded) = String::from_utf8(bytes)
+        {
+            payloads.push(decoded);
+        }
+        pos = header_end + 1 + data.len();
+    }
+
+    payloads
+}
+
+/// Targets of `<meta http-equiv="refresh" content="0;url=...">` and JS `location` assignments
+fn redirect_targets(text: &str) -> Vec<String> {
+    if !text.contains(['=', '(']) {
+        return Vec::new();
+    }
+
+    // Markers are matched with whitespace removed, e.g. `location.href = "..."`
+    let original: String = text.split_whitespace().collect();
+    let compact = original.to_ascii_lowercase();
+    let mut targets = Vec::new();
+
+    for marker in [
+        "url=",
+        "location=",
+        "location.href=",
+        "location.replace(",
+        "location.assign(",
+    ] {
+        let mut pos = 0;
+        while let Some(offset) = compact[pos..].find(marker) {
+            let start = pos + offset + marker.len();
+            pos = start;
+            let value = original[start..].trim_start_matches(['"', '\'']);
+            let end = value
+                .find(['"', '\'', ';', ')', '<', '>'])
+                .unwrap_or(value.len());
+            let target = &value[..end];
+            if let Some(rest) = target.strip_prefix("//") {
+                targets.push(format!("https://{}", rest));
+            } else if target.to_ascii_lowercase().starts_with("http") {
+                targets.push(target.to_string());
+            }
+        }
+    }
+
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::deobfuscate;
+    use crate::body::extract_urls;
+
+    fn urls(html: &str) -> Vec<String> {
+        extract_urls(&deobfuscate(html))
+    }
+
+    #[test]
+    fn test_entity_and_zero_width_obfuscation() {
+        let html = "<a href=\"ht&#116;ps&#x3a;//ev\u{200b}il&period;com/login\">Login</a>";
+        assert_eq!(urls(html), vec!["https://evil.com/login"]);
+    }
+
+    #[test]
+    fn test_split_href_and_concatenation() {
+        let html = "<a href=\"h t t p s://evil.com/a\">x</a><script>var u = 'https://ph' + 'ish.test/b';</script>";
+        assert_eq!(
+            urls(html),
+            vec!["https://evil.com/a", "https://phish.test/b"]
+        );
+    }
+
+    #[test]
+    fn test_meta_refresh_and_js_location() {
+        let html = "<meta http-equiv=\"refresh\" content=\"0; URL='//landing.test/x'\">\
+                    <script>window.location.href = \"https://js.test/y\";</script>";
+        let found = urls(html);
+        assert!(found.contains(&"https://landing.test/x".to_string()));
+        assert!(found.contains(&"https://js.test/y".to_string()));
+    }
+
+    #[test]
+    fn test_base64_data_uri() {
+        // <a href="https://hidden.test/">
+        let html = "<iframe src=\"data:text/html;base64,PGEgaHJlZj0iaHR0cHM6Ly9oaWRkZW4udGVzdC8iPg\"></iframe>";
+        assert_eq!(urls(html), vec!["https://hidden.test/"]);
+    }
+}
@@ -0,0 +1,169 @@
+use serde_json::Value;
+
+use crate::email_verdict::AnalysisResult;
+
+/// A result field whose value differs between two analyses
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FieldChange {
+    /// Dotted path of the field, e.g. `evidence.spf_policy`
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// What changed between two analyses of the same message or domain
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct ResultDiff {
+    /// Changed verdict, score, severity, and evidence fields
+    pub changes: Vec<FieldChange>,
+
+    /// Reasons present only in the later analysis
+    pub reasons_added: Vec<Value>,
+
+    /// Reasons present only in the earlier analysis
+    pub reasons_removed: Vec<Value>,
+}
+
+impl ResultDiff {
+    /// Compares two serialized results, e.g. one saved with `--json` and a fresh one
+    ///
+    /// Reasons are matched by key and arguments, so a change of language is not a change.
+    pub fn between(before: &Value, after: &Value) -> Self {
+        let mut diff = ResultDiff::default();
+        diff_fields("", before, after, &mut diff.changes);
+
+        let reasons = |v: &Value| -> Vec<Value> {
+            v.get("reasons")
+                .and_then(Value::as_array)
+                .map(|r| r.iter().map(without_message).collect())
+                .unwrap_or_default()
+        };
+        let (old, new) = (reasons(before), reasons(after));
+        diff.reasons_added = new.iter().filter(|r| !old.contains(r)).cloned().collect();
+        diff.reasons_removed = old.iter().filter(|r| !new.contains(r)).cloned().collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.reasons_added.is_empty() && self.reasons_removed.is_empty()
+    }
+}
+
+impl AnalysisResult {
+    /// Lists what changed from `self` (before) to `other` (after)
+    pub fn diff(&self, other: &AnalysisResult) -> ResultDiff {
+        let before = serde_json::to_value(self).unwrap_or_default();
+        let after = serde_json::to_value(other).unwrap_or_default();
+        ResultDiff::between(&before, &after)
+    }
+}
+
+/// Recurses into objects; any other differing values are recorded as one change
+fn diff_fields(path: &str, before: &Value, after: &Value, changes: &mut Vec<FieldChange>) {
+    match (before, after) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                // The digest and signature change whenever anything else does, and
+                // timings on every run
+                if path.is_empty()
+                    && ["reasons", "result_digest", "signature", "timings"].contains(&key.as_str())
+                {
+                    continue;
+                }
+                let field = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                diff_fields(
+                    &field,
+                    old.get(key).unwrap_or(&Value::Null),
+                    new.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ if before != after => changes.push(FieldChange {
+            field: path.to_string(),
+            before: before.clone(),
+            after: after.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// A reason without its rendered, language-dependent message
+fn without_message(reason: &Value) -> Value {
+    let mut reason = reason.clone();
+    if let Some(obj) = reason.as_object_mut() {
+        obj.remove("message");
+        obj.remove("rule_id");
+    }
+    reason
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResultDiff;
+    use crate::{dns::DnsSnapshot, email_verdict::analyze_email, parse::parse_email};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_result_diff_between_analyses() {
+        let parsed = parse_email(b"From: user@example.com\r\n").unwrap();
+        let snapshot = |dmarc: &str| -> DnsSnapshot {
+            serde_json::from_value(json!({
+                "domains": {"example.com": {"spf": "v=spf1 -all", "dmarc": dmarc, "exists": true}}
+            }))
+            .unwrap()
+        };
+
+        let before = analyze_email(&parsed, &snapshot("v=DMARC1; p=none"))
+            .await
+            .unwrap();
+        let after = analyze_email(&parsed, &snapshot("v=DMARC1; p=reject"))
+            .await
+            .unwrap();
+
+        let diff = before.diff(&after);
+        assert!(
+            diff.changes
+                .iter()
+                .any(|c| c.field == "evidence.dmarc_policy")
+        );
+        assert!(before.diff(&before).is_empty());
+    }
+
+    #[test]
+    fn test_diff_after_spf_fix() {
+        let before = json!({
+            "verdict": "Suspicious",
+            "risk_score": 50,
+            "reasons": [
+                {"key": "spf_not_strict", "args": {"domain": "example.com"}, "message": "..."},
+                {"key": "dkim_missing", "args": {}, "message": "No DKIM."}
+            ],
+            "evidence": {"spf_policy": "v=spf1 ~all", "dkim_present": false, "body": {"urls": []}}
+        });
+        let after = json!({
+            "verdict": "Suspicious",
+            "risk_score": 50,
+            "reasons": [
+                {"key": "dkim_missing", "args": {}, "message": "Keine DKIM-Signatur."}
+            ],
+            "evidence": {"spf_policy": "v=spf1 -all", "dkim_present": false, "body": {"urls": []}}
+        });
+
+        let diff = ResultDiff::between(&before, &after);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].field, "evidence.spf_policy");
+        assert_eq!(diff.changes[0].after, "v=spf1 -all");
+        assert!(diff.reasons_added.is_empty());
+        assert_eq!(diff.reasons_removed[0]["key"], "spf_not_strict");
+
+        assert!(ResultDiff::between(&after, &after).is_empty());
+    }
+}
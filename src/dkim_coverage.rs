@@ -0,0 +1,86 @@
+use crate::{dkim_replay::CRITICAL_HEADERS, parse::EmailParsed};
+
+/// How one DKIM signature covers a critical header
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HeaderCoverage {
+    pub header: String,
+    /// Instances of the header in the message
+    pub present: usize,
+    /// Times the header is listed in `h=`
+    pub signed: usize,
+    /// Listed more often than present, so adding an instance breaks the signature
+    pub oversigned: bool,
+}
+
+/// The headers one DKIM signature covers
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DkimCoverage {
+    pub domain: String,
+    pub selector: String,
+    /// The `h=` tag, lowercased, in signing order
+    pub signed_headers: Vec<String>,
+    /// Coverage of From, Subject, To, Date, and Reply-To
+    pub critical: Vec<HeaderCoverage>,
+}
+
+/// Reports the header coverage of every DKIM signature of `parsed`
+pub fn dkim_coverage(parsed: &EmailParsed) -> Vec<DkimCoverage> {
+    let count = |names: &[String], name: &str| names.iter().filter(|n| *n == name).count();
+    parsed
+        .dkim_signatures
+        .iter()
+        .map(|signature| DkimCoverage {
+            domain: signature.domain.clone(),
+            selector: signature.selector.clone(),
+            signed_headers: signature.signed_headers.clone(),
+            critical: CRITICAL_HEADERS
+                .iter()
+                .map(|header| {
+                    let present = count(&parsed.header_names, header);
+                    let signed = count(&signature.signed_headers, header);
+                    HeaderCoverage {
+                        header: header.to_string(),
+                        present,
+                        signed,
+                        oversigned: signed > present,
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dkim_coverage;
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_dkim_coverage() {
+        let raw =
+            b"DKIM-Signature: v=1; d=esp.example; s=s1; h=from:from:subject:date; bh=x; b=y\r\n\
+From: news@esp.example\r\n\
+Reply-To: sales@esp.example\r\n\
+Subject: Newsletter\r\n\r\nHello\r\n";
+        let parsed = parse_email(raw).unwrap();
+
+        let coverage = dkim_coverage(&parsed);
+        assert_eq!(coverage.len(), 1);
+        assert_eq!(
+            coverage[0].signed_headers,
+            vec!["from", "from", "subject", "date"]
+        );
+        let of = |header: &str| {
+            let c = coverage[0]
+                .critical
+                .iter()
+                .find(|c| c.header == header)
+                .unwrap();
+            (c.present, c.signed, c.oversigned)
+        };
+        assert_eq!(of("from"), (1, 2, true));
+        assert_eq!(of("subject"), (1, 1, false));
+        assert_eq!(of("date"), (0, 1, true));
+        assert_eq!(of("reply-to"), (1, 0, false));
+    }
+}
@@ -0,0 +1,245 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{
+    lint::{LintFinding, RecordSource},
+    reasons::Severity,
+};
+
+/// A DKIM selector that publishes a key or points somewhere via CNAME
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DkimSelector {
+    pub selector: String,
+    /// CNAME target, typically a key hosted by an email service provider
+    pub cname: Option<String>,
+    /// The key record, when the selector resolves to one
+    pub record: Option<String>,
+}
+
+/// Result of auditing a domain's DKIM key records
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct DkimLint {
+    pub selectors: Vec<DkimSelector>,
+    pub findings: Vec<LintFinding>,
+}
+
+/// Audits the key records of `selectors` under `domain`
+///
+/// Selectors without a CNAME or key record are skipped; a CNAME whose target no longer
+/// resolves to a key is reported as a dangling selector that could be taken over.
+pub async fn lint_dkim<S: RecordSource + Sync>(
+    source: &S,
+    domain: &str,
+    selectors: &[String],
+) -> DkimLint {
+    let mut lint = DkimLint::default();
+
+    for selector in selectors {
+        let name = format!("{}._domainkey.{}", selector, domain);
+        let cname = source.cname(&name).await;
+        let record = source
+            .txt_records(&name)
+            .await
+            .filter(|records| !records.is_empty())
+            .map(|records| records.concat());
+
+        match (&cname, &record) {
+            (_, Some(record)) => lint.findings.extend(lint_dkim_record(&name, record)),
+            (Some(target), None) => lint.findings.push(LintFinding::new(
+                "dangling_selector",
+                Severity::High,
+                &name,
+                format!(
+                    "The selector points at {}, which no longer publishes a key; whoever registers that account or name can sign mail as {}.",
+                    target, domain
+                ),
+                "Remove the CNAME, or re-provision the key with the email service provider.",
+            )),
+            (None, None) => continue,
+        }
+
+        lint.selectors.push(DkimSelector {
+            selector: selector.clone(),
+            cname,
+            record,
+        });
+    }
+
+    lint
+}
+
+/// Lints one DKIM key record published at `name`
+pub fn lint_dkim_record(name: &str, record: &str) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    let tags: Vec<(String, String)> = record
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(tag, value)| (tag.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    let value = |tag: &str| tags.iter().find(|(t, _)| t == tag).map(|(_, v)| v.as_str());
+
+    let mut seen: Vec<&str> = Vec::new();
+    for (tag, _) in &tags {
+        if seen.contains(&tag.as_str()) {
+            findings.push(LintFinding::new(
+                "duplicate_tag",
+                Severity::High,
+                name,
+                format!(
+                    "The {}= tag appears more than once; verifiers reject the key.",
+                    tag
+                ),
+                "Publish each tag once.",
+            ));
+        } else {
+            seen.push(tag);
+        }
+    }
+
+    match value("p") {
+        None => findings.push(LintFinding::new(
+            "missing_key",
+            Severity::High,
+            name,
+            "The record has no p= tag.".to_string(),
+            "Publish the public key in p=.",
+        )),
+        Some("") => findings.push(LintFinding::new(
+            "revoked_key",
+            Severity::Info,
+            name,
+            "The key is revoked (empty p=); signatures with this selector fail.".to_string(),
+            "Remove the selector once no mail is signed with it.",
+        )),
+        Some(key) => {
+            let key: String = key.chars().filter(|c| !c.is_whitespace()).collect();
+            if STANDARD.decode(&key).is_err() {
+                findings.push(LintFinding::new(
+                    "invalid_key",
+                    Severity::High,
+                    name,
+                    "p= is not valid base64; signatures cannot be verified.".to_string(),
+                    "Republish the public key exactly as generated, without quotes or line breaks inside it.",
+                ));
+            }
+        }
+    }
+
+    if let Some(k) = value("k")
+        && !matches!(k.to_ascii_lowercase().as_str(), "rsa" | "ed25519")
+    {
+        findings.push(LintFinding::new(
+            "unsupported_key_type",
+            Severity::High,
+            name,
+            format!("k={} is not a supported key type.", k),
+            "Use k=rsa or k=ed25519.",
+        ));
+    }
+
+    if let Some(h) = value("h") {
+        let unsupported: Vec<&str> = h
+            .split(':')
+            .map(str::trim)
+            .filter(|alg| !alg.eq_ignore_ascii_case("sha256"))
+            .collect();
+        if !unsupported.is_empty() {
+            findings.push(LintFinding::new(
+                "unsupported_hash",
+                Severity::Medium,
+                name,
+                format!(
+                    "h= allows {}; sha1 is deprecated (RFC 8301) and other values are unknown.",
+                    unsupported.join(", ")
+                ),
+                "Set h=sha256 or remove h=.",
+            ));
+        }
+    }
+
+    if value("t").is_some_and(|t| {
+        t.split(':')
+            .any(|flag| flag.trim().eq_ignore_ascii_case("y"))
+    }) {
+        findings.push(LintFinding::new(
+            "testing_mode",
+            Severity::Medium,
+            name,
+            "t=y marks the domain as testing DKIM; verifiers may treat failures as unsigned mail."
+                .to_string(),
+            "Remove the y flag once signing works.",
+        ));
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint_dkim, lint_dkim_record};
+    use crate::lint::RecordSource;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5fYGFiY2RlZmdoaWprbG1ub3BxcnN0dXZ3eHl6e3x9fn+AgYKDhIWGh4iJiouMjY6PkJGSk5SVlpeYmZqbnJ2en6Ch";
+
+    #[derive(Default)]
+    struct Zone {
+        txt: HashMap<String, Vec<String>>,
+        cname: HashMap<String, String>,
+    }
+
+    #[async_trait]
+    impl RecordSource for Zone {
+        async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+            self.txt.get(name).cloned()
+        }
+
+        async fn cname(&self, name: &str) -> Option<String> {
+            self.cname.get(name).cloned()
+        }
+    }
+
+    fn codes(record: &str) -> Vec<&'static str> {
+        let mut codes: Vec<_> = lint_dkim_record("s._domainkey.example.com", record)
+            .iter()
+            .map(|f| f.code)
+            .collect();
+        codes.sort();
+        codes
+    }
+
+    #[test]
+    fn test_dkim_record_findings() {
+        assert!(codes(&format!("v=DKIM1; k=rsa; p={}", KEY)).is_empty());
+        assert_eq!(
+            codes(&format!("v=DKIM1; p={}; p={}", KEY, KEY)),
+            vec!["duplicate_tag"]
+        );
+        assert_eq!(codes("v=DKIM1; p=not*base64"), vec!["invalid_key"]);
+        assert_eq!(
+            codes(&format!("v=DKIM1; k=dsa; h=sha1:sha256; t=y; p={}", KEY)),
+            vec!["testing_mode", "unsupported_hash", "unsupported_key_type"]
+        );
+        assert_eq!(codes("v=DKIM1; p="), vec!["revoked_key"]);
+    }
+
+    #[tokio::test]
+    async fn test_dangling_selector() {
+        let mut zone = Zone::default();
+        zone.txt.insert(
+            "selector1._domainkey.example.com".to_string(),
+            vec![format!("v=DKIM1; k=rsa; p={}", KEY)],
+        );
+        zone.cname.insert(
+            "s1._domainkey.example.com".to_string(),
+            "s1.domainkey.u123.esp.example.net".to_string(),
+        );
+
+        let selectors = ["selector1", "selector2", "s1"].map(String::from);
+        let lint = lint_dkim(&zone, "example.com", &selectors).await;
+        assert_eq!(lint.selectors.len(), 2);
+        assert_eq!(lint.findings.len(), 1);
+        assert_eq!(lint.findings[0].code, "dangling_selector");
+        assert_eq!(lint.findings[0].domain, "s1._domainkey.example.com");
+    }
+}
@@ -0,0 +1,152 @@
+use crate::parse::{DkimSignature, EmailParsed};
+
+/// Signing this long before delivery suggests a signed message is being replayed
+const REPLAY_AGE_SECS: i64 = 3 * 24 * 3600;
+
+/// Headers a replayer rewrites to retarget a signed message, or to redirect replies,
+/// when they are not signed
+const RETARGETING_HEADERS: &[&str] = &["to", "subject", "reply-to"];
+
+/// Headers whose extra unsigned instances change what the recipient sees
+pub const CRITICAL_HEADERS: &[&str] = &["from", "subject", "to", "date", "reply-to"];
+
+/// A sign that a DKIM-signed message was replayed or modified after signing
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReplaySignal {
+    /// Signed (or dated) this long before our relay received it
+    StaleSignature { age_secs: i64 },
+    /// A header a replayer would rewrite is not signed
+    UnsignedHeader { header: String },
+    /// The message carries more instances of a signed header than the signature covers,
+    /// e.g. a second `Subject` added after signing
+    AddedHeader { header: String },
+    /// The body hash covers only the first `length` bytes (`l=`), so content can be
+    /// appended; `unsigned_bytes` of the canonicalized body follow them
+    BodyLength {
+        length: u64,
+        unsigned_bytes: u64,
+        canonicalization: String,
+    },
+}
+
+/// The replay signals of one DKIM signature
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DkimReplayCheck {
+    pub domain: String,
+    pub selector: String,
+    pub signals: Vec<ReplaySignal>,
+}
+
+/// Checks every DKIM signature of `parsed` for signs of replay, given the time our
+/// relay received the message
+///
+/// Signatures are not verified; the signals describe what a valid one would leave
+/// open. Only signatures with signals are returned.
+pub fn check_replay(parsed: &EmailParsed, delivered_at: Option<i64>) -> Vec<DkimReplayCheck> {
+    parsed
+        .dkim_signatures
+        .iter()
+        .filter_map(|signature| {
+            let signals = signals(parsed, signature, delivered_at);
+            (!signals.is_empty()).then(|| DkimReplayCheck {
+                domain: signature.domain.clone(),
+                selector: signature.selector.clone(),
+                signals,
+            })
+        })
+        .collect()
+}
+
+fn signals(
+    parsed: &EmailParsed,
+    signature: &DkimSignature,
+    delivered_at: Option<i64>,
+) -> Vec<ReplaySignal> {
+    let mut signals = Vec::new();
+    let count = |names: &[String], name: &str| names.iter().filter(|n| *n == name).count();
+
+    let signed_at = signature.timestamp.or_else(|| parsed.date_timestamp());
+    if let (Some(signed_at), Some(delivered_at)) = (signed_at, delivered_at)
+        && delivered_at - signed_at > REPLAY_AGE_SECS
+    {
+        signals.push(ReplaySignal::StaleSignature {
+            age_secs: delivered_at - signed_at,
+        });
+    }
+
+    for header in RETARGETING_HEADERS {
+        if count(&parsed.header_names, header) > 0 && count(&signature.signed_headers, header) == 0
+        {
+            signals.push(ReplaySignal::UnsignedHeader {
+                header: header.to_string(),
+            });
+        }
+    }
+
+    for header in CRITICAL_HEADERS {
+        let signed = count(&signature.signed_headers, header);
+        if signed > 0 && count(&parsed.header_names, header) > signed {
+            signals.push(ReplaySignal::AddedHeader {
+                header: header.to_string(),
+            });
+        }
+    }
+
+    if let Some(length) = signature.body_length {
+        signals.push(ReplaySignal::BodyLength {
+            length,
+            unsigned_bytes: signature.unsigned_body_bytes.unwrap_or_default(),
+            canonicalization: signature.canonicalization.clone(),
+        });
+    }
+    signals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReplaySignal, check_replay};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_replay_signals() {
+        let raw = b"Subject: Invoice overdue\r\n\
+DKIM-Signature: v=1; d=esp.example; s=s1; h=from:subject:date; l=5; t=1769000000; bh=x; b=y\r\n\
+From: news@esp.example\r\n\
+To: victim@example.org\r\n\
+Subject: Newsletter\r\n\
+Date: Wed, 21 Jan 2026 12:53:20 +0000\r\n\r\nHello\r\n";
+        let parsed = parse_email(raw).unwrap();
+
+        let checks = check_replay(&parsed, Some(1_769_000_000 + 10 * 86_400));
+        assert_eq!(checks.len(), 1);
+        assert_eq!(
+            checks[0].signals,
+            vec![
+                ReplaySignal::StaleSignature {
+                    age_secs: 10 * 86_400
+                },
+                ReplaySignal::UnsignedHeader {
+                    header: "to".to_string()
+                },
+                ReplaySignal::AddedHeader {
+                    header: "subject".to_string()
+                },
+                ReplaySignal::BodyLength {
+                    length: 5,
+                    unsigned_bytes: 2,
+                    canonicalization: "simple/simple".to_string()
+                },
+            ]
+        );
+
+        // Delivered an hour after signing
+        let fresh = check_replay(&parsed, Some(1_769_000_000 + 3600));
+        assert!(
+            !fresh[0]
+                .signals
+                .iter()
+                .any(|s| matches!(s, ReplaySignal::StaleSignature { .. }))
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    lint::{LintFinding, RecordSource},
+    reasons::Severity,
+};
+
+/// Result of auditing a domain's DMARC record
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct DmarcLint {
+    /// The `_dmarc` record, if one is published
+    pub record: Option<String>,
+
+    /// Effective `p=` policy
+    pub policy: Option<String>,
+
+    pub findings: Vec<LintFinding>,
+
+    /// Suggested next deployment stage (none → quarantine → reject), if any
+    pub next_step: Option<String>,
+}
+
+/// Audits the DMARC record of `domain`, including authorization of external report destinations
+pub async fn lint_dmarc<S: RecordSource + Sync>(source: &S, domain: &str) -> DmarcLint {
+    let record = source
+        .txt_records(&format!("_dmarc.{}", domain))
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .find(|r| r.to_ascii_lowercase().starts_with("v=dmarc1"));
+
+    let Some(record) = record else {
+        return DmarcLint {
+            findings: vec![LintFinding::new(
+                "no_record",
+                Severity::Medium,
+                domain,
+                "No DMARC record is published.".to_string(),
+                "Publish a monitoring record to start collecting reports.",
+            )],
+            next_step: Some(format!(
+                "Publish \"v=DMARC1; p=none; rua=mailto:dmarc-reports@{}\" at _dmarc.{}.",
+                domain, domain
+            )),
+            ..DmarcLint::default()
+        };
+    };
+
+    let mut lint = lint_dmarc_record(domain, &record);
+
+    // Report destinations outside the domain must publish <domain>._report._dmarc.<destination>
+    let tags = parse_tags(&record);
+    for destination in external_destinations(domain, &tags) {
+        let name = format!("{}._report._dmarc.{}", domain, destination);
+        let authorized = source.txt_records(&name).await.is_some_and(|records| {
+            records
+                .iter()
+                .any(|r| r.to_ascii_lowercase().starts_with("v=dmarc1"))
+        });
+        if !authorized {
+            lint.findings.push(LintFinding::new(
+                "external_destination_unauthorized",
+                Severity::Medium,
+                domain,
+                format!(
+                    "Reports go to {}, which does not authorize them at {}; receivers will not send them.",
+                    destination, name
+                ),
+                "Ask the report receiver to publish \"v=DMARC1\" at that name, or use an address in your own domain.",
+            ));
+        }
+    }
+
+    lint
+}
+
+/// Lints a DMARC record's syntax and policy without further lookups
+pub fn lint_dmarc_record(domain: &str, record: &str) -> DmarcLint {
+    let tags = parse_tags(record);
+    let mut findings = Vec::new();
+
+    let policy = tags.get("p").cloned();
+    let rank = |p: Option<&str>| match p {
+        Some("none") => Some(0),
+        Some("quarantine") => Some(1),
+        Some("reject") => Some(2),
+        _ => None,
+    };
+
+    if rank(policy.as_deref()).is_none() {
+        findings.push(LintFinding::new(
+            "invalid_policy",
+            Severity::High,
+            domain,
+            format!(
+                "p={} is not one of none, quarantine, or reject; receivers ignore the record.",
+                policy.as_deref().unwrap_or("(missing)")
+            ),
+            "Set p=none, p=quarantine, or p=reject.",
+        ));
+    }
+
+    let rua = tags.get("rua").map(String::as_str).unwrap_or_default();
+    if policy.as_deref() == Some("none") && rua.is_empty() {
+        findings.push(LintFinding::new(
+            "none_without_rua",
+            Severity::Medium,
+            domain,
+            "p=none without rua= neither protects the domain nor collects reports.".to_string(),
+            "Add rua=mailto:<address> so you can see who sends as this domain.",
+        ));
+    }
+
+    let pct = tags
+        .get("pct")
+        .and_then(|p| p.parse::<u32>().ok())
+        .unwrap_or(100);
+    if pct < 100 && matches!(policy.as_deref(), Some("quarantine" | "reject")) {
+        findings.push(LintFinding::new(
+            "partial_enforcement",
+            Severity::Low,
+            domain,
+            format!(
+                "pct={} applies p={} to only part of the failing mail.",
+                pct,
+                policy.as_deref().unwrap_or_default()
+            ),
+            "Raise pct to 100 once reports show no legitimate failures.",
+        ));
+    }
+
+    for tag in ["rua", "ruf"] {
+        for uri in report_uris(&tags, tag) {
+            if mailto_domain(uri).is_none() {
+                findings.push(LintFinding::new(
+                    "malformed_uri",
+                    Severity::Medium,
+                    domain,
+                    format!("{}={} is not a valid mailto: URI.", tag, uri),
+                    "Use comma-separated mailto:<local>@<domain> URIs.",
+                ));
+            }
+        }
+    }
+
+    if let (Some(p), Some(sp)) = (
+        rank(policy.as_deref()),
+        rank(tags.get("sp").map(String::as_str)),
+    ) && sp < p
+    {
+        findings.push(LintFinding::new(
+            "weak_subdomain_policy",
+            Severity::Medium,
+            domain,
+            format!(
+                "sp={} is weaker than p={}, so subdomains can be spoofed more easily.",
+                tags["sp"],
+                policy.as_deref().unwrap_or_default()
+            ),
+            "Remove sp= or set it to the same policy as p=.",
+        ));
+    }
+
+    let next_step = match policy.as_deref() {
+        Some("none") if rua.is_empty() => {
+            Some("Add rua= reporting, then review the aggregate reports.".to_string())
+        }
+        Some("none") => Some(
+            "Move to p=quarantine once the reports show all legitimate senders passing."
+                .to_string(),
+        ),
+        Some("quarantine") | Some("reject") if pct < 100 => {
+            Some(format!("Raise pct from {} to 100.", pct))
+        }
+        Some("quarantine") => {
+            Some("Move to p=reject once quarantined mail shows no legitimate senders.".to_string())
+        }
+        _ => None,
+    };
+
+    DmarcLint {
+        record: Some(record.to_string()),
+        policy,
+        findings,
+        next_step,
+    }
+}
+
+/// Tag-value pairs of a DMARC record; tag names are lowercased
+pub(crate) fn parse_tags(record: &str) -> BTreeMap<String, String> {
+    record
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(tag, value)| (tag.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect()
+}
+
+/// The comma-separated URIs of a report tag
+fn report_uris<'a>(tags: &'a BTreeMap<String, String>, tag: &str) -> impl Iterator<Item = &'a str> {
+    tags.get(tag)
+        .into_iter()
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|u| !u.is_empty())
+}
+
+/// The domain of a `mailto:` report URI, ignoring a `!size` limit
+fn mailto_domain(uri: &str) -> Option<String> {
+    let address = uri
+        .get(..7)?
+        .eq_ignore_ascii_case("mailto:")
+        .then(|| &uri[7..])?;
+    let address = address.split('!').next()?;
+    let (local, domain) = address.rsplit_once('@')?;
+    (!local.is_empty() && domain.contains('.') && !domain.contains(char::is_whitespace))
+        .then(|| domain.trim_end_matches('.').to_ascii_lowercase())
+}
+
+/// Report destination domains that are neither `domain` nor one of its subdomains
+fn external_destinations(domain: &str, tags: &BTreeMap<String, String>) -> Vec<String> {
+    let domain = domain.to_ascii_lowercase();
+    let mut destinations: Vec<String> = ["rua", "ruf"]
+        .iter()
+        .flat_map(|tag| report_uris(tags, tag))
+        .filter_map(mailto_domain)
+        .filter(|d| {
+            *d != domain
+                && !d.ends_with(&format!(".{}", domain))
+                && !domain.ends_with(&format!(".{}", d))
+        })
+        .collect();
+    destinations.sort();
+    destinations.dedup();
+    destinations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lint_dmarc, lint_dmarc_record};
+    use crate::lint::RecordSource;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+
+    struct Records(HashMap<String, Vec<String>>);
+
+    #[async_trait]
+    impl RecordSource for Records {
+        async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+            self.0.get(name).cloned()
+        }
+    }
+
+    fn codes(record: &str) -> Vec<&'static str> {
+        let mut codes: Vec<_> = lint_dmarc_record("example.com", record)
+            .findings
+            .iter()
+            .map(|f| f.code)
+            .collect();
+        codes.sort();
+        codes
+    }
+
+    #[test]
+    fn test_dmarc_record_findings_and_next_step() {
+        assert_eq!(codes("v=DMARC1; p=none"), vec!["none_without_rua"]);
+        assert_eq!(
+            codes("v=DMARC1; p=reject; pct=50; sp=none; rua=dmarc@example.com"),
+            vec![
+                "malformed_uri",
+                "partial_enforcement",
+                "weak_subdomain_policy"
+            ]
+        );
+        assert_eq!(codes("v=DMARC1; p=block"), vec!["invalid_policy"]);
+        assert!(codes("v=DMARC1; p=reject; rua=mailto:d@example.com!10m").is_empty());
+
+        let stage = |record: &str| lint_dmarc_record("example.com", record).next_step;
+        assert!(
+            stage("v=DMARC1; p=none; rua=mailto:d@example.com")
+                .unwrap()
+                .contains("p=quarantine")
+        );
+        assert!(
+            stage("v=DMARC1; p=quarantine")
+                .unwrap()
+                .contains("p=reject")
+        );
+        assert!(stage("v=DMARC1; p=reject").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_external_report_destination_authorization() {
+        let record = "v=DMARC1; p=none; rua=mailto:a@reports.example.net,mailto:b@vendor.example.org,mailto:c@mail.example.com";
+        let source = Records(HashMap::from([
+            ("_dmarc.example.com".to_string(), vec![record.to_string()]),
+            (
+                "example.com._report._dmarc.reports.example.net".to_string(),
+                vec!["v=DMARC1".to_string()],
+            ),
+        ]));
+
+        let lint = lint_dmarc(&source, "example.com").await;
+        assert_eq!(lint.policy.as_deref(), Some("none"));
+        assert_eq!(lint.findings.len(), 1);
+        assert_eq!(lint.findings[0].code, "external_destination_unauthorized");
+        assert!(lint.findings[0].detail.contains("vendor.example.org"));
+
+        let missing = lint_dmarc(&Records(HashMap::new()), "example.com").await;
+        assert_eq!(missing.findings[0].code, "no_record");
+        assert!(missing.next_step.is_some());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn prop_lint_dmarc_record_never_panics(
+            tags in proptest::collection::vec(("[a-z]{1,5}", "[ -:<-~]{0,20}"), 0..8),
+            garbage in "\\PC{0,40}",
+        ) {
+            let record = tags
+                .iter()
+                .map(|(tag, value)| format!("{}={}", tag, value))
+                .collect::<Vec<_>>()
+                .join("; ");
+            lint_dmarc_record("example.com", &format!("v=DMARC1; {}", record));
+            lint_dmarc_record("example.com", &garbage);
+        }
+    }
+}
@@ -1,8 +1,23 @@
 use async_trait::async_trait;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::time::Instant;
 use trust_dns_resolver::{
     TokioAsyncResolver,
-    config::{ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::{
+        error::ProtoErrorKind,
+        rr::{RData, RecordType},
+    },
+};
+
+use crate::{
+    dns_backends::{self, Backend, BackendStats, is_answer},
+    offline::{check_network, is_offline},
+    timing::Timing,
+    zone_limit::{ConcurrencyLimit, ConcurrencyLimiter, ZoneLimit, ZoneLimiter},
 };
 
 /// Resolver trait for real or mock DNS
@@ -15,26 +30,278 @@ pub trait ResolverTrait {
 
     /// Check if domain has MX records
     async fn resolve_mx(&self, domain: &str) -> bool;
+
+    /// IPv4 and IPv6 addresses of a host name, for SPF `a` and `mx` mechanisms
+    async fn addresses(&self, _name: &str) -> Vec<IpAddr> {
+        Vec::new()
+    }
+
+    /// MX exchange host names of a domain
+    async fn mx_hosts(&self, _domain: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Authoritative name servers of a domain, sorted
+    async fn ns_hosts(&self, _domain: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// SPF and DMARC records of `domain` on which independent resolvers disagreed
+    fn disagreements(&self, _domain: &str) -> Vec<DnsDisagreement> {
+        Vec::new()
+    }
+}
+
+/// Records of a name on which two independent resolver backends disagreed, a sign
+/// that one of them is poisoned
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DnsDisagreement {
+    /// The queried name, e.g. `_dmarc.example.com`
+    pub name: String,
+    /// The SPF or DMARC records each backend returned, by backend spec
+    pub answers: BTreeMap<String, Vec<String>>,
+}
+
+/// Disagreements kept before the oldest are dropped
+const MAX_DISAGREEMENTS: usize = 10_000;
+
+/// Latency and failures of one record type's queries
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct QueryStats {
+    /// Queries answered or failed
+    pub count: u64,
+    pub errors: u64,
+    /// Total time spent waiting for answers
+    pub seconds_sum: f64,
+    /// Slowest query
+    pub seconds_max: f64,
+}
+
+/// Health of a `DnsResolver` since it was created
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct ResolverStats {
+    /// Nameservers queried, as configured
+    pub nameservers: Vec<String>,
+    /// Health of each backend, in configured order
+    pub backends: Vec<BackendStats>,
+    /// Queries awaiting an answer
+    pub in_flight: u64,
+    /// Queries by record type
+    pub queries: BTreeMap<String, QueryStats>,
+    /// Failed queries by cause: `no_records`, `timeout`, `socket`, `no_connections`,
+    /// `protocol`, or `other`
+    pub errors: BTreeMap<String, u64>,
+    /// Queries delayed by the per-zone rate limit
+    pub throttled: u64,
+    /// Queries that waited for a permit of the concurrency limit
+    pub queued: u64,
+}
+
+/// Counters behind `ResolverStats`, shared by clones of a resolver
+#[derive(Default)]
+struct ResolverCounters {
+    in_flight: AtomicU64,
+    queries: Mutex<BTreeMap<&'static str, QueryStats>>,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+/// Counts a query as in flight until dropped, including when the analysis is cancelled
+struct InFlight<'a>(&'a AtomicU64);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Cause of a failed query, as reported in `ResolverStats::errors`
+fn error_cause(error: &ResolveError) -> &'static str {
+    match error.kind() {
+        ResolveErrorKind::NoRecordsFound { .. } => "no_records",
+        ResolveErrorKind::Timeout => "timeout",
+        ResolveErrorKind::Io(_) => "socket",
+        ResolveErrorKind::NoConnections => "no_connections",
+        ResolveErrorKind::Proto(e) => match e.kind() {
+            ProtoErrorKind::Timeout => "timeout",
+            ProtoErrorKind::Io(_) => "socket",
+            _ => "protocol",
+        },
+        _ => "other",
+    }
 }
 
 /// DNS resolver wrapper
 #[derive(Clone)]
 pub struct DnsResolver {
-    inner: Arc<TokioAsyncResolver>,
+    backends: Arc<Vec<Backend>>,
+    counters: Arc<ResolverCounters>,
+    limiter: Option<Arc<ZoneLimiter>>,
+    concurrency: Option<Arc<ConcurrencyLimiter>>,
+    cross_check: bool,
+    /// Latest disagreement per queried name, while the backends keep disagreeing
+    disagreements: Arc<Mutex<HashMap<String, DnsDisagreement>>>,
 }
 
 impl DnsResolver {
     pub fn new() -> anyhow::Result<Self> {
-        let resolver =
-            TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default());
+        Self::with_backends(&["default"])
+    }
+
+    /// A resolver that fails over between backends, see [`Backend::from_spec`]
+    pub fn with_backends(specs: &[&str]) -> anyhow::Result<Self> {
+        check_network("DNS resolver")?;
+        let backends = specs
+            .iter()
+            .map(|spec| Backend::from_spec(spec))
+            .collect::<anyhow::Result<_>>()?;
         Ok(Self {
-            inner: Arc::new(resolver),
+            backends: Arc::new(backends),
+            counters: Arc::default(),
+            limiter: None,
+            concurrency: None,
+            cross_check: false,
+            disagreements: Arc::default(),
         })
     }
 
+    /// Cross-checks SPF and DMARC records between the first two backends
+    ///
+    /// Both are queried in parallel and the first one's records are used. When both
+    /// answer and the records differ, the analysis reports it, as a poisoned local
+    /// resolver could otherwise make a spoofed message pass.
+    pub fn with_cross_check(mut self) -> anyhow::Result<Self> {
+        if self.backends.len() < 2 {
+            anyhow::bail!("cross-checking DNS answers needs two backends");
+        }
+        self.cross_check = true;
+        Ok(self)
+    }
+
+    /// Rate limits queries per zone, see [`zone_of`](crate::zone_limit::zone_of)
+    ///
+    /// Queries over the limit wait for their turn rather than fail, so answers stay
+    /// complete; an analysis deadline still cuts them off.
+    pub fn with_zone_limit(mut self, limit: ZoneLimit) -> Self {
+        self.limiter = Some(Arc::new(ZoneLimiter::new(limit)));
+        self
+    }
+
+    /// Bounds the queries outstanding at once, in all and per zone, shared by clones
+    ///
+    /// Meant for applications embedding the analyzer next to their own workloads.
+    /// Queries over the limit wait for a permit; an analysis deadline still cuts them
+    /// off. A cross-checked lookup takes a permit per backend.
+    pub fn with_concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.concurrency = Some(Arc::new(ConcurrencyLimiter::new(limit)));
+        self
+    }
+
+    /// Query counters, to tell whether slow verdicts are spent waiting on DNS
+    ///
+    /// The underlying resolver does not report which nameserver answered, so latency
+    /// is broken down by record type.
+    pub fn stats(&self) -> ResolverStats {
+        ResolverStats {
+            nameservers: self
+                .backends
+                .iter()
+                .flat_map(|backend| backend.nameservers().iter().cloned())
+                .collect(),
+            backends: self.backends.iter().map(Backend::stats).collect(),
+            in_flight: self.counters.in_flight.load(Ordering::Relaxed),
+            queries: self
+                .counters
+                .queries
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(record_type, stats)| (record_type.to_string(), *stats))
+                .collect(),
+            errors: self
+                .counters
+                .errors
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(cause, count)| (cause.to_string(), *count))
+                .collect(),
+            throttled: self.limiter.as_ref().map_or(0, |l| l.throttled()),
+            queued: self.concurrency.as_ref().map_or(0, |l| l.queued()),
+        }
+    }
+
+    /// Runs one query of `record_type` for `name` on the backends, counting it in the
+    /// stats
+    async fn counted<T, Fut>(
+        &self,
+        record_type: &'static str,
+        name: &str,
+        query: impl Fn(Arc<TokioAsyncResolver>) -> Fut,
+    ) -> Result<T, ResolveError>
+    where
+        Fut: Future<Output = Result<T, ResolveError>>,
+    {
+        self.counted_via(&self.backends, record_type, name, query)
+            .await
+    }
+
+    /// [`counted`](Self::counted), on the given backends only
+    async fn counted_via<T, Fut>(
+        &self,
+        backends: &[Backend],
+        record_type: &'static str,
+        name: &str,
+        query: impl Fn(Arc<TokioAsyncResolver>) -> Fut,
+    ) -> Result<T, ResolveError>
+    where
+        Fut: Future<Output = Result<T, ResolveError>>,
+    {
+        if is_offline() {
+            let forbidden = "DNS query in offline mode";
+            return Err(ResolveError::from(ResolveErrorKind::Message(forbidden)));
+        }
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(name).await;
+        }
+        let _permit = match &self.concurrency {
+            Some(limiter) => Some(limiter.acquire(name).await),
+            None => None,
+        };
+        self.counters.in_flight.fetch_add(1, Ordering::Relaxed);
+        let _in_flight = InFlight(&self.counters.in_flight);
+        let started = Instant::now();
+        let answer = dns_backends::query(backends, query).await;
+        let seconds = started.elapsed().as_secs_f64();
+
+        let mut queries = self.counters.queries.lock().unwrap();
+        let stats = queries.entry(record_type).or_default();
+        stats.count += 1;
+        stats.seconds_sum += seconds;
+        stats.seconds_max = stats.seconds_max.max(seconds);
+        if let Err(e) = &answer {
+            stats.errors += 1;
+            *self
+                .counters
+                .errors
+                .lock()
+                .unwrap()
+                .entry(error_cause(e))
+                .or_default() += 1;
+        }
+        answer
+    }
+
     /// Resolve TXT records for a domain
     pub async fn resolve_txt(&self, name: &str) -> Option<Vec<String>> {
-        let response = self.inner.txt_lookup(name).await.ok()?;
+        self.txt_via(&self.backends, name).await.ok()
+    }
+
+    async fn txt_via(&self, backends: &[Backend], name: &str) -> Result<Vec<String>, ResolveError> {
+        let response = self
+            .counted_via(backends, "TXT", name, |r| async move {
+                r.txt_lookup(name).await
+            })
+            .await?;
         let mut records = Vec::new();
         for r in response.iter() {
             for txt in r.txt_data() {
@@ -43,7 +310,62 @@ impl DnsResolver {
                 }
             }
         }
-        Some(records)
+        Ok(records)
+    }
+
+    /// The first TXT record of `name` starting with `prefix`, cross-checked when enabled
+    async fn policy_record(&self, name: &str, prefix: &str) -> Option<String> {
+        if !self.cross_check {
+            return self
+                .resolve_txt(name)
+                .await?
+                .into_iter()
+                .find(|s| s.starts_with(prefix));
+        }
+        let (first, second) = futures::join!(
+            self.txt_via(&self.backends[..1], name),
+            self.txt_via(&self.backends[1..2], name)
+        );
+        let policies = |answer: &Result<Vec<String>, ResolveError>| match answer {
+            Ok(records) => {
+                let mut policies: Vec<String> = records
+                    .iter()
+                    .filter(|s| s.starts_with(prefix))
+                    .cloned()
+                    .collect();
+                policies.sort();
+                Some(policies)
+            }
+            Err(e) if is_answer(e) => Some(Vec::new()),
+            // A backend that failed to answer cannot be compared
+            Err(_) => None,
+        };
+        if let (Some(ours), Some(theirs)) = (policies(&first), policies(&second)) {
+            let mut disagreements = self.disagreements.lock().unwrap();
+            if ours == theirs {
+                disagreements.remove(name);
+            } else {
+                if disagreements.len() >= MAX_DISAGREEMENTS {
+                    disagreements.clear();
+                }
+                let answers = [(&self.backends[0], ours), (&self.backends[1], theirs)]
+                    .into_iter()
+                    .map(|(backend, records)| (backend.name().to_string(), records))
+                    .collect();
+                disagreements.insert(
+                    name.to_string(),
+                    DnsDisagreement {
+                        name: name.to_string(),
+                        answers,
+                    },
+                );
+            }
+        }
+        first
+            .or(second)
+            .ok()?
+            .into_iter()
+            .find(|s| s.starts_with(prefix))
     }
 }
 
@@ -54,38 +376,105 @@ impl DnsResolver {
             Ok(d) => d,
             Err(_) => return false, // invalid IDN
         };
+        let ascii = ascii_domain.as_str();
 
         // Query A/AAAA records
-        let a_exists = match self.inner.lookup_ip(ascii_domain.clone()).await {
+        let a_exists = match self
+            .counted("A/AAAA", ascii, |r| async move { r.lookup_ip(ascii).await })
+            .await
+        {
             Ok(ips) => ips.iter().next().is_some(),
             Err(_) => false,
         };
 
         // Query MX records
-        let mx_exists = match self.inner.mx_lookup(ascii_domain).await {
+        let mx_exists = match self
+            .counted("MX", ascii, |r| async move { r.mx_lookup(ascii).await })
+            .await
+        {
             Ok(mx) => mx.iter().next().is_some(),
             Err(_) => false,
         };
 
         a_exists || mx_exists
     }
+
+    /// IPv4 and IPv6 addresses of a host name
+    pub async fn addresses(&self, name: &str) -> Vec<std::net::IpAddr> {
+        match self
+            .counted("A/AAAA", name, |r| async move { r.lookup_ip(name).await })
+            .await
+        {
+            Ok(ips) => ips.iter().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// MX exchange host names of a domain, sorted
+    pub async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        let mut hosts: Vec<String> = match self
+            .counted("MX", domain, |r| async move { r.mx_lookup(domain).await })
+            .await
+        {
+            Ok(mx) => mx
+                .iter()
+                .map(|r| r.exchange().to_ascii().trim_end_matches('.').to_string())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        hosts.sort();
+        hosts
+    }
+
+    /// Target of a CNAME record at `name`, if there is one
+    pub async fn cname_target(&self, name: &str) -> Option<String> {
+        let response = self
+            .counted("CNAME", name, |r| async move {
+                r.lookup(name, RecordType::CNAME).await
+            })
+            .await
+            .ok()?;
+        response.iter().find_map(|r| match r {
+            RData::CNAME(target) => Some(target.0.to_ascii().trim_end_matches('.').to_string()),
+            _ => None,
+        })
+    }
+
+    /// Authoritative name servers of a domain, sorted
+    ///
+    /// Subdomains without their own NS records fall back to the closest parent that has
+    /// them, stopping before the TLD.
+    pub async fn ns_hosts(&self, domain: &str) -> Vec<String> {
+        let mut name = domain.trim_end_matches('.');
+        while name.contains('.') {
+            if let Ok(ns) = self
+                .counted("NS", name, |r| async move { r.ns_lookup(name).await })
+                .await
+            {
+                let mut hosts: Vec<String> = ns
+                    .iter()
+                    .map(|r| r.0.to_ascii().trim_end_matches('.').to_ascii_lowercase())
+                    .collect();
+                if !hosts.is_empty() {
+                    hosts.sort();
+                    return hosts;
+                }
+            }
+            name = name.split_once('.').map_or("", |(_, parent)| parent);
+        }
+        Vec::new()
+    }
 }
 
 #[async_trait]
 impl ResolverTrait for DnsResolver {
     async fn resolve_spf(&self, domain: &str) -> Option<String> {
-        self.resolve_txt(domain)
-            .await?
-            .into_iter()
-            .find(|s| s.starts_with("v=spf1"))
+        self.policy_record(domain, "v=spf1").await
     }
 
     async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
         let name = format!("_dmarc.{}", domain);
-        self.resolve_txt(&name)
-            .await?
-            .into_iter()
-            .find(|s| s.starts_with("v=DMARC1"))
+        self.policy_record(&name, "v=DMARC1").await
     }
 
     async fn domain_exists(&self, domain: &str) -> bool {
@@ -94,9 +483,374 @@ impl ResolverTrait for DnsResolver {
 
     /// Check if domain has MX records
     async fn resolve_mx(&self, domain: &str) -> bool {
-        match self.inner.mx_lookup(domain).await {
+        match self
+            .counted("MX", domain, |r| async move { r.mx_lookup(domain).await })
+            .await
+        {
             Ok(mx_lookup) => mx_lookup.iter().next().is_some(),
             Err(_) => false,
         }
     }
+
+    async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        DnsResolver::addresses(self, name).await
+    }
+
+    async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        DnsResolver::mx_hosts(self, domain).await
+    }
+
+    async fn ns_hosts(&self, domain: &str) -> Vec<String> {
+        DnsResolver::ns_hosts(self, domain).await
+    }
+
+    fn disagreements(&self, domain: &str) -> Vec<DnsDisagreement> {
+        let disagreements = self.disagreements.lock().unwrap();
+        [domain.to_string(), format!("_dmarc.{}", domain)]
+            .iter()
+            .filter_map(|name| disagreements.get(name).cloned())
+            .collect()
+    }
+}
+
+/// Recorded DNS answers for a single domain
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SnapshotRecords {
+    pub spf: Option<String>,
+    pub dmarc: Option<String>,
+    pub exists: bool,
+    pub mx: bool,
+    /// Addresses of the name, when an SPF evaluation looked them up
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub addresses: Vec<IpAddr>,
+    /// MX host names, when an SPF evaluation looked them up
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mx_hosts: Vec<String>,
+}
+
+/// Offline resolver answering from a fixed set of recorded DNS answers.
+///
+/// Domains missing from the snapshot behave like NXDOMAIN, so an empty
+/// snapshot performs a fully offline analysis with no DNS evidence.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DnsSnapshot {
+    pub domains: BTreeMap<String, SnapshotRecords>,
+}
+
+impl DnsSnapshot {
+    fn records(&self, domain: &str) -> Option<&SnapshotRecords> {
+        let domain = domain.trim_end_matches('.');
+        self.domains.get(domain).or_else(|| {
+            self.domains
+                .iter()
+                .find(|(name, _)| name.trim_end_matches('.').eq_ignore_ascii_case(domain))
+                .map(|(_, records)| records)
+        })
+    }
+}
+
+#[async_trait]
+impl ResolverTrait for DnsSnapshot {
+    async fn resolve_spf(&self, domain: &str) -> Option<String> {
+        self.records(domain)?.spf.clone()
+    }
+
+    async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
+        self.records(domain)?.dmarc.clone()
+    }
+
+    async fn domain_exists(&self, domain: &str) -> bool {
+        self.records(domain).is_some_and(|r| r.exists || r.mx)
+    }
+
+    async fn resolve_mx(&self, domain: &str) -> bool {
+        self.records(domain).is_some_and(|r| r.mx)
+    }
+
+    async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        self.records(name)
+            .map(|r| r.addresses.clone())
+            .unwrap_or_default()
+    }
+
+    async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        self.records(domain)
+            .map(|r| r.mx_hosts.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Resolver passing queries through to another one and recording the answers, so an
+/// analysis can later be repeated against the same DNS
+pub struct SnapshotRecorder<'a, R> {
+    inner: &'a R,
+    snapshot: Mutex<DnsSnapshot>,
+}
+
+impl<'a, R> SnapshotRecorder<'a, R> {
+    pub fn new(inner: &'a R) -> Self {
+        Self {
+            inner,
+            snapshot: Mutex::new(DnsSnapshot::default()),
+        }
+    }
+
+    /// The answers seen so far
+    pub fn into_snapshot(self) -> DnsSnapshot {
+        self.snapshot.into_inner().unwrap()
+    }
+
+    fn record(&self, domain: &str, update: impl FnOnce(&mut SnapshotRecords)) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        update(snapshot.domains.entry(domain.to_string()).or_default());
+    }
+}
+
+#[async_trait]
+impl<R: ResolverTrait + Sync + Send> ResolverTrait for SnapshotRecorder<'_, R> {
+    async fn resolve_spf(&self, domain: &str) -> Option<String> {
+        let spf = self.inner.resolve_spf(domain).await;
+        self.record(domain, |records| records.spf = spf.clone());
+        spf
+    }
+
+    async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
+        let dmarc = self.inner.resolve_dmarc(domain).await;
+        self.record(domain, |records| records.dmarc = dmarc.clone());
+        dmarc
+    }
+
+    async fn domain_exists(&self, domain: &str) -> bool {
+        let exists = self.inner.domain_exists(domain).await;
+        self.record(domain, |records| records.exists = exists);
+        exists
+    }
+
+    async fn resolve_mx(&self, domain: &str) -> bool {
+        let mx = self.inner.resolve_mx(domain).await;
+        self.record(domain, |records| records.mx = mx);
+        mx
+    }
+
+    async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        let addresses = self.inner.addresses(name).await;
+        self.record(name, |records| records.addresses = addresses.clone());
+        addresses
+    }
+
+    async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        let hosts = self.inner.mx_hosts(domain).await;
+        self.record(domain, |records| records.mx_hosts = hosts.clone());
+        hosts
+    }
+
+    fn disagreements(&self, domain: &str) -> Vec<DnsDisagreement> {
+        self.inner.disagreements(domain)
+    }
+}
+
+/// Resolver giving up on lookups still pending at a deadline
+///
+/// A lookup cut off counts as unanswered, except that the domain is assumed to exist so
+/// a slow resolver is not reported as a nonexistent sender domain. Without a deadline,
+/// queries are passed through unchanged.
+pub struct DeadlineResolver<'a, R> {
+    inner: &'a R,
+    deadline: Option<Instant>,
+    expired: AtomicBool,
+}
+
+impl<'a, R> DeadlineResolver<'a, R> {
+    pub fn new(inner: &'a R, deadline: Option<Instant>) -> Self {
+        Self {
+            inner,
+            deadline,
+            expired: AtomicBool::new(false),
+        }
+    }
+
+    /// Whether any lookup was cut off
+    pub fn expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    async fn bounded<T>(&self, lookup: impl Future<Output = T>, cut_off: T) -> T {
+        let Some(deadline) = self.deadline else {
+            return lookup.await;
+        };
+        match tokio::time::timeout_at(deadline, lookup).await {
+            Ok(answer) => answer,
+            Err(_) => {
+                self.expired.store(true, Ordering::Relaxed);
+                cut_off
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<R: ResolverTrait + Sync + Send> ResolverTrait for DeadlineResolver<'_, R> {
+    async fn resolve_spf(&self, domain: &str) -> Option<String> {
+        self.bounded(self.inner.resolve_spf(domain), None).await
+    }
+
+    async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
+        self.bounded(self.inner.resolve_dmarc(domain), None).await
+    }
+
+    async fn domain_exists(&self, domain: &str) -> bool {
+        self.bounded(self.inner.domain_exists(domain), true).await
+    }
+
+    async fn resolve_mx(&self, domain: &str) -> bool {
+        self.bounded(self.inner.resolve_mx(domain), false).await
+    }
+
+    async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        self.bounded(self.inner.addresses(name), Vec::new()).await
+    }
+
+    async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        self.bounded(self.inner.mx_hosts(domain), Vec::new()).await
+    }
+
+    fn disagreements(&self, domain: &str) -> Vec<DnsDisagreement> {
+        self.inner.disagreements(domain)
+    }
+}
+
+/// Resolver timing the lookups made through it, by class, for [`crate::timing::Timings::dns`]
+pub struct TimingResolver<'a, R> {
+    inner: &'a R,
+    timings: Mutex<BTreeMap<&'static str, Timing>>,
+}
+
+impl<'a, R> TimingResolver<'a, R> {
+    pub fn new(inner: &'a R) -> Self {
+        Self {
+            inner,
+            timings: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Time spent so far by lookup class
+    pub fn timings(&self) -> BTreeMap<String, Timing> {
+        let timings = self.timings.lock().unwrap();
+        timings
+            .iter()
+            .map(|(class, timing)| (class.to_string(), *timing))
+            .collect()
+    }
+
+    async fn timed<T>(&self, class: &'static str, lookup: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let answer = lookup.await;
+        let mut timings = self.timings.lock().unwrap();
+        let timing = timings.entry(class).or_default();
+        timing.count += 1;
+        timing.seconds += start.elapsed().as_secs_f64();
+        answer
+    }
+}
+
+#[async_trait]
+impl<R: ResolverTrait + Sync + Send> ResolverTrait for TimingResolver<'_, R> {
+    async fn resolve_spf(&self, domain: &str) -> Option<String> {
+        self.timed("spf", self.inner.resolve_spf(domain)).await
+    }
+
+    async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
+        self.timed("dmarc", self.inner.resolve_dmarc(domain)).await
+    }
+
+    async fn domain_exists(&self, domain: &str) -> bool {
+        self.timed("exists", self.inner.domain_exists(domain)).await
+    }
+
+    async fn resolve_mx(&self, domain: &str) -> bool {
+        self.timed("mx", self.inner.resolve_mx(domain)).await
+    }
+
+    async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        self.timed("addresses", self.inner.addresses(name)).await
+    }
+
+    async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        self.timed("mx_hosts", self.inner.mx_hosts(domain)).await
+    }
+
+    fn disagreements(&self, domain: &str) -> Vec<DnsDisagreement> {
+        self.inner.disagreements(domain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DnsResolver, DnsSnapshot, ResolverTrait, SnapshotRecorder};
+    use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+
+    #[tokio::test]
+    async fn test_snapshot_lookup_is_case_insensitive() {
+        let snapshot: DnsSnapshot = serde_json::from_str(
+            r#"{"domains": {"Example.com.": {"spf": "v=spf1 -all", "mx": true}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            snapshot.resolve_spf("example.com").await.as_deref(),
+            Some("v=spf1 -all")
+        );
+        assert!(snapshot.domain_exists("EXAMPLE.COM").await);
+        assert_eq!(snapshot.resolve_dmarc("example.com").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_empty_snapshot_is_offline() {
+        let snapshot = DnsSnapshot::default();
+        assert!(!snapshot.domain_exists("example.com").await);
+        assert_eq!(snapshot.resolve_spf("example.com").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_recorded_snapshot_answers_like_resolver() {
+        let live: DnsSnapshot = serde_json::from_str(
+            r#"{"domains": {"example.com": {"spf": "v=spf1 -all", "exists": true}}}"#,
+        )
+        .unwrap();
+        let recorder = SnapshotRecorder::new(&live);
+        recorder.resolve_spf("example.com").await;
+        recorder.domain_exists("example.com").await;
+        recorder.resolve_dmarc("missing.example").await;
+
+        let snapshot = recorder.into_snapshot();
+        assert_eq!(
+            snapshot.resolve_spf("example.com").await.as_deref(),
+            Some("v=spf1 -all")
+        );
+        assert!(snapshot.domain_exists("example.com").await);
+        assert!(!snapshot.domain_exists("missing.example").await);
+    }
+
+    #[tokio::test]
+    async fn test_resolver_stats_count_failures_by_cause() {
+        let resolver = DnsResolver::new().unwrap();
+        let answered = resolver
+            .counted("TXT", "example.com", |_| async { Ok(()) })
+            .await;
+        let timed_out = resolver
+            .counted("TXT", "example.com", |_| async {
+                Err::<(), _>(ResolveError::from(ResolveErrorKind::Timeout))
+            })
+            .await;
+        assert!(answered.is_ok() && timed_out.is_err());
+
+        let stats = resolver.clone().stats();
+        assert!(!stats.nameservers.is_empty());
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.queries["TXT"].count, 2);
+        assert_eq!(stats.queries["TXT"].errors, 1);
+        assert_eq!(stats.errors["timeout"], 1);
+    }
 }
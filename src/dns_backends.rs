@@ -0,0 +1,264 @@
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use trust_dns_resolver::{
+    TokioAsyncResolver,
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+};
+
+/// Consecutive failures after which a backend is tried only after the healthy ones
+const FAILURE_THRESHOLD: u32 = 3;
+
+/// How long a failing backend is put last before it is trusted again
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// How long a backend may go without answering before the next one is queried too
+const STAGGER: Duration = Duration::from_millis(300);
+
+#[derive(Default)]
+struct Health {
+    consecutive_failures: u32,
+    down_until: Option<Instant>,
+    failures: u64,
+}
+
+/// One resolver configuration queries can be sent to
+pub struct Backend {
+    name: String,
+    resolver: Arc<TokioAsyncResolver>,
+    nameservers: Vec<String>,
+    health: Mutex<Health>,
+}
+
+/// Health of a backend, as reported in `ResolverStats::backends`
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BackendStats {
+    /// The spec the backend was configured with, e.g. `system` or `192.0.2.53`
+    pub name: String,
+    /// False after repeated failures, until the backend answers again
+    pub healthy: bool,
+    /// Queries that timed out or could not reach the backend
+    pub failures: u64,
+}
+
+impl Backend {
+    /// Builds a backend from its spec:
+    ///
+    /// - `default`: the resolver's built-in public nameservers
+    /// - `system`: the nameservers of `/etc/resolv.conf`
+    /// - `192.0.2.53`, `192.0.2.53:5353`, `[2001:db8::53]:53`: a specific upstream
+    /// - `cloudflare-doh`, `google-doh`, `quad9-doh`, `doh:1.1.1.1@cloudflare-dns.com`:
+    ///   DNS over HTTPS, with the `doh` feature
+    pub fn from_spec(spec: &str) -> anyhow::Result<Self> {
+        let (config, opts) = match spec {
+            "default" => (ResolverConfig::default(), ResolverOpts::default()),
+            "system" => trust_dns_resolver::system_conf::read_system_conf()?,
+            _ if spec.starts_with("doh:") || spec.ends_with("-doh") => doh_config(spec)?,
+            _ => {
+                let addr = socket_addr(spec, 53)?;
+                let group = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true);
+                (
+                    ResolverConfig::from_parts(None, Vec::new(), group),
+                    ResolverOpts::default(),
+                )
+            }
+        };
+        let mut nameservers: Vec<String> = config
+            .name_servers()
+            .iter()
+            .map(|ns| ns.socket_addr.to_string())
+            .collect();
+        nameservers.dedup();
+        Ok(Self {
+            name: spec.to_string(),
+            resolver: Arc::new(TokioAsyncResolver::tokio(config, opts)),
+            nameservers,
+            health: Mutex::default(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn nameservers(&self) -> &[String] {
+        &self.nameservers
+    }
+
+    pub fn stats(&self) -> BackendStats {
+        let health = self.health.lock().unwrap();
+        BackendStats {
+            name: self.name.clone(),
+            healthy: health.down_until.is_none(),
+            failures: health.failures,
+        }
+    }
+
+    fn is_up(&self, now: Instant) -> bool {
+        let health = self.health.lock().unwrap();
+        health.down_until.is_none_or(|until| now >= until)
+    }
+
+    fn record(&self, answered: bool) {
+        let mut health = self.health.lock().unwrap();
+        if answered {
+            *health = Health {
+                failures: health.failures,
+                ..Health::default()
+            };
+        } else {
+            health.failures += 1;
+            health.consecutive_failures += 1;
+            if health.consecutive_failures >= FAILURE_THRESHOLD {
+                health.down_until = Some(Instant::now() + RETRY_AFTER);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "doh")]
+fn doh_config(spec: &str) -> anyhow::Result<(ResolverConfig, ResolverOpts)> {
+    let config = match spec {
+        "cloudflare-doh" => ResolverConfig::cloudflare_https(),
+        "google-doh" => ResolverConfig::google_https(),
+        "quad9-doh" => ResolverConfig::quad9_https(),
+        _ => {
+            let Some((addr, tls_name)) = spec.trim_start_matches("doh:").split_once('@') else {
+                anyhow::bail!("{}: expected doh:<ip>@<tls name>", spec);
+            };
+            let addr = socket_addr(addr, 443)?;
+            let group = NameServerConfigGroup::from_ips_https(
+                &[addr.ip()],
+                addr.port(),
+                tls_name.to_string(),
+                true,
+            );
+            ResolverConfig::from_parts(None, Vec::new(), group)
+        }
+    };
+    Ok((config, ResolverOpts::default()))
+}
+
+#[cfg(not(feature = "doh"))]
+fn doh_config(spec: &str) -> anyhow::Result<(ResolverConfig, ResolverOpts)> {
+    anyhow::bail!("{}: DNS over HTTPS needs the doh feature", spec)
+}
+
+/// `192.0.2.53`, `192.0.2.53:5353`, `2001:db8::53`, or `[2001:db8::53]:53`
+fn socket_addr(spec: &str, default_port: u16) -> anyhow::Result<SocketAddr> {
+    if let Ok(addr) = spec.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    match spec.parse::<IpAddr>() {
+        Ok(ip) => Ok(SocketAddr::new(ip, default_port)),
+        Err(_) => anyhow::bail!("{}: not a DNS backend", spec),
+    }
+}
+
+/// Whether `error` is an answer from the nameserver rather than a failure to get one
+pub(crate) fn is_answer(error: &ResolveError) -> bool {
+    matches!(error.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+async fn attempt<T>(
+    backend: &Backend,
+    answer: impl Future<Output = Result<T, ResolveError>>,
+) -> (&Backend, Result<T, ResolveError>) {
+    (backend, answer.await)
+}
+
+/// Runs `query` against the backends, happy-eyeballs style
+///
+/// Healthy backends go first, in configured order. The next backend is queried when
+/// the previous one fails or has not answered within a short delay, and the first
+/// answer wins, so one resolver outage costs a delay rather than the answer.
+pub async fn query<T, F, Fut>(backends: &[Backend], query: F) -> Result<T, ResolveError>
+where
+    F: Fn(Arc<TokioAsyncResolver>) -> Fut,
+    Fut: Future<Output = Result<T, ResolveError>>,
+{
+    let now = Instant::now();
+    let mut waiting: Vec<&Backend> = backends.iter().collect();
+    // Stable, so healthy backends keep their configured order
+    waiting.sort_by_key(|backend| !backend.is_up(now));
+    waiting.reverse();
+
+    let mut running = FuturesUnordered::new();
+    let mut last_error = ResolveError::from(ResolveErrorKind::Message("no DNS backends"));
+    let Some(first) = waiting.pop() else {
+        return Err(last_error);
+    };
+    running.push(attempt(first, query(first.resolver.clone())));
+    loop {
+        tokio::select! {
+            Some((backend, answer)) = running.next() => {
+                match answer {
+                    Err(error) if !is_answer(&error) => {
+                        backend.record(false);
+                        last_error = error;
+                    }
+                    answer => {
+                        backend.record(true);
+                        return answer;
+                    }
+                }
+                match waiting.pop() {
+                    Some(next) => running.push(attempt(next, query(next.resolver.clone()))),
+                    None if running.is_empty() => return Err(last_error),
+                    None => {}
+                }
+            }
+            _ = tokio::time::sleep(STAGGER), if !waiting.is_empty() => {
+                if let Some(next) = waiting.pop() {
+                    running.push(attempt(next, query(next.resolver.clone())));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, query};
+    use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
+
+    #[tokio::test]
+    async fn test_query_fails_over_to_the_next_backend() {
+        let backends = [
+            Backend::from_spec("192.0.2.1").unwrap(),
+            Backend::from_spec("[2001:db8::1]:5353").unwrap(),
+        ];
+        assert_eq!(backends[1].nameservers()[0], "[2001:db8::1]:5353");
+        let primary = std::sync::Arc::as_ptr(&backends[0].resolver);
+
+        for _ in 0..3 {
+            let answer = query(&backends, |resolver| async move {
+                if std::sync::Arc::as_ptr(&resolver) == primary {
+                    Err(ResolveError::from(ResolveErrorKind::Timeout))
+                } else {
+                    Ok("answer")
+                }
+            })
+            .await;
+            assert_eq!(answer.unwrap(), "answer");
+        }
+        let stats = backends[0].stats();
+        assert_eq!((stats.healthy, stats.failures), (false, 3));
+        assert!(backends[1].stats().healthy);
+
+        // The failing backend is now tried last, so the other one answers first
+        let answer = query(&backends, |resolver| async move {
+            match std::sync::Arc::as_ptr(&resolver) == primary {
+                true => Ok("primary"),
+                false => Ok("secondary"),
+            }
+        })
+        .await;
+        assert_eq!(answer.unwrap(), "secondary");
+
+        assert!(Backend::from_spec("resolver.example").is_err());
+    }
+}
@@ -1,9 +1,13 @@
-use crate::dns::ResolverTrait;
 use crate::DnsResolver;
-use std::future::Future;
-use std::pin::Pin;
+use crate::dns::ResolverTrait;
+use crate::lint::RecordSource;
+use crate::spf_lint::MAX_DNS_LOOKUPS;
+pub use crate::spf_walk::SpfNode;
+use crate::spf_walk::walk_spf;
+use async_trait::async_trait;
 
-const MAX_SPF_DEPTH: usize = 10;
+/// Common DKIM selectors; intentionally small allowlist
+pub const COMMON_DKIM_SELECTORS: [&str; 4] = ["default", "google", "selector1", "selector2"];
 
 #[derive(Debug, serde::Serialize)]
 pub enum DomainVerdict {
@@ -16,65 +20,111 @@ pub enum DomainVerdict {
 /// Structured evaluation of SPF
 #[derive(Debug, Default, serde::Serialize)]
 pub struct SpfEvaluation {
+    /// The domain's default result is `fail`: its record, or the record it redirects to,
+    /// ends in `-all`
     pub has_strict_all: bool,
+    /// The default result is `softfail` or `neutral` (`~all`, `?all`)
     pub has_soft_all: bool,
+    /// Any sender passes: the default result is `pass` (`+all`, or a bare `all`), or a
+    /// record included before the `all` passes any sender
+    pub has_pass_all: bool,
+    /// The record has no `all`, nor a redirect to a record with one, so mail it does
+    /// not match is `neutral`
+    pub missing_all: bool,
+    /// DNS lookups evaluating the record takes: `include`, `redirect`, `a`, `mx`, `ptr`,
+    /// and `exists` terms, over all records followed
+    pub lookup_count: usize,
+    /// Receivers evaluate the record to `permerror`: it needs more than 10 lookups,
+    /// includes or redirects to a domain without a record, or loops
+    pub permerror: bool,
+    /// The record and the records it includes or redirects to; absent without a record
+    pub tree: Option<SpfNode>,
+}
+
+impl SpfNode {
+    /// The `all` term deciding mail this record does not match, e.g. `-all`: the
+    /// record's own, or else that of the record it redirects to
+    ///
+    /// An included record's `all` only ends the include, so it does not count.
+    fn default_all(&self) -> Option<String> {
+        let own = self.mechanisms.iter().find_map(|term| {
+            let lower = term.to_ascii_lowercase();
+            let all = lower.trim_start_matches(['+', '-', '~', '?']) == "all";
+            all.then(|| match lower.as_bytes()[0] {
+                b'-' | b'~' | b'?' | b'+' => lower,
+                _ => format!("+{}", lower),
+            })
+        });
+        own.or_else(|| {
+            self.children
+                .iter()
+                .find(|child| child.via.as_deref() == Some("redirect"))
+                .and_then(SpfNode::default_all)
+        })
+    }
+
+    /// Whether the record passes any sender: through its `all`, a record it includes
+    /// before that, or the record it redirects to
+    fn passes_all(&self) -> bool {
+        // Children follow the include terms in order, so each include finds its own
+        let mut includes = self
+            .children
+            .iter()
+            .filter(|child| child.via.as_deref() == Some("include"));
+        for term in &self.mechanisms {
+            let lower = term.to_ascii_lowercase();
+            let (pass, name) = match lower.strip_prefix(['+', '-', '~', '?']) {
+                Some(name) => (lower.starts_with('+'), name),
+                None => (true, lower.as_str()),
+            };
+            if name == "all" {
+                return pass;
+            }
+            let Some(target) = name.strip_prefix("include:") else {
+                continue;
+            };
+            let included = includes.find(|child| child.domain.eq_ignore_ascii_case(target));
+            // An include passing everyone matches everyone, with its own qualifier
+            if included.is_some_and(SpfNode::passes_all) {
+                return pass;
+            }
+        }
+        self.children
+            .iter()
+            .find(|child| child.via.as_deref() == Some("redirect"))
+            .is_some_and(SpfNode::passes_all)
+    }
 }
 
 /// Structured SPF resolver entrypoint
-pub async fn resolve_spf_structured(
-    resolver: &DnsResolver,
+pub async fn resolve_spf_structured<R: ResolverTrait + Sync>(
+    resolver: &R,
     domain: &str,
     depth: usize,
 ) -> SpfEvaluation {
-    resolve_spf_structured_inner(resolver, domain, depth).await
+    let walk = walk_spf(&SpfRecords(resolver), domain, depth, false).await;
+    let tree = walk.tree.filter(|node| node.record.is_some());
+    let default_all = tree.as_ref().and_then(SpfNode::default_all);
+    let permerror = tree.is_some() && (walk.broken || walk.lookups > MAX_DNS_LOOKUPS);
+    SpfEvaluation {
+        has_strict_all: default_all.as_deref() == Some("-all"),
+        has_soft_all: matches!(default_all.as_deref(), Some("~all" | "?all")),
+        has_pass_all: tree.as_ref().is_some_and(SpfNode::passes_all),
+        missing_all: tree.is_some() && default_all.is_none(),
+        lookup_count: tree.as_ref().map_or(0, |node| node.lookup_count),
+        permerror,
+        tree,
+    }
 }
 
-/// Boxed recursive SPF resolver
-fn resolve_spf_structured_inner<'a>(
-    resolver: &'a DnsResolver,
-    domain: &'a str,
-    depth: usize,
-) -> Pin<Box<dyn Future<Output = SpfEvaluation> + Send + 'a>> {
-    Box::pin(async move {
-        if depth >= MAX_SPF_DEPTH {
-            // Depth limit reached, stop recursion safely
-            return SpfEvaluation::default();
-        }
+/// The SPF records of an analysis resolver, for the shared SPF walk
+struct SpfRecords<'a, R>(&'a R);
 
-        let spf_txt = match resolver.resolve_spf(domain).await {
-            Some(txt) => txt,
-            None => return SpfEvaluation::default(),
-        };
-
-        let mut eval = SpfEvaluation::default();
-
-        for part in spf_txt.split_whitespace() {
-            match part {
-                "-all" => eval.has_strict_all = true,
-                "~all" | "?all" => eval.has_soft_all = true,
-                _ => {}
-            }
-
-            if let Some(include_domain) = part.strip_prefix("include:") {
-                let child = resolve_spf_structured_inner(
-                    resolver,
-                    include_domain,
-                    depth + 1,
-                )
-                    .await;
-
-                eval.has_strict_all |= child.has_strict_all;
-                eval.has_soft_all |= child.has_soft_all;
-            }
-
-            // Fast exit if strongest signals are already found
-            if eval.has_strict_all && eval.has_soft_all {
-                break;
-            }
-        }
-
-        eval
-    })
+#[async_trait]
+impl<R: ResolverTrait + Sync> RecordSource for SpfRecords<'_, R> {
+    async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+        self.0.resolve_spf(name).await.map(|record| vec![record])
+    }
 }
 
 /// Compute verdict using structured SPF + DMARC
@@ -87,39 +137,188 @@ pub fn calculate_domain_verdict(
         return DomainVerdict::Invalid;
     }
 
+    // A broken SPF record authorizes nothing and protects nothing
+    let (strict_all, soft_all) = if spf_eval.permerror {
+        (false, false)
+    } else {
+        (spf_eval.has_strict_all, spf_eval.has_soft_all)
+    };
+    // Anyone can send as a domain whose SPF passes every sender, and the pass aligns
+    // for DMARC, so no policy protects it
+    if spf_eval.has_pass_all && !spf_eval.permerror {
+        return DomainVerdict::Invalid;
+    }
     let dmarc_policy = dmarc.unwrap_or("");
     let dmarc_strong = dmarc_policy.contains("p=reject");
     let dmarc_medium = dmarc_policy.contains("p=quarantine");
 
-    match (
-        spf_eval.has_strict_all,
-        spf_eval.has_soft_all,
-        dmarc_strong,
-        dmarc_medium,
-    ) {
+    match (strict_all, soft_all, dmarc_strong, dmarc_medium) {
         (true, _, true, _) => DomainVerdict::Strong,
         (_, _, true, _) => DomainVerdict::Medium,
+        // Without an `all`, unmatched mail is neutral: only DMARC can protect the domain
+        _ if spf_eval.missing_all => DomainVerdict::Weak,
         (_, true, _, _) => DomainVerdict::Medium,
         _ => DomainVerdict::Weak,
     }
 }
 
-
 /// Check DKIM selector presence
-pub async fn resolve_dkim(
-    resolver: &DnsResolver,
-    domain: &str,
-) -> bool {
-    // Common selectors; intentionally small allowlist
-    const SELECTORS: [&str; 4] = ["default", "google", "selector1", "selector2"];
+pub async fn resolve_dkim(resolver: &DnsResolver, domain: &str) -> bool {
+    !resolve_dkim_selectors(resolver, domain).await.is_empty()
+}
 
-    for selector in SELECTORS {
+/// Common DKIM selectors that publish a key record
+pub async fn resolve_dkim_selectors(resolver: &DnsResolver, domain: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    for selector in COMMON_DKIM_SELECTORS {
         let name = format!("{}._domainkey.{}", selector, domain);
         if resolver.resolve_txt(&name).await.is_some() {
-            return true ;
+            found.push(selector.to_string());
         }
     }
 
-    false
+    found
 }
 
+#[cfg(test)]
+mod tests {
+    use super::{DomainVerdict, calculate_domain_verdict, resolve_spf_structured};
+    use crate::dns::DnsSnapshot;
+
+    #[tokio::test]
+    async fn test_spf_tree() {
+        let dns: DnsSnapshot = serde_json::from_value(serde_json::json!({"domains": {
+            "example.com": {"spf": "v=spf1 mx include:_spf.example.net redirect=loop.example"},
+            "_spf.example.net": {"spf": "v=spf1 a ~all"},
+            "loop.example": {"spf": "v=spf1 include:Example.com -all"},
+        }}))
+        .unwrap();
+        let eval = resolve_spf_structured(&dns, "example.com", 0).await;
+        // The redirect target's `-all` decides; the included `~all` does not
+        assert!(eval.has_strict_all && !eval.has_soft_all);
+        // loop.example includes example.com again
+        assert!(eval.permerror);
+        assert_eq!(eval.lookup_count, 5);
+
+        let tree = serde_json::to_value(eval.tree.unwrap()).unwrap();
+        assert_eq!(tree["lookup_count"], 5);
+        assert_eq!(tree.get("via"), None);
+        assert_eq!(
+            tree["mechanisms"],
+            serde_json::json!(["mx", "include:_spf.example.net", "redirect=loop.example"])
+        );
+        let children = tree["children"].as_array().unwrap();
+        assert_eq!(children[0]["domain"], "_spf.example.net");
+        assert_eq!(children[0]["via"], "include");
+        assert_eq!(children[1]["via"], "redirect");
+        assert_eq!(children[1]["children"][0]["domain"], "example.com");
+        assert_eq!(children[1]["children"][0]["repeated"], true);
+
+        let dns: DnsSnapshot = serde_json::from_value(serde_json::json!({"domains": {
+            "example.org": {"spf": "v=spf1 ip4:192.0.2.0/24 ~all redirect=strict.example"},
+            "strict.example": {"spf": "v=spf1 -all"},
+        }}))
+        .unwrap();
+        let eval = resolve_spf_structured(&dns, "example.org", 0).await;
+        assert!(eval.has_soft_all && !eval.has_strict_all);
+        assert_eq!((eval.lookup_count, eval.permerror), (0, false));
+        assert!(eval.tree.unwrap().children.is_empty());
+
+        let none = resolve_spf_structured(&dns, "missing.example", 0).await;
+        assert!(none.tree.is_none() && !none.has_soft_all && !none.permerror);
+    }
+
+    #[tokio::test]
+    async fn test_spf_permerror() {
+        let dns: DnsSnapshot = serde_json::from_value(serde_json::json!({"domains": {
+            "broken.example": {"spf": "v=spf1 include:gone.example -all"},
+            "busy.example": {"spf": "v=spf1 a mx include:_spf.busy.example -all"},
+            "_spf.busy.example": {"spf": "v=spf1 a:x.test mx:y.test exists:z.test ptr include:_deep.busy.example"},
+            "_deep.busy.example": {"spf": "v=spf1 a:1.test a:2.test a:3.test a:4.test ~all"},
+        }}))
+        .unwrap();
+
+        let eval = resolve_spf_structured(&dns, "broken.example", 0).await;
+        assert!(eval.permerror && eval.has_strict_all);
+        assert!(matches!(
+            calculate_domain_verdict(true, &eval, Some("v=DMARC1; p=reject")),
+            DomainVerdict::Medium
+        ));
+
+        let eval = resolve_spf_structured(&dns, "busy.example", 0).await;
+        assert_eq!(eval.lookup_count, 12);
+        assert!(eval.permerror);
+    }
+
+    #[tokio::test]
+    async fn test_spf_shared_include() {
+        let dns: DnsSnapshot = serde_json::from_value(serde_json::json!({"domains": {
+            "shared.example": {"spf": "v=spf1 a include:crm.example include:esp.example -all"},
+            "crm.example": {"spf": "v=spf1 include:_spf.cloud.example ~all"},
+            "esp.example": {"spf": "v=spf1 include:_spf.cloud.example ~all"},
+            "_spf.cloud.example": {"spf": "v=spf1 a mx exists:%{i}.cloud.example ~all"},
+        }}))
+        .unwrap();
+
+        // Receivers evaluate the shared record once per include reaching it
+        let eval = resolve_spf_structured(&dns, "shared.example", 0).await;
+        assert_eq!(eval.lookup_count, 11);
+        assert!(eval.permerror);
+        let tree = eval.tree.unwrap();
+        for include in &tree.children {
+            let cloud = &include.children[0];
+            assert!(!cloud.repeated);
+            assert_eq!(cloud.lookup_count, 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spf_pass_all_and_missing_all() {
+        let dns: DnsSnapshot = serde_json::from_value(serde_json::json!({"domains": {
+            "open.example": {"spf": "v=spf1 mx +all"},
+            "bare.example": {"spf": "v=spf1 all"},
+            "vendor.example": {"spf": "v=spf1 include:open.example -all"},
+            "late.example": {"spf": "v=spf1 -all include:open.example"},
+            "failing.example": {"spf": "v=spf1 -include:open.example ~all"},
+            // open.example is reached under `-include:` first, then under `include:`
+            "twice.example": {"spf": "v=spf1 include:failing.example include:open.example -all"},
+            "unended.example": {"spf": "v=spf1 ip4:192.0.2.0/24"},
+            "redirected.example": {"spf": "v=spf1 redirect=unended.example"},
+        }}))
+        .unwrap();
+        let reject = Some("v=DMARC1; p=reject");
+
+        for domain in [
+            "open.example",
+            "bare.example",
+            "vendor.example",
+            "twice.example",
+        ] {
+            let eval = resolve_spf_structured(&dns, domain, 0).await;
+            assert!(eval.has_pass_all && !eval.missing_all, "{domain}");
+            assert!(matches!(
+                calculate_domain_verdict(true, &eval, reject),
+                DomainVerdict::Invalid
+            ));
+        }
+        for domain in ["late.example", "failing.example"] {
+            let eval = resolve_spf_structured(&dns, domain, 0).await;
+            assert!(!eval.has_pass_all, "{domain}");
+        }
+
+        for domain in ["unended.example", "redirected.example"] {
+            let eval = resolve_spf_structured(&dns, domain, 0).await;
+            assert!(eval.missing_all && !eval.has_pass_all, "{domain}");
+            assert!(matches!(
+                calculate_domain_verdict(true, &eval, None),
+                DomainVerdict::Weak
+            ));
+            assert!(matches!(
+                calculate_domain_verdict(true, &eval, reject),
+                DomainVerdict::Medium
+            ));
+        }
+        let none = resolve_spf_structured(&dns, "missing.example", 0).await;
+        assert!(!none.missing_all && !none.has_pass_all);
+    }
+}
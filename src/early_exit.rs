@@ -0,0 +1,85 @@
+use crate::email_verdict::{AnalysisResult, Verdict};
+
+/// Why the third-party enrichments of a deep analysis were skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EarlyExit {
+    /// The risk score already reached the decisive score; enrichments only add risk
+    Decisive,
+    /// The message is authenticated and nothing about it was reported
+    Clean,
+}
+
+/// When a deep analysis may stop before its enrichments (passive DNS, registration, CT
+/// logs, URL expansion), which cost third-party round trips for every message
+///
+/// The default never stops early.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EarlyExitPolicy {
+    /// Risk score from which a message is handled as a threat whatever else is found
+    pub decisive_score: Option<u32>,
+    /// Skip the enrichments of authenticated messages without any reason
+    pub skip_clean: bool,
+}
+
+impl EarlyExitPolicy {
+    /// Whether `result` is decided enough to skip its enrichments, and why
+    ///
+    /// Partial results are never decided: their evidence is incomplete.
+    pub fn decide(&self, result: &AnalysisResult) -> Option<EarlyExit> {
+        if result.partial {
+            return None;
+        }
+        if self
+            .decisive_score
+            .is_some_and(|score| result.risk_score >= score)
+        {
+            return Some(EarlyExit::Decisive);
+        }
+        let clean = result.verdict == Verdict::Authenticated && result.reasons.is_empty();
+        (self.skip_clean && clean).then_some(EarlyExit::Clean)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EarlyExit, EarlyExitPolicy};
+    use crate::email_verdict::{AnalysisDepth, AnalysisOptions, analyze_email_at_depth};
+    use crate::{dns::DnsSnapshot, parse::parse_email};
+
+    #[tokio::test]
+    async fn test_decide() {
+        let snapshot: DnsSnapshot = serde_json::from_str(
+            r#"{"domains": {"example.com": {"spf": "v=spf1 -all", "dmarc": "v=DMARC1; p=reject", "mx": true}}}"#,
+        )
+        .unwrap();
+        let options = AnalysisOptions::default();
+        let analyze = |raw: &'static [u8]| {
+            let parsed = parse_email(raw).unwrap();
+            let (snapshot, options) = (&snapshot, &options);
+            async move {
+                analyze_email_at_depth(&parsed, snapshot, options, AnalysisDepth::Deep)
+                    .await
+                    .unwrap()
+            }
+        };
+        let clean =
+            analyze(b"From: a@example.com\r\nDKIM-Signature: v=1; d=example.com\r\n\r\nHi").await;
+        let unknown = analyze(b"From: a@unknown.example\r\n\r\nHi").await;
+
+        let policy = EarlyExitPolicy {
+            decisive_score: Some(unknown.risk_score),
+            skip_clean: true,
+        };
+        assert_eq!(policy.decide(&clean), Some(EarlyExit::Clean));
+        assert_eq!(policy.decide(&unknown), Some(EarlyExit::Decisive));
+        assert_eq!(EarlyExitPolicy::default().decide(&clean), None);
+
+        let stricter = EarlyExitPolicy {
+            decisive_score: Some(unknown.risk_score + 1),
+            skip_clean: false,
+        };
+        assert_eq!(stricter.decide(&unknown), None);
+        assert_eq!(stricter.decide(&clean), None);
+    }
+}
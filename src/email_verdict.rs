@@ -1,4 +1,33 @@
-use crate::{dns::ResolverTrait, parse::EmailParsed};
+use std::pin::Pin;
+
+use crate::{
+    attachments::Attachment,
+    automated::{AutomatedMessage, detect_automated},
+    body::{BodyEvidence, add_attachment_urls, analyze_body},
+    checks::spf::{EnvelopeSpf, SpfExplanation, SpfResult, explain_ip},
+    dedup::message_hash,
+    dkim_coverage::{DkimCoverage, dkim_coverage},
+    dkim_replay::{DkimReplayCheck, check_replay},
+    dmarc_lint::parse_tags,
+    dns::{DnsDisagreement, ResolverTrait, TimingResolver},
+    early_exit::EarlyExit,
+    envelope::{EnvelopeSender, envelope_sender},
+    fingerprint::{FingerprintAnomaly, MailerFingerprint, fingerprint},
+    idn::{DomainLabels, domain_labels},
+    lists::{ListMatch, SenderLists, check_lists},
+    lookalike::{LookalikeMatch, find_lookalike},
+    messages::Lang,
+    parse::{AuthResults, EmailParsed, ParseAnomaly, organizational_domain, parse_auth_results},
+    passive_dns::PassiveDnsFindings,
+    reasons::{Reason, Severity, explain, max_severity},
+    received::{ReceivedTimeline, received_timeline},
+    registration::RegistrationFindings,
+    rules::{RuleSettings, clear_evidence},
+    session::{SessionEvidence, check_session},
+    text_heuristics::PhraseList,
+    timing::{Stopwatch, Timings},
+    trust_store::{InfrastructureCheck, TrustStore, check_infrastructure},
+};
 
 /// Final verdict enums
 /// Represents the final classification of an email after analysis.
@@ -11,24 +40,73 @@ use crate::{dns::ResolverTrait, parse::EmailParsed};
 /// - `Unauthenticated` – The email cannot be verified (missing SPF, DKIM, or DMARC records).
 /// - `Suspicious` – The email shows inconsistencies, but not enough to definitively label as spoofed.
 /// - `Indeterminate` – The verdict cannot be determined due to missing or malformed data.
-#[derive(Debug, serde::Serialize, PartialEq)]
+/// - `Automated` – A bounce or automatic reply that would otherwise be `Suspicious` or
+///   `Unauthenticated`; such mail legitimately fails many sender checks.
+#[derive(Debug, Clone, Copy, serde::Serialize, PartialEq)]
 pub enum Verdict {
     Authenticated,
     PolicyViolation,
     Unauthenticated,
     Suspicious,
     Indeterminate,
+    Automated,
+}
+
+impl Verdict {
+    /// Parses a verdict named as serialized (`PolicyViolation`) or in snake or kebab
+    /// case (`policy_violation`, `policy-violation`), ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.replace(['-', '_'], "").to_ascii_lowercase().as_str() {
+            "authenticated" => Some(Self::Authenticated),
+            "policyviolation" => Some(Self::PolicyViolation),
+            "unauthenticated" => Some(Self::Unauthenticated),
+            "suspicious" => Some(Self::Suspicious),
+            "indeterminate" => Some(Self::Indeterminate),
+            "automated" => Some(Self::Automated),
+            _ => None,
+        }
+    }
+}
+
+/// How thoroughly a message is analyzed
+///
+/// - `HeadersOnly` – Authentication headers, DNS, sender lists, and lookalikes; the body
+///   is not read. Meant as a cheap first pass for high-volume gateways.
+/// - `Standard` – Adds the body: links and, when enabled, text heuristics.
+/// - `Deep` – Standard plus the third-party enrichments the caller has configured
+///   (passive DNS, registration, CT logs, URL expansion).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisDepth {
+    HeadersOnly,
+    #[default]
+    Standard,
+    Deep,
+}
+
+impl AnalysisDepth {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.replace('-', "_").as_str() {
+            "headers_only" | "headers" => Some(Self::HeadersOnly),
+            "standard" => Some(Self::Standard),
+            "deep" => Some(Self::Deep),
+            _ => None,
+        }
+    }
 }
 
 /// Represents the individual evidence collected from the email that contributes to the final verdict.
 ///
 /// This struct contains both the raw extracted data and computed boolean indicators
 /// that describe alignment and authorization status.
-#[derive(Debug, serde::Serialize)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct Evidence {
     /// The domain extracted from the "From" header of the email.
     pub from_domain: Option<String>,
 
+    /// `from_domain` as A-label and U-label.
+    pub from_domain_labels: Option<DomainLabels>,
+
     /// The SPF record retrieved for the sender domain, if available.
     pub spf_policy: Option<String>,
 
@@ -38,6 +116,14 @@ pub struct Evidence {
     /// Indicates whether the sending IP is authorized by the SPF policy.
     pub spf_authorized: bool,
 
+    /// The SPF result of the `From` domain for the sending IP and the terms that
+    /// decided it, when the message names the IP.
+    pub spf_result: Option<SpfExplanation>,
+
+    /// The SPF result of the envelope sender (`Return-Path`) domain for the sending IP,
+    /// the identity SPF is defined on; `None` without an IP or for bounces.
+    pub envelope_spf_result: Option<EnvelopeSpf>,
+
     /// Indicates whether a DKIM signature is present in the email.
     pub dkim_present: bool,
 
@@ -45,6 +131,60 @@ pub struct Evidence {
     pub alignment_ok: bool,
 
     pub domain_valid: bool,
+
+    /// Authentication-Results from a trusted border MTA, when used instead of our own checks.
+    pub upstream_auth: Option<AuthResults>,
+
+    /// DNS history of the sender domain, when passive DNS enrichment is enabled.
+    pub passive_dns: Option<PassiveDnsFindings>,
+
+    /// Name servers and registrar of the sender domain, when registration lookups are enabled.
+    pub registration: Option<RegistrationFindings>,
+
+    /// Whether a message from an owned domain came from its registered sending infrastructure.
+    pub infrastructure: Option<InfrastructureCheck>,
+
+    /// The protected domain the sender domain imitates, if any.
+    pub lookalike: Option<LookalikeMatch>,
+
+    /// Allowlist, blocklist, or VIP entries the sender matched.
+    pub lists: Option<ListMatch>,
+
+    /// Links found in the message body.
+    pub body: BodyEvidence,
+
+    /// Relays from the `Received` headers, oldest first, with timestamp anomalies.
+    pub received: Option<ReceivedTimeline>,
+
+    /// The SMTP session reported by the receiving MTA, with HELO problems.
+    pub session: Option<SessionEvidence>,
+
+    /// The envelope sender (`Return-Path`), SRS-decoded, and its alignment with the "From" domain.
+    pub envelope: Option<EnvelopeSender>,
+
+    /// Why the message looks like a bounce or automatic reply, if it does.
+    pub automated: Option<AutomatedMessage>,
+
+    /// The mailer and header layout of the software that composed the message.
+    pub fingerprint: MailerFingerprint,
+
+    /// How the fingerprint departs from the sender domain's history, when analyses are stored.
+    pub fingerprint_anomaly: Option<FingerprintAnomaly>,
+
+    /// DKIM signatures showing signs of replay or modification after signing.
+    pub dkim_replay: Vec<DkimReplayCheck>,
+
+    /// The headers each DKIM signature covers, with critical ones broken out.
+    pub dkim_coverage: Vec<DkimCoverage>,
+
+    /// Malformed headers or MIME parts that parsing worked around.
+    pub parse_anomalies: Vec<ParseAnomaly>,
+
+    /// Attached files, with any resource limit their inspection ran into.
+    pub attachments: Vec<Attachment>,
+
+    /// SPF or DMARC records that independent resolvers disagreed on, when cross-checking.
+    pub dns_disagreements: Vec<DnsDisagreement>,
 }
 
 /// Represents the result of analyzing an email for spoofing.
@@ -53,11 +193,286 @@ pub struct Evidence {
 /// This struct is intended for both programmatic consumption (e.g., web API) and human inspection.
 #[derive(Debug, serde::Serialize)]
 pub struct AnalysisResult {
+    /// Identifier of the analyzed message (its `message_hash`), referenced by feedback.
+    pub id: String,
+
     /// The final classification of the email.
     pub verdict: Verdict,
 
+    /// Overall risk from 0 (benign) to 100, combining the verdict with heuristic evidence.
+    pub risk_score: u32,
+
+    /// Human-readable explanations of the evidence behind the verdict and score.
+    pub reasons: Vec<Reason>,
+
+    /// Reasons left out, along with their score, because their rule is disabled or
+    /// suppressed for the sender domain.
+    pub suppressed: Vec<Reason>,
+
+    /// The highest severity among `reasons`.
+    pub severity: Severity,
+
+    /// How thoroughly the message was analyzed.
+    pub depth: AnalysisDepth,
+
+    /// Whether the analysis stopped at its deadline before all evidence was gathered.
+    pub partial: bool,
+
+    /// Why the enrichments of a deep analysis, or the rest of them, were skipped when an
+    /// `EarlyExitPolicy` found the message already decided.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub early_exit: Option<EarlyExit>,
+
     /// Detailed evidence supporting the verdict.
     pub evidence: Evidence,
+
+    /// Results for the messages attached to this one, such as a phish a user reported by
+    /// forwarding it as an attachment. The other fields describe this (carrier) message
+    /// only; each attached message has its own verdict here.
+    pub embedded: Vec<AnalysisResult>,
+
+    /// Time spent on each check and class of DNS lookup, when `AnalysisOptions::timings`
+    /// is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+
+    /// The rule settings applied when scoring.
+    #[serde(skip)]
+    pub rules: RuleSettings,
+
+    /// The time, in Unix seconds, suppression expiry is checked against when scoring;
+    /// `None` for now.
+    #[serde(skip)]
+    pub as_of: Option<i64>,
+}
+
+/// Options controlling how an email is analyzed.
+#[derive(Debug, Default, Clone)]
+pub struct AnalysisOptions {
+    /// authserv-ids of border MTAs whose Authentication-Results are taken as authoritative.
+    ///
+    /// When the topmost Authentication-Results header comes from one of these, its
+    /// SPF/DKIM/DMARC outcomes are used as-is and the SPF/DMARC lookups are skipped.
+    pub trusted_authserv_ids: Vec<String>,
+
+    /// Domains of the organization's brands; sender domains imitating them are reported.
+    pub protected_domains: Vec<String>,
+
+    /// Phrases checked against the body text; `None` disables text heuristics.
+    pub text_phrases: Option<PhraseList>,
+
+    /// Registered sending infrastructure of owned domains; mail claiming an owned domain
+    /// from elsewhere is reported.
+    pub trust_store: Option<TrustStore>,
+
+    /// Allow-, block-, and VIP lists maintained by the operator.
+    pub sender_lists: Option<SenderLists>,
+
+    /// Rules disabled, or suppressed for specific sender domains.
+    pub rules: RuleSettings,
+
+    /// Evaluate time-dependent rules as of this time (Unix seconds) instead of now.
+    ///
+    /// Together with a `DnsSnapshot`, this makes the result a function of the message
+    /// alone: the same input always serializes to byte-identical JSON.
+    pub as_of: Option<i64>,
+
+    /// Record the time spent on each check and class of DNS lookup in `timings`.
+    pub timings: bool,
+}
+
+impl AnalysisResult {
+    /// Recomputes `risk_score` and `reasons`, e.g. after enrichments were added to the evidence
+    ///
+    /// Reasons are rendered in English; call `localize` afterwards for other languages.
+    ///
+    /// Evidence of inactive rules (see `rules`) is left out of the score, and their
+    /// reasons are moved to `suppressed`.
+    pub fn rescore(&mut self) {
+        let now = self.as_of.unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default()
+        });
+        let inactive = self
+            .rules
+            .inactive_keys(self.evidence.from_domain.as_deref(), now);
+        let mut scored = self.evidence.clone();
+        for key in &inactive {
+            clear_evidence(&mut scored, key);
+        }
+        self.risk_score = risk_score(&self.verdict, &scored);
+
+        let mut reasons = explain(&self.verdict, &self.evidence);
+        if self.partial {
+            reasons.push(Reason::deadline_exceeded());
+        }
+        (self.suppressed, self.reasons) = reasons
+            .into_iter()
+            .partition(|reason| inactive.contains(&reason.key.as_str()));
+        self.severity = max_severity(&self.reasons);
+    }
+
+    /// Renders the reason messages in `lang`
+    pub fn localize(&mut self, lang: Lang) {
+        for reason in &mut self.reasons {
+            reason.localize(lang);
+        }
+        for embedded in &mut self.embedded {
+            embedded.localize(lang);
+        }
+    }
+}
+
+/// Combines the verdict with heuristic evidence into a 0–100 risk score
+///
+/// Text heuristics contribute at most `MAX_TEXT_SCORE` points.
+/// Blocklisted senders always score 100; authenticated allowlisted senders score 0.
+pub fn risk_score(verdict: &Verdict, evidence: &Evidence) -> u32 {
+    if let Some(lists) = &evidence.lists {
+        if lists.blocklisted.is_some() {
+            return 100;
+        }
+        if lists.allowlisted.is_some() && *verdict == Verdict::Authenticated {
+            return 0;
+        }
+    }
+
+    let mut score = match verdict {
+        Verdict::Authenticated => 0,
+        Verdict::Automated => 20,
+        Verdict::Unauthenticated => 30,
+        Verdict::Indeterminate => 40,
+        Verdict::Suspicious => 50,
+        Verdict::PolicyViolation => 80,
+    };
+
+    if evidence
+        .infrastructure
+        .as_ref()
+        .is_some_and(|i| !i.verified)
+    {
+        score += 40;
+    }
+    if let Some(lookalike) = &evidence.lookalike {
+        score += 30;
+        if lookalike
+            .certificates
+            .as_ref()
+            .is_some_and(|c| c.recently_issued)
+        {
+            score += 10;
+        }
+    }
+    if evidence.body.urls.iter().any(|u| u.lookalike.is_some()) {
+        score += 20;
+    }
+    if evidence
+        .lists
+        .as_ref()
+        .is_some_and(|l| l.vip_impersonation.is_some())
+    {
+        score += 30;
+    }
+    if let Some(history) = &evidence.passive_dns
+        && (history.recently_created || history.changed_before_message)
+    {
+        score += 15;
+    }
+    if let Some(registration) = &evidence.registration
+        && (registration.abused_registrar.is_some() || !registration.abused_nameservers.is_empty())
+    {
+        score += 15;
+    }
+    if evidence
+        .received
+        .as_ref()
+        .is_some_and(|r| !r.anomalies.is_empty())
+    {
+        score += 15;
+    }
+    if evidence
+        .session
+        .as_ref()
+        .is_some_and(|s| !s.helo_problems.is_empty())
+    {
+        score += 10;
+    }
+    if evidence.envelope.as_ref().is_some_and(|e| e.misaligned()) {
+        score += 10;
+    }
+    if evidence.fingerprint_anomaly.is_some() {
+        score += 15;
+    }
+    if !evidence.dkim_replay.is_empty() {
+        score += 20;
+    }
+    if !evidence.parse_anomalies.is_empty() {
+        score += 15;
+    }
+    if evidence
+        .attachments
+        .iter()
+        .any(|a| !a.violations.is_empty())
+    {
+        score += 15;
+    }
+    let archives = || {
+        evidence
+            .attachments
+            .iter()
+            .filter_map(|a| a.archive.as_ref())
+    };
+    if archives().any(|a| !a.executables.is_empty()) {
+        score += 30;
+    }
+    if archives().any(|a| a.encrypted) {
+        score += 15;
+    }
+    if archives().any(|a| !a.nested_archives.is_empty()) {
+        score += 5;
+    }
+    let pages = || evidence.attachments.iter().filter_map(|a| a.html.as_ref());
+    if pages().next().is_some() {
+        score += 15;
+    }
+    if pages().any(|h| h.smuggling) {
+        score += 30;
+    }
+    let pdfs = || evidence.attachments.iter().filter_map(|a| a.pdf.as_ref());
+    if pdfs().any(|p| p.javascript) {
+        score += 30;
+    }
+    if pdfs().any(|p| p.open_action) {
+        score += 5;
+    }
+    if pdfs().any(|p| p.embedded_files > 0) {
+        score += 15;
+    }
+    let office = || {
+        evidence
+            .attachments
+            .iter()
+            .filter_map(|a| a.office.as_ref())
+    };
+    if office().any(|o| o.macros) {
+        score += 30;
+    }
+    if office().any(|o| !o.external_references.is_empty()) {
+        score += 30;
+    }
+    if office().any(|o| !o.dde.is_empty()) {
+        score += 30;
+    }
+    if !evidence.dns_disagreements.is_empty() {
+        score += 20;
+    }
+    if let Some(text) = &evidence.body.text {
+        score += text.score;
+    }
+
+    score.min(100)
 }
 
 /// Core function: Analyze parsed email + DNS
@@ -65,26 +480,216 @@ pub async fn analyze_email<R: ResolverTrait + Sync + Send>(
     parsed: &EmailParsed,
     dns: &R,
 ) -> anyhow::Result<AnalysisResult> {
-    let from_domain = crate::parse::extract_domain(parsed.from.as_deref());
+    analyze_email_with_options(parsed, dns, &AnalysisOptions::default()).await
+}
 
-    let spf_policy = from_domain
-        .as_deref()
-        .and_then(|d| futures::executor::block_on(dns.resolve_spf(d)));
+/// Analyze parsed email + DNS using the given options
+pub async fn analyze_email_with_options<R: ResolverTrait + Sync + Send>(
+    parsed: &EmailParsed,
+    dns: &R,
+    options: &AnalysisOptions,
+) -> anyhow::Result<AnalysisResult> {
+    analyze_email_at_depth(parsed, dns, options, AnalysisDepth::Standard).await
+}
+
+/// Analyze parsed email + DNS using the given options, as thoroughly as `depth` asks
+///
+/// `Deep` analyzes like `Standard` here; the caller adds the enrichments. Attached
+/// messages are analyzed the same way, into `embedded`.
+pub async fn analyze_email_at_depth<R: ResolverTrait + Sync + Send>(
+    parsed: &EmailParsed,
+    dns: &R,
+    options: &AnalysisOptions,
+    depth: AnalysisDepth,
+) -> anyhow::Result<AnalysisResult> {
+    if !options.timings {
+        return analyze_with_embedded(parsed, dns, options, depth).await;
+    }
+    let timed = TimingResolver::new(dns);
+    let mut result = analyze_with_embedded(parsed, &timed, options, depth).await?;
+    if let Some(timings) = &mut result.timings {
+        timings.dns = timed.timings();
+    }
+    Ok(result)
+}
 
-    let dmarc_policy = from_domain
+/// Boxed, as attached messages are analyzed recursively
+fn analyze_with_embedded<'a, R: ResolverTrait + Sync + Send>(
+    parsed: &'a EmailParsed,
+    dns: &'a R,
+    options: &'a AnalysisOptions,
+    depth: AnalysisDepth,
+) -> Pin<Box<dyn Future<Output = anyhow::Result<AnalysisResult>> + Send + 'a>> {
+    Box::pin(async move {
+        let mut result = analyze_message(parsed, dns, options, depth).await?;
+        for attached in &parsed.embedded {
+            result
+                .embedded
+                .push(analyze_with_embedded(attached, dns, options, depth).await?);
+        }
+        Ok(result)
+    })
+}
+
+/// Analyzes `parsed` alone, leaving attached messages out
+async fn analyze_message<R: ResolverTrait + Sync + Send>(
+    parsed: &EmailParsed,
+    dns: &R,
+    options: &AnalysisOptions,
+    depth: AnalysisDepth,
+) -> anyhow::Result<AnalysisResult> {
+    let mut stopwatch = Stopwatch::new(options.timings);
+    let from_domain = crate::parse::extract_domain(parsed.from.as_deref());
+    let lookalike = from_domain
         .as_deref()
-        .and_then(|d| futures::executor::block_on(dns.resolve_dmarc(d)));
+        .and_then(|d| find_lookalike(d, &options.protected_domains));
+    stopwatch.lap("lookalike");
+    let body = match depth {
+        AnalysisDepth::HeadersOnly => BodyEvidence::default(),
+        AnalysisDepth::Standard | AnalysisDepth::Deep => {
+            let mut body = analyze_body(
+                &parsed.body,
+                &options.protected_domains,
+                options.text_phrases.as_ref(),
+            );
+            add_attachment_urls(&mut body, &parsed.attachments, &options.protected_domains);
+            body
+        }
+    };
+    stopwatch.lap("body");
+
+    let infrastructure = match (&options.trust_store, from_domain.as_deref()) {
+        (Some(store), Some(domain)) => match store.get(domain) {
+            Some(infra) => Some(check_infrastructure(parsed, domain, infra, dns).await),
+            None => None,
+        },
+        _ => None,
+    };
+    stopwatch.lap("infrastructure");
+
+    let received = (!parsed.received.is_empty()).then(|| received_timeline(&parsed.received));
+    let delivered_at = received
+        .as_ref()
+        .and_then(|timeline| timeline.hops.last()?.timestamp);
+    stopwatch.lap("received");
+    let dkim_replay = check_replay(parsed, delivered_at);
+    stopwatch.lap("dkim_replay");
+    let dkim_coverage = dkim_coverage(parsed);
+    stopwatch.lap("dkim_coverage");
+    let session = match &parsed.session {
+        Some(session) => Some(check_session(session, dns).await),
+        None => None,
+    };
+    stopwatch.lap("session");
+
+    let envelope = envelope_sender(parsed, from_domain.as_deref());
+    stopwatch.lap("envelope");
+    let automated = detect_automated(parsed);
+    stopwatch.lap("automated");
+
+    let lists = match (&options.sender_lists, parsed.from.as_deref()) {
+        (Some(sender_lists), Some(from)) => {
+            check_lists(from, sender_lists, &options.protected_domains)
+        }
+        _ => None,
+    };
+    stopwatch.lap("lists");
+
+    if let Some(upstream) = trusted_auth_results(parsed, options) {
+        let mut result = analyze_with_upstream(parsed, dns, from_domain, upstream).await;
+        stopwatch.lap("upstream");
+        result.depth = depth;
+        result.evidence.infrastructure = infrastructure;
+        result.evidence.lookalike = lookalike;
+        result.evidence.lists = lists;
+        result.evidence.body = body;
+        result.evidence.received = received;
+        result.evidence.session = session;
+        result.evidence.envelope = envelope;
+        result.verdict = classify_automated(result.verdict, automated.as_ref());
+        result.evidence.automated = automated;
+        result.evidence.dkim_replay = dkim_replay;
+        result.evidence.dkim_coverage = dkim_coverage;
+        result.evidence.parse_anomalies = parsed.anomalies.clone();
+        result.evidence.attachments = parsed.attachments.clone();
+        result.rules = options.rules.clone();
+        result.as_of = options.as_of;
+        result.rescore();
+        stopwatch.lap("scoring");
+        result.timings = stopwatch.finish();
+        return Ok(result);
+    }
+
+    let spf_policy = match from_domain.as_deref() {
+        Some(domain) => dns.resolve_spf(domain).await,
+        None => None,
+    };
+    stopwatch.lap("spf_policy");
+
+    let dmarc_policy = match from_domain.as_deref() {
+        Some(domain) => dns.resolve_dmarc(domain).await,
+        None => None,
+    };
+    stopwatch.lap("dmarc_policy");
 
     // Check domain existence (A/AAAA or MX)
     let domain_valid = if let Some(ref domain) = from_domain {
-        futures::executor::block_on(dns.domain_exists(domain))
+        dns.domain_exists(domain).await
     } else {
         false
     };
+    stopwatch.lap("domain_exists");
 
-    let alignment_ok = match (&from_domain, &spf_policy) {
-        (Some(_), Some(p)) => p.contains("-all"),
-        _ => false,
+    let dns_disagreements = from_domain
+        .as_deref()
+        .map(|domain| dns.disagreements(domain))
+        .unwrap_or_default();
+    stopwatch.lap("dns_disagreements");
+
+    let client_ip = parsed.client_ip.as_deref().and_then(|ip| ip.parse().ok());
+    let spf_result = match (&from_domain, client_ip) {
+        (Some(domain), Some(ip)) => Some(explain_ip(dns, domain, ip).await),
+        _ => None,
+    };
+    let mail_from_domain = envelope
+        .as_ref()
+        .and_then(|envelope| envelope.return_path.rsplit_once('@'))
+        .map(|(_, domain)| domain.to_ascii_lowercase());
+    let envelope_spf_result = match (mail_from_domain, client_ip) {
+        // The same domain needs no second evaluation
+        (Some(domain), Some(_)) if Some(&domain) == from_domain.as_ref() => {
+            spf_result.clone().map(|explanation| EnvelopeSpf {
+                domain,
+                explanation,
+            })
+        }
+        (Some(domain), Some(ip)) => Some(EnvelopeSpf {
+            explanation: explain_ip(dns, &domain, ip).await,
+            domain,
+        }),
+        _ => None,
+    };
+    stopwatch.lap("spf_evaluation");
+    // A pass for the From domain itself is aligned, as is one for an envelope domain of
+    // the same organization, or only the same domain under `aspf=s`; without a sending
+    // IP only a strict policy can be credited
+    let strict_spf = dmarc_policy.as_deref().is_some_and(|record| {
+        parse_tags(record)
+            .get("aspf")
+            .is_some_and(|mode| mode.eq_ignore_ascii_case("s"))
+    });
+    let envelope_aligned = envelope_spf_result.as_ref().is_some_and(|spf| {
+        let same = match from_domain.as_deref() {
+            Some(domain) if strict_spf => domain.eq_ignore_ascii_case(&spf.domain),
+            Some(domain) => organizational_domain(domain) == organizational_domain(&spf.domain),
+            None => false,
+        };
+        spf.explanation.result == SpfResult::Pass && same
+    });
+    let alignment_ok = match (&spf_result, &spf_policy) {
+        (Some(spf), _) => spf.result == SpfResult::Pass || envelope_aligned,
+        (None, Some(p)) => from_domain.is_some() && p.contains("-all"),
+        (None, None) => false,
     };
 
     let spf_authorized = alignment_ok;
@@ -99,18 +704,156 @@ pub async fn analyze_email<R: ResolverTrait + Sync + Send>(
         domain_valid,
     );
 
-    Ok(AnalysisResult {
+    let verdict = classify_automated(verdict, automated.as_ref());
+
+    let mut result = AnalysisResult {
+        id: message_hash(parsed),
         verdict,
+        risk_score: 0,
+        reasons: Vec::new(),
+        suppressed: Vec::new(),
+        severity: Severity::Info,
+        depth,
+        partial: false,
+        early_exit: None,
         evidence: Evidence {
+            from_domain_labels: from_domain.as_deref().map(domain_labels),
             from_domain,
             spf_policy,
             dmarc_policy,
             spf_authorized,
+            spf_result,
+            envelope_spf_result,
             dkim_present,
             alignment_ok,
             domain_valid,
+            upstream_auth: None,
+            passive_dns: None,
+            registration: None,
+            infrastructure,
+            lookalike,
+            lists,
+            body,
+            received,
+            session,
+            envelope,
+            automated,
+            fingerprint: fingerprint(parsed),
+            fingerprint_anomaly: None,
+            dkim_replay,
+            dkim_coverage,
+            parse_anomalies: parsed.anomalies.clone(),
+            attachments: parsed.attachments.clone(),
+            dns_disagreements,
         },
-    })
+        embedded: Vec::new(),
+        timings: None,
+        rules: options.rules.clone(),
+        as_of: options.as_of,
+    };
+    result.rescore();
+    stopwatch.lap("scoring");
+    result.timings = stopwatch.finish();
+    Ok(result)
+}
+
+/// Bounces and automatic replies get their own verdict instead of `Suspicious` or
+/// `Unauthenticated`; policy violations and authenticated mail keep theirs
+fn classify_automated(verdict: Verdict, automated: Option<&AutomatedMessage>) -> Verdict {
+    match (verdict, automated) {
+        (Verdict::Suspicious | Verdict::Unauthenticated, Some(_)) => Verdict::Automated,
+        (verdict, _) => verdict,
+    }
+}
+
+/// Returns the topmost Authentication-Results if it was added by a trusted authserv-id
+fn trusted_auth_results(parsed: &EmailParsed, options: &AnalysisOptions) -> Option<AuthResults> {
+    let header = parsed.auth_results.as_deref()?;
+    let results = parse_auth_results(header)?;
+    options
+        .trusted_authserv_ids
+        .iter()
+        .any(|id| id.eq_ignore_ascii_case(&results.authserv_id))
+        .then_some(results)
+}
+
+/// Builds the result from upstream SPF/DKIM/DMARC outcomes, skipping our own SPF/DMARC lookups
+async fn analyze_with_upstream<R: ResolverTrait + Sync + Send>(
+    parsed: &EmailParsed,
+    dns: &R,
+    from_domain: Option<String>,
+    upstream: AuthResults,
+) -> AnalysisResult {
+    let domain_valid = match from_domain.as_deref() {
+        Some(domain) => dns.domain_exists(domain).await,
+        None => false,
+    };
+
+    let spf_authorized = upstream.spf.as_deref() == Some("pass");
+    let alignment_ok = upstream.dmarc.as_deref() == Some("pass");
+    let verdict = decide_upstream_verdict(&upstream, domain_valid);
+
+    AnalysisResult {
+        id: message_hash(parsed),
+        verdict,
+        risk_score: 0,
+        reasons: Vec::new(),
+        suppressed: Vec::new(),
+        severity: Severity::Info,
+        depth: AnalysisDepth::default(),
+        partial: false,
+        early_exit: None,
+        evidence: Evidence {
+            from_domain_labels: from_domain.as_deref().map(domain_labels),
+            from_domain,
+            spf_policy: None,
+            dmarc_policy: None,
+            spf_authorized,
+            spf_result: None,
+            envelope_spf_result: None,
+            dkim_present: parsed.dkim_present,
+            alignment_ok,
+            domain_valid,
+            upstream_auth: Some(upstream),
+            passive_dns: None,
+            registration: None,
+            infrastructure: None,
+            lookalike: None,
+            lists: None,
+            body: BodyEvidence::default(),
+            received: None,
+            session: None,
+            envelope: None,
+            automated: None,
+            fingerprint: fingerprint(parsed),
+            fingerprint_anomaly: None,
+            dkim_replay: Vec::new(),
+            dkim_coverage: Vec::new(),
+            parse_anomalies: Vec::new(),
+            attachments: Vec::new(),
+            dns_disagreements: Vec::new(),
+        },
+        embedded: Vec::new(),
+        timings: None,
+        rules: RuleSettings::default(),
+        as_of: None,
+    }
+}
+
+/// Verdict from upstream Authentication-Results
+pub fn decide_upstream_verdict(upstream: &AuthResults, domain_valid: bool) -> Verdict {
+    if !domain_valid {
+        return Verdict::Suspicious;
+    }
+
+    let missing = |r: &Option<String>| matches!(r.as_deref(), None | Some("none"));
+
+    match upstream.dmarc.as_deref() {
+        Some("pass") => Verdict::Authenticated,
+        Some("fail") => Verdict::PolicyViolation,
+        _ if missing(&upstream.spf) && missing(&upstream.dkim) => Verdict::Unauthenticated,
+        _ => Verdict::Suspicious,
+    }
 }
 
 pub fn decide_verdict(
@@ -126,10 +869,11 @@ pub fn decide_verdict(
     }
 
     // Policy violation: DMARC is p=reject but alignment fails
-    if let Some(dmarc_policy) = dmarc {
-        if dmarc_policy.contains("p=reject") && !alignment_ok {
-            return Verdict::PolicyViolation;
-        }
+    if let Some(dmarc_policy) = dmarc
+        && dmarc_policy.contains("p=reject")
+        && !alignment_ok
+    {
+        return Verdict::PolicyViolation;
     }
 
     match (from_domain, spf, dmarc, dkim_present, alignment_ok) {
@@ -147,9 +891,24 @@ mod verdict_tests {
     async fn test_unauthenticated_email() {
         let email = EmailParsed {
             from: Some("user@evil.com".to_string()),
+            subject: None,
+            reply_to: None,
             return_path: Some("bounce@evil.com".to_string()),
             auth_results: None,
             dkim_present: false,
+            dkim_signatures: Vec::new(),
+            client_ip: None,
+            date: None,
+            received: Vec::new(),
+            header_names: Vec::new(),
+            mailer: None,
+            auto_submitted: None,
+            content_type: None,
+            session: None,
+            body: String::new(),
+            anomalies: Vec::new(),
+            embedded: Vec::new(),
+            attachments: Vec::new(),
         };
 
         let alignment_ok = false;
@@ -164,10 +923,18 @@ mod verdict_tests {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod integration_tests {
-    use super::super::dns::ResolverTrait;
-    use crate::email_verdict::{Verdict, analyze_email};
+    use super::super::dns::{DnsDisagreement, ResolverTrait};
+    use crate::checks::spf::SpfResult;
+    use crate::email_verdict::{
+        AnalysisDepth, AnalysisOptions, Verdict, analyze_email, analyze_email_at_depth,
+        analyze_email_with_options,
+    };
+    use crate::messages::Lang;
     use crate::parse::{EmailParsed, parse_email};
+    use crate::reasons::Severity;
+    use crate::text_heuristics::{MAX_TEXT_SCORE, PhraseList};
     use async_trait::async_trait;
 
     struct MockResolver;
@@ -199,6 +966,59 @@ mod integration_tests {
         }
     }
 
+    /// Answers like `MockResolver`, but a second backend disagrees on the SPF record
+    struct PoisonedResolver;
+
+    #[async_trait]
+    impl ResolverTrait for PoisonedResolver {
+        async fn resolve_spf(&self, domain: &str) -> Option<String> {
+            MockResolver.resolve_spf(domain).await
+        }
+
+        async fn resolve_dmarc(&self, domain: &str) -> Option<String> {
+            MockResolver.resolve_dmarc(domain).await
+        }
+
+        async fn domain_exists(&self, domain: &str) -> bool {
+            MockResolver.domain_exists(domain).await
+        }
+
+        async fn resolve_mx(&self, domain: &str) -> bool {
+            MockResolver.resolve_mx(domain).await
+        }
+
+        fn disagreements(&self, domain: &str) -> Vec<DnsDisagreement> {
+            vec![DnsDisagreement {
+                name: domain.to_string(),
+                answers: [
+                    ("default".to_string(), vec!["v=spf1 -all".to_string()]),
+                    ("system".to_string(), Vec::new()),
+                ]
+                .into(),
+            }]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dns_disagreement_is_reported() {
+        let raw = b"From: user@example.com\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n";
+        let parsed = parse_email(raw).unwrap();
+        let result = analyze_email(&parsed, &PoisonedResolver).await.unwrap();
+
+        assert_eq!(result.evidence.dns_disagreements.len(), 1);
+        let reason = &result.reasons[0];
+        assert_eq!(
+            (reason.key.as_str(), reason.rule_id.as_str()),
+            ("dns_disagreement", "ESD-0032")
+        );
+        assert!(
+            reason
+                .message
+                .contains("example.com (default: v=spf1 -all; system: none)")
+        );
+        assert_eq!(result.severity, Severity::High);
+    }
+
     #[tokio::test]
     async fn test_authenticated_email() {
         let raw = b"From: user@example.com\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n";
@@ -218,6 +1038,166 @@ mod integration_tests {
         assert_eq!(result.evidence.domain_valid, true);
     }
 
+    #[tokio::test]
+    async fn test_authenticated_email_has_no_reasons() {
+        let raw = b"From: user@example.com\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+
+        let result = analyze_email(&parsed, &MockResolver).await.unwrap();
+        assert!(result.reasons.is_empty());
+        assert_eq!(result.severity, Severity::Info);
+    }
+
+    #[tokio::test]
+    async fn test_timings_are_opt_in() {
+        let raw = b"From: user@example.com\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+
+        let result = analyze_email(&parsed, &MockResolver).await.unwrap();
+        assert!(result.timings.is_none());
+        assert!(
+            serde_json::to_value(&result)
+                .unwrap()
+                .get("timings")
+                .is_none()
+        );
+
+        let options = AnalysisOptions {
+            timings: true,
+            ..AnalysisOptions::default()
+        };
+        let result = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+        let timings = result.timings.unwrap();
+        assert!(timings.checks.contains_key("body"));
+        assert_eq!(timings.checks["spf_policy"].count, 1);
+        assert_eq!(timings.dns["spf"].count, 1);
+        assert_eq!(timings.dns["dmarc"].count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_sender_spf() {
+        let snapshot: crate::dns::DnsSnapshot = serde_json::from_str(
+            r#"{"domains": {
+                "example.com": {"spf": "v=spf1 -all", "exists": true},
+                "bounce.example.com": {"spf": "v=spf1 ip4:203.0.113.0/24 -all"},
+                "esp.test": {"spf": "v=spf1 ip4:203.0.113.9 -all"}
+            }}"#,
+        )
+        .unwrap();
+        let received = "Received: from relay.example.net (relay.example.net [203.0.113.9]) by mx.example.com\r\n";
+
+        let raw = format!(
+            "{}Return-Path: <b@bounce.example.com>\r\nFrom: a@example.com\r\n\r\n",
+            received
+        );
+        let result = analyze_email(&parse_email(raw.as_bytes()).unwrap(), &snapshot)
+            .await
+            .unwrap();
+        assert_eq!(result.evidence.spf_result.unwrap().result, SpfResult::Fail);
+        let envelope = result.evidence.envelope_spf_result.unwrap();
+        assert_eq!(envelope.domain, "bounce.example.com");
+        assert_eq!(envelope.explanation.result, SpfResult::Pass);
+        assert!(result.evidence.alignment_ok);
+
+        // A pass for an unrelated envelope domain is not aligned
+        let raw = format!(
+            "{}Return-Path: <b@esp.test>\r\nFrom: a@example.com\r\n\r\n",
+            received
+        );
+        let result = analyze_email(&parse_email(raw.as_bytes()).unwrap(), &snapshot)
+            .await
+            .unwrap();
+        let envelope = result.evidence.envelope_spf_result.unwrap();
+        assert_eq!(envelope.explanation.result, SpfResult::Pass);
+        assert!(!result.evidence.alignment_ok);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_sender_spf_strict_alignment() {
+        let snapshot: crate::dns::DnsSnapshot = serde_json::from_str(
+            r#"{"domains": {
+                "strict.example": {"spf": "v=spf1 -all",
+                    "dmarc": "v=DMARC1; p=reject; aspf=s", "exists": true},
+                "bounce.strict.example": {"spf": "v=spf1 ip4:203.0.113.0/24 -all"},
+                "relaxed.example": {"spf": "v=spf1 -all",
+                    "dmarc": "v=DMARC1; p=reject; aspf=r", "exists": true},
+                "bounce.relaxed.example": {"spf": "v=spf1 ip4:203.0.113.0/24 -all"}
+            }}"#,
+        )
+        .unwrap();
+        let received = "Received: from relay.example.net (relay.example.net [203.0.113.9]) by mx.example.com\r\n";
+        let analyze = |domain: &str| {
+            let raw = format!(
+                "{}Return-Path: <b@bounce.{}>\r\nFrom: a@{}\r\n\r\n",
+                received, domain, domain
+            );
+            let snapshot = &snapshot;
+            async move {
+                analyze_email(&parse_email(raw.as_bytes()).unwrap(), snapshot)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let relaxed = analyze("relaxed.example").await;
+        assert_eq!(
+            relaxed
+                .evidence
+                .envelope_spf_result
+                .unwrap()
+                .explanation
+                .result,
+            SpfResult::Pass
+        );
+        assert!(relaxed.evidence.alignment_ok);
+
+        // Under aspf=s only a pass for the From domain itself is aligned
+        let strict = analyze("strict.example").await;
+        assert_eq!(
+            strict
+                .evidence
+                .envelope_spf_result
+                .unwrap()
+                .explanation
+                .result,
+            SpfResult::Pass
+        );
+        assert!(!strict.evidence.alignment_ok);
+        assert_eq!(strict.verdict, Verdict::PolicyViolation);
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_spf_fail_with_dkim() {
+        let snapshot: crate::dns::DnsSnapshot = serde_json::from_str(
+            r#"{"domains": {"example.com": {"spf": "v=spf1 ip4:192.0.2.0/24 -all",
+                "dmarc": "v=DMARC1; p=reject", "exists": true}}}"#,
+        )
+        .unwrap();
+        let received = "Received: from relay.example.net (relay.example.net [198.51.100.5]) by mx.example.org\r\n";
+        let analyze = |signature: &str| {
+            let raw = format!("{}{}From: a@example.com\r\n\r\nHi", received, signature);
+            let snapshot = &snapshot;
+            async move {
+                analyze_email(&parse_email(raw.as_bytes()).unwrap(), snapshot)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        // The signature is not verified, so anyone could have added it
+        let signed = analyze("DKIM-Signature: v=1; d=mail.example.com; s=s1; b=x\r\n").await;
+        assert_eq!(signed.evidence.spf_result.unwrap().result, SpfResult::Fail);
+        assert!(!signed.evidence.spf_authorized);
+        assert!(!signed.evidence.alignment_ok);
+        assert_eq!(signed.verdict, Verdict::PolicyViolation);
+
+        let foreign = analyze("DKIM-Signature: v=1; d=relay.example.net; s=s1; b=x\r\n").await;
+        assert!(!foreign.evidence.alignment_ok);
+        assert_eq!(foreign.verdict, Verdict::PolicyViolation);
+    }
+
     #[tokio::test]
     async fn test_nonexistent_domain() {
         let raw = b"From: user@fake-domain.com\r\n";
@@ -230,6 +1210,182 @@ mod integration_tests {
         assert_eq!(result.evidence.domain_valid, false);
     }
 
+    #[tokio::test]
+    async fn test_localized_reasons() {
+        let raw = b"From: user@fake-domain.com\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+
+        let mut result = analyze_email(&parsed, &MockResolver).await.unwrap();
+        assert_eq!(result.reasons[0].key, "domain_invalid");
+        assert_eq!(result.severity, Severity::High);
+        assert_eq!(
+            result.reasons[0].message,
+            "The sender domain fake-domain.com does not exist."
+        );
+
+        result.localize(Lang::Fr);
+        assert_eq!(
+            result.reasons[0].message,
+            "Le domaine expéditeur fake-domain.com n'existe pas."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trusted_upstream_auth_results() {
+        // DMARC failed at the border MTA even though our own DNS view would authenticate it
+        let raw = b"Authentication-Results: mx.corp.test; spf=fail smtp.mailfrom=example.com; dkim=none; dmarc=fail header.from=example.com\r\nFrom: user@example.com\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let options = AnalysisOptions {
+            trusted_authserv_ids: vec!["MX.corp.test".to_string()],
+            ..AnalysisOptions::default()
+        };
+
+        let result = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(result.verdict, Verdict::PolicyViolation);
+        assert_eq!(result.evidence.spf_policy, None);
+        assert_eq!(
+            result.evidence.upstream_auth.unwrap().authserv_id,
+            "mx.corp.test"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_auth_results_ignored() {
+        let raw =
+            b"Authentication-Results: attacker.test; dmarc=pass\r\nFrom: user@misaligned.com\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let options = AnalysisOptions {
+            trusted_authserv_ids: vec!["mx.corp.test".to_string()],
+            ..AnalysisOptions::default()
+        };
+
+        let result = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(result.verdict, Verdict::Suspicious);
+        assert!(result.evidence.upstream_auth.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lookalike_sender_domain() {
+        let raw = b"From: ceo@examp1e.com\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let options = AnalysisOptions {
+            protected_domains: vec!["example.com".to_string()],
+            ..AnalysisOptions::default()
+        };
+
+        let result = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+
+        let lookalike = result.evidence.lookalike.as_ref().unwrap();
+        assert_eq!(lookalike.protected_domain, "example.com");
+        assert!(lookalike.certificates.is_none());
+        assert!(result.risk_score >= 30);
+
+        // Suppressed for the sender: still in the evidence, but neither explained nor scored
+        let options = AnalysisOptions {
+            rules: serde_json::from_value(serde_json::json!({"suppressions": [{
+                "rule_id": "ESD-0007", "domain": "examp1e.com",
+                "expires": "2099-01-01T00:00:00", "justification": "Our own typo domain"
+            }]}))
+            .unwrap(),
+            ..options
+        };
+        let suppressed = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+        assert!(suppressed.evidence.lookalike.is_some());
+        assert_eq!(suppressed.risk_score + 30, result.risk_score);
+        assert_eq!(suppressed.suppressed[0].rule_id, "ESD-0007");
+        assert!(suppressed.reasons.iter().all(|r| r.key != "lookalike"));
+
+        // Scored as of a time after the suppression expired
+        let options = AnalysisOptions {
+            as_of: Some(4_102_444_800 + 1),
+            ..options
+        };
+        let expired = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+        assert_eq!(expired.risk_score, result.risk_score);
+        assert!(expired.suppressed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_exact_domain_spoof_outside_registered_infrastructure() {
+        let raw = b"Received: from relay.example.net (relay.example.net [203.0.113.9]) by mx.example.com\r\n\
+                    From: ceo@example.com\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let store = serde_json::from_value(serde_json::json!({
+            "domains": {"example.com": {"ip_ranges": ["192.0.2.0/24"]}}
+        }))
+        .unwrap();
+        let options = AnalysisOptions {
+            trust_store: Some(store),
+            ..AnalysisOptions::default()
+        };
+
+        let result = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+
+        assert!(!result.evidence.infrastructure.unwrap().verified);
+        assert!(
+            result
+                .reasons
+                .iter()
+                .any(|r| r.key == "unregistered_infrastructure")
+        );
+        assert!(result.risk_score >= 40);
+    }
+
+    #[tokio::test]
+    async fn test_text_heuristics_bounded_score() {
+        let raw = b"From: user@example.com\r\nDKIM-Signature: v=1;\r\n\r\n\
+URGENT: buy a gift card and verify your account now.\r\nSent from my iPhone\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let options = AnalysisOptions {
+            text_phrases: Some(PhraseList::default()),
+            ..AnalysisOptions::default()
+        };
+
+        let result = analyze_email_with_options(&parsed, &MockResolver, &options)
+            .await
+            .unwrap();
+
+        assert_eq!(result.verdict, Verdict::Authenticated);
+        assert_eq!(result.evidence.body.text.unwrap().score, MAX_TEXT_SCORE);
+        assert_eq!(result.risk_score, MAX_TEXT_SCORE);
+    }
+
+    #[tokio::test]
+    async fn test_headers_only_skips_body() {
+        let raw = b"From: user@example.com\r\nDKIM-Signature: v=1;\r\n\r\n\
+URGENT: buy a gift card at https://example.net/claim now.\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let options = AnalysisOptions {
+            text_phrases: Some(PhraseList::default()),
+            ..AnalysisOptions::default()
+        };
+
+        let result =
+            analyze_email_at_depth(&parsed, &MockResolver, &options, AnalysisDepth::HeadersOnly)
+                .await
+                .unwrap();
+
+        assert_eq!(result.verdict, Verdict::Authenticated);
+        assert_eq!(result.depth, AnalysisDepth::HeadersOnly);
+        assert!(result.evidence.body.urls.is_empty());
+        assert!(result.evidence.body.text.is_none());
+        assert_eq!(result.risk_score, 0);
+    }
+
     // #[tokio::test]
     // async fn test_policy_violation_due_to_dmarc() {
     //     // DKIM present but alignment fails
@@ -259,4 +1415,37 @@ mod integration_tests {
         assert_eq!(result.evidence.dkim_present, false);
         assert_eq!(result.evidence.domain_valid, true);
     }
+
+    #[tokio::test]
+    async fn test_bounce_is_automated() {
+        // Same as above, but a bounce → Automated instead of Suspicious
+        let raw =
+            b"From: user@misaligned.com\r\nReturn-Path: <>\r\nAuto-Submitted: auto-replied\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        let resolver = MockResolver;
+
+        let result = analyze_email(&parsed, &resolver).await.unwrap();
+
+        assert_eq!(result.verdict, Verdict::Automated);
+        assert!(result.reasons.iter().any(|r| r.key == "automated_message"));
+    }
+
+    #[tokio::test]
+    async fn test_attached_message_is_analyzed_separately() {
+        // A user reports a phish by forwarding it as an attachment
+        let raw = b"From: user@example.com\r\nSubject: Fwd: phish\r\nMIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\nContent-Type: text/plain\r\n\r\nLooks fishy\r\n--b\r\nContent-Type: message/rfc822\r\n\r\nFrom: ceo@misaligned.com\r\nSubject: Urgent\r\n\r\nPay now\r\n--b--\r\n";
+        let parsed: EmailParsed = parse_email(raw).unwrap();
+        assert_eq!(parsed.embedded.len(), 1);
+
+        let result = analyze_email(&parsed, &MockResolver).await.unwrap();
+
+        assert_eq!(result.evidence.from_domain.as_deref(), Some("example.com"));
+        assert_eq!(result.embedded.len(), 1);
+        let reported = &result.embedded[0];
+        assert_eq!(
+            reported.evidence.from_domain.as_deref(),
+            Some("misaligned.com")
+        );
+        assert_eq!(reported.verdict, Verdict::Suspicious);
+    }
 }
@@ -0,0 +1,210 @@
+use crate::{
+    esp::identify_esp,
+    parse::{EmailParsed, organizational_domain},
+};
+
+/// An envelope sender rewritten by the Sender Rewriting Scheme, decoded
+///
+/// Forwarders rewrite `user@origin.example` to `SRS0=HHH=TT=origin.example=user@forwarder.example`
+/// so their relay passes SPF; a second forwarder turns that into an `SRS1` address.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct SrsAddress {
+    /// The envelope sender before forwarding
+    pub original: String,
+    /// Domains of the forwarders, first hop first
+    pub forwarders: Vec<String>,
+}
+
+/// The envelope sender (`Return-Path`) and how it relates to the `From` domain
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct EnvelopeSender {
+    /// The address as received, without angle brackets
+    pub return_path: String,
+    /// SRS decoding of `return_path`, when it was rewritten by forwarders
+    pub srs: Option<SrsAddress>,
+    /// The original envelope sender with sub-address and VERP tags removed, see
+    /// [`normalize_address`]
+    pub mailbox: String,
+    /// Domain of the original envelope sender, after SRS decoding
+    pub domain: String,
+    /// Whether `domain` and the `From` domain share an organizational domain, as DMARC
+    /// relaxed alignment requires
+    pub aligned: bool,
+    /// The email service provider `domain` belongs to, e.g. `SendGrid`
+    pub esp: Option<String>,
+    /// Whether a `DKIM-Signature` claims a signing domain aligned with the `From` domain
+    pub dkim_aligned: bool,
+}
+
+impl EnvelopeSender {
+    /// Whether the envelope is unaligned for a reason other than sending through a known
+    /// ESP on the sender's behalf (ESP bounce domain, sender-aligned DKIM)
+    pub fn misaligned(&self) -> bool {
+        !(self.aligned || self.esp.is_some() && self.dkim_aligned)
+    }
+}
+
+/// Decodes an `SRS0` or `SRS1` address; `None` for any other address
+///
+/// The hash and timestamp are not verified: only the forwarder holds the key.
+pub fn decode_srs(address: &str) -> Option<SrsAddress> {
+    let (local, forwarder) = address.rsplit_once('@')?;
+    let tag = local.get(..4)?;
+    let separated = local[4..].strip_prefix(['=', '+', '-'])?;
+    let forwarder = forwarder.to_ascii_lowercase();
+
+    let (srs0, mut forwarders) = if tag.eq_ignore_ascii_case("SRS0") {
+        (separated, Vec::new())
+    } else if tag.eq_ignore_ascii_case("SRS1") {
+        // SRS1=HHH=first-forwarder==HHH=TT=origin=user: the SRS0 part keeps its separator
+        let mut parts = separated.splitn(3, '=');
+        let _hash = parts.next()?;
+        let first = parts.next().filter(|first| !first.is_empty())?;
+        let srs0 = parts.next()?.strip_prefix(['=', '+', '-'])?;
+        (srs0, vec![first.to_ascii_lowercase()])
+    } else {
+        return None;
+    };
+
+    let mut parts = srs0.splitn(4, '=');
+    let (_hash, _timestamp) = (parts.next()?, parts.next()?);
+    let domain = parts.next().filter(|domain| !domain.is_empty())?;
+    let user = parts.next().filter(|user| !user.is_empty())?;
+    forwarders.push(forwarder);
+    Some(SrsAddress {
+        original: format!("{}@{}", user, domain.to_ascii_lowercase()),
+        forwarders,
+    })
+}
+
+/// `address` without its sub-address or VERP bounce tag, domain lowercased
+///
+/// `bounces+1234@esp.example` and the VERP addresses `bounces+alice=example.org@esp.example`
+/// and `bounces-alice=example.org@esp.example` all become `bounces@esp.example`, so
+/// per-recipient envelopes compare equal.
+pub fn normalize_address(address: &str) -> String {
+    let Some((local, domain)) = address.rsplit_once('@') else {
+        return address.to_string();
+    };
+    let local = match local.split_once('+') {
+        Some((base, _)) if !base.is_empty() => base,
+        _ => match local.split_once('-') {
+            Some((base, tag)) if !base.is_empty() && tag.contains('=') => base,
+            _ => local,
+        },
+    };
+    format!("{}@{}", local, domain.to_ascii_lowercase())
+}
+
+/// The envelope sender of `parsed`, SRS-decoded and checked for alignment with
+/// `from_domain`; `None` without a `Return-Path` or for the null sender of bounces
+///
+/// Only the organizational domains are compared, as in DMARC relaxed alignment, so
+/// sub-address and VERP tags in the local part never count as misalignment.
+pub fn envelope_sender(parsed: &EmailParsed, from_domain: Option<&str>) -> Option<EnvelopeSender> {
+    let return_path = parsed
+        .return_path
+        .as_deref()?
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .trim()
+        .to_string();
+    let srs = decode_srs(&return_path);
+    let address = srs
+        .as_ref()
+        .map_or(return_path.as_str(), |srs| &srs.original);
+    let mailbox = normalize_address(address);
+    let (_, domain) = mailbox.rsplit_once('@')?;
+    let domain = domain.to_string();
+    let from_org = from_domain.map(organizational_domain);
+    let aligned = from_org.as_deref() == Some(organizational_domain(&domain).as_str());
+    let dkim_aligned = parsed.dkim_signatures.iter().any(|signature| {
+        from_org.as_deref() == Some(organizational_domain(&signature.domain).as_str())
+    });
+    Some(EnvelopeSender {
+        return_path,
+        srs,
+        mailbox,
+        esp: identify_esp(&domain).map(String::from),
+        domain,
+        aligned,
+        dkim_aligned,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_srs, envelope_sender, normalize_address};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_decode_srs() {
+        let srs0 = decode_srs("SRS0=HHH=TT=Shop.example=orders@forwarder.test").unwrap();
+        assert_eq!(srs0.original, "orders@shop.example");
+        assert_eq!(srs0.forwarders, ["forwarder.test"]);
+
+        let srs1 =
+            decode_srs("SRS1=HHH=forwarder.test==HHH=TT=shop.example=orders@relay.test").unwrap();
+        assert_eq!(srs1.original, "orders@shop.example");
+        assert_eq!(srs1.forwarders, ["forwarder.test", "relay.test"]);
+
+        assert_eq!(
+            decode_srs("srs0+HHH=TT=shop.example=a=b@forwarder.test")
+                .unwrap()
+                .original,
+            "a=b@shop.example"
+        );
+        assert!(decode_srs("orders@shop.example").is_none());
+        assert!(decode_srs("SRS0=HHH=TT@forwarder.test").is_none());
+    }
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(
+            normalize_address("bounces+1234@ESP.example"),
+            "bounces@esp.example"
+        );
+        assert_eq!(
+            normalize_address("bounces+alice=example.org@esp.example"),
+            "bounces@esp.example"
+        );
+        assert_eq!(
+            normalize_address("bounces-alice=example.org@esp.example"),
+            "bounces@esp.example"
+        );
+        assert_eq!(
+            normalize_address("no-reply@shop.example"),
+            "no-reply@shop.example"
+        );
+        assert_eq!(normalize_address("+tag@shop.example"), "+tag@shop.example");
+    }
+
+    #[test]
+    fn test_envelope_sender() {
+        let raw = b"From: orders@shop.example\r\nReturn-Path: <SRS0=HHH=TT=mail.shop.example=bounce@forwarder.test>\r\n\r\n";
+        let envelope = envelope_sender(&parse_email(raw).unwrap(), Some("shop.example")).unwrap();
+        assert_eq!(envelope.domain, "mail.shop.example");
+        assert!(envelope.aligned);
+
+        let raw = b"From: orders@shop.example\r\nReturn-Path: <bounces+alice=example.org@em.shop.example>\r\n\r\n";
+        let envelope = envelope_sender(&parse_email(raw).unwrap(), Some("shop.example")).unwrap();
+        assert_eq!(envelope.mailbox, "bounces@em.shop.example");
+        assert!(envelope.aligned);
+
+        let raw = b"From: orders@shop.example\r\nReturn-Path: <bounce@esp.test>\r\n\r\n";
+        let envelope = envelope_sender(&parse_email(raw).unwrap(), Some("shop.example")).unwrap();
+        assert!(envelope.srs.is_none());
+        assert!(!envelope.aligned);
+        assert!(envelope.misaligned());
+
+        let raw = b"From: orders@shop.example\r\nReturn-Path: <bounces+123@em1234.sendgrid.net>\r\nDKIM-Signature: v=1; a=rsa-sha256; d=shop.example; s=s1; h=from; bh=x; b=y\r\n\r\n";
+        let envelope = envelope_sender(&parse_email(raw).unwrap(), Some("shop.example")).unwrap();
+        assert_eq!(envelope.esp.as_deref(), Some("SendGrid"));
+        assert!(envelope.dkim_aligned);
+        assert!(!envelope.misaligned());
+
+        let raw = b"From: orders@shop.example\r\nReturn-Path: <>\r\n\r\n";
+        assert!(envelope_sender(&parse_email(raw).unwrap(), Some("shop.example")).is_none());
+    }
+}
@@ -0,0 +1,51 @@
+/// Bounce (envelope) domains of major email service providers
+///
+/// Mail sent through an ESP commonly keeps the ESP's domain in `Return-Path` to process
+/// bounces, while the sender's own domain signs with DKIM. Subdomains match too.
+pub const KNOWN_ESPS: &[(&str, &[&str])] = &[
+    ("Amazon SES", &["amazonses.com"]),
+    ("Brevo", &["sendinblue.com", "brevosend.com"]),
+    (
+        "Campaign Monitor",
+        &["cmail19.com", "cmail20.com", "createsend.com"],
+    ),
+    ("Constant Contact", &["constantcontact.com"]),
+    ("Customer.io", &["customeriomail.com"]),
+    ("HubSpot", &["hubspotemail.net", "hubspotstarter.net"]),
+    ("Klaviyo", &["klaviyomail.com"]),
+    ("Mailchimp", &["mcsv.net", "mcdlv.net", "rsgsv.net"]),
+    ("Mailgun", &["mailgun.org", "mailgun.net"]),
+    ("Mailjet", &["mailjet.com"]),
+    ("Mandrill", &["mandrillapp.com"]),
+    ("Marketo", &["mktomail.com"]),
+    ("Postmark", &["mtasv.net", "postmarkapp.com"]),
+    ("Salesforce Marketing Cloud", &["exacttarget.com"]),
+    ("SendGrid", &["sendgrid.net"]),
+    ("SparkPost", &["sparkpostmail.com"]),
+];
+
+/// The ESP whose bounce domain `domain` is, or lies under
+pub fn identify_esp(domain: &str) -> Option<&'static str> {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    KNOWN_ESPS
+        .iter()
+        .find(|(_, domains)| {
+            domains
+                .iter()
+                .any(|d| domain == *d || domain.ends_with(&format!(".{}", d)))
+        })
+        .map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::identify_esp;
+
+    #[test]
+    fn test_identify_esp() {
+        assert_eq!(identify_esp("sendgrid.net"), Some("SendGrid"));
+        assert_eq!(identify_esp("eu-west-1.AmazonSES.com"), Some("Amazon SES"));
+        assert_eq!(identify_esp("notsendgrid.net"), None);
+        assert_eq!(identify_esp("shop.example"), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::Write;
+
+use crate::store::StoredAnalysis;
+
+/// Rows per Parquet row group
+#[cfg(feature = "parquet")]
+const ROW_GROUP: usize = 8192;
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One JSON object per line
+    Ndjson,
+    /// Columns for the verdict, risk score, and sender domain, plus the result as JSON;
+    /// needs the `parquet` feature
+    Parquet,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ndjson" | "jsonl" => Some(Self::Ndjson),
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+/// An analysis as exported: the stored result, and the raw message when asked for
+#[derive(Debug, serde::Serialize)]
+struct ExportedAnalysis<'a> {
+    id: &'a str,
+    at: i64,
+    tenant: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw_email: Option<&'a str>,
+    result: &'a serde_json::Value,
+}
+
+impl<'a> ExportedAnalysis<'a> {
+    fn new(analysis: &'a StoredAnalysis, raw: bool) -> Self {
+        Self {
+            id: &analysis.id,
+            at: analysis.at,
+            tenant: &analysis.tenant,
+            raw_email: (raw && analysis.has_raw()).then_some(analysis.raw_email.as_str()),
+            result: &analysis.result,
+        }
+    }
+}
+
+/// `analysis` as a line of NDJSON; with `raw`, including its base64 raw message if kept
+pub fn ndjson_line(analysis: &StoredAnalysis, raw: bool) -> anyhow::Result<String> {
+    let mut line = serde_json::to_string(&ExportedAnalysis::new(analysis, raw))?;
+    line.push('\n');
+    Ok(line)
+}
+
+/// Writes `analyses` to `out` in `format`; with `raw`, including the raw messages kept
+pub fn export(
+    analyses: &[StoredAnalysis],
+    format: ExportFormat,
+    raw: bool,
+    mut out: impl Write + Send,
+) -> anyhow::Result<()> {
+    match format {
+        ExportFormat::Ndjson => {
+            for analysis in analyses {
+                out.write_all(ndjson_line(analysis, raw)?.as_bytes())?;
+            }
+            out.flush()?;
+            Ok(())
+        }
+        ExportFormat::Parquet => write_parquet(analyses, raw, out),
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(
+    analyses: &[StoredAnalysis],
+    raw: bool,
+    out: impl Write + Send,
+) -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray, UInt32Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("at", DataType::Int64, false),
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("verdict", DataType::Utf8, true),
+        Field::new("risk_score", DataType::UInt32, true),
+        Field::new("from_domain", DataType::Utf8, true),
+        Field::new("raw_email", DataType::Utf8, true),
+        Field::new("result", DataType::Utf8, false),
+    ]));
+    let mut writer = ArrowWriter::try_new(out, schema.clone(), None)?;
+    for chunk in analyses.chunks(ROW_GROUP) {
+        let text = |value: &serde_json::Value| value.as_str().map(str::to_string);
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(StringArray::from_iter_values(chunk.iter().map(|a| &a.id))),
+            Arc::new(Int64Array::from_iter_values(chunk.iter().map(|a| a.at))),
+            Arc::new(StringArray::from_iter_values(
+                chunk.iter().map(|a| &a.tenant),
+            )),
+            Arc::new(StringArray::from_iter(
+                chunk.iter().map(|a| text(&a.result["verdict"])),
+            )),
+            Arc::new(UInt32Array::from_iter(chunk.iter().map(|a| {
+                a.result["risk_score"].as_u64().map(|score| score as u32)
+            }))),
+            Arc::new(StringArray::from_iter(
+                chunk
+                    .iter()
+                    .map(|a| text(&a.result["evidence"]["from_domain"])),
+            )),
+            Arc::new(StringArray::from_iter(
+                chunk
+                    .iter()
+                    .map(|a| (raw && a.has_raw()).then(|| a.raw_email.clone())),
+            )),
+            Arc::new(StringArray::from_iter_values(
+                chunk.iter().map(|a| a.result.to_string()),
+            )),
+        ];
+        writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(
+    _analyses: &[StoredAnalysis],
+    _raw: bool,
+    _out: impl Write + Send,
+) -> anyhow::Result<()> {
+    anyhow::bail!("Parquet export needs the parquet feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExportFormat, export};
+    use crate::dns::DnsSnapshot;
+    use crate::store::StoredAnalysis;
+    use serde_json::json;
+
+    #[test]
+    fn test_export_ndjson() {
+        let mut analysis = StoredAnalysis::new(
+            "acme",
+            b"From: a@example.com\r\n\r\nbody",
+            DnsSnapshot::default(),
+            json!({"id": "ab".repeat(32), "verdict": "Suspicious"}),
+        );
+        let mut out = Vec::new();
+        export(&[analysis.clone()], ExportFormat::Ndjson, false, &mut out).unwrap();
+        let line: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line["tenant"], "acme");
+        assert_eq!(line["result"]["verdict"], "Suspicious");
+        assert!(line.get("raw_email").is_none());
+
+        let mut out = Vec::new();
+        export(&[analysis.clone()], ExportFormat::Ndjson, true, &mut out).unwrap();
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains(&analysis.raw_email)
+        );
+        analysis.drop_raw();
+        let mut out = Vec::new();
+        export(&[analysis], ExportFormat::Ndjson, true, &mut out).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("raw_email"));
+        assert_eq!(
+            ExportFormat::from_name("Parquet"),
+            Some(ExportFormat::Parquet)
+        );
+    }
+}
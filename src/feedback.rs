@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::audit::{append_json_line, read_json_lines};
+
+/// An analyst's judgement of a verdict
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackLabel {
+    /// A legitimate message was flagged
+    FalsePositive,
+    /// A spoofed message was not flagged
+    FalseNegative,
+}
+
+impl FeedbackLabel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "false_positive" | "false-positive" | "fp" => Some(Self::FalsePositive),
+            "false_negative" | "false-negative" | "fn" => Some(Self::FalseNegative),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FalsePositive => "false_positive",
+            Self::FalseNegative => "false_negative",
+        }
+    }
+}
+
+/// Feedback on one analysis
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Feedback {
+    /// Unix time the feedback was given
+    pub at: i64,
+    /// `id` of the analysis result
+    pub analysis_id: String,
+    pub label: FeedbackLabel,
+    /// Who gave the feedback
+    pub analyst: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Whether `id` looks like an analysis id, i.e. a hex SHA-256
+pub fn is_analysis_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Append-only log of analyst feedback, kept like the audit log: as JSON lines in a
+/// file, or in memory when there is none
+pub struct FeedbackLog {
+    path: Option<String>,
+    entries: Mutex<Vec<Feedback>>,
+}
+
+impl FeedbackLog {
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records feedback on the analysis `analysis_id`
+    pub fn record(
+        &self,
+        analysis_id: &str,
+        label: FeedbackLabel,
+        analyst: &str,
+        note: Option<String>,
+    ) -> anyhow::Result<Feedback> {
+        if !is_analysis_id(analysis_id) {
+            anyhow::bail!("Not an analysis id: {}", analysis_id);
+        }
+        let feedback = Feedback {
+            at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            analysis_id: analysis_id.to_ascii_lowercase(),
+            label,
+            analyst: analyst.to_string(),
+            note: note.filter(|note| !note.trim().is_empty()),
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        match &self.path {
+            Some(path) => append_json_line(path, &feedback)?,
+            None => entries.push(feedback.clone()),
+        }
+        Ok(feedback)
+    }
+
+    /// All feedback, oldest first
+    pub fn entries(&self) -> anyhow::Result<Vec<Feedback>> {
+        let entries = self.entries.lock().unwrap();
+        match &self.path {
+            Some(path) => read_json_lines(path),
+            None => Ok(entries.clone()),
+        }
+    }
+
+    /// Labeled data for scoring: the latest label of each analysis
+    pub fn labels(&self) -> anyhow::Result<BTreeMap<String, FeedbackLabel>> {
+        Ok(self
+            .entries()?
+            .into_iter()
+            .map(|feedback| (feedback.analysis_id, feedback.label))
+            .collect())
+    }
+
+    /// Number of feedback entries by label
+    pub fn stats(&self) -> anyhow::Result<BTreeMap<FeedbackLabel, u64>> {
+        let mut stats = BTreeMap::new();
+        for feedback in self.entries()? {
+            *stats.entry(feedback.label).or_default() += 1;
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FeedbackLabel, FeedbackLog};
+
+    #[test]
+    fn test_latest_feedback_labels_analysis() {
+        let id = "ab".repeat(32);
+        let log = FeedbackLog::new(None);
+        assert!(
+            log.record("nope", FeedbackLabel::FalsePositive, "alice", None)
+                .is_err()
+        );
+
+        log.record(
+            &id,
+            FeedbackLabel::FalsePositive,
+            "alice",
+            Some("newsletter".into()),
+        )
+        .unwrap();
+        log.record(&id, FeedbackLabel::FalseNegative, "bob", Some(" ".into()))
+            .unwrap();
+
+        let entries = log.entries().unwrap();
+        assert_eq!(entries[0].note.as_deref(), Some("newsletter"));
+        assert_eq!(entries[1].note, None);
+        assert_eq!(log.labels().unwrap()[&id], FeedbackLabel::FalseNegative);
+        assert_eq!(log.stats().unwrap()[&FeedbackLabel::FalsePositive], 1);
+    }
+}
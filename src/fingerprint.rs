@@ -0,0 +1,191 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::parse::EmailParsed;
+
+/// Messages a sender domain needs in its history before a new fingerprint is judged
+pub const MIN_HISTORY: usize = 5;
+
+/// Fingerprints kept per sender domain, most recent last
+const MAX_HISTORY: usize = 50;
+
+/// Header-set similarity below which two messages count as composed differently
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// Headers added in transit rather than by the software composing the message
+const TRACE_HEADERS: &[&str] = &[
+    "received",
+    "return-path",
+    "delivered-to",
+    "authentication-results",
+    "received-spf",
+    "dkim-signature",
+    "arc-seal",
+    "arc-message-signature",
+    "arc-authentication-results",
+];
+
+/// What the software that composed a message looks like
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MailerFingerprint {
+    /// `X-Mailer` or `User-Agent` without version, lowercase, e.g. `microsoft outlook`
+    pub mailer: Option<String>,
+    /// Names of the headers the composing software wrote, in first-seen order; `X-`
+    /// headers and those added in transit are left out
+    pub headers: Vec<String>,
+}
+
+/// A message whose fingerprint differs sharply from everything its sender domain sent
+/// before, e.g. an Outlook shop suddenly sending PHPMailer-style messages
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FingerprintAnomaly {
+    /// Mailers seen in the domain's history, most common first
+    pub usual_mailers: Vec<String>,
+    /// Highest header-set similarity (0–1) to a message in the history
+    pub similarity: f64,
+    /// Messages in the history compared against
+    pub history: usize,
+}
+
+/// The fingerprint of `parsed`
+pub fn fingerprint(parsed: &EmailParsed) -> MailerFingerprint {
+    let mut headers: Vec<String> = Vec::new();
+    for name in &parsed.header_names {
+        let transit = name.starts_with("x-") || TRACE_HEADERS.contains(&name.as_str());
+        if !transit && !headers.contains(name) {
+            headers.push(name.clone());
+        }
+    }
+    MailerFingerprint {
+        mailer: parsed.mailer.as_deref().and_then(mailer_family),
+        headers,
+    }
+}
+
+/// `Microsoft Outlook 16.0` → `microsoft outlook`, `PHPMailer 6.5.0 (https://...)` →
+/// `phpmailer`
+fn mailer_family(mailer: &str) -> Option<String> {
+    let end = mailer
+        .find(|c: char| c.is_ascii_digit() || c == '(' || c == '/' || c == ';')
+        .unwrap_or(mailer.len());
+    let family = mailer[..end]
+        .trim_end_matches(|c: char| c.is_whitespace() || c == '-')
+        .to_ascii_lowercase();
+    let family = family
+        .strip_suffix(" v")
+        .unwrap_or(&family)
+        .trim()
+        .to_string();
+    (!family.is_empty()).then_some(family)
+}
+
+/// Jaccard similarity of the header sets of two fingerprints
+fn similarity(a: &MailerFingerprint, b: &MailerFingerprint) -> f64 {
+    let shared = a.headers.iter().filter(|h| b.headers.contains(h)).count();
+    let union = a.headers.len() + b.headers.len() - shared;
+    if union == 0 {
+        1.0
+    } else {
+        shared as f64 / union as f64
+    }
+}
+
+/// Recent fingerprints of each sender domain
+#[derive(Debug, Default)]
+pub struct FingerprintHistory {
+    domains: BTreeMap<String, VecDeque<MailerFingerprint>>,
+}
+
+impl FingerprintHistory {
+    /// Builds the history from stored results, oldest first
+    pub fn from_results<'a>(results: impl IntoIterator<Item = &'a serde_json::Value>) -> Self {
+        let mut history = Self::default();
+        for result in results {
+            let evidence = &result["evidence"];
+            let (Some(domain), Ok(fingerprint)) = (
+                evidence["from_domain"].as_str(),
+                serde_json::from_value(evidence["fingerprint"].clone()),
+            ) else {
+                continue;
+            };
+            history.record(domain, fingerprint);
+        }
+        history
+    }
+
+    pub fn record(&mut self, domain: &str, fingerprint: MailerFingerprint) {
+        let seen = self.domains.entry(domain.to_ascii_lowercase()).or_default();
+        if seen.len() == MAX_HISTORY {
+            seen.pop_front();
+        }
+        seen.push_back(fingerprint);
+    }
+
+    /// Compares `fingerprint` with the history of `domain`
+    ///
+    /// It is anomalous when the domain has at least [`MIN_HISTORY`] messages, none of
+    /// them came from the same mailer, and none has a similar set of headers.
+    pub fn check(
+        &self,
+        domain: &str,
+        fingerprint: &MailerFingerprint,
+    ) -> Option<FingerprintAnomaly> {
+        let seen = self.domains.get(&domain.to_ascii_lowercase())?;
+        if seen.len() < MIN_HISTORY || seen.iter().any(|s| s.mailer == fingerprint.mailer) {
+            return None;
+        }
+        let best = seen
+            .iter()
+            .map(|s| similarity(s, fingerprint))
+            .fold(0.0, f64::max);
+        if best >= MIN_SIMILARITY {
+            return None;
+        }
+
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for mailer in seen.iter().filter_map(|s| s.mailer.as_deref()) {
+            *counts.entry(mailer).or_default() += 1;
+        }
+        let mut usual: Vec<(&str, usize)> = counts.into_iter().collect();
+        usual.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        Some(FingerprintAnomaly {
+            usual_mailers: usual.into_iter().map(|(m, _)| m.to_string()).collect(),
+            similarity: best,
+            history: seen.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FingerprintHistory, MIN_HISTORY, fingerprint};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_fingerprint_anomaly() {
+        let outlook = parse_email(
+            b"Received: from a\r\nFrom: cfo@corp.example\r\nTo: b@corp.example\r\nSubject: Q3\r\nThread-Topic: Q3\r\nThread-Index: AQ==\r\nDate: Mon, 1 Jan 2024 10:00:00 +0000\r\nMessage-ID: <1@corp.example>\r\nAccept-Language: en-US\r\nContent-Language: en-US\r\nX-Mailer: Microsoft Outlook 16.0\r\nMIME-Version: 1.0\r\n\r\nbody",
+        )
+        .unwrap();
+        let php = parse_email(
+            b"Received: from b\r\nDate: Mon, 1 Jan 2024 10:00:00 +0000\r\nTo: b@corp.example\r\nFrom: cfo@corp.example\r\nReply-To: cfo@mail.test\r\nSubject: Invoice\r\nX-Mailer: PHPMailer 6.5.0 (https://github.com/PHPMailer/PHPMailer)\r\nContent-Transfer-Encoding: 8bit\r\n\r\nbody",
+        )
+        .unwrap();
+        let usual = fingerprint(&outlook);
+        assert_eq!(usual.mailer.as_deref(), Some("microsoft outlook"));
+        assert!(!usual.headers.contains(&"received".to_string()));
+        assert_eq!(fingerprint(&php).mailer.as_deref(), Some("phpmailer"));
+
+        let mut history = FingerprintHistory::default();
+        for _ in 0..MIN_HISTORY - 1 {
+            history.record("corp.example", usual.clone());
+        }
+        assert!(history.check("corp.example", &fingerprint(&php)).is_none());
+        history.record("corp.example", usual.clone());
+
+        let anomaly = history.check("Corp.example", &fingerprint(&php)).unwrap();
+        assert_eq!(anomaly.usual_mailers, ["microsoft outlook"]);
+        assert_eq!(anomaly.history, MIN_HISTORY);
+        assert!(history.check("corp.example", &usual).is_none());
+        assert!(history.check("other.example", &fingerprint(&php)).is_none());
+    }
+}
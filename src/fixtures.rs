@@ -0,0 +1,149 @@
+use crate::{
+    analyzer::Analyzer,
+    dns::{DnsSnapshot, ResolverTrait},
+    email_verdict::{AnalysisOptions, Verdict},
+    parse::parse_email,
+};
+
+/// What kind of mail a fixture is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureCategory {
+    Legit,
+    Spoofed,
+    Forwarded,
+    ListMail,
+    DkimReplay,
+}
+
+/// An annotated sample message with the outcome the default configuration gives it
+#[derive(Debug, Clone, Copy)]
+pub struct Fixture {
+    pub name: &'static str,
+    pub category: FixtureCategory,
+    pub raw: &'static [u8],
+    pub expected_verdict: Verdict,
+    /// Rule IDs expected among the reasons
+    pub expected_rules: &'static [&'static str],
+}
+
+/// The curated corpus; its domains resolve through [`dns_snapshot`]
+pub const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "legit",
+        category: FixtureCategory::Legit,
+        raw: include_bytes!("../fixtures/legit.eml"),
+        expected_verdict: Verdict::Authenticated,
+        expected_rules: &[],
+    },
+    Fixture {
+        name: "spoofed",
+        category: FixtureCategory::Spoofed,
+        raw: include_bytes!("../fixtures/spoofed.eml"),
+        expected_verdict: Verdict::PolicyViolation,
+        expected_rules: &["ESD-0005"],
+    },
+    Fixture {
+        name: "forwarded",
+        category: FixtureCategory::Forwarded,
+        raw: include_bytes!("../fixtures/forwarded.eml"),
+        // The relay breaks SPF and the DKIM signature alone is not verified
+        expected_verdict: Verdict::PolicyViolation,
+        expected_rules: &["ESD-0005"],
+    },
+    Fixture {
+        name: "list_mail",
+        category: FixtureCategory::ListMail,
+        raw: include_bytes!("../fixtures/list_mail.eml"),
+        expected_verdict: Verdict::Authenticated,
+        expected_rules: &[],
+    },
+    Fixture {
+        name: "dkim_replay",
+        category: FixtureCategory::DkimReplay,
+        raw: include_bytes!("../fixtures/dkim_replay.eml"),
+        expected_verdict: Verdict::PolicyViolation,
+        expected_rules: &["ESD-0005", "ESD-0020", "ESD-0021", "ESD-0022", "ESD-0024"],
+    },
+];
+
+/// The DNS records the corpus was annotated against
+pub fn dns_snapshot() -> DnsSnapshot {
+    serde_json::from_str(include_str!("../fixtures/dns.json")).expect("fixtures/dns.json is valid")
+}
+
+/// An analyzer resolving through the corpus snapshot, with `options` under test
+pub fn fixture_analyzer(options: AnalysisOptions) -> Analyzer<DnsSnapshot> {
+    Analyzer::new(dns_snapshot(), options)
+}
+
+/// How one fixture fared
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct FixtureOutcome {
+    pub name: &'static str,
+    pub category: FixtureCategory,
+    pub expected_verdict: Verdict,
+    pub verdict: Verdict,
+    pub risk_score: u32,
+    /// Expected rule IDs that did not fire
+    pub missing_rules: Vec<&'static str>,
+}
+
+impl FixtureOutcome {
+    /// Whether the verdict or the expected rules changed
+    pub fn drifted(&self) -> bool {
+        self.verdict != self.expected_verdict || !self.missing_rules.is_empty()
+    }
+}
+
+/// Runs `analyzer` over the corpus, one outcome per fixture in corpus order
+///
+/// Use [`fixture_analyzer`] so the expected outcomes hold; other resolvers measure the
+/// live DNS as well as the configuration.
+pub async fn run_corpus<R: ResolverTrait + Sync + Send>(
+    analyzer: &Analyzer<R>,
+) -> anyhow::Result<Vec<FixtureOutcome>> {
+    let mut outcomes = Vec::new();
+    for fixture in FIXTURES {
+        let result = analyzer.analyze(&parse_email(fixture.raw)?).await?;
+        outcomes.push(FixtureOutcome {
+            name: fixture.name,
+            category: fixture.category,
+            expected_verdict: fixture.expected_verdict,
+            verdict: result.verdict,
+            risk_score: result.risk_score,
+            missing_rules: fixture
+                .expected_rules
+                .iter()
+                .filter(|id| !result.reasons.iter().any(|r| r.rule_id == **id))
+                .copied()
+                .collect(),
+        });
+    }
+    Ok(outcomes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fixture_analyzer, run_corpus};
+    use crate::email_verdict::AnalysisOptions;
+
+    #[tokio::test]
+    async fn test_corpus_matches_annotations() {
+        let outcomes = run_corpus(&fixture_analyzer(AnalysisOptions::default()))
+            .await
+            .unwrap();
+        for outcome in &outcomes {
+            assert!(!outcome.drifted(), "{:?}", outcome);
+        }
+
+        // Disabling a rule shows up as drift
+        let options = AnalysisOptions {
+            rules: serde_json::from_str(r#"{"disabled": ["ESD-0020"]}"#).unwrap(),
+            ..AnalysisOptions::default()
+        };
+        let outcomes = run_corpus(&fixture_analyzer(options)).await.unwrap();
+        let replay = outcomes.iter().find(|o| o.name == "dkim_replay").unwrap();
+        assert_eq!(replay.missing_rules, vec!["ESD-0020"]);
+    }
+}
@@ -0,0 +1,125 @@
+use aho_corasick::AhoCorasick;
+
+/// Extensions of web pages, lowercase
+const HTML_EXTENSIONS: &[&str] = &["html", "htm", "shtml", "xhtml"];
+
+/// Base64 characters in a row from which a run counts as an embedded payload
+const MIN_BLOB_LEN: usize = 4096;
+
+/// Script calls that assemble and save a file in the browser, in the order of the
+/// counts in [`HtmlIndicators`]
+const PATTERNS: &[&str] = &[
+    "atob(",
+    "new Blob(",
+    "msSaveBlob(",
+    "msSaveOrOpenBlob(",
+    "createObjectURL(",
+    ".click()",
+];
+
+/// Signs of HTML smuggling in a web page attachment: a file carried as base64 and
+/// assembled and downloaded by script when the page is opened
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct HtmlIndicators {
+    /// Runs of at least 4096 base64 characters
+    pub base64_blobs: usize,
+    /// Length of the longest base64 run, in characters
+    pub largest_blob: usize,
+    /// `atob(` calls, decoding base64
+    pub atob_calls: usize,
+    /// `new Blob(` constructions, building a file in memory
+    pub blob_constructors: usize,
+    /// `msSaveBlob(` and `msSaveOrOpenBlob(` calls, saving a file in Internet Explorer
+    pub ms_save_blob: usize,
+    /// `createObjectURL(` calls, giving a built file a URL to download from
+    pub object_urls: usize,
+    /// `.click()` calls, clicking a download link without the user
+    pub scripted_clicks: usize,
+    /// Whether the page builds a file to download: a base64 payload decoded or turned
+    /// into a `Blob`, or a file saved or clicked for download
+    pub smuggling: bool,
+}
+
+/// Whether an attachment is a web page, by file extension or MIME type
+pub fn is_html_attachment(filename: Option<&str>, content_type: &str) -> bool {
+    let extension = filename
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    extension.is_some_and(|extension| HTML_EXTENSIONS.contains(&extension.as_str()))
+        || matches!(content_type, "text/html" | "application/xhtml+xml")
+}
+
+/// Counts the base64 payloads and file-building script calls of a web page
+pub fn inspect_html(data: &[u8]) -> HtmlIndicators {
+    let mut indicators = HtmlIndicators::default();
+    for run in data.split(|&b| !(b.is_ascii_alphanumeric() || b"+/=".contains(&b))) {
+        if run.len() >= MIN_BLOB_LEN {
+            indicators.base64_blobs += 1;
+            indicators.largest_blob = indicators.largest_blob.max(run.len());
+        }
+    }
+
+    let matcher = AhoCorasick::new(PATTERNS).expect("fixed patterns");
+    for found in matcher.find_iter(data) {
+        let count = match found.pattern().as_usize() {
+            0 => &mut indicators.atob_calls,
+            1 => &mut indicators.blob_constructors,
+            2 | 3 => &mut indicators.ms_save_blob,
+            4 => &mut indicators.object_urls,
+            _ => &mut indicators.scripted_clicks,
+        };
+        *count += 1;
+    }
+
+    let decoded = indicators.atob_calls > 0 || indicators.blob_constructors > 0;
+    let saved = indicators.ms_save_blob > 0
+        || (indicators.object_urls > 0 && indicators.scripted_clicks > 0);
+    indicators.smuggling = (indicators.base64_blobs > 0 && decoded) || saved;
+    indicators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect_html, is_html_attachment};
+
+    #[test]
+    fn test_inspect_html() {
+        assert!(is_html_attachment(
+            Some("Invoice.HTM"),
+            "application/octet-stream"
+        ));
+        assert!(is_html_attachment(None, "text/html"));
+        assert!(!is_html_attachment(Some("report.pdf"), "application/pdf"));
+
+        let page = format!(
+            r#"<html><body><script>
+            var data = "{}";
+            var bytes = Uint8Array.from(atob(data), c => c.charCodeAt(0));
+            var blob = new Blob([bytes], {{type: "application/zip"}});
+            if (window.navigator.msSaveOrOpenBlob) {{ window.navigator.msSaveOrOpenBlob(blob, "invoice.zip"); }}
+            var a = document.createElement("a");
+            a.href = URL.createObjectURL(blob); a.download = "invoice.zip"; a.click();
+            </script></body></html>"#,
+            "UEsDBBQAAAAIAA".repeat(400)
+        );
+        let indicators = inspect_html(page.as_bytes());
+        assert_eq!(indicators.base64_blobs, 1);
+        assert_eq!(indicators.largest_blob, 5600);
+        assert_eq!(
+            (
+                indicators.atob_calls,
+                indicators.blob_constructors,
+                indicators.ms_save_blob,
+                indicators.object_urls,
+                indicators.scripted_clicks
+            ),
+            (1, 1, 1, 1, 1)
+        );
+        assert!(indicators.smuggling);
+
+        let login = b"<html><form action=\"https://example.net/login\"><input name=\"password\"></form></html>";
+        let indicators = inspect_html(login);
+        assert_eq!(indicators.base64_blobs, 0);
+        assert!(!indicators.smuggling);
+    }
+}
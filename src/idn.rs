@@ -0,0 +1,53 @@
+/// A domain in both its ASCII (`xn--`) and Unicode forms
+///
+/// Reports carry both so readers see what `xn--80ak6aa92e.com` actually displays as,
+/// and can tell at a glance whether a domain mixes in non-ASCII characters.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DomainLabels {
+    /// The A-label form, as used in DNS
+    pub a_label: String,
+
+    /// The U-label form, as a mail client displays it
+    pub u_label: String,
+
+    /// Whether the U-label contains characters outside ASCII
+    pub contains_non_ascii: bool,
+}
+
+/// Renders `domain`, given in either form, as A-label and U-label
+///
+/// Domains that are not valid IDNs are returned unchanged in both forms.
+pub fn domain_labels(domain: &str) -> DomainLabels {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    let a_label = idna::domain_to_ascii(&domain).unwrap_or_else(|_| domain.clone());
+    let (u_label, result) = idna::domain_to_unicode(&a_label);
+    let u_label = match result {
+        Ok(()) => u_label,
+        Err(_) => domain,
+    };
+    DomainLabels {
+        contains_non_ascii: !u_label.is_ascii(),
+        a_label,
+        u_label,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::domain_labels;
+
+    #[test]
+    fn test_domain_labels() {
+        let idn = domain_labels("xn--pypal-4ve.com");
+        assert_eq!(idn.u_label, "pаypal.com");
+        assert!(idn.contains_non_ascii);
+        assert_eq!(domain_labels("pаypal.com"), idn);
+
+        let ascii = domain_labels("Example.com.");
+        assert_eq!(
+            (ascii.a_label.as_str(), ascii.u_label.as_str()),
+            ("example.com", "example.com")
+        );
+        assert!(!ascii.contains_non_ascii);
+    }
+}
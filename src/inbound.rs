@@ -0,0 +1,267 @@
+use std::borrow::Cow;
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::tenants::keys_equal;
+
+/// Inbound-mail webhook formats of email service providers
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub enum InboundFormat {
+    /// SendGrid Inbound Parse with "POST the raw, full MIME message" enabled (`email` field)
+    SendGrid,
+    /// Mailgun route forwarding to a URL ending in `mime` (`body-mime` field)
+    Mailgun,
+    /// Postmark inbound JSON with "Include raw email content" enabled (`RawEmail`)
+    Postmark,
+}
+
+impl InboundFormat {
+    /// Parses the provider name used in the webhook path, e.g. `sendgrid`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sendgrid" => Some(Self::SendGrid),
+            "mailgun" => Some(Self::Mailgun),
+            "postmark" => Some(Self::Postmark),
+            _ => None,
+        }
+    }
+}
+
+/// Extracts the raw MIME message from a provider's webhook request body
+pub fn extract_raw_mime(
+    format: InboundFormat,
+    content_type: &str,
+    body: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let field = match format {
+        InboundFormat::Postmark => {
+            let payload: serde_json::Value = serde_json::from_slice(body)?;
+            return payload["RawEmail"]
+                .as_str()
+                .map(|raw| raw.as_bytes().to_vec())
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "RawEmail missing; enable \"Include raw email content\" in Postmark"
+                    )
+                });
+        }
+        InboundFormat::SendGrid => "email",
+        InboundFormat::Mailgun => "body-mime",
+    };
+    form_field(content_type, body, field)?
+        .ok_or_else(|| anyhow::anyhow!("form field `{}` with the raw message is missing", field))
+}
+
+/// Reads one field of a `multipart/form-data` or `application/x-www-form-urlencoded` body
+pub fn form_field(content_type: &str, body: &[u8], name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let mimetype = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    match mimetype.as_str() {
+        "multipart/form-data" => {
+            // Form data is MIME multipart, so the mail parser can split it given the boundary
+            let mut message = format!("Content-Type: {}\r\n\r\n", content_type).into_bytes();
+            message.extend_from_slice(body);
+            let form = mailparse::parse_mail(&message)?;
+            for part in &form.subparts {
+                let disposition = part.get_content_disposition();
+                if disposition.params.get("name").map(String::as_str) == Some(name) {
+                    return Ok(Some(part.get_body_raw()?));
+                }
+            }
+            Ok(None)
+        }
+        "application/x-www-form-urlencoded" => Ok(url::form_urlencoded::parse(body)
+            .find(|(key, _)| key == name)
+            .map(|(_, value): (_, Cow<str>)| value.into_owned().into_bytes())),
+        other => anyhow::bail!("unsupported webhook content type `{}`", other),
+    }
+}
+
+/// Whether an inbound webhook request carries `secret`
+///
+/// SendGrid and Postmark do not sign inbound webhooks, but send the credentials of the
+/// webhook URL (`https://inbound:<secret>@host/inbound/postmark`) as basic
+/// authorization, whose password must be `secret`. Mailgun may instead sign its
+/// `timestamp` and `token` fields into `signature` with `secret` as the signing key.
+pub fn authenticate(
+    format: InboundFormat,
+    secret: &str,
+    authorization: Option<&str>,
+    content_type: &str,
+    body: &[u8],
+) -> bool {
+    let password = authorization
+        .and_then(|h| h.strip_prefix("Basic "))
+        .and_then(|credentials| STANDARD.decode(credentials.trim()).ok())
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .and_then(|credentials| Some(credentials.split_once(':')?.1.to_string()));
+    if password.is_some_and(|password| keys_equal(&password, secret)) {
+        return true;
+    }
+    format == InboundFormat::Mailgun && mailgun_signed(secret, content_type, body)
+}
+
+/// Whether the `signature` field of a Mailgun webhook is the HMAC-SHA256 of its
+/// `timestamp` and `token` under `key`
+fn mailgun_signed(key: &str, content_type: &str, body: &[u8]) -> bool {
+    let field = |name| form_field(content_type, body, name).ok().flatten();
+    let (Some(timestamp), Some(token), Some(signature)) =
+        (field("timestamp"), field("token"), field("signature"))
+    else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key.as_bytes()) else {
+        return false;
+    };
+    mac.update(&timestamp);
+    mac.update(&token);
+    let expected = crate::dedup::hex(&mac.finalize().into_bytes());
+    keys_equal(
+        &expected,
+        &String::from_utf8_lossy(&signature).to_ascii_lowercase(),
+    )
+}
+
+/// Posts a JSON verdict to the configured destination
+pub async fn forward(
+    client: &reqwest::Client,
+    url: &str,
+    verdict: &serde_json::Value,
+) -> anyhow::Result<()> {
+    client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(verdict.to_string())
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InboundFormat, authenticate, extract_raw_mime};
+
+    const RAW: &str = "From: user@example.com\r\nSubject: hi\r\n\r\nhello\r\n";
+
+    #[test]
+    fn test_sendgrid_multipart() {
+        let body = format!(
+            "--xYzZY\r\n\
+             Content-Disposition: form-data; name=\"to\"\r\n\r\n\
+             inbox@example.org\r\n\
+             --xYzZY\r\n\
+             Content-Disposition: form-data; name=\"email\"\r\n\r\n\
+             {}\r\n\
+             --xYzZY--\r\n",
+            RAW
+        );
+        let raw = extract_raw_mime(
+            InboundFormat::SendGrid,
+            "multipart/form-data; boundary=xYzZY",
+            body.as_bytes(),
+        )
+        .unwrap();
+        assert!(
+            String::from_utf8(raw)
+                .unwrap()
+                .starts_with("From: user@example.com")
+        );
+    }
+
+    #[test]
+    fn test_mailgun_urlencoded_and_postmark_json() {
+        let body = format!(
+            "recipient=inbox%40example.org&body-mime={}",
+            url::form_urlencoded::byte_serialize(RAW.as_bytes()).collect::<String>()
+        );
+        let raw = extract_raw_mime(
+            InboundFormat::Mailgun,
+            "application/x-www-form-urlencoded",
+            body.as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(raw, RAW.as_bytes());
+
+        let json = serde_json::json!({"From": "user@example.com", "RawEmail": RAW}).to_string();
+        let raw =
+            extract_raw_mime(InboundFormat::Postmark, "application/json", json.as_bytes()).unwrap();
+        assert_eq!(raw, RAW.as_bytes());
+
+        let without_raw = serde_json::json!({"From": "user@example.com"}).to_string();
+        assert!(
+            extract_raw_mime(
+                InboundFormat::Postmark,
+                "application/json",
+                without_raw.as_bytes()
+            )
+            .is_err()
+        );
+        assert_eq!(
+            InboundFormat::from_name("SendGrid"),
+            Some(InboundFormat::SendGrid)
+        );
+    }
+
+    #[test]
+    fn test_authenticate_webhook() {
+        let json = "application/json";
+        // inbound:s3cret and inbound:wrong
+        let basic = Some("Basic aW5ib3VuZDpzM2NyZXQ=");
+        let wrong = Some("Basic aW5ib3VuZDp3cm9uZw==");
+        assert!(authenticate(
+            InboundFormat::Postmark,
+            "s3cret",
+            basic,
+            json,
+            b"{}"
+        ));
+        assert!(!authenticate(
+            InboundFormat::Postmark,
+            "s3cret",
+            wrong,
+            json,
+            b"{}"
+        ));
+        assert!(!authenticate(
+            InboundFormat::SendGrid,
+            "s3cret",
+            None,
+            json,
+            b"{}"
+        ));
+
+        let form = "application/x-www-form-urlencoded";
+        let signed = "timestamp=1767225600&token=5f1c2b7e9d\
+                      &signature=7e0ed9aef93afbab0866d2dbf2630408767e6d51b29a02513575537e683875c2";
+        let key = "mailgun-signing-key";
+        assert!(authenticate(
+            InboundFormat::Mailgun,
+            key,
+            None,
+            form,
+            signed.as_bytes()
+        ));
+        assert!(!authenticate(
+            InboundFormat::Mailgun,
+            "other",
+            None,
+            form,
+            signed.as_bytes()
+        ));
+        assert!(!authenticate(
+            InboundFormat::SendGrid,
+            key,
+            None,
+            form,
+            signed.as_bytes()
+        ));
+    }
+}
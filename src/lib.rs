@@ -1,8 +1,85 @@
+pub mod analytics;
+pub mod analyzer;
+pub mod arc;
+pub mod archive;
+pub mod attachments;
+pub mod audit;
+pub mod auth_results;
+pub mod automated;
+pub mod batch;
+pub mod body;
+pub mod brand_watch;
+pub mod bundle;
+pub mod calibration;
+pub mod campaign;
+pub mod canonical;
+pub mod checks;
+pub mod config;
+pub mod ct;
+pub mod dangling;
+pub mod dedup;
+pub mod deobfuscate;
+pub mod diff;
+pub mod dkim_coverage;
+pub mod dkim_lint;
+pub mod dkim_replay;
+pub mod dmarc_lint;
 pub mod dns;
+pub mod dns_backends;
 pub mod domain_verdict;
+pub mod early_exit;
 pub mod email_verdict;
+pub mod envelope;
+pub mod esp;
+pub mod export;
+pub mod feedback;
+pub mod fingerprint;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod html;
+pub mod http;
+pub mod idn;
+pub mod inbound;
+pub mod integrity;
+pub mod lint;
+pub mod lists;
+pub mod lookalike;
+pub mod mbox;
+pub mod messages;
+pub mod monitor;
+pub mod normalize;
+pub mod office;
+pub mod offline;
 pub mod parse;
+pub mod passive_dns;
+pub mod pdf;
+pub mod phish_report;
+pub mod pool;
+pub mod reasons;
+pub mod received;
+pub mod redact;
+pub mod registration;
+pub mod report;
+pub mod rules;
+pub mod session;
+pub mod settings;
+pub mod shadow;
+pub mod spf_flatten;
+pub mod spf_lint;
+pub mod spf_walk;
+pub mod store;
+pub mod store_backends;
+pub mod systemd;
+pub mod tenants;
+pub mod text_heuristics;
+pub mod timing;
+pub mod trust_store;
+pub mod url_expand;
+pub mod zone_limit;
 
+pub use analyzer::Analyzer;
 pub use dns::DnsResolver;
-pub use email_verdict::{AnalysisResult, Evidence, Verdict, analyze_email};
+pub use email_verdict::{
+    AnalysisOptions, AnalysisResult, Evidence, Verdict, analyze_email, analyze_email_with_options,
+};
 pub use parse::{EmailParsed, extract_domain};
@@ -0,0 +1,85 @@
+use async_trait::async_trait;
+use std::net::IpAddr;
+
+use crate::{DnsResolver, reasons::Severity};
+
+/// Source of raw DNS records, used by the record linters
+#[async_trait]
+pub trait RecordSource {
+    /// All TXT records at `name`, or `None` when the lookup fails
+    async fn txt_records(&self, name: &str) -> Option<Vec<String>>;
+
+    /// Target of a CNAME record at `name`, if there is one
+    async fn cname(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// MX exchange host names of `domain`
+    async fn mx_hosts(&self, _domain: &str) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// IPv4 and IPv6 addresses of `name`
+    async fn addresses(&self, _name: &str) -> Vec<IpAddr> {
+        Vec::new()
+    }
+}
+
+#[async_trait]
+impl RecordSource for DnsResolver {
+    async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+        self.resolve_txt(name).await
+    }
+
+    async fn cname(&self, name: &str) -> Option<String> {
+        self.cname_target(name).await
+    }
+
+    async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+        DnsResolver::mx_hosts(self, domain).await
+    }
+
+    async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+        DnsResolver::addresses(self, name).await
+    }
+}
+
+/// One problem found in a domain's published mail authentication records
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct LintFinding {
+    /// Stable identifier, e.g. `multiple_records`
+    pub code: &'static str,
+    pub severity: Severity,
+    /// Domain whose record has the problem (the audited domain or e.g. an include target)
+    pub domain: String,
+    pub detail: String,
+    /// What to change to fix it
+    pub remediation: String,
+    /// The offending record, when a single one is at fault
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<String>,
+}
+
+impl LintFinding {
+    pub(crate) fn new(
+        code: &'static str,
+        severity: Severity,
+        domain: &str,
+        detail: String,
+        remediation: &str,
+    ) -> Self {
+        Self {
+            code,
+            severity,
+            domain: domain.to_string(),
+            detail,
+            remediation: remediation.to_string(),
+            record: None,
+        }
+    }
+
+    pub(crate) fn with_record(mut self, record: impl Into<String>) -> Self {
+        self.record = Some(record.into());
+        self
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::BTreeSet;
+
+use mailparse::{MailAddr, addrparse};
+
+/// Operator-maintained sender lists
+///
+/// Allow- and blocklist entries are addresses (`ceo@example.com`) or domains, which also
+/// cover their subdomains. VIPs are display names of people attackers are likely to
+/// impersonate, e.g. executives.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SenderLists {
+    pub allowlist: BTreeSet<String>,
+    pub blocklist: BTreeSet<String>,
+    pub vips: BTreeSet<String>,
+}
+
+/// One of the lists in [`SenderLists`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListKind {
+    Allowlist,
+    Blocklist,
+    Vips,
+}
+
+impl ListKind {
+    /// Parses the name used in the admin API paths
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "allowlist" => Some(Self::Allowlist),
+            "blocklist" => Some(Self::Blocklist),
+            "vips" => Some(Self::Vips),
+            _ => None,
+        }
+    }
+}
+
+impl SenderLists {
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the lists to `path`, replacing the file atomically
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn list(&self, kind: ListKind) -> &BTreeSet<String> {
+        match kind {
+            ListKind::Allowlist => &self.allowlist,
+            ListKind::Blocklist => &self.blocklist,
+            ListKind::Vips => &self.vips,
+        }
+    }
+
+    fn list_mut(&mut self, kind: ListKind) -> &mut BTreeSet<String> {
+        match kind {
+            ListKind::Allowlist => &mut self.allowlist,
+            ListKind::Blocklist => &mut self.blocklist,
+            ListKind::Vips => &mut self.vips,
+        }
+    }
+
+    /// Adds an entry, returning whether it was new
+    pub fn add(&mut self, kind: ListKind, entry: &str) -> bool {
+        let entry = normalize(entry);
+        !entry.is_empty() && self.list_mut(kind).insert(entry)
+    }
+
+    /// Removes an entry, returning whether it was present
+    pub fn remove(&mut self, kind: ListKind, entry: &str) -> bool {
+        self.list_mut(kind).remove(&normalize(entry))
+    }
+}
+
+/// Lowercased with whitespace collapsed, so entries compare like the headers they match
+fn normalize(entry: &str) -> String {
+    entry
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches('.')
+        .to_lowercase()
+}
+
+/// List entries the sender of a message matched
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ListMatch {
+    /// Allowlist entry covering the sender address
+    pub allowlisted: Option<String>,
+    /// Blocklist entry covering the sender address
+    pub blocklisted: Option<String>,
+    /// VIP whose name is the display name of a sender outside `owned_domains`
+    pub vip_impersonation: Option<String>,
+}
+
+/// Matches the `From` header against the lists
+///
+/// Returns `None` when no list matched. A VIP name from one of `owned_domains` or an
+/// allowlisted sender is not an impersonation.
+pub fn check_lists(from: &str, lists: &SenderLists, owned_domains: &[String]) -> Option<ListMatch> {
+    let (display_name, address) = match addrparse(from).ok()?.first()? {
+        MailAddr::Single(info) => (info.display_name.clone(), normalize(&info.addr)),
+        MailAddr::Group(_) => return None,
+    };
+    let domain = address.rsplit_once('@').map_or("", |(_, d)| d);
+    let covers = |entry: &&String| {
+        **entry == address
+            || **entry == domain
+            || (domain.ends_with(&format!(".{}", entry)) && !entry.contains('@'))
+    };
+
+    let allowlisted = lists.allowlist.iter().find(covers).cloned();
+    let blocklisted = lists.blocklist.iter().find(covers).cloned();
+    let owned = owned_domains
+        .iter()
+        .any(|d| domain == normalize(d) || domain.ends_with(&format!(".{}", normalize(d))));
+    let vip_impersonation = match display_name {
+        Some(name) if !owned && allowlisted.is_none() => {
+            let name = normalize(&name);
+            lists.vips.iter().find(|vip| **vip == name).cloned()
+        }
+        _ => None,
+    };
+
+    let found = ListMatch {
+        allowlisted,
+        blocklisted,
+        vip_impersonation,
+    };
+    (found.allowlisted.is_some()
+        || found.blocklisted.is_some()
+        || found.vip_impersonation.is_some())
+    .then_some(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListKind, SenderLists, check_lists};
+
+    fn lists() -> SenderLists {
+        let mut lists = SenderLists::default();
+        lists.add(ListKind::Allowlist, "partner.example");
+        lists.add(ListKind::Blocklist, "Evil.test");
+        lists.add(ListKind::Blocklist, "spammer@gmail.example");
+        lists.add(ListKind::Vips, "Jane  Doe");
+        lists
+    }
+
+    #[test]
+    fn test_check_lists() {
+        let lists = lists();
+        let owned = vec!["corp.example".to_string()];
+
+        let blocked = check_lists("x <a@mail.evil.test>", &lists, &owned).unwrap();
+        assert_eq!(blocked.blocklisted.as_deref(), Some("evil.test"));
+        assert!(check_lists("other@gmail.example", &lists, &owned).is_none());
+        assert!(check_lists("spammer@gmail.example", &lists, &owned).is_some());
+
+        let vip = check_lists("\"Jane Doe\" <jane.doe.ceo@gmail.example>", &lists, &owned).unwrap();
+        assert_eq!(vip.vip_impersonation.as_deref(), Some("jane doe"));
+        assert!(check_lists("Jane Doe <jane@corp.example>", &lists, &owned).is_none());
+
+        let partner = check_lists("Jane Doe <jane@partner.example>", &lists, &owned).unwrap();
+        assert_eq!(partner.allowlisted.as_deref(), Some("partner.example"));
+        assert_eq!(partner.vip_impersonation, None);
+    }
+
+    #[test]
+    fn test_add_and_remove_entries() {
+        let mut lists = lists();
+        assert!(!lists.add(ListKind::Blocklist, "EVIL.test."));
+        assert!(lists.remove(ListKind::Vips, "jane doe"));
+        assert!(!lists.remove(ListKind::Vips, "jane doe"));
+        assert!(lists.list(ListKind::Vips).is_empty());
+    }
+}
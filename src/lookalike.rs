@@ -0,0 +1,338 @@
+use crate::ct::CtFindings;
+use crate::idn::{DomainLabels, domain_labels};
+
+/// How a sender domain imitates a protected domain
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub enum LookalikeKind {
+    /// Visually confusable characters, e.g. `paypa1.com` or a Cyrillic `а`
+    Homoglyph,
+    /// One edit away from the protected name, e.g. `paypall.com`
+    Typo,
+    /// Protected name reused under another TLD or with extra words, e.g. `paypal-secure.com`
+    Cousin,
+}
+
+/// A sender domain that imitates one of the protected domains
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LookalikeMatch {
+    /// The imitating domain (A-label)
+    pub domain: String,
+
+    /// `domain` as A-label and U-label
+    pub domain_labels: DomainLabels,
+
+    /// The protected domain it imitates
+    pub protected_domain: String,
+
+    pub kind: LookalikeKind,
+
+    /// Certificates logged for the lookalike domain, when CT lookups are enabled.
+    pub certificates: Option<CtFindings>,
+}
+
+/// Checks `domain` against the protected domains, returning the first imitation found
+///
+/// The protected domains themselves and their subdomains are never lookalikes.
+pub fn find_lookalike(domain: &str, protected: &[String]) -> Option<LookalikeMatch> {
+    let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+    let (label, tld) = split_domain(&domain)?;
+    let skeleton = skeleton(&unicode_label(label));
+
+    protected.iter().find_map(|p| {
+        let p = p.trim_end_matches('.').to_ascii_lowercase();
+        if domain
+            .strip_suffix(p.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+        {
+            return None;
+        }
+        let (p_label, p_tld) = split_domain(&p)?;
+
+        let kind = if label == p_label {
+            (tld != p_tld).then_some(LookalikeKind::Cousin)?
+        } else if skeleton == skeleton_ascii(p_label) {
+            LookalikeKind::Homoglyph
+        } else if p_label.len() >= 4 && levenshtein(label, p_label) == 1 {
+            LookalikeKind::Typo
+        } else if label.split('-').any(|part| part == p_label) {
+            LookalikeKind::Cousin
+        } else {
+            return None;
+        };
+
+        Some(LookalikeMatch {
+            domain_labels: domain_labels(&domain),
+            domain: domain.clone(),
+            protected_domain: p,
+            kind,
+            certificates: None,
+        })
+    })
+}
+
+/// TLDs tried when generating cousin domains
+const COUSIN_TLDS: &[&str] = &[
+    "com", "net", "org", "co", "io", "info", "biz", "app", "online",
+];
+
+/// Words commonly attached to a brand name in phishing domains
+const COUSIN_AFFIXES: &[&str] = &["secure", "login", "support", "account", "verify", "mail"];
+
+/// Generates typo, homoglyph, and cousin permutations of a protected domain
+///
+/// Every candidate is confirmed with [`find_lookalike`], so the kinds match what the
+/// sender check would report. Candidates are unique and sorted by domain.
+pub fn permutations(protected: &str) -> Vec<LookalikeMatch> {
+    let protected = protected.trim_end_matches('.').to_ascii_lowercase();
+    let Some((label, tld)) = split_domain(&protected) else {
+        return Vec::new();
+    };
+    let prefix = &protected[..protected.len() - label.len() - tld.len() - 1];
+    let chars: Vec<char> = label.chars().collect();
+    let mut labels = Vec::new();
+
+    // Typos: omission, repetition, transposition, and adjacent-key substitution
+    for i in 0..chars.len() {
+        let mut omitted = chars.clone();
+        omitted.remove(i);
+        labels.push(omitted.iter().collect::<String>());
+
+        let mut repeated = chars.clone();
+        repeated.insert(i, chars[i]);
+        labels.push(repeated.iter().collect());
+
+        if i + 1 < chars.len() {
+            let mut swapped = chars.clone();
+            swapped.swap(i, i + 1);
+            labels.push(swapped.iter().collect());
+        }
+
+        for neighbour in keyboard_neighbours(chars[i]).chars() {
+            let mut replaced = chars.clone();
+            replaced[i] = neighbour;
+            labels.push(replaced.iter().collect());
+        }
+    }
+
+    // Homoglyphs: one confusable substitution at a time, IDN ones as A-labels
+    for (from, to) in [
+        ("o", "0"),
+        ("l", "1"),
+        ("i", "1"),
+        ("l", "i"),
+        ("i", "l"),
+        ("e", "3"),
+        ("s", "5"),
+        ("m", "rn"),
+        ("w", "vv"),
+        ("a", "а"),
+        ("e", "е"),
+        ("o", "о"),
+        ("p", "р"),
+        ("c", "с"),
+    ] {
+        for (i, _) in label.match_indices(from) {
+            let unicode = format!("{}{}{}", &label[..i], to, &label[i + from.len()..]);
+            if let Ok(ascii) = idna::domain_to_ascii(&unicode) {
+                labels.push(ascii);
+            }
+        }
+    }
+
+    let mut domains: Vec<String> = labels
+        .iter()
+        .map(|l| format!("{}{}.{}", prefix, l, tld))
+        .collect();
+
+    // Cousins: other TLDs and brand-plus-word labels
+    for other in COUSIN_TLDS.iter().filter(|t| **t != tld) {
+        domains.push(format!("{}{}.{}", prefix, label, other));
+    }
+    for affix in COUSIN_AFFIXES {
+        domains.push(format!("{}{}-{}.{}", prefix, label, affix, tld));
+        domains.push(format!("{}{}-{}.{}", prefix, affix, label, tld));
+    }
+
+    domains.sort();
+    domains.dedup();
+    let protected = [protected];
+    domains
+        .iter()
+        .filter_map(|d| find_lookalike(d, &protected))
+        .collect()
+}
+
+/// Keys next to `c` on a QWERTY keyboard
+fn keyboard_neighbours(c: char) -> &'static str {
+    match c {
+        'q' => "wa",
+        'w' => "qeas",
+        'e' => "wrsd",
+        'r' => "etdf",
+        't' => "ryfg",
+        'y' => "tugh",
+        'u' => "yihj",
+        'i' => "uojk",
+        'o' => "ipkl",
+        'p' => "ol",
+        'a' => "qwsz",
+        's' => "awedxz",
+        'd' => "serfcx",
+        'f' => "drtgvc",
+        'g' => "ftyhbv",
+        'h' => "gyujnb",
+        'j' => "huikmn",
+        'k' => "jiolm",
+        'l' => "kop",
+        'z' => "asx",
+        'x' => "zsdc",
+        'c' => "xdfv",
+        'v' => "cfgb",
+        'b' => "vghn",
+        'n' => "bhjm",
+        'm' => "njk",
+        _ => "",
+    }
+}
+
+/// Splits a domain into the label left of the TLD and the TLD
+fn split_domain(domain: &str) -> Option<(&str, &str)> {
+    let mut labels = domain.rsplit('.');
+    let tld = labels.next()?;
+    let label = labels.next()?;
+    Some((label, tld))
+}
+
+/// Decodes a punycode label (`xn--...`) to Unicode, leaving others untouched
+fn unicode_label(label: &str) -> String {
+    if label.starts_with("xn--") {
+        idna::domain_to_unicode(label).0
+    } else {
+        label.to_string()
+    }
+}
+
+/// Maps confusable characters to the ASCII letter they imitate
+fn skeleton(label: &str) -> String {
+    let mapped: String = label
+        .chars()
+        .map(|c| match c {
+            '0' | 'о' | 'ο' => 'o',
+            '1' | 'ӏ' | 'ı' => 'l',
+            '3' => 'e',
+            '5' => 's',
+            'а' | 'α' => 'a',
+            'е' => 'e',
+            'р' | 'ρ' => 'p',
+            'с' => 'c',
+            'х' => 'x',
+            'у' => 'y',
+            'і' => 'i',
+            'ј' => 'j',
+            'ѕ' => 's',
+            'ԁ' => 'd',
+            'ν' => 'v',
+            _ => c,
+        })
+        .collect();
+    skeleton_ascii(&mapped)
+}
+
+/// Collapses multi-character ASCII confusables
+fn skeleton_ascii(label: &str) -> String {
+    label
+        .replace("rn", "m")
+        .replace("vv", "w")
+        .replace('i', "l")
+}
+
+/// Edit distance between two strings, by characters, counting a swap of adjacent
+/// characters as one edit
+fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_prev: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+            if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+                cur[j + 1] = cur[j + 1].min(before_prev[j - 1] + 1);
+            }
+        }
+        before_prev = std::mem::replace(&mut prev, cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LookalikeKind, find_lookalike, permutations};
+
+    fn protected() -> Vec<String> {
+        vec!["paypal.com".to_string(), "example.org".to_string()]
+    }
+
+    #[test]
+    fn test_homoglyph_lookalikes() {
+        let m = find_lookalike("paypa1.com", &protected()).unwrap();
+        assert_eq!(m.kind, LookalikeKind::Homoglyph);
+        assert_eq!(m.protected_domain, "paypal.com");
+
+        // "раypal" with Cyrillic р and а
+        let idn = idna::domain_to_ascii("раypal.com").unwrap();
+        assert_eq!(
+            find_lookalike(&idn, &protected()).unwrap().kind,
+            LookalikeKind::Homoglyph
+        );
+    }
+
+    #[test]
+    fn test_typo_and_cousin_lookalikes() {
+        assert_eq!(
+            find_lookalike("paypall.com", &protected()).unwrap().kind,
+            LookalikeKind::Typo
+        );
+        assert_eq!(
+            find_lookalike("mail.paypal-secure.com", &protected())
+                .unwrap()
+                .kind,
+            LookalikeKind::Cousin
+        );
+        assert_eq!(
+            find_lookalike("example.net", &protected()).unwrap().kind,
+            LookalikeKind::Cousin
+        );
+    }
+
+    #[test]
+    fn test_protected_domains_are_not_lookalikes() {
+        assert!(find_lookalike("paypal.com", &protected()).is_none());
+        assert!(find_lookalike("mail.PayPal.com.", &protected()).is_none());
+        assert!(find_lookalike("unrelated.com", &protected()).is_none());
+    }
+
+    #[test]
+    fn test_permutations_of_protected_domain() {
+        let candidates = permutations("paypal.com");
+        let find = |domain: &str| candidates.iter().find(|c| c.domain == domain);
+
+        assert_eq!(find("paypa1.com").unwrap().kind, LookalikeKind::Homoglyph);
+        assert_eq!(find("paypall.com").unwrap().kind, LookalikeKind::Typo);
+        assert_eq!(find("papyal.com").unwrap().kind, LookalikeKind::Typo);
+        assert_eq!(find("paypal.net").unwrap().kind, LookalikeKind::Cousin);
+        assert_eq!(
+            find("paypal-login.com").unwrap().kind,
+            LookalikeKind::Cousin
+        );
+        assert!(find(&idna::domain_to_ascii("pаypal.com").unwrap()).is_some());
+        assert!(find("paypal.com").is_none());
+        assert!(
+            candidates
+                .iter()
+                .all(|c| c.protected_domain == "paypal.com")
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::io::{self, BufRead};
+
+/// Splits an mbox file into its messages
+///
+/// Messages start at lines beginning with `From ` (the mboxrd separator line is dropped);
+/// `>From ` quoting inside bodies is undone.
+pub fn split_mbox(data: &[u8]) -> Vec<Vec<u8>> {
+    // Reading from memory cannot fail
+    MboxReader::new(data).map_while(Result::ok).collect()
+}
+
+/// Reads the messages of an mbox file one at a time, split as by [`split_mbox`], so a
+/// mailbox of any size is read with one message in memory
+pub struct MboxReader<R> {
+    lines: R,
+    line: Vec<u8>,
+    current: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> MboxReader<R> {
+    pub fn new(lines: R) -> Self {
+        Self {
+            lines,
+            line: Vec::new(),
+            current: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for MboxReader<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.lines.read_until(b'\n', &mut self.line) {
+                Ok(0) => return self.current.take().map(Ok),
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            let line = &self.line;
+            if line.starts_with(b"From ") {
+                match self.current.replace(Vec::new()) {
+                    Some(message) => return Some(Ok(message)),
+                    None => continue,
+                }
+            }
+            let Some(message) = self.current.as_mut() else {
+                continue;
+            };
+            let quoted = line.iter().take_while(|b| **b == b'>').count();
+            if quoted > 0 && line[quoted..].starts_with(b"From ") {
+                message.extend_from_slice(&line[1..]);
+            } else {
+                message.extend_from_slice(line);
+            }
+        }
+    }
+}
+
+/// Whether `data` looks like an mbox file rather than a single message
+pub fn is_mbox(data: &[u8]) -> bool {
+    data.starts_with(b"From ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_mbox, split_mbox};
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = b"From alice@example.com Thu Jan  1 00:00:00 2026\n\
+                     From: alice@example.com\n\
+                     Subject: one\n\
+                     \n\
+                     >From the start\n\
+                     \n\
+                     From bob@example.org Thu Jan  1 00:00:01 2026\n\
+                     From: bob@example.org\n\
+                     Subject: two\n\
+                     \n\
+                     body\n";
+        assert!(is_mbox(mbox));
+
+        let messages = split_mbox(mbox);
+        assert_eq!(messages.len(), 2);
+        let first = String::from_utf8(messages[0].clone()).unwrap();
+        assert!(first.starts_with("From: alice@example.com\n"));
+        assert!(first.contains("\nFrom the start\n"));
+        assert!(
+            String::from_utf8(messages[1].clone())
+                .unwrap()
+                .ends_with("body\n")
+        );
+    }
+}
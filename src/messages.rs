@@ -0,0 +1,692 @@
+use std::collections::BTreeMap;
+
+/// Language of human-readable result messages
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lang {
+    #[default]
+    En,
+    De,
+    Fr,
+}
+
+impl Lang {
+    /// Parses a language tag such as `de` or `fr-CH`, ignoring region and case
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match primary.as_str() {
+            "en" => Some(Lang::En),
+            "de" => Some(Lang::De),
+            "fr" => Some(Lang::Fr),
+            _ => None,
+        }
+    }
+
+    /// Picks the supported language with the highest weight in an `Accept-Language` header
+    pub fn from_accept_language(header: &str) -> Option<Self> {
+        let mut best: Option<(Lang, f32)> = None;
+        for entry in header.split(',') {
+            let mut parts = entry.split(';');
+            let Some(lang) = parts.next().and_then(Lang::from_tag) else {
+                continue;
+            };
+            let weight = parts
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            if weight > 0.0 && best.is_none_or(|(_, w)| weight > w) {
+                best = Some((lang, weight));
+            }
+        }
+        best.map(|(lang, _)| lang)
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Lang::En => EN,
+            Lang::De => DE,
+            Lang::Fr => FR,
+        }
+    }
+}
+
+const EN: &[(&str, &str)] = &[
+    (
+        "domain_invalid",
+        "The sender domain {domain} does not exist.",
+    ),
+    (
+        "no_authentication",
+        "{domain} publishes neither SPF nor DMARC and the message is not DKIM-signed.",
+    ),
+    ("dkim_missing", "The message carries no DKIM signature."),
+    (
+        "spf_not_strict",
+        "The SPF policy of {domain} does not reject unauthorized senders.",
+    ),
+    (
+        "dmarc_reject_misaligned",
+        "{domain} requests rejection (DMARC p=reject) of unaligned mail, and this message is not aligned.",
+    ),
+    (
+        "upstream_dmarc",
+        "{authserv_id} recorded DMARC result: {result}.",
+    ),
+    (
+        "lookalike",
+        "The sender domain {domain} imitates {protected_domain} ({kind}).",
+    ),
+    (
+        "lookalike_fresh_certificate",
+        "A certificate for {domain} was issued shortly before this message.",
+    ),
+    (
+        "url_lookalike",
+        "The link {url} leads to {domain}, which imitates {protected_domain}.",
+    ),
+    (
+        "recent_dns",
+        "The DNS records of {domain} were created or changed shortly before this message.",
+    ),
+    (
+        "unregistered_infrastructure",
+        "The message claims to be from {domain} but was not sent from its registered mail infrastructure (source: {source}).",
+    ),
+    (
+        "abused_registrar",
+        "{domain} is registered through {registrar}, a registrar frequently abused for phishing.",
+    ),
+    (
+        "abused_nameserver",
+        "{domain} uses name servers of a provider frequently abused for phishing: {nameservers}.",
+    ),
+    (
+        "blocklisted_sender",
+        "The sender matches the blocklist entry {entry}.",
+    ),
+    (
+        "allowlisted_sender",
+        "The sender matches the allowlist entry {entry}.",
+    ),
+    (
+        "vip_impersonation",
+        "The sender uses the name of {name} but writes from {domain}, outside the organization.",
+    ),
+    (
+        "received_after_delivery",
+        "The Received header of {hop} is dated {delta} after our server received the message, which no genuine relay can be.",
+    ),
+    (
+        "received_negative_delta",
+        "The message reached {hop} {delta} before the previous relay sent it; the Received headers may be forged.",
+    ),
+    (
+        "received_slow_transit",
+        "The message took {delta} to reach {hop}, longer than mail servers keep queued mail.",
+    ),
+    (
+        "dkim_stale_signature",
+        "The DKIM signature of {domain} was made {age} before the message arrived; it may be a replayed old message.",
+    ),
+    (
+        "dkim_unsigned_header",
+        "The DKIM signature of {domain} does not cover the {header} header, which can be changed without breaking it.",
+    ),
+    (
+        "dkim_added_header",
+        "The message has more {header} headers than the DKIM signature of {domain} covers; one was likely added after signing.",
+    ),
+    (
+        "dkim_body_length",
+        "The DKIM signature of {domain} covers only the first {length} bytes of the body (l=), so content may have been appended.",
+    ),
+    (
+        "dkim_appended_content",
+        "{unsigned_bytes} bytes of the body follow the {length} bytes signed by {domain} (l=, {canonicalization} canonicalization) and may have been appended after signing.",
+    ),
+    (
+        "helo_not_fqdn",
+        "The sending server introduced itself as {helo}, which is not a fully qualified domain name.",
+    ),
+    (
+        "helo_ip_mismatch",
+        "The sending server introduced itself as {helo}, but connected from {client_ip}.",
+    ),
+    (
+        "helo_nonexistent",
+        "The sending server introduced itself as {helo}, a domain that does not exist.",
+    ),
+    (
+        "session_plaintext",
+        "The message was received over an unencrypted connection.",
+    ),
+    (
+        "text_phrase",
+        "The text contains the {category} phrase \"{phrase}\".",
+    ),
+    (
+        "deadline_exceeded",
+        "The analysis stopped at its deadline; the verdict is based on incomplete evidence.",
+    ),
+    (
+        "malformed_message",
+        "The message is malformed in {count} place(s) ({detail}); the affected parts were skipped or read undecoded.",
+    ),
+    (
+        "dns_disagreement",
+        "Independent resolvers returned different records for {name} ({answers}); the analyzer's DNS may be poisoned, so the verdict is unreliable.",
+    ),
+    (
+        "envelope_forwarded",
+        "The message was forwarded by {forwarders}; the original envelope sender is {original}.",
+    ),
+    (
+        "envelope_misaligned",
+        "The envelope sender domain {envelope_domain} is unrelated to the From domain {domain}.",
+    ),
+    (
+        "envelope_esp",
+        "The envelope sender domain {envelope_domain} belongs to the email service provider {esp}, and the message is DKIM-signed by {domain}.",
+    ),
+    (
+        "mailer_fingerprint_changed",
+        "Mail from {domain} usually comes from {usual}, but this message was composed by {mailer} with an unfamiliar header layout.",
+    ),
+    (
+        "automated_message",
+        "The message was sent automatically ({kind}: {signals}); failed sender checks are expected for such mail.",
+    ),
+    (
+        "attachment_limit_exceeded",
+        "Inspecting the attachment {name} was stopped at a resource limit ({detail}); it may be a decompression bomb or built to exhaust scanners.",
+    ),
+    (
+        "archive_executable",
+        "The archive {name} contains files that run when opened: {files}.",
+    ),
+    (
+        "archive_encrypted",
+        "The archive {name} is password-protected, which keeps scanners from seeing its contents.",
+    ),
+    (
+        "archive_nested",
+        "The archive {name} contains further archives: {files}.",
+    ),
+    (
+        "office_macros",
+        "The Office document {name} contains macros, which can run code when the document is opened.",
+    ),
+    (
+        "office_external_reference",
+        "The Office document {name} loads content from outside the document when opened: {targets}.",
+    ),
+    (
+        "office_dde",
+        "The Office document {name} contains DDE commands, which can start programs: {commands}.",
+    ),
+    ("pdf_javascript", "The PDF {name} contains JavaScript."),
+    (
+        "pdf_open_action",
+        "The PDF {name} runs an action when it is opened.",
+    ),
+    (
+        "pdf_embedded_file",
+        "The PDF {name} carries {count} embedded file(s).",
+    ),
+    (
+        "html_attachment",
+        "The web page {name} is attached; opened from disk, it can show a fake sign-in form without any browser warning.",
+    ),
+    (
+        "html_smuggling",
+        "The web page {name} builds a file in the browser to download ({signals}), a way to smuggle malware past mail scanners.",
+    ),
+];
+
+const DE: &[(&str, &str)] = &[
+    (
+        "domain_invalid",
+        "Die Absenderdomain {domain} existiert nicht.",
+    ),
+    (
+        "no_authentication",
+        "{domain} veröffentlicht weder SPF noch DMARC, und die Nachricht ist nicht DKIM-signiert.",
+    ),
+    ("dkim_missing", "Die Nachricht trägt keine DKIM-Signatur."),
+    (
+        "spf_not_strict",
+        "Die SPF-Richtlinie von {domain} weist nicht autorisierte Absender nicht ab.",
+    ),
+    (
+        "dmarc_reject_misaligned",
+        "{domain} verlangt die Ablehnung (DMARC p=reject) nicht ausgerichteter Mails, und diese Nachricht ist nicht ausgerichtet.",
+    ),
+    (
+        "upstream_dmarc",
+        "{authserv_id} hat das DMARC-Ergebnis {result} vermerkt.",
+    ),
+    (
+        "lookalike",
+        "Die Absenderdomain {domain} imitiert {protected_domain} ({kind}).",
+    ),
+    (
+        "lookalike_fresh_certificate",
+        "Für {domain} wurde kurz vor dieser Nachricht ein Zertifikat ausgestellt.",
+    ),
+    (
+        "url_lookalike",
+        "Der Link {url} führt zu {domain}, das {protected_domain} imitiert.",
+    ),
+    (
+        "recent_dns",
+        "Die DNS-Einträge von {domain} wurden kurz vor dieser Nachricht angelegt oder geändert.",
+    ),
+    (
+        "unregistered_infrastructure",
+        "Die Nachricht gibt vor, von {domain} zu stammen, wurde aber nicht über deren registrierte Mail-Infrastruktur versendet (Quelle: {source}).",
+    ),
+    (
+        "abused_registrar",
+        "{domain} ist über {registrar} registriert, einen häufig für Phishing missbrauchten Registrar.",
+    ),
+    (
+        "abused_nameserver",
+        "{domain} nutzt Nameserver eines häufig für Phishing missbrauchten Anbieters: {nameservers}.",
+    ),
+    (
+        "blocklisted_sender",
+        "Der Absender steht auf der Sperrliste ({entry}).",
+    ),
+    (
+        "allowlisted_sender",
+        "Der Absender steht auf der Zulassungsliste ({entry}).",
+    ),
+    (
+        "vip_impersonation",
+        "Der Absender verwendet den Namen {name}, schreibt aber von {domain} außerhalb der Organisation.",
+    ),
+    (
+        "received_after_delivery",
+        "Der Received-Header von {hop} ist {delta} nach dem Empfang durch unseren Server datiert, was bei einem echten Relay unmöglich ist.",
+    ),
+    (
+        "received_negative_delta",
+        "Die Nachricht erreichte {hop} {delta} bevor das vorherige Relay sie versandte; die Received-Header sind möglicherweise gefälscht.",
+    ),
+    (
+        "received_slow_transit",
+        "Die Nachricht brauchte {delta} bis {hop}, länger als Mailserver Nachrichten in der Warteschlange halten.",
+    ),
+    (
+        "dkim_stale_signature",
+        "Die DKIM-Signatur von {domain} wurde {age} vor dem Eingang der Nachricht erstellt; es kann sich um eine wiederverwendete alte Nachricht handeln.",
+    ),
+    (
+        "dkim_unsigned_header",
+        "Die DKIM-Signatur von {domain} deckt den Header {header} nicht ab; er kann geändert werden, ohne sie zu brechen.",
+    ),
+    (
+        "dkim_added_header",
+        "Die Nachricht enthält mehr {header}-Header, als die DKIM-Signatur von {domain} abdeckt; einer wurde vermutlich nach dem Signieren hinzugefügt.",
+    ),
+    (
+        "dkim_body_length",
+        "Die DKIM-Signatur von {domain} deckt nur die ersten {length} Bytes des Inhalts ab (l=), sodass Inhalte angehängt worden sein können.",
+    ),
+    (
+        "dkim_appended_content",
+        "Auf die {length} von {domain} signierten Bytes folgen {unsigned_bytes} weitere Bytes (l=, Kanonisierung {canonicalization}), die nach dem Signieren angehängt worden sein können.",
+    ),
+    (
+        "helo_not_fqdn",
+        "Der sendende Server meldete sich als {helo}, was kein vollqualifizierter Domainname ist.",
+    ),
+    (
+        "helo_ip_mismatch",
+        "Der sendende Server meldete sich als {helo}, verband sich aber von {client_ip}.",
+    ),
+    (
+        "helo_nonexistent",
+        "Der sendende Server meldete sich als {helo}, eine Domain, die nicht existiert.",
+    ),
+    (
+        "session_plaintext",
+        "Die Nachricht wurde über eine unverschlüsselte Verbindung empfangen.",
+    ),
+    (
+        "text_phrase",
+        "Der Text enthält die Formulierung „{phrase}“ ({category}).",
+    ),
+    (
+        "deadline_exceeded",
+        "Die Analyse wurde bei Fristablauf abgebrochen; das Ergebnis beruht auf unvollständigen Belegen.",
+    ),
+    (
+        "malformed_message",
+        "Die Nachricht ist an {count} Stelle(n) fehlerhaft ({detail}); die betroffenen Teile wurden übersprungen oder undekodiert gelesen.",
+    ),
+    (
+        "dns_disagreement",
+        "Unabhängige Resolver lieferten unterschiedliche Einträge für {name} ({answers}); das DNS des Analysators könnte manipuliert sein, das Ergebnis ist daher unzuverlässig.",
+    ),
+    (
+        "envelope_forwarded",
+        "Die Nachricht wurde von {forwarders} weitergeleitet; der ursprüngliche Envelope-Absender ist {original}.",
+    ),
+    (
+        "envelope_misaligned",
+        "Die Envelope-Absenderdomain {envelope_domain} gehört nicht zur From-Domain {domain}.",
+    ),
+    (
+        "envelope_esp",
+        "Die Envelope-Absenderdomain {envelope_domain} gehört zum E-Mail-Dienstleister {esp}, und die Nachricht ist von {domain} DKIM-signiert.",
+    ),
+    (
+        "mailer_fingerprint_changed",
+        "Mails von {domain} stammen sonst von {usual}, diese Nachricht wurde jedoch von {mailer} mit ungewohntem Header-Aufbau erstellt.",
+    ),
+    (
+        "automated_message",
+        "Die Nachricht wurde automatisch versendet ({kind}: {signals}); fehlgeschlagene Absenderprüfungen sind bei solchen Mails zu erwarten.",
+    ),
+    (
+        "attachment_limit_exceeded",
+        "Die Prüfung des Anhangs {name} wurde an einer Ressourcengrenze abgebrochen ({detail}); es könnte sich um eine Dekompressionsbombe oder eine gegen Scanner gerichtete Datei handeln.",
+    ),
+    (
+        "archive_executable",
+        "Das Archiv {name} enthält ausführbare Dateien: {files}.",
+    ),
+    (
+        "archive_encrypted",
+        "Das Archiv {name} ist passwortgeschützt, sodass Scanner seinen Inhalt nicht prüfen können.",
+    ),
+    (
+        "archive_nested",
+        "Das Archiv {name} enthält weitere Archive: {files}.",
+    ),
+    (
+        "office_macros",
+        "Das Office-Dokument {name} enthält Makros, die beim Öffnen Code ausführen können.",
+    ),
+    (
+        "office_external_reference",
+        "Das Office-Dokument {name} lädt beim Öffnen externe Inhalte: {targets}.",
+    ),
+    (
+        "office_dde",
+        "Das Office-Dokument {name} enthält DDE-Befehle, die Programme starten können: {commands}.",
+    ),
+    ("pdf_javascript", "Das PDF {name} enthält JavaScript."),
+    (
+        "pdf_open_action",
+        "Das PDF {name} führt beim Öffnen eine Aktion aus.",
+    ),
+    (
+        "pdf_embedded_file",
+        "Das PDF {name} enthält {count} eingebettete Datei(en).",
+    ),
+    (
+        "html_attachment",
+        "Die Webseite {name} ist angehängt; lokal geöffnet kann sie ein gefälschtes Anmeldeformular ohne Browserwarnung zeigen.",
+    ),
+    (
+        "html_smuggling",
+        "Die Webseite {name} erzeugt im Browser eine Datei zum Herunterladen ({signals}), eine Methode, Schadsoftware an Mail-Scannern vorbeizuschleusen.",
+    ),
+];
+
+const FR: &[(&str, &str)] = &[
+    (
+        "domain_invalid",
+        "Le domaine expéditeur {domain} n'existe pas.",
+    ),
+    (
+        "no_authentication",
+        "{domain} ne publie ni SPF ni DMARC et le message n'est pas signé DKIM.",
+    ),
+    (
+        "dkim_missing",
+        "Le message ne comporte aucune signature DKIM.",
+    ),
+    (
+        "spf_not_strict",
+        "La politique SPF de {domain} ne rejette pas les expéditeurs non autorisés.",
+    ),
+    (
+        "dmarc_reject_misaligned",
+        "{domain} demande le rejet (DMARC p=reject) des messages non alignés, et ce message n'est pas aligné.",
+    ),
+    (
+        "upstream_dmarc",
+        "{authserv_id} a enregistré le résultat DMARC : {result}.",
+    ),
+    (
+        "lookalike",
+        "Le domaine expéditeur {domain} imite {protected_domain} ({kind}).",
+    ),
+    (
+        "lookalike_fresh_certificate",
+        "Un certificat pour {domain} a été émis peu avant ce message.",
+    ),
+    (
+        "url_lookalike",
+        "Le lien {url} mène à {domain}, qui imite {protected_domain}.",
+    ),
+    (
+        "recent_dns",
+        "Les enregistrements DNS de {domain} ont été créés ou modifiés peu avant ce message.",
+    ),
+    (
+        "unregistered_infrastructure",
+        "Le message prétend venir de {domain} mais n'a pas été envoyé depuis son infrastructure de messagerie enregistrée (source : {source}).",
+    ),
+    (
+        "abused_registrar",
+        "{domain} est enregistré auprès de {registrar}, un registraire fréquemment utilisé pour le phishing.",
+    ),
+    (
+        "abused_nameserver",
+        "{domain} utilise les serveurs de noms d'un fournisseur fréquemment utilisé pour le phishing : {nameservers}.",
+    ),
+    (
+        "blocklisted_sender",
+        "L'expéditeur figure sur la liste de blocage ({entry}).",
+    ),
+    (
+        "allowlisted_sender",
+        "L'expéditeur figure sur la liste d'autorisation ({entry}).",
+    ),
+    (
+        "vip_impersonation",
+        "L'expéditeur utilise le nom de {name} mais écrit depuis {domain}, hors de l'organisation.",
+    ),
+    (
+        "received_after_delivery",
+        "L'en-tête Received de {hop} est daté de {delta} après la réception du message par notre serveur, ce qui est impossible pour un relais authentique.",
+    ),
+    (
+        "received_negative_delta",
+        "Le message a atteint {hop} {delta} avant que le relais précédent ne l'envoie ; les en-têtes Received sont peut-être falsifiés.",
+    ),
+    (
+        "received_slow_transit",
+        "Le message a mis {delta} pour atteindre {hop}, plus longtemps que les serveurs ne gardent un message en file d'attente.",
+    ),
+    (
+        "dkim_stale_signature",
+        "La signature DKIM de {domain} a été créée {age} avant l'arrivée du message ; il peut s'agir d'un ancien message rejoué.",
+    ),
+    (
+        "dkim_unsigned_header",
+        "La signature DKIM de {domain} ne couvre pas l'en-tête {header}, qui peut être modifié sans l'invalider.",
+    ),
+    (
+        "dkim_added_header",
+        "Le message contient plus d'en-têtes {header} que la signature DKIM de {domain} n'en couvre ; l'un d'eux a probablement été ajouté après la signature.",
+    ),
+    (
+        "dkim_body_length",
+        "La signature DKIM de {domain} ne couvre que les {length} premiers octets du corps (l=), du contenu a donc pu être ajouté.",
+    ),
+    (
+        "dkim_appended_content",
+        "{unsigned_bytes} octets du corps suivent les {length} octets signés par {domain} (l=, canonicalisation {canonicalization}) et ont pu être ajoutés après la signature.",
+    ),
+    (
+        "helo_not_fqdn",
+        "Le serveur expéditeur s'est présenté comme {helo}, qui n'est pas un nom de domaine complet.",
+    ),
+    (
+        "helo_ip_mismatch",
+        "Le serveur expéditeur s'est présenté comme {helo}, mais s'est connecté depuis {client_ip}.",
+    ),
+    (
+        "helo_nonexistent",
+        "Le serveur expéditeur s'est présenté comme {helo}, un domaine qui n'existe pas.",
+    ),
+    (
+        "session_plaintext",
+        "Le message a été reçu par une connexion non chiffrée.",
+    ),
+    (
+        "text_phrase",
+        "Le texte contient l'expression « {phrase} » ({category}).",
+    ),
+    (
+        "deadline_exceeded",
+        "L'analyse s'est arrêtée à son échéance ; le verdict repose sur des éléments incomplets.",
+    ),
+    (
+        "malformed_message",
+        "Le message est mal formé à {count} endroit(s) ({detail}) ; les parties concernées ont été ignorées ou lues sans décodage.",
+    ),
+    (
+        "dns_disagreement",
+        "Des résolveurs indépendants ont renvoyé des enregistrements différents pour {name} ({answers}) ; le DNS de l'analyseur est peut-être empoisonné, le verdict n'est donc pas fiable.",
+    ),
+    (
+        "envelope_forwarded",
+        "Le message a été transféré par {forwarders} ; l'expéditeur d'enveloppe d'origine est {original}.",
+    ),
+    (
+        "envelope_misaligned",
+        "Le domaine de l'expéditeur d'enveloppe {envelope_domain} n'a aucun lien avec le domaine From {domain}.",
+    ),
+    (
+        "envelope_esp",
+        "Le domaine de l'expéditeur d'enveloppe {envelope_domain} appartient au prestataire d'envoi {esp}, et le message est signé DKIM par {domain}.",
+    ),
+    (
+        "mailer_fingerprint_changed",
+        "Les messages de {domain} proviennent habituellement de {usual}, mais celui-ci a été composé par {mailer} avec une structure d'en-têtes inhabituelle.",
+    ),
+    (
+        "automated_message",
+        "Le message a été envoyé automatiquement ({kind} : {signals}) ; l'échec des vérifications de l'expéditeur est attendu pour ce type de message.",
+    ),
+    (
+        "attachment_limit_exceeded",
+        "L'inspection de la pièce jointe {name} a été interrompue à une limite de ressources ({detail}) ; il peut s'agir d'une bombe de décompression ou d'un fichier conçu pour épuiser les analyseurs.",
+    ),
+    (
+        "archive_executable",
+        "L'archive {name} contient des fichiers qui s'exécutent à l'ouverture : {files}.",
+    ),
+    (
+        "archive_encrypted",
+        "L'archive {name} est protégée par un mot de passe, ce qui empêche les analyseurs d'en voir le contenu.",
+    ),
+    (
+        "archive_nested",
+        "L'archive {name} contient d'autres archives : {files}.",
+    ),
+    (
+        "office_macros",
+        "Le document Office {name} contient des macros, qui peuvent exécuter du code à l'ouverture.",
+    ),
+    (
+        "office_external_reference",
+        "Le document Office {name} charge à l'ouverture du contenu extérieur au document : {targets}.",
+    ),
+    (
+        "office_dde",
+        "Le document Office {name} contient des commandes DDE, qui peuvent lancer des programmes : {commands}.",
+    ),
+    ("pdf_javascript", "Le PDF {name} contient du JavaScript."),
+    (
+        "pdf_open_action",
+        "Le PDF {name} exécute une action à son ouverture.",
+    ),
+    (
+        "pdf_embedded_file",
+        "Le PDF {name} contient {count} fichier(s) incorporé(s).",
+    ),
+    (
+        "html_attachment",
+        "La page web {name} est jointe ; ouverte depuis le disque, elle peut afficher un faux formulaire de connexion sans avertissement du navigateur.",
+    ),
+    (
+        "html_smuggling",
+        "La page web {name} fabrique dans le navigateur un fichier à télécharger ({signals}), une technique pour faire passer un logiciel malveillant à travers les analyseurs de messagerie.",
+    ),
+];
+
+/// Renders the message for `key` in `lang`, substituting `{name}` placeholders from `args`
+///
+/// Falls back to English for keys missing from a catalog, and to the key itself.
+pub fn render(lang: Lang, key: &str, args: &BTreeMap<String, String>) -> String {
+    let lookup = |lang: Lang| {
+        lang.catalog()
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, t)| *t)
+    };
+    let mut message = lookup(lang)
+        .or_else(|| lookup(Lang::En))
+        .unwrap_or(key)
+        .to_string();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), value);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DE, EN, FR, Lang, render};
+    use crate::rules::rule_id;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_catalogs_are_complete() {
+        for catalog in [DE, FR] {
+            for (key, _) in EN {
+                assert!(catalog.iter().any(|(k, _)| k == key), "missing {}", key);
+            }
+        }
+        for (key, _) in EN {
+            assert!(rule_id(key).is_some(), "no rule ID for {}", key);
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let args = BTreeMap::from([("domain".to_string(), "evil.test".to_string())]);
+        assert_eq!(
+            render(Lang::De, "domain_invalid", &args),
+            "Die Absenderdomain evil.test existiert nicht."
+        );
+        assert_eq!(render(Lang::Fr, "unknown_key", &args), "unknown_key");
+    }
+
+    #[test]
+    fn test_accept_language() {
+        assert_eq!(
+            Lang::from_accept_language("it-IT, fr-CH;q=0.9, de;q=0.95, en;q=0.5"),
+            Some(Lang::De)
+        );
+        assert_eq!(Lang::from_accept_language("es, it;q=0.8"), None);
+        assert_eq!(Lang::from_tag("FR_be"), Some(Lang::Fr));
+    }
+}
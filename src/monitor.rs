@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use crate::{DnsResolver, dns::ResolverTrait, domain_verdict::resolve_dkim_selectors};
+
+/// Mail-related DNS records of a domain at one point in time
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DomainSnapshot {
+    pub spf: Option<String>,
+    pub dmarc: Option<String>,
+    /// Common DKIM selectors that publish a key
+    pub dkim_selectors: Vec<String>,
+    /// MX host names, sorted
+    pub mx: Vec<String>,
+    /// Unix timestamp of the check
+    pub checked_at: i64,
+}
+
+/// Snapshots of every monitored domain, persisted between checks
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MonitorState {
+    pub domains: BTreeMap<String, DomainSnapshot>,
+}
+
+impl MonitorState {
+    /// Loads the state file, starting empty when it does not exist yet
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// One record that differs from the previous snapshot
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RecordChange {
+    pub domain: String,
+    /// `spf`, `dmarc`, `dkim_selectors`, or `mx`
+    pub record: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+/// Resolves the current mail-related records of `domain`
+pub async fn snapshot_domain(resolver: &DnsResolver, domain: &str) -> DomainSnapshot {
+    let (spf, dmarc, dkim_selectors, mx) = futures::join!(
+        resolver.resolve_spf(domain),
+        resolver.resolve_dmarc(domain),
+        resolve_dkim_selectors(resolver, domain),
+        resolver.mx_hosts(domain),
+    );
+    DomainSnapshot {
+        spf,
+        dmarc,
+        dkim_selectors,
+        mx,
+        checked_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default(),
+    }
+}
+
+/// Lists the records that differ between two snapshots of `domain`
+pub fn changes(domain: &str, before: &DomainSnapshot, after: &DomainSnapshot) -> Vec<RecordChange> {
+    let text = |record: &Option<String>| record.clone().unwrap_or_else(|| "(none)".to_string());
+    let list = |records: &[String]| {
+        if records.is_empty() {
+            "(none)".to_string()
+        } else {
+            records.join(", ")
+        }
+    };
+
+    [
+        ("spf", text(&before.spf), text(&after.spf)),
+        ("dmarc", text(&before.dmarc), text(&after.dmarc)),
+        (
+            "dkim_selectors",
+            list(&before.dkim_selectors),
+            list(&after.dkim_selectors),
+        ),
+        ("mx", list(&before.mx), list(&after.mx)),
+    ]
+    .into_iter()
+    .filter(|(_, old, new)| old != new)
+    .map(|(record, before, after)| RecordChange {
+        domain: domain.to_string(),
+        record,
+        before,
+        after,
+    })
+    .collect()
+}
+
+/// Re-checks `domains`, updates `state`, and returns the changes since the previous check
+///
+/// Domains seen for the first time are recorded without reporting changes.
+pub async fn check_domains(
+    resolver: &DnsResolver,
+    domains: &[String],
+    state: &mut MonitorState,
+) -> Vec<RecordChange> {
+    let mut found = Vec::new();
+    for domain in domains {
+        let current = snapshot_domain(resolver, domain).await;
+        if let Some(previous) = state.domains.get(domain) {
+            found.extend(changes(domain, previous, &current));
+        }
+        state.domains.insert(domain.clone(), current);
+    }
+    found
+}
+
+/// Human-readable summary of a set of changes
+pub fn describe(changes: &[RecordChange]) -> String {
+    changes
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {} changed: {} -> {}",
+                c.domain, c.record, c.before, c.after
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Posts changes to a webhook
+///
+/// The payload carries a Slack-compatible `text` summary plus the structured `changes`.
+pub async fn send_alert(
+    client: &reqwest::Client,
+    webhook_url: &str,
+    changes: &[RecordChange],
+) -> anyhow::Result<()> {
+    let payload = serde_json::json!({
+        "text": format!("DNS changes detected:\n{}", describe(changes)),
+        "changes": changes,
+    });
+    client
+        .post(webhook_url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(payload.to_string())
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DomainSnapshot, changes};
+
+    #[test]
+    fn test_snapshot_changes() {
+        let before = DomainSnapshot {
+            spf: Some("v=spf1 include:_spf.example.net -all".to_string()),
+            dmarc: Some("v=DMARC1; p=reject".to_string()),
+            dkim_selectors: vec!["selector1".to_string()],
+            mx: vec!["mx1.example.com".to_string()],
+            checked_at: 1,
+        };
+        let after = DomainSnapshot {
+            spf: Some("v=spf1 +all".to_string()),
+            mx: vec!["mx.attacker.test".to_string()],
+            checked_at: 2,
+            ..before.clone()
+        };
+
+        let found = changes("example.com", &before, &after);
+        let records: Vec<_> = found.iter().map(|c| c.record).collect();
+        assert_eq!(records, vec!["spf", "mx"]);
+        assert_eq!(found[1].after, "mx.attacker.test");
+
+        assert!(changes("example.com", &before, &before).is_empty());
+    }
+}
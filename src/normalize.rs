@@ -0,0 +1,259 @@
+use mailparse::parse_headers;
+
+/// Rewrites a message in a canonical form for hashing, deduplication, and diffing
+///
+/// Header values are unfolded, their encoded-words decoded, and runs of whitespace
+/// collapsed. Line endings become `\n`, and trailing blank lines of the body are
+/// dropped. With `cut_at`, the trace added after the message reached that host is
+/// removed: every header above and including the topmost `Received` header naming it
+/// in its `by` clause. The body is otherwise kept byte for byte, still encoded.
+pub fn normalize(raw: &[u8], cut_at: Option<&str>) -> anyhow::Result<Vec<u8>> {
+    let (headers, body_offset) = parse_headers(raw)?;
+
+    let cut = cut_at
+        .and_then(|host| {
+            headers.iter().position(|header| {
+                header.get_key().eq_ignore_ascii_case("Received")
+                    && received_by(&header.get_value())
+                        .is_some_and(|by| by.eq_ignore_ascii_case(host))
+            })
+        })
+        .map_or(0, |position| position + 1);
+
+    let mut out = Vec::with_capacity(raw.len());
+    for header in &headers[cut..] {
+        let value = header.get_value();
+        let value: Vec<&str> = value.split_whitespace().collect();
+        out.extend_from_slice(format!("{}: {}\n", header.get_key(), value.join(" ")).as_bytes());
+    }
+    out.push(b'\n');
+
+    let body = raw.get(body_offset..).unwrap_or_default();
+    let mut lines: Vec<&[u8]> = body
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    for line in lines {
+        out.extend_from_slice(line);
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+/// Field labels localized clients show instead of the header names, e.g. in Outlook's
+/// header pane or a forwarded message, by lowercase label
+const LOCALIZED_LABELS: &[(&str, &str)] = &[
+    ("sent", "Date"),
+    ("von", "From"),
+    ("an", "To"),
+    ("betreff", "Subject"),
+    ("datum", "Date"),
+    ("gesendet", "Date"),
+    ("antwort an", "Reply-To"),
+    ("de", "From"),
+    ("à", "To"),
+    ("objet", "Subject"),
+    ("envoyé", "Date"),
+    ("répondre à", "Reply-To"),
+    ("para", "To"),
+    ("asunto", "Subject"),
+    ("fecha", "Date"),
+    ("enviado", "Date"),
+    ("responder a", "Reply-To"),
+    ("da", "From"),
+    ("a", "To"),
+    ("oggetto", "Subject"),
+    ("data", "Date"),
+    ("inviato", "Date"),
+    ("rispondi a", "Reply-To"),
+    ("van", "From"),
+    ("aan", "To"),
+    ("onderwerp", "Subject"),
+    ("verzonden", "Date"),
+    ("antwoord aan", "Reply-To"),
+];
+
+/// Headers recognized in the two-column `Name<TAB>Value` tables of header analyzers
+const TABLE_HEADERS: &[&str] = &[
+    "arc-authentication-results",
+    "arc-message-signature",
+    "arc-seal",
+    "authentication-results",
+    "cc",
+    "date",
+    "dkim-signature",
+    "from",
+    "message-id",
+    "received",
+    "received-spf",
+    "reply-to",
+    "return-path",
+    "subject",
+    "to",
+];
+
+/// Repairs a header block pasted from a mail client ("Show original", "View headers")
+/// into a message without a body
+///
+/// Copies lose structure in predictable ways: the whole block indented, bare LF line
+/// endings, long lines wrapped without the whitespace that marks a continuation, blank
+/// lines inserted between fields, and a summary above the headers, like Gmail's
+/// "Message ID" / "Created at" table. Outlook shows localized labels (`Von:`,
+/// `De :`) and header analyzers a `Name<TAB>Value` table; both are mapped back to
+/// header fields. Lines before the first header are dropped, lines not starting a
+/// header continue the previous one, and the block ends at a blank line followed by
+/// neither another header nor an indented continuation.
+pub fn header_block(pasted: &str) -> String {
+    let lines: Vec<&str> = pasted.lines().collect();
+    // Indentation shared by every line, e.g. from a quoted paste
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or_default();
+    let lines: Vec<String> = lines
+        .iter()
+        .map(|line| relabel(line.get(indent..).unwrap_or(line.trim_start()).trim_end()))
+        .collect();
+    let mut lines = lines.iter().map(String::as_str).peekable();
+
+    let mut block = String::new();
+    while let Some(line) = lines.next() {
+        if line.is_empty() {
+            let continues = |next: &&str| starts_header(next) || next.starts_with([' ', '\t']);
+            if !block.is_empty() && !lines.peek().is_some_and(continues) {
+                break;
+            }
+            continue;
+        }
+        match (starts_header(line), block.is_empty()) {
+            (true, true) => {}
+            (true, false) => block.push_str("\r\n"),
+            // Summary text above the headers
+            (false, true) => continue,
+            (false, false) if line.starts_with([' ', '\t']) => block.push_str("\r\n"),
+            (false, false) => block.push_str("\r\n "),
+        }
+        block.push_str(line);
+    }
+    block.push_str("\r\n\r\n");
+    block
+}
+
+/// Rewrites a localized label or a header table row as a header field
+fn relabel(line: &str) -> String {
+    if let Some((name, value)) = line.split_once('\t')
+        && TABLE_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+    {
+        return format!("{}: {}", name, value.trim_start());
+    }
+    if let Some((label, value)) = line.split_once(':')
+        && let Some(name) = localized_header(label)
+        && !line.starts_with([' ', '\t'])
+    {
+        return format!("{}:{}", name, value);
+    }
+    line.to_string()
+}
+
+/// The header a localized label stands for, e.g. `From` for `Von`
+pub(crate) fn localized_header(label: &str) -> Option<&'static str> {
+    let label = label.trim_end().to_lowercase();
+    LOCALIZED_LABELS
+        .iter()
+        .find(|(localized, _)| label == *localized)
+        .map(|(_, name)| *name)
+}
+
+/// Whether `line` starts a header field: a name without whitespace, then a colon
+fn starts_header(line: &str) -> bool {
+    line.split_once(':')
+        .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_graphic()))
+}
+
+/// Host of the `by` clause of a `Received` header
+fn received_by(received: &str) -> Option<&str> {
+    let mut tokens = received.split_whitespace();
+    tokens.find(|token| token.eq_ignore_ascii_case("by"))?;
+    tokens.next().map(|host| host.trim_end_matches(';'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header_block, normalize};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_normalize_is_stable_across_transport() {
+        let delivered =
+            b"Received: from mx.corp.example by mailbox.corp.example; Mon, 1 Jan 2024\r\n\
+            X-Spam-Status: No\r\n\
+            Received: from relay.evil.example by MX.corp.example; Mon, 1 Jan 2024\r\n\
+            From: =?UTF-8?Q?Caf=C3=A9?= <ceo@evil.example>\r\n\
+            Subject: Urgent\r\n  wire transfer\r\n\
+            \r\n\
+            Pay now.\r\n\r\n";
+        let sent = b"From: =?UTF-8?Q?Caf=C3=A9?= <ceo@evil.example>\n\
+            Subject: Urgent wire transfer\n\
+            \n\
+            Pay now.\n";
+
+        let canonical = normalize(delivered, Some("mx.corp.example")).unwrap();
+        assert_eq!(
+            String::from_utf8(canonical.clone()).unwrap(),
+            "From: Café <ceo@evil.example>\nSubject: Urgent wire transfer\n\nPay now.\n"
+        );
+        assert_eq!(canonical, normalize(sent, None).unwrap());
+
+        // Without a cut point, or an unknown one, the trace is kept
+        let kept = normalize(delivered, Some("other.example")).unwrap();
+        assert!(kept.starts_with(b"Received: from mx.corp.example by mailbox.corp.example;"));
+    }
+
+    #[test]
+    fn test_header_block_repairs_pasted_headers() {
+        let pasted = "    Message ID\t<1@evil.example>\n    \
+                      Created at:\tMon, Jan 1, 2024\n\n    \
+                      Received: from relay.evil.example\n        \
+                      by mx.corp.example; Mon, 1 Jan 2024 10:00:00 +0000\n    \
+                      DKIM-Signature: v=1; a=rsa-sha256; d=evil.example; s=s1; b=AAAA\n    \
+                      BBBB\n\n    \
+                      From: CEO <ceo@evil.example>\n\n    \
+                      Body text: not a header\n";
+        assert_eq!(
+            header_block(pasted),
+            "Received: from relay.evil.example\r\n    by mx.corp.example; \
+             Mon, 1 Jan 2024 10:00:00 +0000\r\n\
+             DKIM-Signature: v=1; a=rsa-sha256; d=evil.example; s=s1; b=AAAA\r\n BBBB\r\n\
+             From: CEO <ceo@evil.example>\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_header_block_accepts_client_formats() {
+        // Outlook with a French locale, blank lines between fields, and a fold whose
+        // continuation follows a blank line
+        let outlook = "De : Direction <ceo@evil.example>\n\n\
+                       Objet : Virement urgent\n\n\
+                       Received: from relay.evil.example\n\n\
+                       \tby mx.corp.example; Mon, 1 Jan 2024 10:00:00 +0000\n";
+        let parsed = parse_email(header_block(outlook).as_bytes()).unwrap();
+        assert_eq!(parsed.from.as_deref(), Some("Direction <ceo@evil.example>"));
+        assert_eq!(parsed.subject.as_deref(), Some("Virement urgent"));
+        assert_eq!(
+            parsed.received,
+            vec!["from relay.evil.example by mx.corp.example; Mon, 1 Jan 2024 10:00:00 +0000"]
+        );
+
+        // A header analyzer's two-column table
+        let table =
+            "Received\tfrom relay.evil.example by mx.corp.example\nFrom\tceo@evil.example\n";
+        let parsed = parse_email(header_block(table).as_bytes()).unwrap();
+        assert_eq!(parsed.from.as_deref(), Some("ceo@evil.example"));
+        assert_eq!(parsed.received.len(), 1);
+    }
+}
@@ -0,0 +1,379 @@
+use std::io::Read;
+
+use aho_corasick::AhoCorasick;
+use flate2::read::DeflateDecoder;
+
+use crate::archive::{ZipMember, zip_members};
+
+/// Signature of OLE compound files: `.doc`, `.xls`, `.ppt`
+const OLE_SIGNATURE: &[u8] = &[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+
+/// Characters of a DDE command kept, from the field's start
+const MAX_DDE_LEN: usize = 120;
+
+/// The container of an Office document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OfficeFormat {
+    /// Office Open XML: `.docx`, `.docm`, `.xlsx`, `.pptx`, ...
+    Ooxml,
+    /// OLE compound file: `.doc`, `.xls`, `.ppt`
+    Ole,
+}
+
+/// A relationship pointing outside the document, fetched when it is opened
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExternalReference {
+    /// The relationship type, e.g. `attachedTemplate`, `oleObject`, `frame`
+    pub kind: String,
+    pub target: String,
+}
+
+/// Structural signs of active content in an Office attachment
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct OfficeIndicators {
+    pub format: OfficeFormat,
+    /// Whether the document carries a VBA project
+    pub macros: bool,
+    /// External relationships other than hyperlinks; OOXML only
+    pub external_references: Vec<ExternalReference>,
+    /// DDE fields and links, e.g. `DDEAUTO c:\windows\system32\cmd.exe "/k calc"`
+    pub dde: Vec<String>,
+}
+
+/// Looks for macros, external references, and DDE in an Office document; `None` for
+/// other data
+///
+/// Nothing is executed or fully parsed. OOXML relationship and document parts are
+/// decompressed, at most `max_decompressed` bytes in all; OLE files are scanned for
+/// the names of VBA streams and for `DDEAUTO` fields.
+pub fn inspect_office(data: &[u8], max_decompressed: u64) -> Option<OfficeIndicators> {
+    if data.starts_with(OLE_SIGNATURE) {
+        return Some(inspect_ole(data));
+    }
+    if !data.starts_with(b"PK\x03\x04") {
+        return None;
+    }
+    let members = zip_members(data);
+    if !members.iter().any(|m| m.name == "[Content_Types].xml") {
+        return None;
+    }
+
+    let mut indicators = OfficeIndicators {
+        format: OfficeFormat::Ooxml,
+        macros: members
+            .iter()
+            .any(|m| m.name.to_ascii_lowercase().ends_with("vbaproject.bin")),
+        external_references: Vec::new(),
+        dde: Vec::new(),
+    };
+    let mut budget = max_decompressed;
+    for member in &members {
+        let name = member.name.to_ascii_lowercase();
+        let is_rels = name.ends_with(".rels");
+        let is_field_part = name.starts_with("word/") && name.ends_with(".xml");
+        let is_external_link = name.starts_with("xl/externallinks/") && name.ends_with(".xml");
+        if !(is_rels || is_field_part || is_external_link) {
+            continue;
+        }
+        let Some(xml) = read_member(data, member, &mut budget) else {
+            continue;
+        };
+        if is_rels {
+            indicators
+                .external_references
+                .extend(external_relationships(&xml));
+        } else if is_field_part {
+            indicators.dde.extend(dde_fields(&xml));
+        } else {
+            indicators.dde.extend(dde_links(&xml));
+        }
+    }
+    Some(indicators)
+}
+
+/// Decompresses a zip member as text, charging it to `budget`; `None` once the budget
+/// is spent or for encrypted members and unknown compression methods
+fn read_member(data: &[u8], member: &ZipMember, budget: &mut u64) -> Option<String> {
+    if member.encrypted || *budget == 0 {
+        return None;
+    }
+    let compressed = data.get(member.data_offset..)?;
+    let compressed = &compressed[..compressed.len().min(member.compressed_size as usize)];
+    let mut out = Vec::new();
+    let read = match member.method {
+        0 => compressed.take(*budget).read_to_end(&mut out),
+        8 => DeflateDecoder::new(compressed)
+            .take(*budget)
+            .read_to_end(&mut out),
+        _ => return None,
+    };
+    *budget -= out.len() as u64;
+    // Corrupt data ends the member; what was read so far is still scanned
+    if read.is_err() && out.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// The `<tag ...>` elements of `xml`, without their content
+fn elements<'a>(xml: &'a str, tag: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    xml.match_indices(tag).filter_map(move |(at, _)| {
+        let rest = &xml[at + tag.len()..];
+        if !rest.starts_with([' ', '>', '/', '\t', '\r', '\n']) {
+            return None;
+        }
+        rest.find('>').map(|end| &rest[..end])
+    })
+}
+
+/// The value of attribute `name` in the attributes of an element
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut from = 0;
+    while let Some(at) = attributes[from..].find(name) {
+        let start = from + at;
+        from = start + name.len();
+        let preceded = attributes[..start].ends_with([' ', '\t', '\r', '\n', ':']);
+        let rest = &attributes[from..];
+        if let (true, Some(rest)) = (preceded, rest.strip_prefix('=')) {
+            let quote = rest.chars().next()?;
+            let value = rest[1..].split(quote).next()?;
+            return Some(unescape(value));
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// External relationships of a `.rels` part, hyperlinks aside
+fn external_relationships(xml: &str) -> Vec<ExternalReference> {
+    elements(xml, "<Relationship")
+        .filter(|attributes| {
+            attribute(attributes, "TargetMode").is_some_and(|mode| mode == "External")
+        })
+        .filter_map(|attributes| {
+            let kind = attribute(attributes, "Type")?;
+            let kind = kind.rsplit('/').next().unwrap_or_default().to_string();
+            (kind != "hyperlink").then(|| ExternalReference {
+                kind,
+                target: attribute(attributes, "Target").unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// `DDE` and `DDEAUTO` field instructions of a WordprocessingML part
+///
+/// A field's instruction is often split over several runs, so the instruction texts
+/// of the part are joined before they are searched.
+fn dde_fields(xml: &str) -> Vec<String> {
+    let mut instructions = String::new();
+    for (at, _) in xml.match_indices("<w:instrText") {
+        let rest = &xml[at..];
+        let Some(start) = rest.find('>') else {
+            continue;
+        };
+        if rest[..start].ends_with('/') {
+            continue;
+        }
+        let text = &rest[start + 1..];
+        instructions.push_str(&text[..text.find('<').unwrap_or(text.len())]);
+    }
+    let mut fields: Vec<String> = elements(xml, "<w:fldSimple")
+        .filter_map(|attributes| attribute(attributes, "w:instr"))
+        .collect();
+    fields.push(unescape(&instructions));
+
+    let mut dde = Vec::new();
+    for field in fields {
+        let upper = field.to_ascii_uppercase();
+        for (at, _) in upper.match_indices("DDE") {
+            let starts_word = upper[..at]
+                .chars()
+                .next_back()
+                .is_none_or(|c| !c.is_ascii_alphanumeric());
+            let keyword = upper[at..].split_whitespace().next().unwrap_or_default();
+            if starts_word && (keyword == "DDE" || keyword == "DDEAUTO") {
+                dde.push(
+                    field[at..]
+                        .chars()
+                        .take(MAX_DDE_LEN)
+                        .collect::<String>()
+                        .trim()
+                        .to_string(),
+                );
+            }
+        }
+    }
+    dde
+}
+
+/// DDE links of a SpreadsheetML external link part, as `service|topic`
+fn dde_links(xml: &str) -> Vec<String> {
+    elements(xml, "<ddeLink")
+        .map(|attributes| {
+            format!(
+                "{}|{}",
+                attribute(attributes, "ddeService").unwrap_or_default(),
+                attribute(attributes, "ddeTopic").unwrap_or_default()
+            )
+        })
+        .collect()
+}
+
+/// Scans an OLE compound file for VBA stream names and `DDEAUTO` fields, in 8-bit and
+/// UTF-16 text alike
+fn inspect_ole(data: &[u8]) -> OfficeIndicators {
+    let utf16 = |text: &str| -> Vec<u8> { text.bytes().flat_map(|b| [b, 0]).collect() };
+    let patterns = [utf16("_VBA_PROJECT"), b"DDEAUTO".to_vec(), utf16("DDEAUTO")];
+    let matcher = AhoCorasick::builder()
+        .ascii_case_insensitive(true)
+        .build(&patterns)
+        .expect("fixed patterns");
+
+    let mut indicators = OfficeIndicators {
+        format: OfficeFormat::Ole,
+        macros: false,
+        external_references: Vec::new(),
+        dde: Vec::new(),
+    };
+    for found in matcher.find_iter(data) {
+        if found.pattern().as_usize() == 0 {
+            indicators.macros = true;
+            continue;
+        }
+        let command: String = data[found.start()..]
+            .iter()
+            .filter(|&&b| b != 0)
+            .take_while(|&&b| b == b' ' || b.is_ascii_graphic())
+            .take(MAX_DDE_LEN)
+            .map(|&b| b as char)
+            .collect();
+        indicators.dde.push(command.trim().to_string());
+    }
+    indicators
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{OfficeFormat, inspect_office};
+    use flate2::{Compression, write::DeflateEncoder};
+
+    /// A zip archive of deflated members
+    fn zip(members: &[(&str, &str)]) -> Vec<u8> {
+        let (mut out, mut central) = (Vec::new(), Vec::new());
+        for (name, content) in members {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(content.as_bytes()).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let offset = out.len() as u32;
+            let sizes = [compressed.len() as u32, content.len() as u32];
+            out.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00\x08\x00");
+            out.extend_from_slice(&[0; 8]);
+            sizes
+                .iter()
+                .for_each(|s| out.extend_from_slice(&s.to_le_bytes()));
+            out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            out.extend_from_slice(&[0, 0]);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&compressed);
+
+            central.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x00\x00\x08\x00");
+            central.extend_from_slice(&[0; 8]);
+            sizes
+                .iter()
+                .for_each(|s| central.extend_from_slice(&s.to_le_bytes()));
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+        let directory_offset = out.len() as u32;
+        out.extend_from_slice(&central);
+        out.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+        let count = (members.len() as u16).to_le_bytes();
+        out.extend_from_slice(&[count[0], count[1], count[0], count[1]]);
+        out.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        out.extend_from_slice(&directory_offset.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out
+    }
+
+    #[test]
+    fn test_inspect_ooxml() {
+        let docx = zip(&[
+            ("[Content_Types].xml", "<Types/>"),
+            ("word/vbaProject.bin", "\u{1}"),
+            (
+                "word/_rels/settings.xml.rels",
+                r#"<Relationships><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/attachedTemplate" Target="http://203.0.113.5/t.dotm?a=1&amp;b=2" TargetMode="External"/></Relationships>"#,
+            ),
+            (
+                "word/_rels/document.xml.rels",
+                r#"<Relationships><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com/" TargetMode="External"/></Relationships>"#,
+            ),
+            (
+                "word/document.xml",
+                r#"<w:document><w:r><w:instrText xml:space="preserve"> DDE</w:instrText></w:r><w:r><w:instrText>AUTO c:\windows\system32\cmd.exe "/k calc"</w:instrText></w:r><w:t>DDE is a protocol</w:t></w:document>"#,
+            ),
+        ]);
+        let office = inspect_office(&docx, 1024 * 1024).unwrap();
+        assert_eq!(office.format, OfficeFormat::Ooxml);
+        assert!(office.macros);
+        assert_eq!(office.external_references.len(), 1);
+        assert_eq!(office.external_references[0].kind, "attachedTemplate");
+        assert_eq!(
+            office.external_references[0].target,
+            "http://203.0.113.5/t.dotm?a=1&b=2"
+        );
+        assert_eq!(
+            office.dde,
+            [r#"DDEAUTO c:\windows\system32\cmd.exe "/k calc""#]
+        );
+
+        let xlsx = zip(&[
+            ("[Content_Types].xml", "<Types/>"),
+            (
+                "xl/externalLinks/externalLink1.xml",
+                r#"<externalLink><ddeLink ddeService="cmd" ddeTopic="/c calc"/></externalLink>"#,
+            ),
+        ]);
+        let office = inspect_office(&xlsx, 1024 * 1024).unwrap();
+        assert!(!office.macros);
+        assert_eq!(office.dde, ["cmd|/c calc"]);
+
+        let plain = zip(&[
+            ("[Content_Types].xml", "<Types/>"),
+            ("word/document.xml", "<w:document/>"),
+        ]);
+        let office = inspect_office(&plain, 1024 * 1024).unwrap();
+        assert!(!office.macros && office.external_references.is_empty() && office.dde.is_empty());
+        assert!(inspect_office(&zip(&[("a.txt", "a")]), 1024 * 1024).is_none());
+    }
+
+    #[test]
+    fn test_inspect_ole() {
+        let mut doc = vec![0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+        doc.extend_from_slice(&[0; 64]);
+        doc.extend("_VBA_PROJECT".bytes().flat_map(|b| [b, 0]));
+        doc.extend_from_slice(&[0; 16]);
+        doc.extend_from_slice(b"\x13 DDEAUTO c:\\windows\\system32\\cmd.exe \"/k calc\" \x14");
+        let office = inspect_office(&doc, 0).unwrap();
+        assert_eq!(office.format, OfficeFormat::Ole);
+        assert!(office.macros);
+        assert_eq!(
+            office.dde,
+            [r#"DDEAUTO c:\windows\system32\cmd.exe "/k calc""#]
+        );
+        assert!(office.external_references.is_empty());
+    }
+}
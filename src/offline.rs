@@ -0,0 +1,24 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbids all network I/O for the rest of the process, for analyzing mail in sealed
+/// sandboxes
+///
+/// Creating a DNS resolver or HTTP client afterwards fails, as do lookups and requests
+/// of ones created before. There is no way back.
+pub fn forbid_network() {
+    OFFLINE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Fails if network I/O is forbidden, naming the `component` that attempted it
+pub fn check_network(component: &str) -> anyhow::Result<()> {
+    if is_offline() {
+        anyhow::bail!("Offline mode: {} attempted network access", component);
+    }
+    Ok(())
+}
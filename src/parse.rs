@@ -1,43 +1,593 @@
 use idna::domain_to_ascii;
-use mailparse::{MailHeaderMap, parse_mail};
+use mailparse::{MailAddr, MailHeader, MailHeaderMap, ParsedMail, parse_header, parse_mail};
+use publicsuffix::{IcannList, List, Psl};
+use std::sync::LazyLock;
+
+use crate::{
+    attachments::{Attachment, AttachmentLimits, inspect_attachments},
+    canonical::canonical_body,
+    session::SmtpSession,
+};
 
 /// Parsed email with extracted headers
 #[derive(Debug)]
 pub struct EmailParsed {
     pub from: Option<String>,
+    pub subject: Option<String>,
+    pub reply_to: Option<String>,
     pub return_path: Option<String>,
     pub auth_results: Option<String>,
     pub dkim_present: bool,
+    /// Signing domain and selector of each `DKIM-Signature`
+    pub dkim_signatures: Vec<DkimSignature>,
+    /// Connecting IP recorded by the topmost `Received` header, i.e. our own MTA
+    pub client_ip: Option<String>,
+    /// Raw `Date` header
+    pub date: Option<String>,
+    /// Raw `Received` headers, topmost (most recent) first
+    pub received: Vec<String>,
+    /// Names of all header fields, lowercase, in message order
+    pub header_names: Vec<String>,
+    /// Software that composed the message: `X-Mailer`, or `User-Agent` without one
+    pub mailer: Option<String>,
+    /// Raw `Auto-Submitted` header (RFC 3834)
+    pub auto_submitted: Option<String>,
+    /// Raw top-level `Content-Type` header
+    pub content_type: Option<String>,
+    /// SMTP session the message arrived in, when the receiving MTA reports it
+    pub session: Option<SmtpSession>,
+    /// Decoded text/plain and text/html parts, in message order
+    pub body: String,
+    /// Parts of the message that could not be parsed and were skipped or taken raw
+    pub anomalies: Vec<ParseAnomaly>,
+    /// Messages attached as `message/rfc822` parts or `.eml` files, e.g. phish a user
+    /// forwarded as an attachment, parsed in turn
+    pub embedded: Vec<EmailParsed>,
+    /// Attached files, hashed and inspected within resource limits
+    pub attachments: Vec<Attachment>,
+}
+
+/// Levels of attached messages parsed; deeper ones are left unparsed
+const MAX_EMBEDDED_DEPTH: usize = 3;
+
+/// Which stage of parsing a message failed
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseAnomalyKind {
+    /// A header line that is not `Name: value`; it was skipped
+    HeaderSyntax,
+    /// The MIME structure could not be parsed; the body was taken undecoded
+    MimeStructure,
+    /// A text part whose transfer or charset encoding could not be decoded
+    PartDecoding,
+}
+
+/// Something wrong with a message that parsing worked around
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ParseAnomaly {
+    pub kind: ParseAnomalyKind,
+    pub detail: String,
+}
+
+impl ParseAnomaly {
+    fn new(kind: ParseAnomalyKind, detail: impl ToString) -> Self {
+        Self {
+            kind,
+            detail: detail.to_string(),
+        }
+    }
 }
 
+/// Parses a message, working around malformed parts instead of failing
+///
+/// Header lines that cannot be parsed are skipped, and a body whose MIME structure is
+/// broken is taken as undecoded text; each is recorded in `anomalies`.
 pub fn parse_email(raw: &[u8]) -> anyhow::Result<EmailParsed> {
-    let parsed = parse_mail(raw)?;
-    let from_header = parsed.headers.get_first_value("From");
-    let return_path = parsed.headers.get_first_value("Return-Path");
-    let auth_results = parsed.headers.get_first_value("Authentication-Results");
-    let dkim_present = parsed.headers.get_first_value("DKIM-Signature").is_some();
+    parse_email_with_limits(raw, &AttachmentLimits::default())
+}
+
+/// Parses a message like [`parse_email`], inspecting attachments within `limits`
+pub fn parse_email_with_limits(
+    raw: &[u8],
+    limits: &AttachmentLimits,
+) -> anyhow::Result<EmailParsed> {
+    parse_email_nested(raw, 0, limits)
+}
+
+fn parse_email_nested(
+    raw: &[u8],
+    level: usize,
+    limits: &AttachmentLimits,
+) -> anyhow::Result<EmailParsed> {
+    let mut anomalies = Vec::new();
+    let (headers, body_offset) = parse_headers_lenient(raw, &mut anomalies);
+    let headers = headers.as_slice();
+    let from_header = headers.get_first_value("From");
+    let subject = headers.get_first_value("Subject");
+    let reply_to = headers.get_first_value("Reply-To");
+    let return_path = headers.get_first_value("Return-Path");
+    let auth_results = headers.get_first_value("Authentication-Results");
+    let dkim_present = headers.get_first_value("DKIM-Signature").is_some();
+    let raw_body = raw.get(body_offset..).unwrap_or_default();
+    let dkim_signatures = headers
+        .get_all_values("DKIM-Signature")
+        .iter()
+        .filter_map(|h| parse_dkim_signature(h))
+        .map(|mut signature| {
+            if let Some(length) = signature.body_length {
+                let body = canonical_body(raw_body, &signature.canonicalization);
+                signature.unsigned_body_bytes = Some((body.len() as u64).saturating_sub(length));
+            }
+            signature
+        })
+        .collect();
+    let client_ip = headers
+        .get_first_value("Received")
+        .as_deref()
+        .and_then(received_client_ip);
+    let date = headers.get_first_value("Date");
+    let received = headers.get_all_values("Received");
+    let header_names = headers
+        .iter()
+        .map(|h| h.get_key().to_ascii_lowercase())
+        .collect();
+    let mailer = headers
+        .get_first_value("X-Mailer")
+        .or_else(|| headers.get_first_value("User-Agent"));
+    let auto_submitted = headers.get_first_value("Auto-Submitted");
+    let content_type = headers.get_first_value("Content-Type");
+
+    let mut body = String::new();
+    let mut embedded = Vec::new();
+    let mut attachments = Vec::new();
+    match parse_mail(raw) {
+        Ok(parsed) => {
+            collect_text(&parsed, &mut body, &mut anomalies);
+            attachments = inspect_attachments(&parsed, limits);
+            if level < MAX_EMBEDDED_DEPTH {
+                let mut attached = Vec::new();
+                collect_embedded(&parsed, &mut attached, &mut anomalies);
+                embedded = attached
+                    .iter()
+                    .filter_map(|raw| parse_email_nested(raw, level + 1, limits).ok())
+                    .collect();
+            }
+        }
+        Err(err) => {
+            // Header problems already explain why the message as a whole did not parse
+            if anomalies.is_empty() {
+                anomalies.push(ParseAnomaly::new(ParseAnomalyKind::MimeStructure, err));
+            }
+            body = String::from_utf8_lossy(raw_body).into_owned();
+        }
+    }
 
     Ok(EmailParsed {
         from: from_header,
+        subject,
+        reply_to,
         return_path,
         auth_results,
         dkim_present,
+        dkim_signatures,
+        client_ip,
+        date,
+        received,
+        header_names,
+        mailer,
+        auto_submitted,
+        content_type,
+        session: None,
+        body,
+        anomalies,
+        embedded,
+        attachments,
+    })
+}
+
+/// Parses the header section one field at a time, skipping lines that are not headers
+///
+/// Returns the headers and the offset of the body.
+fn parse_headers_lenient<'a>(
+    raw: &'a [u8],
+    anomalies: &mut Vec<ParseAnomaly>,
+) -> (Vec<MailHeader<'a>>, usize) {
+    let mut headers = Vec::new();
+    let mut ix = 0;
+    let mut line = 1;
+    while ix < raw.len() {
+        let rest = &raw[ix..];
+        if rest.starts_with(b"\n") || rest.starts_with(b"\r\n") {
+            ix += if rest[0] == b'\r' { 2 } else { 1 };
+            break;
+        }
+        let parsed = match rest[0] {
+            b'\r' => Err("bare carriage return".to_string()),
+            _ => match parse_header(rest) {
+                Ok((header, _)) if !is_field_name(header.get_key_ref().as_bytes()) => {
+                    Err("not a header field".to_string())
+                }
+                parsed => parsed.map_err(|err| err.to_string()),
+            },
+        };
+        let consumed = match parsed {
+            Ok((header, consumed)) => {
+                headers.push(header);
+                consumed
+            }
+            Err(err) => {
+                anomalies.push(ParseAnomaly::new(
+                    ParseAnomalyKind::HeaderSyntax,
+                    format!("line {}: {}", line, err),
+                ));
+                rest.iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(rest.len(), |end| end + 1)
+            }
+        };
+        line += rest[..consumed].iter().filter(|&&b| b == b'\n').count();
+        ix += consumed.max(1);
+    }
+    (headers, ix.min(raw.len()))
+}
+
+/// Whether `name` is a header field name: printable ASCII up to the colon, optionally
+/// followed by whitespace (obsolete syntax)
+fn is_field_name(name: &[u8]) -> bool {
+    let name = name.trim_ascii_end();
+    !name.is_empty() && name.iter().all(|&b| (33..=126).contains(&b))
+}
+
+/// The tags of a DKIM signature that describe what it covers
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct DkimSignature {
+    /// Signing domain (`d=`)
+    pub domain: String,
+    /// Selector (`s=`)
+    pub selector: String,
+    /// Signed header names (`h=`), lowercase, in order
+    pub signed_headers: Vec<String>,
+    /// Bytes of the body covered (`l=`); the whole body when absent
+    pub body_length: Option<u64>,
+    /// Header and body canonicalization (`c=`), e.g. `relaxed/simple`
+    pub canonicalization: String,
+    /// Signing time (`t=`) in Unix seconds
+    pub timestamp: Option<i64>,
+    /// Canonicalized body bytes after the `l=` length, which the signature does not
+    /// cover; set when the message body is known
+    pub unsigned_body_bytes: Option<u64>,
+}
+
+/// Extracts the tags of a `DKIM-Signature` header value; `d=` and `s=` are required
+pub fn parse_dkim_signature(header: &str) -> Option<DkimSignature> {
+    let tag = |name: &str| {
+        header.split(';').find_map(|part| {
+            let (tag, value) = part.split_once('=')?;
+            let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+            tag.trim().eq_ignore_ascii_case(name).then_some(value)
+        })
+    };
+    let canonicalization = tag("c").unwrap_or_default().to_ascii_lowercase();
+    let (header_c, body_c) = canonicalization
+        .split_once('/')
+        .unwrap_or((&canonicalization, ""));
+    let or_simple = |c: &str| if c.is_empty() { "simple" } else { c }.to_string();
+    Some(DkimSignature {
+        domain: tag("d")?.trim_end_matches('.').to_ascii_lowercase(),
+        selector: tag("s")?.to_ascii_lowercase(),
+        signed_headers: tag("h")
+            .unwrap_or_default()
+            .split(':')
+            .filter(|name| !name.is_empty())
+            .map(str::to_ascii_lowercase)
+            .collect(),
+        body_length: tag("l").and_then(|l| l.parse().ok()),
+        canonicalization: format!("{}/{}", or_simple(header_c), or_simple(body_c)),
+        timestamp: tag("t").and_then(|t| t.parse().ok()),
+        unsigned_body_bytes: None,
+    })
+}
+
+/// The bracketed client IP of a `Received: from ... ([ip])` header
+pub fn received_client_ip(header: &str) -> Option<String> {
+    let from_clause = header
+        .trim_start()
+        .strip_prefix("from ")?
+        .split(" by ")
+        .next()?;
+    from_clause.split('[').skip(1).find_map(|part| {
+        let candidate = part.split(']').next()?;
+        let candidate = candidate.strip_prefix("IPv6:").unwrap_or(candidate);
+        candidate
+            .parse::<std::net::IpAddr>()
+            .ok()
+            .map(|ip| ip.to_string())
     })
 }
 
+/// Appends the decoded text parts of a (possibly multipart) message
+fn collect_text(part: &ParsedMail, out: &mut String, anomalies: &mut Vec<ParseAnomaly>) {
+    if part.subparts.is_empty() {
+        let mimetype = part.ctype.mimetype.to_ascii_lowercase();
+        if mimetype == "text/plain" || mimetype == "text/html" {
+            match part.get_body() {
+                Ok(text) => {
+                    out.push_str(&text);
+                    out.push('\n');
+                }
+                Err(err) => anomalies.push(ParseAnomaly::new(
+                    ParseAnomalyKind::PartDecoding,
+                    format!("{} part: {}", mimetype, err),
+                )),
+            }
+        }
+    }
+    for sub in &part.subparts {
+        collect_text(sub, out, anomalies);
+    }
+}
+
+/// The raw bytes of the messages attached to `raw`, transfer encoding removed, in
+/// message order
+pub fn attached_messages(raw: &[u8]) -> Vec<Vec<u8>> {
+    let mut attached = Vec::new();
+    if let Ok(parsed) = parse_mail(raw) {
+        collect_embedded(&parsed, &mut attached, &mut Vec::new());
+    }
+    attached
+}
+
+/// Collects the raw bytes of attached messages, transfer encoding removed
+fn collect_embedded(part: &ParsedMail, out: &mut Vec<Vec<u8>>, anomalies: &mut Vec<ParseAnomaly>) {
+    if part.subparts.is_empty() {
+        let mimetype = part.ctype.mimetype.to_ascii_lowercase();
+        let filename = part
+            .get_content_disposition()
+            .params
+            .get("filename")
+            .or_else(|| part.ctype.params.get("name"))
+            .map(|name| name.to_ascii_lowercase());
+        let eml = filename.is_some_and(|name| name.ends_with(".eml"));
+        if mimetype == "message/rfc822" || eml {
+            match part.get_body_raw() {
+                Ok(raw) => out.push(raw),
+                Err(err) => anomalies.push(ParseAnomaly::new(
+                    ParseAnomalyKind::PartDecoding,
+                    format!("{} part: {}", mimetype, err),
+                )),
+            }
+        }
+    }
+    for sub in &part.subparts {
+        collect_embedded(sub, out, anomalies);
+    }
+}
+
+impl EmailParsed {
+    /// The `Date` header as a Unix timestamp, if present and well-formed
+    pub fn date_timestamp(&self) -> Option<i64> {
+        mailparse::dateparse(self.date.as_deref()?).ok()
+    }
+
+    /// Attaches the SMTP session the message arrived in
+    ///
+    /// The session's client IP and envelope sender take precedence over the topmost
+    /// `Received` and `Return-Path` headers.
+    pub fn with_session(mut self, session: SmtpSession) -> Self {
+        if let Some(ip) = &session.client_ip {
+            self.client_ip = Some(ip.clone());
+        }
+        if let Some(mail_from) = &session.mail_from {
+            self.return_path = Some(format!("<{}>", mail_from));
+        }
+        self.session = Some(session);
+        self
+    }
+}
+
+/// Parses a point in time given as Unix seconds, a UTC `YYYY-MM-DDTHH:MM:SS`
+/// timestamp, or an RFC 2822 date like a `Date` header
+pub fn parse_time(value: &str) -> Option<i64> {
+    let value = value.trim();
+    value
+        .parse()
+        .ok()
+        .or_else(|| crate::ct::parse_timestamp(value))
+        // dateparse reads most garbage as the epoch; RFC 2822 dates always have a time
+        .or_else(|| {
+            mailparse::dateparse(value)
+                .ok()
+                .filter(|_| value.contains(':'))
+        })
+}
+
+/// Outcomes recorded in an `Authentication-Results` header (RFC 8601)
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct AuthResults {
+    /// The authserv-id of the MTA that added the header
+    pub authserv_id: String,
+    pub spf: Option<String>,
+    pub dkim: Option<String>,
+    pub dmarc: Option<String>,
+}
+
+/// Parses an `Authentication-Results` header value into per-method results
+pub fn parse_auth_results(header: &str) -> Option<AuthResults> {
+    let header = strip_comments(header);
+    let mut parts = header.split(';');
+
+    // The authserv-id may be followed by an optional version number
+    let authserv_id = parts
+        .next()?
+        .split_whitespace()
+        .next()?
+        .to_ascii_lowercase();
+    let mut results = AuthResults {
+        authserv_id,
+        ..AuthResults::default()
+    };
+
+    for part in parts {
+        let Some((method, rest)) = part.trim().split_once('=') else {
+            continue;
+        };
+        let method = method.trim().to_ascii_lowercase();
+        let result = rest.split_whitespace().next().map(str::to_ascii_lowercase);
+        let slot = match method.as_str() {
+            "spf" => &mut results.spf,
+            "dkim" => &mut results.dkim,
+            "dmarc" => &mut results.dmarc,
+            _ => continue,
+        };
+        // Keep the first result per method; a pass from any signature wins for DKIM
+        if slot.is_none() || (method == "dkim" && result.as_deref() == Some("pass")) {
+            *slot = result;
+        }
+    }
+
+    Some(results)
+}
+
+/// Removes RFC 5322 comments `( ... )`, which may carry free-form text
+fn strip_comments(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    for c in value.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
 /// Extracts domain from an email address, normalized to ASCII
 pub fn extract_domain(from: Option<&str>) -> Option<String> {
     from.and_then(|f| {
-        f.split('@').nth(1).map(|s| {
+        // The display name may itself contain an `@`, e.g. `"ceo@bank.example"
+        // <x@attacker.example>`, so take the domain from the parsed address
+        let addr = match mailparse::addrparse(f).as_deref().map(|list| list.first()) {
+            Ok(Some(MailAddr::Single(info))) => info.addr.clone(),
+            _ => f.to_string(),
+        };
+        addr.rsplit_once('@').map(|(_, s)| {
             let s = s.trim().trim_end_matches('>').trim();
             domain_to_ascii(s).unwrap_or(s.to_string())
         })
     })
 }
 
+/// A domain name given by a user, e.g. in a URL path, as lowercase ASCII without a
+/// trailing dot; `None` when it is not a domain name
+///
+/// Internationalized names are converted to their A-labels, so `bücher.example`
+/// becomes `xn--bcher-kva.example`. Underscores are accepted for names such as
+/// `_spf.example.com`.
+pub fn normalize_domain(input: &str) -> Option<String> {
+    let domain = domain_to_ascii(input.trim().trim_end_matches('.')).ok()?;
+    let valid = domain.len() <= 253
+        && domain.contains('.')
+        && domain
+            .split('.')
+            .all(|label| (1..=63).contains(&label.len()))
+        && domain
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._".contains(&b));
+    valid.then_some(domain)
+}
+
+/// Snapshot of the Mozilla Public Suffix List (https://publicsuffix.org/list/)
+const PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+
+static ALL_SUFFIXES: LazyLock<List> = LazyLock::new(|| {
+    PUBLIC_SUFFIX_LIST
+        .parse()
+        .expect("public suffix list is valid")
+});
+
+static ICANN_SUFFIXES: LazyLock<IcannList> = LazyLock::new(|| {
+    PUBLIC_SUFFIX_LIST
+        .parse()
+        .expect("public suffix list is valid")
+});
+
+/// Which public suffixes bound an organizational domain
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SuffixRules {
+    /// ICANN and private suffixes, so `alice.github.io` is its own organization
+    #[default]
+    All,
+    /// Only ICANN suffixes, so `alice.github.io` belongs to `github.io`
+    Icann,
+}
+
+/// The organizational domain (RFC 7489, section 3.2) of `domain`: its public suffix
+/// plus one label, e.g. `example.co.uk` for `mail.example.co.uk`
+///
+/// Private suffixes such as `github.io` count; see [`organizational_domain_with`].
+/// A domain that is itself a public suffix is returned as is, lowercased.
+pub fn organizational_domain(domain: &str) -> String {
+    organizational_domain_with(domain, SuffixRules::All)
+}
+
+/// [`organizational_domain`] with a choice of which suffixes count
+pub fn organizational_domain_with(domain: &str, rules: SuffixRules) -> String {
+    let domain = domain.trim().trim_end_matches('.');
+    let domain = domain_to_ascii(domain).unwrap_or_else(|_| domain.to_ascii_lowercase());
+    let org = match rules {
+        SuffixRules::All => ALL_SUFFIXES.domain(domain.as_bytes()),
+        SuffixRules::Icann => ICANN_SUFFIXES.domain(domain.as_bytes()),
+    };
+    org.and_then(|org| std::str::from_utf8(org.as_bytes()).ok().map(String::from))
+        .unwrap_or(domain)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::super::parse::{extract_domain, parse_email};
+    use super::super::parse::{
+        ParseAnomalyKind, SuffixRules, extract_domain, normalize_domain, organizational_domain,
+        organizational_domain_with, parse_auth_results, parse_dkim_signature, parse_email,
+        parse_time, received_client_ip,
+    };
+
+    #[test]
+    fn test_organizational_domain() {
+        assert_eq!(organizational_domain("mail.Example.com."), "example.com");
+        assert_eq!(organizational_domain("a.b.example.co.uk"), "example.co.uk");
+        assert_eq!(organizational_domain("co.uk"), "co.uk");
+        assert_eq!(
+            organizational_domain("host.unknown-tld"),
+            "host.unknown-tld"
+        );
+        assert_eq!(organizational_domain("alice.github.io"), "alice.github.io");
+        assert_eq!(
+            organizational_domain_with("alice.github.io", SuffixRules::Icann),
+            "github.io"
+        );
+        assert_eq!(organizational_domain("mail.bücher.de"), "xn--bcher-kva.de");
+    }
+
+    #[test]
+    fn test_normalize_domain() {
+        assert_eq!(
+            normalize_domain("Example.COM."),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            normalize_domain("bücher.example"),
+            Some("xn--bcher-kva.example".to_string())
+        );
+        assert_eq!(
+            normalize_domain("_spf.example.com"),
+            Some("_spf.example.com".to_string())
+        );
+        assert_eq!(normalize_domain("localhost"), None);
+        assert_eq!(normalize_domain("a..example"), None);
+        assert_eq!(normalize_domain("exa mple.com"), None);
+        assert_eq!(normalize_domain(&format!("{}.com", "a".repeat(64))), None);
+    }
 
     #[test]
     fn test_extract_domain_basic() {
@@ -60,6 +610,25 @@ mod tests {
         assert_eq!(domain, None);
     }
 
+    proptest::proptest! {
+        #[test]
+        fn prop_extract_domain_never_panics(from in "\\PC*") {
+            extract_domain(Some(&from));
+        }
+
+        // Display names may contain anything an attacker likes, including `@` and
+        // another domain
+        #[test]
+        fn prop_extract_domain_takes_the_address(
+            display in "[ -~&&[^\"\\\\]]{0,30}",
+            local in "[a-z0-9._+-]{1,20}",
+            domain in "([a-z0-9]{1,10}\\.){1,3}[a-z]{2,6}",
+        ) {
+            let from = format!("\"{}\" <{}@{}>", display, local, domain);
+            proptest::prop_assert_eq!(extract_domain(Some(&from)), Some(domain));
+        }
+    }
+
     #[tokio::test]
     async fn test_parse_email_simple() {
         let raw = b"From: test@example.com\r\nReturn-Path: <bounce@example.com>\r\n";
@@ -69,10 +638,122 @@ mod tests {
         assert!(!parsed.dkim_present);
     }
 
+    #[test]
+    fn test_parse_email_date() {
+        let raw = b"From: test@example.com\r\nDate: Mon, 02 Feb 2026 15:15:37 +0000\r\n";
+        let parsed = parse_email(raw).unwrap();
+        assert_eq!(parsed.date_timestamp(), Some(1_770_045_337));
+
+        let undated = parse_email(b"From: test@example.com\r\n").unwrap();
+        assert_eq!(undated.date_timestamp(), None);
+    }
+
+    #[test]
+    fn test_parse_time_formats() {
+        assert_eq!(parse_time("1770045337"), Some(1_770_045_337));
+        assert_eq!(parse_time("2026-02-02T15:15:37Z"), Some(1_770_045_337));
+        assert_eq!(
+            parse_time("Mon, 02 Feb 2026 16:15:37 +0100"),
+            Some(1_770_045_337)
+        );
+        assert_eq!(parse_time("last month"), None);
+    }
+
+    #[test]
+    fn test_parse_email_multipart_body() {
+        let raw = b"From: test@example.com\r\n\
+Content-Type: multipart/alternative; boundary=b\r\n\r\n\
+--b\r\nContent-Type: text/plain\r\n\r\nHello\r\n\
+--b\r\nContent-Type: text/html\r\n\r\n<p>Hi</p>\r\n\
+--b\r\nContent-Type: image/png\r\n\r\nxyz\r\n\
+--b--\r\n";
+        let parsed = parse_email(raw).unwrap();
+        assert!(parsed.body.contains("Hello"));
+        assert!(parsed.body.contains("<p>Hi</p>"));
+        assert!(!parsed.body.contains("xyz"));
+    }
+
     #[tokio::test]
     async fn test_parse_email_with_dkim() {
         let raw = b"From: test@example.com\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n";
         let parsed = parse_email(raw).unwrap();
         assert!(parsed.dkim_present);
     }
+
+    #[test]
+    fn test_parse_auth_results() {
+        let header = "mx.example.net 1; spf=pass (sender IP is 192.0.2.1) smtp.mailfrom=example.com; \
+                      dkim=fail header.d=example.com; dkim=pass header.d=example.com; DMARC=pass";
+        let ar = parse_auth_results(header).unwrap();
+        assert_eq!(ar.authserv_id, "mx.example.net");
+        assert_eq!(ar.spf.as_deref(), Some("pass"));
+        assert_eq!(ar.dkim.as_deref(), Some("pass"));
+        assert_eq!(ar.dmarc.as_deref(), Some("pass"));
+    }
+
+    #[test]
+    fn test_parse_auth_results_none() {
+        let ar = parse_auth_results("mx.example.net; none").unwrap();
+        assert_eq!(ar.authserv_id, "mx.example.net");
+        assert_eq!(ar.spf, None);
+        assert_eq!(ar.dkim, None);
+    }
+
+    #[test]
+    fn test_dkim_signature_and_client_ip() {
+        let sig = parse_dkim_signature(
+            "v=1; a=rsa-sha256; d=Example.com; s=selector1;\r\n\th=from:to; bh=abc=; b=def",
+        )
+        .unwrap();
+        assert_eq!(sig.domain, "example.com");
+        assert_eq!(sig.selector, "selector1");
+        assert_eq!(sig.signed_headers, vec!["from", "to"]);
+        assert_eq!(sig.canonicalization, "simple/simple");
+        let sig =
+            parse_dkim_signature("d=example.com; s=s; c=relaxed; l=120; t=1770000000").unwrap();
+        assert_eq!(sig.canonicalization, "relaxed/simple");
+        assert_eq!(
+            (sig.body_length, sig.timestamp),
+            (Some(120), Some(1_770_000_000))
+        );
+        assert!(parse_dkim_signature("v=1; a=rsa-sha256; s=x").is_none());
+
+        assert_eq!(
+            received_client_ip(
+                "from mail.example.net (mail.example.net [192.0.2.25]) by mx.example.com with ESMTPS id 1"
+            ),
+            Some("192.0.2.25".to_string())
+        );
+        assert_eq!(
+            received_client_ip("from host ([IPv6:2001:db8::1]) by mx.example.com"),
+            Some("2001:db8::1".to_string())
+        );
+        assert_eq!(received_client_ip("by mx.example.com [10.0.0.1]"), None);
+    }
+
+    #[test]
+    fn test_parse_email_works_around_malformed_parts() {
+        let raw = b" overhanging\r\nFrom: ceo@example.com\r\nno colon here\r\n\
+                    Subject: hi\r\n\r\nbody";
+        let parsed = parse_email(raw).unwrap();
+        assert_eq!(parsed.from.as_deref(), Some("ceo@example.com"));
+        assert_eq!(parsed.subject.as_deref(), Some("hi"));
+        assert_eq!(parsed.body, "body");
+        let kinds: Vec<_> = parsed.anomalies.iter().map(|a| a.kind).collect();
+        assert_eq!(kinds, [ParseAnomalyKind::HeaderSyntax; 2]);
+        assert!(parsed.anomalies[1].detail.starts_with("line 3:"));
+
+        let raw = b"From: ceo@example.com\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n\
+                    --b\r\n bad: part\r\n\r\nurgent\r\n--b--\r\n";
+        let parsed = parse_email(raw).unwrap();
+        assert_eq!(parsed.anomalies[0].kind, ParseAnomalyKind::MimeStructure);
+        assert!(parsed.body.contains("urgent"));
+
+        let raw = b"From: ceo@example.com\r\nContent-Transfer-Encoding: base64\r\n\r\n!!!";
+        let parsed = parse_email(raw).unwrap();
+        assert_eq!(parsed.anomalies[0].kind, ParseAnomalyKind::PartDecoding);
+
+        let parsed = parse_email(b"From: ceo@example.com\r\n\r\nbody").unwrap();
+        assert!(parsed.anomalies.is_empty());
+    }
 }
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::http::{HttpFetcher, HttpRequest};
+
+/// Records first seen this close to the message `Date` count as "changed right before" it
+const CHANGE_WINDOW_SECS: i64 = 7 * 24 * 3600;
+
+/// Domains whose earliest record is younger than this are considered newly created
+const NEW_DOMAIN_WINDOW_SECS: i64 = 30 * 24 * 3600;
+
+/// One historical DNS record as reported by a passive DNS provider
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct PassiveDnsRecord {
+    pub rrname: String,
+    pub rrtype: String,
+    /// Unix timestamp the record was first observed
+    pub time_first: i64,
+    /// Unix timestamp the record was last observed
+    pub time_last: i64,
+}
+
+/// Source of historical DNS records for a domain
+#[async_trait]
+pub trait PassiveDnsProvider {
+    async fn history(&self, domain: &str) -> anyhow::Result<Vec<PassiveDnsRecord>>;
+}
+
+/// Passive DNS provider speaking the Passive DNS Common Output Format (NDJSON)
+///
+/// Queries `{base_url}/{domain}`, sending the API key as `X-API-Key` when set.
+pub struct HttpPassiveDns {
+    fetcher: Arc<dyn HttpFetcher>,
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl HttpPassiveDns {
+    pub fn new(base_url: &str, api_key: Option<String>, fetcher: Arc<dyn HttpFetcher>) -> Self {
+        Self {
+            fetcher,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+        }
+    }
+}
+
+#[async_trait]
+impl PassiveDnsProvider for HttpPassiveDns {
+    async fn history(&self, domain: &str) -> anyhow::Result<Vec<PassiveDnsRecord>> {
+        let url = format!("{}/{}", self.base_url, domain);
+        let mut request = HttpRequest::get(url, Duration::from_secs(10));
+        if let Some(key) = &self.api_key {
+            request = request.header("X-API-Key", key);
+        }
+        let body = self.fetcher.fetch(request).await?.text()?;
+        Ok(parse_cof(&body))
+    }
+}
+
+/// Parses newline-delimited COF records, skipping lines that do not parse
+pub fn parse_cof(body: &str) -> Vec<PassiveDnsRecord> {
+    body.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// What the domain's DNS history says about the infrastructure behind a message
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct PassiveDnsFindings {
+    /// Earliest time any record for the domain was observed
+    pub first_seen: Option<i64>,
+
+    /// The domain's first record appeared shortly before the reference time
+    pub recently_created: bool,
+
+    /// An MX or A record first appeared within days before the message `Date`
+    pub changed_before_message: bool,
+
+    pub records: Vec<PassiveDnsRecord>,
+}
+
+/// Evaluates DNS history relative to the message `Date` (or `now` when absent)
+pub fn evaluate_history(
+    records: Vec<PassiveDnsRecord>,
+    message_date: Option<i64>,
+    now: i64,
+) -> PassiveDnsFindings {
+    let reference = message_date.unwrap_or(now);
+    let first_seen = records.iter().map(|r| r.time_first).min();
+
+    let recently_created =
+        first_seen.is_some_and(|t| reference - t >= 0 && reference - t < NEW_DOMAIN_WINDOW_SECS);
+
+    let changed_before_message = message_date.is_some_and(|date| {
+        records
+            .iter()
+            .filter(|r| matches!(r.rrtype.to_ascii_uppercase().as_str(), "MX" | "A" | "AAAA"))
+            .any(|r| date - r.time_first >= 0 && date - r.time_first < CHANGE_WINDOW_SECS)
+    });
+
+    PassiveDnsFindings {
+        first_seen,
+        recently_created,
+        changed_before_message,
+        records,
+    }
+}
+
+/// Fetches and evaluates the DNS history of `domain`, as of `as_of` (default now)
+pub async fn enrich<P: PassiveDnsProvider + Sync>(
+    provider: &P,
+    domain: &str,
+    message_date: Option<i64>,
+    as_of: Option<i64>,
+) -> anyhow::Result<PassiveDnsFindings> {
+    let records = provider.history(domain).await?;
+    let now = match as_of {
+        Some(as_of) => as_of,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as i64,
+    };
+    Ok(evaluate_history(records, message_date, now))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{evaluate_history, parse_cof};
+
+    const DAY: i64 = 24 * 3600;
+
+    #[test]
+    fn test_parse_cof_skips_garbage() {
+        let body = concat!(
+            r#"{"rrname":"example.com","rrtype":"MX","rdata":"mx.example.com","time_first":100,"time_last":200}"#,
+            "\nnot json\n\n",
+            r#"{"rrname":"example.com","rrtype":"A","time_first":50,"time_last":300}"#,
+        );
+        let records = parse_cof(body);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].rrtype, "A");
+    }
+
+    #[test]
+    fn test_mx_created_right_before_message() {
+        let date = 1_000 * DAY;
+        let records = parse_cof(&format!(
+            r#"{{"rrname":"evil.test","rrtype":"MX","time_first":{},"time_last":{}}}"#,
+            date - 2 * DAY,
+            date
+        ));
+        let findings = evaluate_history(records, Some(date), date + 400 * DAY);
+        assert!(findings.recently_created);
+        assert!(findings.changed_before_message);
+        assert_eq!(findings.first_seen, Some(date - 2 * DAY));
+    }
+
+    #[test]
+    fn test_established_domain() {
+        let date = 1_000 * DAY;
+        let records = parse_cof(&format!(
+            r#"{{"rrname":"example.com","rrtype":"A","time_first":{},"time_last":{}}}"#,
+            date - 900 * DAY,
+            date
+        ));
+        let findings = evaluate_history(records, Some(date), date);
+        assert!(!findings.recently_created);
+        assert!(!findings.changed_before_message);
+    }
+}
@@ -0,0 +1,284 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+/// Bytes at the start of a file searched for the `%PDF-` header, which readers accept
+/// after leading junk
+const HEADER_WINDOW: usize = 1024;
+
+/// Bytes before a `stream` keyword searched for its dictionary's `/FlateDecode`
+const DICTIONARY_WINDOW: usize = 512;
+
+/// Structural signs of links and active content in a PDF attachment
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct PdfIndicators {
+    /// Targets of `/URI` link actions, in order, without duplicates
+    pub uris: Vec<String>,
+    /// Whether the document contains JavaScript (`/JavaScript`, `/JS`)
+    pub javascript: bool,
+    /// Whether an action runs when the document or a page is opened (`/OpenAction`,
+    /// `/AA`)
+    pub open_action: bool,
+    /// Embedded file streams (`/EmbeddedFile`)
+    pub embedded_files: usize,
+}
+
+/// Scans a PDF for link targets, JavaScript, automatic actions, and embedded files;
+/// `None` for other data
+///
+/// The document is not rendered or fully parsed: names and strings are read from the
+/// file and from its Flate-compressed streams, which hold the objects of modern PDFs.
+/// At most `max_decompressed` bytes are decompressed in all.
+pub fn inspect_pdf(data: &[u8], max_decompressed: u64) -> Option<PdfIndicators> {
+    let header = &data[..data.len().min(HEADER_WINDOW)];
+    if !header.windows(5).any(|w| w == b"%PDF-") {
+        return None;
+    }
+    let mut indicators = PdfIndicators::default();
+    scan(data, &mut indicators);
+
+    let mut budget = max_decompressed;
+    let mut from = 0;
+    while budget > 0 {
+        let Some(at) = find(&data[from..], b"stream").map(|at| from + at) else {
+            break;
+        };
+        from = at + 6;
+        if data[..at].ends_with(b"end") {
+            continue;
+        }
+        let start = match &data[from..] {
+            [b'\r', b'\n', ..] => from + 2,
+            [b'\n', ..] => from + 1,
+            _ => continue,
+        };
+        let dictionary = &data[at.saturating_sub(DICTIONARY_WINDOW)..at];
+        let dictionary = match rfind(dictionary, b"obj") {
+            Some(obj) => &dictionary[obj..],
+            None => dictionary,
+        };
+        let end = find(&data[start..], b"endstream").map_or(data.len(), |end| start + end);
+        if find(dictionary, b"/FlateDecode").is_none() {
+            continue;
+        }
+        let mut decoded = Vec::new();
+        // Corrupt data ends the stream; what was decompressed so far is still scanned
+        let _ = ZlibDecoder::new(&data[start..end])
+            .take(budget)
+            .read_to_end(&mut decoded);
+        budget -= decoded.len() as u64;
+        scan(&decoded, &mut indicators);
+        from = end;
+    }
+    Some(indicators)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+fn is_delimiter(b: u8) -> bool {
+    b.is_ascii_whitespace() || b"/[]()<>{}%".contains(&b) || b == 0
+}
+
+/// Reads the names and strings of PDF syntax, skipping the contents of strings and
+/// comments so text cannot pass for a name
+fn scan(data: &[u8], indicators: &mut PdfIndicators) {
+    let mut i = 0;
+    while i < data.len() {
+        match data[i] {
+            b'/' => {
+                let (name, end) = name(data, i + 1);
+                i = end;
+                match name.as_slice() {
+                    b"JavaScript" | b"JS" => indicators.javascript = true,
+                    b"OpenAction" | b"AA" => indicators.open_action = true,
+                    b"EmbeddedFile" => indicators.embedded_files += 1,
+                    b"URI" => {
+                        while data.get(i).is_some_and(u8::is_ascii_whitespace) {
+                            i += 1;
+                        }
+                        let (uri, end) = match data.get(i..i + 2) {
+                            Some([b'(', _]) => literal_string(data, i + 1),
+                            Some([b'<', next]) if *next != b'<' => hex_string(data, i + 1),
+                            _ => continue,
+                        };
+                        i = end;
+                        let uri = String::from_utf8_lossy(&uri).trim().to_string();
+                        if !uri.is_empty() && !indicators.uris.contains(&uri) {
+                            indicators.uris.push(uri);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            b'(' => i = literal_string(data, i + 1).1,
+            // `<<` opens a dictionary, a single `<` a hex string
+            b'<' if data.get(i + 1) == Some(&b'<') => i += 2,
+            b'<' => i = hex_string(data, i + 1).1,
+            b'%' => {
+                while i < data.len() && !matches!(data[i], b'\r' | b'\n') {
+                    i += 1;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+}
+
+/// The name starting at `start`, after its `/`, with `#xx` escapes decoded, and the
+/// offset after it
+fn name(data: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let mut name = Vec::new();
+    let mut i = start;
+    while i < data.len() && !is_delimiter(data[i]) {
+        let escaped = (data[i] == b'#')
+            .then(|| data.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(b) => {
+                name.push(b);
+                i += 3;
+            }
+            None => {
+                name.push(data[i]);
+                i += 1;
+            }
+        }
+    }
+    (name, i)
+}
+
+/// The literal string starting at `start`, after its `(`, and the offset after its `)`
+fn literal_string(data: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut i = start;
+    while i < data.len() {
+        let b = data[i];
+        i += 1;
+        match b {
+            b'\\' => {
+                let Some(&next) = data.get(i) else {
+                    break;
+                };
+                i += 1;
+                match next {
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b'b' => out.push(8),
+                    b'f' => out.push(12),
+                    b'0'..=b'7' => {
+                        let mut value = u32::from(next - b'0');
+                        for _ in 0..2 {
+                            match data.get(i) {
+                                Some(&digit @ b'0'..=b'7') => {
+                                    value = value * 8 + u32::from(digit - b'0');
+                                    i += 1;
+                                }
+                                _ => break,
+                            }
+                        }
+                        out.push(value as u8);
+                    }
+                    // A line continuation
+                    b'\r' => i += usize::from(data.get(i) == Some(&b'\n')),
+                    b'\n' => {}
+                    _ => out.push(next),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                out.push(b);
+            }
+            b')' if depth == 0 => break,
+            b')' => {
+                depth -= 1;
+                out.push(b);
+            }
+            _ => out.push(b),
+        }
+    }
+    (out, i)
+}
+
+/// The hex string starting at `start`, after its `<`, and the offset after its `>`
+fn hex_string(data: &[u8], start: usize) -> (Vec<u8>, usize) {
+    let end = data[start..]
+        .iter()
+        .position(|&b| b == b'>')
+        .map_or(data.len(), |end| start + end);
+    let mut digits: Vec<u8> = data[start..end]
+        .iter()
+        .filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8))
+        .collect();
+    // An odd final digit is followed by an implied 0
+    if digits.len() % 2 == 1 {
+        digits.push(0);
+    }
+    let bytes = digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect();
+    (bytes, (end + 1).min(data.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::inspect_pdf;
+    use flate2::{Compression, write::ZlibEncoder};
+
+    #[test]
+    fn test_inspect_pdf() {
+        let mut objects = ZlibEncoder::new(Vec::new(), Compression::default());
+        // Repeated, so the stream is compressed rather than stored
+        for _ in 0..16 {
+            objects
+                .write_all(b"<< /Type /Action /S /J#61vaScript /JS (app.alert\\(1\\)) >>\n")
+                .unwrap();
+        }
+        let objects = objects.finish().unwrap();
+
+        let mut pdf = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        pdf.extend_from_slice(b"1 0 obj << /Type /Catalog /OpenAction 3 0 R >> endobj\n");
+        pdf.extend_from_slice(
+            b"2 0 obj << /Type /Annot /A << /S /URI /URI (https://login.example.net/\\(x\\)) >> >> endobj\n",
+        );
+        pdf.extend_from_slice(
+            b"4 0 obj << /A << /S /URI /URI <68747470733A2F2F612E74657374> >> >> endobj\n",
+        );
+        pdf.extend_from_slice(
+            b"% /EmbeddedFile in a comment\n5 0 obj (/JavaScript in a string) endobj\n",
+        );
+        pdf.extend_from_slice(
+            format!(
+                "3 0 obj << /Length {} /Filter /FlateDecode >>\nstream\n",
+                objects.len()
+            )
+            .as_bytes(),
+        );
+        pdf.extend_from_slice(&objects);
+        pdf.extend_from_slice(b"\nendstream\nendobj\n%%EOF\n");
+
+        let indicators = inspect_pdf(&pdf, 1024 * 1024).unwrap();
+        assert_eq!(
+            indicators.uris,
+            ["https://login.example.net/(x)", "https://a.test"]
+        );
+        assert!(indicators.javascript);
+        assert!(indicators.open_action);
+        assert_eq!(indicators.embedded_files, 0);
+
+        // Without a decompression budget, the JavaScript in the stream stays hidden
+        assert!(!inspect_pdf(&pdf, 0).unwrap().javascript);
+        assert_eq!(inspect_pdf(b"PK\x03\x04", 1024), None);
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt::Write;
+
+use crate::{
+    email_verdict::{AnalysisResult, Verdict},
+    normalize::localized_header,
+    parse::{EmailParsed, attached_messages},
+    report::{code, incident_markdown},
+};
+
+/// Risk score from which a reported message is triaged as phishing
+pub const PHISHING_SCORE: u32 = 70;
+
+/// Lines introducing an inline forward (Gmail, Outlook, Thunderbird, Apple Mail),
+/// lowercase and without the surrounding dashes
+const FORWARD_MARKERS: &[&str] = &[
+    "forwarded message",
+    "original message",
+    "begin forwarded message:",
+    "weitergeleitete nachricht",
+    "ursprüngliche nachricht",
+    "message transféré",
+    "message d'origine",
+];
+
+/// Headers quoted in an inline forward; localized labels are mapped as in
+/// [`header_block`](crate::normalize::header_block)
+const FORWARD_HEADERS: &[&str] = &["From", "Date", "To", "Cc", "Reply-To", "Subject"];
+
+/// Where in the report the reported message was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportSource {
+    /// Attached as a `message/rfc822` part or `.eml` file, with its original headers
+    Attachment,
+    /// Quoted in the body below a forward marker; only the quoted headers survive
+    InlineForward,
+}
+
+/// The message a user reported, as found in their report
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportedOriginal {
+    pub source: ReportSource,
+    /// The message itself; rebuilt from the quoted headers and text for inline forwards
+    pub raw: Vec<u8>,
+}
+
+/// What to do with a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Triage {
+    /// The reported message violates its sender's policy or scores at least
+    /// [`PHISHING_SCORE`]
+    Phishing,
+    /// The reported message is unauthenticated or suspicious; an analyst should look
+    Suspicious,
+    /// Nothing points at spoofing, e.g. an authenticated newsletter
+    NoThreatFound,
+    /// The report carried no message to analyze
+    NoOriginal,
+}
+
+/// The triage of a reported message's analysis
+pub fn triage(reported: &AnalysisResult) -> Triage {
+    match reported.verdict {
+        Verdict::PolicyViolation => Triage::Phishing,
+        _ if reported.risk_score >= PHISHING_SCORE => Triage::Phishing,
+        Verdict::Suspicious | Verdict::Unauthenticated | Verdict::Indeterminate => {
+            Triage::Suspicious
+        }
+        Verdict::Authenticated | Verdict::Automated => Triage::NoThreatFound,
+    }
+}
+
+/// Finds the message a user reported in `raw`, their report: the first attached message,
+/// or else one forwarded inline
+pub fn locate_original(raw: &[u8], report: &EmailParsed) -> Option<ReportedOriginal> {
+    if let Some(raw) = attached_messages(raw).into_iter().next() {
+        return Some(ReportedOriginal {
+            source: ReportSource::Attachment,
+            raw,
+        });
+    }
+    inline_forward(&report.body).map(|raw| ReportedOriginal {
+        source: ReportSource::InlineForward,
+        raw: raw.into_bytes(),
+    })
+}
+
+/// Rebuilds the message quoted below the first forward marker in `body`; `None`
+/// without a marker or a quoted `From`
+///
+/// Quoted headers are those the mail client chose to show, so the rebuilt message has
+/// no `Received`, `DKIM-Signature`, or authentication results.
+pub fn inline_forward(body: &str) -> Option<String> {
+    let mut lines = body.lines();
+    lines.by_ref().find(|line| {
+        let marker = line.trim().trim_matches('-').trim().to_ascii_lowercase();
+        FORWARD_MARKERS.contains(&marker.as_str())
+    })?;
+
+    let mut headers = Vec::new();
+    let mut text = Vec::new();
+    for line in lines.by_ref() {
+        let quoted = line.trim();
+        if quoted.is_empty() && headers.is_empty() {
+            continue;
+        }
+        let header = quoted.split_once(':').and_then(|(label, value)| {
+            let name = FORWARD_HEADERS
+                .iter()
+                .find(|name| label.trim().eq_ignore_ascii_case(name))
+                .copied()
+                .or_else(|| localized_header(label))?;
+            Some((name, value.trim()))
+        });
+        match header {
+            Some(header) => headers.push(header),
+            None => {
+                if !quoted.is_empty() {
+                    text.push(line);
+                }
+                break;
+            }
+        }
+    }
+    text.extend(lines);
+    if !headers.iter().any(|(name, _)| *name == "From") {
+        return None;
+    }
+
+    let mut raw = String::new();
+    for (name, value) in headers {
+        // Older Outlook quotes addresses as `Name [mailto:a@example.com]`
+        let value = match value.split_once("[mailto:") {
+            Some((name, address)) => format!("{}<{}", name, address.replacen(']', ">", 1)),
+            None => value.to_string(),
+        };
+        let _ = write!(raw, "{}: {}\r\n", name, value);
+    }
+    raw.push_str("\r\n");
+    raw.push_str(&text.join("\r\n"));
+    Some(raw)
+}
+
+/// The outcome of a phishing report: the triage, a summary for the ticket, and the
+/// analyses of the report and of the message it reported
+#[derive(Debug, serde::Serialize)]
+pub struct PhishReport {
+    pub triage: Triage,
+    /// Where the reported message was found, if anywhere
+    pub source: Option<ReportSource>,
+    /// Markdown summary for pasting into a ticket; see [`incident_markdown`]
+    pub summary: String,
+    /// The analysis of the report itself
+    pub carrier: AnalysisResult,
+    /// The analysis of the reported message
+    pub reported: Option<AnalysisResult>,
+}
+
+impl PhishReport {
+    /// Triages and summarizes a report, given the reported message, if found, with its
+    /// analysis
+    pub fn new(
+        report: &EmailParsed,
+        carrier: AnalysisResult,
+        reported: Option<(ReportSource, &EmailParsed, AnalysisResult)>,
+    ) -> Self {
+        let triage = reported
+            .as_ref()
+            .map_or(Triage::NoOriginal, |(_, _, result)| triage(result));
+
+        let mut summary = String::new();
+        let _ = writeln!(summary, "# Phishing report: {:?}\n", triage);
+        let reporter = report.from.as_deref().unwrap_or("(unknown)");
+        let _ = writeln!(summary, "- **Reported by:** {}", code(reporter));
+        if let Some(subject) = &report.subject {
+            let _ = writeln!(summary, "- **Report subject:** {}", code(subject));
+        }
+        let found = match reported.as_ref().map(|(source, _, _)| source) {
+            Some(ReportSource::Attachment) => "attached",
+            Some(ReportSource::InlineForward) => {
+                "forwarded inline; only the quoted headers were analyzed"
+            }
+            None => "no message attached or forwarded",
+        };
+        let _ = writeln!(summary, "- **Reported message:** {}", found);
+        let _ = writeln!(summary, "- **Report analysis ID:** {}", code(&carrier.id));
+        if let Some((_, parsed, result)) = &reported {
+            summary.push('\n');
+            summary.push_str(&incident_markdown(parsed, result));
+        }
+
+        Self {
+            triage,
+            source: reported.as_ref().map(|(source, _, _)| *source),
+            summary,
+            carrier,
+            reported: reported.map(|(_, _, result)| result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReportSource, inline_forward, locate_original};
+    use crate::parse::parse_email;
+
+    #[test]
+    fn test_locate_original() {
+        let body = "See below, looks fishy\r\n\r\n-----Original Message-----\r\nFrom: IT Support [mailto:it@helpdesk.test]\r\nSent: Monday, January 1, 2024 10:00 AM\r\nTo: Alice\r\nSubject: Password expires\r\n\r\nReset it here: https://helpdesk.test/reset\r\n";
+        let raw = inline_forward(body).unwrap();
+        let forwarded = parse_email(raw.as_bytes()).unwrap();
+        assert_eq!(
+            forwarded.from.as_deref(),
+            Some("IT Support <it@helpdesk.test>")
+        );
+        assert_eq!(forwarded.subject.as_deref(), Some("Password expires"));
+        assert!(forwarded.body.contains("https://helpdesk.test/reset"));
+        assert!(inline_forward("Just a note\r\nFrom: me\r\n").is_none());
+        let localized = inline_forward(
+            "---------- Weitergeleitete Nachricht ---------\nVon: Bank <info@bank.test>\nBetreff: Konto gesperrt\n\nText",
+        )
+        .unwrap();
+        assert!(
+            localized.starts_with("From: Bank <info@bank.test>\r\nSubject: Konto gesperrt\r\n")
+        );
+
+        let report = format!(
+            "From: alice@corp.example\r\nSubject: Fwd: Password expires\r\n\r\n{}",
+            body
+        );
+        let parsed = parse_email(report.as_bytes()).unwrap();
+        let original = locate_original(report.as_bytes(), &parsed).unwrap();
+        assert_eq!(original.source, ReportSource::InlineForward);
+
+        let report = b"From: alice@corp.example\r\nMIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\nContent-Type: text/plain\r\n\r\n-----Original Message-----\r\nFrom: x@y.test\r\n\r\n--b\r\nContent-Type: message/rfc822\r\n\r\nFrom: ceo@corp.test\r\n\r\nPay\r\n--b--\r\n";
+        let parsed = parse_email(report).unwrap();
+        let original = locate_original(report, &parsed).unwrap();
+        assert_eq!(original.source, ReportSource::Attachment);
+        assert!(original.raw.starts_with(b"From: ceo@corp.test"));
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Returned when every worker is busy and the queue is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Saturated;
+
+impl std::fmt::Display for Saturated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "analysis pool saturated")
+    }
+}
+
+impl std::error::Error for Saturated {}
+
+/// Bounds concurrent analyses, letting a limited number of requests wait for a slot
+pub struct WorkerPool {
+    permits: Semaphore,
+    workers: usize,
+    max_queue: usize,
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+/// Point-in-time pool counters
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+pub struct PoolStats {
+    pub workers: usize,
+    pub in_flight: usize,
+    pub max_queue: usize,
+    pub queued: usize,
+    pub rejected_total: u64,
+}
+
+/// Counts a request as queued until dropped, including when the request is cancelled
+struct QueueSlot<'a>(&'a AtomicUsize);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl WorkerPool {
+    pub fn new(workers: usize, max_queue: usize) -> Self {
+        Self {
+            permits: Semaphore::new(workers),
+            workers,
+            max_queue,
+            queued: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Waits for a worker slot, or fails immediately when the queue is already full
+    ///
+    /// The slot is released when the returned permit is dropped.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, Saturated> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(permit);
+        }
+
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queue {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Saturated);
+        }
+        let _slot = QueueSlot(&self.queued);
+
+        self.permits.acquire().await.map_err(|_| Saturated)
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            workers: self.workers,
+            in_flight: self.workers - self.permits.available_permits(),
+            max_queue: self.max_queue,
+            queued: self.queued.load(Ordering::SeqCst),
+            rejected_total: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Saturated, WorkerPool};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_pool_queues_then_rejects() {
+        let pool = Arc::new(WorkerPool::new(1, 1));
+        let busy = pool.acquire().await.unwrap();
+
+        // Second request waits in the queue
+        let waiter = {
+            let pool = pool.clone();
+            tokio::spawn(async move { pool.acquire().await.map(|_| ()) })
+        };
+        while pool.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        // Third request is turned away
+        assert_eq!(pool.acquire().await.err(), Some(Saturated));
+        assert_eq!(pool.stats().rejected_total, 1);
+        assert_eq!(pool.stats().in_flight, 1);
+
+        drop(busy);
+        waiter.await.unwrap().unwrap();
+        assert_eq!(pool.stats().queued, 0);
+        assert_eq!(pool.stats().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_leaves_queue() {
+        let pool = WorkerPool::new(1, 1);
+        let _busy = pool.acquire().await.unwrap();
+
+        let waiting =
+            tokio::time::timeout(std::time::Duration::from_millis(10), pool.acquire()).await;
+        assert!(waiting.is_err());
+        assert_eq!(pool.stats().queued, 0);
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    automated::AutomatedKind,
+    dkim_replay::ReplaySignal,
+    email_verdict::{Evidence, Verdict},
+    messages::{Lang, render},
+    received::{HopAnomalyKind, format_duration},
+    rules::rule_id,
+    session::HeloProblem,
+};
+
+/// How strongly a piece of evidence indicates spoofing or phishing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub enum Severity {
+    #[default]
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// A human-readable explanation of one piece of evidence behind a result
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Reason {
+    /// Message catalog key, stable across languages
+    pub key: String,
+
+    /// Stable rule ID of `key`, e.g. `ESD-0001`
+    pub rule_id: String,
+
+    pub severity: Severity,
+
+    /// Values substituted into the message
+    pub args: BTreeMap<String, String>,
+
+    /// The rendered message
+    pub message: String,
+}
+
+impl Reason {
+    fn new(key: &str, severity: Severity, args: &[(&str, String)]) -> Self {
+        let args: BTreeMap<String, String> = args
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        Self {
+            key: key.to_string(),
+            rule_id: rule_id(key).unwrap_or_default().to_string(),
+            severity,
+            message: render(Lang::En, key, &args),
+            args,
+        }
+    }
+
+    /// Explains that the analysis stopped at its deadline
+    pub fn deadline_exceeded() -> Self {
+        Self::new("deadline_exceeded", Severity::Medium, &[])
+    }
+
+    /// Re-renders the message in `lang`
+    pub fn localize(&mut self, lang: Lang) {
+        self.message = render(lang, &self.key, &self.args);
+    }
+}
+
+/// Explains the evidence that contributed to the verdict and risk score, in English
+pub fn explain(verdict: &Verdict, evidence: &Evidence) -> Vec<Reason> {
+    let mut reasons = Vec::new();
+    let domain = evidence.from_domain.clone().unwrap_or_default();
+
+    if evidence.from_domain.is_some() && !evidence.domain_valid {
+        reasons.push(Reason::new(
+            "domain_invalid",
+            Severity::High,
+            &[("domain", domain.clone())],
+        ));
+    }
+
+    if let Some(upstream) = &evidence.upstream_auth {
+        let severity = match upstream.dmarc.as_deref() {
+            Some("pass") => Severity::Info,
+            Some("fail") => Severity::High,
+            _ => Severity::Low,
+        };
+        reasons.push(Reason::new(
+            "upstream_dmarc",
+            severity,
+            &[
+                ("authserv_id", upstream.authserv_id.clone()),
+                (
+                    "result",
+                    upstream.dmarc.clone().unwrap_or_else(|| "none".to_string()),
+                ),
+            ],
+        ));
+    } else if evidence.domain_valid {
+        if evidence.spf_policy.is_none()
+            && evidence.dmarc_policy.is_none()
+            && !evidence.dkim_present
+        {
+            reasons.push(Reason::new(
+                "no_authentication",
+                Severity::Medium,
+                &[("domain", domain.clone())],
+            ));
+        } else {
+            if *verdict == Verdict::PolicyViolation {
+                reasons.push(Reason::new(
+                    "dmarc_reject_misaligned",
+                    Severity::Critical,
+                    &[("domain", domain.clone())],
+                ));
+            } else if evidence.spf_policy.is_some() && !evidence.alignment_ok {
+                reasons.push(Reason::new(
+                    "spf_not_strict",
+                    Severity::Low,
+                    &[("domain", domain.clone())],
+                ));
+            }
+            if !evidence.dkim_present {
+                reasons.push(Reason::new("dkim_missing", Severity::Low, &[]));
+            }
+        }
+    }
+
+    if let Some(check) = &evidence.infrastructure
+        && !check.verified
+    {
+        reasons.push(Reason::new(
+            "unregistered_infrastructure",
+            Severity::High,
+            &[
+                ("domain", check.domain.clone()),
+                (
+                    "source",
+                    check
+                        .client_ip
+                        .clone()
+                        .unwrap_or_else(|| "unknown".to_string()),
+                ),
+            ],
+        ));
+    }
+
+    if let Some(lists) = &evidence.lists {
+        if let Some(entry) = &lists.blocklisted {
+            reasons.push(Reason::new(
+                "blocklisted_sender",
+                Severity::Critical,
+                &[("entry", entry.clone())],
+            ));
+        } else if let Some(entry) = &lists.allowlisted {
+            reasons.push(Reason::new(
+                "allowlisted_sender",
+                Severity::Info,
+                &[("entry", entry.clone())],
+            ));
+        }
+        if let Some(name) = &lists.vip_impersonation {
+            reasons.push(Reason::new(
+                "vip_impersonation",
+                Severity::High,
+                &[("name", name.clone()), ("domain", domain.clone())],
+            ));
+        }
+    }
+
+    if let Some(lookalike) = &evidence.lookalike {
+        reasons.push(Reason::new(
+            "lookalike",
+            Severity::High,
+            &[
+                ("domain", lookalike.domain.clone()),
+                ("protected_domain", lookalike.protected_domain.clone()),
+                ("kind", format!("{:?}", lookalike.kind).to_lowercase()),
+            ],
+        ));
+        if lookalike
+            .certificates
+            .as_ref()
+            .is_some_and(|c| c.recently_issued)
+        {
+            reasons.push(Reason::new(
+                "lookalike_fresh_certificate",
+                Severity::Critical,
+                &[("domain", lookalike.domain.clone())],
+            ));
+        }
+    }
+
+    for url in &evidence.body.urls {
+        if let Some(lookalike) = &url.lookalike {
+            reasons.push(Reason::new(
+                "url_lookalike",
+                Severity::High,
+                &[
+                    ("url", url.url.clone()),
+                    ("domain", lookalike.domain.clone()),
+                    ("protected_domain", lookalike.protected_domain.clone()),
+                ],
+            ));
+        }
+    }
+
+    if let Some(history) = &evidence.passive_dns
+        && (history.recently_created || history.changed_before_message)
+    {
+        reasons.push(Reason::new(
+            "recent_dns",
+            Severity::Medium,
+            &[("domain", domain.clone())],
+        ));
+    }
+
+    if let Some(registration) = &evidence.registration {
+        if let Some(rule) = &registration.abused_registrar {
+            let registrar = registration
+                .registrar
+                .as_ref()
+                .and_then(|r| r.name.clone())
+                .unwrap_or_else(|| rule.clone());
+            reasons.push(Reason::new(
+                "abused_registrar",
+                Severity::Medium,
+                &[("domain", domain.clone()), ("registrar", registrar)],
+            ));
+        }
+        if !registration.abused_nameservers.is_empty() {
+            reasons.push(Reason::new(
+                "abused_nameserver",
+                Severity::Medium,
+                &[
+                    ("domain", domain.clone()),
+                    ("nameservers", registration.abused_nameservers.join(", ")),
+                ],
+            ));
+        }
+    }
+
+    if let Some(timeline) = &evidence.received {
+        for anomaly in &timeline.anomalies {
+            let key = match anomaly.kind {
+                HopAnomalyKind::AfterDelivery => "received_after_delivery",
+                HopAnomalyKind::NegativeDelta => "received_negative_delta",
+                HopAnomalyKind::SlowTransit => "received_slow_transit",
+            };
+            reasons.push(Reason::new(
+                key,
+                Severity::Medium,
+                &[
+                    ("hop", timeline.hop_name(anomaly.hop)),
+                    ("delta", format_duration(anomaly.delta_secs)),
+                ],
+            ));
+        }
+    }
+
+    for check in &evidence.dkim_replay {
+        let signer = ("domain", check.domain.clone());
+        for signal in &check.signals {
+            reasons.push(match signal {
+                ReplaySignal::StaleSignature { age_secs } => Reason::new(
+                    "dkim_stale_signature",
+                    Severity::High,
+                    &[signer.clone(), ("age", format_duration(*age_secs))],
+                ),
+                ReplaySignal::UnsignedHeader { header } => Reason::new(
+                    "dkim_unsigned_header",
+                    Severity::Medium,
+                    &[signer.clone(), ("header", header.clone())],
+                ),
+                ReplaySignal::AddedHeader { header } => Reason::new(
+                    "dkim_added_header",
+                    Severity::Medium,
+                    &[signer.clone(), ("header", header.clone())],
+                ),
+                ReplaySignal::BodyLength {
+                    length,
+                    unsigned_bytes,
+                    canonicalization,
+                } => Reason::new(
+                    match unsigned_bytes {
+                        0 => "dkim_body_length",
+                        _ => "dkim_appended_content",
+                    },
+                    match unsigned_bytes {
+                        0 => Severity::Medium,
+                        _ => Severity::High,
+                    },
+                    &[
+                        signer.clone(),
+                        ("length", length.to_string()),
+                        ("unsigned_bytes", unsigned_bytes.to_string()),
+                        ("canonicalization", canonicalization.clone()),
+                    ],
+                ),
+            });
+        }
+    }
+
+    if let Some(session) = &evidence.session {
+        let helo = session.session.helo.clone().unwrap_or_default();
+        let client_ip = session.session.client_ip.clone().unwrap_or_default();
+        for problem in &session.helo_problems {
+            let key = match problem {
+                HeloProblem::NotFqdn => "helo_not_fqdn",
+                HeloProblem::IpMismatch => "helo_ip_mismatch",
+                HeloProblem::Nonexistent => "helo_nonexistent",
+            };
+            reasons.push(Reason::new(
+                key,
+                Severity::Medium,
+                &[("helo", helo.clone()), ("client_ip", client_ip.clone())],
+            ));
+        }
+        if session.session.tls == Some(false) {
+            reasons.push(Reason::new("session_plaintext", Severity::Info, &[]));
+        }
+    }
+
+    if let Some(envelope) = &evidence.envelope {
+        if let Some(srs) = &envelope.srs {
+            reasons.push(Reason::new(
+                "envelope_forwarded",
+                Severity::Info,
+                &[
+                    ("forwarders", srs.forwarders.join(", ")),
+                    ("original", srs.original.clone()),
+                ],
+            ));
+        }
+        let envelope_domain = ("envelope_domain", envelope.domain.clone());
+        if envelope.misaligned() {
+            reasons.push(Reason::new(
+                "envelope_misaligned",
+                Severity::Low,
+                &[envelope_domain, ("domain", domain.clone())],
+            ));
+        } else if let Some(esp) = envelope.esp.as_ref().filter(|_| !envelope.aligned) {
+            reasons.push(Reason::new(
+                "envelope_esp",
+                Severity::Info,
+                &[
+                    envelope_domain,
+                    ("domain", domain.clone()),
+                    ("esp", esp.clone()),
+                ],
+            ));
+        }
+    }
+
+    if let Some(automated) = &evidence.automated {
+        let kind = match automated.kind {
+            AutomatedKind::Bounce => "bounce",
+            AutomatedKind::AutoReply => "auto_reply",
+            AutomatedKind::AutoGenerated => "auto_generated",
+        };
+        reasons.push(Reason::new(
+            "automated_message",
+            Severity::Info,
+            &[
+                ("kind", kind.to_string()),
+                ("signals", automated.signals.join(", ")),
+            ],
+        ));
+    }
+
+    if let Some(anomaly) = &evidence.fingerprint_anomaly {
+        let usual = if anomaly.usual_mailers.is_empty() {
+            "unnamed mailers".to_string()
+        } else {
+            anomaly.usual_mailers.join(", ")
+        };
+        let mailer = evidence
+            .fingerprint
+            .mailer
+            .as_deref()
+            .unwrap_or("an unnamed mailer");
+        reasons.push(Reason::new(
+            "mailer_fingerprint_changed",
+            Severity::Medium,
+            &[
+                ("domain", domain.clone()),
+                ("usual", usual),
+                ("mailer", mailer.to_string()),
+            ],
+        ));
+    }
+
+    if let Some(text) = &evidence.body.text {
+        for m in &text.matches {
+            let category = m.category.label().to_string();
+            reasons.push(Reason::new(
+                "text_phrase",
+                Severity::Low,
+                &[("category", category), ("phrase", m.phrase.clone())],
+            ));
+        }
+    }
+
+    for disagreement in &evidence.dns_disagreements {
+        let answers = disagreement
+            .answers
+            .iter()
+            .map(|(backend, records)| {
+                let records = if records.is_empty() {
+                    "none".to_string()
+                } else {
+                    records.join(" | ")
+                };
+                format!("{}: {}", backend, records)
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        reasons.push(Reason::new(
+            "dns_disagreement",
+            Severity::High,
+            &[("name", disagreement.name.clone()), ("answers", answers)],
+        ));
+    }
+
+    if let Some(first) = evidence.parse_anomalies.first() {
+        reasons.push(Reason::new(
+            "malformed_message",
+            Severity::Medium,
+            &[
+                ("count", evidence.parse_anomalies.len().to_string()),
+                ("detail", first.detail.clone()),
+            ],
+        ));
+    }
+
+    for attachment in &evidence.attachments {
+        let name = attachment
+            .filename
+            .clone()
+            .unwrap_or_else(|| attachment.content_type.clone());
+        if let Some(first) = attachment.violations.first() {
+            reasons.push(Reason::new(
+                "attachment_limit_exceeded",
+                Severity::Medium,
+                &[("name", name.clone()), ("detail", first.detail.clone())],
+            ));
+        }
+        if let Some(office) = &attachment.office {
+            if office.macros {
+                reasons.push(Reason::new(
+                    "office_macros",
+                    Severity::High,
+                    &[("name", name.clone())],
+                ));
+            }
+            if !office.external_references.is_empty() {
+                let targets: Vec<&str> = office
+                    .external_references
+                    .iter()
+                    .map(|reference| reference.target.as_str())
+                    .collect();
+                reasons.push(Reason::new(
+                    "office_external_reference",
+                    Severity::High,
+                    &[("name", name.clone()), ("targets", targets.join(", "))],
+                ));
+            }
+            if !office.dde.is_empty() {
+                reasons.push(Reason::new(
+                    "office_dde",
+                    Severity::High,
+                    &[("name", name.clone()), ("commands", office.dde.join("; "))],
+                ));
+            }
+        }
+        if let Some(html) = &attachment.html {
+            reasons.push(Reason::new(
+                "html_attachment",
+                Severity::Medium,
+                &[("name", name.clone())],
+            ));
+            if html.smuggling {
+                let signals: Vec<String> = [
+                    (html.base64_blobs, "base64 payload"),
+                    (html.atob_calls, "atob"),
+                    (html.blob_constructors, "Blob"),
+                    (html.ms_save_blob, "msSaveBlob"),
+                    (html.object_urls, "createObjectURL"),
+                    (html.scripted_clicks, "scripted click"),
+                ]
+                .iter()
+                .filter(|(count, _)| *count > 0)
+                .map(|(count, signal)| format!("{} ×{}", signal, count))
+                .collect();
+                reasons.push(Reason::new(
+                    "html_smuggling",
+                    Severity::High,
+                    &[("name", name.clone()), ("signals", signals.join(", "))],
+                ));
+            }
+        }
+        if let Some(pdf) = &attachment.pdf {
+            if pdf.javascript {
+                reasons.push(Reason::new(
+                    "pdf_javascript",
+                    Severity::High,
+                    &[("name", name.clone())],
+                ));
+            }
+            if pdf.open_action {
+                reasons.push(Reason::new(
+                    "pdf_open_action",
+                    Severity::Low,
+                    &[("name", name.clone())],
+                ));
+            }
+            if pdf.embedded_files > 0 {
+                reasons.push(Reason::new(
+                    "pdf_embedded_file",
+                    Severity::Medium,
+                    &[
+                        ("name", name.clone()),
+                        ("count", pdf.embedded_files.to_string()),
+                    ],
+                ));
+            }
+        }
+        let Some(archive) = &attachment.archive else {
+            continue;
+        };
+        if !archive.executables.is_empty() {
+            reasons.push(Reason::new(
+                "archive_executable",
+                Severity::High,
+                &[
+                    ("name", name.clone()),
+                    ("files", archive.executables.join(", ")),
+                ],
+            ));
+        }
+        if archive.encrypted {
+            reasons.push(Reason::new(
+                "archive_encrypted",
+                Severity::Medium,
+                &[("name", name.clone())],
+            ));
+        }
+        if !archive.nested_archives.is_empty() {
+            reasons.push(Reason::new(
+                "archive_nested",
+                Severity::Low,
+                &[
+                    ("name", name),
+                    ("files", archive.nested_archives.join(", ")),
+                ],
+            ));
+        }
+    }
+
+    reasons
+}
+
+/// The highest severity among `reasons`, or `Info` when there are none
+pub fn max_severity(reasons: &[Reason]) -> Severity {
+    reasons.iter().map(|r| r.severity).max().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde_json::json;
+
+    use super::{Reason, Severity, max_severity};
+    use crate::{
+        dns::DnsSnapshot, email_verdict::analyze_email, messages::Lang, parse::parse_email,
+    };
+
+    /// A 7z archive with an uncompressed header listing `names`
+    fn sevenz(names: &[&str]) -> Vec<u8> {
+        let mut utf16 = vec![0];
+        for name in names {
+            for unit in name.encode_utf16().chain([0]) {
+                utf16.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        let mut header = vec![0x01, 0x05, names.len() as u8, 0x11, utf16.len() as u8];
+        header.extend_from_slice(&utf16);
+        header.extend_from_slice(&[0, 0]);
+        let mut data = b"7z\xbc\xaf\x27\x1c\x00\x04".to_vec();
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&header);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_explain_archive() {
+        let snapshot: DnsSnapshot = serde_json::from_value(json!({
+            "domains": {"example.com": {"spf": "v=spf1 -all", "dmarc": "v=DMARC1; p=reject", "exists": true}}
+        }))
+        .unwrap();
+        let raw = format!(
+            "From: ceo@example.com\r\nMIME-Version: 1.0\r\nContent-Type: multipart/mixed; boundary=b\r\n\r\n--b\r\nContent-Type: text/plain\r\n\r\nSee attached\r\n--b\r\nContent-Type: application/octet-stream\r\nContent-Disposition: attachment; filename=\"files.7z\"\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n--b--\r\n",
+            STANDARD.encode(sevenz(&["invoice.pdf.js", "more.zip", "old.rar"]))
+        );
+        let parsed = parse_email(raw.as_bytes()).unwrap();
+        let result = analyze_email(&parsed, &snapshot).await.unwrap();
+        let reason = |key: &str| result.reasons.iter().find(|r| r.key == key);
+
+        let executable = reason("archive_executable").unwrap();
+        assert_eq!(executable.rule_id, "ESD-0039");
+        assert_eq!(executable.severity, Severity::High);
+        assert_eq!(
+            executable.message,
+            "The archive files.7z contains files that run when opened: invoice.pdf.js."
+        );
+        let nested = reason("archive_nested").unwrap();
+        assert_eq!(nested.args["name"], "files.7z");
+        assert_eq!(nested.args["files"], "more.zip, old.rar");
+        assert!(reason("archive_encrypted").is_none());
+        assert_eq!(max_severity(&result.reasons), result.severity);
+    }
+
+    #[test]
+    fn test_reason() {
+        assert_eq!(max_severity(&[]), Severity::Info);
+
+        let mut reason = Reason::deadline_exceeded();
+        assert_eq!(reason.rule_id, "ESD-0030");
+        assert_eq!(max_severity(&[reason.clone()]), Severity::Medium);
+        reason.localize(Lang::De);
+        assert_eq!(
+            reason.message,
+            "Die Analyse wurde bei Fristablauf abgebrochen; das Ergebnis beruht auf unvollständigen Belegen."
+        );
+        assert!(Severity::Critical > Severity::High && Severity::Low > Severity::Info);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::parse::parse_time;
+
+/// Clock differences between relays tolerated before a negative delta is suspicious
+const CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// Longest plausible time between two relays; MTAs give up on queued mail after ~5 days
+const MAX_TRANSIT_SECS: i64 = 5 * 24 * 3600;
+
+/// One relay of the message, from a `Received` header
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ReceivedHop {
+    /// Host the relay received the message from, as it named itself
+    pub from: Option<String>,
+
+    /// The relay that added the header
+    pub by: Option<String>,
+
+    /// When the relay received the message, in Unix seconds
+    pub timestamp: Option<i64>,
+
+    /// Seconds since the previous dated hop
+    pub delta_secs: Option<i64>,
+}
+
+/// What is wrong with the timestamp of a hop
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HopAnomalyKind {
+    /// Dated after our own relay received the message, which no genuine relay can be
+    AfterDelivery,
+    /// Received well before the previous relay sent it
+    NegativeDelta,
+    /// Longer in transit from the previous relay than any queue keeps mail
+    SlowTransit,
+}
+
+/// A hop whose timestamp does not fit the chain
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HopAnomaly {
+    /// Index into `ReceivedTimeline::hops`
+    pub hop: usize,
+
+    pub kind: HopAnomalyKind,
+
+    /// Seconds the hop is off: after delivery, or from the previous dated hop
+    pub delta_secs: i64,
+}
+
+/// The relays of a message, oldest first, with their timestamp anomalies
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct ReceivedTimeline {
+    pub hops: Vec<ReceivedHop>,
+    pub anomalies: Vec<HopAnomaly>,
+}
+
+impl ReceivedTimeline {
+    /// Name of hop `index` for messages: its relay, or its position
+    pub fn hop_name(&self, index: usize) -> String {
+        self.hops
+            .get(index)
+            .and_then(|hop| hop.by.clone())
+            .unwrap_or_else(|| format!("hop {}", index + 1))
+    }
+}
+
+/// Parses one `Received` header: `from X (...) by Y with ...; <date>`
+pub fn parse_received(header: &str) -> ReceivedHop {
+    let (clauses, date) = match header.rsplit_once(';') {
+        Some((clauses, date)) => (clauses, parse_time(date)),
+        None => (header, None),
+    };
+    let token_after = |keyword: &str| {
+        let mut tokens = clauses.split_whitespace();
+        tokens.find(|t| t.eq_ignore_ascii_case(keyword))?;
+        tokens.next().map(|t| t.to_ascii_lowercase())
+    };
+    ReceivedHop {
+        from: token_after("from"),
+        by: token_after("by"),
+        timestamp: date,
+        delta_secs: None,
+    }
+}
+
+/// Builds the timeline of `headers`, given topmost (most recent) first as they appear
+///
+/// The topmost header is added by our own relay, so its timestamp is trusted as the
+/// time of delivery.
+pub fn received_timeline<S: AsRef<str>>(headers: &[S]) -> ReceivedTimeline {
+    let mut hops: Vec<ReceivedHop> = headers
+        .iter()
+        .rev()
+        .map(|header| parse_received(header.as_ref()))
+        .collect();
+    let mut anomalies = Vec::new();
+
+    let delivered = hops.last().and_then(|hop| hop.timestamp);
+    let mut after_delivery = vec![false; hops.len()];
+    for (index, hop) in hops.iter().enumerate().take(hops.len().saturating_sub(1)) {
+        if let (Some(delivered), Some(at)) = (delivered, hop.timestamp)
+            && at - delivered > CLOCK_SKEW_SECS
+        {
+            after_delivery[index] = true;
+            anomalies.push(HopAnomaly {
+                hop: index,
+                kind: HopAnomalyKind::AfterDelivery,
+                delta_secs: at - delivered,
+            });
+        }
+    }
+
+    // Deltas next to a hop dated after delivery only restate that anomaly
+    let mut previous: Option<usize> = None;
+    for index in 0..hops.len() {
+        let Some(at) = hops[index].timestamp else {
+            continue;
+        };
+        if let Some(prev) = previous {
+            let delta = at - hops[prev].timestamp.unwrap_or(at);
+            hops[index].delta_secs = Some(delta);
+            let kind = if delta < -CLOCK_SKEW_SECS {
+                Some(HopAnomalyKind::NegativeDelta)
+            } else if delta > MAX_TRANSIT_SECS {
+                Some(HopAnomalyKind::SlowTransit)
+            } else {
+                None
+            };
+            if let Some(kind) = kind
+                && !after_delivery[prev]
+                && !after_delivery[index]
+            {
+                anomalies.push(HopAnomaly {
+                    hop: index,
+                    kind,
+                    delta_secs: delta,
+                });
+            }
+        }
+        previous = Some(index);
+    }
+
+    anomalies.sort_by_key(|anomaly| anomaly.hop);
+    ReceivedTimeline { hops, anomalies }
+}
+
+/// Renders `secs` as e.g. `2d 3h`, `45m`, or `30s`, ignoring the sign
+pub fn format_duration(secs: i64) -> String {
+    let secs = secs.unsigned_abs();
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HopAnomalyKind, format_duration, parse_received, received_timeline};
+
+    #[test]
+    fn test_parse_received() {
+        let hop = parse_received(
+            "from mail.sender.test (mail.sender.test [192.0.2.1])\r\n by MX.Example.com with ESMTPS id abc;\r\n Mon, 02 Feb 2026 15:15:37 +0000",
+        );
+        assert_eq!(hop.from.as_deref(), Some("mail.sender.test"));
+        assert_eq!(hop.by.as_deref(), Some("mx.example.com"));
+        assert_eq!(hop.timestamp, Some(1_770_045_337));
+        assert_eq!(parse_received("by mx.example.com").timestamp, None);
+        assert_eq!(format_duration(-(2 * 86_400 + 3 * 3600 + 5)), "2d 3h");
+    }
+
+    #[test]
+    fn test_timeline_anomalies() {
+        // Topmost first: our relay, a forged relay dated after it, a relay "before" the
+        // origin, and the origin
+        let timeline = received_timeline(&[
+            "from relay2.test by mx.example.com; Mon, 02 Feb 2026 15:00:00 +0000",
+            "from relay1.test by relay2.test; Mon, 02 Feb 2026 18:00:00 +0000",
+            "from origin.test by relay1.test; Mon, 02 Feb 2026 10:00:00 +0000",
+            "from localhost by origin.test; Mon, 02 Feb 2026 12:00:00 +0000",
+        ]);
+        assert_eq!(timeline.hops[0].by.as_deref(), Some("origin.test"));
+        assert_eq!(timeline.hops[1].delta_secs, Some(-2 * 3600));
+        let kinds: Vec<_> = timeline.anomalies.iter().map(|a| (a.hop, a.kind)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, HopAnomalyKind::NegativeDelta),
+                (2, HopAnomalyKind::AfterDelivery)
+            ]
+        );
+
+        let genuine = received_timeline(&[
+            "from relay.test by mx.example.com; Mon, 02 Feb 2026 15:00:30 +0000",
+            "from origin.test by relay.test; Mon, 02 Feb 2026 15:00:40 +0000",
+            "by origin.test; Mon, 02 Feb 2026 15:00:00 +0000",
+        ]);
+        assert!(genuine.anomalies.is_empty());
+    }
+}
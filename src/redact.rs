@@ -0,0 +1,108 @@
+use serde_json::Value;
+
+/// Replacement of redacted message content
+pub const REDACTED: &str = "[redacted]";
+
+/// Fields holding message content, replaced as a whole
+const CONTENT_FIELDS: &[&str] = &["raw_email", "body"];
+
+/// Masks personal data in a serialized result or report so it can be shared externally
+///
+/// Local parts of email addresses become `***`, links keep only their scheme and host,
+/// and message content fields are replaced. Domains, IPs, and verdict data are kept.
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::String(text) => *text = redact_text(text),
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        Value::Object(fields) => {
+            for (key, value) in fields.iter_mut() {
+                if CONTENT_FIELDS.contains(&key.as_str()) && value.is_string() {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Redacts one string: a link is cut to its origin, anything else has the local parts
+/// of its addresses masked
+pub fn redact_text(text: &str) -> String {
+    match url::Url::parse(text) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {
+            let origin = url.origin().ascii_serialization();
+            let bare = url.path() == "/" && url.query().is_none() && url.fragment().is_none();
+            if bare {
+                format!("{}/", origin)
+            } else {
+                format!("{}/***", origin)
+            }
+        }
+        _ => mask_local_parts(text),
+    }
+}
+
+/// Replaces the local part of each `local@domain` in `text` with `***`
+fn mask_local_parts(text: &str) -> String {
+    let is_local = |c: char| c.is_ascii_alphanumeric() || "._%+-".contains(c);
+    let mut out = String::with_capacity(text.len());
+    let mut local = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_local(c) {
+            local.push(c);
+            continue;
+        }
+        let address = c == '@'
+            && !local.is_empty()
+            && chars
+                .peek()
+                .is_some_and(|next| next.is_ascii_alphanumeric());
+        out.push_str(if address { "***" } else { &local });
+        local.clear();
+        out.push(c);
+    }
+    out.push_str(&local);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::redact;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_keeps_domains_and_verdict() {
+        let mut result = json!({
+            "verdict": "Suspicious",
+            "raw_email": "RnJvbTog...",
+            "evidence": {
+                "from_domain": "evil.example",
+                "body": {"urls": [{
+                    "url": "https://evil.example/reset?user=alice@corp.example",
+                    "redirect_chain": ["https://landing.example/"],
+                }]},
+            },
+            "reasons": [{"args": {"entry": "ceo.name@freemail.example"},
+                         "message": "Sender ceo.name@freemail.example is blocklisted"}],
+        });
+        redact(&mut result);
+
+        assert_eq!(result["verdict"], "Suspicious");
+        assert_eq!(result["raw_email"], "[redacted]");
+        assert_eq!(result["evidence"]["from_domain"], "evil.example");
+        let url = &result["evidence"]["body"]["urls"][0];
+        assert_eq!(url["url"], "https://evil.example/***");
+        assert_eq!(url["redirect_chain"][0], "https://landing.example/");
+        assert_eq!(
+            result["reasons"][0]["args"]["entry"],
+            "***@freemail.example"
+        );
+        assert_eq!(
+            result["reasons"][0]["message"],
+            "Sender ***@freemail.example is blocklisted"
+        );
+    }
+}
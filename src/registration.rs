@@ -0,0 +1,217 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::{
+    ct::parse_timestamp,
+    http::{HttpFetcher, HttpRequest},
+};
+
+/// Public RDAP bootstrap redirector
+pub const RDAP_URL: &str = "https://rdap.org";
+
+/// Registrar of a domain as reported by RDAP
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct RegistrarInfo {
+    pub name: Option<String>,
+    /// IANA registrar ID
+    pub iana_id: Option<String>,
+    /// Unix timestamp the domain was registered
+    pub registered: Option<i64>,
+}
+
+/// Source of domain registration data
+#[async_trait]
+pub trait RegistrationProvider {
+    async fn registrar(&self, domain: &str) -> anyhow::Result<RegistrarInfo>;
+}
+
+/// RDAP client
+///
+/// Queries `{base_url}/domain/{domain}`.
+pub struct Rdap {
+    fetcher: Arc<dyn HttpFetcher>,
+    base_url: String,
+}
+
+impl Rdap {
+    pub fn new(base_url: &str, fetcher: Arc<dyn HttpFetcher>) -> Self {
+        Self {
+            fetcher,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl RegistrationProvider for Rdap {
+    async fn registrar(&self, domain: &str) -> anyhow::Result<RegistrarInfo> {
+        let url = format!("{}/domain/{}", self.base_url, domain);
+        let request = HttpRequest::get(url, Duration::from_secs(10))
+            .header("Accept", "application/rdap+json");
+        let body = self.fetcher.fetch(request).await?.text()?;
+        parse_rdap(&body)
+    }
+}
+
+/// Extracts the registrar and registration date from an RDAP domain response
+pub fn parse_rdap(body: &str) -> anyhow::Result<RegistrarInfo> {
+    let response: serde_json::Value = serde_json::from_str(body)?;
+
+    let registrar = response["entities"].as_array().and_then(|entities| {
+        entities.iter().find(|e| {
+            e["roles"]
+                .as_array()
+                .is_some_and(|roles| roles.iter().any(|r| r == "registrar"))
+        })
+    });
+
+    // vcardArray is ["vcard", [[name, params, type, value], ...]]
+    let name = registrar.and_then(|r| {
+        r["vcardArray"][1]
+            .as_array()?
+            .iter()
+            .find_map(|field| (field[0] == "fn").then(|| field[3].as_str().map(str::to_string))?)
+    });
+    let iana_id = registrar.and_then(|r| {
+        r["publicIds"].as_array()?.iter().find_map(|id| {
+            (id["type"] == "IANA Registrar ID")
+                .then(|| id["identifier"].as_str().map(str::to_string))?
+        })
+    });
+    let registered = response["events"].as_array().and_then(|events| {
+        events
+            .iter()
+            .find(|e| e["eventAction"] == "registration")
+            .and_then(|e| parse_timestamp(e["eventDate"].as_str()?))
+    });
+
+    Ok(RegistrarInfo {
+        name,
+        iana_id,
+        registered,
+    })
+}
+
+/// Registrars and name server providers known to be abused, for scoring
+///
+/// Loaded from JSON, e.g. `{"registrars": ["Example Registrar"], "nameservers": ["dns.example.net"]}`.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ReputationRules {
+    /// Registrar names or IANA IDs, matched case-insensitively against the name as a substring
+    /// or against the ID exactly
+    pub registrars: Vec<String>,
+
+    /// Name server host suffixes, e.g. `freedns.example` matches `ns1.freedns.example`
+    pub nameservers: Vec<String>,
+}
+
+impl ReputationRules {
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Who registered the sender domain and who serves its DNS
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct RegistrationFindings {
+    /// Authoritative name servers, sorted
+    pub nameservers: Vec<String>,
+
+    /// Registrar data, when the RDAP lookup succeeded
+    pub registrar: Option<RegistrarInfo>,
+
+    /// The rule the registrar matched, if any
+    pub abused_registrar: Option<String>,
+
+    /// Name servers matching an abused-provider rule
+    pub abused_nameservers: Vec<String>,
+}
+
+/// Applies the reputation rules to the collected registration data
+pub fn evaluate_registration(
+    nameservers: Vec<String>,
+    registrar: Option<RegistrarInfo>,
+    rules: &ReputationRules,
+) -> RegistrationFindings {
+    let abused_registrar = registrar.as_ref().and_then(|info| {
+        let name = info.name.as_deref().unwrap_or_default().to_lowercase();
+        rules
+            .registrars
+            .iter()
+            .find(|rule| {
+                let rule = rule.to_lowercase();
+                (!name.is_empty() && name.contains(&rule)) || info.iana_id.as_deref() == Some(&rule)
+            })
+            .cloned()
+    });
+
+    let abused_nameservers = nameservers
+        .iter()
+        .filter(|ns| {
+            rules.nameservers.iter().any(|rule| {
+                let rule = rule.trim_end_matches('.').to_lowercase();
+                ns.strip_suffix(rule.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.ends_with('.'))
+            })
+        })
+        .cloned()
+        .collect();
+
+    RegistrationFindings {
+        nameservers,
+        registrar,
+        abused_registrar,
+        abused_nameservers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReputationRules, evaluate_registration, parse_rdap};
+
+    #[test]
+    fn test_rdap_registrar_and_reputation_rules() {
+        let body = r#"{
+            "objectClassName": "domain",
+            "ldhName": "paypa1.com",
+            "events": [
+                {"eventAction": "registration", "eventDate": "2026-02-01T10:00:00Z"},
+                {"eventAction": "expiration", "eventDate": "2027-02-01T10:00:00Z"}
+            ],
+            "entities": [
+                {"roles": ["abuse"], "vcardArray": ["vcard", [["fn", {}, "text", "Abuse Desk"]]]},
+                {"roles": ["registrar"],
+                 "publicIds": [{"type": "IANA Registrar ID", "identifier": "9999"}],
+                 "vcardArray": ["vcard", [["version", {}, "text", "4.0"], ["fn", {}, "text", "Cheap Names LLC"]]]}
+            ]
+        }"#;
+        let info = parse_rdap(body).unwrap();
+        assert_eq!(info.name.as_deref(), Some("Cheap Names LLC"));
+        assert_eq!(info.iana_id.as_deref(), Some("9999"));
+        assert_eq!(info.registered, Some(1_769_940_000));
+
+        let rules: ReputationRules = serde_json::from_str(
+            r#"{"registrars": ["cheap names"], "nameservers": ["freedns.example"]}"#,
+        )
+        .unwrap();
+        let nameservers = vec![
+            "ns1.freedns.example".to_string(),
+            "ns1.notfreedns.example".to_string(),
+        ];
+        let findings = evaluate_registration(nameservers, Some(info), &rules);
+        assert_eq!(findings.abused_registrar.as_deref(), Some("cheap names"));
+        assert_eq!(findings.abused_nameservers, vec!["ns1.freedns.example"]);
+
+        // Raw data is kept even when no rule fires
+        let quiet = evaluate_registration(
+            vec!["ns1.example.net".to_string()],
+            None,
+            &ReputationRules::default(),
+        );
+        assert!(quiet.abused_registrar.is_none() && quiet.abused_nameservers.is_empty());
+        assert_eq!(quiet.nameservers, vec!["ns1.example.net"]);
+    }
+}
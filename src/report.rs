@@ -0,0 +1,299 @@
+use std::fmt::Write;
+
+use crate::{email_verdict::AnalysisResult, parse::EmailParsed};
+
+/// Renders the analysis of one message as a Markdown incident summary, for pasting
+/// into tickets
+///
+/// Header values and DNS records are quoted verbatim in code spans, so nothing the
+/// sender controls is rendered as Markdown, and links are defanged (`hxxps://...`) so
+/// they cannot be clicked by accident.
+pub fn incident_markdown(parsed: &EmailParsed, result: &AnalysisResult) -> String {
+    let evidence = &result.evidence;
+    let mut md = String::new();
+    let subject = parsed.subject.as_deref().unwrap_or("(no subject)");
+    let _ = writeln!(md, "# Incident summary: {}\n", code(subject));
+    let _ = writeln!(md, "- **Verdict:** {:?}", result.verdict);
+    let _ = writeln!(
+        md,
+        "- **Risk score:** {} (severity {:?})",
+        result.risk_score, result.severity
+    );
+    let _ = writeln!(md, "- **Analysis ID:** {}", code(&result.id));
+    if result.partial {
+        let _ = writeln!(md, "- **Partial:** the analysis stopped at its deadline");
+    }
+
+    md.push_str("\n## Claimed sender\n\n");
+    for (name, value) in [
+        ("From", parsed.from.as_deref()),
+        ("Reply-To", parsed.reply_to.as_deref()),
+        ("Return-Path", parsed.return_path.as_deref()),
+        ("Date", parsed.date.as_deref()),
+        ("Sending IP", parsed.client_ip.as_deref()),
+    ] {
+        if let Some(value) = value {
+            let _ = writeln!(md, "- **{}:** {}", name, code(value));
+        }
+    }
+    if let Some(labels) = evidence.from_domain_labels.as_ref()
+        && labels.contains_non_ascii
+    {
+        let _ = writeln!(
+            md,
+            "- **From domain:** {} (displayed as {})",
+            code(&labels.a_label),
+            code(&labels.u_label)
+        );
+    }
+
+    md.push_str("\n## Key evidence\n\n");
+    if result.reasons.is_empty() {
+        md.push_str("No findings.\n");
+    }
+    for reason in &result.reasons {
+        let _ = writeln!(
+            md,
+            "- **[{:?}] {}** {}",
+            reason.severity, reason.rule_id, reason.message
+        );
+    }
+    for reason in &result.suppressed {
+        let _ = writeln!(md, "- *Suppressed:* {} {}", reason.rule_id, reason.message);
+    }
+
+    md.push_str("\n## DNS records\n");
+    let domain = evidence.from_domain.as_deref().unwrap_or("(unknown)");
+    for (name, record) in [
+        (domain.to_string(), evidence.spf_policy.as_deref()),
+        (
+            format!("_dmarc.{}", domain),
+            evidence.dmarc_policy.as_deref(),
+        ),
+    ] {
+        let record = record.unwrap_or("(no record)");
+        let fence = "`".repeat(longest_backtick_run(record).max(2) + 1);
+        let _ = writeln!(md, "\n{fence}\n{} TXT {}\n{fence}", name, record);
+    }
+    if let Some(upstream) = &evidence.upstream_auth {
+        let _ = writeln!(
+            md,
+            "\nAuthentication results of {}: spf={}, dkim={}, dmarc={}",
+            code(&upstream.authserv_id),
+            upstream.spf.as_deref().unwrap_or("none"),
+            upstream.dkim.as_deref().unwrap_or("none"),
+            upstream.dmarc.as_deref().unwrap_or("none")
+        );
+    }
+
+    if !evidence.body.urls.is_empty() {
+        md.push_str("\n## Links\n\n| Link | Lands on | Imitates |\n| --- | --- | --- |\n");
+        for url in &evidence.body.urls {
+            let landing = url
+                .landing_domain_labels
+                .as_ref()
+                .map(|labels| code(&labels.u_label))
+                .unwrap_or_default();
+            let imitates = url
+                .lookalike
+                .as_ref()
+                .map(|lookalike| code(&lookalike.protected_domain))
+                .unwrap_or_default();
+            let _ = writeln!(
+                md,
+                "| {} | {} | {} |",
+                code(&defang(&url.url)).replace('|', "\\|"),
+                landing,
+                imitates
+            );
+        }
+    }
+
+    if let Some(timeline) = evidence.received.as_ref().filter(|t| !t.hops.is_empty()) {
+        md.push_str("\n## Relay path\n\nOldest first:\n\n");
+        for hop in &timeline.hops {
+            let _ = writeln!(
+                md,
+                "1. {} by {}",
+                code(hop.from.as_deref().unwrap_or("?")),
+                code(hop.by.as_deref().unwrap_or("?"))
+            );
+        }
+    }
+    md
+}
+
+/// Wraps `text` in a code span whose fence outlasts any backticks inside it
+pub(crate) fn code(text: &str) -> String {
+    let text = text.replace(['\r', '\n'], " ");
+    let fence = "`".repeat(longest_backtick_run(&text) + 1);
+    let pad = if text.starts_with('`') || text.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{fence}{pad}{text}{pad}{fence}")
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default()
+}
+
+/// Makes a link unclickable: `https://a.example/x` becomes `hxxps://a[.]example/x`
+fn defang(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let scheme = scheme.replacen("http", "hxxp", 1);
+    let host = host.replace('.', "[.]");
+    match scheme.as_str() {
+        "" => format!("{host}{path}"),
+        _ => format!("{scheme}://{host}{path}"),
+    }
+}
+
+/// Renders an incident summary from [`incident_markdown`] as a PDF
+///
+/// Headings are set in bold and code blocks in a monospaced font; other Markdown is
+/// printed as written. Characters outside Latin-1 are replaced by `?`, as only the
+/// standard PDF fonts are used.
+#[cfg(feature = "pdf")]
+pub fn incident_pdf(markdown: &str) -> Vec<u8> {
+    const WIDTH: usize = 90;
+    const LINES_PER_PAGE: usize = 60;
+
+    // (font, size, text) per output line
+    let mut lines: Vec<(&str, u32, String)> = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        let (font, size, text) = match line.trim_start_matches('#') {
+            heading if !in_code && heading.len() < line.len() => ("F2", 13, heading.trim()),
+            _ if in_code => ("F3", 9, line),
+            _ => ("F1", 10, line),
+        };
+        let chars: Vec<char> = text.chars().collect();
+        if chars.is_empty() {
+            lines.push((font, size, String::new()));
+        }
+        for chunk in chars.chunks(WIDTH) {
+            lines.push((font, size, chunk.iter().collect()));
+        }
+    }
+
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        String::new(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    let mut pages = Vec::new();
+    for page in lines.chunks(LINES_PER_PAGE).collect::<Vec<_>>().iter() {
+        let mut content = String::from("BT 50 800 Td 13 TL\n");
+        for (font, size, text) in page.iter() {
+            let _ = writeln!(content, "/{} {} Tf ({}) '", font, size, pdf_string(text));
+        }
+        content.push_str("ET");
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}\nendstream",
+            content.chars().count(),
+            content
+        ));
+        let contents = objects.len();
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Contents {} 0 R \
+             /Resources << /Font << /F1 3 0 R /F2 4 0 R /F3 5 0 R >> >> >>",
+            contents
+        ));
+        pages.push(format!("{} 0 R", objects.len()));
+    }
+    objects[1] = format!(
+        "<< /Type /Pages /Kids [{}] /Count {} >>",
+        pages.join(" "),
+        pages.len()
+    );
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).bytes());
+        pdf.extend(object.chars().map(|c| c as u8));
+        pdf.extend(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(trailer, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    );
+    pdf.extend(trailer.bytes());
+    pdf
+}
+
+/// Escapes `text` as the body of a PDF literal string, as Latin-1
+#[cfg(feature = "pdf")]
+fn pdf_string(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{}", c),
+            c if (' '..='\u{ff}').contains(&c) && c != '\u{7f}' => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{code, defang, incident_markdown};
+    use crate::{dns::DnsSnapshot, email_verdict::analyze_email, parse::parse_email};
+
+    #[tokio::test]
+    async fn test_incident_markdown() {
+        let raw = b"From: \"Bank `Support`\" <alerts@bank.example>\r\n\
+                    Subject: Verify your account\r\n\r\n\
+                    Log in at https://bank.example.evil.test/login today";
+        let parsed = parse_email(raw).unwrap();
+        let dns: DnsSnapshot = serde_json::from_value(serde_json::json!({"domains": {
+            "bank.example": {"spf": "v=spf1 -all", "dmarc": "v=DMARC1; p=reject", "exists": true}
+        }}))
+        .unwrap();
+        let result = analyze_email(&parsed, &dns).await.unwrap();
+        let md = incident_markdown(&parsed, &result);
+
+        assert!(md.starts_with("# Incident summary: `Verify your account`"));
+        assert!(md.contains("- **From:** ``\"Bank `Support`\" <alerts@bank.example>``"));
+        assert!(md.contains("bank.example TXT v=spf1 -all"));
+        assert!(md.contains("_dmarc.bank.example TXT v=DMARC1; p=reject"));
+        assert!(md.contains("- **[Low] ESD-0003** The message carries no DKIM signature."));
+        assert!(md.contains("`hxxps://bank[.]example[.]evil[.]test/login`"));
+
+        assert_eq!(code("a`b"), "``a`b``");
+        assert_eq!(defang("http://x.test"), "hxxp://x[.]test");
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_incident_pdf() {
+        let pdf = super::incident_pdf("# Incident (1)\n\n```\nexample.com TXT v=spf1 -all\n```\n");
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.contains("(Incident \\(1\\)) '"));
+        assert!(text.contains("/F3 9 Tf (example.com TXT v=spf1 -all) '"));
+        assert!(text.ends_with("%%EOF\n"));
+    }
+}
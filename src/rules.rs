@@ -0,0 +1,357 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    dkim_replay::ReplaySignal, email_verdict::Evidence, parse::parse_time,
+    received::HopAnomalyKind, session::HeloProblem,
+};
+
+/// Stable identifiers of the reason keys
+///
+/// IDs are never renumbered or reused, so they can be referenced in documentation,
+/// tickets, and tuning notes; new rules are appended.
+const RULES: &[(&str, &str)] = &[
+    ("ESD-0001", "domain_invalid"),
+    ("ESD-0002", "no_authentication"),
+    ("ESD-0003", "dkim_missing"),
+    ("ESD-0004", "spf_not_strict"),
+    ("ESD-0005", "dmarc_reject_misaligned"),
+    ("ESD-0006", "upstream_dmarc"),
+    ("ESD-0007", "lookalike"),
+    ("ESD-0008", "lookalike_fresh_certificate"),
+    ("ESD-0009", "url_lookalike"),
+    ("ESD-0010", "recent_dns"),
+    ("ESD-0011", "unregistered_infrastructure"),
+    ("ESD-0012", "abused_registrar"),
+    ("ESD-0013", "abused_nameserver"),
+    ("ESD-0014", "blocklisted_sender"),
+    ("ESD-0015", "allowlisted_sender"),
+    ("ESD-0016", "vip_impersonation"),
+    ("ESD-0017", "received_after_delivery"),
+    ("ESD-0018", "received_negative_delta"),
+    ("ESD-0019", "received_slow_transit"),
+    ("ESD-0020", "dkim_stale_signature"),
+    ("ESD-0021", "dkim_unsigned_header"),
+    ("ESD-0022", "dkim_added_header"),
+    ("ESD-0023", "dkim_body_length"),
+    ("ESD-0024", "dkim_appended_content"),
+    ("ESD-0025", "helo_not_fqdn"),
+    ("ESD-0026", "helo_ip_mismatch"),
+    ("ESD-0027", "helo_nonexistent"),
+    ("ESD-0028", "session_plaintext"),
+    ("ESD-0029", "text_phrase"),
+    ("ESD-0030", "deadline_exceeded"),
+    ("ESD-0031", "malformed_message"),
+    ("ESD-0032", "dns_disagreement"),
+    ("ESD-0033", "envelope_forwarded"),
+    ("ESD-0034", "envelope_misaligned"),
+    ("ESD-0035", "envelope_esp"),
+    ("ESD-0036", "mailer_fingerprint_changed"),
+    ("ESD-0037", "automated_message"),
+    ("ESD-0038", "attachment_limit_exceeded"),
+    ("ESD-0039", "archive_executable"),
+    ("ESD-0040", "archive_encrypted"),
+    ("ESD-0041", "archive_nested"),
+    ("ESD-0042", "office_macros"),
+    ("ESD-0043", "office_external_reference"),
+    ("ESD-0044", "office_dde"),
+    ("ESD-0045", "pdf_javascript"),
+    ("ESD-0046", "pdf_open_action"),
+    ("ESD-0047", "pdf_embedded_file"),
+    ("ESD-0048", "html_attachment"),
+    ("ESD-0049", "html_smuggling"),
+];
+
+/// The rule ID of a reason key, e.g. `ESD-0001` for `domain_invalid`
+pub fn rule_id(key: &str) -> Option<&'static str> {
+    RULES.iter().find(|(_, k)| *k == key).map(|(id, _)| *id)
+}
+
+/// The reason key of a rule ID
+pub fn rule_key(id: &str) -> Option<&'static str> {
+    RULES
+        .iter()
+        .find(|(i, _)| i.eq_ignore_ascii_case(id))
+        .map(|(_, key)| *key)
+}
+
+/// All rules as (ID, reason key), in ID order
+pub fn rules() -> &'static [(&'static str, &'static str)] {
+    RULES
+}
+
+/// Rules switched off by the operator
+///
+/// Disabled rules are off for every message. Suppressions switch a rule off for one
+/// sender domain, and its subdomains, until they expire, e.g. for a known-noisy sender
+/// that should not be allowlisted outright.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RuleSettings {
+    /// Rule IDs, e.g. `ESD-0029`
+    pub disabled: BTreeSet<String>,
+    pub suppressions: Vec<Suppression>,
+}
+
+/// One rule switched off for one sender domain
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Suppression {
+    pub rule_id: String,
+    /// `From` domain the rule is suppressed for, including its subdomains
+    pub domain: String,
+    /// End of the suppression, in any form `parse::parse_time` accepts
+    pub expires: String,
+    /// Why the rule is suppressed
+    pub justification: String,
+}
+
+impl Suppression {
+    /// Checks that the rule exists, the expiry parses, and a justification is given
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if rule_key(&self.rule_id).is_none() {
+            anyhow::bail!("Unknown rule {}", self.rule_id);
+        }
+        if parse_time(&self.expires).is_none() {
+            anyhow::bail!("Unrecognized expiry {}", self.expires);
+        }
+        if self.justification.trim().is_empty() || self.domain.trim().is_empty() {
+            anyhow::bail!("A suppression needs a domain and a justification");
+        }
+        Ok(())
+    }
+
+    fn covers(&self, from_domain: &str, now: i64) -> bool {
+        let domain = self.domain.trim_end_matches('.').to_ascii_lowercase();
+        let from_domain = from_domain.to_ascii_lowercase();
+        parse_time(&self.expires).is_some_and(|expires| now < expires)
+            && (from_domain == domain || from_domain.ends_with(&format!(".{}", domain)))
+    }
+}
+
+impl RuleSettings {
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the settings to `path`, replacing the file atomically
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Reason keys switched off for mail from `from_domain` at `now` (Unix seconds)
+    pub fn inactive_keys(&self, from_domain: Option<&str>, now: i64) -> Vec<&'static str> {
+        RULES
+            .iter()
+            .filter(|(id, _)| {
+                self.disabled.iter().any(|d| d.eq_ignore_ascii_case(id))
+                    || self.suppressions.iter().any(|s| {
+                        s.rule_id.eq_ignore_ascii_case(id)
+                            && from_domain.is_some_and(|domain| s.covers(domain, now))
+                    })
+            })
+            .map(|(_, key)| *key)
+            .collect()
+    }
+}
+
+/// Removes the evidence that triggers the rule `key`, so it neither explains nor
+/// scores a result
+///
+/// Rules explaining the authentication verdict itself (`domain_invalid`, `spf_not_strict`,
+/// ...) leave the evidence alone: the verdict stands, and only the reason is dropped.
+pub(crate) fn clear_evidence(evidence: &mut Evidence, key: &str) {
+    match key {
+        "lookalike" => evidence.lookalike = None,
+        "lookalike_fresh_certificate" => {
+            if let Some(lookalike) = &mut evidence.lookalike {
+                lookalike.certificates = None;
+            }
+        }
+        "url_lookalike" => {
+            for url in &mut evidence.body.urls {
+                url.lookalike = None;
+            }
+        }
+        "recent_dns" => evidence.passive_dns = None,
+        "unregistered_infrastructure" => evidence.infrastructure = None,
+        "abused_registrar" | "abused_nameserver" => {
+            if let Some(registration) = &mut evidence.registration {
+                match key {
+                    "abused_registrar" => registration.abused_registrar = None,
+                    _ => registration.abused_nameservers.clear(),
+                }
+            }
+        }
+        "blocklisted_sender" | "allowlisted_sender" | "vip_impersonation" => {
+            if let Some(lists) = &mut evidence.lists {
+                match key {
+                    "blocklisted_sender" => lists.blocklisted = None,
+                    "allowlisted_sender" => lists.allowlisted = None,
+                    _ => lists.vip_impersonation = None,
+                }
+            }
+        }
+        "received_after_delivery" | "received_negative_delta" | "received_slow_transit" => {
+            if let Some(timeline) = &mut evidence.received {
+                timeline.anomalies.retain(|anomaly| {
+                    key != match anomaly.kind {
+                        HopAnomalyKind::AfterDelivery => "received_after_delivery",
+                        HopAnomalyKind::NegativeDelta => "received_negative_delta",
+                        HopAnomalyKind::SlowTransit => "received_slow_transit",
+                    }
+                });
+            }
+        }
+        "dkim_stale_signature"
+        | "dkim_unsigned_header"
+        | "dkim_added_header"
+        | "dkim_body_length"
+        | "dkim_appended_content" => {
+            for check in &mut evidence.dkim_replay {
+                check.signals.retain(|signal| {
+                    key != match signal {
+                        ReplaySignal::StaleSignature { .. } => "dkim_stale_signature",
+                        ReplaySignal::UnsignedHeader { .. } => "dkim_unsigned_header",
+                        ReplaySignal::AddedHeader { .. } => "dkim_added_header",
+                        ReplaySignal::BodyLength {
+                            unsigned_bytes: 0, ..
+                        } => "dkim_body_length",
+                        ReplaySignal::BodyLength { .. } => "dkim_appended_content",
+                    }
+                });
+            }
+            evidence
+                .dkim_replay
+                .retain(|check| !check.signals.is_empty());
+        }
+        "helo_not_fqdn" | "helo_ip_mismatch" | "helo_nonexistent" => {
+            if let Some(session) = &mut evidence.session {
+                session.helo_problems.retain(|problem| {
+                    key != match problem {
+                        HeloProblem::NotFqdn => "helo_not_fqdn",
+                        HeloProblem::IpMismatch => "helo_ip_mismatch",
+                        HeloProblem::Nonexistent => "helo_nonexistent",
+                    }
+                });
+            }
+        }
+        "text_phrase" => evidence.body.text = None,
+        "malformed_message" => evidence.parse_anomalies.clear(),
+        "attachment_limit_exceeded" => {
+            for attachment in &mut evidence.attachments {
+                attachment.violations.clear();
+            }
+        }
+        "archive_executable" | "archive_encrypted" | "archive_nested" => {
+            for archive in evidence
+                .attachments
+                .iter_mut()
+                .filter_map(|a| a.archive.as_mut())
+            {
+                match key {
+                    "archive_executable" => archive.executables.clear(),
+                    "archive_encrypted" => archive.encrypted = false,
+                    _ => archive.nested_archives.clear(),
+                }
+            }
+        }
+        "office_macros" | "office_external_reference" | "office_dde" => {
+            for office in evidence
+                .attachments
+                .iter_mut()
+                .filter_map(|a| a.office.as_mut())
+            {
+                match key {
+                    "office_macros" => office.macros = false,
+                    "office_external_reference" => office.external_references.clear(),
+                    _ => office.dde.clear(),
+                }
+            }
+        }
+        "pdf_javascript" | "pdf_open_action" | "pdf_embedded_file" => {
+            for pdf in evidence
+                .attachments
+                .iter_mut()
+                .filter_map(|a| a.pdf.as_mut())
+            {
+                match key {
+                    "pdf_javascript" => pdf.javascript = false,
+                    "pdf_open_action" => pdf.open_action = false,
+                    _ => pdf.embedded_files = 0,
+                }
+            }
+        }
+        "html_attachment" => {
+            for attachment in &mut evidence.attachments {
+                if attachment.html.as_ref().is_some_and(|html| !html.smuggling) {
+                    attachment.html = None;
+                }
+            }
+        }
+        "html_smuggling" => {
+            for html in evidence
+                .attachments
+                .iter_mut()
+                .filter_map(|a| a.html.as_mut())
+            {
+                html.smuggling = false;
+            }
+        }
+        "dns_disagreement" => evidence.dns_disagreements.clear(),
+        "mailer_fingerprint_changed" => evidence.fingerprint_anomaly = None,
+        "envelope_forwarded" | "envelope_misaligned" => {
+            if let Some(envelope) = &mut evidence.envelope {
+                match key {
+                    "envelope_forwarded" => envelope.srs = None,
+                    _ => envelope.aligned = true,
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RULES, RuleSettings, rule_id, rule_key};
+    use serde_json::json;
+
+    #[test]
+    fn test_rule_ids_are_unique() {
+        for (i, (id, key)) in RULES.iter().enumerate() {
+            assert_eq!(*id, format!("ESD-{:04}", i + 1), "{} out of sequence", key);
+            assert_eq!(rule_id(key), Some(*id), "{} listed twice", key);
+        }
+        assert_eq!(rule_key("esd-0007"), Some("lookalike"));
+    }
+
+    #[test]
+    fn test_inactive_rules() {
+        let settings: RuleSettings = serde_json::from_value(json!({
+            "disabled": ["ESD-0029"],
+            "suppressions": [
+                {"rule_id": "ESD-0021", "domain": "news.test", "expires": "2026-02-01T00:00:00",
+                 "justification": "ESP does not sign To"}
+            ]
+        }))
+        .unwrap();
+        let jan = 1_767_225_600 + 86_400;
+        let mar = 1_772_323_200;
+
+        assert_eq!(
+            settings.inactive_keys(Some("mail.news.test"), jan),
+            vec!["dkim_unsigned_header", "text_phrase"]
+        );
+        assert_eq!(
+            settings.inactive_keys(Some("news.test"), mar),
+            vec!["text_phrase"]
+        );
+        assert_eq!(
+            settings.inactive_keys(Some("other.test"), jan),
+            vec!["text_phrase"]
+        );
+        assert!(settings.suppressions[0].validate().is_ok());
+    }
+}
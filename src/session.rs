@@ -0,0 +1,104 @@
+use std::net::IpAddr;
+
+use crate::dns::ResolverTrait;
+
+/// SMTP session data reported by the receiving MTA
+///
+/// Unlike the headers, which the sender writes, these are what the MTA observed.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SmtpSession {
+    /// Address of the connecting client
+    pub client_ip: Option<String>,
+
+    /// Name the client gave in HELO/EHLO
+    pub helo: Option<String>,
+
+    /// Envelope sender (`MAIL FROM`), without angle brackets; empty for bounces
+    pub mail_from: Option<String>,
+
+    /// Whether the session was encrypted with STARTTLS
+    pub tls: Option<bool>,
+}
+
+/// What is wrong with a HELO name
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeloProblem {
+    /// Not a fully qualified domain name, e.g. `localhost` or `DESKTOP-1234`
+    NotFqdn,
+    /// An address literal other than the client's address
+    IpMismatch,
+    /// A domain name that does not exist
+    Nonexistent,
+}
+
+/// The session the message arrived in, with the problems found in it
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SessionEvidence {
+    #[serde(flatten)]
+    pub session: SmtpSession,
+
+    pub helo_problems: Vec<HeloProblem>,
+}
+
+/// Checks the HELO name of `session` for names no properly configured MTA uses
+pub async fn check_session<R: ResolverTrait + Sync>(
+    session: &SmtpSession,
+    dns: &R,
+) -> SessionEvidence {
+    let mut helo_problems = Vec::new();
+    if let Some(helo) = session.helo.as_deref().map(str::trim) {
+        let client_ip: Option<IpAddr> = session.client_ip.as_deref().and_then(|ip| ip.parse().ok());
+        if let Some(literal) = helo.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+            let literal: Option<IpAddr> = literal.trim_start_matches("IPv6:").parse().ok();
+            if client_ip.is_some() && literal != client_ip {
+                helo_problems.push(HeloProblem::IpMismatch);
+            }
+        } else if !helo.trim_end_matches('.').contains('.') {
+            helo_problems.push(HeloProblem::NotFqdn);
+        } else if !dns.domain_exists(helo).await {
+            helo_problems.push(HeloProblem::Nonexistent);
+        }
+    }
+    SessionEvidence {
+        session: session.clone(),
+        helo_problems,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeloProblem, SmtpSession, check_session};
+    use crate::dns::DnsSnapshot;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_helo_problems() {
+        let dns: DnsSnapshot = serde_json::from_value(json!({
+            "domains": {"mail.sender.test": {"exists": true}}
+        }))
+        .unwrap();
+        let problems = |helo: &str| {
+            let session = SmtpSession {
+                client_ip: Some("192.0.2.1".to_string()),
+                helo: Some(helo.to_string()),
+                ..SmtpSession::default()
+            };
+            let dns = dns.clone();
+            async move { check_session(&session, &dns).await.helo_problems }
+        };
+
+        assert!(problems("mail.sender.test").await.is_empty());
+        assert!(problems("[192.0.2.1]").await.is_empty());
+        assert_eq!(
+            problems("[192.0.2.99]").await,
+            vec![HeloProblem::IpMismatch]
+        );
+        assert_eq!(problems("DESKTOP-1234").await, vec![HeloProblem::NotFqdn]);
+        assert_eq!(
+            problems("mx.gone.test").await,
+            vec![HeloProblem::Nonexistent]
+        );
+    }
+}
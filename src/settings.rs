@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// Settings a config file can hold, by section and key, with the environment variable
+/// each one stands for
+pub const SETTINGS: &[(&str, &str, &str)] = &[
+    ("server", "host", "HOST"),
+    ("server", "port", "PORT"),
+    ("server", "shutdown_timeout_secs", "SHUTDOWN_TIMEOUT_SECS"),
+    ("server", "inbound_max_bytes", "INBOUND_MAX_BYTES"),
+    ("server", "redact", "REDACT"),
+    ("limits", "workers", "ANALYSIS_WORKERS"),
+    ("limits", "queue", "ANALYSIS_QUEUE"),
+    ("limits", "retry_after_secs", "RETRY_AFTER_SECS"),
+    ("limits", "deadline_ms", "ANALYSIS_DEADLINE_MS"),
+    ("limits", "dedup_cache_size", "DEDUP_CACHE_SIZE"),
+    ("limits", "attachment_max_bytes", "ATTACHMENT_MAX_BYTES"),
+    (
+        "limits",
+        "attachment_max_decompressed",
+        "ATTACHMENT_MAX_DECOMPRESSED",
+    ),
+    ("limits", "attachment_max_ratio", "ATTACHMENT_MAX_RATIO"),
+    ("limits", "attachment_timeout_ms", "ATTACHMENT_TIMEOUT_MS"),
+    ("limits", "attachment_office", "ATTACHMENT_OFFICE"),
+    ("dns", "backends", "DNS_BACKENDS"),
+    ("dns", "cross_check", "DNS_CROSS_CHECK"),
+    ("dns", "zone_rate", "DNS_ZONE_RATE"),
+    ("dns", "zone_burst", "DNS_ZONE_BURST"),
+    ("dns", "max_in_flight", "DNS_MAX_IN_FLIGHT"),
+    ("dns", "zone_max_in_flight", "DNS_ZONE_MAX_IN_FLIGHT"),
+    ("dns", "prefetch_domains", "PREFETCH_DOMAINS"),
+    ("auth", "admin_token", "ADMIN_TOKEN"),
+    ("auth", "admin_tokens", "ADMIN_TOKENS"),
+    ("auth", "tenants", "TENANTS"),
+    ("auth", "result_signing_key", "RESULT_SIGNING_KEY"),
+    ("auth", "audit_log", "AUDIT_LOG"),
+    ("analysis", "trusted_authserv_ids", "TRUSTED_AUTHSERV_IDS"),
+    ("analysis", "protected_domains", "PROTECTED_DOMAINS"),
+    ("analysis", "trust_store", "TRUST_STORE"),
+    ("analysis", "text_heuristics", "TEXT_HEURISTICS"),
+    ("analysis", "phrases_file", "PHRASES_FILE"),
+    ("analysis", "sender_lists", "SENDER_LISTS"),
+    ("analysis", "rule_settings", "RULE_SETTINGS"),
+    ("analysis", "reputation_rules", "REPUTATION_RULES"),
+    ("analysis", "config_bundle", "CONFIG_BUNDLE"),
+    ("analysis", "config_bundle_key", "CONFIG_BUNDLE_KEY"),
+    ("analysis", "shadow_config_bundle", "SHADOW_CONFIG_BUNDLE"),
+    ("analysis", "timings", "ANALYSIS_TIMINGS"),
+    ("enrichment", "passive_dns_url", "PASSIVE_DNS_URL"),
+    ("enrichment", "passive_dns_key", "PASSIVE_DNS_KEY"),
+    ("enrichment", "ct_lookup", "CT_LOOKUP"),
+    ("enrichment", "ct_log_url", "CT_LOG_URL"),
+    ("enrichment", "registration_lookup", "REGISTRATION_LOOKUP"),
+    ("enrichment", "rdap_url", "RDAP_URL"),
+    ("enrichment", "expand_urls", "EXPAND_URLS"),
+    ("enrichment", "max_redirects", "MAX_REDIRECTS"),
+    ("enrichment", "skip_score", "ENRICHMENT_SKIP_SCORE"),
+    ("enrichment", "skip_clean", "ENRICHMENT_SKIP_CLEAN"),
+    ("egress", "proxy", "EGRESS_PROXY"),
+    ("egress", "allowed_hosts", "EGRESS_ALLOWED_HOSTS"),
+    ("egress", "timeout_secs", "EGRESS_TIMEOUT_SECS"),
+    ("storage", "analysis_store", "ANALYSIS_STORE"),
+    ("storage", "analysis_store_key", "ANALYSIS_STORE_KEY"),
+    ("storage", "raw_retention_days", "RAW_RETENTION_DAYS"),
+    (
+        "storage",
+        "analysis_retention_days",
+        "ANALYSIS_RETENTION_DAYS",
+    ),
+    ("storage", "prune_interval_secs", "PRUNE_INTERVAL_SECS"),
+    ("storage", "feedback_log", "FEEDBACK_LOG"),
+    ("alerting", "inbound_forward_url", "INBOUND_FORWARD_URL"),
+    ("alerting", "analytics_url", "ANALYTICS_URL"),
+    ("alerting", "analytics_batch_size", "ANALYTICS_BATCH_SIZE"),
+    ("alerting", "analytics_flush_secs", "ANALYTICS_FLUSH_SECS"),
+    ("alerting", "analytics_queue", "ANALYTICS_QUEUE"),
+];
+
+/// Settings of the loaded config file, by environment variable
+static LOADED: OnceLock<BTreeMap<&'static str, String>> = OnceLock::new();
+
+/// Loads the YAML config file at `path`; its settings then take precedence over the
+/// environment for the rest of the process
+pub fn load(path: &str) -> anyhow::Result<()> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Reading {} failed: {}", path, e))?;
+    let settings = parse_settings(&text, |name| std::env::var(name).ok())
+        .map_err(|e| anyhow::anyhow!("{}: {}", path, e))?;
+    LOADED
+        .set(settings)
+        .map_err(|_| anyhow::anyhow!("A config file is already loaded"))
+}
+
+/// The setting standing for the environment variable `name`: from the config file if
+/// loaded and set there, else from the environment
+pub fn var(name: &str) -> Result<String, std::env::VarError> {
+    match LOADED.get().and_then(|settings| settings.get(name)) {
+        Some(value) => Ok(value.clone()),
+        None => std::env::var(name),
+    }
+}
+
+/// Reads a YAML config file into settings by environment variable, resolving
+/// `${VAR}` and `${VAR:-default}` in strings with `env`
+///
+/// Lists become comma-separated, as in the environment, and maps `name=value` pairs
+/// (for `admin_tokens`). Unknown sections and keys are errors, so typos surface.
+pub fn parse_settings(
+    text: &str,
+    env: impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<BTreeMap<&'static str, String>> {
+    let document: serde_yaml::Value = serde_yaml::from_str(text)?;
+    let mut settings = BTreeMap::new();
+    let sections = match document {
+        serde_yaml::Value::Null => return Ok(settings),
+        serde_yaml::Value::Mapping(sections) => sections,
+        _ => anyhow::bail!("expected sections such as `server:` at the top level"),
+    };
+    for (section, keys) in sections {
+        let section = section.as_str().unwrap_or_default().to_string();
+        if !SETTINGS.iter().any(|(s, _, _)| *s == section) {
+            anyhow::bail!("unknown section `{}`", section);
+        }
+        let keys = match keys {
+            serde_yaml::Value::Null => continue,
+            serde_yaml::Value::Mapping(keys) => keys,
+            _ => anyhow::bail!("`{}` must hold settings", section),
+        };
+        for (key, value) in keys {
+            let key = key.as_str().unwrap_or_default();
+            let Some((_, _, name)) = SETTINGS.iter().find(|(s, k, _)| *s == section && *k == key)
+            else {
+                anyhow::bail!("unknown setting `{}.{}`", section, key);
+            };
+            if value.is_null() {
+                continue;
+            }
+            let value = setting(&value, &env)
+                .map_err(|e| anyhow::anyhow!("`{}.{}`: {}", section, key, e))?;
+            settings.insert(*name, value);
+        }
+    }
+    Ok(settings)
+}
+
+/// A setting's value as its environment variable would hold it
+fn setting(
+    value: &serde_yaml::Value,
+    env: &impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<String> {
+    let joined = match value {
+        serde_yaml::Value::Sequence(items) => items
+            .iter()
+            .map(|item| scalar(item, env))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        serde_yaml::Value::Mapping(pairs) => pairs
+            .iter()
+            .map(|(k, v)| Ok(format!("{}={}", scalar(k, env)?, scalar(v, env)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        value => return scalar(value, env),
+    };
+    Ok(joined.join(","))
+}
+
+fn scalar(
+    value: &serde_yaml::Value,
+    env: &impl Fn(&str) -> Option<String>,
+) -> anyhow::Result<String> {
+    match value {
+        serde_yaml::Value::String(text) => interpolate(text, env),
+        serde_yaml::Value::Bool(flag) => Ok(flag.to_string()),
+        serde_yaml::Value::Number(number) => Ok(number.to_string()),
+        _ => anyhow::bail!("expected a string, number, or boolean"),
+    }
+}
+
+/// Replaces `${VAR}` and `${VAR:-default}` in `text`; `$$` is a literal `$`
+fn interpolate(text: &str, env: &impl Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            out.push('$');
+            rest = after;
+        } else if let Some(after) = rest.strip_prefix("${") {
+            let Some(end) = after.find('}') else {
+                anyhow::bail!("unclosed `${{` in `{}`", text);
+            };
+            let (name, default) = match after[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&after[..end], None),
+            };
+            match env(name)
+                .filter(|value| !value.is_empty())
+                .or(default.map(str::to_string))
+            {
+                Some(value) => out.push_str(&value),
+                None => anyhow::bail!("environment variable {} is not set", name),
+            }
+            rest = &after[end + 1..];
+        } else {
+            out.push('$');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_settings;
+
+    #[test]
+    fn test_parse_settings() {
+        let env = |name: &str| (name == "DB_PASSWORD").then(|| "s3cret".to_string());
+        let yaml = "
+server:
+  port: 9090
+dns:
+  backends: [system, 192.0.2.53]
+  cross_check: true
+auth:
+  admin_tokens:
+    alice: ${ALICE_TOKEN:-dev}
+storage:
+  analysis_store: postgres://esd:${DB_PASSWORD}@db/esd
+  raw_retention_days: ~
+alerting:
+";
+        let settings = parse_settings(yaml, env).unwrap();
+        assert_eq!(settings["PORT"], "9090");
+        assert_eq!(settings["DNS_BACKENDS"], "system,192.0.2.53");
+        assert_eq!(settings["DNS_CROSS_CHECK"], "true");
+        assert_eq!(settings["ADMIN_TOKENS"], "alice=dev");
+        assert_eq!(settings["ANALYSIS_STORE"], "postgres://esd:s3cret@db/esd");
+        assert!(!settings.contains_key("RAW_RETENTION_DAYS"));
+
+        assert!(parse_settings("server:\n  prot: 9090\n", env).is_err());
+        assert!(parse_settings("storage:\n  analysis_store: ${UNSET}\n", env).is_err());
+    }
+
+    #[test]
+    fn test_parse_settings_errors() {
+        let env = |_: &str| None;
+        let error = |yaml: &str| parse_settings(yaml, env).unwrap_err().to_string();
+        assert_eq!(
+            error("- server\n"),
+            "expected sections such as `server:` at the top level"
+        );
+        assert_eq!(error("sever:\n  port: 1\n"), "unknown section `sever`");
+        assert_eq!(error("server: 9090\n"), "`server` must hold settings");
+        assert_eq!(
+            error("dns:\n  backends: [[system]]\n"),
+            "`dns.backends`: expected a string, number, or boolean"
+        );
+        assert_eq!(
+            error("egress:\n  proxy: http://${PROXY_HOST\n"),
+            "`egress.proxy`: unclosed `${` in `http://${PROXY_HOST`"
+        );
+        assert!(parse_settings("server: [\n", env).is_err());
+
+        // `$$` escapes a literal dollar, and an empty variable takes the default
+        let env = |name: &str| (name == "EMPTY").then(String::new);
+        let settings = parse_settings("auth:\n  admin_token: $$${EMPTY:-dev}\n", env).unwrap();
+        assert_eq!(settings["ADMIN_TOKEN"], "$dev");
+    }
+}
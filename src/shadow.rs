@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{
+    diff::ResultDiff,
+    dns::DnsSnapshot,
+    email_verdict::{AnalysisOptions, AnalysisResult, analyze_email_at_depth},
+    parse::EmailParsed,
+};
+
+/// How often the shadow configuration agreed with the live one
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ShadowStats {
+    /// Messages scored by both configurations
+    pub analyses: u64,
+    /// Messages whose verdict or risk score differed, by live and shadow verdict
+    pub divergences: BTreeMap<(String, String), u64>,
+}
+
+/// A candidate configuration scored on live traffic without affecting verdicts
+///
+/// Each message is re-analyzed against the DNS answers of its live analysis, so
+/// differences come from the configuration alone and cost no extra lookups.
+pub struct Shadow {
+    options: RwLock<Arc<AnalysisOptions>>,
+    stats: Mutex<ShadowStats>,
+}
+
+impl Shadow {
+    pub fn new(options: AnalysisOptions) -> Self {
+        Self {
+            options: RwLock::new(Arc::new(options)),
+            stats: Mutex::new(ShadowStats::default()),
+        }
+    }
+
+    pub fn set_options(&self, options: AnalysisOptions) {
+        *self.options.write().unwrap() = Arc::new(options);
+    }
+
+    /// Scores the message with the shadow configuration and returns how its result
+    /// differs from the live one
+    ///
+    /// `live` must be the result before enrichments, which the shadow does not run.
+    pub async fn evaluate(
+        &self,
+        parsed: &EmailParsed,
+        snapshot: &DnsSnapshot,
+        live: &AnalysisResult,
+    ) -> anyhow::Result<ResultDiff> {
+        let options = self.options.read().unwrap().clone();
+        let shadow = analyze_email_at_depth(parsed, snapshot, &options, live.depth).await?;
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.analyses += 1;
+        if shadow.verdict != live.verdict || shadow.risk_score != live.risk_score {
+            let verdicts = (
+                format!("{:?}", live.verdict),
+                format!("{:?}", shadow.verdict),
+            );
+            *stats.divergences.entry(verdicts).or_default() += 1;
+        }
+        Ok(live.diff(&shadow))
+    }
+
+    pub fn stats(&self) -> ShadowStats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shadow;
+    use crate::{
+        dns::DnsSnapshot,
+        email_verdict::{AnalysisOptions, analyze_email_with_options},
+        lists::{ListKind, SenderLists},
+        parse::parse_email,
+    };
+
+    #[tokio::test]
+    async fn test_shadow_counts_divergence() {
+        let parsed = parse_email(b"From: ceo@evil.example\r\nSubject: hi\r\n\r\nbody").unwrap();
+        let snapshot = DnsSnapshot::default();
+        let live = analyze_email_with_options(&parsed, &snapshot, &AnalysisOptions::default())
+            .await
+            .unwrap();
+
+        let same = Shadow::new(AnalysisOptions::default());
+        assert!(
+            same.evaluate(&parsed, &snapshot, &live)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(same.stats().divergences.is_empty());
+
+        let mut lists = SenderLists::default();
+        lists.add(ListKind::Blocklist, "evil.example");
+        let stricter = Shadow::new(AnalysisOptions {
+            sender_lists: Some(lists),
+            ..AnalysisOptions::default()
+        });
+        let diff = stricter.evaluate(&parsed, &snapshot, &live).await.unwrap();
+        assert!(
+            diff.changes
+                .iter()
+                .any(|change| change.field == "risk_score")
+        );
+        let stats = stricter.stats();
+        assert_eq!(stats.analyses, 1);
+        assert_eq!(stats.divergences.values().sum::<u64>(), 1);
+    }
+}
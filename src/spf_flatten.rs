@@ -0,0 +1,357 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::pin::Pin;
+
+use crate::{
+    lint::RecordSource,
+    spf_walk::{SpfNode, walk_spf},
+};
+
+/// A network authorized by an SPF record
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SpfNetwork {
+    /// The network with host bits cleared, e.g. `192.0.2.0/24`
+    pub cidr: String,
+    /// Addresses in the network, saturating at `u128::MAX` for `::/0`
+    pub addresses: u128,
+    /// Domain whose record authorizes it
+    pub domain: String,
+    /// The term that authorizes it, e.g. `ip4:192.0.2.0/24` or `mx`
+    pub mechanism: String,
+}
+
+/// A network that lies within another authorized network
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SpfOverlap {
+    pub cidr: String,
+    pub within: String,
+}
+
+/// Every network an SPF record authorizes, with `include`, `redirect`, `a`, and `mx`
+/// expanded
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize)]
+pub struct SpfIpSet {
+    pub networks: Vec<SpfNetwork>,
+    /// The networks with those inside others removed, for firewall and monitoring rules
+    pub effective: Vec<String>,
+    /// IPv4 addresses authorized, each counted once
+    pub ipv4_addresses: u128,
+    /// IPv6 addresses authorized, each counted once (saturating)
+    pub ipv6_addresses: u128,
+    pub overlaps: Vec<SpfOverlap>,
+    /// Terms that cannot be expanded to networks: `ptr`, `exists`, macros, and
+    /// includes without a record
+    pub unresolved: Vec<String>,
+    /// DNS lookups an evaluation of the record needs (RFC 7208 limit: 10)
+    pub dns_lookups: usize,
+}
+
+/// An IPv4 or IPv6 network, IPv4 as the low 32 bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Net {
+    v6: bool,
+    start: u128,
+    prefix: u32,
+}
+
+impl Net {
+    fn new(ip: IpAddr, prefix: u32) -> Self {
+        let (v6, bits, address) = match ip {
+            IpAddr::V4(ip) => (false, 32, u32::from(ip) as u128),
+            IpAddr::V6(ip) => (true, 128, u128::from(ip)),
+        };
+        let prefix = prefix.min(bits);
+        let host_bits = u128::MAX.checked_shr(128 - (bits - prefix)).unwrap_or(0);
+        Self {
+            v6,
+            start: address & !host_bits,
+            prefix,
+        }
+    }
+
+    /// `192.0.2.0/24`, or a single address
+    fn parse(range: &str) -> Option<Self> {
+        let (ip, prefix) = match range.split_once('/') {
+            Some((ip, prefix)) => (ip.parse().ok()?, Some(prefix.parse().ok()?)),
+            None => (range.parse().ok()?, None),
+        };
+        Some(Self::new(
+            ip,
+            prefix.unwrap_or(if range.contains(':') { 128 } else { 32 }),
+        ))
+    }
+
+    fn bits(&self) -> u32 {
+        if self.v6 { 128 } else { 32 }
+    }
+
+    fn size(&self) -> u128 {
+        1u128
+            .checked_shl(self.bits() - self.prefix)
+            .unwrap_or(u128::MAX)
+    }
+
+    fn contains(&self, other: &Net) -> bool {
+        let shift = self.bits() - self.prefix;
+        self.v6 == other.v6
+            && self.prefix <= other.prefix
+            && self.start.checked_shr(shift).unwrap_or(0)
+                == other.start.checked_shr(shift).unwrap_or(0)
+    }
+
+    fn cidr(&self) -> String {
+        let ip = if self.v6 {
+            IpAddr::V6(Ipv6Addr::from(self.start))
+        } else {
+            IpAddr::V4(Ipv4Addr::from(self.start as u32))
+        };
+        format!("{}/{}", ip, self.prefix)
+    }
+}
+
+/// Resolves the SPF record of `domain` to the networks it authorizes
+///
+/// Only terms that pass (no qualifier or `+`) are expanded; exceptions carved out by
+/// earlier `-`, `~`, or `?` terms are not subtracted.
+pub async fn flatten_spf<S: RecordSource + Sync>(source: &S, domain: &str) -> SpfIpSet {
+    let walk = walk_spf(source, domain, 0, false).await;
+    let mut set = SpfIpSet {
+        dns_lookups: walk.lookups,
+        ..SpfIpSet::default()
+    };
+    let mut nets = Vec::new();
+    if let Some(tree) = &walk.tree {
+        flatten(source, tree, &mut HashSet::new(), &mut set, &mut nets).await;
+    }
+
+    nets.sort_by_key(|(net, _)| *net);
+    let mut top: Option<Net> = None;
+    for (net, index) in nets {
+        match top {
+            Some(outer) if outer.contains(&net) => set.overlaps.push(SpfOverlap {
+                cidr: set.networks[index].cidr.clone(),
+                within: outer.cidr(),
+            }),
+            _ => {
+                if net.v6 {
+                    set.ipv6_addresses = set.ipv6_addresses.saturating_add(net.size());
+                } else {
+                    set.ipv4_addresses += net.size();
+                }
+                set.effective.push(net.cidr());
+                top = Some(net);
+            }
+        }
+    }
+    set
+}
+
+fn flatten<'a, S: RecordSource + Sync>(
+    source: &'a S,
+    node: &'a SpfNode,
+    expanded: &'a mut HashSet<String>,
+    set: &'a mut SpfIpSet,
+    nets: &'a mut Vec<(Net, usize)>,
+) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+    Box::pin(async move {
+        // A record reached again authorizes the same networks again
+        if !expanded.insert(node.domain.to_ascii_lowercase()) {
+            return;
+        }
+        let domain = node.domain.as_str();
+        let Some(record) = &node.record else {
+            if node.via.is_some() && !node.repeated {
+                set.unresolved.push(format!("{} (no SPF record)", domain));
+            }
+            return;
+        };
+
+        // Children follow the include terms in order, so each include finds its own
+        let mut includes = node
+            .children
+            .iter()
+            .filter(|child| child.via.as_deref() == Some("include"));
+        for term in record.split_whitespace().skip(1) {
+            let term = term.to_ascii_lowercase();
+            let passes = !term.starts_with(['-', '~', '?']);
+            let mechanism = term.trim_start_matches('+');
+            let name = mechanism.split([':', '/', '=']).next().unwrap_or_default();
+            if !passes {
+                continue;
+            }
+            if mechanism.contains('%') {
+                set.unresolved.push(format!("{} ({})", term, domain));
+                continue;
+            }
+
+            let mut add = |net: Net| {
+                nets.push((net, set.networks.len()));
+                set.networks.push(SpfNetwork {
+                    cidr: net.cidr(),
+                    addresses: net.size(),
+                    domain: domain.to_string(),
+                    mechanism: term.clone(),
+                });
+            };
+            match name {
+                "ip4" | "ip6" => match mechanism.split_once(':').and_then(|(_, r)| Net::parse(r)) {
+                    Some(net) => add(net),
+                    None => set.unresolved.push(format!("{} ({})", term, domain)),
+                },
+                "all" => {
+                    add(Net::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+                    add(Net::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0));
+                }
+                "a" | "mx" => {
+                    let Some((target, v4_prefix, v6_prefix)) = dual_cidr(mechanism, domain) else {
+                        set.unresolved.push(format!("{} ({})", term, domain));
+                        continue;
+                    };
+                    let hosts = match name {
+                        "a" => vec![target],
+                        _ => source.mx_hosts(&target).await,
+                    };
+                    for host in hosts {
+                        for ip in source.addresses(&host).await {
+                            let prefix = if ip.is_ipv4() { v4_prefix } else { v6_prefix };
+                            add(Net::new(ip, prefix));
+                        }
+                    }
+                }
+                // Records the walk did not follow, e.g. past the lookup limit, add nothing
+                "include" => {
+                    let target = mechanism.split_once(':').map_or("", |(_, target)| target);
+                    if let Some(child) = includes.find(|child| child.domain == target) {
+                        flatten(source, child, expanded, set, nets).await;
+                    }
+                }
+                "redirect" => {
+                    let redirect = node
+                        .children
+                        .iter()
+                        .find(|child| child.via.as_deref() == Some("redirect"));
+                    if let Some(child) = redirect {
+                        flatten(source, child, expanded, set, nets).await;
+                    }
+                }
+                "ptr" | "exists" => set.unresolved.push(format!("{} ({})", term, domain)),
+                // exp= and unknown modifiers authorize nothing
+                _ => {}
+            }
+        }
+    })
+}
+
+/// The target and prefixes of `a[:domain][/v4][//v6]` or `mx[:domain][/v4][//v6]`;
+/// `None` when a prefix does not parse or is too long
+pub(crate) fn dual_cidr(mechanism: &str, domain: &str) -> Option<(String, u32, u32)> {
+    let prefix = |prefix: &str, bits: u32| prefix.parse().ok().filter(|&p| p <= bits);
+    let (spec, v6) = match mechanism.split_once("//") {
+        Some((spec, v6)) => (spec, Some(prefix(v6, 128)?)),
+        None => (mechanism, None),
+    };
+    let (spec, v4) = match spec.split_once('/') {
+        Some((spec, v4)) => (spec, Some(prefix(v4, 32)?)),
+        None => (spec, None),
+    };
+    let target = spec.split_once(':').map_or(domain, |(_, target)| target);
+    Some((target.to_string(), v4.unwrap_or(32), v6.unwrap_or(128)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::flatten_spf;
+    use crate::lint::RecordSource;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::net::IpAddr;
+
+    struct Zone(HashMap<&'static str, &'static str>);
+
+    #[async_trait]
+    impl RecordSource for Zone {
+        async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+            self.0.get(name).map(|r| vec![r.to_string()])
+        }
+
+        async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+            match domain {
+                "example.com" => vec!["mx1.example.com".to_string()],
+                _ => Vec::new(),
+            }
+        }
+
+        async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+            match name {
+                "mx1.example.com" => vec!["192.0.2.10".parse().unwrap()],
+                "example.com" => vec!["2001:db8::1".parse().unwrap()],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flatten_spf() {
+        let zone = Zone(HashMap::from([
+            (
+                "example.com",
+                "v=spf1 mx/24 a ip4:192.0.2.130/25 include:_spf.esp.test -ip4:198.51.100.0/24 ptr -all",
+            ),
+            (
+                "_spf.esp.test",
+                "v=spf1 ip4:198.51.100.0/22 ip6:2001:db8::/32 include:gone.test ~all",
+            ),
+        ]));
+        let set = flatten_spf(&zone, "example.com").await;
+
+        let cidrs: Vec<&str> = set.networks.iter().map(|n| n.cidr.as_str()).collect();
+        assert_eq!(
+            cidrs,
+            [
+                "192.0.2.0/24",
+                "2001:db8::1/128",
+                "192.0.2.128/25",
+                "198.51.100.0/22",
+                "2001:db8::/32"
+            ]
+        );
+        assert_eq!(set.networks[0].mechanism, "mx/24");
+        assert_eq!(
+            set.effective,
+            ["192.0.2.0/24", "198.51.100.0/22", "2001:db8::/32"]
+        );
+        assert_eq!(set.ipv4_addresses, 256 + 1024);
+        assert_eq!(set.ipv6_addresses, 1 << 96);
+        assert_eq!(set.overlaps.len(), 2);
+        assert_eq!(set.overlaps[0].cidr, "192.0.2.128/25");
+        assert_eq!(set.overlaps[0].within, "192.0.2.0/24");
+        assert_eq!(
+            set.unresolved,
+            ["gone.test (no SPF record)", "ptr (example.com)"]
+        );
+        assert_eq!(set.dns_lookups, 5);
+    }
+
+    #[tokio::test]
+    async fn test_flatten_shared_include() {
+        let zone = Zone(HashMap::from([
+            (
+                "shared.test",
+                "v=spf1 include:crm.test include:esp.test -all",
+            ),
+            ("crm.test", "v=spf1 include:_spf.cloud.test ~all"),
+            ("esp.test", "v=spf1 include:_spf.cloud.test ~all"),
+            (
+                "_spf.cloud.test",
+                "v=spf1 ip4:192.0.2.0/24 a:x.test a:y.test ~all",
+            ),
+        ]));
+        let set = flatten_spf(&zone, "shared.test").await;
+
+        // Receivers look up the shared record's terms once per include reaching it
+        assert_eq!(set.dns_lookups, 8);
+        let cidrs: Vec<&str> = set.networks.iter().map(|n| n.cidr.as_str()).collect();
+        assert_eq!(cidrs, ["192.0.2.0/24"]);
+    }
+}
@@ -0,0 +1,480 @@
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::{
+    lint::{LintFinding, RecordSource},
+    reasons::Severity,
+    spf_walk::{SpfNode, walk_spf},
+};
+
+/// RFC 7208 limit on DNS-querying mechanisms and modifiers per SPF evaluation
+pub const MAX_DNS_LOOKUPS: usize = 10;
+
+/// Widest `ip4:` range not reported as overly broad
+const MIN_IP4_PREFIX: u8 = 17;
+
+/// RFC 7208 limit on lookups answered with no records, beyond which receivers return
+/// a permerror
+pub const MAX_VOID_LOOKUPS: usize = 2;
+
+/// Mechanisms defined by RFC 7208; any other term without `=` is a permerror
+const MECHANISMS: &[&str] = &["all", "include", "a", "mx", "ptr", "ip4", "ip6", "exists"];
+
+/// The `v=spf1` records among the TXT records of `domain`, or `None` when the lookup fails
+pub(crate) async fn spf_records<S: RecordSource + Sync>(
+    source: &S,
+    domain: &str,
+) -> Option<Vec<String>> {
+    let records = source.txt_records(domain).await?;
+    Some(
+        records
+            .into_iter()
+            .filter(|r| r.to_ascii_lowercase().starts_with("v=spf1"))
+            .collect(),
+    )
+}
+
+/// Domains referenced by the `include:` mechanisms and `redirect=` modifier of a record
+pub(crate) fn include_targets(record: &str) -> Vec<String> {
+    record
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|term| {
+            let term = term
+                .trim_start_matches(['+', '-', '~', '?'])
+                .to_ascii_lowercase();
+            let (name, target) = term.split_once([':', '='])?;
+            matches!(name, "include" | "redirect").then(|| target.to_string())
+        })
+        .collect()
+}
+
+/// Whether an SPF term queries DNS and counts toward [`MAX_DNS_LOOKUPS`]
+pub(crate) fn costs_lookup(term: &str) -> bool {
+    let mechanism = term
+        .trim_start_matches(['+', '-', '~', '?'])
+        .to_ascii_lowercase();
+    let name = mechanism.split([':', '/', '=']).next().unwrap_or_default();
+    matches!(name, "include" | "a" | "mx" | "ptr" | "exists" | "redirect")
+}
+
+/// Result of auditing a domain's SPF record and its includes
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct SpfLint {
+    /// DNS-querying mechanisms counted across the whole include tree
+    pub dns_lookups: usize,
+    /// Lookups of `include`, `redirect`, `a`, `mx`, and `exists` targets that found no
+    /// (SPF) records
+    pub void_lookups: usize,
+    pub findings: Vec<LintFinding>,
+}
+
+/// Audits the SPF record of `domain`, following `include:` and `redirect=`
+pub async fn lint_spf<S: RecordSource + Sync>(source: &S, domain: &str) -> SpfLint {
+    let walk = walk_spf(source, domain, 0, true).await;
+    let mut lint = SpfLint {
+        dns_lookups: walk.lookups,
+        void_lookups: walk.void_lookups,
+        findings: Vec::new(),
+    };
+
+    match &walk.tree {
+        Some(tree) if tree.record.is_some() => lint_node(tree, &mut HashSet::new(), &mut lint),
+        _ => lint.findings.push(LintFinding::new(
+            "no_record",
+            Severity::Medium,
+            domain,
+            "No SPF record is published.".to_string(),
+            "Publish a TXT record listing your senders, ending in -all, e.g. \"v=spf1 mx -all\".",
+        )),
+    }
+
+    if lint.dns_lookups > MAX_DNS_LOOKUPS {
+        lint.findings.push(LintFinding::new(
+            "too_many_lookups",
+            Severity::High,
+            domain,
+            format!(
+                "Evaluation needs {} DNS lookups; receivers stop at {} with a permerror.",
+                lint.dns_lookups, MAX_DNS_LOOKUPS
+            ),
+            "Remove unused includes, replace a/mx mechanisms with ip4/ip6 ranges, or flatten includes.",
+        ));
+    }
+    if lint.void_lookups > MAX_VOID_LOOKUPS {
+        lint.findings.push(LintFinding::new(
+            "too_many_void_lookups",
+            Severity::High,
+            domain,
+            format!(
+                "{} lookups find no records; receivers stop after {} with a permerror.",
+                lint.void_lookups, MAX_VOID_LOOKUPS
+            ),
+            "Remove the include, a, mx, and exists terms that point at names without records.",
+        ));
+    }
+    lint
+}
+
+/// Lints the record of one node and those it includes or redirects to
+///
+/// A record reached more than once is linted the first time only; its lookups already
+/// count every time.
+fn lint_node(node: &SpfNode, linted: &mut HashSet<String>, lint: &mut SpfLint) {
+    let Some(record) = &node.record else {
+        return;
+    };
+    if !linted.insert(node.domain.to_ascii_lowercase()) {
+        return;
+    }
+    let domain = node.domain.as_str();
+
+    if node.record_count > 1 {
+        lint.findings.push(LintFinding::new(
+            "multiple_records",
+            Severity::High,
+            domain,
+            format!(
+                "{} SPF records are published; receivers treat this as a permerror.",
+                node.record_count
+            ),
+            "Merge the mechanisms into a single \"v=spf1 ... -all\" record.",
+        ));
+    }
+
+    let mut seen_all = false;
+    let mut redirected = false;
+    let mut ignored = Vec::new();
+    for term in record.split_whitespace().skip(1) {
+        let term = term.to_ascii_lowercase();
+        let mechanism = term.trim_start_matches(['+', '-', '~', '?']);
+        let name = mechanism.split([':', '/', '=']).next().unwrap_or_default();
+        let is_modifier = mechanism
+            .split([':', '/'])
+            .next()
+            .unwrap_or_default()
+            .contains('=');
+
+        if !is_modifier && !MECHANISMS.contains(&name) {
+            lint.findings.push(LintFinding::new(
+                "unknown_mechanism",
+                Severity::High,
+                domain,
+                format!(
+                    "\"{}\" is not an SPF mechanism; receivers return a permerror.",
+                    term
+                ),
+                "Fix the spelling or remove the term; modifiers need the form name=value.",
+            ));
+            continue;
+        }
+        if let Some(problem) = malformed(mechanism, name) {
+            lint.findings.push(LintFinding::new(
+                "malformed_term",
+                Severity::High,
+                domain,
+                format!("\"{}\" {}; receivers return a permerror.", term, problem),
+                "Correct the term's syntax, e.g. ip4:192.0.2.0/24 or include:_spf.example.com.",
+            ));
+            continue;
+        }
+        if seen_all && !is_modifier {
+            ignored.push(term.clone());
+            continue;
+        }
+
+        match name {
+            "redirect" => redirected = true,
+            "ptr" => lint.findings.push(LintFinding::new(
+                "ptr_mechanism",
+                Severity::Low,
+                domain,
+                format!("\"{}\" is deprecated (RFC 7208) and slow for receivers.", term),
+                "Replace ptr with the ip4/ip6 ranges or include of the servers it was meant to cover.",
+            )),
+            "all" => {
+                seen_all = true;
+                if !term.starts_with(['-', '~', '?']) {
+                    lint.findings.push(LintFinding::new(
+                        "plus_all",
+                        Severity::Critical,
+                        domain,
+                        format!("\"{}\" authorizes every server on the Internet to send as this domain.", term),
+                        "End the record with -all (or ~all while rolling out).",
+                    ));
+                }
+            }
+            "ip4" => {
+                let prefix = mechanism
+                    .split_once('/')
+                    .and_then(|(_, p)| p.parse::<u8>().ok());
+                if prefix.is_some_and(|p| p < MIN_IP4_PREFIX) {
+                    lint.findings.push(LintFinding::new(
+                        "broad_ip4",
+                        Severity::Medium,
+                        domain,
+                        format!("\"{}\" authorizes a very large address range.", term),
+                        "Narrow the range to the addresses of your actual mail servers.",
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !ignored.is_empty() {
+        lint.findings.push(LintFinding::new(
+            "terms_after_all",
+            Severity::Low,
+            domain,
+            format!("{} after \"all\" are never evaluated.", ignored.join(" ")),
+            "Move the terms before \"all\" or remove them; all must come last.",
+        ));
+    }
+    // Included records may end without `all`: the including record decides
+    if node.via.is_none() && !seen_all && !redirected {
+        lint.findings.push(LintFinding::new(
+            "missing_all",
+            Severity::Medium,
+            domain,
+            "The record does not end in \"all\", so unlisted servers get a neutral result."
+                .to_string(),
+            "End the record with -all (or ~all while rolling out).",
+        ));
+    }
+
+    for child in &node.children {
+        if child.record.is_none() && !child.repeated {
+            lint.findings.push(LintFinding::new(
+                "include_unreachable",
+                Severity::High,
+                domain,
+                format!(
+                    "{} has no reachable SPF record; receivers return a permerror.",
+                    child.domain
+                ),
+                "Remove the include or fix the target's SPF record.",
+            ));
+        }
+        lint_node(child, linted, lint);
+    }
+}
+
+/// What is wrong with the syntax of a known mechanism, if anything
+fn malformed(mechanism: &str, name: &str) -> Option<&'static str> {
+    let argument = mechanism.split_once(':').map(|(_, argument)| argument);
+    let prefix_ok = |argument: &str, max: u8| match argument.split_once('/') {
+        Some((_, prefix)) => prefix.parse::<u8>().is_ok_and(|prefix| prefix <= max),
+        None => true,
+    };
+    match name {
+        "ip4" | "ip6" if argument.is_none_or(str::is_empty) => Some("names no address"),
+        "ip4" => match argument {
+            Some(argument)
+                if argument
+                    .split('/')
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<Ipv4Addr>()
+                    .is_ok()
+                    && prefix_ok(argument, 32) =>
+            {
+                None
+            }
+            _ => Some("is not an IPv4 address or network"),
+        },
+        "ip6" => match argument {
+            Some(argument)
+                if argument
+                    .split('/')
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<Ipv6Addr>()
+                    .is_ok()
+                    && prefix_ok(argument, 128) =>
+            {
+                None
+            }
+            _ => Some("is not an IPv6 address or network"),
+        },
+        "include" | "exists" if argument.is_none_or(str::is_empty) => Some("names no domain"),
+        // `a` and `mx` default to the current domain, but a `:` needs a domain after it
+        "a" | "mx" if argument.is_some_and(|argument| argument.split('/').next() == Some("")) => {
+            Some("names no domain")
+        }
+        "all" if mechanism != "all" => Some("takes no argument"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lint_spf;
+    use crate::lint::RecordSource;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::net::IpAddr;
+
+    #[derive(Default)]
+    struct Records(HashMap<String, Vec<String>>);
+
+    impl Records {
+        fn with(mut self, domain: &str, records: &[&str]) -> Self {
+            let records = records.iter().map(|r| r.to_string()).collect();
+            self.0.insert(domain.to_string(), records);
+            self
+        }
+    }
+
+    /// Names with TXT records also have an address and an MX host
+    #[async_trait]
+    impl RecordSource for Records {
+        async fn txt_records(&self, domain: &str) -> Option<Vec<String>> {
+            self.0.get(domain).cloned()
+        }
+
+        async fn mx_hosts(&self, domain: &str) -> Vec<String> {
+            match self.0.contains_key(domain) {
+                true => vec![format!("mx.{}", domain)],
+                false => Vec::new(),
+            }
+        }
+
+        async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+            match self.0.contains_key(name) {
+                true => vec!["192.0.2.1".parse().unwrap()],
+                false => Vec::new(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spf_lint_findings() {
+        let source = Records::default()
+            .with(
+                "example.com",
+                &["v=spf1 ptr ip4:10.0.0.0/8 ip4:192.0.2.0/24 include:_spf.example.net include:gone.example.org +all"],
+            )
+            .with("_spf.example.net", &["v=spf1 a mx -all", "v=spf1 -all"]);
+
+        let lint = lint_spf(&source, "example.com").await;
+        let mut codes: Vec<_> = lint.findings.iter().map(|f| f.code).collect();
+        codes.sort();
+        assert_eq!(
+            codes,
+            vec![
+                "broad_ip4",
+                "include_unreachable",
+                "multiple_records",
+                "plus_all",
+                "ptr_mechanism"
+            ]
+        );
+        assert_eq!(lint.dns_lookups, 5);
+        assert!(lint.findings.iter().all(|f| !f.remediation.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_spf_lint_lookup_limit() {
+        let includes: Vec<String> = (0..11)
+            .map(|i| format!("include:s{}.example.net", i))
+            .collect();
+        let mut source = Records::default().with(
+            "example.com",
+            &[&format!("v=spf1 {} -all", includes.join(" "))],
+        );
+        for i in 0..11 {
+            source = source.with(&format!("s{}.example.net", i), &["v=spf1 -all"]);
+        }
+
+        let lint = lint_spf(&source, "example.com").await;
+        assert_eq!(lint.dns_lookups, 11);
+        assert_eq!(lint.findings.len(), 1);
+        assert_eq!(lint.findings[0].code, "too_many_lookups");
+
+        let empty = lint_spf(&Records::default(), "example.com").await;
+        assert_eq!(empty.findings[0].code, "no_record");
+    }
+
+    #[tokio::test]
+    async fn test_spf_lint_shared_include() {
+        // Both branches include the same record, and receivers evaluate it twice
+        let source = Records::default()
+            .with(
+                "example.com",
+                &["v=spf1 include:left.example include:right.example -all"],
+            )
+            .with("left.example", &["v=spf1 include:shared.example -all"])
+            .with("right.example", &["v=spf1 include:shared.example -all"])
+            .with("shared.example", &["v=spf1 a mx ptr a:shared.example -all"]);
+
+        let lint = lint_spf(&source, "example.com").await;
+        assert_eq!(lint.dns_lookups, 12);
+        let codes: Vec<_> = lint.findings.iter().map(|f| f.code).collect();
+        assert_eq!(codes, ["ptr_mechanism", "too_many_lookups"]);
+    }
+
+    #[tokio::test]
+    async fn test_spf_lint_syntax() {
+        let source = Records::default().with(
+            "example.com",
+            &["v=spf1 ip4:192.0.2.300 inclde:_spf.example.net a:gone.example.org mx:gone.example.org exists:gone.example.org exp=explain.example.com"],
+        );
+        let lint = lint_spf(&source, "example.com").await;
+        let mut codes: Vec<_> = lint.findings.iter().map(|f| f.code).collect();
+        codes.sort();
+        assert_eq!(
+            codes,
+            vec![
+                "malformed_term",
+                "missing_all",
+                "too_many_void_lookups",
+                "unknown_mechanism"
+            ]
+        );
+        assert_eq!(lint.void_lookups, 3);
+
+        let source = Records::default().with(
+            "example.com",
+            &["v=spf1 mx -all ip4:192.0.2.0/24 redirect=_spf.example.com"],
+        );
+        let lint = lint_spf(&source, "example.com").await;
+        assert_eq!(lint.findings.len(), 1);
+        assert_eq!(lint.findings[0].code, "terms_after_all");
+        assert!(
+            lint.findings[0]
+                .detail
+                .starts_with("ip4:192.0.2.0/24 after")
+        );
+
+        let source =
+            Records::default().with("example.com", &["v=spf1 a: mx:/24 ip4: ip6: a/24 mx -all"]);
+        let lint = lint_spf(&source, "example.com").await;
+        let details: Vec<_> = lint
+            .findings
+            .iter()
+            .map(|f| (f.code, f.detail.as_str()))
+            .collect();
+        assert_eq!(
+            details,
+            [
+                (
+                    "malformed_term",
+                    "\"a:\" names no domain; receivers return a permerror."
+                ),
+                (
+                    "malformed_term",
+                    "\"mx:/24\" names no domain; receivers return a permerror."
+                ),
+                (
+                    "malformed_term",
+                    "\"ip4:\" names no address; receivers return a permerror."
+                ),
+                (
+                    "malformed_term",
+                    "\"ip6:\" names no address; receivers return a permerror."
+                ),
+            ]
+        );
+    }
+}
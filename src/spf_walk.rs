@@ -0,0 +1,297 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::{
+    lint::RecordSource,
+    spf_lint::{MAX_DNS_LOOKUPS, costs_lookup, spf_records},
+};
+
+/// Nesting limit; records deeper down are not followed and make the walk broken
+pub const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// One SPF record in the include/redirect graph of a domain
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SpfNode {
+    pub domain: String,
+    /// How the parent record refers to this one: `include` or `redirect`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+    /// The SPF record, absent when the domain publishes none
+    pub record: Option<String>,
+    /// SPF records the domain publishes; receivers return a permerror for more than one
+    #[serde(skip)]
+    pub record_count: usize,
+    /// Terms of the record after `v=spf1`, in order
+    pub mechanisms: Vec<String>,
+    /// DNS lookups counted toward the RFC 7208 limit by this record and its children
+    pub lookup_count: usize,
+    /// The domain is already being expanded higher up this branch, a loop, so it is not
+    /// expanded again
+    pub repeated: bool,
+    /// The records of the `include` terms followed, in order, then that of the redirect
+    pub children: Vec<SpfNode>,
+}
+
+/// An SPF record and the records it includes or redirects to, walked the way receivers
+/// evaluate them
+#[derive(Debug, Default)]
+pub struct SpfWalk {
+    /// The walked domain's node; `None` when it lies beyond the depth limit
+    pub tree: Option<SpfNode>,
+    /// DNS lookups counted over every record reached
+    pub lookups: usize,
+    /// Lookups that found no records: `include` and `redirect` targets without an SPF
+    /// record, and, when probed, `a`, `mx`, and `exists` targets without any
+    pub void_lookups: usize,
+    /// A record refers to a domain without one, back to one of its own referrers, or
+    /// nests too deep
+    pub broken: bool,
+}
+
+/// Walk state shared by the records of one walk
+struct State {
+    /// Domains from the root to the record being expanded, lowercase
+    path: Vec<String>,
+    probe_voids: bool,
+    walk: SpfWalk,
+}
+
+/// Walks the SPF record of `domain`, `depth` levels down, and the records it includes
+/// or redirects to
+///
+/// A record reached twice is evaluated twice, so its lookups count every time; terms
+/// after `all`, and a redirect next to one, are never evaluated and count nothing.
+/// Records are followed until the lookups pass [`MAX_DNS_LOOKUPS`], where receivers give
+/// up. With `probe_voids`, the targets of `a`, `mx`, and `exists` terms are looked up to
+/// count the void ones too.
+pub async fn walk_spf<S: RecordSource + Sync>(
+    source: &S,
+    domain: &str,
+    depth: usize,
+    probe_voids: bool,
+) -> SpfWalk {
+    let mut state = State {
+        path: Vec::new(),
+        probe_voids,
+        walk: SpfWalk::default(),
+    };
+    let tree = spf_node(source, domain, None, depth, &mut state).await;
+    SpfWalk { tree, ..state.walk }
+}
+
+/// Boxed recursive walk of one record; `None` beyond the depth limit
+fn spf_node<'a, S: RecordSource + Sync>(
+    source: &'a S,
+    domain: &'a str,
+    via: Option<String>,
+    depth: usize,
+    state: &'a mut State,
+) -> Pin<Box<dyn Future<Output = Option<SpfNode>> + Send + 'a>> {
+    Box::pin(async move {
+        if depth >= MAX_INCLUDE_DEPTH {
+            state.walk.broken = true;
+            return None;
+        }
+        let key = domain.to_ascii_lowercase();
+
+        let mut node = SpfNode {
+            domain: domain.to_string(),
+            via,
+            record: None,
+            record_count: 0,
+            mechanisms: Vec::new(),
+            lookup_count: 0,
+            repeated: state.path.contains(&key),
+            children: Vec::new(),
+        };
+        if node.repeated {
+            state.walk.broken = true;
+            return Some(node);
+        }
+        let records = spf_records(source, domain).await.unwrap_or_default();
+        let Some(record) = records.first().cloned() else {
+            if node.via.is_some() {
+                state.walk.void_lookups += 1;
+                state.walk.broken = true;
+            }
+            return Some(node);
+        };
+        node.record_count = records.len();
+        node.mechanisms = record
+            .split_whitespace()
+            .skip(1)
+            .map(String::from)
+            .collect();
+
+        // A redirect is ignored when the record has an `all` (RFC 7208, section 6.1)
+        let has_all = node.mechanisms.iter().any(|term| {
+            term.trim_start_matches(['+', '-', '~', '?'])
+                .eq_ignore_ascii_case("all")
+        });
+        let mut redirect = None;
+        let mut children = Vec::new();
+        state.path.push(key);
+        for term in &node.mechanisms {
+            let term = term
+                .trim_start_matches(['+', '-', '~', '?'])
+                .to_ascii_lowercase();
+            let name = term.split([':', '/', '=']).next().unwrap_or_default();
+            if name == "redirect" {
+                if !has_all && redirect.is_none() {
+                    redirect = term.split_once('=').map(|(_, target)| target.to_string());
+                }
+                continue;
+            }
+            if costs_lookup(&term) {
+                node.lookup_count += 1;
+                state.walk.lookups += 1;
+            }
+            match name {
+                // Evaluation ends at `all`
+                "all" => break,
+                "include" => {
+                    let target = term.split_once(':').map_or("", |(_, target)| target);
+                    children.extend(follow(source, target, "include", depth, state).await);
+                }
+                "a" | "mx" | "exists" if state.probe_voids => {
+                    let target = term.split_once(':').map_or(domain, |(_, rest)| {
+                        rest.split('/').next().unwrap_or_default()
+                    });
+                    // Targets with macros depend on the message
+                    let void = !target.is_empty()
+                        && !target.contains('%')
+                        && match name {
+                            "mx" => source.mx_hosts(target).await.is_empty(),
+                            _ => source.addresses(target).await.is_empty(),
+                        };
+                    state.walk.void_lookups += usize::from(void);
+                }
+                _ => {}
+            }
+        }
+        // The redirect is evaluated once no mechanism matched
+        if let Some(target) = redirect {
+            node.lookup_count += 1;
+            state.walk.lookups += 1;
+            children.extend(follow(source, &target, "redirect", depth, state).await);
+        }
+        state.path.pop();
+
+        node.lookup_count += children
+            .iter()
+            .map(|child| child.lookup_count)
+            .sum::<usize>();
+        node.children = children;
+        node.record = Some(record);
+        Some(node)
+    })
+}
+
+/// Walks the record an `include` or `redirect` term refers to, unless the lookups
+/// already passed the limit, where receivers stop
+async fn follow<S: RecordSource + Sync>(
+    source: &S,
+    target: &str,
+    via: &str,
+    depth: usize,
+    state: &mut State,
+) -> Option<SpfNode> {
+    if state.walk.lookups > MAX_DNS_LOOKUPS {
+        return None;
+    }
+    if target.is_empty() {
+        state.walk.broken = true;
+        return None;
+    }
+    spf_node(source, target, Some(via.to_string()), depth + 1, state).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::walk_spf;
+    use crate::lint::RecordSource;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::net::IpAddr;
+
+    /// SPF records by domain; only `mail.test` has an address
+    struct Zone(HashMap<&'static str, &'static str>);
+
+    #[async_trait]
+    impl RecordSource for Zone {
+        async fn txt_records(&self, name: &str) -> Option<Vec<String>> {
+            self.0.get(name).map(|r| vec![r.to_string()])
+        }
+
+        async fn addresses(&self, name: &str) -> Vec<IpAddr> {
+            match name {
+                "mail.test" => vec!["192.0.2.1".parse().unwrap()],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_walk_counts_as_receivers_evaluate() {
+        let zone = Zone(HashMap::from([
+            (
+                "example.com",
+                "v=spf1 include:a.test include:b.test a:mail.test a:gone.test -all mx redirect=c.test",
+            ),
+            ("a.test", "v=spf1 include:shared.test ~all"),
+            (
+                "b.test",
+                "v=spf1 include:shared.test include:none.test ~all",
+            ),
+            ("shared.test", "v=spf1 mx exists:%{i}.shared.test"),
+        ]));
+        let walk = walk_spf(&zone, "example.com", 0, true).await;
+        let tree = walk.tree.unwrap();
+
+        // Both includes of shared.test count; mx after -all and the redirect do not
+        assert_eq!(walk.lookups, 11);
+        assert_eq!(tree.lookup_count, 11);
+        let children: Vec<_> = tree.children.iter().map(|c| c.domain.as_str()).collect();
+        assert_eq!(children, ["a.test", "b.test"]);
+        assert!(
+            tree.children
+                .iter()
+                .all(|c| c.children[0].lookup_count == 2)
+        );
+        // gone.test, none.test, and the mx of shared.test twice
+        assert_eq!(walk.void_lookups, 4);
+        assert!(walk.broken);
+
+        let unprobed = walk_spf(&zone, "example.com", 0, false).await;
+        assert_eq!(unprobed.void_lookups, 1);
+    }
+
+    #[tokio::test]
+    async fn test_walk_stops_at_loops_and_limits() {
+        let zone = Zone(HashMap::from([
+            ("loop.test", "v=spf1 include:back.test -all"),
+            ("back.test", "v=spf1 redirect=LOOP.test"),
+            (
+                "busy.test",
+                "v=spf1 a a a a a a a a a a include:late.test -all",
+            ),
+            ("late.test", "v=spf1 -all"),
+        ]));
+        let walk = walk_spf(&zone, "loop.test", 0, false).await;
+        let back = &walk.tree.unwrap().children[0];
+        assert!(back.children[0].repeated && walk.broken);
+
+        // The include is the 11th lookup, where receivers give up
+        let walk = walk_spf(&zone, "busy.test", 0, false).await;
+        assert_eq!(walk.lookups, 11);
+        assert!(walk.tree.unwrap().children.is_empty());
+        assert!(!walk.broken);
+
+        assert!(
+            walk_spf(&zone, "late.test", super::MAX_INCLUDE_DEPTH, false)
+                .await
+                .tree
+                .is_none()
+        );
+    }
+}
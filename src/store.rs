@@ -0,0 +1,584 @@
+use std::path::PathBuf;
+
+use aes_gcm::{
+    Aes256Gcm, KeyInit,
+    aead::{Aead, AeadCore, OsRng, Payload},
+};
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+
+use crate::{
+    dns::DnsSnapshot,
+    feedback::is_analysis_id,
+    redact::{REDACTED, redact},
+    store_backends::{connect_postgres, open_sqlite},
+};
+
+/// A message kept with what is needed to analyze it again
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StoredAnalysis {
+    /// `id` of the result
+    pub id: String,
+    /// Unix time of the analysis
+    pub at: i64,
+    /// Tenant whose configuration analyzed the message
+    pub tenant: String,
+    /// The raw message, base64-encoded so 8-bit content survives
+    pub raw_email: String,
+    /// DNS answers the analysis was based on
+    pub dns_snapshot: DnsSnapshot,
+    /// The result, before third-party enrichments
+    pub result: serde_json::Value,
+}
+
+impl StoredAnalysis {
+    pub fn new(
+        tenant: &str,
+        raw: &[u8],
+        dns_snapshot: DnsSnapshot,
+        result: serde_json::Value,
+    ) -> Self {
+        Self {
+            id: result["id"].as_str().unwrap_or_default().to_string(),
+            at: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or_default(),
+            tenant: tenant.to_string(),
+            raw_email: STANDARD.encode(raw),
+            dns_snapshot,
+            result,
+        }
+    }
+
+    /// Drops the raw message and masks personal data in the result
+    pub fn redact(&mut self) {
+        self.drop_raw();
+        redact(&mut self.result);
+    }
+
+    /// Drops the raw message and DNS answers, keeping the result; the analysis can no
+    /// longer be replayed
+    pub fn drop_raw(&mut self) {
+        self.raw_email = REDACTED.to_string();
+        self.dns_snapshot = DnsSnapshot::default();
+    }
+
+    /// Whether the raw message is still kept
+    pub fn has_raw(&self) -> bool {
+        self.raw_email != REDACTED
+    }
+
+    pub fn raw(&self) -> anyhow::Result<Vec<u8>> {
+        if !self.has_raw() {
+            anyhow::bail!("The message of analysis {} is no longer kept", self.id);
+        }
+        Ok(STANDARD.decode(&self.raw_email)?)
+    }
+}
+
+/// Which stored analyses to return; unset fields match all
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct StoreQuery {
+    pub tenant: Option<String>,
+    /// Analyzed at or after this Unix time
+    pub since: Option<i64>,
+    /// Analyzed before this Unix time
+    pub until: Option<i64>,
+}
+
+impl StoreQuery {
+    pub fn matches(&self, analysis: &StoredAnalysis) -> bool {
+        self.tenant
+            .as_ref()
+            .is_none_or(|tenant| *tenant == analysis.tenant)
+            && self.since.is_none_or(|since| analysis.at >= since)
+            && self.until.is_none_or(|until| analysis.at < until)
+    }
+}
+
+/// Database an [`AnalysisStore`] keeps analyses in
+///
+/// Backends store analyses as given: redaction and encryption happen in the
+/// `AnalysisStore`, and ids are validated and lowercased before they get here.
+/// Implement it to keep analyses elsewhere, e.g. in DynamoDB or ClickHouse, and pass
+/// the backend to [`AnalysisStore::with_backend`].
+#[async_trait]
+pub trait ResultStore: Send + Sync {
+    /// Saves `analysis`, replacing an earlier one of the same tenant and id
+    ///
+    /// Ids are message hashes, so tenants analyzing the same message share an id; each
+    /// keeps its own analysis.
+    async fn save(&self, analysis: &StoredAnalysis) -> anyhow::Result<()>;
+
+    /// The analysis `id` of `tenant`, if stored
+    async fn get(&self, tenant: &str, id: &str) -> anyhow::Result<Option<StoredAnalysis>>;
+
+    /// The analyses matching `query`, oldest first
+    async fn query(&self, query: &StoreQuery) -> anyhow::Result<Vec<StoredAnalysis>>;
+
+    /// Deletes analyses older than `retention.result_days` and drops the raw message of
+    /// those older than `retention.raw_days` (see [`StoredAnalysis::drop_raw`]), as of
+    /// the Unix time `now`; only those of `tenant`, when given
+    async fn prune(
+        &self,
+        tenant: Option<&str>,
+        retention: &Retention,
+        now: i64,
+    ) -> anyhow::Result<PruneStats>;
+}
+
+/// Marks raw messages encrypted with the store key, followed by the base64 of the
+/// nonce and ciphertext
+const ENCRYPTED_PREFIX: &str = "aes-256-gcm:";
+
+/// Length of an AES-GCM nonce
+const NONCE_LEN: usize = 12;
+
+/// Analyzed messages kept for replay, in a [`ResultStore`]
+///
+/// A message analyzed again for the same tenant replaces its earlier entry. Entries are
+/// kept until pruned according to a [`Retention`].
+pub struct AnalysisStore {
+    backend: Box<dyn ResultStore>,
+    /// Store analyses redacted
+    redact: bool,
+    /// Encrypts raw messages at rest; results stay readable
+    cipher: Option<Aes256Gcm>,
+}
+
+impl AnalysisStore {
+    /// A store keeping each analysis as a JSON file in `dir`
+    pub fn new(dir: impl Into<PathBuf>, redact: bool) -> anyhow::Result<Self> {
+        Ok(Self::with_backend(FileStore::new(dir)?, redact))
+    }
+
+    pub fn with_backend(backend: impl ResultStore + 'static, redact: bool) -> Self {
+        Self {
+            backend: Box::new(backend),
+            redact,
+            cipher: None,
+        }
+    }
+
+    /// Opens the store at `location`: a directory, `sqlite:<path>` with the `sqlite`
+    /// feature, or a `postgres://` connection URL with the `postgres` feature
+    pub async fn open(location: &str, redact: bool) -> anyhow::Result<Self> {
+        let backend = if let Some(path) = location.strip_prefix("sqlite:") {
+            open_sqlite(path.trim_start_matches("//"))?
+        } else if location.starts_with("postgres://") || location.starts_with("postgresql://") {
+            connect_postgres(location).await?
+        } else {
+            return Self::new(location, redact);
+        };
+        Ok(Self {
+            backend,
+            redact,
+            cipher: None,
+        })
+    }
+
+    /// Encrypts raw messages with AES-256-GCM under the 32-byte `key`
+    pub fn with_key(mut self, key: &[u8]) -> anyhow::Result<Self> {
+        let cipher = Aes256Gcm::new_from_slice(key)
+            .map_err(|_| anyhow::anyhow!("The store key must be 32 bytes"))?;
+        self.cipher = Some(cipher);
+        Ok(self)
+    }
+
+    pub async fn save(&self, mut analysis: StoredAnalysis) -> anyhow::Result<()> {
+        if !is_analysis_id(&analysis.id) {
+            anyhow::bail!("Not an analysis id: {}", analysis.id);
+        }
+        analysis.id = analysis.id.to_ascii_lowercase();
+        if self.redact {
+            analysis.redact();
+        }
+        if let Some(cipher) = &self.cipher
+            && analysis.has_raw()
+        {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let payload = Payload {
+                msg: &analysis.raw()?,
+                aad: analysis.id.as_bytes(),
+            };
+            let sealed = cipher
+                .encrypt(&nonce, payload)
+                .map_err(|_| anyhow::anyhow!("Encrypting analysis {} failed", analysis.id))?;
+            analysis.raw_email = format!(
+                "{}{}",
+                ENCRYPTED_PREFIX,
+                STANDARD.encode([nonce.as_slice(), &sealed].concat())
+            );
+        }
+        self.backend.save(&analysis).await
+    }
+
+    /// The stored analysis `id` of `tenant`, if any
+    pub async fn load(&self, tenant: &str, id: &str) -> anyhow::Result<Option<StoredAnalysis>> {
+        if !is_analysis_id(id) {
+            return Ok(None);
+        }
+        match self.backend.get(tenant, &id.to_ascii_lowercase()).await? {
+            Some(analysis) => Ok(Some(self.unseal(analysis)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The stored analyses matching `query`, oldest first
+    pub async fn query(&self, query: &StoreQuery) -> anyhow::Result<Vec<StoredAnalysis>> {
+        let analyses = self.backend.query(query).await?;
+        analyses
+            .into_iter()
+            .map(|analysis| self.unseal(analysis))
+            .collect()
+    }
+
+    /// The stored analyses matching `query`, oldest first, without their raw messages;
+    /// needs no key
+    pub async fn query_results(&self, query: &StoreQuery) -> anyhow::Result<Vec<StoredAnalysis>> {
+        let mut analyses = self.backend.query(query).await?;
+        analyses.iter_mut().for_each(StoredAnalysis::drop_raw);
+        Ok(analyses)
+    }
+
+    /// The results of all stored analyses, oldest first
+    pub async fn results(&self) -> anyhow::Result<Vec<serde_json::Value>> {
+        let analyses = self.backend.query(&StoreQuery::default()).await?;
+        Ok(analyses
+            .into_iter()
+            .map(|analysis| analysis.result)
+            .collect())
+    }
+
+    /// Applies `retention` to the stored analyses, or those of `tenant`, as of the Unix
+    /// time `now`
+    pub async fn prune(
+        &self,
+        tenant: Option<&str>,
+        retention: &Retention,
+        now: i64,
+    ) -> anyhow::Result<PruneStats> {
+        self.backend.prune(tenant, retention, now).await
+    }
+
+    /// Decrypts the raw message of `analysis`, if it was encrypted
+    fn unseal(&self, mut analysis: StoredAnalysis) -> anyhow::Result<StoredAnalysis> {
+        if let Some(sealed) = analysis.raw_email.strip_prefix(ENCRYPTED_PREFIX) {
+            let Some(cipher) = &self.cipher else {
+                anyhow::bail!("Analysis {} is encrypted and no key is set", analysis.id);
+            };
+            let sealed = STANDARD.decode(sealed)?;
+            if sealed.len() < NONCE_LEN {
+                anyhow::bail!("Encrypted message of analysis {} is truncated", analysis.id);
+            }
+            let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+            let payload = Payload {
+                msg: ciphertext,
+                aad: analysis.id.as_bytes(),
+            };
+            let raw = cipher
+                .decrypt(nonce.into(), payload)
+                .map_err(|_| anyhow::anyhow!("Decrypting analysis {} failed", analysis.id))?;
+            analysis.raw_email = STANDARD.encode(raw);
+        }
+        Ok(analysis)
+    }
+}
+
+/// Directory of analyses, one JSON file per tenant and analysis id
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// The file of analysis `id` of `tenant`; tenant names are hex-encoded, as they may
+    /// hold any character
+    fn path(&self, tenant: &str, id: &str) -> PathBuf {
+        self.dir.join(format!(
+            "{}-{}.json",
+            crate::dedup::hex(tenant.as_bytes()),
+            id
+        ))
+    }
+
+    fn write(&self, path: &std::path::Path, analysis: &StoredAnalysis) -> anyhow::Result<()> {
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec(analysis)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Every stored analysis with its file, in directory order
+    fn entries(&self) -> anyhow::Result<Vec<(PathBuf, StoredAnalysis)>> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let analysis = serde_json::from_slice(&std::fs::read(&path)?)?;
+            entries.push((path, analysis));
+        }
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl ResultStore for FileStore {
+    async fn save(&self, analysis: &StoredAnalysis) -> anyhow::Result<()> {
+        self.write(&self.path(&analysis.tenant, &analysis.id), analysis)
+    }
+
+    async fn get(&self, tenant: &str, id: &str) -> anyhow::Result<Option<StoredAnalysis>> {
+        match std::fs::read(self.path(tenant, id)) {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn query(&self, query: &StoreQuery) -> anyhow::Result<Vec<StoredAnalysis>> {
+        let mut analyses: Vec<StoredAnalysis> = self
+            .entries()?
+            .into_iter()
+            .map(|(_, analysis)| analysis)
+            .filter(|analysis| query.matches(analysis))
+            .collect();
+        analyses.sort_by_key(|analysis| analysis.at);
+        Ok(analyses)
+    }
+
+    async fn prune(
+        &self,
+        tenant: Option<&str>,
+        retention: &Retention,
+        now: i64,
+    ) -> anyhow::Result<PruneStats> {
+        let (raw_cutoff, result_cutoff) = retention.cutoffs(now);
+        let mut stats = PruneStats::default();
+        for (path, mut analysis) in self.entries()? {
+            if tenant.is_some_and(|tenant| tenant != analysis.tenant) {
+                continue;
+            }
+            if result_cutoff.is_some_and(|cutoff| analysis.at < cutoff) {
+                std::fs::remove_file(&path)?;
+                stats.removed += 1;
+            } else if analysis.has_raw() && raw_cutoff.is_some_and(|cutoff| analysis.at < cutoff) {
+                analysis.drop_raw();
+                self.write(&path, &analysis)?;
+                stats.stripped += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// How long stored analyses are kept; `None` keeps them indefinitely
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Retention {
+    /// Days the raw message and DNS answers are kept
+    pub raw_days: Option<u64>,
+    /// Days the result is kept
+    pub result_days: Option<u64>,
+}
+
+impl Retention {
+    pub fn is_unlimited(&self) -> bool {
+        self.raw_days.is_none() && self.result_days.is_none()
+    }
+
+    /// The Unix times before which raw messages and results expire, as of `now`
+    pub fn cutoffs(&self, now: i64) -> (Option<i64>, Option<i64>) {
+        let cutoff = |days: Option<u64>| days.map(|days| now.saturating_sub(days as i64 * 86_400));
+        (cutoff(self.raw_days), cutoff(self.result_days))
+    }
+}
+
+/// What a pruning run did
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PruneStats {
+    /// Analyses whose raw message was dropped
+    pub stripped: usize,
+    /// Analyses deleted
+    pub removed: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalysisStore, PruneStats, Retention, StoreQuery, StoredAnalysis};
+    use crate::dns::DnsSnapshot;
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_store_round_trips_raw_message() {
+        let dir = std::env::temp_dir().join(format!("analyses-{}", std::process::id()));
+        let store = AnalysisStore::new(&dir, false).unwrap();
+        let id = "0f".repeat(32);
+        let raw = b"From: a@example.com\r\nSubject: caf\xe9\r\n\r\nbody";
+        let analysis = StoredAnalysis::new(
+            "acme",
+            raw,
+            DnsSnapshot::default(),
+            json!({"id": id, "verdict": "Suspicious"}),
+        );
+        store.save(analysis).await.unwrap();
+
+        let loaded = store
+            .load("acme", &id.to_uppercase())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(store.load("acme", "../etc/passwd").await.unwrap().is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(loaded.raw().unwrap(), raw);
+        assert_eq!(loaded.tenant, "acme");
+        assert_eq!(loaded.result["verdict"], "Suspicious");
+    }
+
+    #[tokio::test]
+    async fn test_raw_message_encrypted_at_rest() {
+        let dir = std::env::temp_dir().join(format!("sealed-{}", std::process::id()));
+        let key = [7u8; 32];
+        let store = AnalysisStore::new(&dir, false)
+            .unwrap()
+            .with_key(&key)
+            .unwrap();
+        let id = "5a".repeat(32);
+        let raw = b"From: cfo@corp.example\r\n\r\nQuarterly figures";
+        let analysis =
+            StoredAnalysis::new("default", raw, DnsSnapshot::default(), json!({"id": id}));
+        store.save(analysis).await.unwrap();
+
+        let file = dir.join(format!("{}-{}.json", crate::dedup::hex(b"default"), id));
+        let on_disk = std::fs::read_to_string(file).unwrap();
+        let loaded = store.load("default", &id).await.unwrap().unwrap();
+        let keyless = AnalysisStore::new(&dir, false)
+            .unwrap()
+            .load("default", &id)
+            .await;
+        let wrong_key = AnalysisStore::new(&dir, false)
+            .unwrap()
+            .with_key(&[8u8; 32])
+            .unwrap()
+            .load("default", &id)
+            .await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(on_disk.contains("aes-256-gcm:"));
+        assert!(!on_disk.contains(&STANDARD.encode(raw)));
+        assert_eq!(loaded.raw().unwrap(), raw);
+        assert!(keyless.is_err());
+        assert!(wrong_key.is_err());
+    }
+
+    /// Saves analyses 5, 40, and 400 days old to `store` and prunes them
+    async fn check_prune(store: &AnalysisStore) {
+        let day = 86_400;
+        for (n, age) in [(1, 5), (2, 40), (3, 400)] {
+            let mut analysis = StoredAnalysis::new(
+                "default",
+                b"From: a@example.com\r\n\r\nbody",
+                DnsSnapshot::default(),
+                json!({"id": format!("{:064x}", n)}),
+            );
+            analysis.at = 1_000 * day - age * day;
+            store.save(analysis).await.unwrap();
+        }
+        let recent = StoreQuery {
+            since: Some(1_000 * day - 100 * day),
+            ..StoreQuery::default()
+        };
+        assert_eq!(store.query(&recent).await.unwrap().len(), 2);
+
+        let retention = Retention {
+            raw_days: Some(30),
+            result_days: Some(365),
+        };
+        let stats = store.prune(None, &retention, 1_000 * day).await.unwrap();
+        assert_eq!(
+            stats,
+            PruneStats {
+                stripped: 1,
+                removed: 1
+            }
+        );
+        let kept = store.query(&StoreQuery::default()).await.unwrap();
+        let ids: Vec<String> = kept.iter().map(|a| a.id.clone()).collect();
+        assert_eq!(ids, [format!("{:064x}", 2), format!("{:064x}", 1)]);
+        assert!(!kept[0].has_raw());
+        assert!(kept[1].has_raw());
+        assert!(
+            store
+                .load("default", &format!("{:064x}", 3))
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    /// Saves one message analyzed by two tenants to `store` and prunes one tenant's
+    async fn check_tenants(store: &AnalysisStore) {
+        let id = "3c".repeat(32);
+        for (tenant, verdict) in [("acme", "Suspicious"), ("globex", "Legit")] {
+            let mut analysis = StoredAnalysis::new(
+                tenant,
+                b"From: a@example.com\r\n\r\nbody",
+                DnsSnapshot::default(),
+                json!({"id": id, "verdict": verdict}),
+            );
+            analysis.at = 0;
+            store.save(analysis).await.unwrap();
+        }
+        let acme = store.load("acme", &id).await.unwrap().unwrap();
+        let globex = store.load("globex", &id).await.unwrap().unwrap();
+        assert_eq!(acme.tenant, "acme");
+        assert_eq!(acme.result["verdict"], "Suspicious");
+        assert_eq!(globex.tenant, "globex");
+        assert_eq!(globex.result["verdict"], "Legit");
+        assert!(store.load("initech", &id).await.unwrap().is_none());
+
+        let retention = Retention {
+            raw_days: None,
+            result_days: Some(1),
+        };
+        let stats = store
+            .prune(Some("acme"), &retention, 86_400 * 10)
+            .await
+            .unwrap();
+        assert_eq!(stats.removed, 1);
+        assert!(store.load("acme", &id).await.unwrap().is_none());
+        assert!(store.load("globex", &id).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_prune_drops_raw_before_result() {
+        let dir = std::env::temp_dir().join(format!("pruned-{}", std::process::id()));
+        let store = AnalysisStore::new(&dir, false).unwrap();
+        check_prune(&store).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tenants_keep_separate_analyses() {
+        let dir = std::env::temp_dir().join(format!("tenants-{}", std::process::id()));
+        let store = AnalysisStore::new(&dir, false).unwrap();
+        check_tenants(&store).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        let store = AnalysisStore::open("sqlite::memory:", false).await.unwrap();
+        check_prune(&store).await;
+        check_tenants(&store).await;
+    }
+}
@@ -0,0 +1,337 @@
+use crate::store::ResultStore;
+
+/// Opens the SQLite database at `path`; needs the `sqlite` feature
+#[cfg(feature = "sqlite")]
+pub fn open_sqlite(path: &str) -> anyhow::Result<Box<dyn ResultStore>> {
+    Ok(Box::new(sqlite::SqliteStore::open(path)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+pub fn open_sqlite(path: &str) -> anyhow::Result<Box<dyn ResultStore>> {
+    anyhow::bail!("sqlite:{}: SQLite stores need the sqlite feature", path)
+}
+
+/// Connects to the PostgreSQL database at `url`; needs the `postgres` feature
+#[cfg(feature = "postgres")]
+pub async fn connect_postgres(url: &str) -> anyhow::Result<Box<dyn ResultStore>> {
+    Ok(Box::new(postgres::PostgresStore::connect(url).await?))
+}
+
+#[cfg(not(feature = "postgres"))]
+pub async fn connect_postgres(_url: &str) -> anyhow::Result<Box<dyn ResultStore>> {
+    anyhow::bail!("PostgreSQL stores need the postgres feature")
+}
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStore;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use rusqlite::{Connection, OptionalExtension, Row, params};
+
+    use crate::{
+        dns::DnsSnapshot,
+        redact::REDACTED,
+        store::{PruneStats, ResultStore, Retention, StoreQuery, StoredAnalysis},
+    };
+
+    /// One row per tenant's analysis, with the DNS snapshot and result as JSON text
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS analyses (
+        id TEXT NOT NULL,
+        at INTEGER NOT NULL,
+        tenant TEXT NOT NULL,
+        raw_email TEXT NOT NULL,
+        dns_snapshot TEXT NOT NULL,
+        result TEXT NOT NULL,
+        PRIMARY KEY (tenant, id)
+    );
+    CREATE INDEX IF NOT EXISTS analyses_at ON analyses (at);";
+
+    const COLUMNS: &str = "id, at, tenant, raw_email, dns_snapshot, result";
+
+    /// Analyses in a SQLite database
+    ///
+    /// Queries run on the calling task; SQLite answers them from the local file.
+    pub struct SqliteStore {
+        conn: Mutex<Connection>,
+    }
+
+    impl SqliteStore {
+        /// Opens or creates the database at `path`; `:memory:` keeps it in memory
+        pub fn open(path: &str) -> anyhow::Result<Self> {
+            let conn = Connection::open(path)?;
+            conn.execute_batch(SCHEMA)?;
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+            self.conn.lock().unwrap_or_else(|e| e.into_inner())
+        }
+    }
+
+    /// The analysis in `row`, selected as [`COLUMNS`]
+    fn analysis(row: &Row) -> rusqlite::Result<(StoredAnalysis, String, String)> {
+        Ok((
+            StoredAnalysis {
+                id: row.get(0)?,
+                at: row.get(1)?,
+                tenant: row.get(2)?,
+                raw_email: row.get(3)?,
+                dns_snapshot: DnsSnapshot::default(),
+                result: serde_json::Value::Null,
+            },
+            row.get(4)?,
+            row.get(5)?,
+        ))
+    }
+
+    fn decode(
+        (mut analysis, dns_snapshot, result): (StoredAnalysis, String, String),
+    ) -> anyhow::Result<StoredAnalysis> {
+        analysis.dns_snapshot = serde_json::from_str(&dns_snapshot)?;
+        analysis.result = serde_json::from_str(&result)?;
+        Ok(analysis)
+    }
+
+    #[async_trait]
+    impl ResultStore for SqliteStore {
+        async fn save(&self, analysis: &StoredAnalysis) -> anyhow::Result<()> {
+            self.conn().execute(
+                &format!(
+                    "INSERT INTO analyses ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                     ON CONFLICT (tenant, id) DO UPDATE SET at = excluded.at,
+                     raw_email = excluded.raw_email, dns_snapshot = excluded.dns_snapshot,
+                     result = excluded.result",
+                    COLUMNS
+                ),
+                params![
+                    analysis.id,
+                    analysis.at,
+                    analysis.tenant,
+                    analysis.raw_email,
+                    serde_json::to_string(&analysis.dns_snapshot)?,
+                    serde_json::to_string(&analysis.result)?,
+                ],
+            )?;
+            Ok(())
+        }
+
+        async fn get(&self, tenant: &str, id: &str) -> anyhow::Result<Option<StoredAnalysis>> {
+            let row = self
+                .conn()
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM analyses WHERE tenant = ?1 AND id = ?2",
+                        COLUMNS
+                    ),
+                    params![tenant, id],
+                    analysis,
+                )
+                .optional()?;
+            row.map(decode).transpose()
+        }
+
+        async fn query(&self, query: &StoreQuery) -> anyhow::Result<Vec<StoredAnalysis>> {
+            let conn = self.conn();
+            let mut statement = conn.prepare(&format!(
+                "SELECT {} FROM analyses
+                 WHERE (?1 IS NULL OR tenant = ?1) AND (?2 IS NULL OR at >= ?2)
+                 AND (?3 IS NULL OR at < ?3)
+                 ORDER BY at",
+                COLUMNS
+            ))?;
+            let rows = statement
+                .query_map(params![query.tenant, query.since, query.until], analysis)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows.into_iter().map(decode).collect()
+        }
+
+        async fn prune(
+            &self,
+            tenant: Option<&str>,
+            retention: &Retention,
+            now: i64,
+        ) -> anyhow::Result<PruneStats> {
+            let (raw_cutoff, result_cutoff) = retention.cutoffs(now);
+            let conn = self.conn();
+            let mut stats = PruneStats::default();
+            if let Some(cutoff) = result_cutoff {
+                stats.removed = conn.execute(
+                    "DELETE FROM analyses WHERE at < ?1 AND (?2 IS NULL OR tenant = ?2)",
+                    params![cutoff, tenant],
+                )?;
+            }
+            if let Some(cutoff) = raw_cutoff {
+                stats.stripped = conn.execute(
+                    "UPDATE analyses SET raw_email = ?1, dns_snapshot = ?2
+                     WHERE at < ?3 AND raw_email != ?1 AND (?4 IS NULL OR tenant = ?4)",
+                    params![
+                        REDACTED,
+                        serde_json::to_string(&DnsSnapshot::default())?,
+                        cutoff,
+                        tenant
+                    ],
+                )?;
+            }
+            Ok(stats)
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+mod postgres {
+    use async_trait::async_trait;
+    use tokio_postgres::{Client, NoTls, Row};
+
+    use crate::{
+        dns::DnsSnapshot,
+        redact::REDACTED,
+        store::{PruneStats, ResultStore, Retention, StoreQuery, StoredAnalysis},
+    };
+
+    /// One row per analysis, as in the SQLite store but with `jsonb` columns
+    const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS analyses (
+        id TEXT NOT NULL,
+        at BIGINT NOT NULL,
+        tenant TEXT NOT NULL,
+        raw_email TEXT NOT NULL,
+        dns_snapshot JSONB NOT NULL,
+        result JSONB NOT NULL,
+        PRIMARY KEY (tenant, id)
+    );
+    CREATE INDEX IF NOT EXISTS analyses_at ON analyses (at);";
+
+    const COLUMNS: &str = "id, at, tenant, raw_email, dns_snapshot, result";
+
+    /// Analyses in a PostgreSQL database
+    pub struct PostgresStore {
+        client: Client,
+    }
+
+    impl PostgresStore {
+        /// Connects to `url`, e.g. `postgres://spoof@db/analyses`, without TLS
+        pub async fn connect(url: &str) -> anyhow::Result<Self> {
+            let (client, connection) = tokio_postgres::connect(url, NoTls).await?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    log::error!("Analysis store connection failed: {}", e);
+                }
+            });
+            client.batch_execute(SCHEMA).await?;
+            Ok(Self { client })
+        }
+    }
+
+    /// The analysis in `row`, selected as [`COLUMNS`]
+    fn analysis(row: &Row) -> anyhow::Result<StoredAnalysis> {
+        Ok(StoredAnalysis {
+            id: row.try_get(0)?,
+            at: row.try_get(1)?,
+            tenant: row.try_get(2)?,
+            raw_email: row.try_get(3)?,
+            dns_snapshot: serde_json::from_value(row.try_get(4)?)?,
+            result: row.try_get(5)?,
+        })
+    }
+
+    #[async_trait]
+    impl ResultStore for PostgresStore {
+        async fn save(&self, analysis: &StoredAnalysis) -> anyhow::Result<()> {
+            self.client
+                .execute(
+                    &format!(
+                        "INSERT INTO analyses ({}) VALUES ($1, $2, $3, $4, $5, $6)
+                         ON CONFLICT (tenant, id) DO UPDATE SET at = excluded.at,
+                         raw_email = excluded.raw_email, dns_snapshot = excluded.dns_snapshot,
+                         result = excluded.result",
+                        COLUMNS
+                    ),
+                    &[
+                        &analysis.id,
+                        &analysis.at,
+                        &analysis.tenant,
+                        &analysis.raw_email,
+                        &serde_json::to_value(&analysis.dns_snapshot)?,
+                        &analysis.result,
+                    ],
+                )
+                .await?;
+            Ok(())
+        }
+
+        async fn get(&self, tenant: &str, id: &str) -> anyhow::Result<Option<StoredAnalysis>> {
+            let row = self
+                .client
+                .query_opt(
+                    &format!(
+                        "SELECT {} FROM analyses WHERE tenant = $1 AND id = $2",
+                        COLUMNS
+                    ),
+                    &[&tenant, &id],
+                )
+                .await?;
+            row.as_ref().map(analysis).transpose()
+        }
+
+        async fn query(&self, query: &StoreQuery) -> anyhow::Result<Vec<StoredAnalysis>> {
+            let rows = self
+                .client
+                .query(
+                    &format!(
+                        "SELECT {} FROM analyses
+                         WHERE ($1::TEXT IS NULL OR tenant = $1)
+                         AND ($2::BIGINT IS NULL OR at >= $2)
+                         AND ($3::BIGINT IS NULL OR at < $3)
+                         ORDER BY at",
+                        COLUMNS
+                    ),
+                    &[&query.tenant, &query.since, &query.until],
+                )
+                .await?;
+            rows.iter().map(analysis).collect()
+        }
+
+        async fn prune(
+            &self,
+            tenant: Option<&str>,
+            retention: &Retention,
+            now: i64,
+        ) -> anyhow::Result<PruneStats> {
+            let (raw_cutoff, result_cutoff) = retention.cutoffs(now);
+            let mut stats = PruneStats::default();
+            if let Some(cutoff) = result_cutoff {
+                stats.removed = self
+                    .client
+                    .execute(
+                        "DELETE FROM analyses WHERE at < $1 AND ($2::TEXT IS NULL OR tenant = $2)",
+                        &[&cutoff, &tenant],
+                    )
+                    .await? as usize;
+            }
+            if let Some(cutoff) = raw_cutoff {
+                stats.stripped = self
+                    .client
+                    .execute(
+                        "UPDATE analyses SET raw_email = $1, dns_snapshot = $2
+                         WHERE at < $3 AND raw_email != $1 AND ($4::TEXT IS NULL OR tenant = $4)",
+                        &[
+                            &REDACTED,
+                            &serde_json::to_value(DnsSnapshot::default())?,
+                            &cutoff,
+                            &tenant,
+                        ],
+                    )
+                    .await? as usize;
+            }
+            Ok(stats)
+        }
+    }
+}
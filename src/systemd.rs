@@ -0,0 +1,50 @@
+/// The listening socket systemd passed by socket activation (`LISTEN_FDS`), if any
+#[cfg(feature = "systemd")]
+pub fn inherited_listener() -> anyhow::Result<Option<std::net::TcpListener>> {
+    let mut fds = listenfd::ListenFd::from_env();
+    if fds.len() > 1 {
+        log::warn!(
+            "systemd passed {} sockets; listening on the first",
+            fds.len()
+        );
+    }
+    Ok(fds.take_tcp_listener(0)?)
+}
+
+#[cfg(not(feature = "systemd"))]
+pub fn inherited_listener() -> anyhow::Result<Option<std::net::TcpListener>> {
+    if std::env::var_os("LISTEN_FDS").is_some() {
+        anyhow::bail!("Socket activation needs the systemd feature");
+    }
+    Ok(None)
+}
+
+/// Tells systemd the service is ready (`READY=1`) and, when the unit sets
+/// `WatchdogSec=`, pings the watchdog at half its interval from the calling runtime
+///
+/// Does nothing unless started by systemd with `Type=notify`.
+#[cfg(feature = "systemd")]
+pub fn notify_ready() {
+    use sd_notify::NotifyState;
+
+    if let Err(e) = sd_notify::notify(false, &[NotifyState::Ready]) {
+        log::warn!("Notifying systemd failed: {}", e);
+        return;
+    }
+    let mut usec = 0;
+    if sd_notify::watchdog_enabled(false, &mut usec) {
+        let period = std::time::Duration::from_micros(usec / 2);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = sd_notify::notify(false, &[NotifyState::Watchdog]) {
+                    log::warn!("Pinging the systemd watchdog failed: {}", e);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(not(feature = "systemd"))]
+pub fn notify_ready() {}
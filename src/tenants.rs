@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+
+use crate::bundle::ConfigBundle;
+
+/// Configuration of one tenant (business unit or MSP customer)
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TenantConfig {
+    /// Key clients present in `X-Api-Key`; without one, the tenant is selected by
+    /// `X-Tenant` alone, e.g. behind a gateway that authenticates clients itself
+    pub api_key: Option<String>,
+    /// Protected domains, lists, and the other analysis settings of the tenant
+    #[serde(flatten)]
+    pub config: ConfigBundle,
+}
+
+/// Why a request could not be mapped to a tenant
+#[derive(Debug, Clone, PartialEq)]
+pub enum TenantError {
+    /// The API key belongs to no tenant, or a keyed tenant was named without its key
+    Unauthorized,
+    /// `X-Tenant` names no configured tenant
+    Unknown(String),
+}
+
+impl std::fmt::Display for TenantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TenantError::Unauthorized => write!(f, "invalid API key for tenant"),
+            TenantError::Unknown(name) => write!(f, "unknown tenant '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for TenantError {}
+
+/// Tenants by name, loaded from JSON, e.g.
+/// `{"acme": {"api_key": "...", "protected_domains": ["acme.example"]}}`
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct TenantRegistry {
+    pub tenants: BTreeMap<String, TenantConfig>,
+}
+
+impl TenantRegistry {
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> anyhow::Result<()> {
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The tenant a request belongs to, from its `X-Api-Key` and `X-Tenant` headers
+    ///
+    /// `Ok(None)` means neither header was sent and the default configuration applies.
+    pub fn select(
+        &self,
+        api_key: Option<&str>,
+        tenant: Option<&str>,
+    ) -> Result<Option<&str>, TenantError> {
+        if let Some(key) = api_key {
+            let (name, _) = self
+                .tenants
+                .iter()
+                .find(|(_, t)| t.api_key.as_deref().is_some_and(|k| keys_equal(k, key)))
+                .ok_or(TenantError::Unauthorized)?;
+            return match tenant {
+                Some(requested) if requested != name => Err(TenantError::Unauthorized),
+                _ => Ok(Some(name)),
+            };
+        }
+
+        let Some(requested) = tenant else {
+            return Ok(None);
+        };
+        match self.tenants.get_key_value(requested) {
+            Some((_, t)) if t.api_key.is_some() => Err(TenantError::Unauthorized),
+            Some((name, _)) => Ok(Some(name)),
+            None => Err(TenantError::Unknown(requested.to_string())),
+        }
+    }
+}
+
+/// Compares keys in full so the response time does not reveal the matching prefix
+pub fn keys_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TenantError, TenantRegistry};
+
+    #[test]
+    fn test_select_tenant() {
+        let registry: TenantRegistry = serde_json::from_str(
+            r#"{
+                "acme": {"api_key": "k-acme", "protected_domains": ["acme.example"]},
+                "internal": {"protected_domains": ["corp.example"]}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            registry.tenants["acme"].config.protected_domains,
+            vec!["acme.example"]
+        );
+
+        assert_eq!(registry.select(None, None), Ok(None));
+        assert_eq!(registry.select(Some("k-acme"), None), Ok(Some("acme")));
+        assert_eq!(
+            registry.select(None, Some("internal")),
+            Ok(Some("internal"))
+        );
+        assert_eq!(
+            registry.select(None, Some("acme")),
+            Err(TenantError::Unauthorized)
+        );
+        assert_eq!(
+            registry.select(Some("k-acme"), Some("internal")),
+            Err(TenantError::Unauthorized)
+        );
+        assert_eq!(
+            registry.select(Some("wrong"), None),
+            Err(TenantError::Unauthorized)
+        );
+        assert_eq!(
+            registry.select(None, Some("other")),
+            Err(TenantError::Unknown("other".to_string()))
+        );
+    }
+}
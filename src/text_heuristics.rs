@@ -0,0 +1,255 @@
+use aho_corasick::AhoCorasick;
+use std::collections::BTreeMap;
+
+/// Risk points added per matched category
+const SCORE_PER_CATEGORY: u32 = 5;
+
+/// Upper bound on the risk points text heuristics can contribute
+pub const MAX_TEXT_SCORE: u32 = 15;
+
+/// Social-engineering pattern a phrase belongs to
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PhraseCategory {
+    /// Pressure to act immediately
+    Urgency,
+    /// Wire transfers, invoices, gift cards
+    Payment,
+    /// Prompts to log in or confirm account details
+    Credentials,
+    /// Mobile signatures used to excuse terse, unusual requests from "executives"
+    MobileSignature,
+}
+
+impl PhraseCategory {
+    /// Human-readable category name
+    pub fn label(self) -> &'static str {
+        match self {
+            PhraseCategory::Urgency => "urgency",
+            PhraseCategory::Payment => "payment",
+            PhraseCategory::Credentials => "credentials",
+            PhraseCategory::MobileSignature => "mobile signature",
+        }
+    }
+}
+
+/// Phrases to look for, per category, in any language
+///
+/// Loadable from JSON, e.g. `{"urgency": ["act now", "sofort"], "payment": ["gift card"]}`.
+/// Phrases are normalized and compiled into a single matcher when the list is built.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(
+    from = "BTreeMap<PhraseCategory, Vec<String>>",
+    into = "BTreeMap<PhraseCategory, Vec<String>>"
+)]
+pub struct PhraseList {
+    phrases: BTreeMap<PhraseCategory, Vec<String>>,
+    /// Category and normalized text of each matcher pattern, by pattern id
+    patterns: Vec<(PhraseCategory, String)>,
+    matcher: AhoCorasick,
+}
+
+impl From<BTreeMap<PhraseCategory, Vec<String>>> for PhraseList {
+    fn from(phrases: BTreeMap<PhraseCategory, Vec<String>>) -> Self {
+        let patterns: Vec<(PhraseCategory, String)> = phrases
+            .iter()
+            .flat_map(|(category, list)| list.iter().map(|p| (*category, normalize(p))))
+            .filter(|(_, p)| !p.is_empty())
+            .collect();
+        let matcher = AhoCorasick::new(patterns.iter().map(|(_, p)| p))
+            .expect("phrase patterns are within automaton limits");
+        Self {
+            phrases,
+            patterns,
+            matcher,
+        }
+    }
+}
+
+impl From<PhraseList> for BTreeMap<PhraseCategory, Vec<String>> {
+    fn from(list: PhraseList) -> Self {
+        list.phrases
+    }
+}
+
+impl Default for PhraseList {
+    /// Built-in English, German, French, and Spanish phrases
+    fn default() -> Self {
+        let list = |phrases: &[&str]| phrases.iter().map(|p| p.to_string()).collect();
+        Self::from(BTreeMap::from([
+            (
+                PhraseCategory::Urgency,
+                list(&[
+                    "urgent",
+                    "immediately",
+                    "as soon as possible",
+                    "within 24 hours",
+                    "act now",
+                    "final notice",
+                    "dringend",
+                    "sofort",
+                    "umgehend",
+                    "urgente",
+                    "immédiatement",
+                    "de toute urgence",
+                    "inmediatamente",
+                ]),
+            ),
+            (
+                PhraseCategory::Payment,
+                list(&[
+                    "gift card",
+                    "wire transfer",
+                    "bank details have changed",
+                    "outstanding invoice",
+                    "itunes card",
+                    "gutschein",
+                    "überweisung",
+                    "carte cadeau",
+                    "virement",
+                    "tarjeta regalo",
+                    "transferencia",
+                ]),
+            ),
+            (
+                PhraseCategory::Credentials,
+                list(&[
+                    "verify your account",
+                    "confirm your password",
+                    "your password expires",
+                    "login to avoid",
+                    "account will be suspended",
+                    "konto bestätigen",
+                    "passwort",
+                    "vérifier votre compte",
+                    "mot de passe",
+                    "verifique su cuenta",
+                    "contraseña",
+                ]),
+            ),
+            (
+                PhraseCategory::MobileSignature,
+                list(&[
+                    "sent from my iphone",
+                    "sent from my ipad",
+                    "sent from my mobile",
+                    "von meinem iphone gesendet",
+                    "envoyé de mon iphone",
+                    "enviado desde mi iphone",
+                ]),
+            ),
+        ]))
+    }
+}
+
+impl PhraseList {
+    /// Loads a phrase list from a JSON file
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// The configured phrases, per category
+    pub fn phrases(&self) -> &BTreeMap<PhraseCategory, Vec<String>> {
+        &self.phrases
+    }
+}
+
+/// A phrase found in the message text
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PhraseMatch {
+    pub category: PhraseCategory,
+    pub phrase: String,
+}
+
+/// Social-engineering patterns found in the message text
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct TextFindings {
+    pub matches: Vec<PhraseMatch>,
+
+    /// Risk points contributed, at most `MAX_TEXT_SCORE`
+    pub score: u32,
+}
+
+/// Looks for the listed phrases in `body`, ignoring case, HTML tags, and line breaks
+pub fn analyze_text(body: &str, phrases: &PhraseList) -> TextFindings {
+    let text = normalize(body);
+
+    // One pass over the text; overlapping so phrases nested in others are found too
+    let mut ids: Vec<usize> = phrases
+        .matcher
+        .find_overlapping_iter(&text)
+        .map(|m| m.pattern().as_usize())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let matches: Vec<PhraseMatch> = ids
+        .into_iter()
+        .map(|id| {
+            let (category, phrase) = &phrases.patterns[id];
+            PhraseMatch {
+                category: *category,
+                phrase: phrase.clone(),
+            }
+        })
+        .collect();
+
+    let mut categories: Vec<PhraseCategory> = matches.iter().map(|m| m.category).collect();
+    categories.dedup();
+    let score = (categories.len() as u32 * SCORE_PER_CATEGORY).min(MAX_TEXT_SCORE);
+
+    TextFindings { matches, score }
+}
+
+/// Lowercases, drops HTML tags, and collapses whitespace
+fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                out.push(' ');
+            }
+            _ if in_tag => {}
+            _ => out.extend(c.to_lowercase()),
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_TEXT_SCORE, PhraseCategory, PhraseList, analyze_text};
+
+    #[test]
+    fn test_ceo_fraud_text() {
+        let body =
+            "<p>I need you to buy <b>Gift\r\n Cards</b> IMMEDIATELY.</p>\nSent from my iPhone";
+        let findings = analyze_text(body, &PhraseList::default());
+
+        let categories: Vec<_> = findings.matches.iter().map(|m| m.category).collect();
+        assert!(categories.contains(&PhraseCategory::Urgency));
+        assert!(categories.contains(&PhraseCategory::MobileSignature));
+        assert!(findings.score <= MAX_TEXT_SCORE);
+        assert_eq!(findings.score, 15);
+    }
+
+    #[test]
+    fn test_custom_multilingual_list() {
+        let phrases: PhraseList =
+            serde_json::from_str(r#"{"credentials": ["Konto bestätigen"]}"#).unwrap();
+        let findings = analyze_text("Bitte KONTO BESTÄTIGEN!", &phrases);
+        assert_eq!(findings.matches.len(), 1);
+        assert_eq!(findings.score, 5);
+
+        assert!(
+            analyze_text("Hello there", &PhraseList::default())
+                .matches
+                .is_empty()
+        );
+    }
+}
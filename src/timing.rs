@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Time spent on a check, or on a class of DNS lookups
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize)]
+pub struct Timing {
+    /// Runs of the check, or lookups made
+    pub count: u64,
+    /// Total elapsed time
+    pub seconds: f64,
+}
+
+/// Where the time of an analysis went, recorded when `AnalysisOptions::timings` is set
+///
+/// Checks run one after another, so their times add up to the analysis time. A check's
+/// time includes its DNS lookups, which `dns` breaks out by class.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+pub struct Timings {
+    /// Checks, and enrichments applied afterwards, by name
+    pub checks: BTreeMap<String, Timing>,
+    /// Waiting for DNS answers by lookup: `spf`, `dmarc`, `exists`, `mx`, `addresses`,
+    /// or `mx_hosts`; attached messages' lookups included
+    pub dns: BTreeMap<String, Timing>,
+}
+
+impl Timings {
+    /// Adds a run of `check` taking `elapsed`
+    pub fn record(&mut self, check: &str, elapsed: Duration) {
+        let timing = self.checks.entry(check.to_string()).or_default();
+        timing.count += 1;
+        timing.seconds += elapsed.as_secs_f64();
+    }
+
+    /// Adds the timings of another analysis, for totals across analyses
+    pub fn add(&mut self, other: &Timings) {
+        for (totals, timings) in [
+            (&mut self.checks, &other.checks),
+            (&mut self.dns, &other.dns),
+        ] {
+            for (name, timing) in timings {
+                let total = totals.entry(name.clone()).or_default();
+                total.count += timing.count;
+                total.seconds += timing.seconds;
+            }
+        }
+    }
+}
+
+/// Times checks run one after another, charging each lap to the check that just ended
+pub(crate) struct Stopwatch {
+    timings: Option<Timings>,
+    last: Instant,
+}
+
+impl Stopwatch {
+    /// A stopwatch recording nothing unless `enabled`
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            timings: enabled.then(Timings::default),
+            last: Instant::now(),
+        }
+    }
+
+    /// Charges the time since the previous lap to `check`
+    pub(crate) fn lap(&mut self, check: &str) {
+        if let Some(timings) = &mut self.timings {
+            let now = Instant::now();
+            timings.record(check, now - self.last);
+            self.last = now;
+        }
+    }
+
+    pub(crate) fn finish(self) -> Option<Timings> {
+        self.timings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Stopwatch, Timings};
+    use std::time::Duration;
+
+    #[test]
+    fn test_timings_add_up() {
+        let mut stopwatch = Stopwatch::new(true);
+        stopwatch.lap("body");
+        stopwatch.lap("spf");
+        let mut timings = stopwatch.finish().unwrap();
+        assert_eq!(timings.checks.keys().collect::<Vec<_>>(), ["body", "spf"]);
+
+        timings.record("spf", Duration::from_millis(250));
+        let mut totals = Timings::default();
+        totals.add(&timings);
+        totals.add(&timings);
+        assert_eq!(totals.checks["spf"].count, 4);
+        assert!(totals.checks["spf"].seconds >= 0.5);
+
+        assert!(Stopwatch::new(false).finish().is_none());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+use crate::{dns::ResolverTrait, parse::EmailParsed};
+
+/// Include nesting followed when expanding registered SPF includes
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// Legitimate sending sources of one owned domain
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SendingInfrastructure {
+    /// SPF include domains of the email service providers used, e.g. `_spf.google.com`
+    pub spf_includes: Vec<String>,
+
+    /// Sending IP ranges in CIDR notation; single addresses are allowed
+    pub ip_ranges: Vec<String>,
+
+    /// DKIM selectors the domain signs with
+    pub dkim_selectors: Vec<String>,
+}
+
+/// Registered sending infrastructure of the organization's own domains
+///
+/// Loaded from JSON, e.g.
+/// `{"domains": {"example.com": {"spf_includes": ["_spf.google.com"], "ip_ranges": ["192.0.2.0/24"], "dkim_selectors": ["google"]}}}`.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TrustStore {
+    pub domains: BTreeMap<String, SendingInfrastructure>,
+}
+
+impl TrustStore {
+    pub fn from_file(path: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// The registered infrastructure of `domain`, if it is an owned domain
+    pub fn get(&self, domain: &str) -> Option<&SendingInfrastructure> {
+        let domain = domain.trim_end_matches('.');
+        self.domains
+            .iter()
+            .find(|(owned, _)| owned.trim_end_matches('.').eq_ignore_ascii_case(domain))
+            .map(|(_, infra)| infra)
+    }
+}
+
+/// Whether a message claiming an owned domain came from its registered infrastructure
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct InfrastructureCheck {
+    /// The owned domain the message claims to be from
+    pub domain: String,
+
+    /// Connecting IP recorded by our MTA
+    pub client_ip: Option<String>,
+
+    /// Selectors of the DKIM signatures made by the owned domain
+    pub dkim_selectors: Vec<String>,
+
+    /// The registered source that matched, e.g. `dkim:google` or `ip:192.0.2.0/24`
+    pub matched: Option<String>,
+
+    pub verified: bool,
+}
+
+/// Checks the message's DKIM selectors and client IP against the registered infrastructure
+///
+/// Registered SPF includes are expanded to their `ip4:`/`ip6:` ranges through `dns`.
+pub async fn check_infrastructure<R: ResolverTrait + Sync>(
+    parsed: &EmailParsed,
+    domain: &str,
+    infra: &SendingInfrastructure,
+    dns: &R,
+) -> InfrastructureCheck {
+    let dkim_selectors: Vec<String> = parsed
+        .dkim_signatures
+        .iter()
+        .filter(|sig| sig.domain.eq_ignore_ascii_case(domain))
+        .map(|sig| sig.selector.clone())
+        .collect();
+    let client_ip: Option<IpAddr> = parsed.client_ip.as_deref().and_then(|ip| ip.parse().ok());
+
+    let mut matched = dkim_selectors
+        .iter()
+        .find(|s| {
+            infra
+                .dkim_selectors
+                .iter()
+                .any(|r| r.eq_ignore_ascii_case(s))
+        })
+        .map(|s| format!("dkim:{}", s));
+
+    if matched.is_none()
+        && let Some(ip) = client_ip
+    {
+        matched = infra
+            .ip_ranges
+            .iter()
+            .find(|range| cidr_contains(range, ip))
+            .map(|range| format!("ip:{}", range));
+
+        for include in &infra.spf_includes {
+            if matched.is_some() {
+                break;
+            }
+            if spf_ranges(dns, include)
+                .await
+                .iter()
+                .any(|r| cidr_contains(r, ip))
+            {
+                matched = Some(format!("include:{}", include));
+            }
+        }
+    }
+
+    InfrastructureCheck {
+        domain: domain.to_string(),
+        client_ip: client_ip.map(|ip| ip.to_string()),
+        dkim_selectors,
+        verified: matched.is_some(),
+        matched,
+    }
+}
+
+/// The `ip4:`/`ip6:` ranges authorized by the SPF record of `domain` and its includes
+async fn spf_ranges<R: ResolverTrait + Sync>(dns: &R, domain: &str) -> Vec<String> {
+    let mut ranges = Vec::new();
+    let mut pending = vec![(domain.to_string(), 0)];
+    let mut visited = Vec::new();
+
+    while let Some((name, depth)) = pending.pop() {
+        if depth >= MAX_INCLUDE_DEPTH || visited.contains(&name) {
+            continue;
+        }
+        let Some(record) = dns.resolve_spf(&name).await else {
+            continue;
+        };
+        for term in record.split_whitespace().skip(1) {
+            let term = term.trim_start_matches('+');
+            if let Some(range) = term
+                .strip_prefix("ip4:")
+                .or_else(|| term.strip_prefix("ip6:"))
+            {
+                ranges.push(range.to_string());
+            } else if let Some(target) = term
+                .strip_prefix("include:")
+                .or_else(|| term.strip_prefix("redirect="))
+            {
+                pending.push((target.to_string(), depth + 1));
+            }
+        }
+        visited.push(name);
+    }
+    ranges
+}
+
+/// The network and prefix length of `range` (CIDR notation or a single address);
+/// `None` when either does not parse or the prefix is too long for the address family
+pub fn parse_cidr(range: &str) -> Option<(IpAddr, u32)> {
+    let (network, prefix) = match range.split_once('/') {
+        Some((network, prefix)) => (network, Some(prefix.parse::<u32>().ok()?)),
+        None => (range, None),
+    };
+    let network: IpAddr = network.trim().parse().ok()?;
+    let bits = if network.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(bits);
+    (prefix <= bits).then_some((network, prefix))
+}
+
+/// Whether `ip` is inside `range` (CIDR notation or a single address); never for a
+/// range that does not parse
+pub fn cidr_contains(range: &str, ip: IpAddr) -> bool {
+    let Some((network, prefix)) = parse_cidr(range) else {
+        return false;
+    };
+
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrustStore, check_infrastructure, cidr_contains};
+    use crate::{dns::DnsSnapshot, parse::parse_email};
+    use serde_json::json;
+
+    #[test]
+    fn test_cidr_contains() {
+        let ip = |s: &str| s.parse().unwrap();
+        assert!(cidr_contains("192.0.2.0/24", ip("192.0.2.200")));
+        assert!(!cidr_contains("192.0.2.0/24", ip("192.0.3.1")));
+        assert!(cidr_contains("198.51.100.7", ip("198.51.100.7")));
+        assert!(cidr_contains("0.0.0.0/0", ip("203.0.113.1")));
+        assert!(cidr_contains("2001:db8::/32", ip("2001:db8:1::1")));
+        assert!(!cidr_contains("2001:db8::/32", ip("192.0.2.1")));
+        assert!(!cidr_contains("192.0.2.0/40", ip("192.0.2.1")));
+        assert!(!cidr_contains("192.0.2.0/x", ip("192.0.2.1")));
+    }
+
+    #[tokio::test]
+    async fn test_registered_infrastructure() {
+        let store: TrustStore = serde_json::from_value(json!({
+            "domains": {"example.com": {
+                "spf_includes": ["_spf.esp.example.net"],
+                "ip_ranges": ["192.0.2.0/24"],
+                "dkim_selectors": ["corp"]
+            }}
+        }))
+        .unwrap();
+        let dns: DnsSnapshot = serde_json::from_value(json!({
+            "domains": {
+                "_spf.esp.example.net": {"spf": "v=spf1 include:_ips.esp.example.net -all"},
+                "_ips.esp.example.net": {"spf": "v=spf1 ip4:198.51.100.0/24 -all"}
+            }
+        }))
+        .unwrap();
+        let infra = store.get("Example.com").unwrap();
+
+        let message = |ip: &str, selector: &str| {
+            let raw = format!(
+                "Received: from out.example.net (out.example.net [{}]) by mx.example.com\r\n\
+                 DKIM-Signature: v=1; d=example.com; s={}; b=abc\r\n\
+                 From: ceo@example.com\r\n\r\nhi",
+                ip, selector
+            );
+            parse_email(raw.as_bytes()).unwrap()
+        };
+
+        let via_dkim =
+            check_infrastructure(&message("203.0.113.9", "corp"), "example.com", infra, &dns).await;
+        assert_eq!(via_dkim.matched.as_deref(), Some("dkim:corp"));
+
+        let via_include = check_infrastructure(
+            &message("198.51.100.20", "other"),
+            "example.com",
+            infra,
+            &dns,
+        )
+        .await;
+        assert_eq!(
+            via_include.matched.as_deref(),
+            Some("include:_spf.esp.example.net")
+        );
+
+        let spoof =
+            check_infrastructure(&message("203.0.113.9", "other"), "example.com", infra, &dns)
+                .await;
+        assert!(!spoof.verified);
+        assert_eq!(spoof.client_ip.as_deref(), Some("203.0.113.9"));
+        assert_eq!(spoof.dkim_selectors, vec!["other"]);
+    }
+}
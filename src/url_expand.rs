@@ -0,0 +1,104 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::body::{BodyEvidence, url_domain};
+use crate::http::{HttpFetcher, HttpRequest};
+use crate::idn::domain_labels;
+use crate::lookalike::find_lookalike;
+
+/// Redirects followed per URL unless configured otherwise
+pub const DEFAULT_MAX_HOPS: usize = 5;
+
+/// Follows URL shorteners and redirects with HEAD requests
+///
+/// Redirects are followed manually so every hop is recorded and the depth stays
+/// bounded.
+pub struct UrlExpander {
+    fetcher: Arc<dyn HttpFetcher>,
+    max_hops: usize,
+}
+
+impl UrlExpander {
+    pub fn new(max_hops: usize, fetcher: Arc<dyn HttpFetcher>) -> Self {
+        Self { fetcher, max_hops }
+    }
+
+    /// Returns the redirect targets reached from `url`, in order
+    ///
+    /// Stops at the first non-redirect response, failed request, or repeated URL.
+    pub async fn expand(&self, url: &str) -> Vec<String> {
+        let mut chain: Vec<String> = Vec::new();
+        let Ok(mut current) = url::Url::parse(url) else {
+            return chain;
+        };
+
+        while chain.len() < self.max_hops {
+            let request = HttpRequest::head(current.as_str(), Duration::from_secs(5));
+            let Ok(response) = self.fetcher.fetch(request).await else {
+                break;
+            };
+            if !response.is_redirection() {
+                break;
+            }
+            let Some(next) = response
+                .header("location")
+                .and_then(|l| current.join(l).ok())
+            else {
+                break;
+            };
+            if next.as_str() == url || chain.iter().any(|u| u == next.as_str()) {
+                break;
+            }
+            chain.push(next.to_string());
+            current = next;
+        }
+
+        chain
+    }
+}
+
+/// Expands every body URL and re-checks the landing domains against the protected domains
+pub async fn expand_body_urls(
+    expander: &UrlExpander,
+    body: &mut BodyEvidence,
+    protected_domains: &[String],
+) {
+    for url in &mut body.urls {
+        url.redirect_chain = expander.expand(&url.url).await;
+        if let Some(last) = url.redirect_chain.last() {
+            url.landing_domain = url_domain(last);
+            url.landing_domain_labels = url.landing_domain.as_deref().map(domain_labels);
+            url.lookalike = url
+                .landing_domain
+                .as_deref()
+                .and_then(|d| find_lookalike(d, protected_domains))
+                .or(url.lookalike.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlExpander;
+    use crate::http::{Method, MockFetcher};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_expand_records_each_hop() {
+        let fetcher = Arc::new(
+            MockFetcher::default()
+                .redirect("https://bit.ly/abc", "https://t.example/r?id=1")
+                .redirect("https://t.example/r?id=1", "/landing")
+                .respond("https://t.example/landing", 200, ""),
+        );
+        let expander = UrlExpander::new(5, fetcher.clone());
+
+        assert_eq!(
+            expander.expand("https://bit.ly/abc").await,
+            vec!["https://t.example/r?id=1", "https://t.example/landing"]
+        );
+        let requests = fetcher.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|r| r.method == Method::Head));
+    }
+}
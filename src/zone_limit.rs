@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Buckets kept before idle ones are dropped
+const MAX_TRACKED_ZONES: usize = 10_000;
+
+/// Second-level labels under which ccTLDs register domains, e.g. `co.uk`
+const SECOND_LEVEL_SUFFIXES: &[&str] = &["ac", "co", "com", "edu", "gov", "net", "or", "org"];
+
+/// Query rate allowed per zone
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneLimit {
+    /// Sustained queries per second
+    pub per_second: f64,
+    /// Queries allowed at once after the zone has been idle
+    pub burst: f64,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets keyed by zone, so a flood of messages claiming one domain cannot
+/// hammer that domain's authoritative servers
+pub struct ZoneLimiter {
+    limit: ZoneLimit,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    throttled: AtomicU64,
+}
+
+impl ZoneLimiter {
+    pub fn new(limit: ZoneLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::default(),
+            throttled: AtomicU64::new(0),
+        }
+    }
+
+    /// Queries that had to wait for their zone's bucket
+    pub fn throttled(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+
+    /// Waits until a query of `name` is within its zone's rate
+    pub async fn acquire(&self, name: &str) {
+        let wait = self.reserve(&zone_of(name), Instant::now());
+        if !wait.is_zero() {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token from the bucket of `zone`, returning how long to wait for it
+    ///
+    /// Tokens are taken even when the bucket is empty, so waiting queries are spaced
+    /// out at the sustained rate instead of all firing once it refills.
+    fn reserve(&self, zone: &str, now: Instant) -> Duration {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_ZONES {
+            buckets.retain(|_, bucket| self.refilled(bucket, now) < self.limit.burst);
+        }
+        let bucket = buckets.entry(zone.to_string()).or_insert(Bucket {
+            tokens: self.limit.burst,
+            updated: now,
+        });
+        bucket.tokens = self.refilled(bucket, now) - 1.0;
+        bucket.updated = now;
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / self.limit.per_second)
+        }
+    }
+
+    fn refilled(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        (bucket.tokens + elapsed * self.limit.per_second).min(self.limit.burst)
+    }
+}
+
+/// Queries allowed to be outstanding at once
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConcurrencyLimit {
+    /// Across all zones; `None` for no limit
+    pub max_in_flight: Option<usize>,
+    /// For any one zone, see [`zone_of`]; `None` for no limit
+    pub per_zone: Option<usize>,
+}
+
+/// Semaphores bounding the outstanding queries of a resolver, in all and per zone, so
+/// an embedding application keeps control over the sockets and tasks lookups use
+pub struct ConcurrencyLimiter {
+    limit: ConcurrencyLimit,
+    global: Option<Arc<Semaphore>>,
+    zones: Mutex<HashMap<String, Arc<Semaphore>>>,
+    queued: AtomicU64,
+}
+
+/// Permits of one query, released when it is dropped
+pub struct QueryPermit {
+    _zone: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(limit: ConcurrencyLimit) -> Self {
+        Self {
+            limit,
+            global: limit
+                .max_in_flight
+                .map(|n| Arc::new(Semaphore::new(n.max(1)))),
+            zones: Mutex::default(),
+            queued: AtomicU64::new(0),
+        }
+    }
+
+    /// Queries that had to wait for a permit
+    pub fn queued(&self) -> u64 {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Waits until a query of `name` may be sent, holding its permits until the
+    /// returned value is dropped
+    ///
+    /// The zone's permit is taken first, so a query waiting on a busy zone does not
+    /// hold one of the global permits other zones could use.
+    pub async fn acquire(&self, name: &str) -> QueryPermit {
+        let zone = self
+            .limit
+            .per_zone
+            .map(|n| self.zone(&zone_of(name), n.max(1)));
+        QueryPermit {
+            _zone: match zone {
+                Some(semaphore) => Some(self.permit(semaphore).await),
+                None => None,
+            },
+            _global: match &self.global {
+                Some(semaphore) => Some(self.permit(semaphore.clone()).await),
+                None => None,
+            },
+        }
+    }
+
+    async fn permit(&self, semaphore: Arc<Semaphore>) -> OwnedSemaphorePermit {
+        match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("semaphores are never closed")
+            }
+        }
+    }
+
+    /// The semaphore of `zone`, created with `permits` when first needed
+    fn zone(&self, zone: &str, permits: usize) -> Arc<Semaphore> {
+        let mut zones = self.zones.lock().unwrap();
+        if zones.len() >= MAX_TRACKED_ZONES {
+            // Only zones without outstanding or waiting queries are dropped
+            zones.retain(|_, semaphore| Arc::strong_count(semaphore) > 1);
+        }
+        zones
+            .entry(zone.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(permits)))
+            .clone()
+    }
+}
+
+/// The zone a query name is rate limited under: its last two labels, or three under
+/// a ccTLD's second-level suffix such as `co.uk`
+///
+/// `_dmarc.mail.example.co.uk` and `www.example.co.uk` share `example.co.uk`, so
+/// random subdomains of one domain draw from the same bucket.
+pub fn zone_of(name: &str) -> String {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = name.rsplit('.').collect();
+    let keep = match labels.as_slice() {
+        [tld, second, _, ..] if tld.len() == 2 && SECOND_LEVEL_SUFFIXES.contains(second) => 3,
+        _ => 2,
+    };
+    let mut zone: Vec<&str> = labels.into_iter().take(keep).collect();
+    zone.reverse();
+    zone.join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConcurrencyLimit, ConcurrencyLimiter, ZoneLimit, ZoneLimiter, zone_of};
+    use futures::FutureExt;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn test_zone_of() {
+        assert_eq!(zone_of("_dmarc.Mail.Example.com."), "example.com");
+        assert_eq!(zone_of("www.example.co.uk"), "example.co.uk");
+        assert_eq!(zone_of("example.de"), "example.de");
+        assert_eq!(zone_of("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_reserve_spaces_out_queries_beyond_the_burst() {
+        let limiter = ZoneLimiter::new(ZoneLimit {
+            per_second: 2.0,
+            burst: 2.0,
+        });
+        let now = Instant::now();
+        assert_eq!(limiter.reserve("example.com", now), Duration::ZERO);
+        assert_eq!(limiter.reserve("example.com", now), Duration::ZERO);
+        assert_eq!(
+            limiter.reserve("example.com", now),
+            Duration::from_millis(500)
+        );
+        assert_eq!(limiter.reserve("example.com", now), Duration::from_secs(1));
+        // Other zones have their own bucket
+        assert_eq!(limiter.reserve("example.org", now), Duration::ZERO);
+        // Refilled at the sustained rate
+        let later = now + Duration::from_secs(3);
+        assert_eq!(limiter.reserve("example.com", later), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit() {
+        let limiter = ConcurrencyLimiter::new(ConcurrencyLimit {
+            max_in_flight: Some(2),
+            per_zone: Some(1),
+        });
+        let first = limiter.acquire("example.com").await;
+        // The zone is busy, even for another name in it
+        assert!(
+            limiter
+                .acquire("_dmarc.example.com")
+                .now_or_never()
+                .is_none()
+        );
+        let _second = limiter.acquire("example.org").await;
+        // Both global permits are taken
+        assert!(limiter.acquire("example.net").now_or_never().is_none());
+        drop(first);
+        assert!(limiter.acquire("example.net").now_or_never().is_some());
+        assert_eq!(limiter.queued(), 2);
+    }
+}